@@ -0,0 +1,56 @@
+//! # Compositing
+//!
+//! How `AppHost::flush` puts the apps on screen. Apps draw bottom to top
+//! in z-order: registration order, with the focused app last so that it is
+//! on top wherever two overlap.
+//!
+//! Each app has an alpha, 255 (opaque) unless `transparency` set it lower.
+//! An opaque app's commands go straight to the framebuffer. A translucent
+//! app is drawn in place over a copy of what was under its bounds, then
+//! mixed back onto that copy with `FramebufferWriter::blend_over`: the
+//! same result as rendering it to its own surface and blending that, with
+//! no screen-sized buffer per app. The background is drawn under any app
+//! that is not fully opaque, so a translucent app shows the wallpaper.
+
+use alloc::{collections::BTreeMap, format, string::String};
+use spin::Mutex;
+
+/// Lowest alpha `set_alpha` takes; an app any fainter could not be read.
+pub const MIN_ALPHA: u8 = 32;
+
+/// Alpha per app index; absent means 255.
+static ALPHAS: Mutex<BTreeMap<usize, u8>> = Mutex::new(BTreeMap::new());
+
+/// Sets app `idx`'s alpha, raised to `MIN_ALPHA`. Returns what was set.
+pub fn set_alpha(idx: usize, alpha: u8) -> u8 {
+    let alpha = alpha.max(MIN_ALPHA);
+    let mut alphas = ALPHAS.lock();
+    if alpha == u8::MAX {
+        alphas.remove(&idx);
+    } else {
+        alphas.insert(idx, alpha);
+    }
+    alpha
+}
+
+pub fn alpha(idx: usize) -> u8 {
+    ALPHAS.lock().get(&idx).copied().unwrap_or(u8::MAX)
+}
+
+/// `count` app indices bottom to top, `focused` last.
+pub fn z_order(count: usize, focused: usize) -> impl Iterator<Item = usize> {
+    (0..count)
+        .filter(move |&idx| idx != focused)
+        .chain((focused < count).then_some(focused))
+}
+
+/// Lines for `transparency` without arguments, one per app in `names`.
+pub fn report(names: &[&str]) -> String {
+    let mut out = String::new();
+    for (idx, name) in names.iter().enumerate() {
+        let alpha = alpha(idx);
+        let note = if alpha == u8::MAX { " (opaque)" } else { "" };
+        out.push_str(&format!("{:<10}{}{}\n", name, alpha, note));
+    }
+    out
+}
@@ -0,0 +1,95 @@
+//! # Keys
+//!
+//! What a key press means to an app, independent of the scancodes that
+//! produced it. Printable keys arrive as `KeyCode::Char` with Shift
+//! already applied; every other key has its own variant, so apps match on
+//! names rather than on the control characters a terminal would send.
+//! Ctrl does not turn letters into control codes: Ctrl+R is `Char('r')`
+//! with `Modifiers::CTRL`.
+
+use core::ops::{BitOr, BitOrAssign};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrow {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCode {
+    /// A key that types `char`, Space included.
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Arrow(Arrow),
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Delete,
+    Insert,
+    /// F1 is `Function(1)`.
+    Function(u8),
+}
+
+impl KeyCode {
+    /// The character a line-oriented reader (a serial console, say) would
+    /// see for this key, if it has one.
+    pub fn to_char(self) -> Option<char> {
+        match self {
+            KeyCode::Char(ch) => Some(ch),
+            KeyCode::Enter => Some('\n'),
+            KeyCode::Backspace => Some('\x08'),
+            KeyCode::Tab => Some('\t'),
+            KeyCode::Escape => Some('\x1B'),
+            KeyCode::Delete => Some('\x7F'),
+            _ => None,
+        }
+    }
+}
+
+/// Modifier keys held during a press.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Self = Self(0);
+    pub const SHIFT: Self = Self(1 << 0);
+    pub const CTRL: Self = Self(1 << 1);
+    pub const ALT: Self = Self(1 << 2);
+
+    /// Whether every modifier in `other` is held.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn shift(self) -> bool {
+        self.contains(Self::SHIFT)
+    }
+
+    pub const fn ctrl(self) -> bool {
+        self.contains(Self::CTRL)
+    }
+
+    pub const fn alt(self) -> bool {
+        self.contains(Self::ALT)
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
@@ -0,0 +1,596 @@
+//! # Input Macros
+//!
+//! Ctrl+Alt+R starts recording key presses, and pressing it again stops.
+//! A dialog then asks for a one-word name: F5-F8 binds the macro to that
+//! key, Enter to the first of them that is free, and Escape throws the
+//! recording away. Pressing a bound key replays its keys into the input
+//! queue, one every `REPLAY_INTERVAL_MS`, until they run out or Escape
+//! cancels the replay.
+//!
+//! Keys pass through to the apps while they are recorded; the Ctrl+Alt+R
+//! chords themselves are not recorded. A recording stops taking keys at
+//! `MAX_KEYS`, with a warning in the log. Replayed keys go through the same
+//! shortcuts as typed ones but never back through `Recorder::key`, so a
+//! macro cannot start another replay, nor one of itself.
+//!
+//! Macros are kept in `/config/macros`, one per line: the name, the key it
+//! is bound to, then a token per key press (see `encode_key`). The `macro`
+//! command lists, rebinds and deletes them.
+
+use crate::app::{AppEvent, Arrow, KeyCode, Modifiers};
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::ui_provider::{
+    layers::{self, Layer},
+    render::{flush_commands, RenderList},
+    shape::Rect,
+    theme::Theme,
+    widgets::{Panel, TextInput, Widget},
+};
+use alloc::{format, string::String, vec::Vec};
+use spin::Mutex;
+
+pub const MACRO_CONFIG: &str = "/config/macros";
+/// Longest recording kept.
+pub const MAX_KEYS: usize = 500;
+pub const REPLAY_INTERVAL_MS: u64 = 10;
+/// The function keys a macro can be bound to.
+pub const FIRST_SLOT: u8 = 5;
+pub const LAST_SLOT: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub mods: Modifiers,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    /// `n` for F`n`.
+    pub slot: u8,
+    pub keys: Vec<Key>,
+}
+
+fn is_slot(n: u8) -> bool {
+    (FIRST_SLOT..=LAST_SLOT).contains(&n)
+}
+
+/// The Ctrl+Alt+R chord.
+fn is_record_chord(key: Key) -> bool {
+    key.mods.contains(Modifiers::CTRL | Modifiers::ALT)
+        && matches!(key.code, KeyCode::Char('r' | 'R'))
+}
+
+const NAMED_KEYS: [(KeyCode, &str); 15] = [
+    (KeyCode::Char(' '), "space"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Backspace, "bs"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::Escape, "esc"),
+    (KeyCode::Arrow(Arrow::Up), "up"),
+    (KeyCode::Arrow(Arrow::Down), "down"),
+    (KeyCode::Arrow(Arrow::Left), "left"),
+    (KeyCode::Arrow(Arrow::Right), "right"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pgup"),
+    (KeyCode::PageDown, "pgdn"),
+    (KeyCode::Delete, "del"),
+    (KeyCode::Insert, "ins"),
+];
+
+const MOD_PREFIXES: [(Modifiers, &str); 3] = [
+    (Modifiers::CTRL, "C-"),
+    (Modifiers::ALT, "A-"),
+    (Modifiers::SHIFT, "S-"),
+];
+
+/// One key press as a token without spaces: modifier prefixes `C-`, `A-`
+/// and `S-`, then a key name, `f<n>`, a printable ASCII character as
+/// itself, or any other character as `u+<hex>`.
+pub fn encode_key(key: Key) -> String {
+    let mut out = String::new();
+    for (mods, prefix) in MOD_PREFIXES {
+        if key.mods.contains(mods) {
+            out.push_str(prefix);
+        }
+    }
+    match (
+        key.code,
+        NAMED_KEYS.iter().find(|(code, _)| *code == key.code),
+    ) {
+        (_, Some((_, name))) => out.push_str(name),
+        (KeyCode::Function(n), _) => out.push_str(&format!("f{}", n)),
+        (KeyCode::Char(ch), _) if ch.is_ascii_graphic() => out.push(ch),
+        (KeyCode::Char(ch), _) => out.push_str(&format!("u+{:x}", u32::from(ch))),
+        _ => {}
+    }
+    out
+}
+
+pub fn decode_key(token: &str) -> Option<Key> {
+    let mut mods = Modifiers::NONE;
+    let mut rest = token;
+    // A lone character after the prefixes is the key, even `C` or `-`
+    while rest.len() > 2 {
+        let Some(&(flag, prefix)) = MOD_PREFIXES.iter().find(|(_, p)| rest.starts_with(p)) else {
+            break;
+        };
+        mods |= flag;
+        rest = &rest[prefix.len()..];
+    }
+    let code = if let Some((code, _)) = NAMED_KEYS.iter().find(|(_, name)| *name == rest) {
+        *code
+    } else if let Some(hex) = rest.strip_prefix("u+") {
+        KeyCode::Char(char::from_u32(u32::from_str_radix(hex, 16).ok()?)?)
+    } else if let Some(n) = rest.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        KeyCode::Function(n)
+    } else {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) if ch.is_ascii_graphic() => KeyCode::Char(ch),
+            _ => return None,
+        }
+    };
+    Some(Key { code, mods })
+}
+
+/// `/config/macros` contents for `macros`.
+pub fn serialize(macros: &[Macro]) -> String {
+    let mut out = String::new();
+    for m in macros {
+        out.push_str(&format!("{} F{}", m.name, m.slot));
+        for &key in &m.keys {
+            out.push(' ');
+            out.push_str(&encode_key(key));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The macros in `text`. A line that does not parse is skipped.
+pub fn parse(text: &str) -> Vec<Macro> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let slot = parse_slot(fields.next()?)?;
+            let keys = fields.map(decode_key).collect::<Option<Vec<_>>>()?;
+            Some(Macro {
+                name: String::from(name),
+                slot,
+                keys,
+            })
+        })
+        .collect()
+}
+
+/// `F5`..`F8`, either case.
+pub fn parse_slot(text: &str) -> Option<u8> {
+    let n = text.strip_prefix(['F', 'f'])?.parse().ok()?;
+    is_slot(n).then_some(n)
+}
+
+/// What `Recorder::key` did with a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intercept {
+    /// Not taken: the key goes on to the shortcuts and apps.
+    Pass,
+    Consumed,
+    /// Consumed, and the macro list changed and wants saving.
+    Saved,
+}
+
+enum Mode {
+    Idle,
+    Recording(Vec<Key>),
+    Naming {
+        keys: Vec<Key>,
+        input: TextInput,
+    },
+    Replaying {
+        keys: Vec<Key>,
+        next: usize,
+        due_ms: u64,
+    },
+}
+
+pub struct Recorder {
+    mode: Mode,
+    macros: Vec<Macro>,
+}
+
+impl Recorder {
+    pub const fn new(macros: Vec<Macro>) -> Self {
+        Self {
+            mode: Mode::Idle,
+            macros,
+        }
+    }
+
+    pub fn macros(&self) -> &[Macro] {
+        &self.macros
+    }
+
+    pub fn recording(&self) -> bool {
+        matches!(self.mode, Mode::Recording(_))
+    }
+
+    #[cfg(test)]
+    pub fn replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replaying { .. })
+    }
+
+    fn naming(&self) -> bool {
+        matches!(self.mode, Mode::Naming { .. })
+    }
+
+    /// Offers a typed key. See `Intercept`.
+    pub fn key(&mut self, key: Key, now_ms: u64) -> Intercept {
+        match &mut self.mode {
+            Mode::Idle if is_record_chord(key) => {
+                self.mode = Mode::Recording(Vec::new());
+                Intercept::Consumed
+            }
+            Mode::Idle => match key.code {
+                KeyCode::Function(n) if key.mods == Modifiers::NONE => {
+                    let Some(m) = self.macros.iter().find(|m| m.slot == n) else {
+                        return Intercept::Pass;
+                    };
+                    self.mode = Mode::Replaying {
+                        keys: m.keys.clone(),
+                        next: 0,
+                        due_ms: now_ms,
+                    };
+                    Intercept::Consumed
+                }
+                _ => Intercept::Pass,
+            },
+            Mode::Recording(keys) if is_record_chord(key) => {
+                let keys = core::mem::take(keys);
+                self.mode = if keys.is_empty() {
+                    Mode::Idle
+                } else {
+                    let input = TextInput::new("name").with_max_len(24);
+                    Mode::Naming { keys, input }
+                };
+                Intercept::Consumed
+            }
+            Mode::Recording(keys) => {
+                if keys.len() < MAX_KEYS {
+                    keys.push(key);
+                } else if keys.len() == MAX_KEYS {
+                    crate::log_warn!("macro: recording is full at {} keys", MAX_KEYS);
+                    // Past the cap, so the warning is logged once
+                    keys.push(key);
+                }
+                Intercept::Pass
+            }
+            Mode::Naming { .. } => self.name_key(key),
+            Mode::Replaying { .. } if key.code == KeyCode::Escape => {
+                self.mode = Mode::Idle;
+                Intercept::Consumed
+            }
+            Mode::Replaying { .. } => Intercept::Pass,
+        }
+    }
+
+    /// A key while the naming dialog is open; it takes every key.
+    fn name_key(&mut self, key: Key) -> Intercept {
+        let Mode::Naming { input, .. } = &mut self.mode else {
+            return Intercept::Consumed;
+        };
+        let slot = match key.code {
+            KeyCode::Escape => {
+                self.mode = Mode::Idle;
+                return Intercept::Consumed;
+            }
+            KeyCode::Function(n) if is_slot(n) => n,
+            KeyCode::Enter => {
+                match (FIRST_SLOT..=LAST_SLOT).find(|&n| self.macros.iter().all(|m| m.slot != n)) {
+                    Some(n) => n,
+                    None => return Intercept::Consumed,
+                }
+            }
+            _ => {
+                input.handle_event(&AppEvent::KeyPress {
+                    code: key.code,
+                    mods: key.mods,
+                });
+                return Intercept::Consumed;
+            }
+        };
+        let name = input.value().trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Intercept::Consumed;
+        }
+        let name = String::from(name);
+        let Mode::Naming { keys, .. } = core::mem::replace(&mut self.mode, Mode::Idle) else {
+            return Intercept::Consumed;
+        };
+        self.bind(Macro {
+            name,
+            slot,
+            keys: keys.into_iter().take(MAX_KEYS).collect(),
+        });
+        Intercept::Saved
+    }
+
+    /// Adds `m`, replacing any macro with its name or on its key.
+    fn bind(&mut self, m: Macro) {
+        self.macros
+            .retain(|other| other.name != m.name && other.slot != m.slot);
+        self.macros.push(m);
+        self.macros.sort_by_key(|m| m.slot);
+    }
+
+    /// Moves the macro `name` to F`slot`, replacing what was there.
+    pub fn rebind(&mut self, name: &str, slot: u8) -> bool {
+        let Some(idx) = self.macros.iter().position(|m| m.name == name) else {
+            return false;
+        };
+        let mut m = self.macros.remove(idx);
+        m.slot = slot;
+        self.bind(m);
+        true
+    }
+
+    pub fn delete(&mut self, name: &str) -> bool {
+        let before = self.macros.len();
+        self.macros.retain(|m| m.name != name);
+        self.macros.len() != before
+    }
+
+    /// The replayed keys that are due by `now_ms`: one per
+    /// `REPLAY_INTERVAL_MS` since the replay started.
+    pub fn due(&mut self, now_ms: u64) -> Vec<Key> {
+        let Mode::Replaying { keys, next, due_ms } = &mut self.mode else {
+            return Vec::new();
+        };
+        let mut out = Vec::new();
+        while *next < keys.len() && *due_ms <= now_ms {
+            out.push(keys[*next]);
+            *next += 1;
+            *due_ms += REPLAY_INTERVAL_MS;
+        }
+        if *next == keys.len() {
+            self.mode = Mode::Idle;
+        }
+        out
+    }
+
+    /// The naming dialog, centred in `screen`. Returns the panel.
+    fn collect_dialog(
+        &mut self,
+        screen: Rect,
+        theme: &Theme,
+        out: &mut RenderList,
+    ) -> Option<Rect> {
+        const PADDING: usize = 12;
+        let Mode::Naming { keys, input } = &mut self.mode else {
+            return None;
+        };
+        let title = format!("Name the macro ({} keys)", keys.len().min(MAX_KEYS));
+        let hint = "F5-F8 binds it, Enter the first free key, Esc discards";
+        let width = (hint.len() * 10 + PADDING * 2).min(screen.w);
+        let height = (20 * 3 + 30 + PADDING * 4).min(screen.h);
+        let panel = Rect::new(
+            screen.x + (screen.w - width) / 2,
+            screen.y + (screen.h - height) / 2,
+            width,
+            height,
+        );
+        let frame = Panel::new(panel).with_shadow(true);
+        frame.collect_render(theme, true, out);
+        out.text(title, panel.x + PADDING, panel.y + PADDING, theme.accent);
+        input.set_rect(Rect::new(
+            panel.x + PADDING,
+            panel.y + PADDING * 2 + 20,
+            width.saturating_sub(PADDING * 2),
+            30,
+        ));
+        input.collect_render(theme, true, out);
+        out.text(
+            hint,
+            panel.x + PADDING,
+            panel.y + PADDING * 3 + 50,
+            theme.muted,
+        );
+        // The shadow reaches past the panel
+        Some(frame.bounds())
+    }
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Runs `f` on the shared recorder, loading the macros from
+/// `MACRO_CONFIG` the first time.
+fn with_recorder<R>(f: impl FnOnce(&mut Recorder) -> R) -> R {
+    let mut recorder = RECORDER.lock();
+    let recorder = recorder.get_or_insert_with(|| {
+        let text = crate::fs::ramfs::read(MACRO_CONFIG).unwrap_or_default();
+        Recorder::new(parse(core::str::from_utf8(&text).unwrap_or("")))
+    });
+    f(recorder)
+}
+
+fn save(recorder: &Recorder) {
+    let _ = crate::fs::ramfs::write(MACRO_CONFIG, serialize(recorder.macros()).as_bytes());
+}
+
+/// `Recorder::key` on the shared recorder, saving the macros when they
+/// change and showing the naming dialog while it is open.
+pub fn key(code: KeyCode, mods: Modifiers, now_ms: u64) -> Intercept {
+    with_recorder(|recorder| {
+        let result = recorder.key(Key { code, mods }, now_ms);
+        if result == Intercept::Saved {
+            save(recorder);
+        }
+        if recorder.naming() {
+            layers::register(Layer::Dialog, "macro", draw);
+        } else {
+            layers::unregister("macro");
+        }
+        result
+    })
+}
+
+pub fn due(now_ms: u64) -> Vec<Key> {
+    with_recorder(|recorder| recorder.due(now_ms))
+}
+
+/// For the top bar.
+pub fn recording() -> bool {
+    with_recorder(|recorder| recorder.recording())
+}
+
+/// Lines for `macro list`.
+pub fn list() -> String {
+    with_recorder(|recorder| {
+        if recorder.macros().is_empty() {
+            return String::from("no macros (Ctrl+Alt+R records one)\n");
+        }
+        let mut out = String::new();
+        for m in recorder.macros() {
+            out.push_str(&format!(
+                "F{}  {:<16}{} keys\n",
+                m.slot,
+                m.name,
+                m.keys.len()
+            ));
+        }
+        out
+    })
+}
+
+pub fn rebind(name: &str, slot: u8) -> bool {
+    with_recorder(|recorder| {
+        let found = recorder.rebind(name, slot);
+        if found {
+            save(recorder);
+        }
+        found
+    })
+}
+
+pub fn delete(name: &str) -> bool {
+    with_recorder(|recorder| {
+        let found = recorder.delete(name);
+        if found {
+            save(recorder);
+        }
+        found
+    })
+}
+
+fn draw(fb: &mut FramebufferWriter, theme: &Theme) -> Option<Rect> {
+    let mut out = RenderList::new();
+    let screen = Rect::new(0, 0, fb.width, fb.height);
+    let panel = with_recorder(|recorder| recorder.collect_dialog(screen, theme, &mut out));
+    flush_commands(fb, out.as_slice());
+    panel
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(code: KeyCode, mods: Modifiers) -> Key {
+        Key { code, mods }
+    }
+
+    #[test_case]
+    fn macros_round_trip_through_the_config_text() {
+        let macros = alloc::vec![
+            Macro {
+                name: String::from("greet"),
+                slot: 5,
+                keys: alloc::vec![
+                    typed(KeyCode::Char('h'), Modifiers::NONE),
+                    typed(KeyCode::Char(' '), Modifiers::NONE),
+                    typed(KeyCode::Char('C'), Modifiers::SHIFT),
+                    typed(KeyCode::Char('-'), Modifiers::CTRL),
+                    typed(KeyCode::Char('é'), Modifiers::NONE),
+                    typed(KeyCode::Enter, Modifiers::SHIFT),
+                ],
+            },
+            Macro {
+                name: String::from("nav"),
+                slot: 8,
+                keys: alloc::vec![
+                    typed(
+                        KeyCode::Arrow(Arrow::Left),
+                        Modifiers::CTRL | Modifiers::ALT
+                    ),
+                    typed(KeyCode::Function(2), Modifiers::NONE),
+                ],
+            },
+        ];
+        let text = serialize(&macros);
+        assert!(text.starts_with("greet F5 h space S-C C-- u+e9 S-enter\n"));
+        assert_eq!(parse(&text), macros);
+        // A bad line costs only itself
+        let damaged = format!("broken F9 a\nworse F5 C-\n{}", text);
+        assert_eq!(parse(&damaged), macros);
+    }
+
+    /// Offers `key`, collecting it in `received` (what reaches the
+    /// terminal) if it passes.
+    fn offer(recorder: &mut Recorder, received: &mut Vec<Key>, key: Key, now: u64) -> Intercept {
+        let result = recorder.key(key, now);
+        if result == Intercept::Pass {
+            received.push(key);
+        }
+        result
+    }
+
+    #[test_case]
+    fn recorded_keys_arrive_twice_once_typed_and_once_replayed() {
+        let chord = typed(KeyCode::Char('r'), Modifiers::CTRL | Modifiers::ALT);
+        let (a, b) = (
+            typed(KeyCode::Char('l'), Modifiers::NONE),
+            typed(KeyCode::Enter, Modifiers::SHIFT),
+        );
+        let mut recorder = Recorder::new(Vec::new());
+        let mut received = Vec::new();
+        let r = &mut received;
+
+        assert_eq!(offer(&mut recorder, r, chord, 0), Intercept::Consumed);
+        assert!(recorder.recording());
+        offer(&mut recorder, r, a, 1);
+        offer(&mut recorder, r, b, 2);
+        offer(&mut recorder, r, chord, 3);
+        assert!(!recorder.recording());
+        for ch in "ls".chars() {
+            offer(
+                &mut recorder,
+                r,
+                typed(KeyCode::Char(ch), Modifiers::NONE),
+                4,
+            );
+        }
+        let f6 = typed(KeyCode::Function(6), Modifiers::NONE);
+        assert_eq!(offer(&mut recorder, r, f6, 5), Intercept::Saved);
+        assert_eq!(recorder.macros()[0].name, "ls");
+        assert_eq!(recorder.macros()[0].keys, [a, b]);
+
+        // F6 replays at the fixed rate, and not again while it does
+        assert_eq!(offer(&mut recorder, r, f6, 100), Intercept::Consumed);
+        assert!(recorder.replaying());
+        r.extend(recorder.due(100));
+        assert_eq!(recorder.key(f6, 101), Intercept::Pass);
+        r.extend(recorder.due(100 + REPLAY_INTERVAL_MS));
+        assert!(!recorder.replaying());
+        assert_eq!(received, [a, b, a, b]);
+
+        // Escape cancels a replay before its next key
+        recorder.key(f6, 200);
+        assert_eq!(recorder.due(200), [a]);
+        let escape = typed(KeyCode::Escape, Modifiers::NONE);
+        assert_eq!(recorder.key(escape, 201), Intercept::Consumed);
+        assert!(recorder.due(300).is_empty());
+    }
+}
@@ -3,37 +3,46 @@
 //! GUI-like application model
 
 use crate::devices::drivers::MouseEvent;
+use crate::devices::mouse_cursor::{self, CursorShape};
+use crate::memory::pressure::Pressure;
 use crate::ui_provider::{
+    background,
     color::Color,
+    layers::{self, Layer},
     render::{flush_commands, RenderCommand, RenderList},
     shape::Rect,
     theme::Theme,
+    toast,
 };
 use alloc::boxed::Box;
 use alloc::vec::Vec;
+use core::ops::Range;
 
+pub mod compositor;
+pub mod keys;
+pub mod macros;
 pub mod navigation;
+pub mod split;
+pub mod state;
 
-const OFF_SCREEN_PARK_X: usize = 10_000;
+pub use keys::{Arrow, KeyCode, Modifiers};
+pub use split::Split;
+pub use state::AppStore;
 
-#[derive(Clone, Copy, Debug)]
-pub enum Arrow {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+const OFF_SCREEN_PARK_X: usize = 10_000;
+const OFF_SCREEN: Rect = Rect {
+    x: OFF_SCREEN_PARK_X,
+    y: OFF_SCREEN_PARK_X,
+    w: 1,
+    h: 1,
+};
 
 pub enum AppEvent {
-    KeyPress {
-        ch: char,
-        ctrl: bool,
-        alt: bool,
-        shift: bool,
-        arrow: Option<Arrow>,
-    },
+    KeyPress { code: KeyCode, mods: Modifiers },
     Tick,
-    Mouse(MouseEvent),
+    /// A mouse packet, with where the cursor was once it had moved, in
+    /// screen coordinates (the space app `Rect`s are in).
+    Mouse { event: MouseEvent, x: usize, y: usize },
 }
 
 #[derive(Clone, Copy)]
@@ -51,30 +60,104 @@ pub trait App {
 
     fn collect_render(&mut self, _theme: &Theme, _out: &mut RenderList) {}
 
+    /// Drawn in the `AppOverlay` layer, below the focus ring. Only the
+    /// focused app is asked.
     fn collect_overlay(&mut self, _theme: &Theme, _out: &mut RenderList) {}
 
+    /// Dialogs and pickers, drawn in the `Dialog` layer: above the focus
+    /// ring, below toasts and the cursor. Only the focused app is asked.
+    fn collect_dialog(&mut self, _theme: &Theme, _out: &mut RenderList) {}
+
+    /// Escape: unwind one level of whatever is in progress (a search, a
+    /// selection, a half-typed line) and return `true`. With nothing to
+    /// cancel return `false`, and Escape arrives through `on_event` as an
+    /// ordinary key.
+    fn on_cancel(&mut self) -> bool {
+        false
+    }
+
+    /// Called when the host moves keyboard focus to another of this app's blocks.
+    fn focus_changed(&mut self, _block_id: u32) {}
+
+    /// The blocks Tab visits, in order; Shift+Tab goes backwards. With
+    /// fewer than two, Tab is an ordinary key for the app. Defaults to the
+    /// order of `focus_blocks`.
+    fn focus_order(&mut self) -> Vec<u32> {
+        self.focus_blocks().iter().map(|b| b.id).collect()
+    }
+
+    /// Enter or Space on block `block_id`: press the button, toggle the
+    /// box. Return `false` for a block with nothing to activate, and the
+    /// key arrives through `on_event` instead.
+    fn activate(&mut self, _block_id: u32) -> bool {
+        false
+    }
+
+    /// Called when the app loses focus; write the fields worth keeping.
+    fn save_state(&self, _store: &mut AppStore) {}
+
+    /// Called when the app regains focus, after `save_state` has run once.
+    fn restore_state(&mut self, _store: &AppStore) {}
+
+    /// Memory is running low: drop what can be rebuilt or lived without,
+    /// more at `Critical`. Returns about the bytes freed.
+    fn trim(&mut self, _level: Pressure) -> usize {
+        0
+    }
+
+    /// Whatever was drawn over `rect`, a part of the app's bounds, last
+    /// frame is gone. An app that draws only what changed must draw all
+    /// of `rect` again in its next `collect_render`; one that draws
+    /// everything every frame has nothing to do.
+    fn invalidate(&mut self, _rect: Rect) {}
+
+    /// Whether the app paints every pixel of its bounds. The background
+    /// is not drawn under an opaque app; under any other, or one blended
+    /// at less than full alpha, it is drawn every frame, and the app is
+    /// invalidated whole to draw again over it.
+    fn opaque(&self) -> bool {
+        true
+    }
+
     fn focus_blocks(&mut self) -> &mut [FocusBlock];
     fn bounds(&self) -> Rect;
 }
 
 pub struct AppHost {
     apps: Vec<Box<dyn App>>,
+    states: Vec<AppStore>,
     focus_app: usize,
     focus_block_id: u32,
     render_commands: RenderList,
+    /// Which of `render_commands` each app drew, bottom to top; the rest
+    /// are the host's own.
+    app_commands: Vec<(usize, Range<usize>)>,
     overlay_commands: RenderList,
+    focus_ring_commands: RenderList,
+    dialog_commands: RenderList,
     needs_redraw: bool,
+    /// Area the apps share, as last passed to `layout_content`.
+    content: Rect,
+    split: Option<Split>,
+    mouse_down: bool,
 }
 
 impl AppHost {
     pub fn new() -> Self {
         Self {
             apps: Vec::new(),
+            states: Vec::new(),
             focus_app: 0,
             focus_block_id: 1,
             render_commands: RenderList::new(),
+            app_commands: Vec::new(),
             overlay_commands: RenderList::new(),
+            focus_ring_commands: RenderList::new(),
+            dialog_commands: RenderList::new(),
             needs_redraw: true,
+            content: Rect::new(0, 0, 0, 0),
+            split: None,
+            mouse_down: false,
         }
     }
 
@@ -82,6 +165,7 @@ impl AppHost {
         if self.apps.is_empty() {
             self.focus_block_id = 1;
         }
+        self.states.push(AppStore::new());
         self.apps.push(app);
         self.request_redraw();
     }
@@ -95,8 +179,82 @@ impl AppHost {
         self.request_redraw();
     }
 
+    /// Lays out the visible apps in `content`: the focused one alone, or
+    /// the split pair. Everything else is parked off screen.
+    pub fn layout_content(&mut self, content: Rect) {
+        self.content = content;
+        let panes = self.split.map(|split| (split, split.app_rects(content)));
+        for idx in 0..self.apps.len() {
+            let bounds = match panes {
+                Some((split, (left, _))) if idx == split.left => left,
+                Some((split, (_, right))) if idx == split.right => right,
+                Some(_) => OFF_SCREEN,
+                None if idx == self.focus_app => content,
+                None => OFF_SCREEN,
+            };
+            self.layout_app(idx, bounds);
+        }
+    }
+
+    /// Shows the focused app next to the following one, or goes back to a
+    /// single app. The pair's last ratio is restored.
+    pub fn toggle_split(&mut self) -> bool {
+        if self.split.take().is_some() {
+            self.request_redraw();
+            return true;
+        }
+        if self.apps.len() < 2 {
+            return false;
+        }
+        let left = self.focus_app;
+        let right = (left + 1) % self.apps.len();
+        let ratio = self.states[left]
+            .int(split::RATIO_KEY)
+            .and_then(|r| u32::try_from(r).ok())
+            .unwrap_or(split::DEFAULT_RATIO);
+        self.split = Some(Split::new(left, right, ratio));
+        self.request_redraw();
+        true
+    }
+
+    /// Divider hover and drag. Returns `true` if the event belonged to the
+    /// divider and must not reach the apps.
+    pub fn split_mouse(&mut self, x: usize, y: usize, left_down: bool) -> bool {
+        let pressed = left_down && !self.mouse_down;
+        self.mouse_down = left_down;
+        let content = self.content;
+        let Some(split) = self.split.as_mut() else {
+            return false;
+        };
+
+        if split.dragging() {
+            if left_down {
+                if split.drag_to(content, x) {
+                    self.request_redraw();
+                }
+                return true;
+            }
+            // Released, possibly with the pointer far outside the content.
+            if split.end_drag() {
+                let (left, ratio) = (split.left, split.ratio());
+                self.states[left].set_int(split::RATIO_KEY, i64::from(ratio));
+            }
+            self.request_redraw();
+            return true;
+        }
+
+        let hover = split.hit_divider(content, x, y);
+        mouse_cursor::set_shape(if hover {
+            CursorShape::ResizeHorizontal
+        } else {
+            CursorShape::Arrow
+        });
+        pressed && split.begin_drag(content, x, y)
+    }
+
     pub fn render_app_once(&mut self, idx: usize, theme: &Theme) {
         self.render_commands.clear();
+        self.app_commands.clear();
         self.apps[idx].collect_render(theme, &mut self.render_commands);
     }
 
@@ -106,29 +264,22 @@ impl AppHost {
         }
 
         self.render_commands.clear();
+        self.app_commands.clear();
         self.apps[self.focus_app].collect_render(theme, &mut self.render_commands);
-
-        self.overlay_commands.clear();
-        self.apps[self.focus_app].collect_overlay(theme, &mut self.overlay_commands);
-        self.draw_focus_ring(Color::from_hex(0xFF6B6B));
+        self.collect_overlays(theme, Color::from_hex(0xFF6B6B));
 
         self.needs_redraw = false;
     }
 
     pub fn render_all_apps(&mut self, theme: &Theme) {
         self.render_commands.clear();
+        self.app_commands.clear();
 
         for i in 0..self.apps.len() {
             self.apps[i].collect_render(theme, &mut self.render_commands);
         }
 
-        if self.focus_app < self.apps.len() {
-            self.overlay_commands.clear();
-            self.apps[self.focus_app].collect_overlay(theme, &mut self.overlay_commands);
-            self.draw_focus_ring(Color::from_hex(0xFF6B6B));
-        } else {
-            self.overlay_commands.clear();
-        }
+        self.collect_overlays(theme, Color::from_hex(0xFF6B6B));
 
         self.needs_redraw = false;
     }
@@ -137,22 +288,12 @@ impl AppHost {
         if self.apps.is_empty() {
             return;
         }
-        self.focus_app = (self.focus_app + 1) % self.apps.len();
-        let blocks = self.apps[self.focus_app].focus_blocks();
-        if !blocks.is_empty() {
-            self.focus_block_id = blocks[0].id;
-        }
-        self.request_redraw();
+        self.activate_app((self.focus_app + 1) % self.apps.len());
     }
 
     pub fn switch_to_app(&mut self, idx: usize) -> bool {
         if idx < self.apps.len() {
-            self.focus_app = idx;
-            let blocks = self.apps[self.focus_app].focus_blocks();
-            if !blocks.is_empty() {
-                self.focus_block_id = blocks[0].id;
-            }
-            self.request_redraw();
+            self.activate_app(idx);
             true
         } else {
             false
@@ -165,12 +306,7 @@ impl AppHost {
             if x >= bounds.x && x < bounds.x + bounds.w && y >= bounds.y && y < bounds.y + bounds.h
             {
                 if idx != self.focus_app {
-                    self.focus_app = idx;
-                    let blocks = self.apps[self.focus_app].focus_blocks();
-                    if !blocks.is_empty() {
-                        self.focus_block_id = blocks[0].id;
-                    }
-                    self.request_redraw();
+                    self.activate_app(idx);
                 }
                 break;
             }
@@ -185,12 +321,34 @@ impl AppHost {
         self.focus_app
     }
 
+    /// Bounds of the apps on screen that cover them completely at full
+    /// alpha, for `background::draw`.
+    pub fn opaque_rects(&self) -> Vec<Rect> {
+        (0..self.apps.len())
+            .filter(|&idx| {
+                self.covers_itself(idx) && self.apps[idx].bounds().x < OFF_SCREEN_PARK_X
+            })
+            .map(|idx| self.apps[idx].bounds())
+            .collect()
+    }
+
+    /// Whether nothing under app `idx` shows through it.
+    fn covers_itself(&self, idx: usize) -> bool {
+        self.apps[idx].opaque() && compositor::alpha(idx) == u8::MAX
+    }
+
     pub fn render_commands(&self) -> &[RenderCommand] {
         self.render_commands.as_slice()
     }
 
-    pub fn overlay_commands(&self) -> &[RenderCommand] {
-        self.overlay_commands.as_slice()
+    /// What the host collected for each overlay layer, for
+    /// `layers::composite`.
+    pub fn layer_commands(&self) -> [(Layer, &[RenderCommand]); 3] {
+        [
+            (Layer::AppOverlay, self.overlay_commands.as_slice()),
+            (Layer::FocusRing, self.focus_ring_commands.as_slice()),
+            (Layer::Dialog, self.dialog_commands.as_slice()),
+        ]
     }
 
     pub fn dispatch_event(&mut self, event: AppEvent) {
@@ -200,16 +358,28 @@ impl AppHost {
 
         let changed = match event {
             AppEvent::KeyPress {
-                ch: _,
-                ctrl,
-                alt,
-                shift: _,
-                arrow: Some(dir),
-            } if ctrl || alt => {
+                code: KeyCode::Escape,
+                ..
+            } if self.cancel() => true,
+            AppEvent::KeyPress {
+                code: KeyCode::Tab,
+                mods,
+            } if !mods.ctrl() && !mods.alt() && self.tab_focus(mods.shift()) => true,
+            AppEvent::KeyPress {
+                code: KeyCode::Enter | KeyCode::Char(' '),
+                mods,
+            } if !mods.ctrl() && !mods.alt() && self.activate_focused() => true,
+            AppEvent::KeyPress {
+                code: KeyCode::Arrow(dir),
+                mods,
+            } if mods.alt() => {
+                // Alt only: Ctrl+arrows belong to the app (word-wise movement).
                 let blocks = self.apps[self.focus_app].focus_blocks().to_vec();
                 let next_focus = navigation::move_focus(&blocks, self.focus_block_id, dir);
                 let changed = next_focus != self.focus_block_id;
-                self.focus_block_id = next_focus;
+                if changed {
+                    self.set_focus_block(next_focus);
+                }
                 changed
             }
             _ => self.apps[self.focus_app].on_event(event),
@@ -220,6 +390,58 @@ impl AppHost {
         }
     }
 
+    fn activate_focused(&mut self) -> bool {
+        self.apps[self.focus_app].activate(self.focus_block_id)
+    }
+
+    /// Moves focus one step along the focused app's `focus_order`. False,
+    /// leaving Tab to the app, when there is nowhere to step to.
+    fn tab_focus(&mut self, back: bool) -> bool {
+        let order = self.apps[self.focus_app].focus_order();
+        if order.len() < 2 {
+            return false;
+        }
+        let next = navigation::next_in_order(&order, self.focus_block_id, back);
+        self.set_focus_block(next);
+        true
+    }
+
+    /// Offers Escape to the innermost thing that can take it: a divider
+    /// drag, then the focused app. One level per press.
+    fn cancel(&mut self) -> bool {
+        if let Some(split) = self.split.as_mut() {
+            if split.cancel_drag() {
+                return true;
+            }
+        }
+        self.apps[self.focus_app].on_cancel()
+    }
+
+    /// Trims every app; the total bytes freed.
+    pub fn trim(&mut self, level: Pressure) -> usize {
+        self.apps.iter_mut().map(|app| app.trim(level)).sum()
+    }
+
+    /// Shows `message` in a toast for `duration_ticks` timer ticks.
+    pub fn notify(&mut self, message: &str, duration_ticks: u64) {
+        toast::notify(message, duration_ticks);
+        self.request_redraw();
+    }
+
+    /// Has every shown app under `rect` draw its part of it again (see
+    /// `App::invalidate`).
+    pub fn invalidate(&mut self, rect: Rect) {
+        for app in &mut self.apps {
+            let bounds = app.bounds();
+            if bounds.x >= OFF_SCREEN_PARK_X {
+                continue;
+            }
+            if let Some(part) = bounds.intersect(rect) {
+                app.invalidate(part);
+            }
+        }
+    }
+
     pub fn request_redraw(&mut self) {
         self.needs_redraw = true;
     }
@@ -230,33 +452,427 @@ impl AppHost {
 
     pub fn compose(&mut self, theme: &Theme, accent: Color) {
         self.render_commands.clear();
+        self.app_commands.clear();
 
-        for i in 0..self.apps.len() {
+        for i in compositor::z_order(self.apps.len(), self.focus_app) {
             if self.apps[i].bounds().x >= OFF_SCREEN_PARK_X {
                 continue;
             }
+            if !self.covers_itself(i) {
+                // The background went over it, or a blend needs all of it
+                let bounds = self.apps[i].bounds();
+                self.apps[i].invalidate(bounds);
+            }
+            let start = self.render_commands.len();
             self.apps[i].collect_render(theme, &mut self.render_commands);
+            self.app_commands
+                .push((i, start..self.render_commands.len()));
         }
+        self.collect_divider(theme);
+        self.collect_overlays(theme, accent);
+
+        self.needs_redraw = false;
+    }
 
+    /// The apps' part of a frame, up to where the layers go on. What the
+    /// layers drew last frame (`layers::stale`) and `gone` (anything else
+    /// drawn over the apps that has since gone, like a destroyed surface's
+    /// window) is handed back to the apps under it, the wallpaper goes
+    /// wherever no opaque app or `covered` (what paints itself whole every
+    /// frame, like the top bar) is, and under that stale area, and then the
+    /// apps draw.
+    pub fn draw_frame(
+        &mut self,
+        fb: &mut crate::devices::framebuffer::framebuffer::FramebufferWriter,
+        theme: &Theme,
+        covered: &[Rect],
+        gone: &[Rect],
+    ) {
+        let mut stale = layers::stale();
+        stale.extend_from_slice(gone);
+        for &rect in &stale {
+            self.invalidate(rect);
+        }
+        self.compose(theme, theme.accent);
+        let mut opaque = self.opaque_rects();
+        opaque.extend_from_slice(covered);
+        background::draw(fb, theme, &opaque, &stale);
+        self.flush(fb);
+    }
+
+    /// Draws the apps in z-order, blending the translucent ones (see
+    /// `compositor`), then the divider. Their overlays go on with the other
+    /// layers, through `layer_commands`.
+    pub fn flush(&self, fb: &mut crate::devices::framebuffer::framebuffer::FramebufferWriter) {
+        let commands = self.render_commands.as_slice();
+        let mut host_start = 0;
+        for (idx, range) in &self.app_commands {
+            let alpha = compositor::alpha(*idx);
+            let own = &commands[range.clone()];
+            if alpha == u8::MAX {
+                flush_commands(fb, own);
+            } else {
+                let b = self.apps[*idx].bounds();
+                let under = fb.snapshot_rect(b.x, b.y, b.w, b.h);
+                flush_commands(fb, own);
+                fb.blend_over(b.x, b.y, b.w, b.h, &under, alpha);
+            }
+            host_start = range.end;
+        }
+        flush_commands(fb, &commands[host_start..]);
+    }
+
+    /// The focused app's overlay and dialogs, and the focus ring.
+    fn collect_overlays(&mut self, theme: &Theme, accent: Color) {
         self.overlay_commands.clear();
-        if self.focus_app < self.apps.len() {
-            self.apps[self.focus_app].collect_overlay(theme, &mut self.overlay_commands);
-            self.draw_focus_ring(accent);
+        self.focus_ring_commands.clear();
+        self.dialog_commands.clear();
+        if self.focus_app >= self.apps.len() {
+            return;
         }
+        let app = &mut self.apps[self.focus_app];
+        app.collect_overlay(theme, &mut self.overlay_commands);
+        app.collect_dialog(theme, &mut self.dialog_commands);
+        self.draw_focus_ring(accent, theme.focus_ring);
+    }
 
-        self.needs_redraw = false;
+    /// Divider bar, and while dragging the strip between where the apps
+    /// end and where the bar is now.
+    fn collect_divider(&mut self, theme: &Theme) {
+        let Some(split) = self.split else {
+            return;
+        };
+        if let Some(strip) = split.exposed_strip(self.content) {
+            self.render_commands
+                .push(RenderCommand::fill_rect(strip, theme.background));
+        }
+        let bar = split.divider_rect(self.content);
+        self.render_commands
+            .push(RenderCommand::fill_rect(bar, theme.surface));
+        let line = Rect::new(bar.x + bar.w / 2 - 1 + bar.w % 2, bar.y, 1, bar.h);
+        let accent = if split.dragging() {
+            theme.accent
+        } else {
+            theme.border
+        };
+        self.render_commands
+            .push(RenderCommand::fill_rect(line, accent));
     }
 
-    pub fn flush(&self, fb: &mut crate::devices::framebuffer::framebuffer::FramebufferWriter) {
-        flush_commands(fb, self.render_commands.as_slice());
-        flush_commands(fb, self.overlay_commands.as_slice());
+    /// Saves the outgoing app's state and restores the incoming one's,
+    /// including which focus block was active. Activating an app outside
+    /// the split pair leaves split mode.
+    fn activate_app(&mut self, idx: usize) {
+        if let Some(split) = self.split {
+            if idx != split.left && idx != split.right {
+                self.split = None;
+            }
+        }
+        if idx != self.focus_app {
+            let prev = self.focus_app;
+            self.apps[prev].save_state(&mut self.states[prev]);
+            self.states[prev].set_int(state::FOCUS_KEY, i64::from(self.focus_block_id));
+
+            self.focus_app = idx;
+            self.apps[idx].restore_state(&self.states[idx]);
+        }
+
+        let saved_focus = self.states[idx]
+            .int(state::FOCUS_KEY)
+            .and_then(|id| u32::try_from(id).ok())
+            .filter(|&id| self.apps[idx].focus_blocks().iter().any(|b| b.id == id));
+        match saved_focus {
+            Some(id) => self.set_focus_block(id),
+            None => self.focus_first_block(),
+        }
+        self.request_redraw();
+    }
+
+    fn focus_first_block(&mut self) {
+        let first = self.apps[self.focus_app].focus_blocks().first().map(|b| b.id);
+        if let Some(id) = first {
+            self.set_focus_block(id);
+        }
     }
 
-    fn draw_focus_ring(&mut self, accent: Color) {
+    fn set_focus_block(&mut self, id: u32) {
+        self.focus_block_id = id;
+        self.apps[self.focus_app].focus_changed(id);
+    }
+
+    fn draw_focus_ring(&mut self, accent: Color, width: usize) {
         let blocks = self.apps[self.focus_app].focus_blocks().to_vec();
         if let Some(b) = blocks.iter().find(|b| b.id == self.focus_block_id) {
-            self.overlay_commands
-                .push(RenderCommand::stroke_rect(b.rect, accent, 2));
+            self.focus_ring_commands
+                .push(RenderCommand::stroke_rect(b.rect, accent, width));
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Has `levels` things to cancel; counts the keys that get through.
+    struct Probe {
+        levels: usize,
+        keys: Arc<AtomicUsize>,
+        block: FocusBlock,
+    }
+
+    impl App for Probe {
+        fn on_cancel(&mut self) -> bool {
+            let had = self.levels > 0;
+            self.levels = self.levels.saturating_sub(1);
+            had
+        }
+
+        fn on_event(&mut self, event: AppEvent) -> bool {
+            if let AppEvent::KeyPress { .. } = event {
+                self.keys.fetch_add(1, Ordering::Relaxed);
+            }
+            false
+        }
+
+        fn focus_blocks(&mut self) -> &mut [FocusBlock] {
+            core::slice::from_mut(&mut self.block)
+        }
+
+        fn bounds(&self) -> Rect {
+            self.block.rect
+        }
+    }
+
+    /// Fills its bounds with one colour.
+    struct Fill {
+        color: Color,
+        block: FocusBlock,
+    }
+
+    impl App for Fill {
+        fn collect_render(&mut self, _theme: &Theme, out: &mut RenderList) {
+            out.fill_rect(self.block.rect, self.color);
+        }
+
+        fn focus_blocks(&mut self) -> &mut [FocusBlock] {
+            core::slice::from_mut(&mut self.block)
         }
+
+        fn bounds(&self) -> Rect {
+            self.block.rect
+        }
+    }
+
+    #[test_case]
+    fn translucent_apps_blend_over_what_is_under_them() {
+        let buffer: &'static mut [u8] = alloc::vec![0u8; 64 * 32 * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, 64, 32, 64, 4);
+        let theme = Theme::dark_modern();
+        let mut host = AppHost::new();
+        let fill = |x, color| {
+            Box::new(Fill {
+                color: Color::from_hex(color),
+                block: FocusBlock {
+                    id: 1,
+                    rect: Rect::new(x, 0, 32, 32),
+                },
+            })
+        };
+        host.register_app(fill(0, 0xff0000));
+        host.register_app(fill(32, 0x00ff00));
+
+        fb.clear(Color::from_hex(0x0000ff));
+        compositor::set_alpha(0, 128);
+        host.compose(&theme, theme.accent);
+        assert_eq!(host.opaque_rects(), [Rect::new(32, 0, 32, 32)]);
+        host.flush(&mut fb);
+        assert_eq!(fb.get_pixel(5, 5), Color::from_hex(0x80007f));
+        // The opaque one is drawn as is
+        assert_eq!(fb.get_pixel(40, 5), Color::from_hex(0x00ff00));
+
+        assert_eq!(compositor::set_alpha(0, 255), 255);
+        host.compose(&theme, theme.accent);
+        host.flush(&mut fb);
+        assert_eq!(fb.get_pixel(5, 5), Color::from_hex(0xff0000));
+        assert_eq!(host.opaque_rects().len(), 2);
+        // The focused app draws last
+        let order: Vec<usize> = compositor::z_order(3, 1).collect();
+        assert_eq!(order, [0, 2, 1]);
+    }
+
+    const GONE: Rect = Rect {
+        x: 200,
+        y: 140,
+        w: 90,
+        h: 40,
+    };
+
+    fn gone(fb: &mut FramebufferWriter, _theme: &Theme) -> Option<Rect> {
+        fb.fill_rect(GONE.x, GONE.y, GONE.w, GONE.h, Color::from_hex(0xff00ff));
+        Some(GONE)
+    }
+
+    #[test_case]
+    fn what_a_layer_covered_is_drawn_again_once_it_goes() {
+        use crate::apps::terminal_app::TerminalApp;
+
+        let buffer: &'static mut [u8] = alloc::vec![0u8; 320 * 200 * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, 320, 200, 320, 4);
+        let theme = Theme::dark_modern();
+        let mut host = AppHost::new();
+        host.register_app(Box::new(TerminalApp::new(320, 200)));
+        host.layout_content(Rect::new(0, 0, 320, 200));
+        let mut frame = |fb: &mut FramebufferWriter| {
+            host.draw_frame(fb, &theme, &[], &[]);
+            layers::composite(fb, &theme, &host.layer_commands());
+            fb.snapshot_rect(GONE.x, GONE.y, GONE.w, GONE.h)
+        };
+
+        // The terminal draws everything once, then only what changed
+        let before = frame(&mut fb);
+        assert_eq!(frame(&mut fb), before);
+        layers::register(Layer::Toast, "test gone", gone);
+        assert_ne!(frame(&mut fb), before);
+        layers::unregister("test gone");
+        assert_eq!(frame(&mut fb), before);
+        assert_eq!(frame(&mut fb), before);
+    }
+
+    #[test_case]
+    fn a_transparent_terminal_keeps_its_text_over_the_wallpaper() {
+        use crate::apps::terminal_app::TerminalApp;
+
+        let buffer: &'static mut [u8] = alloc::vec![0u8; 320 * 200 * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, 320, 200, 320, 4);
+        let theme = Theme::dark_modern();
+        let mut host = AppHost::new();
+        host.register_app(Box::new(TerminalApp::new(320, 200)));
+        host.layout_content(Rect::new(0, 0, 320, 200));
+        background::set_terminal_transparent(true);
+        for ch in "ls".chars() {
+            host.dispatch_event(AppEvent::KeyPress {
+                code: KeyCode::Char(ch),
+                mods: Modifiers::NONE,
+            });
+        }
+
+        host.draw_frame(&mut fb, &theme, &[], &[]);
+        let text = fb.snapshot_rect(0, 0, 320, 40);
+        host.draw_frame(&mut fb, &theme, &[], &[]);
+        host.draw_frame(&mut fb, &theme, &[], &[]);
+        background::set_terminal_transparent(false);
+        assert_eq!(fb.snapshot_rect(0, 0, 320, 40), text);
+    }
+
+    #[test_case]
+    fn a_translucent_terminal_is_blended_whole_every_frame() {
+        use crate::apps::terminal_app::TerminalApp;
+
+        let buffer: &'static mut [u8] = alloc::vec![0u8; 320 * 200 * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, 320, 200, 320, 4);
+        let theme = Theme::dark_modern();
+        let mut host = AppHost::new();
+        host.register_app(Box::new(TerminalApp::new(320, 200)));
+        host.layout_content(Rect::new(0, 0, 320, 200));
+        compositor::set_alpha(0, 160);
+
+        host.draw_frame(&mut fb, &theme, &[], &[]);
+        let first = fb.snapshot_rect(0, 0, 320, 200);
+        host.draw_frame(&mut fb, &theme, &[], &[]);
+        host.draw_frame(&mut fb, &theme, &[], &[]);
+        compositor::set_alpha(0, 255);
+        assert_eq!(fb.snapshot_rect(0, 0, 320, 200), first);
+    }
+
+    /// Three blocks, tabbed in reverse; block 2 activates.
+    struct Form {
+        blocks: [FocusBlock; 3],
+        keys: Arc<AtomicUsize>,
+        presses: Arc<AtomicUsize>,
+    }
+
+    impl App for Form {
+        fn on_event(&mut self, event: AppEvent) -> bool {
+            if let AppEvent::KeyPress { .. } = event {
+                self.keys.fetch_add(1, Ordering::Relaxed);
+            }
+            false
+        }
+
+        fn activate(&mut self, block_id: u32) -> bool {
+            if block_id != 2 {
+                return false;
+            }
+            self.presses.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+
+        fn focus_order(&mut self) -> Vec<u32> {
+            alloc::vec![3, 2, 1]
+        }
+
+        fn focus_blocks(&mut self) -> &mut [FocusBlock] {
+            &mut self.blocks
+        }
+
+        fn bounds(&self) -> Rect {
+            Rect::new(0, 0, 30, 10)
+        }
+    }
+
+    #[test_case]
+    fn tab_walks_the_focus_order_and_enter_activates() {
+        let keys = Arc::new(AtomicUsize::new(0));
+        let presses = Arc::new(AtomicUsize::new(0));
+        let block = |id: u32| FocusBlock {
+            id,
+            rect: Rect::new(id as usize * 10 - 10, 0, 10, 10),
+        };
+        let mut host = AppHost::new();
+        host.register_app(Box::new(Form {
+            blocks: [block(1), block(2), block(3)],
+            keys: keys.clone(),
+            presses: presses.clone(),
+        }));
+        let mut key = |code, mods| host.dispatch_event(AppEvent::KeyPress { code, mods });
+
+        key(KeyCode::Tab, Modifiers::NONE);
+        key(KeyCode::Enter, Modifiers::NONE);
+        key(KeyCode::Tab, Modifiers::SHIFT);
+        key(KeyCode::Tab, Modifiers::SHIFT);
+        key(KeyCode::Char(' '), Modifiers::NONE);
+        // Block 3 has nothing to activate: the key goes to the app
+        assert_eq!(presses.load(Ordering::Relaxed), 1);
+        assert_eq!(keys.load(Ordering::Relaxed), 1);
+        assert_eq!(host.focus_block_id, 3);
+    }
+
+    #[test_case]
+    fn escape_is_offered_as_cancel_before_the_key() {
+        let keys = Arc::new(AtomicUsize::new(0));
+        let mut host = AppHost::new();
+        host.register_app(Box::new(Probe {
+            levels: 2,
+            keys: keys.clone(),
+            block: FocusBlock {
+                id: 1,
+                rect: Rect::new(0, 0, 10, 10),
+            },
+        }));
+        let escape = || AppEvent::KeyPress {
+            code: KeyCode::Escape,
+            mods: Modifiers::NONE,
+        };
+
+        host.dispatch_event(escape());
+        host.dispatch_event(escape());
+        assert_eq!(keys.load(Ordering::Relaxed), 0);
+        host.dispatch_event(escape());
+        assert_eq!(keys.load(Ordering::Relaxed), 1);
     }
 }
@@ -22,6 +22,11 @@
 //! - Left: dx < 0 and |dy| ≤ |dx|
 //! - Right: dx > 0 and |dy| ≤ dx
 //!
+//! ## Tab Order
+//!
+//! Tab and Shift+Tab ignore geometry: `next_in_order` steps through the
+//! app's `App::focus_order`, wrapping at either end.
+//!
 //! ## Visual Feedback
 //!
 //! `draw_focus_ring` renders a 1-pixel border around the focused
@@ -66,12 +71,47 @@ pub fn move_focus(blocks: &[FocusBlock], current: u32, dir: Arrow) -> u32 {
     blocks[best].id
 }
 
+/// The block after `current` in `order`, or before it when `back`,
+/// wrapping around. From a block not in `order`, the first (or last).
+pub fn next_in_order(order: &[u32], current: u32, back: bool) -> u32 {
+    let Some(&first) = order.first() else {
+        return current;
+    };
+    let last = order[order.len() - 1];
+    match order.iter().position(|&id| id == current) {
+        None if back => last,
+        None => first,
+        Some(0) if back => last,
+        Some(i) if back => order[i - 1],
+        Some(i) => order.get(i + 1).copied().unwrap_or(first),
+    }
+}
+
 pub fn draw_focus_ring(fb: &mut FramebufferWriter, rect: Rect, color: Color) {
     if rect.w == 0 || rect.h == 0 {
         return;
     }
     fb.fill_rect(rect.x, rect.y, rect.w, 1, color);
-    fb.fill_rect(rect.x, rect.y + rect.h - 1, rect.w, 1, color);
+    fb.fill_rect(rect.x, rect.y.saturating_add(rect.h - 1), rect.w, 1, color);
     fb.fill_rect(rect.x, rect.y, 1, rect.h, color);
-    fb.fill_rect(rect.x + rect.w - 1, rect.y, 1, rect.h, color);
+    fb.fill_rect(rect.x.saturating_add(rect.w - 1), rect.y, 1, rect.h, color);
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn tab_order_wraps_both_ways() {
+        let order = [5, 2, 9];
+        assert_eq!(next_in_order(&order, 5, false), 2);
+        assert_eq!(next_in_order(&order, 9, false), 5);
+        assert_eq!(next_in_order(&order, 5, true), 9);
+        assert_eq!(next_in_order(&order, 2, true), 5);
+        assert_eq!(next_in_order(&order, 7, false), 5);
+        assert_eq!(next_in_order(&order, 7, true), 9);
+        assert_eq!(next_in_order(&[], 7, false), 7);
+    }
 }
@@ -0,0 +1,214 @@
+//! # Split Layout
+//!
+//! Two apps side by side with a draggable divider between them. The ratio
+//! is kept in permille of the content width and clamped to 20%-80%.
+//!
+//! While the divider is dragged only `ratio` follows the mouse; the apps
+//! stay laid out at `applied` until the button is released. Re-laying out
+//! on every mouse move would rebuild the terminal each time, so during the
+//! drag the host paints the divider at its new place and covers the strip
+//! between the old and new positions instead.
+
+use crate::ui_provider::shape::Rect;
+
+pub const DIVIDER_WIDTH: usize = 4;
+/// Extra pixels either side of the bar that still grab it.
+const GRAB_SLACK: usize = 3;
+const MIN_RATIO: u32 = 200;
+const MAX_RATIO: u32 = 800;
+pub const DEFAULT_RATIO: u32 = 500;
+
+/// App-store key of the left app under which the ratio is remembered.
+pub(crate) const RATIO_KEY: &str = "split_ratio";
+
+#[derive(Clone, Copy, Debug)]
+pub struct Split {
+    pub left: usize,
+    pub right: usize,
+    /// Where the divider is drawn, in permille.
+    ratio: u32,
+    /// What the apps are laid out at; equal to `ratio` outside a drag.
+    applied: u32,
+    /// Offset of the grab point from the divider's left edge.
+    drag_offset: Option<usize>,
+}
+
+impl Split {
+    pub fn new(left: usize, right: usize, ratio: u32) -> Self {
+        let ratio = ratio.clamp(MIN_RATIO, MAX_RATIO);
+        Self {
+            left,
+            right,
+            ratio,
+            applied: ratio,
+            drag_offset: None,
+        }
+    }
+
+    pub fn ratio(&self) -> u32 {
+        self.ratio
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag_offset.is_some()
+    }
+
+    fn divider_x(content: Rect, ratio: u32) -> usize {
+        let usable = content.w.saturating_sub(DIVIDER_WIDTH);
+        content.x + usable * ratio as usize / 1000
+    }
+
+    /// Left app, right app, at the applied ratio.
+    pub fn app_rects(&self, content: Rect) -> (Rect, Rect) {
+        let div = Self::divider_x(content, self.applied);
+        let left = Rect::new(content.x, content.y, div - content.x, content.h);
+        let right_x = div + DIVIDER_WIDTH;
+        let right = Rect::new(
+            right_x,
+            content.y,
+            (content.x + content.w).saturating_sub(right_x),
+            content.h,
+        );
+        (left, right)
+    }
+
+    /// The divider bar at the live ratio.
+    pub fn divider_rect(&self, content: Rect) -> Rect {
+        let x = Self::divider_x(content, self.ratio);
+        Rect::new(x, content.y, DIVIDER_WIDTH, content.h)
+    }
+
+    /// Area to repaint while dragging: from the applied divider position to
+    /// the live one, bar widths included. Empty outside a drag.
+    pub fn exposed_strip(&self, content: Rect) -> Option<Rect> {
+        if self.ratio == self.applied {
+            return None;
+        }
+        let a = Self::divider_x(content, self.applied);
+        let b = Self::divider_x(content, self.ratio);
+        let (lo, hi) = (a.min(b), a.max(b) + DIVIDER_WIDTH);
+        Some(Rect::new(lo, content.y, hi - lo, content.h))
+    }
+
+    pub fn hit_divider(&self, content: Rect, x: usize, y: usize) -> bool {
+        let bar = self.divider_rect(content);
+        y >= content.y
+            && y < content.y + content.h
+            && x + GRAB_SLACK >= bar.x
+            && x < bar.x + bar.w + GRAB_SLACK
+    }
+
+    /// Starts a drag if `(x, y)` is on the divider.
+    pub fn begin_drag(&mut self, content: Rect, x: usize, y: usize) -> bool {
+        if !self.hit_divider(content, x, y) {
+            return false;
+        }
+        let bar_x = self.divider_rect(content).x;
+        self.drag_offset = Some(x.saturating_sub(bar_x).min(DIVIDER_WIDTH));
+        true
+    }
+
+    /// Moves the divider under the pointer. Positions are absolute, so
+    /// packets that skip pixels or leave the content area just clamp.
+    pub fn drag_to(&mut self, content: Rect, x: usize) -> bool {
+        let Some(offset) = self.drag_offset else {
+            return false;
+        };
+        let usable = content.w.saturating_sub(DIVIDER_WIDTH).max(1);
+        let bar_x = x.saturating_sub(offset).saturating_sub(content.x);
+        let ratio = u32::try_from(bar_x.saturating_mul(1000) / usable)
+            .unwrap_or(MAX_RATIO)
+            .clamp(MIN_RATIO, MAX_RATIO);
+        let moved = ratio != self.ratio;
+        self.ratio = ratio;
+        moved
+    }
+
+    /// Abandons the drag, putting the divider back where the apps are laid
+    /// out. False if no drag was in progress.
+    pub fn cancel_drag(&mut self) -> bool {
+        if self.drag_offset.take().is_none() {
+            return false;
+        }
+        self.ratio = self.applied;
+        true
+    }
+
+    /// Ends the drag; `true` if the apps need a new layout.
+    pub fn end_drag(&mut self) -> bool {
+        if self.drag_offset.take().is_none() {
+            return false;
+        }
+        let changed = self.applied != self.ratio;
+        self.applied = self.ratio;
+        changed
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: Rect = Rect {
+        x: 0,
+        y: 38,
+        w: 1004,
+        h: 700,
+    };
+
+    #[test_case]
+    fn rects_tile_the_content_area() {
+        let split = Split::new(0, 1, DEFAULT_RATIO);
+        let (left, right) = split.app_rects(CONTENT);
+        let bar = split.divider_rect(CONTENT);
+        assert_eq!((left.x, left.w), (0, 500));
+        assert_eq!((bar.x, bar.w), (500, DIVIDER_WIDTH));
+        assert_eq!((right.x, right.w), (504, 500));
+        assert_eq!(left.h, CONTENT.h);
+    }
+
+    #[test_case]
+    fn drag_moves_divider_but_not_apps_until_release() {
+        let mut split = Split::new(0, 1, DEFAULT_RATIO);
+        assert!(!split.begin_drag(CONTENT, 300, 100));
+        assert!(split.begin_drag(CONTENT, 502, 100));
+
+        assert!(split.drag_to(CONTENT, 702));
+        assert_eq!(split.ratio(), 700);
+        assert_eq!(split.app_rects(CONTENT).0.w, 500);
+        let strip = split.exposed_strip(CONTENT).unwrap();
+        assert_eq!((strip.x, strip.w), (500, 200 + DIVIDER_WIDTH));
+
+        assert!(split.end_drag());
+        assert_eq!(split.app_rects(CONTENT).0.w, 700);
+        assert!(split.exposed_strip(CONTENT).is_none());
+        assert!(!split.end_drag());
+    }
+
+    #[test_case]
+    fn cancelled_drag_puts_the_divider_back() {
+        let mut split = Split::new(0, 1, DEFAULT_RATIO);
+        assert!(split.begin_drag(CONTENT, 502, 100));
+        split.drag_to(CONTENT, 702);
+        assert!(split.cancel_drag());
+        assert_eq!(split.ratio(), DEFAULT_RATIO);
+        assert!(split.exposed_strip(CONTENT).is_none());
+        assert!(!split.end_drag());
+        assert!(!split.cancel_drag());
+    }
+
+    #[test_case]
+    fn drag_clamps_past_the_edges() {
+        let mut split = Split::new(0, 1, DEFAULT_RATIO);
+        assert!(split.begin_drag(CONTENT, 500, 100));
+        // a fast flick straight to the screen edge, and beyond it
+        split.drag_to(CONTENT, 0);
+        assert_eq!(split.ratio(), MIN_RATIO);
+        split.drag_to(CONTENT, 50_000);
+        assert_eq!(split.ratio(), MAX_RATIO);
+        split.end_drag();
+        assert_eq!(Split::new(0, 1, 990).ratio(), MAX_RATIO);
+    }
+}
@@ -0,0 +1,85 @@
+//! # App State
+//!
+//! Small per-app key/value store. `AppHost` owns one store per registered
+//! app, calls `App::save_state` when the app loses focus and
+//! `App::restore_state` when it gets it back. Apps only keep the few
+//! interaction fields they care about (scroll positions, cursors) as
+//! integers or strings.
+
+use alloc::{collections::BTreeMap, string::String};
+
+/// Key the host uses to remember which focus block was active.
+pub(crate) const FOCUS_KEY: &str = "focus";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateValue {
+    Int(i64),
+    Str(String),
+}
+
+#[derive(Default)]
+pub struct AppStore {
+    values: BTreeMap<String, StateValue>,
+}
+
+impl AppStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_int(&mut self, key: &str, value: i64) {
+        self.values.insert(String::from(key), StateValue::Int(value));
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) {
+        self.values
+            .insert(String::from(key), StateValue::Str(String::from(value)));
+    }
+
+    pub fn int(&self, key: &str) -> Option<i64> {
+        match self.values.get(key) {
+            Some(StateValue::Int(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        match self.values.get(key) {
+            Some(StateValue::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Convenience for the common case of restoring an index or offset.
+    pub fn usize(&self, key: &str) -> Option<usize> {
+        self.int(key).and_then(|v| usize::try_from(v).ok())
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn round_trips_typed_values() {
+        let mut store = AppStore::new();
+        store.set_int("scroll", 42);
+        store.set_str("search", "panic");
+
+        assert_eq!(store.usize("scroll"), Some(42));
+        assert_eq!(store.str("search"), Some("panic"));
+        // wrong type or missing key reads as None
+        assert_eq!(store.str("scroll"), None);
+        assert_eq!(store.int("missing"), None);
+    }
+
+    #[test_case]
+    fn negative_ints_are_not_indices() {
+        let mut store = AppStore::new();
+        store.set_int("cursor_y", -1);
+        assert_eq!(store.int("cursor_y"), Some(-1));
+        assert_eq!(store.usize("cursor_y"), None);
+    }
+}
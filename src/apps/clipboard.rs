@@ -0,0 +1,259 @@
+//! # Clipboard
+//!
+//! The kernel clipboard keeps the last `CLIP_LEN` copies, newest last, so
+//! an address copied from `vmmap` survives the next copy. A copy equal to
+//! the newest entry is not recorded again, and each entry is cut to
+//! `ENTRY_MAX` bytes. Plain paste takes the newest entry; the terminal's
+//! picker (Ctrl+Shift+H, or paste twice in quick succession) offers the
+//! rest.
+//!
+//! `ClipHistory` is the plain data structure, testable on its own; the
+//! shared instance behind `copy`/`newest`/`entries` is what apps and the
+//! `clipboard` command use. Under memory pressure `trim` drops the oldest
+//! entries.
+//!
+//! `ClipPicker` is the picker's state and drawing. The app draws it from
+//! `collect_dialog`, in the `Dialog` layer; once it closes the next frame
+//! simply does not have it.
+
+use crate::memory::pressure::Pressure;
+use crate::ui_provider::{
+    render::{Fill, RenderList},
+    shape::Rect,
+    theme::Theme,
+    widgets::{Panel, Widget},
+};
+use alloc::{string::String, vec::Vec};
+use spin::Mutex;
+
+/// Entries kept; the oldest is dropped past this.
+pub const CLIP_LEN: usize = 10;
+/// Bytes kept of one copy.
+pub const ENTRY_MAX: usize = 4096;
+
+#[derive(Default)]
+pub struct ClipHistory {
+    entries: Vec<String>,
+}
+
+impl ClipHistory {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Oldest first.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn newest(&self) -> Option<&str> {
+        self.entries.last().map(String::as_str)
+    }
+
+    /// Records a copy. Empty text and a repeat of the newest entry are not
+    /// recorded; text past `ENTRY_MAX` bytes is cut (see `cut`).
+    pub fn push(&mut self, text: &str) {
+        let text = cut(text);
+        if text.is_empty() || self.newest() == Some(text) {
+            return;
+        }
+        if self.entries.len() == CLIP_LEN {
+            self.entries.remove(0);
+        }
+        self.entries.push(String::from(text));
+    }
+
+    /// Drops the oldest entries down to `keep`. Returns about the bytes
+    /// freed.
+    pub fn trim(&mut self, keep: usize) -> usize {
+        let excess = self.entries.len().saturating_sub(keep);
+        self.entries
+            .drain(..excess)
+            .map(|entry| entry.capacity() + core::mem::size_of::<String>())
+            .sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+static CLIPBOARD: Mutex<ClipHistory> = Mutex::new(ClipHistory::new());
+
+/// The first `ENTRY_MAX` bytes of `text`, ending on a char boundary.
+pub fn cut(text: &str) -> &str {
+    let mut end = text.len().min(ENTRY_MAX);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+pub fn copy(text: &str) {
+    CLIPBOARD.lock().push(text);
+}
+
+pub fn newest() -> Option<String> {
+    CLIPBOARD.lock().newest().map(String::from)
+}
+
+/// Newest first, as the picker and `clipboard` list them.
+pub fn entries() -> Vec<String> {
+    CLIPBOARD.lock().entries().iter().rev().cloned().collect()
+}
+
+pub fn clear() {
+    CLIPBOARD.lock().clear();
+}
+
+/// Registered with `memory::pressure`: keeps half the entries at `High`,
+/// only the newest at `Critical`.
+pub fn trim(level: Pressure) -> usize {
+    let keep = match level {
+        Pressure::Normal => CLIP_LEN,
+        Pressure::High => CLIP_LEN / 2,
+        Pressure::Critical => 1,
+    };
+    CLIPBOARD.lock().trim(keep)
+}
+
+/// The first line of `entry`, cut to `cols` characters with `...` when
+/// anything was left out.
+pub fn summary(entry: &str, cols: usize) -> String {
+    let first = entry.lines().next().unwrap_or("");
+    let cut = first.chars().count() > cols || first.len() < entry.trim_end().len();
+    if !cut {
+        return String::from(first);
+    }
+    let mut out: String = first.chars().take(cols.saturating_sub(3)).collect();
+    out.push_str("...");
+    out
+}
+
+const ROW_HEIGHT: usize = 24;
+const PADDING: usize = 12;
+const TITLE_HEIGHT: usize = 30;
+/// Widest the panel gets, in characters.
+const MAX_COLS: usize = 60;
+
+/// What a key did to the picker.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PickerKey {
+    Moved,
+    Paste(String),
+    Ignored,
+}
+
+/// The open picker: a snapshot of the entries, newest first, and the one
+/// highlighted.
+pub struct ClipPicker {
+    entries: Vec<String>,
+    selected: usize,
+}
+
+impl ClipPicker {
+    /// `None` with nothing to pick from.
+    pub fn open() -> Option<Self> {
+        let entries = entries();
+        (!entries.is_empty()).then_some(Self {
+            entries,
+            selected: 0,
+        })
+    }
+
+    /// Up and Down move the highlight, Enter picks it. Escape is left to
+    /// the app's `on_cancel`.
+    pub fn key(&mut self, code: crate::app::KeyCode) -> PickerKey {
+        use crate::app::{Arrow, KeyCode};
+
+        match code {
+            KeyCode::Arrow(Arrow::Up) if self.selected > 0 => {
+                self.selected -= 1;
+                PickerKey::Moved
+            }
+            KeyCode::Arrow(Arrow::Down) if self.selected + 1 < self.entries.len() => {
+                self.selected += 1;
+                PickerKey::Moved
+            }
+            KeyCode::Enter => PickerKey::Paste(self.entries[self.selected].clone()),
+            _ => PickerKey::Ignored,
+        }
+    }
+
+    /// The panel, centred in `within`: a title, then a row per entry.
+    pub fn collect_render(&self, within: Rect, theme: &Theme, out: &mut RenderList) {
+        let width = (MAX_COLS * 10 + PADDING * 2).min(within.w);
+        let height = (TITLE_HEIGHT + self.entries.len() * ROW_HEIGHT + PADDING * 2).min(within.h);
+        let panel = Rect::new(
+            within.x + (within.w - width) / 2,
+            within.y + (within.h - height) / 2,
+            width,
+            height,
+        );
+        let cols = width.saturating_sub(PADDING * 2) / 10;
+
+        Panel::new(panel)
+            .with_shadow(true)
+            .with_fill(Fill::VerticalGradient(theme.surface, theme.background))
+            .collect_render(theme, false, out);
+        out.text(
+            "Clipboard (Up/Down, Enter pastes, Esc closes)",
+            panel.x + PADDING,
+            panel.y + PADDING,
+            theme.accent,
+        );
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let y = panel.y + PADDING + TITLE_HEIGHT + idx * ROW_HEIGHT;
+            if y + ROW_HEIGHT > panel.bottom() {
+                break;
+            }
+            let color = if idx == self.selected {
+                let row = Rect::new(panel.x + PADDING / 2, y - 2, width - PADDING, ROW_HEIGHT);
+                out.fill_rect(row, theme.accent);
+                theme.on_accent
+            } else {
+                theme.text
+            };
+            out.text(summary(entry, cols), panel.x + PADDING, y, color);
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test_case]
+    fn history_dedups_caps_and_keeps_order() {
+        let mut clips = ClipHistory::new();
+        clips.push("0x1000");
+        clips.push("0x1000");
+        clips.push("");
+        clips.push("0x2000");
+        assert_eq!(clips.entries(), ["0x1000", "0x2000"]);
+        // Only consecutive repeats are dropped
+        clips.push("0x1000");
+        assert_eq!(clips.entries().len(), 3);
+
+        for n in 0..CLIP_LEN {
+            clips.push(&format!("entry {}", n));
+        }
+        assert_eq!(clips.entries().len(), CLIP_LEN);
+        assert_eq!(clips.entries()[0], "entry 0");
+        assert_eq!(clips.newest(), Some("entry 9"));
+
+        let long = "é".repeat(ENTRY_MAX);
+        clips.push(&long);
+        assert_eq!(clips.newest().map(str::len), Some(ENTRY_MAX));
+
+        assert!(clips.trim(2) > 0);
+        assert_eq!(clips.entries()[0], "entry 9");
+        assert_eq!(summary("first\nsecond", 20), "first...");
+        assert_eq!(summary("0123456789", 8), "01234...");
+    }
+}
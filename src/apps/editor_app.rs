@@ -1,4 +1,4 @@
-use crate::app::{App, AppEvent, Arrow, FocusBlock};
+use crate::app::{App, AppEvent, AppStore, Arrow, FocusBlock, KeyCode};
 
 use crate::ui_provider::{
     color::Color,
@@ -703,60 +703,46 @@ impl EditorApp {
 impl App for EditorApp {
     fn init(&mut self) {}
 
-    fn on_event(&mut self, event: AppEvent) -> bool {
-        match event {
-            AppEvent::KeyPress {
-                ch,
-                ctrl,
-                shift,
-                arrow,
-                ..
-            } => {
-                if let Some(dir) = arrow {
-                    match dir {
-                        Arrow::Left => self.move_left(),
-                        Arrow::Right => self.move_right(),
-                        Arrow::Up => self.move_up(),
-                        Arrow::Down => self.move_down(),
-                    }
-                    return true;
-                }
-
-                if ctrl && ch == 'l' {
-                    self.clear_output();
-                    return true;
-                }
-
-                if ch == '\n' {
-                    if shift {
-                        self.run_program();
-                    } else {
-                        self.insert_newline();
-                    }
-                    return true;
-                }
+    fn save_state(&self, store: &mut AppStore) {
+        store.set_int("cursor_x", self.cursor_x as i64);
+        store.set_int("cursor_y", self.cursor_y as i64);
+        store.set_int("scroll_x", self.scroll_x as i64);
+        store.set_int("scroll_y", self.scroll_y as i64);
+    }
 
-                if ch == '\x08' {
-                    self.backspace();
-                    return true;
-                }
+    fn restore_state(&mut self, store: &AppStore) {
+        self.cursor_x = store.usize("cursor_x").unwrap_or(self.cursor_x);
+        self.cursor_y = store.usize("cursor_y").unwrap_or(self.cursor_y);
+        self.scroll_x = store.usize("scroll_x").unwrap_or(self.scroll_x);
+        self.scroll_y = store.usize("scroll_y").unwrap_or(self.scroll_y);
+        self.clamp_cursor();
+        self.full_redraw = true;
+    }
 
-                if ch == '\t' {
-                    for _ in 0..4 {
-                        self.insert_char(' ');
+    fn on_event(&mut self, event: AppEvent) -> bool {
+        match event {
+            AppEvent::KeyPress { code, mods } => {
+                match code {
+                    KeyCode::Arrow(Arrow::Left) => self.move_left(),
+                    KeyCode::Arrow(Arrow::Right) => self.move_right(),
+                    KeyCode::Arrow(Arrow::Up) => self.move_up(),
+                    KeyCode::Arrow(Arrow::Down) => self.move_down(),
+                    KeyCode::Char('l') if mods.ctrl() => self.clear_output(),
+                    KeyCode::Enter if mods.shift() => self.run_program(),
+                    KeyCode::Enter => self.insert_newline(),
+                    KeyCode::Backspace => self.backspace(),
+                    KeyCode::Tab => {
+                        for _ in 0..4 {
+                            self.insert_char(' ');
+                        }
                     }
-                    return true;
-                }
-
-                if !ctrl && !ch.is_control() {
-                    self.insert_char(ch);
-                    return true;
+                    KeyCode::Char(ch) if !mods.ctrl() && !ch.is_control() => self.insert_char(ch),
+                    _ => return false,
                 }
-
-                false
+                true
             }
             AppEvent::Tick => false,
-            AppEvent::Mouse(_) => true,
+            AppEvent::Mouse { .. } => true,
         }
     }
 
@@ -0,0 +1,191 @@
+use crate::app::{App, AppEvent, FocusBlock};
+use crate::ui_provider::{
+    render::{RenderList, TextStyle},
+    shape::Rect,
+    theme::Theme,
+    widgets::{Button, TextInput, Widget, WidgetEvent},
+};
+use alloc::{format, string::String, vec, vec::Vec};
+
+const CHAR_HEIGHT: usize = 20;
+const MARGIN: usize = 20;
+const INPUT_WIDTH: usize = 320;
+const INPUT_HEIGHT: usize = 28;
+const ROW_GAP: usize = 16;
+const BUTTON_WIDTH: usize = 100;
+
+const NAME_ID: u32 = 1;
+const GREET_ID: u32 = 2;
+const CLEAR_ID: u32 = 3;
+
+/// A text field and two buttons, for trying Tab order and activation.
+///
+/// Clear sits left of Greet, but Tab goes name, Greet, Clear: the order
+/// comes from `focus_order`, not from where the blocks are.
+pub struct FormDemoApp {
+    blocks: [FocusBlock; 3],
+    bounds: Rect,
+    focused: u32,
+    name: TextInput,
+    greet: Button,
+    clear: Button,
+    status: String,
+}
+
+impl FormDemoApp {
+    pub fn new(_width: usize, _height: usize) -> Self {
+        let block = |id| FocusBlock {
+            id,
+            rect: Rect::new(0, 0, 0, 0),
+        };
+        Self {
+            blocks: [block(NAME_ID), block(CLEAR_ID), block(GREET_ID)],
+            bounds: Rect::new(0, 0, 0, 0),
+            focused: NAME_ID,
+            name: TextInput::new("your name").with_max_len(24),
+            greet: Button::new("Greet"),
+            clear: Button::new("Clear"),
+            status: String::from("Tab/Shift+Tab to move, Enter or Space to press"),
+        }
+    }
+
+    fn greet(&mut self) {
+        self.status = if self.name.value().is_empty() {
+            String::from("Hello, whoever you are")
+        } else {
+            format!("Hello, {}", self.name.value())
+        };
+    }
+
+    fn clear(&mut self) {
+        self.name.set_value("");
+        self.status = String::from("Cleared");
+    }
+}
+
+impl App for FormDemoApp {
+    fn on_cancel(&mut self) -> bool {
+        self.focused == NAME_ID && self.name.clear_selection()
+    }
+
+    fn on_event(&mut self, event: AppEvent) -> bool {
+        if self.focused != NAME_ID {
+            return false;
+        }
+        let result = self.name.handle_event(&event);
+        if result == WidgetEvent::Submit {
+            self.greet();
+        }
+        result.needs_redraw()
+    }
+
+    fn activate(&mut self, block_id: u32) -> bool {
+        match block_id {
+            GREET_ID if self.greet.activate() == WidgetEvent::Activate => self.greet(),
+            CLEAR_ID if self.clear.activate() == WidgetEvent::Activate => self.clear(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn layout(&mut self, bounds: Rect) {
+        self.bounds = bounds;
+
+        let x = bounds.x + MARGIN;
+        let input_w = INPUT_WIDTH.min(bounds.w.saturating_sub(MARGIN * 2));
+        let mut y = bounds.y + MARGIN + CHAR_HEIGHT + ROW_GAP;
+
+        self.name.set_rect(Rect::new(x, y, input_w, INPUT_HEIGHT));
+        y += INPUT_HEIGHT + ROW_GAP;
+        self.clear
+            .set_rect(Rect::new(x, y, BUTTON_WIDTH, INPUT_HEIGHT));
+        self.greet.set_rect(Rect::new(
+            x + BUTTON_WIDTH + ROW_GAP,
+            y,
+            BUTTON_WIDTH,
+            INPUT_HEIGHT,
+        ));
+
+        self.blocks[0].rect = self.name.rect();
+        self.blocks[1].rect = self.clear.rect();
+        self.blocks[2].rect = self.greet.rect();
+    }
+
+    fn collect_render(&mut self, theme: &Theme, out: &mut RenderList) {
+        out.fill_rect(self.bounds, theme.surface);
+
+        let x = self.bounds.x + MARGIN;
+        out.styled_text(
+            "Form",
+            x,
+            self.bounds.y + MARGIN,
+            TextStyle::new(theme.accent),
+        );
+
+        self.name
+            .collect_render(theme, self.focused == NAME_ID, out);
+        self.clear
+            .collect_render(theme, self.focused == CLEAR_ID, out);
+        self.greet
+            .collect_render(theme, self.focused == GREET_ID, out);
+
+        let status_y = self.greet.rect().y + INPUT_HEIGHT + ROW_GAP;
+        out.text(self.status.as_str(), x, status_y, theme.muted);
+    }
+
+    fn focus_changed(&mut self, block_id: u32) {
+        self.focused = block_id;
+    }
+
+    fn focus_order(&mut self) -> Vec<u32> {
+        vec![NAME_ID, GREET_ID, CLEAR_ID]
+    }
+
+    fn focus_blocks(&mut self) -> &mut [FocusBlock] {
+        &mut self.blocks
+    }
+
+    fn bounds(&self) -> Rect {
+        self.bounds
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{KeyCode, Modifiers};
+
+    fn key(app: &mut FormDemoApp, code: KeyCode) -> bool {
+        app.on_event(AppEvent::KeyPress {
+            code,
+            mods: Modifiers::NONE,
+        })
+    }
+
+    #[test_case]
+    fn buttons_act_and_the_field_types() {
+        let mut app = FormDemoApp::new(600, 400);
+        app.layout(Rect::new(0, 0, 600, 400));
+        // Clear is left of Greet on screen, after it in the order
+        assert!(app.clear.rect().x < app.greet.rect().x);
+        assert_eq!(app.focus_order(), [NAME_ID, GREET_ID, CLEAR_ID]);
+
+        for ch in "ada".chars() {
+            assert!(key(&mut app, KeyCode::Char(ch)));
+        }
+        assert!(!app.activate(NAME_ID));
+        assert!(app.activate(GREET_ID));
+        assert_eq!(app.status, "Hello, ada");
+
+        app.focus_changed(CLEAR_ID);
+        assert!(!key(&mut app, KeyCode::Char('x')));
+        assert!(app.activate(CLEAR_ID));
+        assert_eq!(app.name.value(), "");
+
+        app.focus_changed(NAME_ID);
+        assert!(key(&mut app, KeyCode::Enter));
+        assert_eq!(app.status, "Hello, whoever you are");
+    }
+}
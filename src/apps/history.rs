@@ -0,0 +1,227 @@
+//! # Command History
+//!
+//! Lines the terminal has executed, oldest first, and the state of a
+//! Ctrl+R reverse incremental search through them.
+//!
+//! The search is kept apart from the terminal so that matching and
+//! stepping can be tested over a plain list of entries: a search only
+//! stores the query and the index of the entry it currently shows.
+
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+/// Entries kept; the oldest is dropped past this.
+pub const HISTORY_LEN: usize = 200;
+
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Oldest first.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Records an executed line. Blank lines and repeats of the newest
+    /// entry are not recorded.
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.entries.len() == HISTORY_LEN {
+            self.entries.remove(0);
+        }
+        self.entries.push(String::from(line));
+    }
+
+    /// Drops the oldest entries down to `keep`. Returns about the bytes
+    /// freed.
+    pub fn trim(&mut self, keep: usize) -> usize {
+        let excess = self.entries.len().saturating_sub(keep);
+        let freed = self
+            .entries
+            .drain(..excess)
+            .map(|entry| entry.capacity() + core::mem::size_of::<String>())
+            .sum();
+        self.entries.shrink_to_fit();
+        freed
+    }
+}
+
+#[derive(Default)]
+pub struct ReverseSearch {
+    query: String,
+    /// Index into the history of the entry shown.
+    found: Option<usize>,
+    /// The last change to the query or step found nothing new.
+    failed: bool,
+}
+
+impl ReverseSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// The entry shown, if any.
+    pub fn candidate<'h>(&self, entries: &'h [String]) -> Option<&'h str> {
+        self.found
+            .and_then(|idx| entries.get(idx))
+            .map(String::as_str)
+    }
+
+    /// Byte range of the query inside the candidate, for highlighting.
+    pub fn match_range(&self, entries: &[String]) -> Option<Range<usize>> {
+        if self.query.is_empty() {
+            return None;
+        }
+        let candidate = self.candidate(entries)?;
+        let start = candidate.find(self.query.as_str())?;
+        Some(start..start + self.query.len())
+    }
+
+    /// Extends the query. The entry shown is kept while it still matches,
+    /// otherwise the search moves on to older entries.
+    pub fn push(&mut self, ch: char, entries: &[String]) {
+        self.query.push(ch);
+        let from = self.found.map_or(entries.len(), |idx| idx + 1);
+        self.search(entries, from);
+    }
+
+    /// Shortens the query and searches again from the newest entry.
+    pub fn pop(&mut self, entries: &[String]) {
+        if self.query.pop().is_none() {
+            return;
+        }
+        self.found = None;
+        self.search(entries, entries.len());
+    }
+
+    /// Repeated Ctrl+R: the next older entry that matches.
+    pub fn step_older(&mut self, entries: &[String]) {
+        if self.query.is_empty() {
+            return;
+        }
+        let from = self.found.unwrap_or(entries.len());
+        self.search(entries, from);
+    }
+
+    /// Shows the newest match below `before`; keeps the current entry and
+    /// marks the search failed if there is none.
+    fn search(&mut self, entries: &[String], before: usize) {
+        if self.query.is_empty() {
+            self.found = None;
+            self.failed = false;
+            return;
+        }
+        let before = before.min(entries.len());
+        match entries[..before]
+            .iter()
+            .rposition(|e| e.contains(self.query.as_str()))
+        {
+            Some(idx) => {
+                self.found = Some(idx);
+                self.failed = false;
+            }
+            None => self.failed = true,
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(lines: &[&str]) -> History {
+        let mut history = History::new();
+        for line in lines {
+            history.push(line);
+        }
+        history
+    }
+
+    fn typed(query: &str, entries: &[String]) -> ReverseSearch {
+        let mut search = ReverseSearch::new();
+        for ch in query.chars() {
+            search.push(ch, entries);
+        }
+        search
+    }
+
+    #[test_case]
+    fn push_skips_blank_lines_and_repeats() {
+        let history = history(&["ls", "ls", "  ", "cat a", "ls"]);
+        assert_eq!(history.entries(), ["ls", "cat a", "ls"]);
+    }
+
+    #[test_case]
+    fn typing_narrows_to_the_newest_match() {
+        let history = history(&["echo one", "cat notes", "echo two", "ls"]);
+        let entries = history.entries();
+
+        let mut search = typed("e", entries);
+        assert_eq!(search.candidate(entries), Some("echo two"));
+        // "ec" still matches the entry shown, so it stays
+        search.push('c', entries);
+        assert_eq!(search.candidate(entries), Some("echo two"));
+        assert_eq!(search.match_range(entries), Some(0..2));
+
+        let search = typed("not", entries);
+        assert_eq!(search.candidate(entries), Some("cat notes"));
+        assert_eq!(search.match_range(entries), Some(4..7));
+        assert!(!search.failed());
+    }
+
+    #[test_case]
+    fn repeated_ctrl_r_steps_to_older_matches() {
+        let history = history(&["echo one", "cat notes", "echo two", "ls"]);
+        let entries = history.entries();
+
+        let mut search = typed("echo", entries);
+        assert_eq!(search.candidate(entries), Some("echo two"));
+        search.step_older(entries);
+        assert_eq!(search.candidate(entries), Some("echo one"));
+        // nothing older: the last match stays, flagged as failed
+        search.step_older(entries);
+        assert_eq!(search.candidate(entries), Some("echo one"));
+        assert!(search.failed());
+    }
+
+    #[test_case]
+    fn no_match_and_backspace() {
+        let history = history(&["echo one", "cat notes"]);
+        let entries = history.entries();
+
+        let mut search = typed("cat", entries);
+        search.push('z', entries);
+        assert!(search.failed());
+        assert_eq!(search.candidate(entries), Some("cat notes"));
+
+        search.pop(entries);
+        assert_eq!(search.query(), "cat");
+        assert!(!search.failed());
+        assert_eq!(search.candidate(entries), Some("cat notes"));
+
+        let mut search = typed("x", entries);
+        assert_eq!(search.candidate(entries), None);
+        assert_eq!(search.match_range(entries), None);
+        search.pop(entries);
+        assert_eq!(search.query(), "");
+        assert!(!search.failed());
+    }
+}
@@ -0,0 +1,404 @@
+//! # Background Jobs
+//!
+//! A command line ending in `&` runs as a job: the executor hands the
+//! command to a kernel task and gives the prompt straight back. Whatever
+//! the command would have printed goes into the job's buffer instead of
+//! the terminal; `fg` shows it a page at a time and forgets the job once
+//! it has finished and the last page has been read.
+//!
+//! A job's body is stepped by its task, one call per scheduler round.
+//! Most shell commands are a single step (the whole command runs in one
+//! frame, just not in the one that typed it); a body that returns
+//! `Progress::More` gets further steps and keeps the frames in between.
+//! `test` runs one test per step and `bench alloc` one round.
+//!
+//! `kill %<id>` sends the task `Signal::Terminate`, which the job sees at
+//! the start of its next step. When a job ends, a notice is queued for
+//! the terminal to print before its next prompt, shown in a toast, and
+//! logged.
+//!
+//! Jobs cannot take keyboard input, and a long single-step command would
+//! hold up every frame for as long as it runs, so the command table
+//! marks which commands can run as one.
+
+use crate::devices::drivers::hpet;
+use crate::kcore::task::{self, Signal, TaskScheduler, TaskState};
+use crate::ui_provider::toast;
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use spin::Mutex;
+
+/// Lines `fg` shows at a time.
+pub const FG_PAGE_LINES: usize = 40;
+/// Output kept per job; what comes after is dropped.
+const OUTPUT_MAX: usize = 64 * 1024;
+/// Finished jobs kept for `fg`; the oldest goes past this.
+const FINISHED_MAX: usize = 16;
+
+pub type JobId = u32;
+
+/// What a step of a job's body reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// Not finished; step again next round.
+    More,
+    /// Finished; false if it failed.
+    Done(bool),
+}
+
+/// Steps a job, appending its output to the buffer it is given.
+pub type JobBody = Box<dyn FnMut(&mut String) -> Progress + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Done,
+    Failed,
+    Killed,
+}
+
+impl JobState {
+    pub fn name(self) -> &'static str {
+        match self {
+            JobState::Running => "running",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+            JobState::Killed => "killed",
+        }
+    }
+}
+
+struct Job {
+    id: JobId,
+    task: u64,
+    command: String,
+    state: JobState,
+    started_ms: u64,
+    ended_ms: Option<u64>,
+    output: String,
+    truncated: bool,
+}
+
+impl Job {
+    fn elapsed_ms(&self, now: u64) -> u64 {
+        self.ended_ms.unwrap_or(now).saturating_sub(self.started_ms)
+    }
+}
+
+struct JobTable {
+    next_id: JobId,
+    jobs: Vec<Job>,
+    notices: Vec<String>,
+}
+
+static JOBS: Mutex<JobTable> = Mutex::new(JobTable {
+    next_id: 1,
+    jobs: Vec::new(),
+    notices: Vec::new(),
+});
+
+/// Starts `body` as job for `command`. Returns the job and task ids.
+pub fn start(command: &str, body: JobBody) -> (JobId, u64) {
+    crate::require_stage!(crate::kcore::kernel::init::Stage::Devices);
+    start_in(&mut task::SCHEDULER.lock(), command, body)
+}
+
+fn start_in(sched: &mut TaskScheduler, command: &str, mut body: JobBody) -> (JobId, u64) {
+    let id = {
+        let mut table = JOBS.lock();
+        let id = table.next_id;
+        table.next_id += 1;
+        table.jobs.push(Job {
+            id,
+            task: 0,
+            command: String::from(command),
+            state: JobState::Running,
+            started_ms: hpet::monotonic_ms(),
+            ended_ms: None,
+            output: String::new(),
+            truncated: false,
+        });
+        id
+    };
+
+    let name = format!("job {}", id);
+    let task = sched.spawn(
+        &name,
+        Box::new(move |ctx| {
+            if ctx.take_signal(Signal::Terminate) {
+                finish(id, JobState::Killed);
+                return TaskState::Completed;
+            }
+            let mut out = String::new();
+            let progress = body(&mut out);
+            append(id, &out);
+            match progress {
+                Progress::More => TaskState::Yield,
+                Progress::Done(ok) => {
+                    finish(id, if ok { JobState::Done } else { JobState::Failed });
+                    TaskState::Completed
+                }
+            }
+        }),
+    );
+    if let Some(job) = JOBS.lock().jobs.iter_mut().find(|j| j.id == id) {
+        job.task = task;
+    }
+    (id, task)
+}
+
+fn append(id: JobId, text: &str) {
+    let mut table = JOBS.lock();
+    let Some(job) = table.jobs.iter_mut().find(|j| j.id == id) else {
+        return;
+    };
+    let room = OUTPUT_MAX.saturating_sub(job.output.len());
+    if text.len() <= room {
+        job.output.push_str(text);
+        return;
+    }
+    let mut cut = room;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    job.output.push_str(&text[..cut]);
+    job.truncated = true;
+}
+
+fn finish(id: JobId, state: JobState) {
+    let now = hpet::monotonic_ms();
+    let mut table = JOBS.lock();
+    let Some(job) = table.jobs.iter_mut().find(|j| j.id == id) else {
+        return;
+    };
+    job.state = state;
+    job.ended_ms = Some(now);
+    let notice = format!(
+        "[{}] {}  {} ({})",
+        id,
+        state.name(),
+        job.command,
+        duration(job.elapsed_ms(now))
+    );
+    crate::log_info!("jobs: {}", notice);
+    table.notices.push(notice.clone());
+
+    let finished = table
+        .jobs
+        .iter()
+        .filter(|j| j.state != JobState::Running)
+        .count();
+    if finished > FINISHED_MAX {
+        if let Some(idx) = table.jobs.iter().position(|j| j.state != JobState::Running) {
+            table.jobs.remove(idx);
+        }
+    }
+    drop(table);
+    toast::notify(&notice, toast::DEFAULT_TICKS);
+}
+
+/// Jobs that ended since the last call, for the terminal to print.
+pub fn take_notices() -> Vec<String> {
+    core::mem::take(&mut JOBS.lock().notices)
+}
+
+/// Sends the job's task `Signal::Terminate`.
+pub fn kill(id: JobId) -> Result<(), String> {
+    kill_in(&mut task::SCHEDULER.lock(), id)
+}
+
+fn kill_in(sched: &mut TaskScheduler, id: JobId) -> Result<(), String> {
+    let task = match JOBS.lock().jobs.iter().find(|j| j.id == id) {
+        None => return Err(format!("kill: no job %{}", id)),
+        Some(job) if job.state != JobState::Running => {
+            return Err(format!(
+                "kill: job %{} has already {}",
+                id,
+                job.state.name()
+            ))
+        }
+        Some(job) => job.task,
+    };
+    if sched.send_signal(task, Signal::Terminate) {
+        Ok(())
+    } else {
+        Err(format!("kill: job %{} has no task", id))
+    }
+}
+
+/// The `jobs` table.
+pub fn report() -> String {
+    let now = hpet::monotonic_ms();
+    let table = JOBS.lock();
+    if table.jobs.is_empty() {
+        return String::from("no jobs");
+    }
+    let mut out = String::from(" JOB  TASK  STATE        TIME  COMMAND");
+    for job in &table.jobs {
+        out.push_str(&format!(
+            "\n{:>4}  {:>4}  {:<7}  {:>8}  {}",
+            job.id,
+            job.task,
+            job.state.name(),
+            duration(job.elapsed_ms(now)),
+            job.command
+        ));
+    }
+    out
+}
+
+/// Page `page` (from 1) of the job's output. Reading the last page of a
+/// finished job removes it.
+pub fn fg(id: JobId, page: usize) -> Result<String, String> {
+    let mut table = JOBS.lock();
+    let Some(idx) = table.jobs.iter().position(|j| j.id == id) else {
+        return Err(format!("fg: no job %{}", id));
+    };
+    let job = &table.jobs[idx];
+    let lines: Vec<&str> = job.output.lines().collect();
+    let pages = lines.len().div_ceil(FG_PAGE_LINES).max(1);
+    if page == 0 || page > pages {
+        return Err(format!("fg: job %{} has {} page(s)", id, pages));
+    }
+
+    let start = (page - 1) * FG_PAGE_LINES;
+    let end = (start + FG_PAGE_LINES).min(lines.len());
+    let mut out = lines[start..end].join("\n");
+    if job.truncated && page == pages {
+        out.push_str(&format!("\n[output cut at {} KiB]", OUTPUT_MAX / 1024));
+    }
+    if page < pages {
+        out.push_str(&format!(
+            "\n-- page {}/{}: fg {} {} for more --",
+            page,
+            pages,
+            id,
+            page + 1
+        ));
+    } else if job.state == JobState::Running {
+        out.push_str(&format!("\n-- job {} still running --", id));
+    } else {
+        table.jobs.remove(idx);
+    }
+    Ok(out)
+}
+
+fn duration(ms: u64) -> String {
+    format!("{}.{} s", ms / 1000, ms % 1000 / 100)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd_executor::{CommandExecutor, CommandResult};
+
+    /// Writes one numbered line per step, `steps` of them.
+    fn slow(steps: usize) -> JobBody {
+        let mut n = 0;
+        Box::new(move |out| {
+            n += 1;
+            out.push_str(&format!("line {}\n", n));
+            if n == steps {
+                Progress::Done(true)
+            } else {
+                Progress::More
+            }
+        })
+    }
+
+    fn state(id: JobId) -> Option<JobState> {
+        JOBS.lock()
+            .jobs
+            .iter()
+            .find(|j| j.id == id)
+            .map(|j| j.state)
+    }
+
+    #[test_case]
+    fn a_slow_job_runs_alongside_commands_and_fg_returns_it_in_order() {
+        let mut sched = TaskScheduler::new();
+        let steps = FG_PAGE_LINES + 5;
+        let (id, _) = start_in(&mut sched, "slow", slow(steps));
+
+        for round in 0..steps {
+            assert_eq!(state(id), Some(JobState::Running));
+            // The prompt is free between steps
+            match CommandExecutor::execute("echo still here") {
+                CommandResult::Output(out) => assert_eq!(out.trim(), "still here"),
+                _ => panic!("echo failed in round {}", round),
+            }
+            sched.run_round();
+        }
+        assert_eq!(state(id), Some(JobState::Done));
+        assert!(take_notices()
+            .iter()
+            .any(|n| n.starts_with(&format!("[{}] done  slow", id))));
+        assert!(toast::take_all()
+            .iter()
+            .any(|n| n.starts_with(&format!("[{}] done  slow", id))));
+
+        let first = fg(id, 1).unwrap();
+        assert!(first.starts_with("line 1\nline 2\n"));
+        assert!(first.ends_with(&format!("fg {} 2 for more --", id)));
+        let second = fg(id, 2).unwrap();
+        let expected: Vec<String> = (FG_PAGE_LINES + 1..=steps)
+            .map(|n| format!("line {}", n))
+            .collect();
+        assert_eq!(second, expected.join("\n"));
+        assert!(fg(id, 1).is_err());
+    }
+
+    #[test_case]
+    fn only_quick_known_commands_start_as_jobs() {
+        for line in [
+            "bench render 1 &",
+            "vm_run loop: jmp loop &",
+            "test_memory &",
+        ] {
+            match CommandExecutor::execute(line) {
+                CommandResult::Error(err) => assert!(err.ends_with("cannot run in the background")),
+                _ => panic!("{} started as a job", line),
+            }
+        }
+        match CommandExecutor::execute("no_such_command &") {
+            CommandResult::Error(err) => {
+                assert!(err.starts_with("Unknown command: no_such_command"))
+            }
+            _ => panic!("an unknown command ran"),
+        }
+    }
+
+    #[test_case]
+    fn a_benchmark_job_runs_one_round_per_step() {
+        let mut sched = TaskScheduler::new();
+        let Ok(body) = CommandExecutor::job_body("bench alloc 3") else {
+            panic!("bench alloc did not start");
+        };
+        let (id, _) = start_in(&mut sched, "bench alloc 3", body);
+        for _ in 0..2 {
+            sched.run_round();
+            assert_eq!(state(id), Some(JobState::Running));
+        }
+        sched.run_round();
+        assert_eq!(state(id), Some(JobState::Done));
+        assert!(fg(id, 1).unwrap().starts_with("alloc: 144 allocations in"));
+        take_notices();
+        toast::take_all();
+    }
+
+    #[test_case]
+    fn kill_ends_a_job_at_its_next_step() {
+        let mut sched = TaskScheduler::new();
+        let (id, _) = start_in(&mut sched, "forever", slow(usize::MAX));
+        sched.run_round();
+        assert_eq!(kill_in(&mut sched, id), Ok(()));
+        sched.run_round();
+        assert_eq!(state(id), Some(JobState::Killed));
+        assert!(sched.info().is_empty());
+        assert!(kill_in(&mut sched, id).is_err());
+        assert_eq!(fg(id, 1).unwrap(), "line 1");
+        take_notices();
+        toast::take_all();
+    }
+}
@@ -0,0 +1,245 @@
+//! # Line Editor
+//!
+//! Logical input line of the terminal: the text typed after the prompt and
+//! a cursor position in chars. `TerminalApp` applies key presses here and
+//! redraws the terminal from the result, so what is shown always matches
+//! what gets executed.
+//!
+//! Word-wise movement treats space, `/` and `=` as delimiters, which makes
+//! Ctrl+Left/Right stop at path components and `key=value` halves.
+
+use alloc::{string::String, vec::Vec};
+
+pub fn is_word_delim(ch: char) -> bool {
+    matches!(ch, ' ' | '/' | '=')
+}
+
+/// Char index Ctrl+Left moves to: the start of the word before `cursor`,
+/// skipping any delimiters directly in front of it.
+pub fn prev_word_start(line: &str, cursor: usize) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let mut pos = cursor.min(chars.len());
+    while pos > 0 && is_word_delim(chars[pos - 1]) {
+        pos -= 1;
+    }
+    while pos > 0 && !is_word_delim(chars[pos - 1]) {
+        pos -= 1;
+    }
+    pos
+}
+
+/// Char index Ctrl+Right moves to: the end of the word at or after `cursor`.
+pub fn next_word_end(line: &str, cursor: usize) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let mut pos = cursor.min(chars.len());
+    while pos < chars.len() && is_word_delim(chars[pos]) {
+        pos += 1;
+    }
+    while pos < chars.len() && !is_word_delim(chars[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+#[derive(Default)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+    overwrite: bool,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.cursor == self.len()
+    }
+
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub fn toggle_overwrite(&mut self) {
+        self.overwrite = !self.overwrite;
+    }
+
+    /// Returns the line and resets the editor; the insert mode is kept.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        core::mem::take(&mut self.text)
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    /// Replaces the line, with the cursor at its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = String::from(text);
+        self.cursor = self.len();
+    }
+
+    fn byte_index(&self, char_idx: usize) -> usize {
+        match self.text.char_indices().nth(char_idx) {
+            Some((idx, _)) => idx,
+            None => self.text.len(),
+        }
+    }
+
+    /// Inserts at the cursor, or replaces the char under it in overwrite
+    /// mode. Line breaks are never overwritten.
+    pub fn insert(&mut self, ch: char) {
+        let at = self.byte_index(self.cursor);
+        match self.text[at..].chars().next() {
+            Some(old) if self.overwrite && old != '\n' && ch != '\n' => {
+                self.text.replace_range(at..at + old.len_utf8(), ch.encode_utf8(&mut [0; 4]));
+            }
+            _ => self.text.insert(at, ch),
+        }
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.delete_range(self.cursor - 1, self.cursor);
+        true
+    }
+
+    pub fn delete(&mut self) -> bool {
+        if self.at_end() {
+            return false;
+        }
+        self.delete_range(self.cursor, self.cursor + 1);
+        true
+    }
+
+    /// Ctrl+Backspace: removes the word before the cursor.
+    pub fn delete_word_before(&mut self) -> bool {
+        let start = prev_word_start(&self.text, self.cursor);
+        if start == self.cursor {
+            return false;
+        }
+        self.delete_range(start, self.cursor);
+        true
+    }
+
+    fn delete_range(&mut self, start: usize, end: usize) {
+        let s = self.byte_index(start);
+        let e = self.byte_index(end);
+        self.text.drain(s..e);
+        self.cursor = start;
+    }
+
+    /// Moves the cursor, clamped to the line. Returns true if it moved.
+    pub fn move_to(&mut self, pos: usize) -> bool {
+        let pos = pos.min(self.len());
+        let moved = pos != self.cursor;
+        self.cursor = pos;
+        moved
+    }
+
+    pub fn move_left(&mut self) -> bool {
+        self.move_to(self.cursor.saturating_sub(1))
+    }
+
+    pub fn move_right(&mut self) -> bool {
+        self.move_to(self.cursor + 1)
+    }
+
+    pub fn word_left(&mut self) -> bool {
+        self.move_to(prev_word_start(&self.text, self.cursor))
+    }
+
+    pub fn word_right(&mut self) -> bool {
+        self.move_to(next_word_end(&self.text, self.cursor))
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(s: &str) -> LineEditor {
+        let mut ed = LineEditor::new();
+        for ch in s.chars() {
+            ed.insert(ch);
+        }
+        ed
+    }
+
+    #[test_case]
+    fn word_boundaries_skip_consecutive_delimiters() {
+        let line = "cat /boot//kernel  x=1";
+        assert_eq!(prev_word_start(line, 22), 21);
+        assert_eq!(prev_word_start(line, 21), 19);
+        assert_eq!(prev_word_start(line, 19), 11);
+        assert_eq!(prev_word_start(line, 11), 5);
+        assert_eq!(next_word_end(line, 3), 9);
+        assert_eq!(next_word_end(line, 9), 17);
+        assert_eq!(next_word_end(line, 17), 20);
+    }
+
+    #[test_case]
+    fn word_boundaries_with_leading_and_trailing_spaces() {
+        let line = "  ls  ";
+        assert_eq!(prev_word_start(line, 6), 2);
+        assert_eq!(prev_word_start(line, 2), 0);
+        assert_eq!(next_word_end(line, 0), 4);
+        assert_eq!(next_word_end(line, 4), 6);
+    }
+
+    #[test_case]
+    fn word_boundaries_at_the_extremes() {
+        assert_eq!(prev_word_start("echo hi", 0), 0);
+        assert_eq!(next_word_end("echo hi", 7), 7);
+        // out-of-range cursors are clamped to the line
+        assert_eq!(next_word_end("echo", 99), 4);
+        assert_eq!(prev_word_start("", 0), 0);
+        assert_eq!(next_word_end("", 0), 0);
+    }
+
+    #[test_case]
+    fn overwrite_replaces_and_appends_at_end() {
+        let mut ed = typed("abcd");
+        ed.move_to(1);
+        ed.toggle_overwrite();
+        ed.insert('X');
+        ed.insert('Y');
+        assert_eq!(ed.text(), "aXYd");
+        assert_eq!(ed.cursor(), 3);
+        ed.insert('Z');
+        ed.insert('!');
+        assert_eq!(ed.text(), "aXYZ!");
+    }
+
+    #[test_case]
+    fn ctrl_backspace_deletes_previous_word() {
+        let mut ed = typed("mount /dev/sda");
+        assert!(ed.delete_word_before());
+        assert_eq!(ed.text(), "mount /dev/");
+        assert!(ed.delete_word_before());
+        assert_eq!(ed.text(), "mount /");
+        assert!(ed.delete_word_before());
+        assert_eq!(ed.text(), "");
+        assert!(!ed.delete_word_before());
+    }
+}
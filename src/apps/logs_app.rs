@@ -1,15 +1,16 @@
 use crate::{
-    app::{App, AppEvent, Arrow, FocusBlock},
+    app::{App, AppEvent, AppStore, Arrow, FocusBlock, KeyCode},
     debug_pipeline::{self, DebugEvent},
 
     ui_provider::{
         color::Color,
+        frame_arena::arena_format,
         render::{RenderList, TextStyle},
         shape::Rect,
         theme::Theme,
     },
 };
-use alloc::{format, string::String, vec, vec::Vec};
+use alloc::{string::String, vec, vec::Vec};
 
 const MAX_LOG_LINES: usize = 500;
 const CHAR_WIDTH: usize = 10;
@@ -129,8 +130,11 @@ impl LogsApp {
         self.last_entry_count = total;
     }
 
-    fn truncate_to_cols(text: &str, cols: usize) -> String {
-        text.chars().take(cols).collect()
+    fn truncate_to_cols(text: &str, cols: usize) -> &str {
+        match text.char_indices().nth(cols) {
+            Some((end, _)) => &text[..end],
+            None => text,
+        }
     }
 
     fn draw_line(
@@ -163,19 +167,19 @@ impl LogsApp {
         self.draw_line(
             out,
             0,
-            &Self::truncate_to_cols("=== Kernel Logs ===", cols),
+            Self::truncate_to_cols("=== Kernel Logs ===", cols),
             theme.accent,
             theme.surface,
         );
 
-        let status = format!(
+        let status = arena_format(format_args!(
             "Lines: {} | Scroll: {} | Pipeline: unified",
             total, self.scroll_offset
-        );
+        ));
         self.draw_line(
             out,
             1,
-            &Self::truncate_to_cols(&status, cols),
+            Self::truncate_to_cols(&status, cols),
             theme.muted,
             theme.surface,
         );
@@ -193,8 +197,9 @@ impl LogsApp {
 
             if entry_idx < end {
                 let event = &events[entry_idx];
-                let line = Self::truncate_to_cols(&event.format_line(), cols);
-                self.draw_line(out, app_row, &line, event.level.color(), theme.surface);
+                let formatted = event.format_line();
+                let line = Self::truncate_to_cols(&formatted, cols);
+                self.draw_line(out, app_row, line, event.level.color(), theme.surface);
             } else {
                 self.draw_line(out, app_row, "", theme.muted, theme.surface);
             }
@@ -203,6 +208,16 @@ impl LogsApp {
 }
 
 impl App for LogsApp {
+    fn save_state(&self, store: &mut AppStore) {
+        store.set_int("scroll", self.scroll_offset as i64);
+        store.set_int("seen", self.last_entry_count as i64);
+    }
+
+    fn restore_state(&mut self, store: &AppStore) {
+        self.scroll_offset = store.usize("scroll").unwrap_or(self.scroll_offset);
+        self.last_entry_count = store.usize("seen").unwrap_or(self.last_entry_count);
+    }
+
     fn init(&mut self) {
         if !debug_pipeline::is_initialized() {
             debug_pipeline::init();
@@ -216,13 +231,13 @@ impl App for LogsApp {
 
     fn on_event(&mut self, event: AppEvent) -> bool {
         match event {
-            AppEvent::KeyPress { ch, ctrl, arrow, .. } => {
+            AppEvent::KeyPress { code, mods } => {
                 let visible_rows = self.visible_rows();
                 let total = debug_pipeline::len().min(MAX_LOG_LINES);
                 let old_scroll_offset = self.scroll_offset;
                 let old_last_entry_count = self.last_entry_count;
 
-                if let Some(dir) = arrow {
+                if let KeyCode::Arrow(dir) = code {
                     match dir {
                         Arrow::Up => {
                             self.scroll_offset = self.scroll_offset.saturating_sub(1);
@@ -237,7 +252,7 @@ impl App for LogsApp {
                     return self.scroll_offset != old_scroll_offset;
                 }
 
-                if ctrl && ch == 'l' {
+                if mods.ctrl() && code == KeyCode::Char('l') {
                     debug_pipeline::clear();
                     self.scroll_offset = 0;
                     self.last_entry_count = 0;
@@ -246,11 +261,11 @@ impl App for LogsApp {
                         || total != 0;
                 }
 
-                match ch {
-                    '[' => {
+                match code {
+                    KeyCode::Char('[') => {
                         self.scroll_offset = self.scroll_offset.saturating_sub(visible_rows);
                     }
-                    ']' => {
+                    KeyCode::Char(']') => {
                         let max_offset = total.saturating_sub(visible_rows);
                         self.scroll_offset = (self.scroll_offset + visible_rows).min(max_offset);
                     }
@@ -260,7 +275,7 @@ impl App for LogsApp {
                 self.scroll_offset != old_scroll_offset
             }
             AppEvent::Tick => false,
-            AppEvent::Mouse(_) => false,
+            AppEvent::Mouse { .. } => false,
         }
     }
 
@@ -287,6 +302,11 @@ impl App for LogsApp {
         }
     }
 
+    /// Rows are filled one by one; what is left below the last is not.
+    fn opaque(&self) -> bool {
+        false
+    }
+
     fn focus_blocks(&mut self) -> &mut [FocusBlock] {
         core::slice::from_mut(&mut self.block)
     }
@@ -6,6 +6,14 @@
 //!
 //! - `terminal_app`: Interactive terminal/shell application
 //! - `logs_app`: Kernel log viewer application
+//! - `editor_app`: VM program editor
+//! - `settings_app`: Settings dialog built from `ui_provider::widgets`
+//! - `form_demo`: a small form exercising Tab order and activation
+//! - `prompt`: prompt template used by the terminal
+//! - `history`: command history and Ctrl+R search for the terminal
+//! - `jobs`: commands run in the background with a trailing `&`
+//! - `clipboard`: the copy history and the terminal's paste picker
+//! - `top`: the terminal's live task view
 //!
 //! ## Architecture
 //!
@@ -16,6 +24,14 @@
 //! - Layout and rendering
 //! - Focus management
 
+pub mod clipboard;
 pub mod editor_app;
+pub mod form_demo;
+pub mod history;
+pub mod jobs;
+pub mod line_edit;
 pub mod logs_app;
+pub mod prompt;
+pub mod settings_app;
 pub mod terminal_app;
+pub mod top;
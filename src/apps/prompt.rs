@@ -0,0 +1,129 @@
+//! Terminal prompt template, expanded by `TerminalApp` before each prompt.
+//!
+//! | Token  | Expands to                                              |
+//! |--------|---------------------------------------------------------|
+//! | `\w`   | working directory (`cd`/`pwd`)                          |
+//! | `\t`   | RTC time of day, `HH:MM:SS`                             |
+//! | `\$?`  | last command: green `+` on success, red `x` on error    |
+//! | `\e`   | ESC, for colour sequences such as `\e[36m`              |
+//! | `\n`   | newline                                                 |
+//! | `\\`   | a backslash                                             |
+//!
+//! Anything else is copied as is. The terminal font is ASCII-only, hence
+//! `+`/`x` rather than check and cross glyphs. Colours are reset after the
+//! prompt so they never carry over into the typed command.
+
+use crate::devices::drivers::rtc::{self, RtcTime};
+use alloc::{format, string::String};
+use spin::Mutex;
+
+pub const DEFAULT_TEMPLATE: &str = "> ";
+
+/// `None` until `prompt <template>` sets one.
+static TEMPLATE: Mutex<Option<String>> = Mutex::new(None);
+
+/// What the tokens expand to; the terminal fills in the live values.
+pub struct PromptContext<'a> {
+    pub cwd: &'a str,
+    pub time: Option<RtcTime>,
+    pub last_ok: bool,
+}
+
+pub fn template() -> String {
+    TEMPLATE
+        .lock()
+        .clone()
+        .unwrap_or_else(|| String::from(DEFAULT_TEMPLATE))
+}
+
+pub fn set_template(template: &str) {
+    *TEMPLATE.lock() = Some(String::from(template));
+}
+
+pub fn reset_template() {
+    *TEMPLATE.lock() = None;
+}
+
+pub fn expand(template: &str, ctx: &PromptContext) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('w') => out.push_str(ctx.cwd),
+            Some('t') => match ctx.time {
+                Some(t) => out.push_str(&format!("{:02}:{:02}:{:02}", t.hour, t.minute, t.second)),
+                None => out.push_str("--:--:--"),
+            },
+            Some('$') if chars.peek() == Some(&'?') => {
+                chars.next();
+                out.push_str(if ctx.last_ok {
+                    "\x1b[32m+\x1b[0m"
+                } else {
+                    "\x1b[31mx\x1b[0m"
+                });
+            }
+            Some('e') => out.push('\x1b'),
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out.push_str("\x1b[0m");
+    out
+}
+
+/// The current template expanded with live values. The RTC is only read
+/// when the template shows the time.
+pub fn render(last_ok: bool) -> String {
+    let template = template();
+    let cwd = crate::cmd_executor::cwd();
+    let ctx = PromptContext {
+        cwd: &cwd,
+        time: template.contains("\\t").then(rtc::read_time),
+        last_ok,
+    };
+    expand(&template, &ctx)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(cwd: &str, last_ok: bool) -> PromptContext<'_> {
+        PromptContext {
+            cwd,
+            time: Some(RtcTime {
+                hour: 9,
+                minute: 5,
+                second: 42,
+            }),
+            last_ok,
+        }
+    }
+
+    #[test_case]
+    fn tokens_expand_from_the_context() {
+        let out = expand("[\\t] \\w \\$? > ", &ctx("/home/user", true));
+        assert_eq!(out, "[09:05:42] /home/user \x1b[32m+\x1b[0m > \x1b[0m");
+
+        let out = expand("\\$?\\$", &ctx("/", false));
+        assert_eq!(out, "\x1b[31mx\x1b[0m\\$\x1b[0m");
+    }
+
+    #[test_case]
+    fn escapes_and_unknown_tokens() {
+        let out = expand("\\e[36m\\w\\e[0m\\n\\\\ \\q\\", &ctx("/", true));
+        assert_eq!(out, "\x1b[36m/\x1b[0m\n\\ \\q\\\x1b[0m");
+        assert_eq!(expand(DEFAULT_TEMPLATE, &ctx("/", true)), "> \x1b[0m");
+    }
+}
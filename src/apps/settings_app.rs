@@ -0,0 +1,170 @@
+use crate::app::{App, AppEvent, AppStore, FocusBlock};
+use crate::ui_provider::{
+    render::{RenderList, TextStyle},
+    shape::Rect,
+    theme::Theme,
+    widgets::{Button, TextInput, Widget, WidgetEvent},
+};
+use alloc::{format, string::String};
+
+const CHAR_HEIGHT: usize = 20;
+const MARGIN: usize = 20;
+const LABEL_WIDTH: usize = 120;
+const INPUT_WIDTH: usize = 320;
+const INPUT_HEIGHT: usize = 28;
+const ROW_GAP: usize = 16;
+const BUTTON_WIDTH: usize = 100;
+
+const PROMPT_ID: u32 = 4;
+const HOSTNAME_ID: u32 = 5;
+const APPLY_ID: u32 = 6;
+
+/// Small settings dialog exercising `TextInput`/`Button` and focus traversal
+/// between several blocks of one app (Tab or Alt+arrows move focus).
+pub struct SettingsApp {
+    blocks: [FocusBlock; 3],
+    bounds: Rect,
+    focused: u32,
+    prompt: TextInput,
+    hostname: TextInput,
+    apply: Button,
+    status: String,
+}
+
+impl SettingsApp {
+    pub fn new(_width: usize, _height: usize) -> Self {
+        let mut prompt = TextInput::new("> ").with_max_len(16);
+        prompt.set_value("> ");
+
+        Self {
+            blocks: [
+                FocusBlock {
+                    id: PROMPT_ID,
+                    rect: Rect::new(0, 0, 0, 0),
+                },
+                FocusBlock {
+                    id: HOSTNAME_ID,
+                    rect: Rect::new(0, 0, 0, 0),
+                },
+                FocusBlock {
+                    id: APPLY_ID,
+                    rect: Rect::new(0, 0, 0, 0),
+                },
+            ],
+            bounds: Rect::new(0, 0, 0, 0),
+            focused: PROMPT_ID,
+            prompt,
+            hostname: TextInput::new("duxos").with_max_len(32),
+            apply: Button::new("Apply"),
+            status: String::from("Tab to move focus, Enter to apply"),
+        }
+    }
+
+    fn apply(&mut self) {
+        let hostname = if self.hostname.value().is_empty() {
+            "duxos"
+        } else {
+            self.hostname.value()
+        };
+        self.status = format!("Applied: prompt={:?} hostname={:?}", self.prompt.value(), hostname);
+        crate::log_info!("Settings: {}", self.status);
+    }
+}
+
+impl App for SettingsApp {
+    fn save_state(&self, store: &mut AppStore) {
+        store.set_str("status", &self.status);
+    }
+
+    fn restore_state(&mut self, store: &AppStore) {
+        if let Some(status) = store.str("status") {
+            self.status = String::from(status);
+        }
+    }
+
+    fn on_cancel(&mut self) -> bool {
+        match self.focused {
+            PROMPT_ID => self.prompt.clear_selection(),
+            HOSTNAME_ID => self.hostname.clear_selection(),
+            _ => false,
+        }
+    }
+
+    fn activate(&mut self, block_id: u32) -> bool {
+        if block_id != APPLY_ID || self.apply.activate() != WidgetEvent::Activate {
+            return false;
+        }
+        self.apply();
+        true
+    }
+
+    fn on_event(&mut self, event: AppEvent) -> bool {
+        let result = match self.focused {
+            PROMPT_ID => self.prompt.handle_event(&event),
+            HOSTNAME_ID => self.hostname.handle_event(&event),
+            APPLY_ID => self.apply.handle_event(&event),
+            _ => WidgetEvent::Ignored,
+        };
+
+        if matches!(result, WidgetEvent::Submit | WidgetEvent::Activate) {
+            self.apply();
+        }
+        result.needs_redraw()
+    }
+
+    fn layout(&mut self, bounds: Rect) {
+        self.bounds = bounds;
+
+        let input_x = bounds.x + MARGIN + LABEL_WIDTH;
+        let input_w = INPUT_WIDTH.min(bounds.w.saturating_sub(LABEL_WIDTH + MARGIN * 2));
+        let mut y = bounds.y + MARGIN + CHAR_HEIGHT + ROW_GAP;
+
+        self.prompt.set_rect(Rect::new(input_x, y, input_w, INPUT_HEIGHT));
+        y += INPUT_HEIGHT + ROW_GAP;
+        self.hostname.set_rect(Rect::new(input_x, y, input_w, INPUT_HEIGHT));
+        y += INPUT_HEIGHT + ROW_GAP;
+        self.apply.set_rect(Rect::new(input_x, y, BUTTON_WIDTH, INPUT_HEIGHT));
+
+        self.blocks[0].rect = self.prompt.rect();
+        self.blocks[1].rect = self.hostname.rect();
+        self.blocks[2].rect = self.apply.rect();
+    }
+
+    fn collect_render(&mut self, theme: &Theme, out: &mut RenderList) {
+        out.fill_rect(self.bounds, theme.surface);
+
+        let label_x = self.bounds.x + MARGIN;
+        out.styled_text(
+            "Settings",
+            label_x,
+            self.bounds.y + MARGIN,
+            TextStyle::new(theme.accent),
+        );
+
+        let label_dy = INPUT_HEIGHT.saturating_sub(CHAR_HEIGHT) / 2;
+        out.text("Prompt", label_x, self.prompt.rect().y + label_dy, theme.text);
+        out.text("Hostname", label_x, self.hostname.rect().y + label_dy, theme.text);
+
+        self.prompt
+            .collect_render(theme, self.focused == PROMPT_ID, out);
+        self.hostname
+            .collect_render(theme, self.focused == HOSTNAME_ID, out);
+        self.apply
+            .collect_render(theme, self.focused == APPLY_ID, out);
+
+        let status_y = self.apply.rect().y + INPUT_HEIGHT + ROW_GAP;
+        out.text(self.status.as_str(), label_x, status_y, theme.muted);
+    }
+
+    fn focus_changed(&mut self, block_id: u32) {
+        self.focused = block_id;
+    }
+
+    fn focus_blocks(&mut self) -> &mut [FocusBlock] {
+        &mut self.blocks
+    }
+
+    fn bounds(&self) -> Rect {
+        self.bounds
+    }
+}
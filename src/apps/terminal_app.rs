@@ -1,23 +1,86 @@
-use crate::app::{App, AppEvent, FocusBlock};
+use crate::app::{App, AppEvent, Arrow, FocusBlock, KeyCode, Modifiers};
+use crate::apps::clipboard::{self, ClipPicker, PickerKey};
+use crate::apps::history::{History, ReverseSearch};
+use crate::apps::line_edit::LineEditor;
+use crate::apps::prompt;
+use crate::apps::top::{TopKey, TopView};
 use crate::cmd_executor::CommandExecutor;
+use crate::memory::pressure::Pressure;
 
 use crate::terminal_v2::Terminal;
-use crate::ui_provider::{render::RenderList, shape::Rect, theme::Theme};
-use alloc::string::String;
+use crate::ui_provider::{
+    background, bell, layers,
+    render::RenderList,
+    shape::Rect,
+    theme::Theme,
+    progress::{self, Report},
+    widgets::{ProgressBar, Scrollbar, Widget, SCROLLBAR_WIDTH},
+};
+use alloc::{format, string::String};
+
+/// Width of the border a visual bell flashes.
+const BELL_BORDER: usize = 3;
+/// The `progress` bar in the bottom right corner, and its margin.
+const PROGRESS_WIDTH: usize = 240;
+const PROGRESS_HEIGHT: usize = 20;
+const PROGRESS_MARGIN: usize = 8;
+/// A second paste this soon after the first opens the clipboard picker.
+const DOUBLE_PASTE_MS: u64 = 500;
+
+/// Ctrl+R in progress, with the line it replaced.
+struct Searching {
+    search: ReverseSearch,
+    saved: String,
+}
+
+/// `top` running, with the screen it replaced.
+struct Top {
+    view: TopView,
+    saved: Terminal,
+}
+
+/// The last paste, so that a quick second one can take it back.
+struct Paste {
+    at_ms: u64,
+    before: String,
+    cursor: usize,
+}
 
 pub struct TerminalApp {
     terminal: Terminal,
     block: FocusBlock,
     bounds: Rect,
-    current_line: String,
+    line: LineEditor,
     full_redraw: bool,
+    /// Pixels to paint again though their rows are clean: something was
+    /// drawn over them last frame and has gone (see `App::invalidate`).
+    stale: Option<Rect>,
+    mouse_down: bool,
+    /// Where in the scrollbar thumb it was grabbed, while dragged.
+    thumb_grab: Option<usize>,
+    /// The scrollbar was drawn last frame.
+    scrollbar_shown: bool,
+    /// `progress::generation` when last looked at, and whether the bar
+    /// was up then.
+    progress_seen: u32,
+    progress_shown: bool,
+    /// Outcome of the last command, for the prompt's `\$?`.
+    last_ok: bool,
+    history: History,
+    searching: Option<Searching>,
+    top: Option<Top>,
+    /// What the last command printed, for Ctrl+Shift+C on an empty line.
+    last_output: String,
+    /// Only while it is the last key pressed.
+    last_paste: Option<Paste>,
+    picker: Option<ClipPicker>,
 }
 
 impl TerminalApp {
     pub fn new(width: usize, height: usize) -> Self {
         let cols = (width / 10).max(1);
         let rows = (height / 20).max(1);
-        let theme = Theme::dark_modern();
+        let theme = Theme::current();
 
         Self {
             terminal: Terminal::new(cols, rows, &theme),
@@ -26,66 +89,473 @@ impl TerminalApp {
                 rect: Rect::new(0, 0, 0, 0),
             },
             bounds: Rect::new(0, 0, 0, 0),
-            current_line: String::new(),
+            line: LineEditor::new(),
             full_redraw: true,
+            stale: None,
+            mouse_down: false,
+            thumb_grab: None,
+            scrollbar_shown: false,
+            progress_seen: progress::generation(),
+            progress_shown: false,
+            last_ok: true,
+            history: History::new(),
+            searching: None,
+            top: None,
+            last_output: String::new(),
+            last_paste: None,
+            picker: None,
         }
     }
 
     fn write_prompt(&mut self) {
-        self.terminal.write("> ");
+        // Jobs that ended since the last prompt, as a shell reports them
+        for notice in super::jobs::take_notices() {
+            self.terminal.write(&notice);
+            self.terminal.write("\n");
+        }
+        self.terminal.write(&prompt::render(self.last_ok));
+        // After the whole expanded prompt, so input editing stops there.
         self.terminal.set_prompt_start();
     }
 
     fn execute_command(&mut self) {
-        let input = self.current_line.clone();
-        self.current_line.clear();
+        // Finish with the cursor after the whole input, not mid-line.
+        let end = self.line.text().chars().count();
+        self.terminal.redraw_input(self.line.text(), end);
+        let input = self.line.take();
+        self.history.push(&input);
 
         self.terminal.write("\n");
 
         use crate::cmd_executor::CommandResult;
-        match CommandExecutor::execute(&input) {
+        let result = CommandExecutor::execute(&input);
+        self.last_ok = !matches!(result, CommandResult::Error(_));
+        self.last_output.clear();
+        match result {
             CommandResult::Output(output) => {
                 self.terminal.write(&output);
                 self.terminal.write("\n");
+                self.last_output.push_str(clipboard::cut(&output));
             }
             CommandResult::Error(error) => {
                 let mut err_display = String::from("Error: ");
                 err_display.push_str(&error);
                 self.terminal.write(&err_display);
                 self.terminal.write("\n");
+                self.last_output.push_str(clipboard::cut(&error));
             }
             CommandResult::Exit => {
                 self.terminal.write("Goodbye!\n");
             }
+            CommandResult::Top => {
+                self.start_top();
+                return;
+            }
         }
 
         self.write_prompt();
     }
 
     fn clear_screen(&mut self) {
-        self.terminal.clear();
-        self.current_line.clear();
+        self.terminal.clear_viewport();
+        self.line.clear();
         self.write_prompt();
         self.full_redraw = true;
     }
 
-    fn resize_terminal(&mut self, theme: &Theme) {
+    /// Shift+PageUp/PageDown: a screen less one row through the scrollback.
+    fn scroll_page(&mut self, direction: isize) -> bool {
+        let (_, rows) = self.terminal.size();
+        let page = rows.saturating_sub(1).max(1) as isize;
+        self.terminal.scroll_view(direction * page)
+    }
+
+    /// The scrollback position along the right edge, whether or not it is
+    /// drawn: only while scrolled back.
+    fn scrollbar(&self) -> Scrollbar {
+        let (_, rows) = self.terminal.size();
+        let back = self.terminal.scrollback_len();
+        Scrollbar {
+            track: Rect::new(
+                self.bounds.right().saturating_sub(SCROLLBAR_WIDTH),
+                self.bounds.y,
+                SCROLLBAR_WIDTH.min(self.bounds.w),
+                self.bounds.h,
+            ),
+            visible: rows,
+            total: back + rows,
+            first: back - self.terminal.view_offset(),
+        }
+    }
+
+    /// The bar for `report` and the panel behind it and its label, in the
+    /// bottom right corner clear of the scrollbar.
+    fn progress_bar(&self, report: &Report) -> (ProgressBar, Rect) {
+        let label_w = report.label.chars().count() * 10;
+        let right = self.bounds.right().saturating_sub(SCROLLBAR_WIDTH + PROGRESS_MARGIN);
+        let w = PROGRESS_WIDTH.min(right.saturating_sub(self.bounds.x + PROGRESS_MARGIN));
+        let y = self.bounds.bottom().saturating_sub(PROGRESS_HEIGHT + PROGRESS_MARGIN);
+        let mut bar = ProgressBar::new(report.total).with_percent();
+        bar.set_value(report.done);
+        bar.set_rect(Rect::new(right - w, y, w, PROGRESS_HEIGHT));
+
+        let left = (right - w).saturating_sub(label_w + PROGRESS_MARGIN).max(self.bounds.x);
+        let panel = Rect::new(
+            left.saturating_sub(PROGRESS_MARGIN / 2),
+            y.saturating_sub(PROGRESS_MARGIN / 2),
+            right - left + PROGRESS_MARGIN,
+            PROGRESS_HEIGHT + PROGRESS_MARGIN,
+        );
+        (bar, panel)
+    }
+
+    /// Whether `progress` changed since the last look. The terminal is
+    /// repainted from under a bar that went away.
+    fn poll_progress(&mut self) -> bool {
+        let generation = progress::generation();
+        if generation == self.progress_seen {
+            return false;
+        }
+        self.progress_seen = generation;
+        let shown = progress::current().is_some();
+        if self.progress_shown && !shown {
+            self.full_redraw = true;
+        }
+        self.progress_shown = shown;
+        true
+    }
+
+    /// A press on the scrollbar grabs the thumb, or if it missed, jumps to
+    /// put the thumb's middle there.
+    fn grab_scrollbar(&mut self, x: usize, y: usize) -> bool {
+        let bar = self.scrollbar();
+        if self.terminal.view_offset() == 0 || !bar.track.contains(x, y) {
+            return false;
+        }
+        let thumb = bar.thumb();
+        let grab = if thumb.contains(x, y) { y - thumb.y } else { thumb.h / 2 };
+        self.thumb_grab = Some(grab);
+        self.drag_scrollbar(y);
+        true
+    }
+
+    /// Scrolls so that the grabbed point of the thumb is at `y`.
+    fn drag_scrollbar(&mut self, y: usize) -> bool {
+        let (Some(grab), bar) = (self.thumb_grab, self.scrollbar()) else {
+            return false;
+        };
+        let top = y.saturating_sub(bar.track.y).saturating_sub(grab);
+        let first = bar.first_at(top);
+        let offset = self.terminal.scrollback_len() - first;
+        self.terminal
+            .scroll_view(offset as isize - self.terminal.view_offset() as isize)
+    }
+
+    /// Fits the terminal to `bounds`: the banner the first time, after
+    /// that a reflow that keeps the output and the line being typed.
+    fn resize_terminal(&mut self, theme: &Theme, first: bool) {
         let cols = (self.bounds.w / 10).max(1);
         let rows = (self.bounds.h / 20).max(1);
 
+        if !first {
+            if (cols, rows) == self.terminal.size() {
+                return;
+            }
+            // The saved shell screen is the one to keep
+            self.end_top();
+            self.terminal.resize(cols, rows);
+            self.full_redraw = true;
+            return;
+        }
         let mut new_terminal = Terminal::new(cols, rows, theme);
         new_terminal.write("Terminal\n");
-        new_terminal.write("Type 'help' for available commands\n");
+        write_banner_hint(&mut new_terminal);
         new_terminal.write("Shortcuts: Alt+Tab to switch apps\n\n");
         self.write_prompt_into(&mut new_terminal);
 
+        new_terminal.set_block_cursor(self.line.overwrite());
         self.terminal = new_terminal;
-        self.current_line.clear();
+        self.line.clear();
         self.full_redraw = true;
     }
 
+    fn start_top(&mut self) {
+        let now = crate::devices::drivers::hpet::monotonic_ms();
+        self.top = Some(Top {
+            view: TopView::new(now),
+            saved: self.terminal.clone(),
+        });
+        self.draw_top(now);
+    }
+
+    fn draw_top(&mut self, now_ms: u64) {
+        let Some(top) = &self.top else {
+            return;
+        };
+        let (cols, rows) = self.terminal.size();
+        let lines = top.view.render(now_ms, cols, rows);
+        // A full reset: this screen is thrown away when `top` ends, so
+        // there is no point keeping its frames as scrollback.
+        self.terminal.write("\x1b[3J\x1b[H");
+        for (idx, line) in lines.iter().enumerate() {
+            if idx == 0 {
+                // Dark on light for the header, padded across the row.
+                self.terminal.write(&format!("\x1b[30;47m{:<1$}\x1b[0m", line, cols));
+            } else {
+                self.terminal.write(line);
+            }
+            if idx + 1 < lines.len() {
+                self.terminal.write("\n");
+            }
+        }
+    }
+
+    /// Puts the shell screen back, with a fresh prompt.
+    fn end_top(&mut self) {
+        if let Some(top) = self.top.take() {
+            self.terminal = top.saved;
+            self.terminal.invalidate_all();
+            self.full_redraw = true;
+            self.write_prompt();
+        }
+    }
+
+    fn top_key(&mut self, code: KeyCode) -> bool {
+        let Some(top) = &mut self.top else {
+            return false;
+        };
+        match top.view.key(code) {
+            TopKey::Quit => self.end_top(),
+            TopKey::Redraw => self.draw_top(crate::devices::drivers::hpet::monotonic_ms()),
+            TopKey::Ignored => return false,
+        }
+        true
+    }
+
+    fn start_search(&mut self) {
+        self.searching = Some(Searching {
+            search: ReverseSearch::new(),
+            saved: String::from(self.line.text()),
+        });
+        self.redraw_search();
+    }
+
+    /// Shows `(reverse-i-search)'query': candidate` in place of the prompt,
+    /// with the matched part in inverse video.
+    fn redraw_search(&mut self) {
+        let Some(searching) = &self.searching else {
+            return;
+        };
+        let search = &searching.search;
+        let entries = self.history.entries();
+        let label = if search.failed() {
+            "failed reverse-i-search"
+        } else {
+            "reverse-i-search"
+        };
+        let mut text = format!("({})'{}': ", label, search.query());
+        if let Some(candidate) = search.candidate(entries) {
+            match search.match_range(entries) {
+                Some(hit) => {
+                    text.push_str(&candidate[..hit.start]);
+                    text.push_str("\x1b[30;47m");
+                    text.push_str(&candidate[hit.clone()]);
+                    text.push_str("\x1b[0m");
+                    text.push_str(&candidate[hit.end..]);
+                }
+                None => text.push_str(candidate),
+            }
+        }
+        self.terminal.redraw_prompt_row(&text);
+    }
+
+    /// Leaves search mode with `line` on the input line.
+    fn end_search(&mut self, line: &str) {
+        self.searching = None;
+        let prompt = prompt::render(self.last_ok);
+        // Only the prompt's last row was replaced.
+        let last_row = prompt.rsplit('\n').next().unwrap_or("");
+        self.terminal.redraw_prompt_row(last_row);
+        self.terminal.set_prompt_start();
+        self.line.set_text(line);
+        self.redraw_line();
+    }
+
+    /// Puts back the line the search replaced.
+    fn cancel_search(&mut self) -> bool {
+        let Some(searching) = &mut self.searching else {
+            return false;
+        };
+        let saved = core::mem::take(&mut searching.saved);
+        self.end_search(&saved);
+        true
+    }
+
+    /// Keys while searching: typing narrows, Ctrl+R steps to older
+    /// matches, Enter runs the match, Right/End keep it for editing, and
+    /// Esc/Ctrl+G put the original line back.
+    fn search_key(&mut self, code: KeyCode, mods: Modifiers) -> bool {
+        let Some(searching) = &mut self.searching else {
+            return false;
+        };
+        let entries = self.history.entries();
+        let accepted = || {
+            String::from(
+                searching
+                    .search
+                    .candidate(entries)
+                    .unwrap_or(searching.saved.as_str()),
+            )
+        };
+        let ctrl = mods.ctrl();
+        let cancel = code == KeyCode::Escape || (ctrl && code == KeyCode::Char('g'));
+        match code {
+            KeyCode::Arrow(Arrow::Right) | KeyCode::End => {
+                let line = accepted();
+                self.end_search(&line);
+            }
+            KeyCode::Enter => {
+                let line = accepted();
+                self.end_search(&line);
+                self.execute_command();
+            }
+            _ if cancel => return self.cancel_search(),
+            KeyCode::Char('r') if ctrl => {
+                searching.search.step_older(entries);
+                self.redraw_search();
+            }
+            KeyCode::Backspace if !ctrl => {
+                searching.search.pop(entries);
+                self.redraw_search();
+            }
+            KeyCode::Char(ch) if !ctrl && !ch.is_control() => {
+                searching.search.push(ch, entries);
+                self.redraw_search();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn redraw_line(&mut self) {
+        self.terminal.redraw_input(self.line.text(), self.line.cursor());
+    }
+
+    fn handle_arrow(&mut self, dir: Arrow, ctrl: bool) -> bool {
+        let moved = match (dir, ctrl) {
+            (Arrow::Left, false) => self.line.move_left(),
+            (Arrow::Right, false) => self.line.move_right(),
+            (Arrow::Left, true) => self.line.word_left(),
+            (Arrow::Right, true) => self.line.word_right(),
+            _ => false,
+        };
+        if moved {
+            self.redraw_line();
+        }
+        moved
+    }
+
+    fn insert_char(&mut self, ch: char) {
+        let append = self.line.at_end();
+        self.line.insert(ch);
+        if append {
+            // Typing at the end only needs the new glyph.
+            let mut buf = [0u8; 4];
+            self.terminal.write(ch.encode_utf8(&mut buf));
+        } else {
+            self.redraw_line();
+        }
+    }
+
+    /// Ctrl+Shift+C: the input line, or with none typed what the last
+    /// command printed.
+    fn copy(&mut self) {
+        let text = match self.line.text() {
+            "" => self.last_output.as_str(),
+            line => line,
+        };
+        clipboard::copy(text);
+    }
+
+    /// Ctrl+Shift+V: inserts the newest copy. Straight after another
+    /// paste (`previous`), takes that one back and opens the picker.
+    fn paste(&mut self, previous: Option<Paste>) -> bool {
+        let now = crate::devices::drivers::hpet::monotonic_ms();
+        if let Some(previous) = previous {
+            if now.saturating_sub(previous.at_ms) <= DOUBLE_PASTE_MS {
+                self.line.set_text(&previous.before);
+                self.line.move_to(previous.cursor);
+                self.redraw_line();
+                return self.open_picker();
+            }
+        }
+        let Some(text) = clipboard::newest() else {
+            return false;
+        };
+        self.last_paste = Some(Paste {
+            at_ms: now,
+            before: String::from(self.line.text()),
+            cursor: self.line.cursor(),
+        });
+        self.insert_text(&text);
+        true
+    }
+
+    /// Pasted text goes in as typed; control characters other than line
+    /// breaks are dropped.
+    fn insert_text(&mut self, text: &str) {
+        for ch in text.chars().filter(|&ch| ch == '\n' || !ch.is_control()) {
+            self.line.insert(ch);
+        }
+        self.redraw_line();
+    }
+
+    fn open_picker(&mut self) -> bool {
+        self.picker = ClipPicker::open();
+        self.picker.is_some()
+    }
+
+    /// The terminal repaints under where the panel was.
+    fn close_picker(&mut self) {
+        self.picker = None;
+        self.full_redraw = true;
+    }
+
+    fn picker_key(&mut self, code: KeyCode) -> bool {
+        let Some(picker) = &mut self.picker else {
+            return false;
+        };
+        match picker.key(code) {
+            PickerKey::Moved => true,
+            PickerKey::Paste(text) => {
+                self.close_picker();
+                self.insert_text(&text);
+                true
+            }
+            PickerKey::Ignored => false,
+        }
+    }
+
+    /// A click on a link (a command name in `help`, say) puts its command
+    /// on the input line, ready for arguments. It is not executed.
+    fn click_at(&mut self, x: usize, y: usize) -> bool {
+        if x < self.bounds.x || y < self.bounds.y {
+            return false;
+        }
+        let payload = match self.terminal.link_at(x - self.bounds.x, y - self.bounds.y) {
+            Some(payload) => String::from(payload),
+            None => return false,
+        };
+        self.line.clear();
+        for ch in payload.chars() {
+            self.line.insert(ch);
+        }
+        self.redraw_line();
+        true
+    }
+
     fn write_prompt_into(&self, terminal: &mut Terminal) {
-        terminal.write("> ");
+        terminal.write(&prompt::render(self.last_ok));
         terminal.set_prompt_start();
     }
 }
@@ -93,72 +563,153 @@ impl TerminalApp {
 impl App for TerminalApp {
     fn init(&mut self) {
         self.terminal.write("Terminal\n");
-        self.terminal.write("Type 'help' for available commands\n");
+        write_banner_hint(&mut self.terminal);
         self.terminal.write("Shortcuts: Alt+Tab to switch apps\n\n");
         self.write_prompt();
         self.full_redraw = true;
     }
 
+    /// Halves the command history, or quarters it at `Critical`. Not
+    /// during a Ctrl+R search, which holds an index into it.
+    fn trim(&mut self, level: Pressure) -> usize {
+        let divisor = if level == Pressure::Critical { 4 } else { 2 };
+        let keep = self.terminal.scrollback_len() / divisor;
+        let freed = self.terminal.trim_scrollback(keep);
+        if self.searching.is_some() {
+            return freed;
+        }
+        freed + self.history.trim(self.history.entries().len() / divisor)
+    }
+
+    /// Escape closes the clipboard picker, leaves `top` or a search first,
+    /// then clears a non-empty input line.
+    fn on_cancel(&mut self) -> bool {
+        if self.picker.is_some() {
+            self.close_picker();
+            return true;
+        }
+        if let Some(top) = &mut self.top {
+            if top.view.cancel() {
+                self.draw_top(crate::devices::drivers::hpet::monotonic_ms());
+            } else {
+                self.end_top();
+            }
+            return true;
+        }
+        if self.cancel_search() {
+            return true;
+        }
+        if self.line.text().is_empty() {
+            return false;
+        }
+        self.line.clear();
+        self.redraw_line();
+        true
+    }
+
     fn on_event(&mut self, event: AppEvent) -> bool {
         match event {
-            AppEvent::Mouse(me) => {
-                if me.buttons != 0 {
-                    let (mx, my) = crate::devices::mouse_cursor::get_position();
-                    self.terminal.write("[click@");
-                    self.terminal.write(&format_num(mx));
-                    self.terminal.write(",");
-                    self.terminal.write(&format_num(my));
-                    self.terminal.write("]");
-                    true
-                } else {
-                    false
-                }
-            }
-            AppEvent::KeyPress {
-                ch,
-                ctrl,
-                alt: _,
-                shift,
-                arrow,
-            } => {
-                if arrow.is_some() {
-                    return false;
+            AppEvent::Mouse { .. } if self.top.is_some() => false,
+            AppEvent::Mouse { event, x, y } => {
+                // Act on the press only, not on every packet while held.
+                let pressed = event.left_button() && !self.mouse_down;
+                self.mouse_down = event.left_button();
+                if !self.mouse_down {
+                    self.thumb_grab = None;
                 }
-
-                if ctrl && ch == 'l' {
-                    self.clear_screen();
+                if pressed && self.grab_scrollbar(x, y) {
                     return true;
                 }
-
-                if ch == '\n' {
-                    if shift {
-                        self.execute_command();
-                    } else {
-                        self.terminal.write("\n");
-                        self.current_line.push('\n');
-                    }
-                    return true;
+                if self.thumb_grab.is_some() {
+                    return self.drag_scrollbar(y);
+                }
+                pressed && self.click_at(x, y)
+            }
+            AppEvent::KeyPress { code, .. } if self.top.is_some() => self.top_key(code),
+            AppEvent::KeyPress { code, .. } if self.picker.is_some() => self.picker_key(code),
+            AppEvent::KeyPress { code, mods } => {
+                if self.searching.is_some() {
+                    return self.search_key(code, mods);
                 }
 
-                if ch == '\x08' {
-                    if !self.current_line.is_empty() {
-                        self.terminal.write("\x08");
-                        self.current_line.pop();
+                let ctrl = mods.ctrl();
+                let previous_paste = self.last_paste.take();
+                let changed = match code {
+                    KeyCode::Char('C') if ctrl && mods.shift() => {
+                        self.copy();
+                        return false;
+                    }
+                    KeyCode::Char('V') if ctrl && mods.shift() => return self.paste(previous_paste),
+                    KeyCode::Char('H') if ctrl && mods.shift() => return self.open_picker(),
+                    KeyCode::Arrow(dir) => return self.handle_arrow(dir, ctrl),
+                    KeyCode::PageUp if mods.shift() => return self.scroll_page(1),
+                    KeyCode::PageDown if mods.shift() => return self.scroll_page(-1),
+                    KeyCode::Char('l') if ctrl => {
+                        self.clear_screen();
                         return true;
                     }
-                    return false;
-                }
+                    KeyCode::Char('r') if ctrl => {
+                        self.start_search();
+                        return true;
+                    }
+                    KeyCode::Enter => {
+                        if mods.shift() {
+                            self.execute_command();
+                        } else {
+                            self.insert_char('\n');
+                        }
+                        return true;
+                    }
+                    KeyCode::Backspace if ctrl => self.line.delete_word_before(),
+                    KeyCode::Backspace => {
+                        let simple = self.line.at_end() && !self.line.text().ends_with('\n');
+                        if !self.line.backspace() {
+                            return false;
+                        }
+                        if simple {
+                            self.terminal.write("\x08");
+                            return true;
+                        }
+                        true
+                    }
+                    KeyCode::Delete => self.line.delete(),
+                    KeyCode::Home => self.line.move_to(0),
+                    KeyCode::End => self.line.move_to(usize::MAX),
+                    KeyCode::Insert => {
+                        // Insert toggles overwrite mode, shown as a block caret.
+                        self.line.toggle_overwrite();
+                        self.terminal.set_block_cursor(self.line.overwrite());
+                        return true;
+                    }
+                    KeyCode::Char(ch) if !ctrl && !ch.is_control() => {
+                        self.insert_char(ch);
+                        return true;
+                    }
+                    _ => return false,
+                };
 
-                if !ctrl && !ch.is_control() {
-                    let mut buf = [0u8; 4];
-                    self.terminal.write(ch.encode_utf8(&mut buf));
-                    self.current_line.push(ch);
-                    return true;
+                if changed {
+                    self.redraw_line();
+                }
+                changed
+            }
+            AppEvent::Tick => {
+                // The flash border is gone once the terminal repaints under it
+                let flash_ended = bell::tick();
+                if flash_ended {
+                    self.full_redraw = true;
+                }
+                let changed = self.poll_progress() || flash_ended;
+                let now = crate::devices::drivers::hpet::monotonic_ms();
+                match &mut self.top {
+                    Some(top) if top.view.due(now) => {
+                        top.view.sample(now);
+                        self.draw_top(now);
+                        true
+                    }
+                    _ => changed,
                 }
-
-                false
             }
-            AppEvent::Tick => false,
         }
     }
 
@@ -167,13 +718,16 @@ impl App for TerminalApp {
             || self.bounds.y != bounds.y
             || self.bounds.w != bounds.w
             || self.bounds.h != bounds.h;
+        let first = self.bounds.w == 0 || self.bounds.h == 0;
 
         self.bounds = bounds;
         self.block.rect = bounds;
 
         if changed {
-            let theme = Theme::dark_modern();
-            self.resize_terminal(&theme);
+            let theme = Theme::current();
+            self.resize_terminal(&theme, first);
+            // Something else was drawn where it now is
+            self.full_redraw = true;
         }
     }
 
@@ -182,10 +736,39 @@ impl App for TerminalApp {
         theme: &Theme,
         out: &mut RenderList,
     ) {
+        if self.terminal.set_theme(theme) {
+            self.full_redraw = true;
+        }
+        let transparent = background::terminal_transparent();
+        // When transparent, the wallpaper went over all of it
+        if self.terminal.set_transparent(transparent) || transparent {
+            self.full_redraw = true;
+        }
+        if self.terminal.take_bell() {
+            bell::ring();
+        }
+        // Repaint the track column away, or from under a thumb that moved
+        let scrolled_back = self.terminal.view_offset() != 0;
+        if scrolled_back != self.scrollbar_shown {
+            self.scrollbar_shown = scrolled_back;
+            self.full_redraw = true;
+        }
+        let stale = self.stale.take();
         if self.full_redraw {
-            out.fill_rect(self.bounds, theme.surface);
+            if !background::terminal_transparent() {
+                out.fill_rect(self.bounds, theme.surface);
+            }
             self.terminal.collect_render_full(out, self.bounds.x, self.bounds.y);
+            self.full_redraw = false;
         } else {
+            if let Some(stale) = stale {
+                // Past the last column and row too, which no row covers
+                out.fill_rect(stale, theme.surface);
+                self.terminal.invalidate_rows(
+                    stale.y - self.bounds.y,
+                    stale.bottom() - self.bounds.y,
+                );
+            }
             self.terminal.collect_render(
                 out,
                 self.bounds.x,
@@ -196,6 +779,36 @@ impl App for TerminalApp {
         }
     }
 
+    fn collect_overlay(&mut self, theme: &Theme, out: &mut RenderList) {
+        if self.terminal.view_offset() != 0 {
+            self.scrollbar().collect_render(theme, false, out);
+        }
+        if let Some(report) = progress::current() {
+            let (bar, panel) = self.progress_bar(&report);
+            out.fill_rounded_rect(panel, 6, theme.surface);
+            let label_y = bar.rect().y + PROGRESS_HEIGHT.saturating_sub(20) / 2;
+            out.text(report.label.as_str(), panel.x + PROGRESS_MARGIN / 2, label_y, theme.text);
+            bar.collect_render(theme, false, out);
+        }
+        if bell::flashing() {
+            out.stroke_rect(self.bounds, theme.accent, BELL_BORDER);
+        }
+    }
+
+    fn collect_dialog(&mut self, theme: &Theme, out: &mut RenderList) {
+        if let Some(picker) = &self.picker {
+            picker.collect_render(self.bounds, theme, out);
+        }
+    }
+
+    fn invalidate(&mut self, rect: Rect) {
+        self.stale = layers::union(self.stale, Some(rect));
+    }
+
+    fn opaque(&self) -> bool {
+        !background::terminal_transparent()
+    }
+
     fn focus_blocks(&mut self) -> &mut [FocusBlock] {
         core::slice::from_mut(&mut self.block)
     }
@@ -205,35 +818,307 @@ impl App for TerminalApp {
     }
 }
 
-/// Simple integer-to-string for mouse coordinates (no alloc formatting).
-fn format_num(n: i32) -> String {
-    if n == 0 {
-        return String::from("0");
+/// Banner line pointing at `help`, which is clickable.
+fn write_banner_hint(terminal: &mut Terminal) {
+    terminal.write("Type '");
+    terminal.write_link("help", "help");
+    terminal.write("' for available commands\n");
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut TerminalApp, code: KeyCode, mods: Modifiers) {
+        app.on_event(AppEvent::KeyPress { code, mods });
+    }
+
+    fn key(app: &mut TerminalApp, ch: char, ctrl: bool) {
+        let mods = if ctrl {
+            Modifiers::CTRL
+        } else {
+            Modifiers::NONE
+        };
+        press(app, KeyCode::Char(ch), mods);
     }
 
-    let mut s = String::new();
-    let mut num = n;
-    let negative = num < 0;
-    if negative {
-        num = -num;
+    fn arrow(app: &mut TerminalApp, dir: Arrow, ctrl: bool) {
+        let mods = if ctrl {
+            Modifiers::CTRL
+        } else {
+            Modifiers::NONE
+        };
+        press(app, KeyCode::Arrow(dir), mods);
     }
 
-    let mut digits = [0u8; 12];
-    let mut i = 0;
-    while num > 0 {
-        digits[i] = (num % 10) as u8;
-        num /= 10;
-        i += 1;
+    /// Shift+Enter runs the line.
+    fn run(app: &mut TerminalApp) {
+        press(app, KeyCode::Enter, Modifiers::SHIFT);
     }
 
-    if negative {
-        s.push('-');
+    /// What the host does with Escape.
+    fn escape(app: &mut TerminalApp) -> bool {
+        app.on_cancel()
+            || app.on_event(AppEvent::KeyPress {
+                code: KeyCode::Escape,
+                mods: Modifiers::NONE,
+            })
     }
 
-    while i > 0 {
-        i -= 1;
-        s.push((b'0' + digits[i]) as char);
+    fn typed(s: &str) -> TerminalApp {
+        let mut app = TerminalApp::new(400, 200);
+        app.init();
+        for ch in s.chars() {
+            key(&mut app, ch, false);
+        }
+        app
     }
 
-    s
+    #[test_case]
+    fn rendered_line_follows_mid_line_edits() {
+        let mut app = typed("echo helo world");
+        let (_, row) = app.terminal.cursor_pos();
+
+        arrow(&mut app, Arrow::Left, true);
+        arrow(&mut app, Arrow::Left, true);
+        for _ in 0..3 {
+            arrow(&mut app, Arrow::Right, false);
+        }
+        key(&mut app, 'l', false);
+        assert_eq!(app.line.text(), "echo hello world");
+        assert_eq!(app.terminal.row_text(row), "> echo hello world");
+        assert_eq!(app.terminal.cursor_pos(), (2 + 9, row));
+
+        press(&mut app, KeyCode::End, Modifiers::NONE);
+        press(&mut app, KeyCode::Backspace, Modifiers::CTRL);
+        press(&mut app, KeyCode::Insert, Modifiers::NONE);
+        press(&mut app, KeyCode::Home, Modifiers::NONE);
+        for ch in "ECHO".chars() {
+            key(&mut app, ch, false);
+        }
+        assert_eq!(app.line.text(), "ECHO hello ");
+        assert_eq!(app.terminal.row_text(row), "> ECHO hello");
+        assert_eq!(app.terminal.cursor_pos(), (2 + 4, row));
+    }
+
+    #[test_case]
+    fn backspace_and_delete_mid_line() {
+        let mut app = typed("lsxx /dev");
+        let (_, row) = app.terminal.cursor_pos();
+
+        press(&mut app, KeyCode::Home, Modifiers::NONE);
+        for _ in 0..3 {
+            arrow(&mut app, Arrow::Right, false);
+        }
+        press(&mut app, KeyCode::Backspace, Modifiers::NONE);
+        press(&mut app, KeyCode::Delete, Modifiers::NONE);
+        assert_eq!(app.line.text(), "ls /dev");
+        assert_eq!(app.terminal.row_text(row), "> ls /dev");
+        assert_eq!(app.terminal.cursor_pos(), (2 + 2, row));
+    }
+
+    #[test_case]
+    fn clicking_a_command_in_help_fills_the_prompt() {
+        let mut app = TerminalApp::new(1000, 800);
+        app.init();
+        for ch in "help".chars() {
+            key(&mut app, ch, false);
+        }
+        run(&mut app);
+
+        let row = (0..40)
+            .find(|&y| app.terminal.row_text(y).starts_with("  meminfo"))
+            .expect("meminfo listed in help");
+        assert!(app.click_at(2 * 10 + 5, row * 20 + 10));
+        assert_eq!(app.line.text(), "meminfo");
+        assert_eq!(app.line.cursor(), 7);
+
+        let (_, prompt_row) = app.terminal.cursor_pos();
+        assert_eq!(app.terminal.row_text(prompt_row), "> meminfo");
+        // plain text is not a link
+        assert!(!app.click_at(30 * 10, row * 20 + 10));
+    }
+
+    #[test_case]
+    fn ctrl_r_finds_and_cancel_restores() {
+        let mut app = TerminalApp::new(800, 400);
+        app.init();
+        for cmd in ["echo hello", "pwd"] {
+            for ch in cmd.chars() {
+                key(&mut app, ch, false);
+            }
+            run(&mut app);
+        }
+        for ch in "draft".chars() {
+            key(&mut app, ch, false);
+        }
+        let (_, row) = app.terminal.cursor_pos();
+
+        key(&mut app, 'r', true);
+        for ch in "ell".chars() {
+            key(&mut app, ch, false);
+        }
+        assert_eq!(app.terminal.row_text(row), "(reverse-i-search)'ell': echo hello");
+        key(&mut app, 'g', true);
+        assert_eq!(app.line.text(), "draft");
+        assert_eq!(app.terminal.row_text(row), "> draft");
+
+        key(&mut app, 'r', true);
+        key(&mut app, 'd', false);
+        assert_eq!(app.terminal.row_text(row), "(reverse-i-search)'d': pwd");
+        key(&mut app, 'r', true);
+        arrow(&mut app, Arrow::Right, false);
+        assert_eq!(app.line.text(), "echo hello");
+        assert_eq!(app.terminal.row_text(row), "> echo hello");
+    }
+
+    #[test_case]
+    fn escape_unwinds_one_level_at_a_time() {
+        let mut app = typed("draft");
+        let (_, row) = app.terminal.cursor_pos();
+
+        key(&mut app, 'r', true);
+        key(&mut app, 'x', false);
+        assert!(app.terminal.row_text(row).contains("reverse-i-search"));
+        assert!(escape(&mut app));
+        assert_eq!(app.terminal.row_text(row), "> draft");
+        assert_eq!(app.line.text(), "draft");
+
+        assert!(escape(&mut app));
+        assert_eq!(app.line.text(), "");
+        assert_eq!(app.terminal.row_text(row), ">");
+
+        assert!(!escape(&mut app));
+        // the shell is still there
+        for ch in "pwd".chars() {
+            key(&mut app, ch, false);
+        }
+        assert_eq!(app.terminal.row_text(row), "> pwd");
+    }
+
+    #[test_case]
+    fn top_hands_the_screen_back() {
+        let mut app = typed("top");
+        let banner = app.terminal.row_text(0);
+        let (_, row) = app.terminal.cursor_pos();
+        run(&mut app);
+
+        let (_, rows) = app.terminal.size();
+        assert!(app.terminal.row_text(0).starts_with("top - up"));
+        assert!(app.terminal.row_text(rows - 1).starts_with("sort busy"));
+        key(&mut app, 'p', false);
+        assert!(app.terminal.row_text(rows - 1).starts_with("sort id"));
+        // typing goes to top, not the shell
+        key(&mut app, 'x', false);
+        assert_eq!(app.line.text(), "");
+
+        key(&mut app, 'q', false);
+        assert_eq!(app.terminal.row_text(0), banner);
+        assert_eq!(app.terminal.row_text(row), "> top");
+        assert_eq!(app.terminal.row_text(row + 1), ">");
+        for ch in "pwd".chars() {
+            key(&mut app, ch, false);
+        }
+        assert_eq!(app.terminal.row_text(row + 1), "> pwd");
+    }
+
+    #[test_case]
+    fn picker_pastes_the_chosen_copy() {
+        clipboard::clear();
+        let mut app = TerminalApp::new(800, 400);
+        app.init();
+        let shortcut = Modifiers::CTRL | Modifiers::SHIFT;
+        for text in ["alpha", "beta", "gamma"] {
+            for ch in text.chars() {
+                key(&mut app, ch, false);
+            }
+            press(&mut app, KeyCode::Char('C'), shortcut);
+            escape(&mut app);
+        }
+        let (_, row) = app.terminal.cursor_pos();
+
+        press(&mut app, KeyCode::Char('V'), shortcut);
+        assert_eq!(app.line.text(), "gamma");
+        // Again at once: the paste is taken back and the picker opens
+        press(&mut app, KeyCode::Char('V'), shortcut);
+        assert_eq!(app.line.text(), "");
+        assert!(app.picker.is_some());
+
+        arrow(&mut app, Arrow::Down, false);
+        press(&mut app, KeyCode::Enter, Modifiers::NONE);
+        assert!(app.picker.is_none());
+        assert_eq!(app.line.text(), "beta");
+        assert_eq!(app.terminal.row_text(row), "> beta");
+        clipboard::clear();
+    }
+
+    #[test_case]
+    fn backspace_stops_at_a_colored_prompt() {
+        prompt::set_template("\\e[36m\\w\\e[0m \\$? \\e[1;33m>\\e[0m ");
+        let mut app = typed("nosuchcmd");
+        run(&mut app);
+        for ch in "ab".chars() {
+            key(&mut app, ch, false);
+        }
+        for _ in 0..6 {
+            press(&mut app, KeyCode::Backspace, Modifiers::NONE);
+        }
+        prompt::reset_template();
+
+        let (x, row) = app.terminal.cursor_pos();
+        assert_eq!(app.line.text(), "");
+        assert_eq!(app.terminal.row_text(row), "/ x >");
+        assert_eq!(x, "/ x > ".len());
+    }
+
+    #[test_case]
+    fn dragging_the_scrollbar_moves_the_view() {
+        use crate::devices::drivers::ps2_mouse::MouseEvent;
+        let mouse = |app: &mut TerminalApp, y: usize, held: bool| {
+            let event = MouseEvent {
+                dx: 0,
+                dy: 0,
+                buttons: held as u8,
+            };
+            app.on_event(AppEvent::Mouse { event, x: 398, y })
+        };
+
+        let mut app = TerminalApp::new(400, 200);
+        app.layout(Rect::new(0, 0, 400, 200));
+        for i in 0..60 {
+            app.terminal.write(&format!("line {}\n", i));
+        }
+        // At the live screen there is no scrollbar to press
+        assert!(!mouse(&mut app, 100, true));
+        mouse(&mut app, 100, false);
+
+        assert!(app.scroll_page(1));
+        let back = app.terminal.scrollback_len();
+        let thumb = app.scrollbar().thumb();
+        assert!(thumb.h < 200 && thumb.bottom() <= 200);
+
+        // Grab the thumb by its top and drag it to the top of the track
+        assert!(mouse(&mut app, thumb.y, true));
+        mouse(&mut app, 0, true);
+        assert_eq!(app.terminal.view_offset(), back);
+        assert_eq!(app.scrollbar().first, 0);
+        assert_eq!(app.terminal.row_text(0), "Terminal");
+
+        // Half way down the travel, the first line is half way too
+        let travel = 200 - thumb.h;
+        mouse(&mut app, travel / 2, true);
+        let first = app.scrollbar().first;
+        assert!(first.abs_diff(back / 2) <= 1, "{} of {}", first, back);
+
+        // Dragged past the bottom: the live screen, and the bar goes
+        mouse(&mut app, 400, true);
+        assert_eq!(app.terminal.view_offset(), 0);
+        mouse(&mut app, 400, false);
+        assert!(app.thumb_grab.is_none());
+        let mut out = RenderList::new();
+        app.collect_overlay(&Theme::current(), &mut out);
+        assert!(out.is_empty());
+    }
 }
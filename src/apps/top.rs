@@ -0,0 +1,229 @@
+//! # Top
+//!
+//! The live view behind the terminal's `top` command: uptime, the idle
+//! share of the last interval, heap use, and one row per task with the
+//! steps it ran in that interval (`task::ActivitySampler`). The terminal
+//! hands the screen over while it runs and puts the shell back after.
+//!
+//! Keys: `q` quits, `p` switches between busiest-first and id order, `s`
+//! steps the refresh interval through 1, 2 and 5 seconds, and `k`, an id
+//! and Enter send that task `term`. Tasks have no priority yet, so id
+//! order (spawn order) stands in for it.
+
+use crate::app::KeyCode;
+use crate::kcore::task::{self, ActivitySampler, Signal, TaskActivity};
+use crate::stats::idle::Snapshot;
+use alloc::{format, string::String, vec::Vec};
+
+const INTERVALS_MS: [u64; 3] = [1_000, 2_000, 5_000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopKey {
+    Redraw,
+    Quit,
+    Ignored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Busiest,
+    Id,
+}
+
+pub struct TopView {
+    sampler: ActivitySampler,
+    rows: Vec<TaskActivity>,
+    sort: Sort,
+    /// Index into `INTERVALS_MS`.
+    interval: usize,
+    idle_from: Snapshot,
+    idle_percent: u64,
+    next_ms: u64,
+    /// Id typed so far after `k`.
+    kill: Option<String>,
+    /// Replaces the key hints until the next key.
+    status: Option<String>,
+}
+
+impl TopView {
+    pub fn new(now_ms: u64) -> Self {
+        let mut view = Self {
+            sampler: ActivitySampler::new(),
+            rows: Vec::new(),
+            sort: Sort::Busiest,
+            interval: 0,
+            idle_from: Snapshot::now(),
+            idle_percent: 0,
+            next_ms: 0,
+            kill: None,
+            status: None,
+        };
+        view.sample(now_ms);
+        view
+    }
+
+    pub fn due(&self, now_ms: u64) -> bool {
+        now_ms >= self.next_ms
+    }
+
+    pub fn sample(&mut self, now_ms: u64) {
+        self.rows = self.sampler.sample(task::info());
+        let now = Snapshot::now();
+        self.idle_percent = now.idle_percent_since(&self.idle_from);
+        self.idle_from = now;
+        self.next_ms = now_ms + INTERVALS_MS[self.interval];
+    }
+
+    pub fn key(&mut self, code: KeyCode) -> TopKey {
+        if let Some(id) = &mut self.kill {
+            match code {
+                KeyCode::Char(ch) if ch.is_ascii_digit() => id.push(ch),
+                KeyCode::Backspace => {
+                    id.pop();
+                }
+                KeyCode::Enter => {
+                    let id = self.kill.take().unwrap_or_default();
+                    self.status = Some(match id.parse() {
+                        Ok(id) if task::send_signal(id, Signal::Terminate) => {
+                            format!("sent term to task {}", id)
+                        }
+                        _ => format!("no task '{}'", id),
+                    });
+                }
+                _ => return TopKey::Ignored,
+            }
+            return TopKey::Redraw;
+        }
+
+        self.status = None;
+        match code {
+            KeyCode::Char('q') => return TopKey::Quit,
+            KeyCode::Char('p') => {
+                self.sort = match self.sort {
+                    Sort::Busiest => Sort::Id,
+                    Sort::Id => Sort::Busiest,
+                };
+            }
+            KeyCode::Char('s') => self.interval = (self.interval + 1) % INTERVALS_MS.len(),
+            KeyCode::Char('k') => self.kill = Some(String::new()),
+            _ => return TopKey::Ignored,
+        }
+        TopKey::Redraw
+    }
+
+    /// Drops a half-typed `k`. False if there was none.
+    pub fn cancel(&mut self) -> bool {
+        self.kill.take().is_some()
+    }
+
+    /// The screen as `rows` lines of at most `cols` characters. The first
+    /// is the header; tasks that do not fit are counted on the last row
+    /// before the footer.
+    pub fn render(&self, uptime_ms: u64, cols: usize, rows: usize) -> Vec<String> {
+        let secs = uptime_ms / 1_000;
+        let (used, size) = crate::memory::heap_usage();
+        let mut lines = Vec::with_capacity(rows);
+        lines.push(format!(
+            "top - up {}:{:02}:{:02}  idle {}%  heap {}/{} KiB",
+            secs / 3_600,
+            secs / 60 % 60,
+            secs % 60,
+            self.idle_percent,
+            used / 1024,
+            size / 1024
+        ));
+        lines.push(format!("{:>5} {:>8} {:>10}  NAME", "ID", "RECENT", "STEPS"));
+
+        let mut tasks: Vec<&TaskActivity> = self.rows.iter().collect();
+        if self.sort == Sort::Id {
+            tasks.sort_by_key(|t| t.id);
+        }
+        let room = rows.saturating_sub(3);
+        let shown = if tasks.len() > room {
+            room.saturating_sub(1)
+        } else {
+            tasks.len()
+        };
+        for t in &tasks[..shown] {
+            lines.push(format!(
+                "{:>5} {:>8} {:>10}  {}",
+                t.id, t.recent, t.steps, t.name
+            ));
+        }
+        if shown < tasks.len() {
+            lines.push(format!("  ... {} more", tasks.len() - shown));
+        } else if tasks.is_empty() {
+            lines.push(String::from("  (no tasks)"));
+        }
+
+        lines.truncate(rows.saturating_sub(1));
+        while lines.len() + 1 < rows {
+            lines.push(String::new());
+        }
+        lines.push(match (&self.kill, &self.status) {
+            (Some(id), _) => format!("kill task: {}", id),
+            (None, Some(status)) => status.clone(),
+            (None, None) => format!(
+                "sort {} | every {}s | q p s k",
+                match self.sort {
+                    Sort::Busiest => "busy",
+                    Sort::Id => "id",
+                },
+                INTERVALS_MS[self.interval] / 1_000
+            ),
+        });
+        for line in &mut lines {
+            if let Some((cut, _)) = line.char_indices().nth(cols) {
+                line.truncate(cut);
+            }
+        }
+        lines
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(id: u64, recent: u64) -> TaskActivity {
+        TaskActivity {
+            id,
+            name: String::from("t"),
+            steps: 100 + recent,
+            recent,
+        }
+    }
+
+    #[test_case]
+    fn more_tasks_than_rows_are_counted() {
+        let mut view = TopView::new(0);
+        view.rows = (1..=10).map(|id| activity(id, 10 - id)).collect();
+
+        let lines = view.render(3_723_000, 60, 8);
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].starts_with("top - up 1:02:03"));
+        // header, column titles, 4 tasks, the count, the footer
+        assert!(lines[2].trim_start().starts_with("1 "));
+        assert_eq!(lines[6], "  ... 6 more");
+        assert!(lines[7].starts_with("sort busy"));
+
+        view.key(KeyCode::Char('p'));
+        let lines = view.render(0, 20, 8);
+        assert!(lines[7].starts_with("sort id"));
+        assert!(lines.iter().all(|l| l.chars().count() <= 20));
+    }
+
+    #[test_case]
+    fn kill_prompt_collects_an_id() {
+        let mut view = TopView::new(0);
+        assert_eq!(view.key(KeyCode::Char('k')), TopKey::Redraw);
+        view.key(KeyCode::Char('9'));
+        view.key(KeyCode::Char('x'));
+        assert_eq!(view.render(0, 40, 5)[4], "kill task: 9");
+        assert!(view.cancel());
+        assert!(!view.cancel());
+        assert_eq!(view.key(KeyCode::Char('q')), TopKey::Quit);
+    }
+}
@@ -1,17 +1,169 @@
 use alloc::{
+    boxed::Box,
     format,
     string::{String, ToString},
+    vec::Vec,
 };
 use core::str::SplitWhitespace;
 
+use crate::apps::jobs::{JobBody, Progress};
+use crate::terminal_v2::format_link;
+
+/// Working directory for `cd`/`pwd` and the prompt's `\w`. There is no
+/// filesystem yet, so `cd` only normalizes paths; it cannot check that a
+/// directory exists.
+static CWD: spin::Mutex<String> = spin::Mutex::new(String::new());
+
+pub fn cwd() -> String {
+    let cwd = CWD.lock();
+    if cwd.is_empty() {
+        String::from("/")
+    } else {
+        cwd.clone()
+    }
+}
+
+/// Resolves `path` against `cwd`, folding `.` and `..`.
+fn resolve_path(cwd: &str, path: &str) -> String {
+    let mut parts: alloc::vec::Vec<&str> = if path.starts_with('/') {
+        alloc::vec::Vec::new()
+    } else {
+        cwd.split('/').filter(|p| !p.is_empty()).collect()
+    };
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            name => parts.push(name),
+        }
+    }
+    let mut out = String::new();
+    for part in parts {
+        out.push('/');
+        out.push_str(part);
+    }
+    if out.is_empty() {
+        out.push('/');
+    }
+    out
+}
+
 pub enum CommandResult {
     Output(String),
     Error(String),
     Exit,
+    /// Start the live `top` view (the terminal app runs it).
+    Top,
 }
 
 pub struct CommandExecutor;
 
+/// Whether a command may run as a background job, `<command> &`. Jobs
+/// cannot take keyboard input, and most run the whole command in one
+/// step, so only quick commands that only report opt in. `test` and
+/// `bench alloc` also run as jobs, one test or round per step (see
+/// `job_body`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Run {
+    /// Only at the prompt.
+    Fg,
+    /// At the prompt or as a job.
+    Bg,
+}
+
+/// `help` lines: usage (command name first), description, and whether the
+/// command can run as a job.
+const HELP: &[(&str, &str, Run)] = &[
+    ("help", "show this message", Run::Fg),
+    ("test [list|run <pattern|tag> [--reverse]]", "run all or selected tests", Run::Bg),
+    ("test_paging", "test paging", Run::Fg),
+    ("test_process", "test process creation", Run::Fg),
+    ("test_memory", "test memory allocation", Run::Fg),
+    ("test_asm", "run all ASM tests", Run::Fg),
+    ("test_asm_return", "test ASM return value", Run::Fg),
+    ("test_asm_add", "test ASM addition", Run::Fg),
+    ("test_sgr", "bold, underline and inverse over the eight colours", Run::Bg),
+    ("vm_help", "show VM language reference", Run::Bg),
+    ("vm_demo", "show the built-in demo program", Run::Bg),
+    ("vm_demo_advanced", "show the advanced demo program", Run::Bg),
+    ("vm_run <src>", "run a VM program (use ; between instructions)", Run::Fg),
+    ("echo <text>", "echo text", Run::Bg),
+    ("info", "kernel information", Run::Bg),
+    ("status", "boot status of kernel components", Run::Bg),
+    ("lsdev", "drivers with their status and init time", Run::Bg),
+    ("dev retry|disable|enable <name>", "start a driver that failed, or suspend and resume one", Run::Fg),
+    ("latency [on|off|reset|delay <ms>]", "input latency histogram", Run::Fg),
+    ("dmesg [n|-c]", "show kernel log (last n lines, -c clears)", Run::Bg),
+    ("vmmap [lo hi]", "list mapped regions (optionally a hex range)", Run::Bg),
+    ("jitstat", "live and freed executable mappings", Run::Bg),
+    ("pstart [count] [abs|pic|based]", "load a program as count processes, each at its own base", Run::Fg),
+    ("ps", "list process code regions", Run::Bg),
+    ("caps <pid>", "show the syscall capabilities of a process", Run::Fg),
+    ("setcap <pid> [-]<cap>", "grant, or with -, revoke surface|spawn", Run::Fg),
+    ("surface [demo [frames]]", "list client surfaces, or run the gradient demo", Run::Fg),
+    ("meminfo", "frame allocator, page-table counts and memory pressure", Run::Bg),
+    ("memtrim [high|critical]", "have the caches trimmed as if memory were low", Run::Fg),
+    ("memtrim limit <KiB|off>", "measure heap pressure against a smaller heap", Run::Fg),
+    ("top", "live task, idle and heap view (q quits)", Run::Fg),
+    ("allocator [fixed|freelist|bump|buddy]", "show or switch the heap allocator", Run::Fg),
+    ("stacks", "stack high-water marks", Run::Bg),
+    ("acpi", "ACPI tables found at boot", Run::Bg),
+    ("irq", "interrupt controller, IRQ routes and dropped keyboard input", Run::Bg),
+    ("hpet", "HPET frequency and counter", Run::Bg),
+    ("regs", "CR0, CR2, CR3, CR4, EFER and RFLAGS, bit by bit", Run::Bg),
+    ("smp [status]", "processors, AP heartbeats and jobs", Run::Fg),
+    ("smp on|off", "start the APs at boot, from the next boot", Run::Fg),
+    ("smp run bench alloc [n]", "run the allocation benchmark on an AP", Run::Fg),
+    ("tasks [spawn [ticks]|keywait]", "list kernel tasks, start a demo ticker (18 ticks ~ 1 s) or wait for a key", Run::Fg),
+    ("demo_progress [ticks]", "move the terminal's progress bar 0-100% over ticks (default 36, ~2 s)", Run::Fg),
+    ("signal <id> <sig>", "send term|int|timer|key|user to a task", Run::Fg),
+    ("renderstat", "text arena usage, rows written, frame pacing, escape errors", Run::Bg),
+    ("layers", "overlay layers bottom to top, their sources and last damage", Run::Bg),
+    ("background [theme|<hex>|gradient <top> <bottom>|image <file>]", "set the wallpaper, or show it", Run::Fg),
+    ("background terminal opaque|transparent", "let the wallpaper show behind terminal text", Run::Fg),
+    ("transparency [<app> <alpha>]", "draw an app at alpha 32-255 over the background", Run::Fg),
+    ("macro [list|bind <name> <F5-F8>|delete <name>]", "key macros (Ctrl+Alt+R records one)", Run::Fg),
+    ("show <file> [<w>x<h>]|off", "show a P6 PPM or 24-bit BMP centred on screen, smoothly resized", Run::Fg),
+    ("fps [cap <30|60|off>|budget <us>]", "cap animation-only frames (input still draws at once), or set the flush budget (0: none)", Run::Fg),
+    ("contrast [on|off]", "high-contrast theme and focus ring", Run::Fg),
+    ("color [<field> <#rrggbb>|reset]", "show or set a theme colour", Run::Fg),
+    ("rotate [0|90|180|270]", "turn the screen clockwise, from the next boot", Run::Fg),
+    ("bell [visual|audible|both|off]", "what BEL does, then ring it", Run::Fg),
+    ("notify <message>", "show the message in a toast for about 3 s", Run::Bg),
+    ("mouse [reset|threshold <n>]", "desyncs; reset now, or by itself after n bad bytes (0 never)", Run::Fg),
+    ("mousestat", "mouse packets in against coalesced events out", Run::Bg),
+    ("sync", "save /config and the /log tail across a warm reboot", Run::Fg),
+    ("reboot", "sync and reboot", Run::Fg),
+    ("poweroff", "sync and power off (ACPI)", Run::Fg),
+    ("crash [message]", "panic on purpose; a warm reboot shows it in /log/lastpanic.txt", Run::Fg),
+    ("crash in <render|println>", "debug builds: panic from inside a frame or a println", Run::Fg),
+    ("fault <divide|ud2|int3|page|gp>", "raise an exception and recover from it", Run::Fg),
+    ("fault policy [strict|lenient]", "show or set whether a faulting program panics the kernel", Run::Fg),
+    ("random [max]", "a random number (below max if given)", Run::Bg),
+    ("seed <value>", "reseed the RNG for a repeatable sequence", Run::Fg),
+    ("aslr [on|off]", "random gaps between mmap placements", Run::Fg),
+    ("bench render [rounds]", "time full-screen redraws", Run::Fg),
+    ("bench alloc [rounds]", "time and verify heap allocations", Run::Bg),
+    ("leaks [mark|clear]", "outstanding heap allocations by caller", Run::Bg),
+    ("cd [dir]", "change the working directory", Run::Fg),
+    ("pwd", "print the working directory", Run::Bg),
+    ("write <file> <text>", "write text to a ramfs file (\\n for newlines)", Run::Fg),
+    ("cat <file>", "print a ramfs file", Run::Bg),
+    ("diff <a> <b>", "compare two ramfs files line by line", Run::Bg),
+    ("prompt [template|reset]", "show or set the prompt (\\w \\t \\$? \\e)", Run::Fg),
+    ("clipboard [clear]", "recent copies, newest first (Ctrl+Shift+C/V/H)", Run::Fg),
+    ("jobs", "background jobs (start one by ending a line with &)", Run::Fg),
+    ("fg <job> [page]", "show a job's output; a finished job goes once read", Run::Fg),
+    ("kill %<job>", "stop a background job", Run::Fg),
+    ("clear [-f]", "clear terminal; -f also drops the scrollback", Run::Fg),
+    ("exit", "exit (no-op)", Run::Fg),
+];
+
+/// Column the descriptions in `help` line up at.
+const HELP_COLUMN: usize = 18;
+
 impl CommandExecutor {
     pub fn execute(input: &str) -> CommandResult {
         let trimmed = input.trim();
@@ -20,6 +172,10 @@ impl CommandExecutor {
             return CommandResult::Output(String::new());
         }
 
+        if let Some(line) = trimmed.strip_suffix('&') {
+            return Self::start_job(line.trim_end());
+        }
+
         let mut parts = trimmed.split_whitespace();
         let cmd = match parts.next() {
             Some(c) => c,
@@ -28,50 +184,220 @@ impl CommandExecutor {
 
         match cmd {
             "help" => Self::help(parts),
-            "test" => Self::test_all(),
+            "test" => Self::test(parts),
             "test_paging" => Self::test_paging(),
             "test_process" => Self::test_process(),
             "test_memory" => Self::test_memory(),
             "test_asm" => Self::test_asm(),
             "test_asm_return" => Self::test_asm_return(),
             "test_asm_add" => Self::test_asm_add(),
+            "test_sgr" => Self::test_sgr(),
             "vm_help" => Self::vm_help(),
             "vm_demo" => Self::vm_demo(),
             "vm_demo_advanced" => Self::vm_demo_advanced(),
             "vm_run" => Self::vm_run(trimmed),
-            "clear" => CommandResult::Output(String::from("\x1b[2J\x1b[H")),
+            "clear" => Self::clear(parts),
             "echo" => Self::echo(parts),
             "info" => Self::info(),
-            "exit" => CommandResult::Exit,
-            _ => {
-                let mut msg = String::from("Unknown command: ");
-                msg.push_str(cmd);
-                CommandResult::Error(msg)
+            "dmesg" => Self::dmesg(parts),
+            "vmmap" => Self::vmmap(parts),
+            "jitstat" => CommandResult::Output(crate::memory::jit::report()),
+            "pstart" => Self::pstart(parts),
+            "ps" => Self::ps(),
+            "caps" => Self::caps(parts),
+            "setcap" => Self::setcap(parts),
+            "surface" => Self::surface(parts),
+            "meminfo" => CommandResult::Output(format!(
+                "{}{}",
+                crate::memory::address_space::report(),
+                crate::memory::pressure::report()
+            )),
+            "memtrim" => Self::memtrim(parts),
+            "stacks" => CommandResult::Output(crate::stats::stacks::report()),
+            "acpi" => CommandResult::Output(crate::kcore::acpi::report()),
+            "irq" => CommandResult::Output(format!(
+                "{}\n{}",
+                crate::kcore::interrupts::ioapic::report().trim_end(),
+                crate::devices::drivers::ps2_keyboard::report()
+            )),
+            "hpet" => CommandResult::Output(crate::devices::drivers::hpet::report()),
+            "regs" => CommandResult::Output(crate::kcore::regs::report()),
+            "smp" => Self::smp(parts),
+            "tasks" => Self::tasks(parts),
+            "top" => CommandResult::Top,
+            "demo_progress" => Self::demo_progress(parts),
+            "signal" => Self::signal(parts),
+            "renderstat" => CommandResult::Output(format!(
+                "{}\nFramebuffer: {} rows written last frame, {} tiles held over (budget {} us)\n{}\n{}\n{}",
+                crate::ui_provider::frame_arena::report(),
+                crate::devices::framebuffer::framebuffer::rows_written(),
+                crate::devices::framebuffer::framebuffer::backlog(),
+                crate::devices::framebuffer::framebuffer::flush_budget_us(),
+                crate::ui_provider::pacing::report(),
+                crate::terminal_v2::escape_report(),
+                crate::ui_provider::glyphs::report()
+            )),
+            "layers" => CommandResult::Output(crate::ui_provider::layers::report()),
+            "show" => Self::show(parts),
+            "background" => Self::background(parts),
+            "macro" => Self::key_macro(parts),
+            "transparency" => Self::transparency(parts),
+            "fps" => Self::fps(parts),
+            "allocator" => Self::allocator(parts),
+            "contrast" => Self::contrast(parts),
+            "color" => Self::color(parts),
+            "rotate" => Self::rotate(parts),
+            "bell" => Self::bell(parts),
+            "notify" => Self::notify(trimmed),
+            "mouse" => Self::mouse(parts),
+            "lsdev" => CommandResult::Output(crate::devices::registry::report()),
+            "dev" => Self::dev(parts),
+            "mousestat" => {
+                CommandResult::Output(crate::devices::drivers::ps2_mouse::coalesce_report())
             }
+            "sync" => match Self::sync_state() {
+                Ok(out) => CommandResult::Output(out),
+                Err(err) => CommandResult::Error(err),
+            },
+            "reboot" | "poweroff" => Self::power(cmd),
+            "crash" => match trimmed["crash".len()..].trim() {
+                "" => panic!("crash command"),
+                rest if rest.starts_with("in ") => Self::crash_in(rest[3..].trim()),
+                message => panic!("{}", message),
+            },
+            "fault" => Self::fault(parts),
+            "random" => Self::random(parts),
+            "seed" => Self::seed(parts),
+            "aslr" => Self::aslr(parts),
+            "leaks" => Self::leaks(parts),
+            "status" => Self::status(),
+            "latency" => Self::latency(parts),
+            "bench" => Self::bench(parts),
+            "cd" => Self::cd(parts),
+            "pwd" => CommandResult::Output(cwd()),
+            "prompt" => Self::prompt(trimmed),
+            "clipboard" => Self::clipboard(parts),
+            "write" => Self::write(trimmed),
+            "cat" => Self::cat(parts),
+            "diff" => Self::diff(parts),
+            "jobs" => CommandResult::Output(crate::apps::jobs::report()),
+            "fg" => Self::fg(parts),
+            "kill" => Self::kill(parts),
+            "exit" => CommandResult::Exit,
+            _ => Self::unknown(cmd),
+        }
+    }
+
+    fn unknown(cmd: &str) -> CommandResult {
+        let mut msg = String::from("Unknown command: ");
+        msg.push_str(cmd);
+        if let Some(name) = suggest(cmd) {
+            msg.push_str("\nDid you mean ");
+            msg.push_str(&format_link(name, name));
+            msg.push('?');
         }
+        CommandResult::Error(msg)
     }
 
     // ── help ──────────────────────────────────────────────────────────────────
 
     fn help(_args: SplitWhitespace) -> CommandResult {
-        let text = "Available commands:\n  \
-            help              show this message\n  \
-            test              run all tests\n  \
-            test_paging       test paging\n  \
-            test_process      test process creation\n  \
-            test_memory       test memory allocation\n  \
-            test_asm          run all ASM tests\n  \
-            test_asm_return   test ASM return value\n  \
-            test_asm_add      test ASM addition\n  \
-            vm_help           show VM language reference\n  \
-            vm_demo           show the built-in demo program\n  \
-            vm_demo_advanced  show the advanced demo program\n  \
-            vm_run <src>      run a VM program (use ; between instructions)\n  \
-            echo <text>       echo text\n  \
-            info              kernel information\n  \
-            clear             clear terminal\n  \
-            exit              exit (no-op)";
-        CommandResult::Output(String::from(text))
+        let mut text = String::from("Available commands:");
+        for (usage, desc, _) in HELP {
+            let (name, args) = usage.split_once(' ').unwrap_or((usage, ""));
+            text.push_str("\n  ");
+            text.push_str(&format_link(name, name));
+            if !args.is_empty() {
+                text.push(' ');
+                text.push_str(args);
+            }
+            let pad = HELP_COLUMN.saturating_sub(usage.len()).max(2);
+            text.push_str(&format!("{:pad$}{}", "", desc, pad = pad));
+        }
+        CommandResult::Output(text)
+    }
+
+    // ── jobs ──────────────────────────────────────────────────────────────────
+
+    /// Runs `line` as a background job if its command has opted in.
+    fn start_job(line: &str) -> CommandResult {
+        use crate::apps::jobs;
+
+        let Some(cmd) = line.split_whitespace().next() else {
+            return CommandResult::Error(String::from("&: no command to run"));
+        };
+        let known = HELP.iter().filter(|(usage, _, _)| usage.split(' ').next() == Some(cmd));
+        match known.map(|&(_, _, run)| run).max_by_key(|&run| run == Run::Bg) {
+            None => return Self::unknown(cmd),
+            Some(Run::Fg) => {
+                return CommandResult::Error(format!("{}: cannot run in the background", cmd))
+            }
+            Some(Run::Bg) => {}
+        }
+
+        let body = match Self::job_body(line) {
+            Ok(body) => body,
+            Err(result) => return result,
+        };
+        let (id, task) = jobs::start(line, body);
+        CommandResult::Output(format!("[{}] task {}", id, task))
+    }
+
+    /// How a job runs `line`: `test` one test per step, `bench alloc` one
+    /// round per step, anything else in a single step.
+    pub(crate) fn job_body(line: &str) -> Result<JobBody, CommandResult> {
+        let mut args = line.split_whitespace();
+        match args.next() {
+            Some("test") => Self::test_job(args),
+            Some("bench") => Self::bench_job(args),
+            _ => {
+                let command = String::from(line);
+                Ok(Box::new(move |out: &mut String| {
+                    Self::job_output(Self::execute(&command), out)
+                }))
+            }
+        }
+    }
+
+    /// Appends a command's result to a job's output and ends the job.
+    fn job_output(result: CommandResult, out: &mut String) -> Progress {
+        match result {
+            CommandResult::Output(text) => {
+                out.push_str(&text);
+                Progress::Done(true)
+            }
+            CommandResult::Error(err) => {
+                out.push_str("Error: ");
+                out.push_str(&err);
+                Progress::Done(false)
+            }
+            CommandResult::Exit | CommandResult::Top => Progress::Done(true),
+        }
+    }
+
+    fn fg(mut args: SplitWhitespace) -> CommandResult {
+        let Some(id) = args.next().and_then(|id| id.trim_start_matches('%').parse().ok()) else {
+            return CommandResult::Error(String::from("Usage: fg <job> [page]"));
+        };
+        let page = match args.next().map(str::parse::<usize>) {
+            None => 1,
+            Some(Ok(page)) => page,
+            Some(Err(_)) => return CommandResult::Error(String::from("Usage: fg <job> [page]")),
+        };
+        match crate::apps::jobs::fg(id, page) {
+            Ok(out) => CommandResult::Output(out),
+            Err(err) => CommandResult::Error(err),
+        }
+    }
+
+    fn kill(mut args: SplitWhitespace) -> CommandResult {
+        let Some(id) = args.next().and_then(|arg| arg.strip_prefix('%')).and_then(|id| id.parse().ok()) else {
+            return CommandResult::Error(String::from("Usage: kill %<job> (for tasks, see 'signal')"));
+        };
+        match crate::apps::jobs::kill(id) {
+            Ok(()) => CommandResult::Output(format!("sent term to job %{}", id)),
+            Err(err) => CommandResult::Error(err),
+        }
     }
 
     fn echo(mut args: SplitWhitespace) -> CommandResult {
@@ -94,6 +420,986 @@ impl CommandExecutor {
         ))
     }
 
+    fn status() -> CommandResult {
+        use crate::kcore::kernel::status::get_all_statuses;
+
+        let mut out = String::from("Component            Status\n");
+        for component in get_all_statuses() {
+            out.push_str(&format!("{:<20} {}\n", component.name, component.status));
+        }
+        CommandResult::Output(out)
+    }
+
+    fn latency(mut args: SplitWhitespace) -> CommandResult {
+        use crate::stats::latency;
+
+        match args.next() {
+            None => {}
+            Some("on") => latency::enable(),
+            Some("off") => latency::disable(),
+            Some("reset") => latency::reset(),
+            Some("delay") => match args.next().and_then(|ms| ms.parse::<u64>().ok()) {
+                Some(ms) => latency::set_render_delay_ms(ms),
+                None => return CommandResult::Error(String::from("Usage: latency delay <ms>")),
+            },
+            Some(other) => {
+                return CommandResult::Error(format!("latency: unknown option '{}'", other))
+            }
+        }
+        CommandResult::Output(latency::report())
+    }
+
+    fn cd(mut args: SplitWhitespace) -> CommandResult {
+        let target = resolve_path(&cwd(), args.next().unwrap_or("/"));
+        *CWD.lock() = target;
+        CommandResult::Output(String::new())
+    }
+
+    /// Takes the whole line so the text keeps its spaces. `\n` becomes a
+    /// newline and one is added at the end, as `echo text > file` would.
+    fn write(line: &str) -> CommandResult {
+        let mut args = line["write".len()..].trim_start().splitn(2, ' ');
+        let Some(path) = args.next().filter(|p| !p.is_empty()) else {
+            return CommandResult::Error(String::from("usage: write <file> <text>"));
+        };
+        let mut text = args.next().unwrap_or("").replace("\\n", "\n");
+        text.push('\n');
+        let path = resolve_path(&cwd(), path);
+        match crate::fs::ramfs::write(&path, text.as_bytes()) {
+            Ok(()) => CommandResult::Output(String::new()),
+            Err(_) => CommandResult::Error(format!("write: invalid path {}", path)),
+        }
+    }
+
+    fn read_file(path: &str) -> Result<String, CommandResult> {
+        let path = resolve_path(&cwd(), path);
+        let data = crate::fs::ramfs::read(&path)
+            .map_err(|_| CommandResult::Error(format!("{}: no such file", path)))?;
+        String::from_utf8(data)
+            .map_err(|_| CommandResult::Error(format!("{}: not a text file", path)))
+    }
+
+    fn cat(mut args: SplitWhitespace) -> CommandResult {
+        let Some(path) = args.next() else {
+            return CommandResult::Error(String::from("usage: cat <file>"));
+        };
+        match Self::read_file(path) {
+            Ok(text) => CommandResult::Output(text),
+            Err(err) => err,
+        }
+    }
+
+    fn diff(mut args: SplitWhitespace) -> CommandResult {
+        use crate::util::diff::{self, Summary};
+
+        let (Some(a_path), Some(b_path)) = (args.next(), args.next()) else {
+            return CommandResult::Error(String::from("usage: diff <a> <b>"));
+        };
+        let a = match Self::read_file(a_path) {
+            Ok(text) => text,
+            Err(err) => return err,
+        };
+        let b = match Self::read_file(b_path) {
+            Ok(text) => text,
+            Err(err) => return err,
+        };
+        if diff::lines(&a).len() > diff::MAX_LINES || diff::lines(&b).len() > diff::MAX_LINES {
+            return CommandResult::Error(format!("diff: files over {} lines", diff::MAX_LINES));
+        }
+
+        let lines = diff::diff_lines(&a, &b);
+        let summary = Summary::of(&lines);
+        if summary.is_empty() {
+            return CommandResult::Output(String::from("files are identical"));
+        }
+        let mut out = format!("--- {}\n+++ {}\n", a_path, b_path);
+        out.push_str(&diff::unified(&lines, 3, true));
+        out.push_str(&format!("{} removed, {} added", summary.removed, summary.added));
+        CommandResult::Output(out)
+    }
+
+    /// Takes the whole line: the template keeps its spaces. Surrounding
+    /// double quotes are dropped, so a trailing space can be given.
+    fn prompt(line: &str) -> CommandResult {
+        use crate::apps::prompt;
+
+        let arg = line["prompt".len()..].trim();
+        match arg {
+            "" => CommandResult::Output(format!(
+                "prompt: \"{}\"\ntokens: \\w cwd, \\t time, \\$? last status, \\e ESC, \\n newline",
+                prompt::template()
+            )),
+            "reset" => {
+                prompt::reset_template();
+                CommandResult::Output(String::new())
+            }
+            template => {
+                let template = template
+                    .strip_prefix('"')
+                    .and_then(|t| t.strip_suffix('"'))
+                    .unwrap_or(template);
+                prompt::set_template(template);
+                CommandResult::Output(String::new())
+            }
+        }
+    }
+
+    fn contrast(mut args: SplitWhitespace) -> CommandResult {
+        use crate::ui_provider::theme;
+
+        match args.next() {
+            None => {}
+            Some(value @ ("on" | "off")) => {
+                theme::set_high_contrast(value == "on");
+                let _ = crate::fs::ramfs::write(theme::CONTRAST_CONFIG, value.as_bytes());
+            }
+            Some(_) => return CommandResult::Error(String::from("Usage: contrast [on|off]")),
+        }
+        let state = if theme::high_contrast_enabled() { "on" } else { "off" };
+        CommandResult::Output(format!("high contrast: {}", state))
+    }
+
+    fn color(mut args: SplitWhitespace) -> CommandResult {
+        use crate::ui_provider::background::parse_color;
+        use crate::ui_provider::theme::{self, ColorField, Theme};
+
+        const USAGE: &str = "Usage: color [<field> <#rrggbb>|reset]";
+        let Some(name) = args.next() else {
+            let current = Theme::current();
+            let custom = theme::custom_colors();
+            let mut out = String::new();
+            for field in ColorField::ALL {
+                let c = current.color(field);
+                let mark = if custom.iter().any(|(f, _)| *f == field) { " (custom)" } else { "" };
+                out.push_str(&format!("{:<10}  #{:02x}{:02x}{:02x}{}\n", field.name(), c.r, c.g, c.b, mark));
+            }
+            return CommandResult::Output(out);
+        };
+        let Some(field) = ColorField::parse(name) else {
+            return CommandResult::Error(format!("color: no field '{}'; fields: {}", name, ColorField::names()));
+        };
+        let color = match args.next() {
+            Some("reset") => None,
+            Some(hex) => match parse_color(hex) {
+                Some(color) => Some(color),
+                None => return CommandResult::Error(format!("color: '{}' is not #rrggbb", hex)),
+            },
+            None => return CommandResult::Error(String::from(USAGE)),
+        };
+        theme::set_custom_color(field, color);
+        theme::save_colors();
+        let c = Theme::current().color(field);
+        CommandResult::Output(format!("{}: #{:02x}{:02x}{:02x}", field.name(), c.r, c.g, c.b))
+    }
+
+    /// Arms a panic at a `kcore::panic::Site`; the next frame or println
+    /// sets it off.
+    fn crash_in(site: &str) -> CommandResult {
+        use crate::kcore::panic::{self, Site};
+
+        let Some(site) = Site::parse(site) else {
+            return CommandResult::Error(String::from("Usage: crash in <render|println>"));
+        };
+        if !cfg!(debug_assertions) {
+            return CommandResult::Error(String::from("crash in: only debug builds check for it"));
+        }
+        panic::arm(site);
+        CommandResult::Output(format!("crash: armed in {}", site.name()))
+    }
+
+    fn rotate(mut args: SplitWhitespace) -> CommandResult {
+        use crate::devices::framebuffer::framebuffer::{self as fb, Rotation, ROTATION_CONFIG};
+
+        let current = fb::with_fb(|fb| fb.rotation()).unwrap_or(Rotation::Deg0);
+        let Some(value) = args.next() else {
+            let saved = fb::saved_rotation().unwrap_or(Rotation::Deg0);
+            return CommandResult::Output(format!(
+                "rotation: {} degrees, {} from the next boot",
+                current.degrees(),
+                saved.degrees()
+            ));
+        };
+        let Some(rotation) = value.parse().ok().and_then(Rotation::from_degrees) else {
+            return CommandResult::Error(String::from("Usage: rotate [0|90|180|270]"));
+        };
+        let _ = crate::fs::ramfs::write(ROTATION_CONFIG, value.as_bytes());
+        CommandResult::Output(format!(
+            "rotation: {} degrees from the next boot (sync to keep it); now {}",
+            rotation.degrees(),
+            current.degrees()
+        ))
+    }
+
+    fn show(mut args: SplitWhitespace) -> CommandResult {
+        use crate::ui_provider::image;
+        const USAGE: &str = "Usage: show <file> [<w>x<h>]|off";
+
+        let path = match args.next() {
+            None => return CommandResult::Error(String::from(USAGE)),
+            Some("off") => {
+                let text = if image::hide() { "image hidden" } else { "no image shown" };
+                return CommandResult::Output(String::from(text));
+            }
+            Some(path) => resolve_path(&cwd(), path),
+        };
+        let in_range = |side: usize| (1..=image::MAX_DIM).contains(&side);
+        let size = match args.next().map(|size| size.split_once('x')) {
+            None => None,
+            Some(Some((w, h))) => match (w.parse(), h.parse()) {
+                (Ok(w), Ok(h)) if in_range(w) && in_range(h) => Some((w, h)),
+                _ => return CommandResult::Error(String::from(USAGE)),
+            },
+            Some(None) => return CommandResult::Error(String::from(USAGE)),
+        };
+        let Ok(data) = crate::fs::ramfs::read(&path) else {
+            return CommandResult::Error(format!("{}: no such file", path));
+        };
+        let mut decoded = match image::decode(&data) {
+            Ok(decoded) => decoded,
+            Err(err) => return CommandResult::Error(format!("{}: {}", path, err)),
+        };
+        if let Some((w, h)) = size {
+            decoded = decoded.scaled(w, h);
+        }
+        let Ok((w, h)) = crate::devices::framebuffer::framebuffer::with_fb(|fb| (fb.width, fb.height))
+        else {
+            return CommandResult::Error(String::from("show: no framebuffer"));
+        };
+        image::show(&decoded, w, h);
+        let note = if decoded.w > w || decoded.h > h { ", scaled to fit" } else { "" };
+        CommandResult::Output(format!(
+            "{}: {}x{}{} (show off hides it)",
+            path, decoded.w, decoded.h, note
+        ))
+    }
+
+    fn transparency(mut args: SplitWhitespace) -> CommandResult {
+        use crate::app::compositor;
+        use crate::ui_provider::layout::TAB_NAMES;
+        const USAGE: &str = "Usage: transparency [<app> <alpha 0-255>]";
+
+        let (app, alpha) = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => return CommandResult::Output(compositor::report(&TAB_NAMES)),
+            (Some(app), Some(alpha), None) => (app, alpha),
+            _ => return CommandResult::Error(String::from(USAGE)),
+        };
+        let Some(idx) = TAB_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(app))
+        else {
+            return CommandResult::Error(format!(
+                "transparency: no app {} (one of {})",
+                app,
+                TAB_NAMES.join(", ")
+            ));
+        };
+        let Ok(alpha) = alpha.parse::<u8>() else {
+            return CommandResult::Error(String::from(USAGE));
+        };
+        let set = compositor::set_alpha(idx, alpha);
+        CommandResult::Output(format!("{}: alpha {}", TAB_NAMES[idx], set))
+    }
+
+    fn key_macro(mut args: SplitWhitespace) -> CommandResult {
+        use crate::app::macros;
+        const USAGE: &str = "Usage: macro [list|bind <name> <F5-F8>|delete <name>]";
+
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (None | Some("list"), None, _, _) => CommandResult::Output(macros::list()),
+            (Some("bind"), Some(name), Some(slot), None) => match macros::parse_slot(slot) {
+                Some(slot) if macros::rebind(name, slot) => CommandResult::Output(macros::list()),
+                Some(_) => CommandResult::Error(format!("macro: no macro named {}", name)),
+                None => CommandResult::Error(String::from(USAGE)),
+            },
+            (Some("delete"), Some(name), None, _) => {
+                if macros::delete(name) {
+                    CommandResult::Output(format!("macro {} deleted", name))
+                } else {
+                    CommandResult::Error(format!("macro: no macro named {}", name))
+                }
+            }
+            _ => CommandResult::Error(String::from(USAGE)),
+        }
+    }
+
+    fn background(mut args: SplitWhitespace) -> CommandResult {
+        use crate::ui_provider::{
+            background::{self, parse_color, Wallpaper},
+            image,
+        };
+        const USAGE: &str = "Usage: background [theme|<hex>|gradient <top> <bottom>|image <file>|terminal opaque|transparent]";
+
+        let wallpaper = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => return CommandResult::Output(background::describe()),
+            (Some("theme"), None, _) => Wallpaper::Theme,
+            (Some("terminal"), Some(mode @ ("opaque" | "transparent")), None) => {
+                background::set_terminal_transparent(mode == "transparent");
+                return CommandResult::Output(background::describe());
+            }
+            (Some("gradient"), Some(top), Some(bottom)) => match (parse_color(top), parse_color(bottom)) {
+                (Some(top), Some(bottom)) => Wallpaper::Gradient(top, bottom),
+                _ => return CommandResult::Error(String::from("background: colours are #rrggbb")),
+            },
+            (Some("image"), Some(path), None) => {
+                let path = resolve_path(&cwd(), path);
+                let Ok(data) = crate::fs::ramfs::read(&path) else {
+                    return CommandResult::Error(format!("{}: no such file", path));
+                };
+                match image::decode(&data) {
+                    Ok(decoded) => Wallpaper::Image(decoded),
+                    Err(err) => return CommandResult::Error(format!("{}: {}", path, err)),
+                }
+            }
+            (Some(color), None, _) => match parse_color(color) {
+                Some(color) => Wallpaper::Solid(color),
+                None => return CommandResult::Error(String::from(USAGE)),
+            },
+            _ => return CommandResult::Error(String::from(USAGE)),
+        };
+        background::set(wallpaper);
+        CommandResult::Output(background::describe())
+    }
+
+    fn clear(mut args: SplitWhitespace) -> CommandResult {
+        match args.next() {
+            None => CommandResult::Output(String::from("\x1b[2J\x1b[H")),
+            Some("-f") => CommandResult::Output(String::from("\x1b[3J\x1b[H")),
+            Some(_) => CommandResult::Error(String::from("Usage: clear [-f]")),
+        }
+    }
+
+    fn bell(mut args: SplitWhitespace) -> CommandResult {
+        use crate::ui_provider::bell::{self, BellStyle};
+
+        match args.next().map(BellStyle::from_name) {
+            None => {}
+            Some(Some(style)) => {
+                bell::set_style(style);
+                let _ = crate::fs::ramfs::write(bell::BELL_CONFIG, style.name().as_bytes());
+            }
+            Some(None) => {
+                return CommandResult::Error(String::from("Usage: bell [visual|audible|both|off]"))
+            }
+        }
+        // The BEL rings it once in the new style
+        CommandResult::Output(format!("bell: {}\x07", bell::style().name()))
+    }
+
+    fn notify(full_input: &str) -> CommandResult {
+        use crate::ui_provider::toast;
+
+        let message = full_input.strip_prefix("notify").unwrap_or("").trim();
+        if message.is_empty() {
+            return CommandResult::Error(String::from("Usage: notify <message>"));
+        }
+        toast::notify(message, toast::DEFAULT_TICKS);
+        CommandResult::Output(String::new())
+    }
+
+    fn memtrim(args: SplitWhitespace) -> CommandResult {
+        use crate::memory::pressure::{self, Pressure};
+
+        let mut args = args.peekable();
+        if args.next_if_eq(&"limit").is_some() {
+            let limit = match args.next() {
+                Some("off") => 0,
+                Some(kib) => match kib.parse::<usize>() {
+                    Ok(kib) if kib > 0 => kib * 1024,
+                    _ => return CommandResult::Error(String::from("Usage: memtrim limit <KiB|off>")),
+                },
+                None => return CommandResult::Error(String::from("Usage: memtrim limit <KiB|off>")),
+            };
+            pressure::set_heap_limit(limit);
+            return CommandResult::Output(pressure::report());
+        }
+        match args.next().map(Pressure::from_name) {
+            None => pressure::request(Pressure::High),
+            Some(Some(level)) if level != Pressure::Normal => pressure::request(level),
+            _ => return CommandResult::Error(String::from("Usage: memtrim [high|critical]")),
+        }
+        // The main loop owns the apps, so the trim runs on its next pass
+        CommandResult::Output(String::from(
+            "memtrim: requested; the result goes to the log and meminfo",
+        ))
+    }
+
+    fn mouse(mut args: SplitWhitespace) -> CommandResult {
+        use crate::devices::drivers::ps2_mouse;
+
+        match (args.next(), args.next().map(str::parse::<u32>)) {
+            (None, _) => {}
+            (Some("reset"), None) => {
+                if let Err(err) = ps2_mouse::reset() {
+                    return CommandResult::Error(format!("mouse: reset failed: {}", err));
+                }
+            }
+            (Some("threshold"), Some(Ok(n))) => ps2_mouse::set_reset_threshold(n),
+            _ => return CommandResult::Error(String::from("Usage: mouse [reset|threshold <n>]")),
+        }
+        CommandResult::Output(ps2_mouse::report())
+    }
+
+    fn dev(mut args: SplitWhitespace) -> CommandResult {
+        use crate::devices::registry::{self, DriverStatus};
+
+        let (Some(action), Some(name)) = (args.next(), args.next()) else {
+            return CommandResult::Error(String::from("Usage: dev retry|disable|enable <name>"));
+        };
+        let result = match action {
+            "retry" => registry::retry(name).map(|_| ()),
+            "disable" => registry::disable(name),
+            "enable" => registry::enable(name),
+            _ => return CommandResult::Error(String::from("Usage: dev retry|disable|enable <name>")),
+        };
+        match (result, registry::status(name)) {
+            (Ok(()), Some(DriverStatus::Ok)) => CommandResult::Output(format!("dev: {} is up", name)),
+            (Ok(()), Some(status)) => CommandResult::Output(format!("dev: {} {}", name, status)),
+            (Ok(()), None) | (Err(_), None) => {
+                CommandResult::Error(format!("dev: no driver named {}", name))
+            }
+            (Err(err), Some(_)) => CommandResult::Error(format!("dev: {}: {}", name, err)),
+        }
+    }
+
+    /// Copies the kernel log to /log/dmesg and saves /config and /log to
+    /// the persistence area.
+    fn sync_state() -> Result<String, String> {
+        use crate::fs::{persist, ramfs};
+
+        let _ = ramfs::write("/log/dmesg", crate::klog::KLOG.snapshot().as_bytes());
+        match persist::save() {
+            Ok((files, bytes)) => Ok(format!("sync: saved {} files ({} bytes)", files, bytes)),
+            Err(err) => Err(format!("sync: {}", err)),
+        }
+    }
+
+    fn power(cmd: &str) -> CommandResult {
+        use crate::kcore::power;
+
+        // An unavailable area is reported but does not stop the reboot.
+        let synced = Self::sync_state().unwrap_or_else(|err| err);
+        crate::println!("{}", synced);
+        match cmd {
+            "reboot" => power::reboot(),
+            _ => {
+                power::poweroff();
+                CommandResult::Error(format!("{}\npoweroff: the machine did not turn off", synced))
+            }
+        }
+    }
+
+    fn fps(mut args: SplitWhitespace) -> CommandResult {
+        use crate::devices::framebuffer::framebuffer as fb;
+        use crate::ui_provider::pacing::{self, FpsCap};
+
+        let usage =
+            || CommandResult::Error(String::from("Usage: fps [cap <30|60|off>|budget <us>]"));
+        match (args.next(), args.next()) {
+            (None, _) => {}
+            (Some("cap"), Some(name)) => match FpsCap::from_name(name) {
+                Some(cap) => pacing::set_cap(cap),
+                None => return usage(),
+            },
+            (Some("budget"), Some(us)) => match us.parse() {
+                Ok(us) => fb::set_flush_budget_us(us),
+                Err(_) => return usage(),
+            },
+            _ => return usage(),
+        }
+        CommandResult::Output(format!(
+            "{}\nFlush budget: {} us, {} tiles held over",
+            pacing::report(),
+            fb::flush_budget_us(),
+            fb::backlog()
+        ))
+    }
+
+    fn allocator(mut args: SplitWhitespace) -> CommandResult {
+        use crate::memory::heap::HeapKind;
+
+        let switched = match args.next() {
+            None | Some("info") => None,
+            Some(name) => match HeapKind::from_name(name) {
+                Some(kind) => Some(crate::memory::switch_heap(kind)),
+                None => {
+                    return CommandResult::Error(String::from(
+                        "Usage: allocator [info|fixed|freelist|bump|buddy]",
+                    ))
+                }
+            },
+        };
+        if let Some(Err(err)) = switched {
+            return CommandResult::Error(format!("allocator: {}", err));
+        }
+        let Some(info) = crate::memory::heap_info() else {
+            return CommandResult::Error(String::from("allocator: heap not initialized"));
+        };
+
+        let mut out = format!("Active: {}", info.active.name());
+        if let Some(guest) = info.guest {
+            out.push_str(&format!(
+                "\n{} region {:#x}+{} KiB{}: {} live, {} served, {} fell back to fixed",
+                guest.kind.name(),
+                guest.start,
+                guest.size / 1024,
+                if guest.retired { " (retired)" } else { "" },
+                guest.live,
+                guest.allocs,
+                guest.fallbacks
+            ));
+            if let Some(used) = guest.bump_used {
+                out.push_str(&format!(", {} KiB bumped (never reused)", used / 1024));
+            }
+            if let Some((free, largest)) = guest.buddy_free {
+                out.push_str(&format!(
+                    ", {} KiB free, largest block {} KiB",
+                    free / 1024,
+                    largest / 1024
+                ));
+            }
+        }
+        if switched.is_some() && info.active != HeapKind::Fixed {
+            out.push_str("\nEarlier allocations stay with the allocator that made them.");
+        }
+        CommandResult::Output(out)
+    }
+
+    fn random(mut args: SplitWhitespace) -> CommandResult {
+        use crate::util::rand;
+
+        match args.next().map(str::parse::<u64>) {
+            None => CommandResult::Output(format!("{}", rand::next_u64())),
+            Some(Ok(max)) if max > 0 => CommandResult::Output(format!("{}", rand::next_range(0, max))),
+            Some(_) => CommandResult::Error(String::from("Usage: random [max] (max > 0)")),
+        }
+    }
+
+    fn fault(mut args: SplitWhitespace) -> CommandResult {
+        use crate::kcore::interrupts::fault::{self, FaultError, FaultKind};
+        use crate::kcore::interrupts::guard::{self, FaultPolicy};
+
+        let first = args.next();
+        if first == Some("policy") {
+            match args.next() {
+                None => {}
+                Some(name) => match FaultPolicy::from_name(name) {
+                    Some(policy) => guard::set_policy(policy),
+                    None => return CommandResult::Error(String::from("Usage: fault policy [strict|lenient]")),
+                },
+            }
+            let effect = match guard::policy() {
+                FaultPolicy::Strict => "any fault panics",
+                FaultPolicy::Lenient => "a fault in a program ends the program",
+            };
+            return CommandResult::Output(format!("fault policy: {} ({})", guard::policy().name(), effect));
+        }
+        let Some(kind) = first.and_then(FaultKind::from_name) else {
+            return CommandResult::Error(String::from("Usage: fault <divide|ud2|int3|page|gp>"));
+        };
+        match fault::trigger(kind) {
+            Ok(seen) => CommandResult::Output(format!(
+                "fault: vector {} at {:#x} handled, back in the shell",
+                seen.vector, seen.rip
+            )),
+            Err(FaultError::Mapped(addr)) => {
+                CommandResult::Error(format!("fault: {:#x} is mapped, no page fault to take", addr))
+            }
+            Err(FaultError::NoFault) => {
+                CommandResult::Error(format!("fault: {} did not reach its handler", kind.name()))
+            }
+        }
+    }
+
+    fn clipboard(mut args: SplitWhitespace) -> CommandResult {
+        use crate::apps::clipboard;
+
+        match args.next() {
+            None => {}
+            Some("clear") => {
+                clipboard::clear();
+                return CommandResult::Output(String::from("clipboard cleared"));
+            }
+            Some(_) => return CommandResult::Error(String::from("Usage: clipboard [clear]")),
+        }
+        let entries = clipboard::entries();
+        if entries.is_empty() {
+            return CommandResult::Output(String::from(
+                "clipboard is empty; Ctrl+Shift+C copies the input line, or the last output",
+            ));
+        }
+        let mut out = String::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            out.push_str(&format!("{:>2} {:>5} B  {}\n", idx, entry.len(), clipboard::summary(entry, 50)));
+        }
+        out.push_str("Ctrl+Shift+V pastes the newest; twice, or Ctrl+Shift+H, to pick");
+        CommandResult::Output(out)
+    }
+
+    fn seed(mut args: SplitWhitespace) -> CommandResult {
+        let value = args.next().and_then(|v| match v.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => v.parse::<u64>().ok(),
+        });
+        match value {
+            Some(value) => {
+                crate::util::rand::seed(value);
+                CommandResult::Output(format!("rng seeded with {}; timer jitter off until reboot", value))
+            }
+            None => CommandResult::Error(String::from("Usage: seed <value>")),
+        }
+    }
+
+    fn aslr(mut args: SplitWhitespace) -> CommandResult {
+        use crate::memory::mmap;
+
+        match args.next() {
+            None => {}
+            Some("on") => mmap::set_aslr(true),
+            Some("off") => mmap::set_aslr(false),
+            Some(_) => return CommandResult::Error(String::from("Usage: aslr [on|off]")),
+        }
+        let state = if mmap::aslr_enabled() { "on" } else { "off" };
+        CommandResult::Output(format!("mmap placement randomization: {}", state))
+    }
+
+    fn bench_rounds(arg: Option<&str>, default: u32) -> Result<u32, CommandResult> {
+        match arg.map(str::parse::<u32>) {
+            None => Ok(default),
+            Some(Ok(n)) if n > 0 => Ok(n),
+            Some(_) => Err(CommandResult::Error(String::from(
+                "bench: rounds must be a positive number",
+            ))),
+        }
+    }
+
+    fn bench(mut args: SplitWhitespace) -> CommandResult {
+        use crate::devices::framebuffer::framebuffer::bench_render;
+
+        let kind = args.next();
+        let rounds = match (kind, Self::bench_rounds(args.next(), 16)) {
+            (_, Err(err)) => return err,
+            (_, Ok(n)) => n,
+        };
+        match kind {
+            Some("render") => match bench_render(rounds) {
+                Ok(report) => CommandResult::Output(report),
+                Err(_) => CommandResult::Error(String::from("bench: framebuffer unavailable")),
+            },
+            Some("alloc") => match crate::memory::bench_alloc(rounds) {
+                Ok(report) => CommandResult::Output(report),
+                Err(err) => CommandResult::Error(err),
+            },
+            _ => CommandResult::Error(String::from("Usage: bench render|alloc [rounds]")),
+        }
+    }
+
+    fn bench_job(mut args: SplitWhitespace) -> Result<JobBody, CommandResult> {
+        use crate::memory::{bench_alloc_report, bench_alloc_round};
+
+        match args.next() {
+            Some("alloc") => {}
+            Some("render") => {
+                return Err(CommandResult::Error(String::from(
+                    "bench render: cannot run in the background",
+                )))
+            }
+            _ => return Err(CommandResult::Error(String::from("Usage: bench render|alloc [rounds]"))),
+        }
+        let rounds = Self::bench_rounds(args.next(), 16)?;
+        let (mut round, mut cycles) = (0, 0);
+        Ok(Box::new(move |out: &mut String| {
+            match bench_alloc_round(round) {
+                Ok(taken) => cycles += taken,
+                Err(err) => return Self::job_output(CommandResult::Error(err), out),
+            }
+            round += 1;
+            if round < rounds {
+                return Progress::More;
+            }
+            out.push_str(&bench_alloc_report(rounds, cycles));
+            Progress::Done(true)
+        }))
+    }
+
+    fn smp(mut args: SplitWhitespace) -> CommandResult {
+        use crate::kcore::smp;
+
+        match (args.next(), args.next(), args.next()) {
+            (None | Some("status"), _, _) => CommandResult::Output(smp::report()),
+            (Some(value @ ("on" | "off")), None, _) => {
+                let _ = crate::fs::ramfs::write(smp::SMP_CONFIG, value.as_bytes());
+                CommandResult::Output(format!(
+                    "smp: APs {} from the next boot (sync to keep it)",
+                    value
+                ))
+            }
+            (Some("run"), Some("bench"), Some("alloc")) => {
+                let rounds = match Self::bench_rounds(args.next(), 256) {
+                    Ok(n) => n,
+                    Err(err) => return err,
+                };
+                let job = alloc::boxed::Box::new(move || {
+                    crate::memory::bench_alloc(rounds).unwrap_or_else(|err| err)
+                });
+                match smp::work::submit("bench alloc", job) {
+                    Ok(id) => CommandResult::Output(format!(
+                        "queued AP job #{}; see 'smp status' for the result",
+                        id
+                    )),
+                    Err(err) => CommandResult::Error(format!("smp: {}", err)),
+                }
+            }
+            _ => CommandResult::Error(String::from(
+                "Usage: smp [status|on|off] | smp run bench alloc [rounds]",
+            )),
+        }
+    }
+
+    fn tasks(mut args: SplitWhitespace) -> CommandResult {
+        use crate::kcore::task::{self, Signal, TaskState};
+
+        match args.next() {
+            None => {}
+            Some("spawn") => {
+                // Ticks every `period` timer ticks (every frame for 0)
+                // until it is sent `term`.
+                let period = match args.next().map(str::parse::<u64>) {
+                    None => 18,
+                    Some(Ok(n)) => n,
+                    Some(Err(_)) => return CommandResult::Error(String::from("Usage: tasks spawn [ticks]")),
+                };
+                let mut ticks = 0u64;
+                let id = task::spawn(
+                    "ticker",
+                    alloc::boxed::Box::new(move |ctx| {
+                        if ctx.take_signal(Signal::Terminate) {
+                            crate::log_info!("ticker {}: terminated after {} ticks", ctx.id, ticks);
+                            return TaskState::Completed;
+                        }
+                        ticks += 1;
+                        if period == 0 {
+                            TaskState::Yield
+                        } else {
+                            TaskState::Sleep(period)
+                        }
+                    }),
+                );
+                return CommandResult::Output(format!("spawned task {} (ticker)", id));
+            }
+            Some("keywait") => {
+                // Sleeps until the keyboard IRQ wakes it, without polling
+                // or taking the key from the main loop.
+                use crate::devices::drivers::ps2_keyboard as kbd;
+                let started = crate::kcore::interrupts::interrupts::TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed);
+                let id = task::spawn(
+                    "keywait",
+                    alloc::boxed::Box::new(move |ctx| {
+                        if ctx.take_signal(Signal::Terminate) {
+                            kbd::unregister_waiter(ctx.id);
+                            return TaskState::Completed;
+                        }
+                        if ctx.take_signal(Signal::Key) {
+                            let now = crate::kcore::interrupts::interrupts::TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed);
+                            crate::log_info!("keywait {}: woken by keyboard input after {} ticks", ctx.id, now - started);
+                            return TaskState::Completed;
+                        }
+                        if !kbd::register_waiter(ctx.id) {
+                            crate::log_error!("keywait {}: no free waiter slot", ctx.id);
+                            return TaskState::Completed;
+                        }
+                        // An hour; it registers again if it runs out
+                        TaskState::Sleep(18 * 3_600)
+                    }),
+                );
+                return CommandResult::Output(format!("spawned task {} (keywait); press a key", id));
+            }
+            Some(other) => return CommandResult::Error(format!("tasks: unknown option '{}'", other)),
+        }
+
+        let tasks = task::SCHEDULER.lock().info();
+        if tasks.is_empty() {
+            return CommandResult::Output(String::from("no tasks"));
+        }
+        let now = crate::kcore::interrupts::interrupts::TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed);
+        let mut out = String::from("  ID  STEPS  STATE       NAME  PENDING\n");
+        for t in tasks {
+            let pending: alloc::vec::Vec<&str> = t.pending.iter().map(|sig| sig.name()).collect();
+            let state = match t.wake_at {
+                Some(at) => format!("sleep {:>4}", at.saturating_sub(now)),
+                None => String::from("ready"),
+            };
+            out.push_str(&format!("{:>4}  {:>5}  {:<10}  {}  {}\n", t.id, t.steps, state, t.name, pending.join(",")));
+        }
+        let (frames, live) = task::slab_stats();
+        out.push_str(&format!("{} task records in {} slab frame(s)\n", live, frames));
+        CommandResult::Output(out)
+    }
+
+    /// A task that reports through `ui_provider::progress`, one tick a
+    /// step, so the terminal can draw every value on the way.
+    fn demo_progress(mut args: SplitWhitespace) -> CommandResult {
+        use crate::kcore::task::{self, Signal, TaskState};
+        use crate::ui_provider::progress;
+
+        let ticks = match args.next().map(str::parse::<u32>) {
+            None => 36,
+            Some(Ok(n)) if n > 0 => n,
+            _ => return CommandResult::Error(String::from("Usage: demo_progress [ticks]")),
+        };
+        progress::begin("demo", 100);
+        let mut elapsed = 0u32;
+        let id = task::spawn(
+            "demo progress",
+            alloc::boxed::Box::new(move |ctx| {
+                if ctx.take_signal(Signal::Terminate) || elapsed == ticks {
+                    progress::finish();
+                    return TaskState::Completed;
+                }
+                elapsed += 1;
+                progress::set(elapsed * 100 / ticks);
+                TaskState::Sleep(1)
+            }),
+        );
+        CommandResult::Output(format!("spawned task {} (demo progress)", id))
+    }
+
+    fn signal(mut args: SplitWhitespace) -> CommandResult {
+        use crate::kcore::task::{self, Signal};
+
+        let usage = || CommandResult::Error(String::from("Usage: signal <id> term|int|timer|key|user"));
+        let Some(id) = args.next().and_then(|id| id.parse::<u64>().ok()) else {
+            return usage();
+        };
+        let Some(sig) = args.next().and_then(Signal::from_name) else {
+            return usage();
+        };
+        if task::send_signal(id, sig) {
+            CommandResult::Output(format!("sent {} to task {}", sig.name(), id))
+        } else {
+            CommandResult::Error(format!("signal: no task {}", id))
+        }
+    }
+
+    #[cfg(feature = "alloc-track")]
+    fn leaks(mut args: SplitWhitespace) -> CommandResult {
+        use crate::memory::alloc_track;
+
+        match args.next() {
+            None => {}
+            Some("mark") => {
+                alloc_track::mark();
+                return CommandResult::Output(String::from("leaks: mark set"));
+            }
+            Some("clear") => alloc_track::clear(),
+            Some(other) => return CommandResult::Error(format!("leaks: unknown option '{}'", other)),
+        }
+        CommandResult::Output(alloc_track::leak_report())
+    }
+
+    #[cfg(not(feature = "alloc-track"))]
+    fn leaks(_args: SplitWhitespace) -> CommandResult {
+        CommandResult::Error(String::from(
+            "leaks: allocation tracking is off (build with --features alloc-track)",
+        ))
+    }
+
+    // ── dmesg ─────────────────────────────────────────────────────────────────
+
+    fn dmesg(mut args: SplitWhitespace) -> CommandResult {
+        let log = &crate::klog::KLOG;
+        let limit = match args.next() {
+            None => None,
+            Some("-c") => {
+                log.clear();
+                return CommandResult::Output(String::from("kernel log cleared"));
+            }
+            Some(n) => match n.parse::<usize>() {
+                Ok(n) => Some(n),
+                Err(_) => return CommandResult::Error(String::from("Usage: dmesg [lines|-c]")),
+            },
+        };
+
+        let text = log.snapshot();
+        let lines: alloc::vec::Vec<&str> = text.lines().collect();
+        let start = match limit {
+            Some(n) => lines.len().saturating_sub(n),
+            None => 0,
+        };
+
+        let mut out = format!(
+            "--- kernel log: {} of {} bytes retained, {} written ---\n",
+            log.len(),
+            log.capacity(),
+            log.total_written()
+        );
+        for line in &lines[start..] {
+            out.push_str(line);
+            out.push('\n');
+        }
+        CommandResult::Output(out)
+    }
+
+    // ── vmmap ─────────────────────────────────────────────────────────────────
+
+    fn vmmap(mut args: SplitWhitespace) -> CommandResult {
+        use crate::memory::vmmap::{walk, RegionCollector};
+
+        const MAX_USER_REGIONS: usize = 128;
+        const MAX_KERNEL_REGIONS: usize = 64;
+
+        let range = match (args.next(), args.next()) {
+            (None, _) => None,
+            (Some(lo), Some(hi)) => match (Self::parse_hex(lo), Self::parse_hex(hi)) {
+                (Some(lo), Some(hi)) if lo < hi => Some((lo, hi)),
+                _ => return CommandResult::Error(String::from("vmmap: invalid range")),
+            },
+            (Some(_), None) => {
+                return CommandResult::Error(String::from("Usage: vmmap [<lo> <hi>] (hex)"))
+            }
+        };
+
+        let mut out = String::new();
+        let mut push_section = |title: &str, c: &RegionCollector| {
+            out.push_str(title);
+            out.push('\n');
+            if c.regions().is_empty() {
+                out.push_str("  (none)\n");
+            }
+            for region in c.regions() {
+                out.push_str("  ");
+                out.push_str(&region.format_line());
+                out.push('\n');
+            }
+            if c.truncated() {
+                out.push_str("  ... output truncated, narrow the range\n");
+            }
+        };
+
+        match range {
+            Some((lo, hi)) => {
+                let mut c = RegionCollector::new(MAX_USER_REGIONS);
+                walk(0..512, lo, hi, false, &mut c);
+                push_section(&format!("Mappings in {:#x}-{:#x}:", lo, hi), &c);
+            }
+            None => {
+                let mut user = RegionCollector::new(MAX_USER_REGIONS);
+                walk(0..256, 0, u64::MAX, false, &mut user);
+                push_section("Lower half (4 KiB granularity):", &user);
+
+                let mut kernel = RegionCollector::new(MAX_KERNEL_REGIONS);
+                walk(256..512, 0, u64::MAX, true, &mut kernel);
+                push_section("Kernel half (2 MiB granularity):", &kernel);
+            }
+        }
+
+        CommandResult::Output(out)
+    }
+
+    fn parse_hex(s: &str) -> Option<u64> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        u64::from_str_radix(&digits.replace('_', ""), 16).ok()
+    }
+
     // ── VM help ───────────────────────────────────────────────────────────────
 
     fn vm_help() -> CommandResult {
@@ -221,14 +1527,269 @@ Example — count 1 to 5, print sum
         out
     }
 
-    fn test_all() -> CommandResult {
-        CommandResult::Output(crate::tests::test_env::test_all())
+    /// `test` arguments: the patterns and `--reverse`, or what to answer
+    /// at once (`list`, bad arguments).
+    fn test_args(mut args: SplitWhitespace<'_>) -> Result<(Vec<&str>, bool), CommandResult> {
+        use crate::tests::test_env;
+
+        let mut patterns = Vec::new();
+        let mut reverse = false;
+        match args.next() {
+            None => {}
+            Some("list") => return Err(CommandResult::Output(test_env::list())),
+            Some("run") => {
+                for arg in args {
+                    match arg {
+                        "--reverse" => reverse = true,
+                        pattern => patterns.push(pattern),
+                    }
+                }
+                if !patterns.is_empty() && test_env::select(&patterns).is_empty() {
+                    return Err(CommandResult::Error(format!("test: nothing matches {}", patterns.join(" "))));
+                }
+            }
+            Some(other) => return Err(CommandResult::Error(format!("test: unknown option '{}'", other))),
+        }
+        Ok((patterns, reverse))
+    }
+
+    fn test_verdict(out: &mut String, failures: usize) {
+        match failures {
+            0 => out.push_str("All tests passed"),
+            n => out.push_str(&format!("{} failure(s)", n)),
+        }
+    }
+
+    fn test(args: SplitWhitespace) -> CommandResult {
+        use crate::tests::test_env;
+
+        let (patterns, reverse) = match Self::test_args(args) {
+            Ok(selection) => selection,
+            Err(result) => return result,
+        };
+        test_env::reset_failures();
+        let mut out = test_env::run_tests(&patterns, reverse);
+        Self::test_verdict(&mut out, test_env::failure_count());
+        CommandResult::Output(out)
+    }
+
+    fn test_job(args: SplitWhitespace) -> Result<JobBody, CommandResult> {
+        let (patterns, reverse) = Self::test_args(args)?;
+        let mut run = crate::tests::test_env::TestRun::new(&patterns, reverse);
+        Ok(Box::new(move |out: &mut String| {
+            if run.step(out) {
+                return Progress::More;
+            }
+            run.finish(out);
+            Self::test_verdict(out, run.failures());
+            Progress::Done(run.failures() == 0)
+        }))
     }
 
     fn test_paging() -> CommandResult {
         CommandResult::Output(crate::tests::test_env::test_basic_paging())
     }
 
+    fn pstart(mut args: SplitWhitespace) -> CommandResult {
+        use crate::kcore::interrupts::guard;
+        use crate::tests::asm::AsmProgram;
+
+        const USAGE: &str = "Usage: pstart [count 1-8] [abs|pic|based]";
+        let count = match args.next().map(str::parse::<usize>) {
+            None => 2,
+            Some(Ok(n @ 1..=8)) => n,
+            _ => return CommandResult::Error(String::from(USAGE)),
+        };
+        let variant = args.next().unwrap_or("abs");
+        let mut out = String::new();
+        let mut ended = alloc::vec::Vec::new();
+        for i in 0..count {
+            // The same program each time, returning its own value
+            let value = 100 + i as u64;
+            let image = match variant {
+                "abs" => AsmProgram::return_argument_absolute(value),
+                "pic" => AsmProgram::return_argument_pic(value).into(),
+                "based" => AsmProgram::return_argument_based(value).into(),
+                _ => return CommandResult::Error(String::from(USAGE)),
+            };
+            let started = unsafe {
+                crate::memory::sys_pstart(image.code.as_ptr(), image.code.len(), &image.fixups)
+            };
+            let code = match started {
+                Ok(code) => code,
+                Err(e) => {
+                    out.push_str(&format!("pstart: {}\n", e));
+                    break;
+                }
+            };
+            crate::memory::jit::note_entry(code.base);
+            ended.push(code.pid);
+            let range = code.base..code.base + code.size as u64;
+            match unsafe { guard::call(code.base, range, [code.base, 0, 0, 0]) } {
+                Ok(ret) => out.push_str(&format!(
+                    "pid {} at {:#x}: returned {} (expected {})\n",
+                    code.pid, code.base, ret, value
+                )),
+                Err(fault) => out.push_str(&format!(
+                    "pid {} at {:#x}: ended by vector {} at {:#x}\n",
+                    code.pid, code.base, fault.vector, fault.rip
+                )),
+            }
+        }
+        // Only now, so each of them got a base of its own
+        for pid in ended {
+            crate::syscalls::handlers::process::release(pid);
+        }
+        CommandResult::Output(out)
+    }
+
+    fn ps() -> CommandResult {
+        let procs = crate::memory::jit::processes();
+        if procs.is_empty() {
+            return CommandResult::Output(String::from("no processes; 'surface demo' runs one"));
+        }
+        let mut out = format!("{:>5}  {:<21}  {:>6}  ENTERED\n", "PID", "CODE", "SIZE");
+        for p in procs {
+            let code = format!("{:#x}-{:#x}", p.start, p.start + p.size - 1);
+            out.push_str(&format!("{:>5}  {:<21}  {:>6}  {}\n", p.pid, code, p.size, p.entries));
+        }
+        CommandResult::Output(out)
+    }
+
+    fn caps(mut args: SplitWhitespace) -> CommandResult {
+        use crate::syscalls::handlers::process;
+        match args.next().map(str::parse::<usize>) {
+            Some(Ok(pid)) => CommandResult::Output(format!("pid {}: {}", pid, process::caps(pid))),
+            _ => CommandResult::Error(String::from("Usage: caps <pid>")),
+        }
+    }
+
+    /// Only the shell can change capabilities; there is no syscall for it.
+    fn setcap(mut args: SplitWhitespace) -> CommandResult {
+        use crate::syscalls::handlers::process;
+        use crate::syscalls::policy::Cap;
+
+        const USAGE: &str = "Usage: setcap <pid> [-]<surface|spawn>";
+        let (Some(Ok(pid)), Some(arg)) = (args.next().map(str::parse::<usize>), args.next()) else {
+            return CommandResult::Error(String::from(USAGE));
+        };
+        let (revoke, name) = match arg.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, arg.strip_prefix('+').unwrap_or(arg)),
+        };
+        let Some(cap) = Cap::parse(name) else {
+            return CommandResult::Error(String::from(USAGE));
+        };
+        let current = process::caps(pid);
+        let caps = if revoke { current.without(cap) } else { current.with(cap) };
+        process::set_caps(pid, caps);
+        CommandResult::Output(format!("pid {}: {}", pid, caps))
+    }
+
+    fn surface(mut args: SplitWhitespace) -> CommandResult {
+        match (args.next(), args.next().map(str::parse::<u32>)) {
+            (None, _) => {
+                let surfaces = crate::gfx::surface::list();
+                if surfaces.is_empty() {
+                    return CommandResult::Output(String::from("no surfaces; try 'surface demo'"));
+                }
+                let mut out = format!("{:>6}  {:>5}  SIZE\n", "HANDLE", "PID");
+                for (handle, pid, w, h) in surfaces {
+                    out.push_str(&format!("{:>6}  {:>5}  {}x{}\n", handle, pid, w, h));
+                }
+                CommandResult::Output(out)
+            }
+            (Some("demo"), None) => Self::surface_demo(600),
+            (Some("demo"), Some(Ok(frames))) => Self::surface_demo(frames),
+            _ => CommandResult::Error(String::from("Usage: surface [demo [frames]]")),
+        }
+    }
+
+    /// Loads `AsmProgram::gradient` as a process and drives it from a task,
+    /// making its syscalls as that process: each step redraws the surface
+    /// one column further along and presents it. `WouldBlock` means the
+    /// last frame is not on screen yet, so the step just tries again. The
+    /// calls are made as user calls, so they need the process's `surface`
+    /// capability.
+    fn surface_demo(frames: u32) -> CommandResult {
+        use crate::kcore::interrupts::guard;
+        use crate::kcore::task::{self, Signal, TaskState};
+        use crate::syscalls::dispatcher::{dispatch_syscall, SyscallContext, SyscallError, SyscallResult};
+        use crate::syscalls::handlers::process::with_current_pid;
+        use crate::syscalls::numbers::SyscallNumber;
+        use crate::syscalls::policy::Origin;
+        use crate::tests::asm::AsmProgram;
+
+        const W: usize = 192;
+        const H: usize = 128;
+
+        fn syscall(num: SyscallNumber, a0: usize, a1: usize, a2: usize) -> SyscallResult {
+            let ctx = SyscallContext::from_registers(num as usize, a0, a1, a2, 0, 0, 0);
+            dispatch_syscall(ctx.with_origin(Origin::User))
+        }
+
+        let code = AsmProgram::gradient();
+        let process = match unsafe { crate::memory::sys_pstart(code.as_ptr(), code.len(), &[]) } {
+            Ok(process) => process,
+            Err(e) => return CommandResult::Error(format!("surface: {}", e)),
+        };
+        crate::memory::jit::note_entry(process.base);
+        let (pid, entry) = (process.pid, process.base);
+        let code_range = process.base..process.base + process.size as u64;
+
+        let mut addr = 0usize;
+        let out = &mut addr as *mut usize as usize;
+        let handle = match with_current_pid(pid, || syscall(SyscallNumber::SurfaceCreate, W, H, out)) {
+            Ok(handle) => handle,
+            Err(e) => {
+                crate::syscalls::handlers::process::release(pid);
+                return CommandResult::Error(format!("surface: create failed: {:?}", e));
+            }
+        };
+
+        let mut frame = 0u32;
+        let id = task::spawn(
+            "surface demo",
+            alloc::boxed::Box::new(move |ctx| {
+                let state = with_current_pid(pid, || {
+                    if ctx.take_signal(Signal::Terminate) || frame >= frames {
+                        let _ = syscall(SyscallNumber::SurfaceDestroy, handle, 0, 0);
+                        return TaskState::Completed;
+                    }
+                    let args = [addr as u64, W as u64, H as u64, frame as u64];
+                    if let Err(fault) = unsafe { guard::call(entry, code_range.clone(), args) } {
+                        crate::log_error!(
+                            "surface demo: ended by vector {} at {:#x}",
+                            fault.vector,
+                            fault.rip
+                        );
+                        let _ = syscall(SyscallNumber::SurfaceDestroy, handle, 0, 0);
+                        return TaskState::Completed;
+                    }
+                    match syscall(SyscallNumber::SurfacePresent, handle, 0, 0) {
+                        Ok(_) => frame += 1,
+                        Err(SyscallError::WouldBlock) => {}
+                        Err(e) => {
+                            crate::log_error!("surface demo: present failed: {:?}", e);
+                            let _ = syscall(SyscallNumber::SurfaceDestroy, handle, 0, 0);
+                            return TaskState::Completed;
+                        }
+                    }
+                    TaskState::Yield
+                });
+                if state == TaskState::Completed {
+                    // The process ends with the task
+                    crate::syscalls::handlers::process::release(pid);
+                }
+                state
+            }),
+        );
+        CommandResult::Output(format!(
+            "pid {}: surface {} ({}x{} at {:#x}) presenting {} frames from task {}",
+            pid, handle, W, H, addr, frames, id
+        ))
+    }
+
     fn test_process() -> CommandResult {
         CommandResult::Output(crate::tests::test_env::test_process_creation())
     }
@@ -251,4 +1812,49 @@ Example — count 1 to 5, print sum
     fn test_asm_add() -> CommandResult {
         CommandResult::Output(crate::tests::test_env::test_asm_add())
     }
+
+    fn test_sgr() -> CommandResult {
+        const ROWS: &[(&str, &str)] = &[
+            ("plain", ""),
+            ("bold", "1;"),
+            ("underline", "4;"),
+            ("inverse", "7;"),
+            ("all three", "1;4;7;"),
+        ];
+        let mut out = String::new();
+        for (name, attrs) in ROWS {
+            out.push_str(&format!("{:<10}", name));
+            for color in 0..8 {
+                out.push_str(&format!("\x1b[{}{}m Aa{} \x1b[0m", attrs, 30 + color, color));
+            }
+            out.push('\n');
+        }
+        CommandResult::Output(out)
+    }
+}
+
+/// Closest command name to a mistyped `cmd`, if any is within two edits.
+fn suggest(cmd: &str) -> Option<&'static str> {
+    HELP.iter()
+        .map(|(usage, _, _)| usage.split(' ').next().unwrap_or(usage))
+        .map(|name| (edit_distance(cmd, name), name))
+        .filter(|&(dist, _)| dist <= 2)
+        .min_by_key(|&(dist, _)| dist)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: alloc::vec::Vec<char> = b.chars().collect();
+    let mut prev: alloc::vec::Vec<usize> = (0..=b.len()).collect();
+    let mut cur = alloc::vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let subst = prev[j] + (ca != cb) as usize;
+            cur[j + 1] = subst.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        core::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
 }
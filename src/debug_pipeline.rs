@@ -1,9 +1,12 @@
 use crate::apps::logs_app::LogLevel;
+use crate::memory::pressure::Pressure;
 use alloc::{collections::VecDeque, format, string::String, vec::Vec};
 use core::fmt;
 use spin::Mutex;
 
 const DEFAULT_CAPACITY: usize = 512;
+/// Memory pressure halves the retention no further than this.
+const MIN_CAPACITY: usize = 64;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DebugCategory {
@@ -72,12 +75,16 @@ impl DebugEvent {
     }
 
     fn level_tag(&self) -> &'static str {
-        match self.level {
-            LogLevel::Debug => "DBG",
-            LogLevel::Info => "INF",
-            LogLevel::Warn => "WRN",
-            LogLevel::Error => "ERR",
-        }
+        level_tag(self.level)
+    }
+}
+
+const fn level_tag(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Debug => "DBG",
+        LogLevel::Info => "INF",
+        LogLevel::Warn => "WRN",
+        LogLevel::Error => "ERR",
     }
 }
 
@@ -143,6 +150,24 @@ impl DebugPipeline {
         self.capacity
     }
 
+    /// Lowers the retention to `capacity` (not below `MIN_CAPACITY`),
+    /// dropping the oldest entries. Returns about the bytes freed.
+    pub fn shrink(&mut self, capacity: usize) -> usize {
+        let capacity = capacity.max(MIN_CAPACITY).min(self.capacity);
+        let mut freed = 0;
+        while self.entries.len() > capacity {
+            if let Some(event) = self.entries.pop_front() {
+                freed += event.message.capacity();
+            }
+        }
+        let slots = self.entries.capacity();
+        self.entries.shrink_to(capacity);
+        freed += (slots - self.entries.capacity()) * core::mem::size_of::<DebugEvent>();
+        self.capacity = capacity;
+        self.dirty = true;
+        freed
+    }
+
     pub fn entries(&self) -> &VecDeque<DebugEvent> {
         &self.entries
     }
@@ -171,6 +196,20 @@ static DEBUG_PIPELINE: Mutex<Option<DebugPipeline>> = Mutex::new(None);
 
 pub fn init() {
     init_with_capacity(DEFAULT_CAPACITY);
+    crate::memory::pressure::register_trimmer("log", trim);
+}
+
+/// Memory-pressure trimmer: halves the log's retention, or quarters it at
+/// `Critical`. The retention stays lowered.
+fn trim(level: Pressure) -> usize {
+    let Some(mut guard) = DEBUG_PIPELINE.try_lock() else {
+        return 0;
+    };
+    let Some(pipeline) = guard.as_mut() else {
+        return 0;
+    };
+    let divisor = if level == Pressure::Critical { 4 } else { 2 };
+    pipeline.shrink(pipeline.capacity() / divisor)
 }
 
 pub fn init_with_capacity(capacity: usize) {
@@ -193,7 +232,19 @@ pub fn push(
     source: &'static str,
     message: String,
 ) -> u64 {
-    let mut guard = DEBUG_PIPELINE.lock();
+    crate::klog::write_fmt(format_args!(
+        "[{}] [{}] {}: {}\n",
+        level_tag(level),
+        category.as_str(),
+        source,
+        message
+    ));
+
+    // Never spin here: an IRQ that logs while the main context holds the
+    // lock would deadlock. The event still reaches the kernel log ring.
+    let Some(mut guard) = DEBUG_PIPELINE.try_lock() else {
+        return 0;
+    };
     let pipeline = guard.get_or_insert_with(|| DebugPipeline::new(DEFAULT_CAPACITY));
     pipeline.push(level, category, source, message)
 }
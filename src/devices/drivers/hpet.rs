@@ -0,0 +1,186 @@
+//! HPET (High Precision Event Timer)
+//!
+//! Only the main counter is used: it runs at a fixed rate independent of
+//! the CPU clock, which makes it a better time base than the TSC on CPUs
+//! without an invariant TSC. The comparators are left alone.
+//!
+//! The register block comes from the ACPI HPET table and is mapped with
+//! `memory::map_mmio`. A 32-bit counter wraps (after ~43 s at 100 MHz);
+//! `Hpet::counter` extends it to 64 bits, which works as long as it is read
+//! at least once per wrap.
+//!
+//! Without an HPET, `busy_wait_us` falls back to the PIT-calibrated TSC.
+use crate::devices::registry::{Driver, DriverError};
+use crate::kcore::acpi;
+use crate::memory::map_mmio;
+use crate::stats::latency::{rdtsc, tsc_per_us};
+use alloc::{format, string::String};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Once;
+use x86_64::{PhysAddr, VirtAddr};
+
+const REG_CAPABILITIES: usize = 0x000;
+const REG_CONFIG: usize = 0x010;
+const REG_COUNTER: usize = 0x0F0;
+
+const CAP_COUNTER_64: u64 = 1 << 13;
+const CONFIG_ENABLE: u64 = 1 << 0;
+/// The spec caps the counter period at 100 ns.
+const MAX_PERIOD_FS: u64 = 100_000_000;
+const FS_PER_NS: u64 = 1_000_000;
+
+static HPET: Once<Hpet> = Once::new();
+/// Last 64-bit counter value handed out, for extending a 32-bit counter.
+static LAST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+pub struct Hpet {
+    base: VirtAddr,
+    /// Counter period in femtoseconds.
+    pub period_fs: u64,
+    pub counter_64: bool,
+}
+
+impl Hpet {
+    fn read(&self, reg: usize) -> u64 {
+        unsafe { core::ptr::read_volatile((self.base.as_u64() as usize + reg) as *const u64) }
+    }
+
+    fn write(&self, reg: usize, value: u64) {
+        unsafe { core::ptr::write_volatile((self.base.as_u64() as usize + reg) as *mut u64, value) }
+    }
+
+    pub fn frequency_hz(&self) -> u64 {
+        1_000_000_000_000_000 / self.period_fs
+    }
+
+    /// Raw main counter, extended to 64 bits if the hardware is 32-bit.
+    pub fn counter(&self) -> u64 {
+        let raw = self.read(REG_COUNTER);
+        if self.counter_64 {
+            return raw;
+        }
+        let mut last = LAST_COUNT.load(Ordering::Relaxed);
+        loop {
+            let mut now = (last & !0xFFFF_FFFF) | (raw & 0xFFFF_FFFF);
+            if now < last {
+                now += 1 << 32;
+            }
+            match LAST_COUNT.compare_exchange_weak(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return now,
+                Err(seen) if seen >= now => return seen,
+                Err(seen) => last = seen,
+            }
+        }
+    }
+
+    fn ticks_to_nanos(&self, ticks: u64) -> u64 {
+        (ticks as u128 * self.period_fs as u128 / FS_PER_NS as u128) as u64
+    }
+}
+
+/// Maps the HPET from the ACPI table and starts its main counter. Needs
+/// `acpi::init`.
+pub fn init() -> Result<(), &'static str> {
+    let table = acpi::hpet().ok_or("no HPET in ACPI tables")?;
+    let base = map_mmio(PhysAddr::new(table.base_phys), 1024).map_err(|_| "cannot map HPET")?;
+    let mut hpet = Hpet {
+        base,
+        period_fs: 0,
+        counter_64: false,
+    };
+    let caps = hpet.read(REG_CAPABILITIES);
+    hpet.period_fs = caps >> 32;
+    hpet.counter_64 = caps & CAP_COUNTER_64 != 0;
+    if hpet.period_fs == 0 || hpet.period_fs > MAX_PERIOD_FS {
+        return Err("HPET reports an invalid period");
+    }
+
+    let config = hpet.read(REG_CONFIG);
+    hpet.write(REG_CONFIG, config | CONFIG_ENABLE);
+    HPET.call_once(|| hpet);
+    Ok(())
+}
+
+/// The `hpet` driver, registered by the HPET stage once ACPI is up.
+pub struct HpetDriver;
+
+impl Driver for HpetDriver {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn init(&mut self) -> Result<(), DriverError> {
+        if acpi::hpet().is_none() {
+            return Err(DriverError::Absent);
+        }
+        init().map_err(DriverError::Failed)
+    }
+}
+
+pub fn get() -> Option<&'static Hpet> {
+    HPET.get()
+}
+
+/// Nanoseconds since the counter started, or `None` without an HPET.
+pub fn hpet_nanos() -> Option<u64> {
+    let hpet = get()?;
+    Some(hpet.ticks_to_nanos(hpet.counter()))
+}
+
+/// Milliseconds since boot from the HPET, or the TSC without one.
+pub fn monotonic_ms() -> u64 {
+    match hpet_nanos() {
+        Some(ns) => ns / 1_000_000,
+        None => rdtsc() / tsc_per_us().max(1) / 1_000,
+    }
+}
+
+/// Spins for at least `us` microseconds: on the HPET counter if there is
+/// one, otherwise on the TSC.
+pub fn busy_wait_us(us: u64) {
+    busy_wait_ns(us.saturating_mul(1_000));
+}
+
+/// Like `busy_wait_us`, for short device delays (the 400 ns ATA status
+/// settle, say). Sub-tick waits round up to one counter tick.
+pub fn busy_wait_ns(ns: u64) {
+    match get() {
+        Some(hpet) => {
+            let ticks = (ns as u128 * FS_PER_NS as u128).div_ceil(hpet.period_fs as u128);
+            let end = hpet.counter().saturating_add(ticks as u64);
+            while hpet.counter() < end {
+                core::hint::spin_loop();
+            }
+        }
+        None => {
+            let ticks = ns.saturating_mul(tsc_per_us()).div_ceil(1_000);
+            let end = rdtsc().saturating_add(ticks);
+            while rdtsc() < end {
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
+
+/// Frequency and counter value for the `hpet` command.
+pub fn report() -> String {
+    match get() {
+        Some(hpet) => {
+            let count = hpet.counter();
+            let ms = hpet_nanos().unwrap_or(0) / 1_000_000;
+            format!(
+                "HPET: {} Hz (period {} fs), {}-bit counter\ncounter: {} ({} ms)\n",
+                hpet.frequency_hz(),
+                hpet.period_fs,
+                if hpet.counter_64 { 64 } else { 32 },
+                count,
+                ms
+            )
+        }
+        None => format!(
+            "HPET: not present, delays use the TSC ({} ticks/us)\n",
+            tsc_per_us()
+        ),
+    }
+}
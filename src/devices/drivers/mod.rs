@@ -3,8 +3,18 @@
 //! This module contains drivers for various hardware devices:
 //! - PS/2 Keyboard (IRQ1)
 //! - PS/2 Mouse (IRQ12)
+//! - CMOS real-time clock
+//! - HPET main counter (high-resolution delays)
+//! - PC speaker (PIT channel 2)
+//! - COM1 serial port
+//!
+//! Each device's `Driver` for `devices::registry` lives with its driver.
+pub mod hpet;
+pub mod pc_speaker;
 pub mod ps2_keyboard;
 pub mod ps2_mouse;
+pub mod rtc;
+pub mod serial;
 
 #[allow(unused)]
 pub use ps2_keyboard::{dequeue_scancode, enqueue_scancode, KeyEvent, ScancodeDecoder};
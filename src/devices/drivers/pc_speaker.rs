@@ -0,0 +1,38 @@
+//! PC speaker
+//!
+//! PIT channel 2 runs as a square-wave generator at the requested pitch
+//! and port 0x61 gates it onto the speaker. There is no timing here: the
+//! caller decides when to `stop`.
+use x86_64::instructions::port::Port;
+
+const PIT_HZ: u32 = 1_193_182;
+const PIT_CHANNEL_2: u16 = 0x42;
+const PIT_COMMAND: u16 = 0x43;
+/// Channel 2, lobyte/hibyte, mode 3 (square wave).
+const CHANNEL_2_SQUARE: u8 = 0xB6;
+const SPEAKER_PORT: u16 = 0x61;
+/// Bit 0 gates channel 2, bit 1 connects it to the speaker.
+const SPEAKER_ON: u8 = 0b11;
+
+/// Starts a tone at `hz` (clamped to what the PIT divisor can reach).
+pub fn start(hz: u32) {
+    let divisor = (PIT_HZ / hz.max(19)).clamp(1, u16::MAX as u32) as u16;
+    unsafe {
+        Port::<u8>::new(PIT_COMMAND).write(CHANNEL_2_SQUARE);
+        let mut data = Port::<u8>::new(PIT_CHANNEL_2);
+        data.write(divisor as u8);
+        data.write((divisor >> 8) as u8);
+
+        let mut gate = Port::<u8>::new(SPEAKER_PORT);
+        let value = gate.read();
+        gate.write(value | SPEAKER_ON);
+    }
+}
+
+pub fn stop() {
+    unsafe {
+        let mut gate = Port::<u8>::new(SPEAKER_PORT);
+        let value = gate.read();
+        gate.write(value & !SPEAKER_ON);
+    }
+}
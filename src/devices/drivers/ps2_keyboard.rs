@@ -1,37 +1,169 @@
 //! # PS/2 Keyboard Driver
 //!
 //! Handles PS/2 keyboard input via IRQ1 interrupt.
+//!
+//! The IRQ handler queues raw scancodes in a ring the main loop drains
+//! each pass. When the ring is full new scancodes are dropped and counted
+//! (`dropped`, shown by `irq`). An `0xE0` prefix is only queued with room
+//! for the byte after it, and a dropped prefix takes that byte with it, so
+//! an overflow never hands the decoder half an extended key.
+//!
+//! ## Waiters
+//!
+//! A task that wants input without polling registers itself with
+//! `register_waiter` and sleeps. After queueing a scancode the IRQ handler
+//! raises `WAKE`; the scheduler, outside interrupt context, collects the
+//! registered ids with `drain_woken` and sends each `Signal::Key`, which
+//! ends its sleep. A wake-up is one-shot: a task registers again for the
+//! next one.
+//!
+//! The IRQ side only touches atomics: no lock, no allocation, nothing
+//! that can spin on state the interrupted code holds. Slots are claimed
+//! with a compare-exchange from 0 (free), so registering never blocks
+//! either. To not miss input that arrives while it registers, a reader
+//! registers first and checks `has_pending` second, and only sleeps if
+//! that is still false.
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::app::{Arrow, KeyCode, Modifiers};
+use crate::devices::registry::{Driver, DriverError};
+use alloc::{format, string::String};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
-const BUFFER_SIZE: usize = 256;
+const BUFFER_SIZE: usize = 1024;
 
 static mut RING_BUF: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+/// TSC at IRQ time per slot, only filled while latency sampling is on.
+static mut TSC_BUF: [u64; BUFFER_SIZE] = [0; BUFFER_SIZE];
 static HEAD: AtomicUsize = AtomicUsize::new(0);
 static TAIL: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+/// Set when an `0xE0` was dropped, so the byte it prefixes is dropped too.
+static DROP_NEXT: AtomicBool = AtomicBool::new(false);
+
+/// Most tasks waiting for input at once.
+pub const MAX_WAITERS: usize = 8;
+/// Ids of the tasks waiting for input; 0 marks a free slot.
+static WAITERS: [AtomicU64; MAX_WAITERS] = [const { AtomicU64::new(0) }; MAX_WAITERS];
+/// Raised by the IRQ after queueing, lowered by `drain_woken`.
+static WAKE: AtomicBool = AtomicBool::new(false);
 
 pub fn enqueue_scancode(scancode: u8) {
+    if DROP_NEXT.swap(false, Ordering::Relaxed) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
     let head = HEAD.load(Ordering::Relaxed);
-    let next = head.wrapping_add(1) % BUFFER_SIZE;
     let tail = TAIL.load(Ordering::Acquire);
-    if next != tail {
-        unsafe {
-            RING_BUF[head] = scancode;
+    let free = (tail + BUFFER_SIZE - head - 1) % BUFFER_SIZE;
+    let needed = if scancode == 0xE0 { 2 } else { 1 };
+    if free < needed {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        DROP_NEXT.store(scancode == 0xE0, Ordering::Relaxed);
+        return;
+    }
+    unsafe {
+        RING_BUF[head] = scancode;
+        TSC_BUF[head] = crate::stats::latency::irq_timestamp();
+    }
+    HEAD.store((head + 1) % BUFFER_SIZE, Ordering::Release);
+    WAKE.store(true, Ordering::Release);
+}
+
+/// Asks for task `task_id` to be woken by the next scancode. False if all
+/// `MAX_WAITERS` slots are taken.
+pub fn register_waiter(task_id: u64) -> bool {
+    if task_id == 0 {
+        return false;
+    }
+    if WAITERS.iter().any(|w| w.load(Ordering::Acquire) == task_id) {
+        return true;
+    }
+    WAITERS.iter().any(|w| {
+        w.compare_exchange(0, task_id, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    })
+}
+
+/// Withdraws a registration, for a waiter that stops waiting. False if
+/// it was not registered (or was already woken).
+pub fn unregister_waiter(task_id: u64) -> bool {
+    task_id != 0
+        && WAITERS.iter().any(|w| {
+            w.compare_exchange(task_id, 0, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        })
+}
+
+/// If input arrived since the last call, hands every registered waiter
+/// to `wake` and frees its slot. Not for IRQ context: `wake` is expected
+/// to take the scheduler lock.
+pub fn drain_woken(mut wake: impl FnMut(u64)) {
+    if !WAKE.swap(false, Ordering::Acquire) {
+        return;
+    }
+    for slot in &WAITERS {
+        let id = slot.swap(0, Ordering::AcqRel);
+        if id != 0 {
+            wake(id);
         }
-        HEAD.store(next, Ordering::Release);
     }
 }
 
+/// Scancodes dropped because the ring was full, since boot.
+pub fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// One line for `irq`.
+pub fn report() -> String {
+    format!(
+        "keyboard: {} scancodes dropped, {} of {} queued",
+        dropped(),
+        (HEAD.load(Ordering::Acquire) + BUFFER_SIZE - TAIL.load(Ordering::Relaxed)) % BUFFER_SIZE,
+        BUFFER_SIZE - 1
+    )
+}
+
+/// The `keyboard` driver: the controller already runs the keyboard at
+/// boot, so init only unmasks IRQ1.
+pub struct Keyboard;
+
+impl Driver for Keyboard {
+    fn name(&self) -> &'static str {
+        "keyboard"
+    }
+
+    fn init(&mut self) -> Result<(), DriverError> {
+        crate::kcore::interrupts::pic::unmask(1);
+        Ok(())
+    }
+
+    /// The console needs it.
+    fn suspend(&mut self) -> Result<(), DriverError> {
+        Err(DriverError::Failed("the console needs the keyboard"))
+    }
+}
+
+/// Whether a scancode is waiting, without taking it.
+pub fn has_pending() -> bool {
+    TAIL.load(Ordering::Relaxed) != HEAD.load(Ordering::Acquire)
+}
+
 pub fn dequeue_scancode() -> Option<u8> {
+    dequeue_scancode_timed().map(|(sc, _)| sc)
+}
+
+/// Like `dequeue_scancode`, also returning the IRQ-time TSC (0 if unsampled).
+pub fn dequeue_scancode_timed() -> Option<(u8, u64)> {
     let tail = TAIL.load(Ordering::Relaxed);
     let head = HEAD.load(Ordering::Acquire);
     if tail == head {
         None
     } else {
-        let sc = unsafe { RING_BUF[tail] };
+        let entry = unsafe { (RING_BUF[tail], TSC_BUF[tail]) };
         let next = tail.wrapping_add(1) % BUFFER_SIZE;
         TAIL.store(next, Ordering::Release);
-        Some(sc)
+        Some(entry)
     }
 }
 
@@ -40,6 +172,8 @@ pub struct ScancodeDecoder {
     shift_pressed: bool,
     ctrl_pressed: bool,
     alt_pressed: bool,
+    /// Toggled by each Caps Lock press; flips the case of letters.
+    caps_lock: bool,
 }
 
 impl ScancodeDecoder {
@@ -49,6 +183,33 @@ impl ScancodeDecoder {
             shift_pressed: false,
             ctrl_pressed: false,
             alt_pressed: false,
+            caps_lock: false,
+        }
+    }
+
+    pub fn caps_lock(&self) -> bool {
+        self.caps_lock
+    }
+
+    fn mods(&self) -> Modifiers {
+        let mut mods = Modifiers::NONE;
+        if self.shift_pressed {
+            mods |= Modifiers::SHIFT;
+        }
+        if self.ctrl_pressed {
+            mods |= Modifiers::CTRL;
+        }
+        if self.alt_pressed {
+            mods |= Modifiers::ALT;
+        }
+        mods
+    }
+
+    fn key(&self, code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            mods: self.mods(),
+            tsc: 0,
         }
     }
 
@@ -68,51 +229,20 @@ impl ScancodeDecoder {
                 return None;
             }
 
-            match key_code {
-                0x48 => {
-                    return Some(KeyEvent {
-                        character: '\0',
-                        ctrl: self.ctrl_pressed,
-                        alt: self.alt_pressed,
-                        shift: self.shift_pressed,
-                        is_arrow: true,
-                        arrow_direction: Some(crate::app::Arrow::Up),
-                    });
-                }
-                0x50 => {
-                    return Some(KeyEvent {
-                        character: '\0',
-                        ctrl: self.ctrl_pressed,
-                        alt: self.alt_pressed,
-                        shift: self.shift_pressed,
-                        is_arrow: true,
-                        arrow_direction: Some(crate::app::Arrow::Down),
-                    });
-                }
-                0x4B => {
-                    return Some(KeyEvent {
-                        character: '\0',
-                        ctrl: self.ctrl_pressed,
-                        alt: self.alt_pressed,
-                        shift: self.shift_pressed,
-                        is_arrow: true,
-                        arrow_direction: Some(crate::app::Arrow::Left),
-                    });
-                }
-                0x4D => {
-                    return Some(KeyEvent {
-                        character: '\0',
-                        ctrl: self.ctrl_pressed,
-                        alt: self.alt_pressed,
-                        shift: self.shift_pressed,
-                        is_arrow: true,
-                        arrow_direction: Some(crate::app::Arrow::Right),
-                    });
-                }
-                _ => {
-                    return None;
-                }
-            }
+            let code = match key_code {
+                0x48 => KeyCode::Arrow(Arrow::Up),
+                0x50 => KeyCode::Arrow(Arrow::Down),
+                0x4B => KeyCode::Arrow(Arrow::Left),
+                0x4D => KeyCode::Arrow(Arrow::Right),
+                0x47 => KeyCode::Home,
+                0x4F => KeyCode::End,
+                0x49 => KeyCode::PageUp,
+                0x51 => KeyCode::PageDown,
+                0x52 => KeyCode::Insert,
+                0x53 => KeyCode::Delete,
+                _ => return None,
+            };
+            return Some(self.key(code));
         }
 
         match key_code {
@@ -131,6 +261,11 @@ impl ScancodeDecoder {
                 self.alt_pressed = !is_release;
                 return None;
             }
+            0x3A => {
+                // Caps Lock
+                self.caps_lock ^= !is_release;
+                return None;
+            }
             _ => {}
         }
 
@@ -138,16 +273,24 @@ impl ScancodeDecoder {
             return None;
         }
 
-        let ch = self.scancode_to_char(key_code);
-
-        ch.map(|c| KeyEvent {
-            character: c,
-            ctrl: self.ctrl_pressed,
-            alt: self.alt_pressed,
-            shift: self.shift_pressed,
-            is_arrow: false,
-            arrow_direction: None,
-        })
+        let code = match key_code {
+            0x1C => KeyCode::Enter,
+            0x0E => KeyCode::Backspace,
+            0x0F => KeyCode::Tab,
+            0x01 => KeyCode::Escape,
+            0x3B..=0x44 => KeyCode::Function(key_code - 0x3B + 1), // F1-F10
+            0x57 => KeyCode::Function(11),
+            0x58 => KeyCode::Function(12),
+            _ => {
+                let ch = self.scancode_to_char(key_code)?;
+                if self.caps_lock && ch.is_ascii_alphabetic() {
+                    KeyCode::Char((ch as u8 ^ 0x20) as char)
+                } else {
+                    KeyCode::Char(ch)
+                }
+            }
+        };
+        Some(self.key(code))
     }
 
     fn scancode_to_char(&self, scancode: u8) -> Option<char> {
@@ -193,23 +336,6 @@ impl ScancodeDecoder {
             0x32 => if self.shift_pressed { 'M' } else { 'm' },
 
             0x39 => ' ',  // Space
-            0x1C => '\n', // Enter
-            0x0E => '\x08', // Backspace
-            0x0F => '\t', // Tab
-
-            // Function keys F1-F10 mapped to special chars
-            0x3B => '\x11', // F1 -> DC1 (Ctrl+Q)
-            0x3C => '\x12', // F2 -> DC2 (Ctrl+R)
-            0x3D => '\x13', // F3 -> DC3 (Ctrl+S)
-            0x3E => '\x14', // F4 -> DC4 (Ctrl+T)
-            0x3F => '\x15', // F5
-            0x40 => '\x16', // F6
-            0x41 => '\x17', // F7
-            0x42 => '\x18', // F8
-            0x43 => '\x19', // F9
-            0x44 => '\x1A', // F10
-            0x57 => '\x1B', // F11 -> ESC
-            0x58 => '\x1C', // F12
 
             0x1A => if self.shift_pressed { '{' } else { '[' },
             0x1B => if self.shift_pressed { '}' } else { ']' },
@@ -232,11 +358,216 @@ impl ScancodeDecoder {
 
 #[derive(Debug, Clone, Copy)]
 pub struct KeyEvent {
-    pub character: char,
-    pub ctrl: bool,
-    pub alt: bool,
-    pub shift: bool,
-    pub is_arrow: bool,
-    /// Arrow direction (Some) if `is_arrow == true`, otherwise None
-    pub arrow_direction: Option<crate::app::Arrow>,
+    pub code: KeyCode,
+    pub mods: Modifiers,
+    /// TSC of the IRQ that delivered the final scancode (0 if unsampled)
+    pub tsc: u64,
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// A key as the decoder reported it before `KeyCode`: a char, with
+    /// control codes standing in for keys that type nothing, the
+    /// modifiers, and the arrow (whose char was `'\0'`).
+    type Legacy = (char, bool, bool, bool, Option<Arrow>);
+
+    fn legacy(key: &KeyEvent) -> Legacy {
+        let ch = match key.code {
+            KeyCode::Arrow(_) => '\0',
+            KeyCode::Char(ch) => ch,
+            KeyCode::Enter => '\n',
+            KeyCode::Backspace => '\x08',
+            KeyCode::Tab => '\t',
+            KeyCode::Escape => '\x1B',
+            KeyCode::Home => '\x01',
+            KeyCode::End => '\x05',
+            KeyCode::Insert => '\x1D',
+            KeyCode::Delete => '\x7F',
+            KeyCode::Function(n @ 1..=10) => (0x10 + n) as char,
+            KeyCode::Function(11) => '\x1B',
+            KeyCode::Function(12) => '\x1C',
+            other => panic!("{:?} was not decoded before", other),
+        };
+        let arrow = match key.code {
+            KeyCode::Arrow(dir) => Some(dir),
+            _ => None,
+        };
+        (ch, key.mods.ctrl(), key.mods.alt(), key.mods.shift(), arrow)
+    }
+
+    fn decode(scancodes: &[u8]) -> Vec<KeyEvent> {
+        let mut decoder = ScancodeDecoder::new();
+        scancodes
+            .iter()
+            .filter_map(|&sc| decoder.process_scancode(sc))
+            .collect()
+    }
+
+    fn plain(ch: char) -> Legacy {
+        (ch, false, false, false, None)
+    }
+
+    #[test_case]
+    fn decoding_matches_the_old_encoding() {
+        let up = Some(Arrow::Up);
+        let left = Some(Arrow::Left);
+        let corpus: &[(&[u8], &[Legacy])] = &[
+            (&[0x1E, 0x9E], &[plain('a')]),
+            (
+                &[0x2A, 0x1E, 0xAA, 0x1E],
+                &[('A', false, false, true, None), plain('a')],
+            ),
+            (
+                &[0x2A, 0x02, 0x0B, 0x35],
+                &[
+                    ('!', false, false, true, None),
+                    (')', false, false, true, None),
+                    ('?', false, false, true, None),
+                ],
+            ),
+            (
+                &[0x1D, 0x13, 0x9D, 0x13],
+                &[('r', true, false, false, None), plain('r')],
+            ),
+            (&[0x38, 0x0F, 0xB8], &[('\t', false, true, false, None)]),
+            (
+                &[0x1C, 0x0E, 0x01, 0x39],
+                &[plain('\n'), plain('\x08'), plain('\x1B'), plain(' ')],
+            ),
+            (
+                &[0xE0, 0x48, 0xE0, 0xC8],
+                &[('\0', false, false, false, up)],
+            ),
+            (&[0x1D, 0xE0, 0x4B], &[('\0', true, false, false, left)]),
+            (
+                &[0xE0, 0x47, 0xE0, 0x4F, 0xE0, 0x52, 0xE0, 0x53],
+                &[plain('\x01'), plain('\x05'), plain('\x1D'), plain('\x7F')],
+            ),
+            (
+                &[0x3B, 0x3E, 0x44, 0x57, 0x58],
+                &[
+                    plain('\x11'),
+                    plain('\x14'),
+                    plain('\x1A'),
+                    plain('\x1B'),
+                    plain('\x1C'),
+                ],
+            ),
+            // Caps Lock flips letters only, and Shift flips them back
+            (
+                &[0x3A, 0xBA, 0x1E, 0x02, 0x2A, 0x1E, 0xAA, 0x3A, 0xBA, 0x1E],
+                &[
+                    plain('A'),
+                    plain('1'),
+                    ('a', false, false, true, None),
+                    plain('a'),
+                ],
+            ),
+            // releases, SysRq and the Windows key decode to nothing
+            (&[0x9E, 0xE0, 0xC8, 0x54, 0xE0, 0x5B], &[]),
+        ];
+        for (scancodes, expected) in corpus {
+            let got: Vec<Legacy> = decode(scancodes).iter().map(legacy).collect();
+            assert_eq!(&got[..], *expected, "scancodes {:x?}", scancodes);
+        }
+    }
+
+    #[test_case]
+    fn flooding_counts_drops_and_keeps_e0_pairs_whole() {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            while dequeue_scancode().is_some() {}
+            let before = dropped();
+
+            // one slot short of full, then an extended key that needs two
+            for _ in 0..BUFFER_SIZE - 2 {
+                enqueue_scancode(0x1E);
+            }
+            enqueue_scancode(0xE0);
+            enqueue_scancode(0x48);
+            assert_eq!(dropped() - before, 2);
+            enqueue_scancode(0x1F);
+            for _ in 0..10 {
+                enqueue_scancode(0x20);
+            }
+            assert_eq!(dropped() - before, 12);
+
+            let mut decoder = ScancodeDecoder::new();
+            let mut keys = Vec::new();
+            while let Some(sc) = dequeue_scancode() {
+                keys.extend(decoder.process_scancode(sc));
+            }
+            assert_eq!(keys.len(), BUFFER_SIZE - 1);
+            assert!(keys[..BUFFER_SIZE - 2].iter().all(|k| k.code == KeyCode::Char('a')));
+            assert_eq!(keys[BUFFER_SIZE - 2].code, KeyCode::Char('s'));
+        });
+    }
+
+    #[test_case]
+    fn every_make_code() {
+        let mut keys = 0;
+        for sc in 0x00..0x80u8 {
+            let Some(key) = decode(&[sc]).pop() else {
+                continue;
+            };
+            keys += 1;
+            assert!(decode(&[sc | 0x80]).is_empty(), "release of {:#x}", sc);
+            let shifted = decode(&[0x2A, sc]).pop().unwrap();
+            assert!(shifted.mods.shift());
+            match (key.code, shifted.code) {
+                (KeyCode::Char(' '), KeyCode::Char(' ')) => {}
+                (KeyCode::Char(a), KeyCode::Char(b)) => assert_ne!(a, b, "shift on {:#x}", sc),
+                (a, b) => assert_eq!(a, b),
+            }
+        }
+        // 48 printable keys, Enter, Backspace, Tab, Escape and F1-F12
+        assert_eq!(keys, 64);
+
+        let extended: Vec<KeyCode> = (0x00..0x80u8)
+            .filter_map(|sc| decode(&[0xE0, sc]).pop())
+            .map(|key| key.code)
+            .collect();
+        assert_eq!(extended.len(), 10);
+        assert!(extended.contains(&KeyCode::PageUp));
+        assert!(extended.contains(&KeyCode::PageDown));
+    }
+
+    #[test_case]
+    fn scancode_wakes_each_waiter_once() {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            while dequeue_scancode().is_some() {}
+            drain_woken(|_| {});
+
+            assert!(register_waiter(41));
+            assert!(register_waiter(42));
+            assert!(register_waiter(42));
+            assert!(!register_waiter(0));
+            let mut woken = Vec::new();
+            drain_woken(|id| woken.push(id));
+            assert!(woken.is_empty(), "no input yet");
+
+            assert!(unregister_waiter(41));
+            enqueue_scancode(0x1E);
+            drain_woken(|id| woken.push(id));
+            assert_eq!(woken, [42]);
+            // one-shot: the slot was freed
+            drain_woken(|id| woken.push(id));
+            enqueue_scancode(0x9E);
+            drain_woken(|id| woken.push(id));
+            assert_eq!(woken, [42]);
+            assert!(!unregister_waiter(42));
+
+            let ids: Vec<u64> = (100..100 + MAX_WAITERS as u64).collect();
+            assert!(ids.iter().all(|&id| register_waiter(id)));
+            assert!(!register_waiter(99));
+            ids.iter().for_each(|&id| {
+                unregister_waiter(id);
+            });
+            while dequeue_scancode().is_some() {}
+        });
+    }
 }
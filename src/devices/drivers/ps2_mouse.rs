@@ -1,6 +1,34 @@
 //! PS/2 Mouse Driver
-
-use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+//!
+//! ## Resync and reset
+//!
+//! A lost byte leaves the decoder reading packets out of step. It notices
+//! in two ways: a byte without the always-set bit 3 where a status byte
+//! belongs, and a packet whose overflow bits are set, which a real mouse
+//! almost never sends but a shifted stream often does. A skipped byte is
+//! dropped; an invalid packet is dropped and its last two bytes are fed
+//! back in, so the decoder slides forward a byte at a time until packets
+//! line up again. Each run of bad input up to the next good packet counts
+//! as one desync.
+//!
+//! When `reset_threshold` bad bytes and packets arrive with no good packet
+//! between them, `poll_mouse_event` gives up on resyncing and calls `reset`, which
+//! re-runs the mouse's side of the setup. `mouse reset` does the same by
+//! hand, which is also how a mouse plugged in after boot is picked up.
+//!
+//! ## Coalescing
+//!
+//! A fast move queues dozens of packets between two frames. The main loop
+//! takes them through `poll_coalesced`, which folds a burst into one event
+//! per button press or release, carrying the movement up to it so the
+//! click lands where it happened, plus at most one trailing motion event
+//! with the rest. A slow move, one packet per frame, comes through
+//! unchanged, and a drag still sees its position once per frame.
+//! `mousestat` shows packets in against events out.
+
+use crate::devices::registry::{Driver, DriverError, DriverStatus};
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use spin::Mutex;
 use x86_64::instructions::port::Port;
 
@@ -15,6 +43,11 @@ static MOUSE_HEAD: AtomicU8 = AtomicU8::new(0);
 static MOUSE_TAIL: AtomicU8 = AtomicU8::new(0);
 static MOUSE_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+/// Bad bytes and packets in a row that make `poll_mouse_event` reset the
+/// mouse; 0 never.
+static RESET_THRESHOLD: AtomicU32 = AtomicU32::new(16);
+static RESETS: AtomicU64 = AtomicU64::new(0);
+
 #[inline]
 pub fn enqueue_mouse_byte(byte: u8) {
     let head = MOUSE_HEAD.load(Ordering::Relaxed) as usize;
@@ -47,7 +80,7 @@ fn dequeue_mouse_byte() -> Option<u8> {
 // MOUSE EVENT
 // =============================================================================
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct MouseEvent {
     pub dx: i16,
     pub dy: i16,
@@ -78,6 +111,12 @@ impl MouseEvent {
 pub struct MouseDecoder {
     buffer: [u8; 3],
     index: usize,
+    /// Desyncs since boot.
+    desyncs: u64,
+    /// Skipped bytes and invalid packets since the last good packet.
+    invalid: u32,
+    /// In a desync that has not yet ended in a good packet.
+    resyncing: bool,
 }
 
 impl MouseDecoder {
@@ -85,27 +124,61 @@ impl MouseDecoder {
         Self {
             buffer: [0; 3],
             index: 0,
+            desyncs: 0,
+            invalid: 0,
+            resyncing: false,
         }
     }
 
     pub fn process_byte(&mut self, byte: u8) -> Option<MouseEvent> {
         if self.index == 0 && (byte & 0x08) == 0 {
+            self.desync();
             return None;
         }
 
         self.buffer[self.index] = byte;
         self.index += 1;
 
-        if self.index >= 3 {
-            self.index = 0;
-            Some(self.decode_packet())
-        } else {
-            None
+        if self.index < 3 {
+            return None;
+        }
+        self.index = 0;
+        if self.buffer[0] & 0xC0 != 0 {
+            self.desync();
+            let [_, b1, b2] = self.buffer;
+            for byte in [b1, b2] {
+                self.process_byte(byte);
+            }
+            return None;
+        }
+        self.resyncing = false;
+        self.invalid = 0;
+        Some(self.decode_packet())
+    }
+
+    fn desync(&mut self) {
+        self.invalid = self.invalid.saturating_add(1);
+        if !self.resyncing {
+            self.resyncing = true;
+            self.desyncs += 1;
         }
     }
 
+    pub fn desyncs(&self) -> u64 {
+        self.desyncs
+    }
+
+    /// Whether `threshold` bad bytes and packets have passed without a
+    /// good packet.
+    pub fn needs_reset(&self, threshold: u32) -> bool {
+        threshold != 0 && self.invalid >= threshold
+    }
+
+    /// Starts over at a packet boundary. The desync total is kept.
     pub fn reset(&mut self) {
         self.index = 0;
+        self.invalid = 0;
+        self.resyncing = false;
     }
 
     fn decode_packet(&self) -> MouseEvent {
@@ -153,11 +226,92 @@ impl MouseDecoder {
     }
 }
 
+// =============================================================================
+// COALESCING
+// =============================================================================
+
+/// Merges a burst of packets; keeps the button state between bursts so a
+/// burst's first packet is only an edge if the buttons really changed.
+pub struct Coalescer {
+    buttons: u8,
+    dx: i16,
+    dy: i16,
+}
+
+impl Coalescer {
+    pub const fn new() -> Self {
+        Self {
+            buttons: 0,
+            dx: 0,
+            dy: 0,
+        }
+    }
+
+    /// Adds one packet, pushing an event to `out` if its buttons differ
+    /// from the last ones seen.
+    pub fn push(&mut self, packet: MouseEvent, out: &mut Vec<MouseEvent>) {
+        self.dx = self.dx.saturating_add(packet.dx);
+        self.dy = self.dy.saturating_add(packet.dy);
+        if packet.buttons != self.buttons {
+            self.buttons = packet.buttons;
+            out.push(self.take());
+        }
+    }
+
+    /// Ends the burst with the movement not yet reported, if any.
+    pub fn finish(&mut self, out: &mut Vec<MouseEvent>) {
+        if self.dx != 0 || self.dy != 0 {
+            out.push(self.take());
+        }
+    }
+
+    fn take(&mut self) -> MouseEvent {
+        let event = MouseEvent {
+            dx: self.dx,
+            dy: self.dy,
+            buttons: self.buttons,
+        };
+        self.dx = 0;
+        self.dy = 0;
+        event
+    }
+}
+
 // =============================================================================
 // GLOBAL DECODER
 // =============================================================================
 
 static DECODER: Mutex<MouseDecoder> = Mutex::new(MouseDecoder::new());
+static COALESCER: Mutex<Coalescer> = Mutex::new(Coalescer::new());
+static PACKETS: AtomicU64 = AtomicU64::new(0);
+static EMITTED: AtomicU64 = AtomicU64::new(0);
+
+/// Drains every decoded packet into `out`, coalesced. For the main loop,
+/// once per pass.
+pub fn poll_coalesced(out: &mut Vec<MouseEvent>) {
+    let start = out.len();
+    let mut packets = 0;
+    let mut coalescer = COALESCER.lock();
+    while let Some(packet) = poll_mouse_event() {
+        packets += 1;
+        coalescer.push(packet, out);
+    }
+    coalescer.finish(out);
+    PACKETS.fetch_add(packets, Ordering::Relaxed);
+    EMITTED.fetch_add((out.len() - start) as u64, Ordering::Relaxed);
+}
+
+/// One line for `mousestat`.
+pub fn coalesce_report() -> String {
+    let packets = PACKETS.load(Ordering::Relaxed);
+    let emitted = EMITTED.load(Ordering::Relaxed);
+    format!(
+        "mouse: {} packets in, {} events out ({} merged away)",
+        packets,
+        emitted,
+        packets.saturating_sub(emitted)
+    )
+}
 
 pub fn poll_mouse_event() -> Option<MouseEvent> {
     let mut decoder = DECODER.lock();
@@ -166,6 +320,13 @@ pub fn poll_mouse_event() -> Option<MouseEvent> {
         if let Some(event) = decoder.process_byte(byte) {
             return Some(event);
         }
+        if decoder.needs_reset(reset_threshold()) {
+            drop(decoder);
+            if let Err(err) = reset() {
+                crate::log_warn!("mouse: reset after repeated desyncs failed: {}", err);
+            }
+            return None;
+        }
     }
 
     None
@@ -175,6 +336,33 @@ pub fn is_initialized() -> bool {
     MOUSE_INITIALIZED.load(Ordering::Relaxed)
 }
 
+pub fn reset_threshold() -> u32 {
+    RESET_THRESHOLD.load(Ordering::Relaxed)
+}
+
+pub fn set_reset_threshold(bad: u32) {
+    RESET_THRESHOLD.store(bad, Ordering::Relaxed);
+}
+
+/// One line for the `mouse` command.
+pub fn report() -> String {
+    let threshold = match reset_threshold() {
+        0 => String::from("never"),
+        n => format!("after {} bad bytes", n),
+    };
+    format!(
+        "mouse: {}, {} desyncs, {} resets, auto-reset {}",
+        if is_initialized() {
+            "ready"
+        } else {
+            "not initialized"
+        },
+        DECODER.lock().desyncs(),
+        RESETS.load(Ordering::Relaxed),
+        threshold
+    )
+}
+
 // =============================================================================
 // INITIALIZATION
 // =============================================================================
@@ -260,6 +448,86 @@ fn send_mouse_command(cmd: u8) -> Result<u8, &'static str> {
     Err("Mouse did not ACK command")
 }
 
+/// Re-runs the mouse's side of the setup, without the controller
+/// self-test that would also disturb the keyboard: reopens the aux port,
+/// resets the mouse (0xFF), restores defaults and turns reporting back on.
+/// Bytes queued from the old stream are dropped and the decoder starts
+/// over. `lsdev` shows the mouse down while this fails.
+pub fn reset() -> Result<(), &'static str> {
+    let result = restart();
+    RESETS.fetch_add(1, Ordering::Relaxed);
+    result
+}
+
+/// `reset` without the count; also the driver's init.
+fn restart() -> Result<(), &'static str> {
+    // The IRQ handler would otherwise take the replies off port 0x60
+    let result = x86_64::instructions::interrupts::without_interrupts(|| {
+        send_controller_command(0xA7)?;
+        flush_output_buffer();
+        send_controller_command(0xA8)?;
+
+        send_controller_command(0x20)?;
+        let config = read_data()?;
+        send_controller_command(0x60)?;
+        send_data((config | 0x02) & !0x20)?;
+
+        send_mouse_command(0xFF)?;
+        // The self-test can take a few hundred milliseconds; then comes
+        // the device id, which is not needed
+        if (0..10).find_map(|_| read_data().ok()) != Some(0xAA) {
+            return Err("Mouse self-test failed");
+        }
+        let _ = read_data();
+
+        send_mouse_command(0xF6)?;
+        send_mouse_command(0xF4)?;
+        Ok(())
+    });
+
+    MOUSE_TAIL.store(MOUSE_HEAD.load(Ordering::Acquire), Ordering::Release);
+    DECODER.lock().reset();
+    MOUSE_INITIALIZED.store(result.is_ok(), Ordering::Release);
+    result
+}
+
+/// The `mouse` driver. A mouse that misses an ACK often answers the next
+/// time, so every failure is worth the registry's retry.
+pub struct Mouse;
+
+impl Driver for Mouse {
+    fn name(&self) -> &'static str {
+        "mouse"
+    }
+
+    fn depends_on(&self) -> &'static [&'static str] {
+        &["keyboard"]
+    }
+
+    fn init(&mut self) -> Result<(), DriverError> {
+        restart().map_err(DriverError::Transient)
+    }
+
+    fn status(&self) -> DriverStatus {
+        if is_initialized() {
+            DriverStatus::Ok
+        } else {
+            DriverStatus::Failed("lost after a reset")
+        }
+    }
+
+    /// Stops data reporting; the IRQ stays unmasked.
+    fn suspend(&mut self) -> Result<(), DriverError> {
+        x86_64::instructions::interrupts::without_interrupts(|| send_mouse_command(0xF5))
+            .map(|_| ())
+            .map_err(DriverError::Failed)
+    }
+
+    fn resume(&mut self) -> Result<(), DriverError> {
+        restart().map_err(DriverError::Transient)
+    }
+}
+
 /// Initialize PS/2 mouse
 ///
 /// This function enables the auxiliary (mouse) port on the PS/2 controller
@@ -344,3 +612,94 @@ pub fn init() -> Result<(), &'static str> {
 
     Ok(())
 }
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(decoder: &mut MouseDecoder, bytes: &[u8]) -> Option<MouseEvent> {
+        let mut last = None;
+        for &byte in bytes {
+            if let Some(event) = decoder.process_byte(byte) {
+                last = Some(event);
+            }
+        }
+        last
+    }
+
+    fn packet(dx: i16, dy: i16, buttons: u8) -> MouseEvent {
+        MouseEvent { dx, dy, buttons }
+    }
+
+    #[test_case]
+    fn bursts_keep_every_click_and_one_motion() {
+        let mut coalescer = Coalescer::new();
+        let mut out = Vec::new();
+
+        // A fast move: ten packets, one event with the sum
+        for _ in 0..10 {
+            coalescer.push(packet(3, -2, 0), &mut out);
+        }
+        coalescer.finish(&mut out);
+        assert_eq!(out, [packet(30, -20, 0)]);
+
+        // A click in the middle of a move lands where it happened
+        out.clear();
+        for p in [
+            packet(4, 0, 0),
+            packet(4, 0, 1),
+            packet(5, 0, 1),
+            packet(1, 1, 0),
+            packet(2, 2, 0),
+        ] {
+            coalescer.push(p, &mut out);
+        }
+        coalescer.finish(&mut out);
+        assert_eq!(out, [packet(8, 0, 1), packet(6, 1, 0), packet(2, 2, 0)]);
+
+        // A slow move comes through as it was; a held button is no edge
+        out.clear();
+        coalescer.push(packet(0, 0, 1), &mut out);
+        coalescer.finish(&mut out);
+        coalescer.push(packet(1, 0, 1), &mut out);
+        coalescer.finish(&mut out);
+        assert_eq!(out, [packet(0, 0, 1), packet(1, 0, 1)]);
+        coalescer.push(packet(0, 0, 1), &mut out);
+        coalescer.finish(&mut out);
+        assert_eq!(out.len(), 2);
+    }
+
+    #[test_case]
+    fn misaligned_bytes_resync_to_the_next_packet() {
+        let mut decoder = MouseDecoder::new();
+
+        // A packet that lost its status byte: both leftovers lack bit 3
+        assert!(feed(&mut decoder, &[0x05, 0x03]).is_none());
+        let event = feed(&mut decoder, &[0x09, 0x05, 0x03]).unwrap();
+        assert_eq!((event.dx, event.dy, event.buttons), (5, -3, 1));
+        assert_eq!(decoder.desyncs(), 1);
+
+        // Leftovers that look like a status byte with overflow set: the
+        // decoder slides forward until the next real packet lines up
+        assert!(feed(&mut decoder, &[0x48, 0x02]).is_none());
+        assert!(feed(&mut decoder, &[0x08]).is_none());
+        let event = feed(&mut decoder, &[0x01, 0x01]).unwrap();
+        assert_eq!((event.dx, event.dy, event.buttons), (1, -1, 0));
+        assert_eq!(decoder.desyncs(), 2);
+        assert!(!decoder.needs_reset(1));
+
+        // Garbage with no good packet in it is one desync, but every bad
+        // byte and packet counts towards a reset
+        for _ in 0..3 {
+            feed(&mut decoder, &[0x00, 0xC8, 0x01, 0x02]);
+        }
+        assert_eq!(decoder.desyncs(), 3);
+        assert!(decoder.needs_reset(12));
+        assert!(!decoder.needs_reset(13));
+        assert!(!decoder.needs_reset(0));
+        decoder.reset();
+        assert!(!decoder.needs_reset(1));
+    }
+}
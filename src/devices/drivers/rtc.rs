@@ -0,0 +1,107 @@
+//! CMOS real-time clock (read-only)
+//!
+//! Reads the wall-clock time from the MC146818-compatible RTC through ports
+//! 0x70/0x71. Registers are read twice until two reads agree, so an update
+//! cycle in between cannot produce a torn value. BCD and 12-hour formats
+//! are converted according to status register B.
+use crate::devices::registry::{Driver, DriverError};
+use x86_64::instructions::port::Port;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+const STATUS_A_UPDATING: u8 = 1 << 7;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const HOUR_PM: u8 = 1 << 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtcTime {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+fn read_register(reg: u8) -> u8 {
+    unsafe {
+        // bit 7 keeps NMIs enabled
+        Port::<u8>::new(0x70).write(reg & 0x7F);
+        Port::<u8>::new(0x71).read()
+    }
+}
+
+fn read_raw() -> [u8; 3] {
+    while read_register(REG_STATUS_A) & STATUS_A_UPDATING != 0 {
+        core::hint::spin_loop();
+    }
+    [
+        read_register(REG_HOURS),
+        read_register(REG_MINUTES),
+        read_register(REG_SECONDS),
+    ]
+}
+
+fn from_bcd(v: u8) -> u8 {
+    (v >> 4) * 10 + (v & 0x0F)
+}
+
+/// Current time of day as kept by the RTC (usually UTC or local time,
+/// depending on how the machine's clock was set).
+pub fn read_time() -> RtcTime {
+    let mut raw = read_raw();
+    loop {
+        let again = read_raw();
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+
+    let status_b = read_register(REG_STATUS_B);
+    let [mut hour, mut minute, mut second] = raw;
+    let pm = hour & HOUR_PM != 0;
+    hour &= !HOUR_PM;
+    if status_b & STATUS_B_BINARY == 0 {
+        hour = from_bcd(hour);
+        minute = from_bcd(minute);
+        second = from_bcd(second);
+    }
+    if status_b & STATUS_B_24_HOUR == 0 {
+        // 12-hour clock: 12 AM is midnight, 12 PM is noon.
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    RtcTime {
+        hour,
+        minute,
+        second,
+    }
+}
+
+/// The `rtc` driver. Nothing to set up; init checks that the chip is there
+/// and keeps sane time, since `read_time` would wait forever on a floating
+/// bus that always reads "update in progress".
+pub struct Rtc;
+
+impl Driver for Rtc {
+    fn name(&self) -> &'static str {
+        "rtc"
+    }
+
+    fn init(&mut self) -> Result<(), DriverError> {
+        if read_register(REG_STATUS_A) == 0xFF {
+            return Err(DriverError::Absent);
+        }
+        let time = read_time();
+        if time.hour > 23 || time.minute > 59 || time.second > 59 {
+            return Err(DriverError::Failed("clock reads garbage"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+//! COM1 serial port
+//!
+//! The console writes to `crate::SERIAL` from the first line of boot, on
+//! whatever setup the firmware left. The `serial` driver checks that a UART
+//! is there, through its scratch register, and programs it properly.
+use crate::devices::registry::{Driver, DriverError};
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+const REG_SCRATCH: u16 = 7;
+
+pub struct Serial;
+
+impl Driver for Serial {
+    fn name(&self) -> &'static str {
+        "serial"
+    }
+
+    fn init(&mut self) -> Result<(), DriverError> {
+        let mut scratch = Port::<u8>::new(COM1 + REG_SCRATCH);
+        unsafe {
+            scratch.write(0x5A);
+            if scratch.read() != 0x5A {
+                return Err(DriverError::Absent);
+            }
+            (*core::ptr::addr_of_mut!(crate::SERIAL)).init();
+        }
+        Ok(())
+    }
+}
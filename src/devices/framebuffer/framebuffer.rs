@@ -1,59 +1,205 @@
 //! Framebuffer writer using embedded-graphics + tiled renderer
-use crate::ui_provider::color::Color;
+//!
+//! The back buffer (`nodes`) is tile-major: every 32x32 tile is one
+//! contiguous block of 1024 pixels, its rows back to back, and the tiles
+//! follow each other left to right, top to bottom. Edge tiles are padded to
+//! full size. Only `idx` knows the layout; callers use the accessors, and
+//! `snapshot`, `restore` and `blit` exchange plain row-major pixels.
+//!
+//! Drawing happens in logical coordinates. For a panel mounted sideways or
+//! upside down, `set_rotation` turns the logical screen relative to the
+//! physical one: `width` and `height` are swapped for 90 and 270 degrees,
+//! and only `render_frame` knows where a logical pixel lands on the panel.
+//! The rotation is read from `ROTATION_CONFIG` when the framebuffer is set
+//! up, before anything is laid out, so changing it takes a reboot.
+//!
+//! ## Budgeted flushes
+//!
+//! Copying a whole screen of changed tiles (a theme switch, say) takes long
+//! enough at high resolutions to hold up the next keystroke.
+//! `render_frame_within` stops once its budget is spent and leaves the
+//! remaining tiles dirty. Those tiles are the backlog, and the main loop
+//! keeps flushing it between input. Tiles marked dirty since the last
+//! flush go first, so a small input-driven change skips the queue. Every
+//! flush takes at least one tile row off the backlog, so it always ends.
+//! A tile is copied whole or not at all, so a partial flush never tears
+//! inside a tile.
+use crate::ui_provider::{
+    color::{blend_packed, Color},
+    shape::{to_i32_clamped, to_u32_clamped},
+};
 use alloc::vec;
 use alloc::vec::Vec;
 use bootloader_api::BootInfo;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use embedded_graphics::{
     mono_font::MonoTextStyle, pixelcolor::Rgb888, prelude::*, text::Text, Drawable,
 };
 use spin::Mutex;
 const TILE_W: usize = 32;
 const TILE_H: usize = 32;
+const TILE_PIXELS: usize = TILE_W * TILE_H;
+
+/// A screen coordinate as a signed one; past `i64::MAX` is off screen anyway.
+fn signed(v: usize) -> i64 {
+    i64::try_from(v).unwrap_or(i64::MAX)
+}
+
+/// Rows copied to the screen by the last `render_frame`, for `renderstat`.
+static ROWS_WRITTEN: AtomicUsize = AtomicUsize::new(0);
+/// Dirty tiles the last flush left for the next.
+static BACKLOG: AtomicUsize = AtomicUsize::new(0);
+/// Time `render_frame_within` gets in the main loop, in microseconds.
+static FLUSH_BUDGET_US: AtomicU64 = AtomicU64::new(3_000);
+
+/// Degrees, as text, read by `init_framebuffer`.
+pub const ROTATION_CONFIG: &str = "/config/rotation";
+
+/// How far the logical screen is turned clockwise on the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => 90,
+            Rotation::Deg180 => 180,
+            Rotation::Deg270 => 270,
+        }
+    }
+
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        [
+            Rotation::Deg0,
+            Rotation::Deg90,
+            Rotation::Deg180,
+            Rotation::Deg270,
+        ]
+        .into_iter()
+        .find(|r| r.degrees() == degrees)
+    }
+
+    /// Whether the logical width is the physical height.
+    fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::Deg90 | Rotation::Deg270)
+    }
+}
 
 pub struct FramebufferWriter {
     framebuffer: &'static mut [u8],
+    /// Logical size, after rotation.
     pub width: usize,
     pub height: usize,
+    /// Physical, in pixels per panel row.
     pub stride: usize,
     pub bytes_per_pixel: usize,
-    nodes: Vec<u32>, // packed RGB888 per pixel
+    rotation: Rotation,
+    phys_width: usize,
+    phys_height: usize,
+    nodes: Vec<u32>, // packed RGB888 per pixel, tile-major
     tiles_x: usize,
     tiles_y: usize,
     tile_dirty: Vec<AtomicBool>,
+    /// Left dirty by an earlier flush that ran out of time.
+    tile_backlog: Vec<bool>,
     tile_row_hash: Vec<u64>,
 }
 
 impl FramebufferWriter {
-    pub fn new(info: &'static mut BootInfo) -> Self {
-        let fb = info.framebuffer.as_mut().unwrap();
+    /// Returns `None` if the bootloader did not provide a framebuffer.
+    pub fn new(info: &'static mut BootInfo) -> Option<Self> {
+        // For the back buffer and the tile tables
+        crate::require_stage!(crate::kcore::kernel::init::Stage::Memory);
+        let fb = info.framebuffer.as_mut()?;
         let info = fb.info();
-        let width = info.width;
-        let height = info.height;
-        let stride = info.stride;
-        let bpp = info.bytes_per_pixel;
+        Some(Self::from_raw(
+            fb.buffer_mut(),
+            info.width,
+            info.height,
+            info.stride,
+            info.bytes_per_pixel,
+        ))
+    }
 
+    /// Wraps a BGR(A) pixel buffer of `stride * height` pixels.
+    pub fn from_raw(
+        framebuffer: &'static mut [u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+        bytes_per_pixel: usize,
+    ) -> Self {
         let tiles_x = (width + TILE_W - 1) / TILE_W;
         let tiles_y = (height + TILE_H - 1) / TILE_H;
         let tile_count = tiles_x * tiles_y;
 
         Self {
-            framebuffer: fb.buffer_mut(),
+            framebuffer,
             width,
             height,
             stride,
-            bytes_per_pixel: bpp,
-            nodes: vec![0u32; width * height],
+            bytes_per_pixel,
+            rotation: Rotation::Deg0,
+            phys_width: width,
+            phys_height: height,
+            nodes: vec![0u32; tile_count * TILE_PIXELS],
             tiles_x,
             tiles_y,
             tile_dirty: (0..tile_count).map(|_| AtomicBool::new(true)).collect(),
+            tile_backlog: vec![false; tile_count],
             tile_row_hash: vec![0u64; tile_count * TILE_H],
         }
     }
 
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Turns the logical screen by `rotation` and resizes the back buffer
+    /// to match. What was drawn is lost; the whole panel is written again
+    /// by the next `render_frame`.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        if rotation == self.rotation {
+            return;
+        }
+        let (width, height) = if rotation.swaps_axes() {
+            (self.phys_height, self.phys_width)
+        } else {
+            (self.phys_width, self.phys_height)
+        };
+        self.tiles_x = width.div_ceil(TILE_W);
+        self.tiles_y = height.div_ceil(TILE_H);
+        let tile_count = self.tiles_x * self.tiles_y;
+        self.width = width;
+        self.height = height;
+        self.rotation = rotation;
+        self.nodes = vec![0u32; tile_count * TILE_PIXELS];
+        self.tile_dirty = (0..tile_count).map(|_| AtomicBool::new(true)).collect();
+        self.tile_backlog = vec![false; tile_count];
+        self.tile_row_hash = vec![0u64; tile_count * TILE_H];
+    }
+
+    /// Byte offset in the framebuffer of the logical pixel `(x, y)`.
+    #[inline]
+    fn phys_offset(&self, x: usize, y: usize) -> usize {
+        let (px, py) = match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (self.phys_width - 1 - y, x),
+            Rotation::Deg180 => (self.phys_width - 1 - x, self.phys_height - 1 - y),
+            Rotation::Deg270 => (y, self.phys_height - 1 - x),
+        };
+        (py * self.stride + px) * self.bytes_per_pixel
+    }
+
     #[inline]
     fn idx(&self, x: usize, y: usize) -> usize {
-        y * self.width + x
+        self.tile_index_of(x, y) * TILE_PIXELS + (y % TILE_H) * TILE_W + x % TILE_W
     }
 
     #[inline]
@@ -106,76 +252,308 @@ impl FramebufferWriter {
         }
     }
 
+    /// Fills `[x0, x1) x [y0, y1)`. Corners out of order draw nothing.
     pub fn draw_rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Color) {
-        let x1 = x1.min(self.width);
-        let y1 = y1.min(self.height);
-        if x0 >= x1 || y0 >= y1 {
-            return;
+        let (w, h) = (x1.saturating_sub(x0), y1.saturating_sub(y0));
+        if let Some(clip) = self.clip_rect(signed(x0), signed(y0), w as u64, h as u64) {
+            self.fill_clipped(clip, color);
         }
+    }
+
+    /// `(x0, y0, x1, y1)`, ends exclusive, of the part of the `w * h` rect
+    /// at `(x, y)` that is on screen, or `None` if none of it is. Signed,
+    /// so a rect hanging off the top or left edge keeps its visible part
+    /// rather than shifting; the sums are done wide, so nothing overflows.
+    pub fn clip_rect(
+        &self,
+        x: i64,
+        y: i64,
+        w: u64,
+        h: u64,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let span = |start: i64, len: u64, limit: usize| {
+            let lo = (start as i128).max(0);
+            let hi = (start as i128 + len as i128).min(limit as i128);
+            (lo < hi).then_some((lo as usize, hi as usize))
+        };
+        let (x0, x1) = span(x, w, self.width)?;
+        let (y0, y1) = span(y, h, self.height)?;
+        Some((x0, y0, x1, y1))
+    }
+
+    /// Fills a rect from `clip_rect`.
+    fn fill_clipped(&mut self, (x0, y0, x1, y1): (usize, usize, usize, usize), color: Color) {
         let val = Self::pack_rgb888(color);
         let tx0 = x0 / TILE_W;
         let ty0 = y0 / TILE_H;
         let tx1 = (x1 + TILE_W - 1) / TILE_W;
         let ty1 = (y1 + TILE_H - 1) / TILE_H;
-        for y in y0..y1 {
-            let base = y * self.width;
-            for x in x0..x1 {
-                self.nodes[base + x] = val;
-            }
-        }
+        // One tile at a time, so each fill stays inside one block.
         for ty in ty0..ty1 {
+            let ry0 = y0.max(ty * TILE_H);
+            let ry1 = y1.min((ty + 1) * TILE_H);
             for tx in tx0..tx1 {
+                let rx0 = x0.max(tx * TILE_W);
+                let rx1 = x1.min((tx + 1) * TILE_W);
+                for y in ry0..ry1 {
+                    let start = self.idx(rx0, y);
+                    self.nodes[start..start + (rx1 - rx0)].fill(val);
+                }
                 let t = ty * self.tiles_x + tx;
                 self.tile_dirty[t].store(true, Ordering::Relaxed);
             }
         }
     }
 
+    /// Row-major copy of the back buffer, packed RGB888.
+    pub fn snapshot(&self) -> Vec<u32> {
+        let mut out = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            for tx in 0..self.tiles_x {
+                let start = self.idx(tx * TILE_W, y);
+                let len = TILE_W.min(self.width - tx * TILE_W);
+                out.extend_from_slice(&self.nodes[start..start + len]);
+            }
+        }
+        out
+    }
+
+    /// Row-major copy of the `w * h` pixels at `(x, y)`. Pixels off the
+    /// screen read as black.
+    pub fn snapshot_rect(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<u32> {
+        let mut out = vec![0u32; w * h];
+        let x1 = x.saturating_add(w).min(self.width);
+        let y1 = y.saturating_add(h).min(self.height);
+        for py in y..y1 {
+            let dst_row = &mut out[(py - y) * w..];
+            let mut px = x;
+            while px < x1 {
+                let span = (TILE_W - px % TILE_W).min(x1 - px);
+                let start = self.idx(px, py);
+                dst_row[px - x..px - x + span].copy_from_slice(&self.nodes[start..start + span]);
+                px += span;
+            }
+        }
+        out
+    }
+
+    /// Puts back a `snapshot` taken at the current size.
+    pub fn restore(&mut self, pixels: &[u32]) {
+        self.blit(0, 0, self.width, self.height, pixels);
+    }
+
+    /// Copies `w * h` row-major packed RGB888 pixels to `(x, y)`, clipped to
+    /// the screen. Only tiles whose contents change are marked dirty.
+    /// A `pixels` shorter than `w * h` draws nothing.
+    pub fn blit(&mut self, x: usize, y: usize, w: usize, h: usize, pixels: &[u32]) {
+        if w.checked_mul(h).is_none_or(|n| pixels.len() < n) {
+            return;
+        }
+        let Some((_, _, x1, y1)) = self.clip_rect(signed(x), signed(y), w as u64, h as u64) else {
+            return;
+        };
+        for py in y..y1 {
+            let src_row = &pixels[(py - y) * w..];
+            let mut px = x;
+            while px < x1 {
+                let span = (TILE_W - px % TILE_W).min(x1 - px);
+                let src = &src_row[px - x..px - x + span];
+                let start = self.idx(px, py);
+                let dst = &mut self.nodes[start..start + span];
+                if dst != src {
+                    dst.copy_from_slice(src);
+                    let t = self.tile_index_of(px, py);
+                    self.tile_dirty[t].store(true, Ordering::Relaxed);
+                }
+                px += span;
+            }
+        }
+    }
+
+    /// Mixes what is at the `w * h` pixels at `(x, y)` over `under`
+    /// (row-major packed RGB888) at `alpha`/255, clipped to the screen.
+    /// Only tiles whose contents change are marked dirty. An `under`
+    /// shorter than `w * h` blends nothing.
+    pub fn blend_over(&mut self, x: usize, y: usize, w: usize, h: usize, under: &[u32], alpha: u8) {
+        if w.checked_mul(h).is_none_or(|n| under.len() < n) {
+            return;
+        }
+        let Some((_, _, x1, y1)) = self.clip_rect(signed(x), signed(y), w as u64, h as u64) else {
+            return;
+        };
+        for py in y..y1 {
+            let under_row = &under[(py - y) * w..];
+            let mut changed = false;
+            for px in x..x1 {
+                let idx = self.idx(px, py);
+                let mixed = blend_packed(under_row[px - x], self.nodes[idx], alpha);
+                if self.nodes[idx] != mixed {
+                    self.nodes[idx] = mixed;
+                    changed = true;
+                }
+                if changed && (px + 1 == x1 || (px + 1) % TILE_W == 0) {
+                    let t = self.tile_index_of(px, py);
+                    self.tile_dirty[t].store(true, Ordering::Relaxed);
+                    changed = false;
+                }
+            }
+        }
+    }
+
+    /// Mixes `color` at `alpha`/255 over the pixel at `(x, y)`.
+    pub fn blend_pixel(&mut self, x: usize, y: usize, color: Color, alpha: u8) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let idx = self.idx(x, y);
+        let mixed = blend_packed(self.nodes[idx], Self::pack_rgb888(color), alpha);
+        if self.nodes[idx] != mixed {
+            self.nodes[idx] = mixed;
+            let t = self.tile_index_of(x, y);
+            self.tile_dirty[t].store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Mixes `color` at `alpha`/255 over the `w` pixels of row `y` from
+    /// `x`, clipped to the screen.
+    pub fn blend_span(&mut self, x: usize, y: usize, w: usize, color: Color, alpha: u8) {
+        let Some((x0, _, x1, _)) = self.clip_rect(signed(x), signed(y), w as u64, 1) else {
+            return;
+        };
+        let over = Self::pack_rgb888(color);
+        for px in x0..x1 {
+            let idx = self.idx(px, y);
+            self.nodes[idx] = blend_packed(self.nodes[idx], over, alpha);
+            if px + 1 == x1 || (px + 1) % TILE_W == 0 {
+                let t = self.tile_index_of(px, y);
+                self.tile_dirty[t].store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Top-left corners of the tiles the next `render_frame` will look at.
+    #[cfg(test)]
+    pub fn dirty_tiles(&self) -> Vec<(usize, usize)> {
+        (0..self.tiles_x * self.tiles_y)
+            .filter(|&t| self.tile_dirty[t].load(Ordering::Relaxed))
+            .map(|t| (t % self.tiles_x * TILE_W, t / self.tiles_x * TILE_H))
+            .collect()
+    }
+
+    /// Marks every tile dirty and forgets the row hashes, so the next
+    /// `render_frame` writes the whole screen.
+    pub fn invalidate(&mut self) {
+        for dirty in &self.tile_dirty {
+            dirty.store(true, Ordering::Relaxed);
+        }
+        self.tile_row_hash.fill(0);
+    }
+
+    /// Copies every dirty tile to the screen.
     pub fn render_frame(&mut self) {
-        let fb_row_bytes = self.stride * self.bytes_per_pixel;
+        self.flush_tiles(|| false);
+    }
+
+    /// `render_frame`, stopping once about `budget_us` microseconds have
+    /// gone. Returns the tiles left dirty for the next call. A budget of 0,
+    /// or no calibrated TSC, copies everything.
+    pub fn render_frame_within(&mut self, budget_us: u64) -> usize {
+        use crate::stats::latency::{rdtsc, tsc_per_us};
+
+        let per_us = tsc_per_us();
+        if budget_us == 0 || per_us == 0 {
+            return self.flush_tiles(|| false);
+        }
+        let deadline = rdtsc().saturating_add(budget_us.saturating_mul(per_us));
+        self.flush_tiles(|| rdtsc() >= deadline)
+    }
+
+    /// Tiles marked dirty since the last call first, then the backlog,
+    /// asking `out_of_time` after each tile. Past the deadline only the
+    /// first tile row of the backlog is still owed. Returns the tiles left.
+    fn flush_tiles(&mut self, mut out_of_time: impl FnMut() -> bool) -> usize {
+        #[cfg(debug_assertions)]
+        if crate::kcore::panic::hit(crate::kcore::panic::Site::RenderFrame) {
+            panic!("injected panic in render_frame");
+        }
         let tiles = self.tiles_x * self.tiles_y;
-        for tile_idx in 0..tiles {
-            if !self.tile_dirty[tile_idx].swap(false, Ordering::Relaxed) {
-                continue;
-            }
-            let tx = tile_idx % self.tiles_x;
-            let ty = tile_idx / self.tiles_x;
-            let sx = tx * TILE_W;
-            let sy = ty * TILE_H;
-            let ex = (sx + TILE_W).min(self.width);
-            let ey = (sy + TILE_H).min(self.height);
-
-            for y in sy..ey {
-                let row_in_tile = y - sy;
-                // rolling hash
-                let base = y * self.width + sx;
-                let mut h: u64 = 1469598103934665603; // FNV offset
-                for v in &self.nodes[base..base + (ex - sx)] {
-                    h ^= *v as u64;
-                    h = h.wrapping_mul(1099511628211);
+        let mut rows_written = 0;
+        let mut late = false;
+        for backlog in [false, true] {
+            let mut flushed = 0;
+            for tile_idx in 0..tiles {
+                if self.tile_backlog[tile_idx] != backlog
+                    || !self.tile_dirty[tile_idx].load(Ordering::Relaxed)
+                {
+                    continue;
                 }
-                let slot = self.tile_row_slot(tile_idx, row_in_tile);
-                if self.tile_row_hash[slot] == h {
-                    continue; // row unchanged
+                if late && !(backlog && flushed < self.tiles_x) {
+                    continue;
                 }
-                self.tile_row_hash[slot] = h;
-
-                let fb_row_off = y * fb_row_bytes;
-                let mut off = fb_row_off + sx * self.bytes_per_pixel;
-                for v in &self.nodes[base..base + (ex - sx)] {
-                    let r = ((v >> 16) & 0xFF) as u8;
-                    let g = ((v >> 8) & 0xFF) as u8;
-                    let b = (v & 0xFF) as u8;
-                    self.framebuffer[off] = b;
-                    self.framebuffer[off + 1] = g;
-                    self.framebuffer[off + 2] = r;
-                    if self.bytes_per_pixel == 4 {
-                        self.framebuffer[off + 3] = 255;
-                    }
-                    off += self.bytes_per_pixel;
+                rows_written += self.flush_tile(tile_idx);
+                flushed += 1;
+                late = late || out_of_time();
+            }
+        }
+
+        let mut left = 0;
+        for (held, dirty) in self.tile_backlog.iter_mut().zip(&self.tile_dirty) {
+            *held = dirty.load(Ordering::Relaxed);
+            left += usize::from(*held);
+        }
+        ROWS_WRITTEN.store(rows_written, Ordering::Relaxed);
+        BACKLOG.store(left, Ordering::Relaxed);
+        left
+    }
+
+    /// Copies the changed rows of one tile and marks it clean. Returns the
+    /// rows written.
+    fn flush_tile(&mut self, tile_idx: usize) -> usize {
+        self.tile_dirty[tile_idx].store(false, Ordering::Relaxed);
+        let fb_row_bytes = self.stride * self.bytes_per_pixel;
+        let mut rows_written = 0;
+
+        let tx = tile_idx % self.tiles_x;
+        let ty = tile_idx / self.tiles_x;
+        let sx = tx * TILE_W;
+        let sy = ty * TILE_H;
+        let ex = (sx + TILE_W).min(self.width);
+        let ey = (sy + TILE_H).min(self.height);
+        let tile = &self.nodes[tile_idx * TILE_PIXELS..(tile_idx + 1) * TILE_PIXELS];
+
+        for y in sy..ey {
+            let row_in_tile = y - sy;
+            let row = &tile[row_in_tile * TILE_W..row_in_tile * TILE_W + (ex - sx)];
+            // rolling hash
+            let mut h: u64 = 1469598103934665603; // FNV offset
+            for v in row {
+                h ^= *v as u64;
+                h = h.wrapping_mul(1099511628211);
+            }
+            let slot = self.tile_row_slot(tile_idx, row_in_tile);
+            if self.tile_row_hash[slot] == h {
+                continue; // row unchanged
+            }
+            self.tile_row_hash[slot] = h;
+            rows_written += 1;
+
+            if self.rotation != Rotation::Deg0 {
+                // Neighbours on a logical row are not neighbours on the
+                // panel, so each pixel finds its own place.
+                for (i, &v) in row.iter().enumerate() {
+                    let off = self.phys_offset(sx + i, y);
+                    write_pixel(self.framebuffer, off, v, self.bytes_per_pixel);
                 }
+                continue;
+            }
+            let fb_row_off = y * fb_row_bytes;
+            let mut off = fb_row_off + sx * self.bytes_per_pixel;
+            for &v in row {
+                write_pixel(self.framebuffer, off, v, self.bytes_per_pixel);
+                off += self.bytes_per_pixel;
             }
         }
+        rows_written
     }
 
     pub fn clear(&mut self, color: Color) {
@@ -183,14 +561,15 @@ impl FramebufferWriter {
     }
 
     pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
-        if width == 0 || height == 0 {
-            return;
+        self.fill_rect_signed(signed(x), signed(y), width as u64, height as u64, color);
+    }
+
+    /// `fill_rect` for callers whose rects can start above or left of the
+    /// screen (VM programs, say).
+    pub fn fill_rect_signed(&mut self, x: i64, y: i64, width: u64, height: u64, color: Color) {
+        if let Some(clip) = self.clip_rect(x, y, width, height) {
+            self.fill_clipped(clip, color);
         }
-        let x0 = x.max(0) as usize;
-        let y0 = y.max(0) as usize;
-        let x1 = x0 + width;
-        let y1 = y0 + height;
-        self.draw_rect(x0, y0, x1, y1, color);
     }
 
     pub fn draw_char(&mut self, ch: char, x: i32, y: i32, style: &MonoTextStyle<Rgb888>) {
@@ -200,12 +579,23 @@ impl FramebufferWriter {
     }
 
     pub fn draw_text(&mut self, text: &str, x: usize, y: usize, style: &MonoTextStyle<Rgb888>) {
-        Text::new(text, Point::new(x as i32, y as i32), *style)
+        Text::new(text, Point::new(to_i32_clamped(x), to_i32_clamped(y)), *style)
             .draw(self)
             .ok();
     }
 }
 
+/// Stores packed RGB888 `v` as BGR(A) at `off`.
+#[inline]
+fn write_pixel(framebuffer: &mut [u8], off: usize, v: u32, bytes_per_pixel: usize) {
+    framebuffer[off] = (v & 0xFF) as u8;
+    framebuffer[off + 1] = ((v >> 8) & 0xFF) as u8;
+    framebuffer[off + 2] = ((v >> 16) & 0xFF) as u8;
+    if bytes_per_pixel == 4 {
+        framebuffer[off + 3] = 255;
+    }
+}
+
 impl DrawTarget for FramebufferWriter {
     type Color = Rgb888;
     type Error = core::convert::Infallible;
@@ -215,7 +605,7 @@ impl DrawTarget for FramebufferWriter {
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
         for Pixel(Point { x, y }, color) in pixels {
-            if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
                 continue;
             }
             let c = Color::new(color.r(), color.g(), color.b());
@@ -227,20 +617,494 @@ impl DrawTarget for FramebufferWriter {
 
 impl OriginDimensions for FramebufferWriter {
     fn size(&self) -> Size {
-        Size::new(self.width as u32, self.height as u32)
+        Size::new(to_u32_clamped(self.width), to_u32_clamped(self.height))
     }
 }
 
+/// Prefer `with_fb`/`with_fb_blocking` over locking this directly.
 pub static FRAMEBUFFER: Mutex<Option<FramebufferWriter>> = Mutex::new(None);
 
-pub fn init_framebuffer(info: &'static mut BootInfo) {
-    let fb = FramebufferWriter::new(info);
+/// Set once the panic screen has taken the framebuffer; from then on the
+/// display may show torn frames and the normal accessors refuse access.
+static DISPLAY_CORRUPT_OK: AtomicBool = AtomicBool::new(false);
+/// Set while a `with_fb*` closure runs. A panic inside one leaves it set.
+static FB_HELD: AtomicBool = AtomicBool::new(false);
+
+/// Attempts `with_fb` makes before giving up with `FbError::Busy`.
+const TRY_LOCK_SPINS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbError {
+    /// Someone else holds the lock (or the panic screen owns the display).
+    Busy,
+    /// Not initialized yet, or headless.
+    NotInitialized,
+}
+
+/// Tile rows (up to 32 pixels each) the last frame copied to the screen.
+pub fn rows_written() -> usize {
+    ROWS_WRITTEN.load(Ordering::Relaxed)
+}
+
+/// Dirty tiles the last flush ran out of time for.
+pub fn backlog() -> usize {
+    BACKLOG.load(Ordering::Relaxed)
+}
+
+pub fn flush_budget_us() -> u64 {
+    FLUSH_BUDGET_US.load(Ordering::Relaxed)
+}
+
+/// 0 lets every frame copy everything.
+pub fn set_flush_budget_us(us: u64) {
+    FLUSH_BUDGET_US.store(us, Ordering::Relaxed);
+}
+
+/// Non-blocking access for toasts, watchdogs and other code that may run
+/// while the main loop is mid-frame. Spins on `try_lock` a bounded number
+/// of times, so it never deadlocks against the current holder.
+pub fn with_fb<T>(f: impl FnOnce(&mut FramebufferWriter) -> T) -> Result<T, FbError> {
+    if DISPLAY_CORRUPT_OK.load(Ordering::Acquire) {
+        return Err(FbError::Busy);
+    }
+    for _ in 0..TRY_LOCK_SPINS {
+        if let Some(mut guard) = FRAMEBUFFER.try_lock() {
+            return guard.as_mut().map(held(f)).ok_or(FbError::NotInitialized);
+        }
+        core::hint::spin_loop();
+    }
+    Err(FbError::Busy)
+}
+
+/// `f`, with `FB_HELD` set while it runs.
+fn held<T>(f: impl FnOnce(&mut FramebufferWriter) -> T) -> impl FnOnce(&mut FramebufferWriter) -> T {
+    |fb| {
+        let was = FB_HELD.swap(true, Ordering::Acquire);
+        let result = f(fb);
+        FB_HELD.store(was, Ordering::Release);
+        result
+    }
+}
+
+/// Whether a `with_fb*` closure is running, or was when it panicked.
+pub fn held_by_accessor() -> bool {
+    FB_HELD.load(Ordering::Acquire)
+}
+
+/// Blocking access for the main loop. Must not be called from an interrupt
+/// handler: the interrupted code may be holding the lock.
+pub fn with_fb_blocking<T>(f: impl FnOnce(&mut FramebufferWriter) -> T) -> Result<T, FbError> {
+    debug_assert!(
+        !crate::kcore::interrupts::pic::in_interrupt(),
+        "with_fb_blocking called from interrupt context"
+    );
+    if DISPLAY_CORRUPT_OK.load(Ordering::Acquire) {
+        return Err(FbError::Busy);
+    }
+    FRAMEBUFFER.lock().as_mut().map(held(f)).ok_or(FbError::NotInitialized)
+}
+
+/// Takes the framebuffer regardless of who holds the lock.
+///
+/// # Safety
+///
+/// Only for the panic screen. The previous holder may be in the middle of
+/// a draw, so the caller must never return to normal operation afterwards.
+/// All later `with_fb*` calls fail with `FbError::Busy`.
+pub unsafe fn steal_for_panic() -> Option<&'static mut FramebufferWriter> {
+    DISPLAY_CORRUPT_OK.store(true, Ordering::Release);
+    FRAMEBUFFER.force_unlock();
+    spin::MutexGuard::leak(FRAMEBUFFER.lock()).as_mut()
+}
+
+/// Times full-screen redraws for `bench render`: `render_frame` alone on
+/// unchanged contents with everything invalidated, and `clear` plus
+/// `render_frame` with every pixel changing. The screen is put back after.
+pub fn bench_render(rounds: u32) -> Result<alloc::string::String, FbError> {
+    use crate::stats::latency::{rdtsc, tsc_per_us};
+
+    let per_us = tsc_per_us().max(1);
+    let rounds = rounds.max(1);
+    with_fb_blocking(|fb| {
+        let saved = fb.snapshot();
+
+        crate::serial_progress("bench render: full redraws");
+        let start = rdtsc();
+        for _ in 0..rounds {
+            fb.invalidate();
+            fb.render_frame();
+        }
+        let redraw = (rdtsc() - start) / per_us / rounds as u64;
+
+        crate::serial_progress("bench render: fill and render");
+        let (mut fill, mut render) = (0, 0);
+        for i in 0..rounds {
+            let shade = if i % 2 == 0 { 0x20 } else { 0x40 };
+            let t0 = rdtsc();
+            fb.clear(Color::new(shade, shade, shade));
+            let t1 = rdtsc();
+            fb.render_frame();
+            fill += t1 - t0;
+            render += rdtsc() - t1;
+        }
+        let fill = fill / per_us / rounds as u64;
+        let render = render / per_us / rounds as u64;
+
+        fb.restore(&saved);
+        fb.render_frame();
+        crate::serial_progress("");
+        alloc::format!(
+            "{}x{}, {} rounds, tile-major back buffer\n\
+             redraw (unchanged): {} us/frame\n\
+             clear:              {} us/frame\n\
+             render (changed):   {} us/frame\n",
+            fb.width,
+            fb.height,
+            rounds,
+            redraw,
+            fill,
+            render
+        )
+    })
+}
+
+/// The rotation saved in ramfs, if any and valid.
+pub fn saved_rotation() -> Option<Rotation> {
+    let value = crate::fs::ramfs::read(ROTATION_CONFIG).ok()?;
+    let degrees = core::str::from_utf8(&value).ok()?.trim().parse().ok()?;
+    Rotation::from_degrees(degrees)
+}
+
+/// Leaves `FRAMEBUFFER` empty (headless mode) if there is no framebuffer.
+/// Runs after the saved state is restored, so `ROTATION_CONFIG` applies.
+pub fn init_framebuffer(info: &'static mut BootInfo) -> Result<(), &'static str> {
+    let mut fb = FramebufferWriter::new(info).ok_or("no framebuffer from bootloader")?;
+    if let Some(rotation) = saved_rotation() {
+        fb.set_rotation(rotation);
+        crate::log_info!(
+            "framebuffer: rotated {} degrees, {}x{} logical",
+            rotation.degrees(),
+            fb.width,
+            fb.height
+        );
+    }
     *FRAMEBUFFER.lock() = Some(fb);
-    {
-        let mut guard = FRAMEBUFFER.lock();
-        if let Some(fb) = guard.as_mut() {
+    with_fb_blocking(|fb| {
+        fb.clear(Color::BLACK);
+        fb.render_frame();
+    })
+    .map_err(|_| "framebuffer unavailable after init")
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mono_font::{ascii::FONT_6X10, MonoTextStyle};
+
+    /// The row-major layout the back buffer used to have, as the reference.
+    struct RowMajor {
+        width: usize,
+        height: usize,
+        pixels: Vec<u32>,
+    }
+
+    impl RowMajor {
+        fn new(width: usize, height: usize) -> Self {
+            Self {
+                width,
+                height,
+                pixels: vec![0; width * height],
+            }
+        }
+
+        fn put_pixel(&mut self, x: usize, y: usize, color: Color) {
+            if x < self.width && y < self.height {
+                self.pixels[y * self.width + x] = FramebufferWriter::pack_rgb888(color);
+            }
+        }
+
+        fn draw_rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Color) {
+            for y in y0..y1.min(self.height) {
+                for x in x0..x1.min(self.width) {
+                    self.put_pixel(x, y, color);
+                }
+            }
+        }
+
+        fn blit(&mut self, x: usize, y: usize, w: usize, h: usize, pixels: &[u32]) {
+            for row in 0..h {
+                for col in 0..w {
+                    let (px, py) = (x + col, y + row);
+                    if px < self.width && py < self.height {
+                        self.pixels[py * self.width + px] = pixels[row * w + col];
+                    }
+                }
+            }
+        }
+    }
+
+    impl DrawTarget for RowMajor {
+        type Color = Rgb888;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(Point { x, y }, color) in pixels {
+                if x >= 0 && y >= 0 {
+                    let c = Color::new(color.r(), color.g(), color.b());
+                    self.put_pixel(x as usize, y as usize, c);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl OriginDimensions for RowMajor {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, self.height as u32)
+        }
+    }
+
+    fn writer(width: usize, height: usize, stride: usize, bpp: usize) -> FramebufferWriter {
+        let buffer: &'static mut [u8] = vec![0u8; stride * height * bpp].leak();
+        FramebufferWriter::from_raw(buffer, width, height, stride, bpp)
+    }
+
+    fn color(i: usize) -> Color {
+        Color::new((i * 37) as u8, (i * 91) as u8, (i * 13 + 7) as u8)
+    }
+
+    /// Draws the same sequence into both, crossing tile edges on purpose.
+    fn script(fb: &mut FramebufferWriter, reference: &mut RowMajor) {
+        let (w, h) = (reference.width, reference.height);
+        fb.clear(color(1));
+        reference.draw_rect(0, 0, w, h, color(1));
+
+        let rects = [
+            (0, 0, 1, 1),
+            (31, 31, 33, 33),
+            (5, 20, 70, 45),
+            (30, 0, 34, w),
+            (w - 3, h - 3, w + 10, h + 10),
+            (40, 40, 40, 50),
+            (0, 63, w, 65),
+        ];
+        for (i, &(x0, y0, x1, y1)) in rects.iter().enumerate() {
+            fb.draw_rect(x0, y0, x1, y1, color(i + 2));
+            reference.draw_rect(x0, y0, x1, y1, color(i + 2));
+        }
+
+        for i in 0..500 {
+            let (x, y) = ((i * 7919) % (w + 4), (i * 104_729) % (h + 4));
+            fb.put_pixel(x, y, color(i));
+            reference.put_pixel(x, y, color(i));
+        }
+
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb888::new(250, 240, 10));
+        fb.draw_text("Tiles 0123 | ~", 3, 30, &style);
+        Text::new("Tiles 0123 | ~", Point::new(3, 30), style)
+            .draw(reference)
+            .ok();
+
+        let block: Vec<u32> = (0..45 * 20).map(|i| i as u32 * 2654435761).collect();
+        fb.blit(25, 28, 45, 20, &block);
+        reference.blit(25, 28, 45, 20, &block);
+    }
+
+    fn check_layout(width: usize, height: usize, stride: usize, bpp: usize) {
+        let mut fb = writer(width, height, stride, bpp);
+        let mut reference = RowMajor::new(width, height);
+        script(&mut fb, &mut reference);
+
+        assert!(fb.snapshot() == reference.pixels);
+        for y in 0..height {
+            for x in 0..width {
+                let c = fb.get_pixel(x, y);
+                assert_eq!(
+                    FramebufferWriter::pack_rgb888(c),
+                    reference.pixels[y * width + x]
+                );
+            }
+        }
+
+        fb.render_frame();
+        for y in 0..height {
+            for x in 0..width {
+                let v = reference.pixels[y * width + x];
+                let off = (y * stride + x) * bpp;
+                let px = &fb.framebuffer[off..off + 3];
+                assert_eq!(px, [v as u8, (v >> 8) as u8, (v >> 16) as u8]);
+            }
+        }
+    }
+
+    #[test_case]
+    fn tile_major_matches_row_major_output() {
+        check_layout(100, 70, 104, 4);
+        check_layout(65, 33, 65, 3);
+        check_layout(64, 64, 64, 4);
+    }
+
+    #[test_case]
+    fn adversarial_rects_clip_exactly() {
+        let fb = writer(100, 70, 100, 4);
+        let (max, min) = (i64::MAX, i64::MIN);
+        let cases = [
+            ((0, 0, 100, 70), Some((0, 0, 100, 70))),
+            ((-10, -5, 20, 10), Some((0, 0, 10, 5))),
+            ((-10, 0, 10, 10), None),
+            ((10, 10, 0, 5), None),
+            ((10, 10, 5, 0), None),
+            ((100, 0, 1, 1), None),
+            ((99, 69, u64::MAX, u64::MAX), Some((99, 69, 100, 70))),
+            ((max, 0, u64::MAX, 1), None),
+            ((min, min, u64::MAX, u64::MAX), Some((0, 0, 100, 70))),
+            ((min, 0, 5, 5), None),
+        ];
+        for ((x, y, w, h), want) in cases {
+            assert_eq!(fb.clip_rect(x, y, w, h), want, "{:?}", (x, y, w, h));
+        }
+
+        let mut fb = writer(100, 70, 100, 4);
+        let mut reference = RowMajor::new(100, 70);
+        fb.fill_rect_signed(-10, -5, 20, 10, color(1));
+        reference.draw_rect(0, 0, 10, 5, color(1));
+        fb.fill_rect(usize::MAX - 1, 3, 10, 10, color(2));
+        fb.fill_rect(3, 3, usize::MAX, 2, color(3));
+        reference.draw_rect(3, 3, 100, 5, color(3));
+        fb.draw_rect(50, 50, 40, 60, color(4));
+        fb.blit(0, 0, 10, 10, &[7; 99]);
+        fb.blit(0, 0, usize::MAX, 2, &[7; 4]);
+        assert!(fb.snapshot() == reference.pixels);
+    }
+
+    #[test_case]
+    fn random_rects_stay_on_screen() {
+        let (w, h) = (70, 40);
+        let mut fb = writer(w, h, w, 4);
+        let mut reference = RowMajor::new(w, h);
+        let mut rng = crate::util::rand::Xorshift64Star::new(1169);
+        let mut coord = |rng: &mut crate::util::rand::Xorshift64Star| match rng.next_range(0, 8) {
+            0 => i64::MIN + rng.next_range(0, 3) as i64,
+            1 => i64::MAX - rng.next_range(0, 3) as i64,
+            _ => rng.next_range(0, 200) as i64 - 80,
+        };
+
+        for i in 0..2000 {
+            let (x, y) = (coord(&mut rng), coord(&mut rng));
+            let (rw, rh) = match rng.next_range(0, 8) {
+                0 => (u64::MAX, rng.next_range(0, 50)),
+                1 => (rng.next_range(0, 50), u64::MAX - 1),
+                _ => (rng.next_range(0, 120), rng.next_range(0, 120)),
+            };
+            fb.fill_rect_signed(x, y, rw, rh, color(i));
+            for py in 0..h {
+                for px in 0..w {
+                    let inside = |p: usize, start: i64, len: u64| {
+                        (start as i128..start as i128 + len as i128).contains(&(p as i128))
+                    };
+                    if inside(px, x, rw) && inside(py, y, rh) {
+                        reference.put_pixel(px, py, color(i));
+                    }
+                }
+            }
+        }
+        assert!(fb.snapshot() == reference.pixels);
+    }
+
+    #[test_case]
+    fn restore_puts_back_a_snapshot() {
+        let mut fb = writer(90, 50, 90, 4);
+        let mut reference = RowMajor::new(90, 50);
+        script(&mut fb, &mut reference);
+        let saved = fb.snapshot();
+
+        fb.clear(Color::BLACK);
+        fb.render_frame();
+        fb.restore(&saved);
+        fb.render_frame();
+
+        assert!(fb.snapshot() == saved);
+        let off = (49 * 90 + 89) * 4;
+        let v = saved[49 * 90 + 89];
+        assert_eq!(fb.framebuffer[off + 2], (v >> 16) as u8);
+    }
+
+    #[test_case]
+    fn rotated_pixels_land_where_the_panel_puts_them() {
+        // Physical 40x24, 3 bytes a pixel, rows padded to 48 pixels
+        let (pw, ph, stride, bpp) = (40, 24, 48, 3);
+        let cases = [
+            (Rotation::Deg0, (40, 24), (5, 2)),
+            (Rotation::Deg90, (24, 40), (pw - 1 - 2, 5)),
+            (Rotation::Deg180, (40, 24), (pw - 1 - 5, ph - 1 - 2)),
+            (Rotation::Deg270, (24, 40), (2, ph - 1 - 5)),
+        ];
+        for (rotation, size, (px, py)) in cases {
+            let mut fb = writer(pw, ph, stride, bpp);
+            fb.set_rotation(rotation);
+            assert_eq!((fb.width, fb.height), size);
             fb.clear(Color::BLACK);
+            fb.put_pixel(5, 2, Color::new(0x11, 0x22, 0x33));
+            // The far corner, to catch an off-by-one at the edge
+            fb.put_pixel(fb.width - 1, fb.height - 1, Color::WHITE);
             fb.render_frame();
+
+            let off = (py * stride + px) * bpp;
+            assert_eq!(&fb.framebuffer[off..off + 3], [0x33, 0x22, 0x11]);
+            let lit = fb.framebuffer.chunks(bpp).filter(|p| p[0] == 0x33).count();
+            assert_eq!(lit, 1, "{:?}", rotation);
+            let white = fb.framebuffer.chunks(bpp).filter(|p| p[0] == 0xFF).count();
+            assert_eq!(white, 1, "{:?}", rotation);
+        }
+        assert_eq!(Rotation::from_degrees(270), Some(Rotation::Deg270));
+        assert_eq!(Rotation::from_degrees(45), None);
+    }
+
+    #[test_case]
+    fn unchanged_blit_leaves_tiles_clean() {
+        let mut fb = writer(70, 40, 70, 4);
+        fb.render_frame();
+        let saved = fb.snapshot();
+        fb.restore(&saved);
+        assert!(fb.tile_dirty.iter().all(|d| !d.load(Ordering::Relaxed)));
+    }
+
+    #[test_case]
+    fn budgeted_flushes_put_new_damage_first_and_always_progress() {
+        // 4 x 2 tiles
+        let mut fb = writer(128, 64, 128, 4);
+        fb.render_frame();
+        fb.clear(Color::WHITE);
+        // Out of time at once: one tile goes, the rest are the backlog
+        assert_eq!(fb.flush_tiles(|| true), 7);
+
+        // New damage in the backlog's last tile and in the flushed tile 0
+        fb.fill_rect(100, 40, 10, 20, Color::BLACK);
+        fb.put_pixel(5, 5, Color::BLACK);
+        let left = fb.flush_tiles(|| true);
+        // Tile 0 went first, then the tile row owed from the backlog
+        assert!(!fb.dirty_tiles().contains(&(0, 0)));
+        assert_eq!(left, 7 - 4);
+
+        let mut last = left;
+        while last > 0 {
+            let left = fb.flush_tiles(|| true);
+            assert!(left < last);
+            last = left;
         }
+        assert!(fb.dirty_tiles().is_empty());
+        let off = (45 * 128 + 105) * 4;
+        assert_eq!(&fb.framebuffer[off..off + 3], [0, 0, 0]);
+        assert_eq!(fb.framebuffer[0], 0xFF);
+
+        // Unbudgeted takes everything in one go
+        fb.clear(Color::BLACK);
+        assert_eq!(fb.render_frame_within(0), 0);
+        assert_eq!(backlog(), 0);
     }
 }
@@ -4,7 +4,9 @@
 //! - `drivers`: PS/2 keyboard and mouse drivers
 //! - `framebuffer`: Graphics output via linear framebuffer
 //! - `mouse_cursor`: Mouse cursor rendering and tracking
+//! - `registry`: the drivers, their init order and status
 
 pub mod drivers;
 pub mod framebuffer;
 pub mod mouse_cursor;
+pub mod registry;
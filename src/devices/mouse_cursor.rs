@@ -3,10 +3,14 @@
 //! Provides mouse cursor tracking and rendering.
 
 use crate::{
-    devices::framebuffer::framebuffer::FramebufferWriter, println, ui_provider::color::Color,
+    devices::framebuffer::framebuffer::FramebufferWriter,
+    println,
+    ui_provider::{
+        color::Color,
+        shape::{to_i32_clamped, Rect},
+    },
 };
-use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
-use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, Ordering};
 
 // =============================================================================
 // CURSOR STATE
@@ -17,7 +21,7 @@ static CURSOR_Y: AtomicI32 = AtomicI32::new(0);
 static CURSOR_VISIBLE: AtomicBool = AtomicBool::new(true);
 static CURSOR_NEEDS_REDRAW: AtomicBool = AtomicBool::new(true);
 
-static mut SAVED_BACKGROUND: Option<(i32, i32, Vec<Color>)> = None;
+static CURSOR_SHAPE: AtomicU8 = AtomicU8::new(CursorShape::Arrow as u8);
 
 // Screen bounds
 static SCREEN_WIDTH: AtomicI32 = AtomicI32::new(800);
@@ -53,16 +57,76 @@ const CURSOR_BITMAP: [[u8; CURSOR_WIDTH]; CURSOR_HEIGHT] = [
     [0,0,0,0,0,0,0,1,1,1,1,0],
 ];
 
+/// Horizontal double arrow, shown over the split divider.
+#[rustfmt::skip]
+const RESIZE_BITMAP: [[u8; CURSOR_WIDTH]; CURSOR_HEIGHT] = [
+    [0,0,0,1,0,0,0,0,1,0,0,0],
+    [0,0,1,1,0,0,0,0,1,1,0,0],
+    [0,1,2,1,1,1,1,1,1,2,1,0],
+    [1,2,2,2,2,2,2,2,2,2,2,1],
+    [0,1,2,1,1,1,1,1,1,2,1,0],
+    [0,0,1,1,0,0,0,0,1,1,0,0],
+    [0,0,0,1,0,0,0,0,1,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0],
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum CursorShape {
+    Arrow,
+    ResizeHorizontal,
+}
+
+impl CursorShape {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => CursorShape::ResizeHorizontal,
+            _ => CursorShape::Arrow,
+        }
+    }
+
+    fn bitmap(self) -> &'static [[u8; CURSOR_WIDTH]; CURSOR_HEIGHT] {
+        match self {
+            CursorShape::Arrow => &CURSOR_BITMAP,
+            CursorShape::ResizeHorizontal => &RESIZE_BITMAP,
+        }
+    }
+
+    /// Pixel of the bitmap that sits on the pointer position.
+    fn hotspot(self) -> (i32, i32) {
+        match self {
+            CursorShape::Arrow => (0, 0),
+            CursorShape::ResizeHorizontal => (6, 3),
+        }
+    }
+}
+
 // =============================================================================
 // PUBLIC API
 // =============================================================================
 
 pub fn init(screen_width: usize, screen_height: usize) {
-    SCREEN_WIDTH.store(screen_width as i32, Ordering::Relaxed);
-    SCREEN_HEIGHT.store(screen_height as i32, Ordering::Relaxed);
-
-    CURSOR_X.store(screen_width as i32 / 2, Ordering::Relaxed);
-    CURSOR_Y.store(screen_height as i32 / 2, Ordering::Relaxed);
+    // A zero-sized screen would make the clamp in `update_position` panic
+    let (width, height) = (
+        to_i32_clamped(screen_width).max(1),
+        to_i32_clamped(screen_height).max(1),
+    );
+    SCREEN_WIDTH.store(width, Ordering::Relaxed);
+    SCREEN_HEIGHT.store(height, Ordering::Relaxed);
+
+    CURSOR_X.store(width / 2, Ordering::Relaxed);
+    CURSOR_Y.store(height / 2, Ordering::Relaxed);
     CURSOR_NEEDS_REDRAW.store(true, Ordering::Relaxed);
 }
 
@@ -73,8 +137,8 @@ pub fn update_position(dx: i16, dy: i16) {
     let screen_w = SCREEN_WIDTH.load(Ordering::Relaxed);
     let screen_h = SCREEN_HEIGHT.load(Ordering::Relaxed);
 
-    let new_x = (old_x + dx as i32).clamp(0, screen_w - 1);
-    let new_y = (old_y - dy as i32).clamp(0, screen_h - 1);
+    let new_x = old_x.saturating_add(i32::from(dx)).clamp(0, screen_w - 1);
+    let new_y = old_y.saturating_sub(i32::from(dy)).clamp(0, screen_h - 1);
 
     if new_x != old_x || new_y != old_y {
         CURSOR_X.store(new_x, Ordering::Relaxed);
@@ -90,6 +154,13 @@ pub fn get_position() -> (i32, i32) {
     )
 }
 
+/// `get_position` in screen coordinates, the space app `Rect`s are in.
+/// The cursor is clamped to the screen, so neither is ever negative.
+pub fn screen_position() -> (usize, usize) {
+    let (x, y) = get_position();
+    (usize::try_from(x).unwrap_or(0), usize::try_from(y).unwrap_or(0))
+}
+
 pub fn set_visible(visible: bool) {
     let was_visible = CURSOR_VISIBLE.swap(visible, Ordering::Relaxed);
     if was_visible != visible {
@@ -106,96 +177,60 @@ pub fn mark_drawn() {
 }
 
 
-pub fn draw(fb: &mut FramebufferWriter) {
-    unsafe {
-        if let Some((old_x, old_y, ref pixels)) = SAVED_BACKGROUND {
-            let mut pixel_idx = 0;
-            for (row, bitmap_row) in CURSOR_BITMAP.iter().enumerate() {
-                let py = old_y + row as i32;
-                if py < 0 || py >= fb.height as i32 {
-                    continue;
-                }
-
-                for (col, &pixel_mask) in bitmap_row.iter().enumerate() {
-                    if pixel_mask == 0 {
-                        continue; // Transparent - skip
-                    }
-
-                    let px = old_x + col as i32;
-                    if px < 0 || px >= fb.width as i32 {
-                        continue;
-                    }
-
-                    if pixel_idx < pixels.len() {
-                        fb.put_pixel(px as usize, py as usize, pixels[pixel_idx]);
-                        pixel_idx += 1;
-                    }
-                }
-            }
-        }
-
-        if !CURSOR_VISIBLE.load(Ordering::Relaxed) {
-            SAVED_BACKGROUND = None;
-            return;
-        }
-
-        let cx = CURSOR_X.load(Ordering::Relaxed);
-        let cy = CURSOR_Y.load(Ordering::Relaxed);
-        let mut saved_pixels = Vec::new();
-
-        for (row, bitmap_row) in CURSOR_BITMAP.iter().enumerate() {
-            let py = cy + row as i32;
-            if py < 0 || py >= fb.height as i32 {
-                continue;
-            }
+pub fn shape() -> CursorShape {
+    CursorShape::from_u8(CURSOR_SHAPE.load(Ordering::Relaxed))
+}
 
-            for (col, &pixel) in bitmap_row.iter().enumerate() {
-                if pixel == 0 {
-                    continue; // Transparent
-                }
+pub fn set_shape(shape: CursorShape) {
+    if CURSOR_SHAPE.swap(shape as u8, Ordering::Relaxed) != shape as u8 {
+        CURSOR_NEEDS_REDRAW.store(true, Ordering::Relaxed);
+    }
+}
 
-                let px = cx + col as i32;
-                if px < 0 || px >= fb.width as i32 {
-                    continue;
-                }
+/// Draws the cursor if it is visible and returns the rect it covers. It
+/// is the top layer (`ui_provider::layers`): nothing is saved or put back
+/// here; the next frame paints the returned rect again from below.
+pub fn draw(fb: &mut FramebufferWriter) -> Option<Rect> {
+    if !CURSOR_VISIBLE.load(Ordering::Relaxed) {
+        return None;
+    }
 
-                // Save the pixel that was there before
-                let existing = fb.get_pixel(px as usize, py as usize);
-                saved_pixels.push(existing);
-            }
+    let shape = shape();
+    let (hx, hy) = shape.hotspot();
+    let cx = CURSOR_X.load(Ordering::Relaxed) - hx;
+    let cy = CURSOR_Y.load(Ordering::Relaxed) - hy;
+    let outline_color = Color::BLACK;
+    let fill_color = Color::WHITE;
+
+    for (row, bitmap_row) in shape.bitmap().iter().enumerate() {
+        let py = cy + row as i32;
+        if py < 0 || py >= to_i32_clamped(fb.height) {
+            continue;
         }
 
-        SAVED_BACKGROUND = Some((cx, cy, saved_pixels));
-
-        // Draw cursor at new position
-        let outline_color = Color::BLACK;
-        let fill_color = Color::WHITE;
+        for (col, &pixel) in bitmap_row.iter().enumerate() {
+            if pixel == 0 {
+                continue; // Transparent
+            }
 
-        for (row, bitmap_row) in CURSOR_BITMAP.iter().enumerate() {
-            let py = cy + row as i32;
-            if py < 0 || py >= fb.height as i32 {
+            let px = cx + col as i32;
+            if px < 0 || px >= to_i32_clamped(fb.width) {
                 continue;
             }
 
-            for (col, &pixel) in bitmap_row.iter().enumerate() {
-                if pixel == 0 {
-                    continue;
-                }
-
-                let px = cx + col as i32;
-                if px < 0 || px >= fb.width as i32 {
-                    continue;
-                }
-
-                let color = if pixel == 1 {
-                    outline_color
-                } else {
-                    fill_color
-                };
-                fb.put_pixel(px as usize, py as usize, color);
-            }
+            let color = if pixel == 1 {
+                outline_color
+            } else {
+                fill_color
+            };
+            fb.put_pixel(px as usize, py as usize, color);
         }
     }
+
+    let (x, y) = (cx.max(0) as usize, cy.max(0) as usize);
+    let right = (cx + CURSOR_WIDTH as i32).max(0) as usize;
+    let bottom = (cy + CURSOR_HEIGHT as i32).max(0) as usize;
+    Some(Rect::new(x, y, right - x, bottom - y))
 }
 
 pub fn dimensions() -> (usize, usize) {
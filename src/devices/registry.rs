@@ -0,0 +1,436 @@
+//! # Driver Registry
+//!
+//! One table for the devices the kernel drives. Boot registers each
+//! `Driver` from its stage and calls `init_pending`, which brings the new
+//! drivers up in dependency order: a driver starts once everything in its
+//! `depends_on` has been tried, and fails without being started if one of
+//! those is not up. A `DriverError::Transient` failure gets one more
+//! attempt straight away; anything else is recorded as it is.
+//!
+//! The recorded status is what `lsdev`, the boot splash and the top bar
+//! show. Once a driver is up its own `status` is asked instead, so a device
+//! that stops answering later (the mouse, after a failed reset) shows up
+//! without the registry being told. `dev retry` runs a driver's init again,
+//! `dev disable`/`dev enable` suspend and resume it.
+//!
+//! Init times are kept in TSC cycles and converted when listed, since the
+//! TSC is only calibrated once interrupts are on.
+
+use crate::kcore::kernel::status::{ComponentStatus, InitStatus};
+use crate::stats::latency;
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::fmt;
+use spin::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverError {
+    /// No such device on this machine.
+    Absent,
+    /// Worth trying again, e.g. a device that did not answer in time.
+    Transient(&'static str),
+    Failed(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverStatus {
+    /// Registered, init not run yet.
+    Pending,
+    Ok,
+    Failed(&'static str),
+    Absent,
+    Disabled,
+}
+
+impl fmt::Display for DriverStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverStatus::Pending => write!(f, "pending"),
+            DriverStatus::Ok => write!(f, "ok"),
+            DriverStatus::Failed(reason) => write!(f, "failed: {}", reason),
+            DriverStatus::Absent => write!(f, "absent"),
+            DriverStatus::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+impl From<DriverStatus> for InitStatus {
+    fn from(status: DriverStatus) -> Self {
+        match status {
+            DriverStatus::Pending => InitStatus::NotStarted,
+            DriverStatus::Ok => InitStatus::Completed,
+            DriverStatus::Failed(reason) => InitStatus::Failed(reason),
+            DriverStatus::Absent => InitStatus::Failed("no device"),
+            DriverStatus::Disabled => InitStatus::Failed("disabled"),
+        }
+    }
+}
+
+pub trait Driver: Send {
+    fn name(&self) -> &'static str;
+
+    /// Drivers that must be up before this one starts.
+    fn depends_on(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn init(&mut self) -> Result<(), DriverError>;
+
+    /// How the device is doing after a successful init.
+    fn status(&self) -> DriverStatus {
+        DriverStatus::Ok
+    }
+
+    fn suspend(&mut self) -> Result<(), DriverError> {
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), DriverError> {
+        Ok(())
+    }
+}
+
+struct Entry {
+    driver: Box<dyn Driver>,
+    status: DriverStatus,
+    /// TSC cycles the last init took, retry included.
+    cycles: u64,
+    attempts: u32,
+}
+
+impl Entry {
+    fn status(&self) -> DriverStatus {
+        match self.status {
+            DriverStatus::Ok => self.driver.status(),
+            status => status,
+        }
+    }
+
+    /// Runs init, once more after a transient failure.
+    fn init(&mut self) -> DriverStatus {
+        let start = latency::rdtsc();
+        let mut result = self.driver.init();
+        self.attempts += 1;
+        if let Err(DriverError::Transient(_)) = result {
+            result = self.driver.init();
+            self.attempts += 1;
+        }
+        self.cycles = latency::rdtsc() - start;
+        self.status = match result {
+            Ok(()) => DriverStatus::Ok,
+            Err(DriverError::Absent) => DriverStatus::Absent,
+            Err(DriverError::Transient(reason) | DriverError::Failed(reason)) => {
+                DriverStatus::Failed(reason)
+            }
+        };
+        self.status
+    }
+}
+
+/// A driver as `list` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DriverInfo {
+    pub name: &'static str,
+    pub status: DriverStatus,
+    pub cycles: u64,
+    pub attempts: u32,
+}
+
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds `driver`, pending. A second driver with the same name is
+    /// ignored.
+    pub fn register(&mut self, driver: Box<dyn Driver>) {
+        if self.find(driver.name()).is_none() {
+            self.entries.push(Entry {
+                driver,
+                status: DriverStatus::Pending,
+                cycles: 0,
+                attempts: 0,
+            });
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.driver.name() == name)
+    }
+
+    /// Whether every dependency of entry `idx` has been tried, and if so
+    /// whether they are all up.
+    fn dependencies(&self, idx: usize) -> Option<bool> {
+        let mut up = true;
+        for name in self.entries[idx].driver.depends_on() {
+            match self.find(name).map(|dep| self.entries[dep].status()) {
+                Some(DriverStatus::Pending) => return None,
+                Some(DriverStatus::Ok) => {}
+                _ => up = false,
+            }
+        }
+        Some(up)
+    }
+
+    /// Initializes every pending driver, each after what it depends on.
+    pub fn init_pending(&mut self) {
+        loop {
+            let pending = (0..self.entries.len())
+                .filter(|&idx| self.entries[idx].status == DriverStatus::Pending);
+            let next = pending
+                .clone()
+                .find_map(|idx| self.dependencies(idx).map(|up| (idx, up)));
+            match next {
+                Some((idx, true)) => {
+                    self.entries[idx].init();
+                }
+                Some((idx, false)) => {
+                    self.entries[idx].status = DriverStatus::Failed("a driver it needs is down");
+                }
+                None => {
+                    // Whatever is left waits on itself
+                    let stuck: Vec<usize> = pending.collect();
+                    for idx in stuck {
+                        self.entries[idx].status = DriverStatus::Failed("dependency cycle");
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn status(&self, name: &str) -> Option<DriverStatus> {
+        self.find(name).map(|idx| self.entries[idx].status())
+    }
+
+    /// Runs `name`'s init again unless it is up or disabled.
+    pub fn retry(&mut self, name: &str) -> Result<DriverStatus, &'static str> {
+        let idx = self.find(name).ok_or("no such driver")?;
+        match self.entries[idx].status() {
+            DriverStatus::Ok => Err("already up"),
+            DriverStatus::Disabled => Err("disabled; use dev enable"),
+            _ if self.dependencies(idx) != Some(true) => Err("a driver it needs is down"),
+            _ => Ok(self.entries[idx].init()),
+        }
+    }
+
+    /// Suspends `name` if it is up.
+    pub fn disable(&mut self, name: &str) -> Result<(), &'static str> {
+        let idx = self.find(name).ok_or("no such driver")?;
+        let entry = &mut self.entries[idx];
+        if entry.status() != DriverStatus::Ok {
+            return Err("not up");
+        }
+        match entry.driver.suspend() {
+            Ok(()) => {
+                entry.status = DriverStatus::Disabled;
+                Ok(())
+            }
+            Err(DriverError::Absent) => Err("no device"),
+            Err(DriverError::Transient(reason) | DriverError::Failed(reason)) => Err(reason),
+        }
+    }
+
+    /// Resumes `name` after `disable`.
+    pub fn enable(&mut self, name: &str) -> Result<(), &'static str> {
+        let idx = self.find(name).ok_or("no such driver")?;
+        let entry = &mut self.entries[idx];
+        if entry.status != DriverStatus::Disabled {
+            return Err("not disabled");
+        }
+        entry.status = match entry.driver.resume() {
+            Ok(()) => DriverStatus::Ok,
+            Err(DriverError::Absent) => DriverStatus::Absent,
+            Err(DriverError::Transient(reason) | DriverError::Failed(reason)) => {
+                DriverStatus::Failed(reason)
+            }
+        };
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<DriverInfo> {
+        self.entries
+            .iter()
+            .map(|entry| DriverInfo {
+                name: entry.driver.name(),
+                status: entry.status(),
+                cycles: entry.cycles,
+                attempts: entry.attempts,
+            })
+            .collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+
+pub fn register(driver: Box<dyn Driver>) {
+    REGISTRY.lock().register(driver);
+}
+
+pub fn init_pending() {
+    REGISTRY.lock().init_pending();
+}
+
+pub fn status(name: &str) -> Option<DriverStatus> {
+    REGISTRY.lock().status(name)
+}
+
+pub fn is_up(name: &str) -> bool {
+    status(name) == Some(DriverStatus::Ok)
+}
+
+pub fn retry(name: &str) -> Result<DriverStatus, &'static str> {
+    REGISTRY.lock().retry(name)
+}
+
+pub fn disable(name: &str) -> Result<(), &'static str> {
+    REGISTRY.lock().disable(name)
+}
+
+pub fn enable(name: &str) -> Result<(), &'static str> {
+    REGISTRY.lock().enable(name)
+}
+
+/// The drivers as rows for the boot splash.
+pub fn components() -> Vec<ComponentStatus> {
+    REGISTRY
+        .lock()
+        .list()
+        .into_iter()
+        .map(|info| ComponentStatus {
+            name: info.name,
+            status: info.status.into(),
+        })
+        .collect()
+}
+
+/// The `lsdev` table.
+pub fn report() -> String {
+    let per_us = latency::tsc_per_us().max(1);
+    let mut out = format!(
+        "{:<10} {:>8}  {:<6} {}\n",
+        "Driver", "Init", "Tries", "Status"
+    );
+    for info in REGISTRY.lock().list() {
+        out.push_str(&format!(
+            "{:<10} {:>5} us  {:<6} {}\n",
+            info.name,
+            info.cycles / per_us,
+            info.attempts,
+            info.status
+        ));
+    }
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static STARTED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+    struct Mock {
+        name: &'static str,
+        needs: &'static [&'static str],
+        /// Errors from successive inits; `Ok` once they run out.
+        failures: Vec<DriverError>,
+    }
+
+    impl Driver for Mock {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn depends_on(&self) -> &'static [&'static str] {
+            self.needs
+        }
+
+        fn init(&mut self) -> Result<(), DriverError> {
+            STARTED.lock().push(self.name);
+            if self.failures.is_empty() {
+                Ok(())
+            } else {
+                Err(self.failures.remove(0))
+            }
+        }
+    }
+
+    fn mock(
+        name: &'static str,
+        needs: &'static [&'static str],
+        failures: Vec<DriverError>,
+    ) -> Box<dyn Driver> {
+        Box::new(Mock {
+            name,
+            needs,
+            failures,
+        })
+    }
+
+    #[test_case]
+    fn drivers_start_in_dependency_order_and_retry_once() {
+        use DriverError::*;
+
+        STARTED.lock().clear();
+        let mut registry = Registry::new();
+        // Registered before what it needs
+        registry.register(mock("mouse", &["ps2"], alloc::vec![Transient("no ack")]));
+        registry.register(mock("ps2", &[], alloc::vec![]));
+        registry.register(mock("serial", &[], alloc::vec![Absent]));
+        registry.register(mock(
+            "rtc",
+            &[],
+            alloc::vec![Transient("busy"), Transient("busy")],
+        ));
+        registry.register(mock("clock", &["rtc"], alloc::vec![]));
+        registry.init_pending();
+
+        assert_eq!(
+            *STARTED.lock(),
+            ["ps2", "mouse", "mouse", "serial", "rtc", "rtc"]
+        );
+        assert_eq!(registry.status("mouse"), Some(DriverStatus::Ok));
+        assert_eq!(registry.status("serial"), Some(DriverStatus::Absent));
+        // Two transient failures are one too many, and take what needs it
+        assert_eq!(registry.status("rtc"), Some(DriverStatus::Failed("busy")));
+        assert_eq!(
+            registry.status("clock"),
+            Some(DriverStatus::Failed("a driver it needs is down"))
+        );
+        assert_eq!(registry.list()[0].attempts, 2);
+
+        // A retry at runtime gets another init, and one more on failure
+        STARTED.lock().clear();
+        assert_eq!(registry.retry("rtc"), Ok(DriverStatus::Ok));
+        assert_eq!(registry.retry("rtc"), Err("already up"));
+        assert_eq!(registry.retry("clock"), Ok(DriverStatus::Ok));
+        assert_eq!(*STARTED.lock(), ["rtc", "clock"]);
+
+        assert_eq!(registry.disable("clock"), Ok(()));
+        assert_eq!(registry.status("clock"), Some(DriverStatus::Disabled));
+        assert_eq!(registry.enable("clock"), Ok(()));
+        assert_eq!(registry.status("clock"), Some(DriverStatus::Ok));
+
+        let mut cyclic = Registry::new();
+        cyclic.register(mock("a", &["b"], alloc::vec![]));
+        cyclic.register(mock("b", &["a"], alloc::vec![]));
+        cyclic.init_pending();
+        assert_eq!(
+            cyclic.status("a"),
+            Some(DriverStatus::Failed("dependency cycle"))
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! # Last Panic
+//!
+//! Keeps the most recent panic message across a warm reboot, for machines
+//! with nothing on serial to read it from. The panic handler writes it
+//! into the last page of the persistence area (see `persist`), which the
+//! frame allocator never hands out; the next boot reports it once and
+//! leaves a copy at `/log/lastpanic.txt`.
+//!
+//! `record` runs in the panic handler, so it takes no locks and does not
+//! allocate: the message is formatted straight into the area and cut off
+//! at `MAX_TEXT` bytes. A panic before the area is found at boot is not
+//! recorded.
+//!
+//! ## Record
+//!
+//! | Field     | Size | Notes                               |
+//! |-----------|------|-------------------------------------|
+//! | magic     | 8    | `KPANIC01`                          |
+//! | uptime    | 8    | ms since boot when it panicked      |
+//! | length    | 2    | text bytes                          |
+//! | checksum  | 8    | FNV-1a of the text                  |
+//! | text      |      | message, then ` at file:line`       |
+
+use super::{persist, ramfs};
+use alloc::string::String;
+use core::fmt::Write;
+
+const MAGIC: [u8; 8] = *b"KPANIC01";
+const HEADER_LEN: usize = 8 + 8 + 2 + 8;
+pub const MAX_TEXT: usize = 1024;
+pub const SLOT_SIZE: usize = 4096;
+pub const PATH: &str = "/log/lastpanic.txt";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastPanic {
+    pub uptime_ms: u64,
+    pub text: String,
+}
+
+/// `fmt::Write` into a byte slice that drops whatever does not fit.
+struct Truncating<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for Truncating<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// Writes a record of `info` into `slot`.
+pub fn encode(slot: &mut [u8], uptime_ms: u64, info: &core::panic::PanicInfo) {
+    encode_args(
+        slot,
+        uptime_ms,
+        format_args!("{}", info.message()),
+        info.location(),
+    );
+}
+
+fn encode_args(
+    slot: &mut [u8],
+    uptime_ms: u64,
+    message: core::fmt::Arguments,
+    location: Option<&core::panic::Location>,
+) {
+    let (header, rest) = slot.split_at_mut(HEADER_LEN);
+    let text_cap = rest.len().min(MAX_TEXT);
+    let mut text = Truncating {
+        buf: &mut rest[..text_cap],
+        len: 0,
+    };
+    let _ = text.write_fmt(message);
+    if let Some(loc) = location {
+        let _ = write!(text, " at {}:{}", loc.file(), loc.line());
+    }
+    let len = text.len;
+    header[8..16].copy_from_slice(&uptime_ms.to_le_bytes());
+    header[16..18].copy_from_slice(&(len as u16).to_le_bytes());
+    header[18..26].copy_from_slice(&persist::fnv1a(&rest[..len]).to_le_bytes());
+    // The magic last, so a panic inside the write leaves no half record.
+    header[..8].copy_from_slice(&MAGIC);
+}
+
+pub fn decode(slot: &[u8]) -> Option<LastPanic> {
+    if slot.len() < HEADER_LEN || slot[..8] != MAGIC {
+        return None;
+    }
+    let uptime_ms = u64::from_le_bytes(slot[8..16].try_into().unwrap());
+    let len = u16::from_le_bytes([slot[16], slot[17]]) as usize;
+    let checksum = u64::from_le_bytes(slot[18..26].try_into().unwrap());
+    let text = slot.get(HEADER_LEN..HEADER_LEN + len.min(MAX_TEXT))?;
+    if persist::fnv1a(text) != checksum {
+        return None;
+    }
+    Some(LastPanic {
+        uptime_ms,
+        text: String::from_utf8_lossy(text).into_owned(),
+    })
+}
+
+/// Called from the panic handler; does nothing without an area.
+pub fn record(info: &core::panic::PanicInfo) {
+    if let Some(slot) = persist::panic_slot() {
+        let uptime_ms = crate::devices::drivers::hpet::monotonic_ms();
+        encode(slot, uptime_ms, info);
+    }
+}
+
+/// The previous session's panic, if it left one. The record is cleared,
+/// so each panic is reported by one boot only.
+pub fn take() -> Option<LastPanic> {
+    let slot = persist::panic_slot()?;
+    let last = decode(slot);
+    slot[..8].fill(0);
+    last
+}
+
+/// At boot, after `persist::restore`: logs the previous session's panic
+/// and saves it to `PATH`.
+pub fn report() -> Option<LastPanic> {
+    let last = take()?;
+    crate::println!(
+        "lastpanic: previous session panicked after {} ms: {}",
+        last.uptime_ms,
+        last.text
+    );
+    let file = alloc::format!("uptime {} ms\n{}\n", last.uptime_ms, last.text);
+    let _ = ramfs::write(PATH, file.as_bytes());
+    Some(last)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn round_trip_and_truncation() {
+        let mut slot = [0u8; SLOT_SIZE];
+        assert_eq!(decode(&slot), None);
+
+        let here = core::panic::Location::caller();
+        encode_args(&mut slot, 1234, format_args!("boom {}", 7), Some(here));
+        let last = decode(&slot).unwrap();
+        assert_eq!(last.uptime_ms, 1234);
+        assert!(last.text.starts_with("boom 7 at "));
+        assert!(last.text.ends_with(&alloc::format!(":{}", here.line())));
+
+        let long = "x".repeat(3 * MAX_TEXT);
+        encode_args(&mut slot, 5, format_args!("{}", long), None);
+        assert_eq!(decode(&slot).unwrap().text.len(), MAX_TEXT);
+
+        slot[HEADER_LEN] ^= 1;
+        assert_eq!(decode(&slot), None, "checksum");
+    }
+}
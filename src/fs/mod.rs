@@ -0,0 +1,9 @@
+//! # File System
+//!
+//! Only an in-memory file system for now, see `ramfs`. `persist` keeps
+//! a few of its files across a warm reboot, and `lastpanic` the last
+//! panic message.
+
+pub mod lastpanic;
+pub mod persist;
+pub mod ramfs;
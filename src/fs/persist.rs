@@ -0,0 +1,258 @@
+//! # Persistence Area
+//!
+//! Best-effort survival of a few ramfs subtrees across a warm reboot, with
+//! no disk: `save` writes `/config` and the tail of `/log` into a small
+//! RAM area and `restore` reads them back on the next boot. RAM keeps its
+//! contents over a warm reboot in QEMU and on most machines, but nothing
+//! promises that, so the image carries a magic, a version and a checksum,
+//! and anything that does not check out is ignored.
+//!
+//! The area is the last `AREA_SIZE` bytes of the highest usable region
+//! the bootloader reports outside the kernel's frame window. The kernel
+//! never allocates from there, and the bootloader hands out low memory
+//! first, so it is the likeliest place to be left alone. Its last
+//! `lastpanic::SLOT_SIZE` bytes hold the last panic record rather than
+//! the image.
+//!
+//! ## Image
+//!
+//! | Field    | Size | Notes                                   |
+//! |----------|------|-----------------------------------------|
+//! | magic    | 8    | `KPERSIST`                              |
+//! | version  | 1    | `VERSION`                               |
+//! | count    | 2    | entries                                 |
+//! | length   | 4    | payload bytes                           |
+//! | checksum | 8    | FNV-1a of the payload                   |
+//! | payload  |      | per entry: u16 path length, path, u32 data length, data |
+//!
+//! Integers are little-endian.
+
+use super::{lastpanic, ramfs};
+use alloc::{string::String, vec::Vec};
+use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
+use spin::Once;
+use x86_64::{PhysAddr, VirtAddr};
+
+const MAGIC: [u8; 8] = *b"KPERSIST";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8 + 1 + 2 + 4 + 8;
+pub const AREA_SIZE: usize = 64 * 1024;
+/// Room for the image; the rest of the area is the panic slot.
+const IMAGE_SIZE: usize = AREA_SIZE - lastpanic::SLOT_SIZE;
+
+/// Subtrees saved whole.
+const SAVED_DIRS: [&str; 1] = ["/config/"];
+/// Subtree of which each file keeps only its last `LOG_TAIL` bytes.
+const LOG_DIR: &str = "/log/";
+const LOG_TAIL: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistError {
+    /// No area was found at boot.
+    Unavailable,
+    /// No image in the area (first boot, cold boot, or overwritten).
+    NoImage,
+    BadVersion(u8),
+    BadChecksum,
+    Truncated,
+    TooLarge(usize),
+}
+
+impl core::fmt::Display for PersistError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Unavailable => write!(f, "persistence unavailable (no area found at boot)"),
+            Self::NoImage => write!(f, "no saved state"),
+            Self::BadVersion(v) => write!(f, "saved state has unknown version {}", v),
+            Self::BadChecksum => write!(f, "saved state is corrupt (checksum mismatch)"),
+            Self::Truncated => write!(f, "saved state is truncated"),
+            Self::TooLarge(n) => write!(f, "{} bytes do not fit in {} bytes", n, IMAGE_SIZE),
+        }
+    }
+}
+
+pub type Entry = (String, Vec<u8>);
+
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+pub fn encode(entries: &[Entry]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for (path, data) in entries {
+        payload.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        payload.extend_from_slice(path.as_bytes());
+        payload.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        payload.extend_from_slice(data);
+    }
+    let mut image = Vec::with_capacity(HEADER_LEN + payload.len());
+    image.extend_from_slice(&MAGIC);
+    image.push(VERSION);
+    image.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    image.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    image.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+    image.extend_from_slice(&payload);
+    image
+}
+
+/// Reads `n` bytes at `*at` and moves past them.
+fn take<'a>(bytes: &'a [u8], at: &mut usize, n: usize) -> Result<&'a [u8], PersistError> {
+    let field = bytes.get(*at..*at + n).ok_or(PersistError::Truncated)?;
+    *at += n;
+    Ok(field)
+}
+
+/// `bytes` may run on past the image; only the header's length is read.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Entry>, PersistError> {
+    if bytes.len() < HEADER_LEN || bytes[..8] != MAGIC {
+        return Err(PersistError::NoImage);
+    }
+    if bytes[8] != VERSION {
+        return Err(PersistError::BadVersion(bytes[8]));
+    }
+    let count = u16::from_le_bytes([bytes[9], bytes[10]]) as usize;
+    let len = u32::from_le_bytes(bytes[11..15].try_into().unwrap()) as usize;
+    let checksum = u64::from_le_bytes(bytes[15..23].try_into().unwrap());
+    let payload = bytes
+        .get(HEADER_LEN..HEADER_LEN + len)
+        .ok_or(PersistError::Truncated)?;
+    if fnv1a(payload) != checksum {
+        return Err(PersistError::BadChecksum);
+    }
+
+    let mut at = 0;
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let path_len = u16::from_le_bytes(take(payload, &mut at, 2)?.try_into().unwrap());
+        let path = take(payload, &mut at, path_len as usize)?;
+        let path = String::from_utf8_lossy(path).into_owned();
+        let data_len = u32::from_le_bytes(take(payload, &mut at, 4)?.try_into().unwrap());
+        let data = take(payload, &mut at, data_len as usize)?.to_vec();
+        entries.push((path, data));
+    }
+    Ok(entries)
+}
+
+/// Physical address of the area.
+static AREA: Once<u64> = Once::new();
+
+/// Picks the area from the bootloader's memory map. `avoid` is the frame
+/// allocator's window. Returns the area's physical address, if any.
+pub fn find_area(regions: &[MemoryRegion], avoid: (u64, u64)) -> Option<u64> {
+    let area = regions
+        .iter()
+        .filter(|r| r.kind == MemoryRegionKind::Usable)
+        .filter_map(|r| {
+            let start = (r.end.checked_sub(AREA_SIZE as u64)?) & !0xFFF;
+            let clear = start >= r.start && (r.end <= avoid.0 || start >= avoid.1);
+            clear.then_some(start)
+        })
+        .max()?;
+    let mapped = (0..AREA_SIZE as u64).step_by(4096).all(|off| {
+        crate::memory::page_is_mapped(crate::memory::phys_to_virt(PhysAddr::new(area + off)))
+    });
+    mapped.then(|| *AREA.call_once(|| area))
+}
+
+/// `len` bytes of the area from `offset`. Lock-free, for the panic path.
+fn area_part(offset: usize, len: usize) -> Option<&'static mut [u8]> {
+    let phys = *AREA.get()?;
+    let virt: VirtAddr = crate::memory::phys_to_virt(PhysAddr::new(phys + offset as u64));
+    // SAFETY: the area is usable RAM that nothing else allocates from, and
+    // it was checked to be mapped when it was picked.
+    Some(unsafe { core::slice::from_raw_parts_mut(virt.as_mut_ptr::<u8>(), len) })
+}
+
+fn area() -> Result<&'static mut [u8], PersistError> {
+    area_part(0, IMAGE_SIZE).ok_or(PersistError::Unavailable)
+}
+
+/// Where `lastpanic` keeps its record, once the area is found.
+pub(super) fn panic_slot() -> Option<&'static mut [u8]> {
+    area_part(IMAGE_SIZE, lastpanic::SLOT_SIZE)
+}
+
+/// The ramfs files that are saved: `/config` whole and the tail of each
+/// file under `/log`.
+fn collect() -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for dir in SAVED_DIRS {
+        entries.extend(ramfs::files_under(dir));
+    }
+    for (path, data) in ramfs::files_under(LOG_DIR) {
+        let tail = data[data.len().saturating_sub(LOG_TAIL)..].to_vec();
+        entries.push((path, tail));
+    }
+    entries
+}
+
+/// Writes the saved subtrees to the area. Returns the file count and the
+/// image size.
+pub fn save() -> Result<(usize, usize), PersistError> {
+    let area = area()?;
+    let entries = collect();
+    let image = encode(&entries);
+    if image.len() > IMAGE_SIZE {
+        return Err(PersistError::TooLarge(image.len()));
+    }
+    area[..image.len()].copy_from_slice(&image);
+    Ok((entries.len(), image.len()))
+}
+
+/// Puts the files of a previous session's image back into ramfs. Returns
+/// how many were restored.
+pub fn restore() -> Result<usize, PersistError> {
+    let entries = decode(area()?)?;
+    for (path, data) in &entries {
+        // Paths came from ramfs, so they were valid when saved.
+        let _ = ramfs::write(path, data);
+    }
+    Ok(entries.len())
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn sample() -> Vec<Entry> {
+        vec![
+            (String::from("/config/contrast"), b"on".to_vec()),
+            (String::from("/config/empty"), Vec::new()),
+            (String::from("/log/boot"), b"line 1\nline 2\n".to_vec()),
+        ]
+    }
+
+    #[test_case]
+    fn round_trip() {
+        let mut image = encode(&sample());
+        assert_eq!(decode(&image), Ok(sample()));
+        // trailing bytes of the area are ignored
+        image.extend_from_slice(&[0xAA; 32]);
+        assert_eq!(decode(&image), Ok(sample()));
+        assert_eq!(decode(&encode(&[])), Ok(Vec::new()));
+    }
+
+    #[test_case]
+    fn damaged_images_are_rejected() {
+        let image = encode(&sample());
+
+        let mut flipped = image.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert_eq!(decode(&flipped), Err(PersistError::BadChecksum));
+
+        let mut version = image.clone();
+        version[8] = 9;
+        assert_eq!(decode(&version), Err(PersistError::BadVersion(9)));
+
+        assert_eq!(
+            decode(&image[..image.len() - 1]),
+            Err(PersistError::Truncated)
+        );
+        assert_eq!(decode(&[0u8; 64]), Err(PersistError::NoImage));
+    }
+}
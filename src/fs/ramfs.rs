@@ -0,0 +1,80 @@
+//! # RAM File System
+//!
+//! Flat map from absolute path to contents, kept on the kernel heap and
+//! lost on reboot (see `persist` for the parts that can be carried over).
+//! Directories are implied by the paths of the files in them; there is no
+//! separate directory entry.
+//!
+//! Paths are expected to be absolute and already normalised (the shell
+//! resolves them against its working directory first).
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use spin::Mutex;
+
+static FILES: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    InvalidPath,
+}
+
+fn check_path(path: &str) -> Result<(), FsError> {
+    if path.starts_with('/') && path.len() > 1 && !path.ends_with('/') {
+        Ok(())
+    } else {
+        Err(FsError::InvalidPath)
+    }
+}
+
+pub fn exists(path: &str) -> bool {
+    FILES.lock().contains_key(path)
+}
+
+/// Creates an empty file unless it already exists.
+pub fn create(path: &str) -> Result<(), FsError> {
+    check_path(path)?;
+    FILES.lock().entry(String::from(path)).or_default();
+    Ok(())
+}
+
+pub fn read(path: &str) -> Result<Vec<u8>, FsError> {
+    FILES.lock().get(path).cloned().ok_or(FsError::NotFound)
+}
+
+/// Replaces the whole file, creating it if needed.
+pub fn write(path: &str, data: &[u8]) -> Result<(), FsError> {
+    check_path(path)?;
+    FILES.lock().insert(String::from(path), Vec::from(data));
+    Ok(())
+}
+
+pub fn size(path: &str) -> Result<usize, FsError> {
+    FILES
+        .lock()
+        .get(path)
+        .map(Vec::len)
+        .ok_or(FsError::NotFound)
+}
+
+/// Deletes the file, returning what it held. Only tests delete files so
+/// far, to put back a file they found missing.
+#[cfg(test)]
+pub fn remove(path: &str) -> Result<Vec<u8>, FsError> {
+    FILES.lock().remove(path).ok_or(FsError::NotFound)
+}
+
+/// Runs `f` on the file's contents in place.
+pub fn with_file<R>(path: &str, f: impl FnOnce(&mut Vec<u8>) -> R) -> Result<R, FsError> {
+    FILES.lock().get_mut(path).map(f).ok_or(FsError::NotFound)
+}
+
+/// Every file whose path starts with `prefix`, with its contents.
+pub fn files_under(prefix: &str) -> Vec<(String, Vec<u8>)> {
+    FILES
+        .lock()
+        .range(String::from(prefix)..)
+        .take_while(|(path, _)| path.starts_with(prefix))
+        .map(|(path, data)| (path.clone(), data.clone()))
+        .collect()
+}
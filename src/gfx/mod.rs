@@ -0,0 +1,8 @@
+//! # Graphics for Programs
+//!
+//! What user programs draw with, as opposed to the kernel's own UI in
+//! `ui_provider`.
+//!
+//! - `surface`: offscreen buffers presented into compositor windows
+
+pub mod surface;
@@ -0,0 +1,462 @@
+//! # Client Surfaces
+//!
+//! Offscreen pixel buffers that programs draw into and the compositor
+//! shows. `SYS_SURFACE_CREATE` maps `w * h` RGBA8 pixels read-write for
+//! the caller and returns a handle; `SYS_SURFACE_PRESENT` copies them into
+//! the surface's window; `SYS_SURFACE_DESTROY` unmaps them. Handles are
+//! never reused, and only the process that created a surface may present
+//! or destroy it.
+//!
+//! ## Windows
+//!
+//! Each surface gets a fixed window at the top right of the app area,
+//! cascading down and left by `CASCADE` per slot, drawn over the apps
+//! every frame and clipped to the screen. When a surface goes, the rect
+//! its window was last drawn at is handed to the next frame
+//! (`take_exposed`) for the apps and background to paint again.
+//!
+//! A surface's pixels stay mapped while it lives: `sys_munmap` refuses a
+//! range that overlaps one (`overlaps_mapping`), so only destroying the
+//! surface unmaps them.
+//!
+//! ## Pacing
+//!
+//! A present copies the pixels into a kernel-side front buffer and asks
+//! for a frame. Until the compositor has drawn it, further presents on
+//! that surface fail with `WouldBlock`, so a client presenting in a tight
+//! loop gets one present per frame and cannot keep the UI busy copying.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::memory::{mmap::sys_mmap, munmap::sys_munmap, MemError};
+use crate::syscalls::dispatcher::{SyscallError, SyscallResult};
+use crate::ui_provider::{layers, shape::Rect};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// Largest surface side, in pixels.
+pub const MAX_SURFACE_DIM: usize = 1024;
+/// Surfaces alive at once, across all processes.
+pub const MAX_SURFACES: usize = 8;
+/// Offset between the windows of consecutive slots.
+const CASCADE: usize = 24;
+/// Gap between a window and the edge of the app area.
+const MARGIN: usize = 16;
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceError {
+    /// No live surface has this handle.
+    BadHandle,
+    /// The surface belongs to another process.
+    NotOwner,
+    /// A side is 0 or over `MAX_SURFACE_DIM`.
+    BadSize,
+    TooMany,
+    /// The last present has not been drawn yet.
+    Busy,
+    Mem(MemError),
+}
+
+impl fmt::Display for SurfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SurfaceError::BadHandle => write!(f, "no such surface"),
+            SurfaceError::NotOwner => write!(f, "surface belongs to another process"),
+            SurfaceError::BadSize => write!(f, "sides must be 1..={}", MAX_SURFACE_DIM),
+            SurfaceError::TooMany => write!(f, "at most {} surfaces", MAX_SURFACES),
+            SurfaceError::Busy => write!(f, "previous present not drawn yet"),
+            SurfaceError::Mem(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<MemError> for SurfaceError {
+    fn from(err: MemError) -> Self {
+        SurfaceError::Mem(err)
+    }
+}
+
+impl From<SurfaceError> for SyscallError {
+    fn from(err: SurfaceError) -> Self {
+        match err {
+            SurfaceError::BadHandle => SyscallError::BadFileDescriptor,
+            SurfaceError::NotOwner => SyscallError::PermissionDenied,
+            SurfaceError::BadSize => SyscallError::InvalidArgument,
+            SurfaceError::TooMany => SyscallError::TooManyFiles,
+            SurfaceError::Busy => SyscallError::WouldBlock,
+            SurfaceError::Mem(err) => err.into(),
+        }
+    }
+}
+
+pub struct Surface {
+    pub owner: usize,
+    pub w: usize,
+    pub h: usize,
+    /// Where the RGBA8 pixels are mapped for the owner.
+    pub addr: usize,
+    /// Window position; picks the cascade offset.
+    slot: usize,
+    /// Packed RGB888 as of the last present, empty before the first.
+    front: Vec<u32>,
+    /// Presented and not drawn yet.
+    pending: bool,
+    /// Where its window was last drawn.
+    drawn: Option<Rect>,
+}
+
+impl Surface {
+    pub fn byte_len(&self) -> usize {
+        self.w * self.h * 4
+    }
+
+    /// Where the window goes in `area`, before clipping.
+    pub fn window(&self, area: Rect) -> Rect {
+        let offset = MARGIN + self.slot * CASCADE;
+        Rect::new(
+            (area.x + area.w).saturating_sub(self.w + offset),
+            area.y + offset,
+            self.w,
+            self.h,
+        )
+    }
+}
+
+/// RGBA8 in memory order to the framebuffer's packed RGB888. Alpha is
+/// ignored; windows are opaque.
+fn pack(rgba: u32) -> u32 {
+    let [r, g, b, _] = rgba.to_le_bytes();
+    u32::from_be_bytes([0, r, g, b])
+}
+
+pub struct SurfaceTable {
+    surfaces: BTreeMap<u32, Surface>,
+    next_handle: u32,
+    /// Windows of surfaces removed since the last `take_exposed`.
+    exposed: Option<Rect>,
+}
+
+impl SurfaceTable {
+    pub const fn new() -> Self {
+        Self {
+            surfaces: BTreeMap::new(),
+            next_handle: 1,
+            exposed: None,
+        }
+    }
+
+    /// Records a surface of `w * h` pixels mapped at `addr` for `owner`.
+    pub fn insert(
+        &mut self,
+        owner: usize,
+        w: usize,
+        h: usize,
+        addr: usize,
+    ) -> Result<u32, SurfaceError> {
+        if !(1..=MAX_SURFACE_DIM).contains(&w) || !(1..=MAX_SURFACE_DIM).contains(&h) {
+            return Err(SurfaceError::BadSize);
+        }
+        if self.surfaces.len() >= MAX_SURFACES {
+            return Err(SurfaceError::TooMany);
+        }
+        let slot = (0..MAX_SURFACES)
+            .find(|slot| self.surfaces.values().all(|s| s.slot != *slot))
+            .unwrap_or(0);
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.surfaces.insert(
+            handle,
+            Surface {
+                owner,
+                w,
+                h,
+                addr,
+                slot,
+                front: Vec::new(),
+                pending: false,
+                drawn: None,
+            },
+        );
+        Ok(handle)
+    }
+
+    fn owned(&mut self, handle: u32, pid: usize) -> Result<&mut Surface, SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&handle)
+            .ok_or(SurfaceError::BadHandle)?;
+        if surface.owner != pid {
+            return Err(SurfaceError::NotOwner);
+        }
+        Ok(surface)
+    }
+
+    /// Copies the pixels at the surface's address into its front buffer.
+    ///
+    /// # Safety
+    /// The surface's `byte_len()` bytes at `addr` must be readable.
+    pub unsafe fn present(&mut self, handle: u32, pid: usize) -> Result<(), SurfaceError> {
+        let surface = self.owned(handle, pid)?;
+        if surface.pending {
+            return Err(SurfaceError::Busy);
+        }
+        let pixels = core::slice::from_raw_parts(surface.addr as *const u32, surface.w * surface.h);
+        surface.front.clear();
+        surface.front.extend(pixels.iter().map(|&p| pack(p)));
+        surface.pending = true;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, handle: u32, pid: usize) -> Result<Surface, SurfaceError> {
+        self.owned(handle, pid)?;
+        let surface = self.surfaces.remove(&handle).unwrap();
+        self.exposed = layers::union(self.exposed, surface.drawn);
+        Ok(surface)
+    }
+
+    /// Takes every surface `pid` still holds, for when it exits.
+    pub fn remove_owner(&mut self, pid: usize) -> Vec<Surface> {
+        let handles: Vec<u32> = self
+            .surfaces
+            .iter()
+            .filter(|(_, s)| s.owner == pid)
+            .map(|(&h, _)| h)
+            .collect();
+        let gone: Vec<Surface> = handles
+            .into_iter()
+            .filter_map(|h| self.surfaces.remove(&h))
+            .collect();
+        for surface in &gone {
+            self.exposed = layers::union(self.exposed, surface.drawn);
+        }
+        gone
+    }
+
+    /// Where windows of removed surfaces were drawn, since the last call.
+    pub fn take_exposed(&mut self) -> Option<Rect> {
+        self.exposed.take()
+    }
+
+    /// Whether `len` bytes at `addr` overlap any live surface's pixels.
+    pub fn overlaps(&self, addr: usize, len: usize) -> bool {
+        let end = addr.saturating_add(len);
+        self.surfaces
+            .values()
+            .any(|s| addr < s.addr + s.byte_len() && s.addr < end)
+    }
+
+    /// Blits every presented surface into its window in `area`, which
+    /// lets each present again.
+    pub fn draw(&mut self, fb: &mut FramebufferWriter, area: Rect) {
+        for surface in self.surfaces.values_mut() {
+            surface.pending = false;
+            if surface.front.is_empty() {
+                continue;
+            }
+            let rect = surface.window(area);
+            fb.blit(rect.x, rect.y, rect.w, rect.h, &surface.front);
+            surface.drawn = Some(rect);
+        }
+    }
+
+    /// Handle, owner and size of every live surface.
+    pub fn list(&self) -> Vec<(u32, usize, usize, usize)> {
+        self.surfaces
+            .iter()
+            .map(|(&handle, s)| (handle, s.owner, s.w, s.h))
+            .collect()
+    }
+}
+
+impl Default for SurfaceTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static SURFACES: Mutex<SurfaceTable> = Mutex::new(SurfaceTable::new());
+/// A present is waiting for the compositor.
+static DAMAGED: AtomicBool = AtomicBool::new(false);
+
+pub fn list() -> Vec<(u32, usize, usize, usize)> {
+    SURFACES.lock().list()
+}
+
+/// Whether a present since the last call wants a frame.
+pub fn take_damage() -> bool {
+    DAMAGED.swap(false, Ordering::Relaxed)
+}
+
+/// Called by the compositor each frame, after the apps are drawn.
+pub fn draw(fb: &mut FramebufferWriter, area: Rect) {
+    SURFACES.lock().draw(fb, area);
+}
+
+/// Where destroyed surfaces' windows were, for the next frame to paint
+/// again from below.
+pub fn take_exposed() -> Option<Rect> {
+    SURFACES.lock().take_exposed()
+}
+
+/// Whether unmapping `len` bytes at `addr` would pull pixels out from
+/// under a live surface.
+pub fn overlaps_mapping(addr: usize, len: usize) -> bool {
+    SURFACES.lock().overlaps(addr, len)
+}
+
+fn current_pid() -> usize {
+    crate::syscalls::handlers::process::current_pid()
+}
+
+/// Maps a `w * h` surface for the caller and writes its address to `addr_out`.
+pub fn sys_surface_create(w: usize, h: usize, addr_out: *mut usize) -> SyscallResult {
+    if !(1..=MAX_SURFACE_DIM).contains(&w) || !(1..=MAX_SURFACE_DIM).contains(&h) {
+        return Err(SurfaceError::BadSize.into());
+    }
+    let len = w * h * 4;
+    let addr = sys_mmap(0, len, PROT_READ | PROT_WRITE, 0, 0, 0)?;
+    let created = SURFACES.lock().insert(current_pid(), w, h, addr);
+    let handle = match created {
+        Ok(handle) => handle,
+        Err(err) => {
+            let _ = sys_munmap(addr, len);
+            return Err(err.into());
+        }
+    };
+    if let Err(err) = crate::syscalls::user::copy_to_user(addr_out as *mut u8, &addr.to_ne_bytes())
+    {
+        let _ = destroy(handle);
+        return Err(err);
+    }
+    Ok(handle as usize)
+}
+
+pub fn sys_surface_present(handle: usize) -> SyscallResult {
+    let handle = u32::try_from(handle).map_err(|_| SurfaceError::BadHandle)?;
+    // Safety: the table only holds surfaces whose pixels are still mapped:
+    // sys_munmap refuses ranges that overlap them, and destroy takes a
+    // surface out of the table before unmapping it
+    unsafe { SURFACES.lock().present(handle, current_pid())? };
+    DAMAGED.store(true, Ordering::Relaxed);
+    Ok(0)
+}
+
+pub fn sys_surface_destroy(handle: usize) -> SyscallResult {
+    let handle = u32::try_from(handle).map_err(|_| SurfaceError::BadHandle)?;
+    destroy(handle)?;
+    Ok(0)
+}
+
+fn destroy(handle: u32) -> Result<(), SurfaceError> {
+    let surface = SURFACES.lock().remove(handle, current_pid())?;
+    sys_munmap(surface.addr, surface.byte_len())?;
+    DAMAGED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Unmaps whatever surfaces `pid` left behind.
+pub fn release_process(pid: usize) {
+    let left = SURFACES.lock().remove_owner(pid);
+    for surface in &left {
+        let _ = sys_munmap(surface.addr, surface.byte_len());
+    }
+    if !left.is_empty() {
+        DAMAGED.store(true, Ordering::Relaxed);
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const PID: usize = 7;
+
+    #[test_case]
+    fn handles_die_with_their_surface() {
+        let mut table = SurfaceTable::new();
+        let pixels = vec![0u32; 16];
+        let addr = pixels.as_ptr() as usize;
+        let a = table.insert(PID, 4, 4, addr).unwrap();
+
+        assert_eq!(table.insert(PID, 0, 4, addr), Err(SurfaceError::BadSize));
+        assert_eq!(
+            table.insert(PID, MAX_SURFACE_DIM + 1, 4, addr),
+            Err(SurfaceError::BadSize)
+        );
+        assert!(matches!(
+            table.remove(a, PID + 1),
+            Err(SurfaceError::NotOwner)
+        ));
+        unsafe {
+            assert_eq!(table.present(a, PID + 1), Err(SurfaceError::NotOwner));
+            assert_eq!(table.present(a, PID), Ok(()));
+            // not drawn yet
+            assert_eq!(table.present(a, PID), Err(SurfaceError::Busy));
+        }
+
+        assert!(table.remove(a, PID).is_ok());
+        assert!(matches!(table.remove(a, PID), Err(SurfaceError::BadHandle)));
+        unsafe {
+            assert_eq!(table.present(a, PID), Err(SurfaceError::BadHandle));
+        }
+        // a new surface never gets the old handle
+        let b = table.insert(PID, 4, 4, addr).unwrap();
+        assert_ne!(a, b);
+        assert!(table.overlaps(addr + 60, 8));
+        assert!(!table.overlaps(addr + 64, 8));
+        assert_eq!(table.remove_owner(PID).len(), 1);
+        assert!(table.list().is_empty());
+        assert_eq!(
+            SyscallError::from(SurfaceError::Busy),
+            SyscallError::WouldBlock
+        );
+    }
+
+    #[test_case]
+    fn present_dirties_only_the_window_tiles() {
+        let (width, height) = (256, 192);
+        let buffer: &'static mut [u8] = vec![0u8; width * height * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, width, height, width, 4);
+        fb.render_frame();
+        assert!(fb.dirty_tiles().is_empty());
+
+        let mut table = SurfaceTable::new();
+        let (w, h) = (40, 30);
+        let pixels: Vec<u32> = (0..w * h)
+            .map(|i| 0xFF00_0000 | i as u32 * 2_654_435_761)
+            .collect();
+        let handle = table.insert(PID, w, h, pixels.as_ptr() as usize).unwrap();
+        unsafe { table.present(handle, PID).unwrap() };
+
+        let area = Rect::new(0, 32, width, height - 32);
+        table.draw(&mut fb, area);
+        let window = table.surfaces[&handle].window(area);
+        let dirty = fb.dirty_tiles();
+        assert!(!dirty.is_empty());
+        for (x, y) in dirty {
+            // every dirty tile overlaps the window
+            assert!(x < window.x + window.w && x + 32 > window.x, "{} {}", x, y);
+            assert!(y < window.y + window.h && y + 32 > window.y, "{} {}", x, y);
+        }
+        assert_eq!(
+            fb.snapshot_rect(window.x, window.y, 1, 1)[0],
+            pack(pixels[0])
+        );
+
+        // drawing let it present again
+        unsafe { assert_eq!(table.present(handle, PID), Ok(())) };
+
+        // Gone, its window is handed back once
+        assert_eq!(table.take_exposed(), None);
+        table.remove(handle, PID).unwrap();
+        assert_eq!(table.take_exposed(), Some(window));
+        assert_eq!(table.take_exposed(), None);
+    }
+}
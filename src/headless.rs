@@ -0,0 +1,124 @@
+//! # Headless Console
+//!
+//! Fallback used when the bootloader hands over no framebuffer. The kernel
+//! skips the UI entirely and runs the command executor as a line-based
+//! console on COM1: PS/2 keystrokes and bytes received on the serial port
+//! both feed the same line buffer, and everything is echoed back over serial.
+//!
+//! Unavailable while headless:
+//! - the app host and tabs (Terminal, Logs, Editor, Settings); use `dmesg`
+//!   instead of the Logs tab
+//! - the mouse cursor; mouse packets are drained and dropped
+//! - the boot splash and VM drawing instructions (both no-ops)
+//! - `latency` samples, since no frame is ever presented
+
+use crate::cmd_executor::{CommandExecutor, CommandResult};
+use crate::devices::drivers::{ps2_keyboard, ps2_mouse};
+use crate::log_warn;
+use alloc::string::String;
+use core::fmt::Write;
+use x86_64::instructions::port::Port;
+
+const COM1_DATA: u16 = 0x3F8;
+const COM1_LSR: u16 = 0x3FD;
+const LSR_DATA_READY: u8 = 0x01;
+
+fn write_str(s: &str) {
+    unsafe {
+        let _ = (*core::ptr::addr_of_mut!(crate::SERIAL)).write_str(s);
+    }
+}
+
+/// `SerialPort::receive` blocks, so check the line status register first.
+fn poll_serial() -> Option<u8> {
+    unsafe {
+        if Port::<u8>::new(COM1_LSR).read() & LSR_DATA_READY == 0 {
+            return None;
+        }
+        Some(Port::<u8>::new(COM1_DATA).read())
+    }
+}
+
+struct LineEditor {
+    line: String,
+}
+
+impl LineEditor {
+    fn prompt(&self) {
+        for notice in crate::apps::jobs::take_notices() {
+            write_lines(&notice);
+        }
+        write_str("> ");
+    }
+
+    fn handle_char(&mut self, ch: char) {
+        match ch {
+            '\n' | '\r' => {
+                write_str("\r\n");
+                let input = core::mem::take(&mut self.line);
+                match CommandExecutor::execute(&input) {
+                    CommandResult::Output(output) => {
+                        if !output.is_empty() {
+                            write_lines(&output);
+                        }
+                    }
+                    CommandResult::Error(error) => {
+                        write_str("Error: ");
+                        write_lines(&error);
+                    }
+                    CommandResult::Exit => write_str("Goodbye!\r\n"),
+                    CommandResult::Top => {
+                        // No live redraw on serial: one snapshot.
+                        let now = crate::devices::drivers::hpet::monotonic_ms();
+                        let lines = crate::apps::top::TopView::new(now).render(now, 80, 24);
+                        write_lines(&lines.join("\n"));
+                    }
+                }
+                self.prompt();
+            }
+            '\x08' | '\x7F' if self.line.pop().is_some() => write_str("\x08 \x08"),
+            c if c == ' ' || c.is_ascii_graphic() => {
+                self.line.push(c);
+                write_str(c.encode_utf8(&mut [0; 4]));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Serial terminals expect CRLF; command output only uses LF.
+fn write_lines(text: &str) {
+    for line in text.lines() {
+        write_str(line);
+        write_str("\r\n");
+    }
+}
+
+pub fn run() -> ! {
+    log_warn!("No framebuffer available, running headless on COM1");
+    write_str("\r\nDuxOS headless console (no framebuffer)\r\n");
+    write_str("Type 'help' for available commands\r\n");
+
+    let mut editor = LineEditor { line: String::new() };
+    let mut decoder = ps2_keyboard::ScancodeDecoder::new();
+    editor.prompt();
+
+    loop {
+        // Background jobs and other kernel tasks
+        crate::kcore::task::run_pending();
+        while let Some(scancode) = ps2_keyboard::dequeue_scancode() {
+            if let Some(key) = decoder.process_scancode(scancode) {
+                let plain = !key.mods.ctrl() && !key.mods.alt();
+                if let Some(ch) = key.code.to_char().filter(|_| plain) {
+                    editor.handle_char(ch);
+                }
+            }
+        }
+        while let Some(byte) = poll_serial() {
+            editor.handle_char(byte as char);
+        }
+        while ps2_mouse::poll_mouse_event().is_some() {}
+
+        x86_64::instructions::hlt();
+    }
+}
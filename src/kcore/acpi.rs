@@ -0,0 +1,617 @@
+//! # ACPI Tables
+//!
+//! Locates the RSDP, walks the RSDT (ACPI 1.0, 32-bit entries) or XSDT
+//! (2.0+, 64-bit entries) and decodes the tables the kernel uses:
+//! - MADT: local APICs (SMP bring-up), IO-APICs and interrupt overrides;
+//! - FADT: the power-management ports and the DSDT pointer;
+//! - HPET: the event timer's register block.
+//!
+//! ## RSDP
+//!
+//! The bootloader's `rsdp_addr` is tried first. Without it (or if it fails
+//! validation) the first KiB of the EBDA and the BIOS area
+//! `0xE0000..0x100000` are scanned on 16-byte boundaries.
+//!
+//! ## Checksums
+//!
+//! Every table is listed by the `acpi` command. A table with a bad checksum
+//! is listed as such and not decoded; a bad root table fails `init`.
+//!
+//! A table's length comes from its own header, so it is not trusted: at
+//! most `TABLE_MAX` bytes are read, never past the end of the physical
+//! memory mapping, and a table whose length runs past that is treated as
+//! corrupt. One whose header does not fit is skipped. The RSDP's own
+//! length gets the same treatment, capped at `RSDP_MAX`.
+
+use crate::memory::phys_to_virt;
+use alloc::{format, string::String, vec::Vec};
+use spin::Once;
+use x86_64::PhysAddr;
+
+const SDT_HEADER_LEN: usize = 36;
+const RSDP_V1_LEN: usize = 20;
+const RSDP_V2_LEN: usize = 36;
+/// Longest revision 2 RSDP accepted; its length field is not trusted.
+const RSDP_MAX: usize = 4096;
+/// Longest table read. The ones decoded, and the root tables, are a few
+/// KiB at most.
+const TABLE_MAX: usize = 64 * 1024;
+const BIOS_AREA: (u64, u64) = (0xE_0000, 0x10_0000);
+/// Real-mode pointer to the EBDA segment in the BIOS data area.
+const EBDA_SEGMENT_PTR: u64 = 0x40E;
+
+const MADT_LOCAL_APIC: u8 = 0;
+const MADT_IO_APIC: u8 = 1;
+const MADT_OVERRIDE: u8 = 2;
+const MADT_LAPIC_ADDRESS: u8 = 5;
+const LAPIC_ENABLED: u32 = 1 << 0;
+
+static ACPI: Once<Acpi> = Once::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootTable {
+    Rsdt,
+    Xsdt,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TableInfo {
+    pub signature: [u8; 4],
+    pub phys: u64,
+    pub len: u32,
+    pub checksum_ok: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Processor {
+    pub acpi_id: u8,
+    pub apic_id: u8,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IoApic {
+    pub id: u8,
+    pub phys: u64,
+    pub gsi_base: u32,
+}
+
+/// ISA IRQ routed to a different global system interrupt.
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct Madt {
+    pub lapic_phys: u64,
+    /// The 8259 PICs are present (PCAT_COMPAT) and must be masked before
+    /// switching to the IO-APIC.
+    pub has_8259: bool,
+    pub processors: Vec<Processor>,
+    pub io_apics: Vec<IoApic>,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+impl Madt {
+    /// APIC IDs of processors that may be started, the BSP included.
+    pub fn enabled_apic_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        self.processors
+            .iter()
+            .filter(|p| p.enabled)
+            .map(|p| p.apic_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fadt {
+    pub sci_irq: u16,
+    pub smi_cmd: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub pm1a_cnt: u32,
+    pub pm1b_cnt: u32,
+    pub pm_timer: u32,
+    pub dsdt: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HpetTable {
+    pub base_phys: u64,
+    pub number: u8,
+    /// Smallest periodic tick the timer supports, in counter ticks.
+    pub min_tick: u16,
+}
+
+pub struct Acpi {
+    pub rsdp_phys: u64,
+    pub revision: u8,
+    pub oem_id: [u8; 6],
+    pub root: RootTable,
+    pub tables: Vec<TableInfo>,
+    pub madt: Option<Madt>,
+    pub fadt: Option<Fadt>,
+    pub hpet: Option<HpetTable>,
+}
+
+/// # Safety
+/// `phys..phys + len` must be covered by the physical memory mapping.
+unsafe fn phys_bytes(phys: u64, len: usize) -> &'static [u8] {
+    core::slice::from_raw_parts(phys_to_virt(PhysAddr::new(phys)).as_ptr(), len)
+}
+
+fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// The RSDP candidate at `phys`, read no further than `RSDP_MAX` or the
+/// end of the physical memory mapping allow; see `rsdp_ok`.
+unsafe fn valid_rsdp(phys: u64) -> bool {
+    use crate::memory::PHYSICAL_MAPPING_END;
+    use core::sync::atomic::Ordering;
+
+    let mapped = PHYSICAL_MAPPING_END
+        .load(Ordering::SeqCst)
+        .saturating_sub(phys);
+    rsdp_ok(phys_bytes(phys, mapped.min(RSDP_MAX as u64) as usize))
+}
+
+/// Checks signature and checksums of the RSDP candidate at the start of
+/// `bytes`. The extended checksum only exists from revision 2 on, over a
+/// length that must be `RSDP_V2_LEN..=RSDP_MAX` and fit in `bytes`.
+fn rsdp_ok(bytes: &[u8]) -> bool {
+    let Some(v1) = bytes.get(..RSDP_V1_LEN) else {
+        return false;
+    };
+    if &v1[..8] != b"RSD PTR " || !checksum_ok(v1) {
+        return false;
+    }
+    if v1[15] < 2 {
+        return true;
+    }
+    if bytes.len() < RSDP_V2_LEN {
+        return false;
+    }
+    let len = read_u32(bytes, 20) as usize;
+    (RSDP_V2_LEN..=RSDP_MAX).contains(&len) && bytes.get(..len).is_some_and(checksum_ok)
+}
+
+unsafe fn scan_rsdp(start: u64, end: u64) -> Option<u64> {
+    (start..end.saturating_sub(RSDP_V1_LEN as u64))
+        .step_by(16)
+        .find(|&phys| valid_rsdp(phys))
+}
+
+unsafe fn find_rsdp(hint: Option<u64>) -> Option<u64> {
+    if let Some(phys) = hint.filter(|&p| valid_rsdp(p)) {
+        return Some(phys);
+    }
+    let ebda = (read_u16(phys_bytes(EBDA_SEGMENT_PTR, 2), 0) as u64) << 4;
+    if ebda >= 0x8_0000 && ebda < BIOS_AREA.0 {
+        if let Some(phys) = scan_rsdp(ebda, ebda + 1024) {
+            return Some(phys);
+        }
+    }
+    scan_rsdp(BIOS_AREA.0, BIOS_AREA.1)
+}
+
+/// The table at `phys`, read no further than its length, `TABLE_MAX` or
+/// the end of the physical memory mapping allow; see `parse_table`.
+unsafe fn read_table(phys: u64) -> Option<(TableInfo, Option<&'static [u8]>)> {
+    use crate::memory::PHYSICAL_MAPPING_END;
+    use core::sync::atomic::Ordering;
+
+    let mapped = PHYSICAL_MAPPING_END
+        .load(Ordering::SeqCst)
+        .saturating_sub(phys);
+    let room = mapped.min(TABLE_MAX as u64) as usize;
+    if room < SDT_HEADER_LEN {
+        return None;
+    }
+    let len = (read_u32(phys_bytes(phys, SDT_HEADER_LEN), 4) as usize).min(room);
+    parse_table(phys, phys_bytes(phys, len.max(SDT_HEADER_LEN)))
+}
+
+/// Header info of the table at the start of `bytes` (`phys` is only
+/// recorded) and, if its length fits in `bytes` and its checksum holds,
+/// its bytes. `None` if `bytes` cannot hold the header.
+fn parse_table(phys: u64, bytes: &[u8]) -> Option<(TableInfo, Option<&[u8]>)> {
+    if bytes.len() < SDT_HEADER_LEN {
+        return None;
+    }
+    let len = read_u32(bytes, 4);
+    let mut info = TableInfo {
+        signature: bytes[..4].try_into().unwrap(),
+        phys,
+        len,
+        checksum_ok: false,
+    };
+    let table = bytes
+        .get(..len as usize)
+        .filter(|table| table.len() >= SDT_HEADER_LEN);
+    let Some(table) = table else {
+        return Some((info, None));
+    };
+    info.checksum_ok = checksum_ok(table);
+    Some((info, info.checksum_ok.then_some(table)))
+}
+
+/// `None` if the table is too short for the local APIC address and flags.
+fn parse_madt(madt: &[u8]) -> Option<Madt> {
+    if madt.len() < SDT_HEADER_LEN + 8 {
+        return None;
+    }
+    let mut out = Madt {
+        lapic_phys: read_u32(madt, SDT_HEADER_LEN) as u64,
+        has_8259: read_u32(madt, SDT_HEADER_LEN + 4) & 1 != 0,
+        processors: Vec::new(),
+        io_apics: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    let mut at = SDT_HEADER_LEN + 8;
+    while at + 2 <= madt.len() {
+        let (kind, len) = (madt[at], madt[at + 1] as usize);
+        if len < 2 || at + len > madt.len() {
+            break;
+        }
+        let entry = &madt[at..at + len];
+        match kind {
+            MADT_LOCAL_APIC if len >= 8 => out.processors.push(Processor {
+                acpi_id: entry[2],
+                apic_id: entry[3],
+                enabled: read_u32(entry, 4) & LAPIC_ENABLED != 0,
+            }),
+            MADT_IO_APIC if len >= 12 => out.io_apics.push(IoApic {
+                id: entry[2],
+                phys: read_u32(entry, 4) as u64,
+                gsi_base: read_u32(entry, 8),
+            }),
+            MADT_OVERRIDE if len >= 10 => out.overrides.push(InterruptOverride {
+                irq: entry[3],
+                gsi: read_u32(entry, 4),
+                flags: read_u16(entry, 8),
+            }),
+            MADT_LAPIC_ADDRESS if len >= 12 => out.lapic_phys = read_u64(entry, 4),
+            _ => {}
+        }
+        at += len;
+    }
+    Some(out)
+}
+
+fn parse_fadt(fadt: &[u8]) -> Option<Fadt> {
+    if fadt.len() < 80 {
+        return None;
+    }
+    // 2.0+ moves the DSDT pointer to a 64-bit X_DSDT field.
+    let x_dsdt = if fadt.len() >= 148 {
+        read_u64(fadt, 140)
+    } else {
+        0
+    };
+    Some(Fadt {
+        sci_irq: read_u16(fadt, 46),
+        smi_cmd: read_u32(fadt, 48),
+        acpi_enable: fadt[52],
+        acpi_disable: fadt[53],
+        pm1a_cnt: read_u32(fadt, 64),
+        pm1b_cnt: read_u32(fadt, 68),
+        pm_timer: read_u32(fadt, 76),
+        dsdt: if x_dsdt != 0 {
+            x_dsdt
+        } else {
+            read_u32(fadt, 40) as u64
+        },
+    })
+}
+
+fn parse_hpet(hpet: &[u8]) -> Option<HpetTable> {
+    // The base address is a generic address structure; 0 = memory space.
+    if hpet.len() < 56 || hpet[40] != 0 {
+        return None;
+    }
+    Some(HpetTable {
+        base_phys: read_u64(hpet, 44),
+        number: hpet[52],
+        min_tick: read_u16(hpet, 53),
+    })
+}
+
+/// Finds and decodes the tables. `rsdp_hint` is the bootloader's
+/// `rsdp_addr`. Needs the heap and the physical memory mapping.
+pub fn init(rsdp_hint: Option<u64>) -> Result<(), &'static str> {
+    let rsdp_phys = unsafe { find_rsdp(rsdp_hint) }.ok_or("no RSDP found")?;
+    let rsdp = unsafe { phys_bytes(rsdp_phys, RSDP_V1_LEN) };
+    let revision = rsdp[15];
+
+    // Prefer the XSDT when there is one; fall back to the RSDT.
+    let xsdt = if revision >= 2 {
+        read_u64(unsafe { phys_bytes(rsdp_phys, RSDP_V2_LEN) }, 24)
+    } else {
+        0
+    };
+    let (root, root_phys, entry_len) = if xsdt != 0 {
+        (RootTable::Xsdt, xsdt, 8)
+    } else {
+        (RootTable::Rsdt, read_u32(rsdp, 16) as u64, 4)
+    };
+    let (root_info, root_bytes) = unsafe { read_table(root_phys) }.ok_or("RSDT/XSDT not mapped")?;
+    let root_bytes = root_bytes.ok_or("bad RSDT/XSDT length or checksum")?;
+
+    let mut acpi = Acpi {
+        rsdp_phys,
+        revision,
+        oem_id: rsdp[9..15].try_into().unwrap(),
+        root,
+        tables: Vec::new(),
+        madt: None,
+        fadt: None,
+        hpet: None,
+    };
+    acpi.tables.push(root_info);
+
+    for entry in root_bytes[SDT_HEADER_LEN..].chunks_exact(entry_len) {
+        let phys = if entry_len == 8 {
+            read_u64(entry, 0)
+        } else {
+            read_u32(entry, 0) as u64
+        };
+        if phys == 0 {
+            continue;
+        }
+        let Some((info, bytes)) = (unsafe { read_table(phys) }) else {
+            continue;
+        };
+        acpi.tables.push(info);
+        match (&info.signature, bytes) {
+            (b"APIC", Some(bytes)) => acpi.madt = parse_madt(bytes),
+            (b"FACP", Some(bytes)) => acpi.fadt = parse_fadt(bytes),
+            (b"HPET", Some(bytes)) => acpi.hpet = parse_hpet(bytes),
+            _ => {}
+        }
+    }
+
+    ACPI.call_once(|| acpi);
+    Ok(())
+}
+
+/// Decoded tables, once `init` succeeded.
+pub fn get() -> Option<&'static Acpi> {
+    ACPI.get()
+}
+
+pub fn madt() -> Option<&'static Madt> {
+    get().and_then(|acpi| acpi.madt.as_ref())
+}
+
+pub fn fadt() -> Option<&'static Fadt> {
+    get().and_then(|acpi| acpi.fadt.as_ref())
+}
+
+pub fn hpet() -> Option<&'static HpetTable> {
+    get().and_then(|acpi| acpi.hpet.as_ref())
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+/// Table list for the `acpi` command.
+pub fn report() -> String {
+    let Some(acpi) = get() else {
+        return String::from("ACPI: no tables (RSDP not found or invalid)");
+    };
+    let mut out = format!(
+        "RSDP at {:#x}, revision {}, OEM '{}', root {:?}\n",
+        acpi.rsdp_phys,
+        acpi.revision,
+        ascii(&acpi.oem_id).trim_end(),
+        acpi.root
+    );
+    for t in &acpi.tables {
+        out.push_str(&format!(
+            "  {}  {:#010x}  {:>6} B{}\n",
+            ascii(&t.signature),
+            t.phys,
+            t.len,
+            if t.checksum_ok {
+                ""
+            } else {
+                "  bad length or checksum"
+            }
+        ));
+    }
+    if let Some(madt) = madt() {
+        out.push_str(&format!(
+            "MADT: LAPIC {:#x}{}\n",
+            madt.lapic_phys,
+            if madt.has_8259 {
+                ", 8259 PICs present"
+            } else {
+                ""
+            }
+        ));
+        for cpu in &madt.processors {
+            out.push_str(&format!(
+                "  cpu    acpi {:<3} apic {:<3} {}\n",
+                cpu.acpi_id,
+                cpu.apic_id,
+                if cpu.enabled { "enabled" } else { "disabled" }
+            ));
+        }
+        for io in &madt.io_apics {
+            out.push_str(&format!(
+                "  ioapic id {:<3} at {:#x}, GSI base {}\n",
+                io.id, io.phys, io.gsi_base
+            ));
+        }
+        for o in &madt.overrides {
+            out.push_str(&format!(
+                "  irq {:<2} -> GSI {:<3} flags {:#06x}\n",
+                o.irq, o.gsi, o.flags
+            ));
+        }
+    }
+    if let Some(fadt) = fadt() {
+        out.push_str(&format!(
+            "FADT: DSDT {:#x}, SCI IRQ {}, PM1a_CNT {:#x}, PM1b_CNT {:#x}, PM timer {:#x}\n",
+            fadt.dsdt, fadt.sci_irq, fadt.pm1a_cnt, fadt.pm1b_cnt, fadt.pm_timer
+        ));
+        out.push_str(&format!(
+            "      SMI {:#x}, enable {:#x}, disable {:#x}\n",
+            fadt.smi_cmd, fadt.acpi_enable, fadt.acpi_disable
+        ));
+    }
+    if let Some(hpet) = hpet() {
+        out.push_str(&format!(
+            "HPET: #{} at {:#x}, min tick {}\n",
+            hpet.number, hpet.base_phys, hpet.min_tick
+        ));
+    }
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// A table with `signature` and `body`, its length and checksum set.
+    fn table(signature: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0u8; SDT_HEADER_LEN];
+        bytes[..4].copy_from_slice(signature);
+        bytes.extend_from_slice(body);
+        let len = bytes.len() as u32;
+        bytes[4..8].copy_from_slice(&len.to_le_bytes());
+        let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        bytes[9] = 0u8.wrapping_sub(sum);
+        bytes
+    }
+
+    /// `size` bytes holding a revision 2 RSDP whose length field says
+    /// `len`, with both checksums set over what of it `size` covers.
+    fn rsdp(len: u32, size: usize) -> Vec<u8> {
+        let sum = |bytes: &[u8]| bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        let mut bytes = vec![0u8; size];
+        bytes[..8].copy_from_slice(b"RSD PTR ");
+        bytes[15] = 2;
+        bytes[20..24].copy_from_slice(&len.to_le_bytes());
+        bytes[8] = 0u8.wrapping_sub(sum(&bytes[..RSDP_V1_LEN]));
+        let end = (len as usize).min(size);
+        bytes[32] = 0u8.wrapping_sub(sum(&bytes[..end]));
+        bytes
+    }
+
+    /// LAPIC address and flags, then `entries`.
+    fn madt(entries: &[u8]) -> Vec<u8> {
+        let mut body = Vec::from(0xFEE0_0000u32.to_le_bytes());
+        body.extend(1u32.to_le_bytes());
+        body.extend_from_slice(entries);
+        table(b"APIC", &body)
+    }
+
+    #[test_case]
+    fn a_table_is_read_only_as_far_as_its_length_and_the_bytes_go() {
+        let good = madt(&[MADT_LOCAL_APIC, 8, 0, 3, 1, 0, 0, 0]);
+        let (info, bytes) = parse_table(0x1000, &good).unwrap();
+        assert!(info.checksum_ok);
+        assert_eq!(info.phys, 0x1000);
+        assert_eq!(bytes, Some(&good[..]));
+
+        // What follows it is not part of it
+        let mut longer = good.clone();
+        longer.extend([0xFF; 8]);
+        assert_eq!(parse_table(0, &longer).unwrap().1, Some(&good[..]));
+
+        // Cut short by the end of the mapping: listed, not decoded
+        let (info, bytes) = parse_table(0, &good[..good.len() - 4]).unwrap();
+        assert_eq!(info.len as usize, good.len());
+        assert!(!info.checksum_ok);
+        assert_eq!(bytes, None);
+        assert!(parse_table(0, &good[..SDT_HEADER_LEN - 1]).is_none());
+    }
+
+    #[test_case]
+    fn an_rsdp_length_is_checked_before_it_is_summed() {
+        assert!(rsdp_ok(&rsdp(36, 36)));
+        assert!(rsdp_ok(&rsdp(40, 64)));
+        // Too short, too long, or past the end of the mapping
+        assert!(!rsdp_ok(&rsdp(20, 36)));
+        assert!(!rsdp_ok(&rsdp(u32::MAX, 64)));
+        assert!(!rsdp_ok(&rsdp(RSDP_MAX as u32 + 16, RSDP_MAX + 16)));
+        assert!(!rsdp_ok(&rsdp(64, 40)));
+        assert!(!rsdp_ok(&rsdp(36, 36)[..RSDP_V2_LEN - 1]));
+
+        // Revision 0 has only the first checksum, over 20 bytes
+        let mut v1 = rsdp(36, 36);
+        v1[15] = 0;
+        v1[8] = v1[8].wrapping_add(2);
+        assert!(rsdp_ok(&v1[..RSDP_V1_LEN]));
+        assert!(!rsdp_ok(&v1[..RSDP_V1_LEN - 1]));
+        v1[0] = b'X';
+        assert!(!rsdp_ok(&v1));
+    }
+
+    #[test_case]
+    fn corrupt_headers_are_not_decoded() {
+        let good = madt(&[]);
+        for len in [0, 8, SDT_HEADER_LEN as u32 - 1, u32::MAX] {
+            let mut bad = good.clone();
+            bad[4..8].copy_from_slice(&len.to_le_bytes());
+            let (info, bytes) = parse_table(0, &bad).unwrap();
+            assert_eq!(info.len, len);
+            assert_eq!(bytes, None, "length {}", len);
+        }
+        let mut flipped = good.clone();
+        flipped[SDT_HEADER_LEN] ^= 1;
+        let (info, bytes) = parse_table(0, &flipped).unwrap();
+        assert!(!info.checksum_ok);
+        assert_eq!(bytes, None);
+    }
+
+    #[test_case]
+    fn the_madt_walk_stops_at_a_corrupt_entry() {
+        let cpu = [MADT_LOCAL_APIC, 8, 0, 3, 1, 0, 0, 0];
+        // A CPU, then an IO-APIC entry running past the end
+        let parsed = parse_madt(&madt(&[&cpu[..], &[MADT_IO_APIC, 12, 1, 0]].concat())).unwrap();
+        assert_eq!(parsed.lapic_phys, 0xFEE0_0000);
+        assert!(parsed.has_8259);
+        assert_eq!(parsed.enabled_apic_ids().collect::<Vec<_>>(), [3]);
+        assert!(parsed.io_apics.is_empty());
+
+        // A zero-length entry ends the walk instead of spinning on it
+        let parsed = parse_madt(&madt(&[&[MADT_OVERRIDE, 0], &cpu[..]].concat()));
+        assert!(parsed.unwrap().processors.is_empty());
+
+        // Too short for the fields every parser reads
+        assert!(parse_madt(&table(b"APIC", &[0; 4])).is_none());
+        assert!(parse_fadt(&table(b"FACP", &[0; 20])).is_none());
+        assert!(parse_hpet(&table(b"HPET", &[0; 10])).is_none());
+    }
+}
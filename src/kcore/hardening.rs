@@ -0,0 +1,130 @@
+//! # SMEP and SMAP
+//!
+//! With SMEP (CR4 bit 20) the CPU refuses to run user pages in ring 0, and
+//! with SMAP (CR4 bit 21) it refuses ring 0 reads and writes of user
+//! pages unless RFLAGS.AC is set. `init` turns on whichever CPUID leaf 7
+//! reports and logs the rest as skipped; the APs inherit CR4 from the BSP
+//! through the SMP trampoline.
+//!
+//! Code that means to touch user memory does it inside `user_access`,
+//! which sets AC with `stac` for the closure and clears it with `clac`
+//! after. Both instructions are #UD on a CPU without SMAP, so they are
+//! only issued once SMAP is on. Syscall handlers reach user memory only
+//! through the copies in `syscalls::user`, which are the sole callers.
+//! Nothing is mapped user-accessible yet, so today this guards those
+//! paths for when usermode arrives.
+
+use alloc::{format, string::String};
+use core::arch::x86_64::__cpuid;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::{
+    instructions::smap::Smap,
+    registers::control::{Cr4, Cr4Flags},
+};
+
+static SMEP_ON: AtomicBool = AtomicBool::new(false);
+static SMAP_ON: AtomicBool = AtomicBool::new(false);
+
+/// `(smep, smap)` as CPUID leaf 7 reports them.
+pub fn supported() -> (bool, bool) {
+    // Leaf 7 is only meaningful if the highest basic leaf reaches it.
+    if __cpuid(0).eax < 7 {
+        return (false, false);
+    }
+    let ebx = __cpuid(7).ebx;
+    (ebx & (1 << 7) != 0, ebx & (1 << 20) != 0)
+}
+
+pub fn smep_active() -> bool {
+    SMEP_ON.load(Ordering::Relaxed)
+}
+
+pub fn smap_active() -> bool {
+    SMAP_ON.load(Ordering::Relaxed)
+}
+
+/// Enables what the CPU supports. Missing support is not an error.
+pub fn init() -> Result<(), &'static str> {
+    let (smep, smap) = supported();
+    let mut flags = Cr4Flags::empty();
+    if smep {
+        flags |= Cr4Flags::SUPERVISOR_MODE_EXECUTION_PROTECTION;
+    }
+    if smap {
+        flags |= Cr4Flags::SUPERVISOR_MODE_ACCESS_PREVENTION;
+    }
+    // Safety: no page is user-accessible, so nothing the kernel runs or
+    // touches is refused by either bit.
+    unsafe { Cr4::update(|cr4| cr4.insert(flags)) };
+    SMEP_ON.store(smep, Ordering::Relaxed);
+    SMAP_ON.store(smap, Ordering::Relaxed);
+
+    for (name, on) in [("SMEP", smep), ("SMAP", smap)] {
+        if on {
+            crate::log_info!("hardening: {} enabled", name);
+        } else {
+            crate::log_warn!("hardening: {} not supported by this CPU, skipped", name);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `f` with SMAP checks lifted, for deliberate access to user memory.
+/// Without SMAP on, just runs `f`.
+pub fn user_access<R>(f: impl FnOnce() -> R) -> R {
+    if !smap_active() {
+        return f();
+    }
+    // Safety: SMAP is on, so CPUID reported it and `stac`/`clac` exist.
+    let smap = unsafe { Smap::new_unchecked() };
+    smap.without_smap(f)
+}
+
+/// Lines for `regs`.
+pub fn report() -> String {
+    let (smep, smap) = supported();
+    let state = |active: bool, supported: bool| match (active, supported) {
+        (true, _) => "active",
+        (false, true) => "supported, off",
+        (false, false) => "not supported",
+    };
+    format!(
+        "SMEP    {}\nSMAP    {}\n",
+        state(smep_active(), smep),
+        state(smap_active(), smap)
+    )
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn user_copies_work_with_whatever_is_enabled() {
+        // Boot ran `init`: CR4 agrees with what it recorded
+        let cr4 = Cr4::read();
+        assert_eq!(
+            cr4.contains(Cr4Flags::SUPERVISOR_MODE_EXECUTION_PROTECTION),
+            smep_active()
+        );
+        assert_eq!(
+            cr4.contains(Cr4Flags::SUPERVISOR_MODE_ACCESS_PREVENTION),
+            smap_active()
+        );
+
+        // On the heap, which is in the lower half that `copy_to_user` takes
+        let mut word = alloc::boxed::Box::new(0u64);
+        let copied = crate::syscalls::user::copy_to_user(
+            &mut *word as *mut u64 as *mut u8,
+            &0x1234_5678u64.to_ne_bytes(),
+        );
+        assert!(copied.is_ok());
+        assert_eq!(*word, 0x1234_5678);
+        // AC is clear again afterwards
+        assert!(!x86_64::registers::rflags::read()
+            .contains(x86_64::registers::rflags::RFlags::ALIGNMENT_CHECK));
+        assert!(report().contains("SMAP"));
+    }
+}
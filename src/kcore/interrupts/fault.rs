@@ -0,0 +1,251 @@
+//! # Fault Test
+//!
+//! Backs the `fault` command: raises one exception on purpose so its
+//! handler and diagnostics can be checked without losing the kernel.
+//!
+//! Before the faulting instruction, `trigger` arms a recovery with that
+//! instruction's address and length. A handler whose fault is at the armed
+//! address prints its usual diagnostics, moves RIP past the instruction and
+//! returns instead of panicking. Any other fault, including one on another
+//! CPU while a recovery is armed, is handled as before. The faulting
+//! instructions pin their registers so their encodings, and so their
+//! lengths, are fixed.
+
+use core::arch::asm;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    DivideByZero,
+    InvalidOpcode,
+    Breakpoint,
+    PageFault,
+    GeneralProtection,
+}
+
+impl FaultKind {
+    pub const ALL: [FaultKind; 5] = [
+        FaultKind::DivideByZero,
+        FaultKind::InvalidOpcode,
+        FaultKind::Breakpoint,
+        FaultKind::PageFault,
+        FaultKind::GeneralProtection,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FaultKind::DivideByZero => "divide",
+            FaultKind::InvalidOpcode => "ud2",
+            FaultKind::Breakpoint => "int3",
+            FaultKind::PageFault => "page",
+            FaultKind::GeneralProtection => "gp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn vector(self) -> u8 {
+        match self {
+            FaultKind::DivideByZero => 0,
+            FaultKind::Breakpoint => 3,
+            FaultKind::InvalidOpcode => 6,
+            FaultKind::GeneralProtection => 13,
+            FaultKind::PageFault => 14,
+        }
+    }
+}
+
+/// Bytes to step over at the armed instruction; 0 when nothing is armed.
+static SKIP: AtomicU64 = AtomicU64::new(0);
+/// Address of the armed instruction, stored by the `arm!` prologue.
+static ARMED_AT: AtomicU64 = AtomicU64::new(0);
+/// Vector of the last fault a handler recovered from or noted, or `NONE`.
+static SEEN: AtomicU8 = AtomicU8::new(NONE);
+static SEEN_RIP: AtomicU64 = AtomicU64::new(0);
+const NONE: u8 = u8::MAX;
+
+/// An unmapped, canonical address for the page fault; the null page.
+const UNMAPPED: u64 = 0;
+/// Bit 63 set with bit 47 clear: non-canonical, so a load raises #GP.
+const NON_CANONICAL: u64 = 0x8000_0000_0000_0000;
+
+/// What `trigger` saw come back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovered {
+    pub vector: u8,
+    pub rip: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultError {
+    /// The test address turned out to be mapped.
+    Mapped(u64),
+    /// The instruction ran without faulting.
+    NoFault,
+}
+
+/// Whether the fault being handled, at `sf`'s RIP, was raised by
+/// `trigger`.
+pub(super) fn armed(sf: &InterruptStackFrame) -> bool {
+    SKIP.load(Ordering::Acquire) != 0
+        && ARMED_AT.load(Ordering::Acquire) == sf.instruction_pointer.as_u64()
+}
+
+/// Called by a handler instead of panicking when `armed`: moves RIP past
+/// the faulting instruction, so the handler can return.
+pub(super) fn recover(vector: u8, sf: &mut InterruptStackFrame) {
+    let skip = SKIP.swap(0, Ordering::AcqRel);
+    ARMED_AT.store(0, Ordering::Release);
+    note(vector, sf.instruction_pointer.as_u64());
+    crate::println!("fault test: recovered, skipping {} bytes", skip);
+    // Safety: `trigger` armed `skip` as the length of the instruction that
+    // faulted, so RIP lands on the next one.
+    unsafe {
+        sf.as_mut()
+            .update(|frame| frame.instruction_pointer += skip);
+    }
+}
+
+/// Records a fault whose handler returns on its own (the breakpoint).
+pub(super) fn note(vector: u8, rip: u64) {
+    SEEN_RIP.store(rip, Ordering::Relaxed);
+    SEEN.store(vector, Ordering::Release);
+}
+
+/// Asm stored ahead of a faulting instruction: writes that instruction's
+/// address (label `2`, placed right before it) through `{slot}`.
+macro_rules! arm {
+    () => {
+        "lea {at}, [rip + 2f]\nmov qword ptr [{slot}], {at}\n2:"
+    };
+}
+
+/// Raises `kind` and returns once its handler has recovered.
+pub fn trigger(kind: FaultKind) -> Result<Recovered, FaultError> {
+    if kind == FaultKind::PageFault && is_mapped(UNMAPPED) {
+        return Err(FaultError::Mapped(UNMAPPED));
+    }
+    SEEN.store(NONE, Ordering::Release);
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        // Safety: each instruction below faults and its handler steps over
+        // it by the armed length, or (int3) traps past it; registers are
+        // pinned so the lengths hold.
+        unsafe {
+            match kind {
+                FaultKind::DivideByZero => {
+                    SKIP.store(2, Ordering::Release);
+                    // div ecx: F7 F1
+                    asm!(
+                        arm!(),
+                        "div ecx",
+                        slot = in(reg) ARMED_AT.as_ptr(),
+                        at = out(reg) _,
+                        in("ecx") 0u32,
+                        inout("eax") 1u32 => _,
+                        inout("edx") 0u32 => _,
+                        options(nostack),
+                    );
+                }
+                FaultKind::InvalidOpcode => {
+                    SKIP.store(2, Ordering::Release);
+                    // ud2: 0F 0B
+                    asm!(
+                        arm!(),
+                        "ud2",
+                        slot = in(reg) ARMED_AT.as_ptr(),
+                        at = out(reg) _,
+                        options(nostack),
+                    );
+                }
+                FaultKind::Breakpoint => asm!("int3", options(nostack)),
+                FaultKind::PageFault | FaultKind::GeneralProtection => {
+                    let addr = match kind {
+                        FaultKind::PageFault => UNMAPPED,
+                        _ => NON_CANONICAL,
+                    };
+                    SKIP.store(2, Ordering::Release);
+                    // mov al, [rcx]: 8A 01
+                    asm!(
+                        arm!(),
+                        "mov al, byte ptr [rcx]",
+                        slot = in(reg) ARMED_AT.as_ptr(),
+                        at = out(reg) _,
+                        in("rcx") addr,
+                        out("al") _,
+                        options(nostack),
+                    );
+                }
+            }
+        }
+        SKIP.store(0, Ordering::Release);
+        ARMED_AT.store(0, Ordering::Release);
+    });
+
+    match SEEN.load(Ordering::Acquire) {
+        vector if vector == kind.vector() => Ok(Recovered {
+            vector,
+            rip: SEEN_RIP.load(Ordering::Relaxed),
+        }),
+        _ => Err(FaultError::NoFault),
+    }
+}
+
+fn is_mapped(addr: u64) -> bool {
+    use x86_64::structures::paging::Translate;
+    // Safety: only reads the active tables.
+    let table = unsafe { crate::memory::active_page_table() };
+    table.translate_addr(x86_64::VirtAddr::new(addr)).is_some()
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn handlers_recover_from_armed_faults() {
+        for kind in FaultKind::ALL {
+            assert_eq!(FaultKind::from_name(kind.name()), Some(kind));
+            let recovered = match trigger(kind) {
+                // Nothing to fault on where the null page is mapped.
+                Err(FaultError::Mapped(_)) => continue,
+                result => result.unwrap(),
+            };
+            assert_eq!(recovered.vector, kind.vector(), "{}", kind.name());
+        }
+        assert_eq!(SKIP.load(Ordering::Relaxed), 0);
+        assert_eq!(ARMED_AT.load(Ordering::Relaxed), 0);
+    }
+
+    #[test_case]
+    fn only_the_armed_instruction_is_stepped_over() {
+        use x86_64::registers::rflags::RFlags;
+        use x86_64::structures::gdt::SegmentSelector;
+        use x86_64::{PrivilegeLevel, VirtAddr};
+
+        let at = |rip: u64| {
+            let cs = SegmentSelector::new(1, PrivilegeLevel::Ring0);
+            let sf = InterruptStackFrame::new(
+                VirtAddr::new(rip),
+                cs,
+                RFlags::empty(),
+                VirtAddr::new(0),
+                cs,
+            );
+            armed(&sf)
+        };
+        assert!(!at(0x1000));
+        SKIP.store(2, Ordering::Release);
+        ARMED_AT.store(0x1000, Ordering::Release);
+        // A fault elsewhere, as on another CPU, is not the armed one
+        assert!(!at(0x2000));
+        assert!(at(0x1000));
+        SKIP.store(0, Ordering::Release);
+        ARMED_AT.store(0, Ordering::Release);
+    }
+}
@@ -6,7 +6,11 @@
 //! 1. Load the GDT
 //! 2. Set segment registers (CS, DS, ES, SS)
 //! 3. Load the TSS
+//!
+//! Application processors get their own GDT and TSS from `new_cpu_tables`:
+//! loading a TSS marks its descriptor busy, so the BSP's cannot be shared.
 
+use alloc::{boxed::Box, vec};
 use spin::Lazy;
 use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
 use x86_64::structures::tss::TaskStateSegment;
@@ -51,7 +55,52 @@ static GDT: Lazy<(GlobalDescriptorTable, Selectors)> = Lazy::new(|| {
 });
 
 pub fn init() {
+    let stack = &raw const DOUBLE_FAULT_STACK as usize;
+    crate::stats::stacks::track("cpu0 #DF", stack, 4096);
     let (ref gdt, ref selectors) = *GDT;
+    load(gdt, selectors);
+}
+
+/// GDT and TSS of one application processor.
+pub struct CpuTables {
+    gdt: GlobalDescriptorTable,
+    selectors: Selectors,
+}
+
+/// Builds tables for CPU number `cpu`, with its own double-fault stack.
+/// They are leaked: CPUs are never taken offline.
+pub fn new_cpu_tables(cpu: usize) -> &'static CpuTables {
+    let stack: &'static mut [u8] = vec![0u8; 4096].leak();
+    crate::stats::stacks::track(
+        &alloc::format!("cpu{} #DF", cpu),
+        stack.as_ptr() as usize,
+        stack.len(),
+    );
+    let mut tss = TaskStateSegment::new();
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+        VirtAddr::new(stack.as_ptr() as u64 + stack.len() as u64);
+    let tss: &'static TaskStateSegment = Box::leak(Box::new(tss));
+
+    let mut gdt = GlobalDescriptorTable::new();
+    let code_selector = gdt.append(Descriptor::kernel_code_segment());
+    let data_selector = gdt.append(Descriptor::kernel_data_segment());
+    let tss_selector = gdt.append(Descriptor::tss_segment(tss));
+    Box::leak(Box::new(CpuTables {
+        gdt,
+        selectors: Selectors {
+            code_selector,
+            data_selector,
+            tss_selector,
+        },
+    }))
+}
+
+/// Loads tables from `new_cpu_tables` on the CPU running this.
+pub fn init_cpu(tables: &'static CpuTables) {
+    load(&tables.gdt, &tables.selectors);
+}
+
+fn load(gdt: &'static GlobalDescriptorTable, selectors: &Selectors) {
     gdt.load();
 
     unsafe {
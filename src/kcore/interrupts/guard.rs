@@ -0,0 +1,252 @@
+//! # Fault Policy and Guarded Calls
+//!
+//! Loaded programs (`pstart` images, the ASM tests, the surface demo) run
+//! in ring 0 on the kernel stack, so a fault in one of them reaches the
+//! same handlers as a kernel bug. The policy decides what happens then:
+//!
+//! - `Strict` (the default): every fault panics, as it always has.
+//! - `Lenient`: a fault whose RIP is inside the code of a program started
+//!   with `call` ends that program instead. `call` returns the fault and
+//!   the kernel carries on. Faults anywhere else still panic, since
+//!   kernel state at that point (locks, half-done updates) cannot be
+//!   trusted.
+//!
+//! `call` is a small setjmp: `guard_call` saves the callee-saved registers,
+//! records its stack pointer in `GUARD_RSP` and calls the program. To end
+//! the program, the handler rewrites the interrupt frame so `iretq` resumes
+//! at `guard_landing` on that stack. The landing pad pops what
+//! `guard_call` pushed and returns to Rust with the fault flag set. A
+//! program that faults in a kernel function it was handed (a syscall, say)
+//! is a kernel-origin fault and is not recovered.
+//!
+//! One guarded call runs at a time; `call` made while one is running calls
+//! the program unguarded. Double faults are never recovered, whatever the
+//! policy.
+
+use core::arch::global_asm;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use x86_64::{structures::idt::InterruptStackFrame, PrivilegeLevel, VirtAddr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPolicy {
+    Strict,
+    Lenient,
+}
+
+impl FaultPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            FaultPolicy::Strict => "strict",
+            FaultPolicy::Lenient => "lenient",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [FaultPolicy::Strict, FaultPolicy::Lenient]
+            .into_iter()
+            .find(|policy| policy.name() == name)
+    }
+}
+
+/// Where a fault came from, by the saved CS and RIP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    Kernel,
+    /// Inside the code of the program `call` is running.
+    Program,
+    /// Ring 3. Nothing runs there yet.
+    User,
+}
+
+impl Origin {
+    pub fn name(self) -> &'static str {
+        match self {
+            Origin::Kernel => "kernel",
+            Origin::Program => "program",
+            Origin::User => "user",
+        }
+    }
+}
+
+/// A program ended by a fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramFault {
+    pub vector: u8,
+    pub rip: u64,
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(FaultPolicy::Strict as u8);
+/// `guard_call`'s stack pointer while a guarded call runs; 0 otherwise.
+static GUARD_RSP: AtomicU64 = AtomicU64::new(0);
+static CODE_START: AtomicU64 = AtomicU64::new(0);
+static CODE_END: AtomicU64 = AtomicU64::new(0);
+static FAULT_VECTOR: AtomicU8 = AtomicU8::new(0);
+static FAULT_RIP: AtomicU64 = AtomicU64::new(0);
+
+pub fn policy() -> FaultPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        0 => FaultPolicy::Strict,
+        _ => FaultPolicy::Lenient,
+    }
+}
+
+pub fn set_policy(policy: FaultPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Returned in RAX:RDX by `guard_call`.
+#[repr(C)]
+struct Outcome {
+    value: u64,
+    faulted: u64,
+}
+
+extern "C" {
+    fn guard_call(a0: u64, a1: u64, a2: u64, a3: u64, entry: u64) -> Outcome;
+    fn guard_landing();
+}
+
+// guard_call(a0..a3 in rdi, rsi, rdx, rcx; entry in r8). Six pushes and
+// the `sub` keep the stack 16-byte aligned at the program's entry.
+global_asm!(
+    ".global guard_call",
+    "guard_call:",
+    "push rbp",
+    "push rbx",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "sub rsp, 8",
+    "mov [rip + {rsp}], rsp",
+    "call r8",
+    "xor edx, edx",
+    "jmp 2f",
+    ".global guard_landing",
+    "guard_landing:",
+    "cld",
+    "xor eax, eax",
+    "mov edx, 1",
+    "2:",
+    "mov qword ptr [rip + {rsp}], 0",
+    "add rsp, 8",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbx",
+    "pop rbp",
+    "ret",
+    rsp = sym GUARD_RSP,
+);
+
+/// Calls the program at `entry` with up to four integer arguments, its
+/// code occupying `code`. Under the lenient policy a fault in that code
+/// ends it and comes back as the error.
+///
+/// # Safety
+/// `entry` must be executable code following the C calling convention
+/// with at most four integer arguments, and `code` must cover it.
+pub unsafe fn call(entry: u64, code: Range<u64>, args: [u64; 4]) -> Result<u64, ProgramFault> {
+    if GUARD_RSP.load(Ordering::Acquire) != 0 {
+        let program: extern "C" fn(u64, u64, u64, u64) -> u64 = core::mem::transmute(entry);
+        return Ok(program(args[0], args[1], args[2], args[3]));
+    }
+    CODE_START.store(code.start, Ordering::Relaxed);
+    CODE_END.store(code.end, Ordering::Relaxed);
+    let outcome = guard_call(args[0], args[1], args[2], args[3], entry);
+    CODE_END.store(0, Ordering::Relaxed);
+    if outcome.faulted == 0 {
+        return Ok(outcome.value);
+    }
+    Err(ProgramFault {
+        vector: FAULT_VECTOR.load(Ordering::Relaxed),
+        rip: FAULT_RIP.load(Ordering::Relaxed),
+    })
+}
+
+pub fn origin(sf: &InterruptStackFrame) -> Origin {
+    let rip = sf.instruction_pointer.as_u64();
+    if sf.code_segment.rpl() == PrivilegeLevel::Ring3 {
+        Origin::User
+    } else if GUARD_RSP.load(Ordering::Acquire) != 0
+        && (CODE_START.load(Ordering::Relaxed)..CODE_END.load(Ordering::Relaxed)).contains(&rip)
+    {
+        Origin::Program
+    } else {
+        Origin::Kernel
+    }
+}
+
+/// Called by an exception handler before it panics. Logs where the fault
+/// came from; under the lenient policy, a program fault is redirected to
+/// `guard_landing` and this returns true, so the handler returns.
+pub(super) fn end_program(vector: u8, sf: &mut InterruptStackFrame, error: Option<u64>) -> bool {
+    let rip = sf.instruction_pointer.as_u64();
+    let origin = origin(sf);
+    let policy = policy();
+    crate::println!(
+        "fault: vector {} at {:#x} (rsp {:#x}, error {:?}) from {} code, {} policy",
+        vector,
+        rip,
+        sf.stack_pointer.as_u64(),
+        error,
+        origin.name(),
+        policy.name()
+    );
+    let rsp = GUARD_RSP.load(Ordering::Acquire);
+    if policy != FaultPolicy::Lenient || origin == Origin::Kernel || rsp == 0 {
+        return false;
+    }
+    FAULT_VECTOR.store(vector, Ordering::Relaxed);
+    FAULT_RIP.store(rip, Ordering::Relaxed);
+    crate::println!("fault: program ended, back to its caller");
+    // Safety: `guard_call` is still on the stack at `rsp` (the guard is
+    // set), and `guard_landing` unwinds exactly what it pushed.
+    unsafe {
+        sf.as_mut().update(|frame| {
+            frame.instruction_pointer = VirtAddr::new(guard_landing as *const () as u64);
+            frame.stack_pointer = VirtAddr::new(rsp);
+        });
+    }
+    true
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ud2; ret`, then `mov al, [rdi]; ret`, then `lea rax, [rdi + rsi]; ret`.
+    const UD2: &[u8] = &[0x0f, 0x0b, 0xc3];
+    const LOAD: &[u8] = &[0x8a, 0x07, 0xc3];
+    const ADD: &[u8] = &[0x48, 0x8d, 0x04, 0x37, 0xc3];
+
+    fn run(code: &[u8], args: [u64; 4]) -> Result<u64, ProgramFault> {
+        let process =
+            unsafe { crate::memory::sys_pstart(code.as_ptr(), code.len(), &[]) }.unwrap();
+        let range = process.base..process.base + process.size as u64;
+        let ended = unsafe { call(process.base, range, args) };
+        crate::syscalls::handlers::process::release(process.pid);
+        ended
+    }
+
+    #[test_case]
+    fn lenient_policy_ends_only_the_faulting_program() {
+        assert_eq!(run(ADD, [40, 2, 0, 0]), Ok(42));
+
+        set_policy(FaultPolicy::Lenient);
+        let ud2 = run(UD2, [0; 4]);
+        let gp = run(LOAD, [0x8000_0000_0000_0000, 0, 0, 0]);
+        set_policy(FaultPolicy::Strict);
+
+        assert_eq!(ud2.map_err(|f| f.vector), Err(6));
+        assert_eq!(gp.map_err(|f| f.vector), Err(13));
+        // The guard is down and the next program runs normally
+        assert_eq!(GUARD_RSP.load(Ordering::Relaxed), 0);
+        assert_eq!(run(ADD, [1, 2, 0, 0]), Ok(3));
+        assert_eq!(FaultPolicy::from_name("lenient"), Some(FaultPolicy::Lenient));
+    }
+}
@@ -4,11 +4,11 @@
 
 use crate::{
     kcore::interrupts::{
-        gdt,
+        fault, gdt, guard, lapic,
         pic::{handle_interrupt, EoiTiming, InterruptIndex},
     },
     println,
-    syscalls::dispatcher::SyscallContext,
+    syscalls::{dispatcher::SyscallContext, policy::Origin},
 };
 use core::sync::atomic::{AtomicU64, Ordering};
 use spin::Lazy;
@@ -40,6 +40,7 @@ static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
     idt[InterruptIndex::Keyboard.as_u8()].set_handler_fn(keyboard_interrupt_handler);
     idt[InterruptIndex::Mouse.as_u8()].set_handler_fn(mouse_interrupt_handler);
     idt[InterruptIndex::Syscall.as_u8()].set_handler_fn(syscall_handler);
+    idt[lapic::SPURIOUS_VECTOR].set_handler_fn(spurious_interrupt_handler);
 
     idt
 });
@@ -50,17 +51,45 @@ pub fn init_idt() {
 
 extern "x86-interrupt" fn breakpoint_handler(sf: InterruptStackFrame) {
     println!("EXCEPTION: BREAKPOINT\n{:#?}", sf);
+    fault::note(3, sf.instruction_pointer.as_u64());
 }
 
-extern "x86-interrupt" fn divide_error_handler(sf: InterruptStackFrame) {
+// The handlers below return only for the `fault` command (see `fault`), or
+// to end a guarded program under the lenient policy (see `guard`).
+
+extern "x86-interrupt" fn divide_error_handler(mut sf: InterruptStackFrame) {
+    if fault::armed(&sf) {
+        println!("EXCEPTION: DIVIDE BY ZERO\n{:#?}", sf);
+        return fault::recover(0, &mut sf);
+    }
+    if guard::end_program(0, &mut sf, None) {
+        return;
+    }
     panic!("EXCEPTION: DIVIDE BY ZERO\n{:#?}", sf);
 }
 
-extern "x86-interrupt" fn invalid_opcode_handler(sf: InterruptStackFrame) {
+extern "x86-interrupt" fn invalid_opcode_handler(mut sf: InterruptStackFrame) {
+    if fault::armed(&sf) {
+        println!("EXCEPTION: INVALID OPCODE\n{:#?}", sf);
+        return fault::recover(6, &mut sf);
+    }
+    if guard::end_program(6, &mut sf, None) {
+        return;
+    }
     panic!("EXCEPTION: INVALID OPCODE\n{:#?}", sf);
 }
 
-extern "x86-interrupt" fn general_protection_fault_handler(sf: InterruptStackFrame, err: u64) {
+extern "x86-interrupt" fn general_protection_fault_handler(mut sf: InterruptStackFrame, err: u64) {
+    if fault::armed(&sf) {
+        println!(
+            "EXCEPTION: GENERAL PROTECTION FAULT (error code: {})\n{:#?}",
+            err, sf
+        );
+        return fault::recover(13, &mut sf);
+    }
+    if guard::end_program(13, &mut sf, Some(err)) {
+        return;
+    }
     panic!(
         "EXCEPTION: GENERAL PROTECTION FAULT (error code: {})\n{:#?}",
         err, sf
@@ -74,6 +103,12 @@ extern "x86-interrupt" fn double_fault_handler(
     crate::println!("DOUBLE FAULT!");
     crate::println!("  IP: {:#x}", frame.instruction_pointer);
     crate::println!("  Stack: {:#x}", frame.stack_pointer);
+    // Never recovered: the faulting stack is gone
+    crate::println!(
+        "  From {} code, {} policy",
+        guard::origin(&frame).name(),
+        guard::policy().name()
+    );
 
     let cr2 = x86_64::registers::control::Cr2::read();
     crate::println!("  CR2: {:#x}", cr2.unwrap());
@@ -82,13 +117,19 @@ extern "x86-interrupt" fn double_fault_handler(
         unsafe { core::arch::x86_64::_mm_pause(); }
     }
 }
-extern "x86-interrupt" fn page_fault_handler(_sf: InterruptStackFrame, _err: PageFaultErrorCode) {
+extern "x86-interrupt" fn page_fault_handler(mut sf: InterruptStackFrame, _err: PageFaultErrorCode) {
     use x86_64::registers::control::Cr2;
     if let Ok(addr) = Cr2::read() {
         println!("PAGE FAULT! Address: {:#x}  Error: {:?}  IP: {:#x}", 
-             addr, _err, _sf.instruction_pointer);
+             addr, _err, sf.instruction_pointer);
         crate::memory::debug::debug_page_walk(addr);
     };
+    if fault::armed(&sf) {
+        return fault::recover(14, &mut sf);
+    }
+    if guard::end_program(14, &mut sf, Some(_err.bits())) {
+        return;
+    }
     panic!("Page fault!");
 }
 
@@ -97,6 +138,7 @@ extern "x86-interrupt" fn timer_interrupt_handler(_sf: InterruptStackFrame) {
         InterruptIndex::Timer,
         || {
             TIMER_TICKS.fetch_add(1, Ordering::Relaxed);
+            crate::util::rand::add_jitter(crate::stats::latency::rdtsc());
         },
         EoiTiming::After,
     );
@@ -114,6 +156,7 @@ extern "x86-interrupt" fn syscall_handler(sf: InterruptStackFrame) {
                 arg3: 0,
                 arg4: 0,
                 arg5: 0,
+                origin: Origin::from_cpl(sf.code_segment.rpl()),
             });
         },
         EoiTiming::Before,
@@ -154,3 +197,6 @@ extern "x86-interrupt" fn mouse_interrupt_handler(_sf: InterruptStackFrame) {
         EoiTiming::After,
     );
 }
+
+/// Spurious local APIC interrupts take no EOI.
+extern "x86-interrupt" fn spurious_interrupt_handler(_sf: InterruptStackFrame) {}
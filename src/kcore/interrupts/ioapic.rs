@@ -0,0 +1,213 @@
+//! # IO-APIC
+//!
+//! Routes the legacy ISA IRQs the kernel uses (timer, keyboard, mouse) to
+//! the bootstrap CPU's local APIC, replacing the 8259 PIC. The vectors stay
+//! the ones the PIC used, so the IDT does not change; only the EOI path in
+//! `pic::eoi` switches to the local APIC.
+//!
+//! ## Routing
+//!
+//! An ISA IRQ maps to the global system interrupt (GSI) of the same number
+//! unless the MADT has an interrupt source override for it (QEMU, for
+//! example, wires the PIT to GSI 2). Overrides may also change polarity and
+//! trigger mode; ISA defaults are active-high, edge-triggered.
+//!
+//! ## Fallback
+//!
+//! Without an IO-APIC in the MADT, or with the `legacy-pic` feature, `init`
+//! fails and the PIC stays in charge.
+
+use crate::kcore::acpi::{self, InterruptOverride};
+use crate::kcore::interrupts::{
+    lapic,
+    pic::{self, InterruptIndex},
+};
+use crate::memory::{map_mmio, MemError};
+use alloc::{format, string::String, vec::Vec};
+use spin::Once;
+use x86_64::{PhysAddr, VirtAddr};
+
+const REG_SELECT: usize = 0x00;
+const REG_WINDOW: usize = 0x10;
+const REG_VERSION: u32 = 0x01;
+const REG_REDIRECTION: u32 = 0x10;
+
+const ENTRY_ACTIVE_LOW: u32 = 1 << 13;
+const ENTRY_LEVEL: u32 = 1 << 15;
+const ENTRY_MASKED: u32 = 1 << 16;
+
+/// ISA IRQs routed at init, with the vectors the PIC delivered them on.
+const ROUTES: [(u8, InterruptIndex); 3] = [
+    (0, InterruptIndex::Timer),
+    (1, InterruptIndex::Keyboard),
+    (12, InterruptIndex::Mouse),
+];
+
+static IO_APICS: Once<Vec<IoApic>> = Once::new();
+static ROUTED: Once<Vec<Route>> = Once::new();
+
+pub struct IoApic {
+    base: VirtAddr,
+    pub id: u8,
+    pub gsi_base: u32,
+    /// Number of redirection entries.
+    pub inputs: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Route {
+    irq: u8,
+    gsi: u32,
+    vector: u8,
+    flags: u32,
+}
+
+impl IoApic {
+    /// Maps the register window at `phys`.
+    pub fn map(id: u8, phys: u64, gsi_base: u32) -> Result<Self, MemError> {
+        let base = map_mmio(PhysAddr::new(phys), 4096)?;
+        let mut ioapic = Self {
+            base,
+            id,
+            gsi_base,
+            inputs: 0,
+        };
+        ioapic.inputs = ((ioapic.read(REG_VERSION) >> 16) & 0xFF) + 1;
+        Ok(ioapic)
+    }
+
+    fn read(&self, reg: u32) -> u32 {
+        let base = self.base.as_u64() as usize;
+        unsafe {
+            core::ptr::write_volatile((base + REG_SELECT) as *mut u32, reg);
+            core::ptr::read_volatile((base + REG_WINDOW) as *const u32)
+        }
+    }
+
+    fn write(&self, reg: u32, value: u32) {
+        let base = self.base.as_u64() as usize;
+        unsafe {
+            core::ptr::write_volatile((base + REG_SELECT) as *mut u32, reg);
+            core::ptr::write_volatile((base + REG_WINDOW) as *mut u32, value);
+        }
+    }
+
+    pub fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi < self.gsi_base + self.inputs
+    }
+
+    /// Sends `gsi` to `vector` on the CPU with `apic_id`, fixed delivery.
+    /// `flags` carries the polarity and trigger bits.
+    fn route(&self, gsi: u32, vector: u8, apic_id: u8, flags: u32) {
+        let reg = REG_REDIRECTION + 2 * (gsi - self.gsi_base);
+        self.write(reg, ENTRY_MASKED);
+        self.write(reg + 1, (apic_id as u32) << 24);
+        self.write(reg, vector as u32 | flags);
+    }
+
+    fn mask_all(&self) {
+        for input in 0..self.inputs {
+            self.write(REG_REDIRECTION + 2 * input, ENTRY_MASKED);
+        }
+    }
+}
+
+/// Polarity and trigger bits for a redirection entry from MPS INTI flags.
+fn entry_flags(source: Option<&InterruptOverride>) -> u32 {
+    let Some(o) = source else {
+        return 0;
+    };
+    let mut flags = 0;
+    if o.flags & 0b11 == 0b11 {
+        flags |= ENTRY_ACTIVE_LOW;
+    }
+    if (o.flags >> 2) & 0b11 == 0b11 {
+        flags |= ENTRY_LEVEL;
+    }
+    flags
+}
+
+/// Switches interrupt delivery from the PIC to the IO-APIC(s). Needs
+/// `acpi::init`; runs with interrupts off while the routes change.
+pub fn init() -> Result<(), &'static str> {
+    if cfg!(feature = "legacy-pic") {
+        return Err("disabled by the legacy-pic feature");
+    }
+    let madt = acpi::madt().ok_or("no MADT")?;
+    if madt.io_apics.is_empty() {
+        return Err("no IO-APIC in MADT");
+    }
+    let lapic = lapic::init(madt.lapic_phys).map_err(|_| "cannot map local APIC")?;
+
+    let mut ioapics = Vec::new();
+    for entry in &madt.io_apics {
+        let ioapic =
+            IoApic::map(entry.id, entry.phys, entry.gsi_base).map_err(|_| "cannot map IO-APIC")?;
+        ioapic.mask_all();
+        ioapics.push(ioapic);
+    }
+
+    let mut routes = Vec::new();
+    for (irq, index) in ROUTES {
+        let source = madt.overrides.iter().find(|o| o.irq == irq);
+        let gsi = source.map_or(irq as u32, |o| o.gsi);
+        if !ioapics.iter().any(|io| io.handles(gsi)) {
+            return Err("IRQ not wired to any IO-APIC");
+        }
+        routes.push(Route {
+            irq,
+            gsi,
+            vector: index.as_u8(),
+            flags: entry_flags(source),
+        });
+    }
+
+    let bsp = lapic.id();
+    let ioapics = IO_APICS.call_once(|| ioapics);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        pic::mask_all();
+        for r in &routes {
+            if let Some(io) = ioapics.iter().find(|io| io.handles(r.gsi)) {
+                io.route(r.gsi, r.vector, bsp, r.flags);
+            }
+        }
+        pic::use_apic();
+    });
+    ROUTED.call_once(|| routes);
+    Ok(())
+}
+
+/// Controller and routes, for the `irq` command.
+pub fn report() -> String {
+    let Some(routes) = ROUTED.get() else {
+        return String::from("Interrupts: 8259 PIC (IO-APIC not in use)");
+    };
+    let mut out = String::from("Interrupts: IO-APIC -> local APIC\n");
+    for io in IO_APICS.get().into_iter().flatten() {
+        out.push_str(&format!(
+            "  ioapic {:<3} GSI {}-{}\n",
+            io.id,
+            io.gsi_base,
+            io.gsi_base + io.inputs - 1
+        ));
+    }
+    for r in routes {
+        out.push_str(&format!(
+            "  irq {:<2} -> GSI {:<3} vector {:<3} {} {}\n",
+            r.irq,
+            r.gsi,
+            r.vector,
+            if r.flags & ENTRY_LEVEL != 0 {
+                "level"
+            } else {
+                "edge "
+            },
+            if r.flags & ENTRY_ACTIVE_LOW != 0 {
+                "low"
+            } else {
+                "high"
+            }
+        ));
+    }
+    out
+}
@@ -0,0 +1,94 @@
+//! # Local APIC
+//!
+//! Minimal xAPIC driver: enough to identify the current CPU, acknowledge
+//! interrupts routed through the IO-APIC and send the INIT and STARTUP IPIs
+//! used by SMP bring-up.
+//!
+//! The register page is mapped uncached with `memory::map_mmio`; every
+//! register is a 32-bit value on a 16-byte boundary.
+
+use crate::memory::{map_mmio, MemError};
+use spin::Once;
+use x86_64::{PhysAddr, VirtAddr};
+
+const REG_ID: usize = 0x20;
+const REG_EOI: usize = 0xB0;
+const REG_SVR: usize = 0xF0;
+const REG_ICR_LOW: usize = 0x300;
+const REG_ICR_HIGH: usize = 0x310;
+
+const SVR_ENABLE: u32 = 1 << 8;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+const ICR_INIT: u32 = 0b101 << 8;
+const ICR_STARTUP: u32 = 0b110 << 8;
+
+/// Vector the local APIC reports spurious interrupts on. Its handler must
+/// not send an EOI.
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+pub static LAPIC: Once<LocalApic> = Once::new();
+
+/// Maps and enables the local APIC on first use; later calls return the
+/// same instance. The register page is at the same address on every CPU.
+pub fn init(phys: u64) -> Result<&'static LocalApic, MemError> {
+    if let Some(lapic) = LAPIC.get() {
+        return Ok(lapic);
+    }
+    let lapic = LocalApic::map(phys)?;
+    lapic.enable();
+    Ok(LAPIC.call_once(|| lapic))
+}
+
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    /// Maps the register page at `phys`.
+    pub fn map(phys: u64) -> Result<Self, MemError> {
+        let base = map_mmio(PhysAddr::new(phys), 4096)?;
+        Ok(Self { base })
+    }
+
+    fn read(&self, reg: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.base.as_u64() as usize + reg) as *const u32) }
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.base.as_u64() as usize + reg) as *mut u32, value) }
+    }
+
+    /// APIC ID of the CPU executing this.
+    pub fn id(&self) -> u8 {
+        (self.read(REG_ID) >> 24) as u8
+    }
+
+    /// Sets the software-enable bit and the spurious vector.
+    pub fn enable(&self) {
+        let svr = self.read(REG_SVR) & !0xFF;
+        self.write(REG_SVR, svr | SVR_ENABLE | SPURIOUS_VECTOR as u32);
+    }
+
+    /// Signals the end of the interrupt being serviced.
+    pub fn eoi(&self) {
+        self.write(REG_EOI, 0);
+    }
+
+    fn send_ipi(&self, apic_id: u8, command: u32) {
+        self.write(REG_ICR_HIGH, (apic_id as u32) << 24);
+        self.write(REG_ICR_LOW, command);
+        while self.read(REG_ICR_LOW) & ICR_DELIVERY_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    pub fn send_init(&self, apic_id: u8) {
+        self.send_ipi(apic_id, ICR_INIT | ICR_LEVEL_ASSERT);
+    }
+
+    /// STARTUP IPI: the target starts in real mode at `page << 12`.
+    pub fn send_startup(&self, apic_id: u8, page: u8) {
+        self.send_ipi(apic_id, ICR_STARTUP | ICR_LEVEL_ASSERT | page as u32);
+    }
+}
@@ -5,7 +5,11 @@
 //! - **GDT**: Global Descriptor Table with TSS for stack switching
 //! - **IDT**: Interrupt Descriptor Table with exception and hardware interrupt handlers
 //! - **PIC**: 8259 Programmable Interrupt Controller initialization and EOI
+//! - **Local APIC**: CPU identification, EOI and IPIs for SMP bring-up
+//! - **IO-APIC**: routes the ISA IRQs to the local APIC when the MADT has one
 //! - **Timer**: System timer tick tracking
+//! - **Guard**: the fault policy, and calls into loaded programs that a
+//!   fault can end without taking the kernel down
 //!
 //! ## Interrupt Vector Layout
 //!
@@ -16,24 +20,25 @@
 //! | 33     | Keyboard (IRQ1)        | keyboard_interrupt_handler |
 //! | 44     | Mouse (IRQ12)          | mouse_interrupt_handler    |
 //! | 0x80   | Syscall                | syscall_handler            |
+//! | 0xFF   | Local APIC spurious    | spurious_interrupt_handler |
 //!
 //! ## Usage
 //!
+//! The GDT, IDT and PIC are separate boot stages (see `kernel::init`), set
+//! up in that order:
+//!
 //! ```ignore
-//! use crate::core::interrupts;
-//! interrupts::init(); // Initializes GDT, IDT, PIC
+//! gdt::init();
+//! interrupts::init_idt();
+//! pic::remap();
 //! x86_64::instructions::interrupts::enable();
 //! ```
 
-use crate::kcore::interrupts::interrupts::init_idt;
-
+pub mod fault;
 pub mod gdt;
+pub mod guard;
 pub mod interrupts;
+pub mod ioapic;
+pub mod lapic;
 pub mod pic;
-mod timer;
-
-pub fn init() {
-    gdt::init();
-    pic::remap();
-    init_idt();
-}
+pub mod timer;
@@ -29,10 +29,27 @@
 //! | 0   | 32     | Timer     |
 //! | 1   | 33     | Keyboard  |
 //! | 12  | 44     | Mouse     |
+//!
+//! ## IO-APIC Mode
+//!
+//! Once `ioapic::init` has taken over routing, both PICs are fully masked
+//! and `eoi` acknowledges through the local APIC instead.
 
 //! PIC (Programmable Interrupt Controller) remapping
+use crate::kcore::smp::{percpu::PerCpu, MAX_CPUS};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use x86_64::instructions::port::Port;
 
+/// Nesting depth of `handle_interrupt` on each CPU; non-zero while that
+/// CPU is in IRQ context.
+static IRQ_DEPTH: PerCpu<AtomicUsize> = PerCpu::new([const { AtomicUsize::new(0) }; MAX_CPUS]);
+/// Set by `ioapic::init` once the IO-APIC delivers the hardware IRQs.
+static APIC_MODE: AtomicBool = AtomicBool::new(false);
+
+pub fn in_interrupt() -> bool {
+    IRQ_DEPTH.this_cpu().load(Ordering::Relaxed) != 0
+}
+
 /// Remap PIC controllers so IRQs start at offsets 0x20 and 0x28.
 /// Call this during interrupt subsystem init before unmasking IRQs.
 pub fn remap() {
@@ -72,7 +89,45 @@ pub const PIC_1_OFFSET: u8 = 32; // Primary PIC handles IRQs 0-7
 pub const PIC_2_OFFSET: u8 = 40; // Secondary PIC handles IRQs 8-15
 pub const KERNEL_OFFSET: u8 = 120;
 
+/// Masks every line on both PICs.
+pub fn mask_all() {
+    unsafe {
+        Port::<u8>::new(0x21).write(0xFF);
+        Port::<u8>::new(0xA1).write(0xFF);
+    }
+}
+
+/// Unmasks `irq` (0-15) on the PIC that serves it.
+pub fn unmask(irq: u8) {
+    let (port, bit) = if irq < 8 { (0x21, irq) } else { (0xA1, irq - 8) };
+    unsafe {
+        let mut data = Port::<u8>::new(port);
+        let mask = data.read();
+        data.write(mask & !(1 << bit));
+    }
+}
+
+/// Sends further EOIs to the local APIC. Only for `ioapic::init`, after it
+/// masked the PICs.
+pub fn use_apic() {
+    APIC_MODE.store(true, Ordering::SeqCst);
+}
+
+pub fn apic_mode() -> bool {
+    APIC_MODE.load(Ordering::Relaxed)
+}
+
 pub fn eoi(interrupt_id: InterruptIndex) {
+    if apic_mode() {
+        // A software interrupt has nothing in service to acknowledge.
+        if interrupt_id.is_irq() {
+            if let Some(lapic) = super::lapic::LAPIC.get() {
+                lapic.eoi();
+            }
+        }
+        return;
+    }
+
     let irq = interrupt_id.as_u8() - PIC_1_OFFSET;
 
     unsafe {
@@ -92,6 +147,8 @@ pub fn handle_interrupt<F>(interrupt_id: InterruptIndex, handler: F, timing: Eoi
 where
     F: FnOnce(),
 {
+    let depth = IRQ_DEPTH.this_cpu();
+    depth.fetch_add(1, Ordering::Relaxed);
     match timing {
         EoiTiming::Before => eoi(interrupt_id),
         EoiTiming::After => (),
@@ -101,6 +158,7 @@ where
         EoiTiming::Before => (),
         EoiTiming::After => eoi(interrupt_id),
     }
+    depth.fetch_sub(1, Ordering::Relaxed);
 }
 
 // Interrupt indices - these are the actual vector numbers the CPU sees
@@ -118,6 +176,11 @@ impl InterruptIndex {
         self as u8
     }
 
+    /// Hardware IRQ rather than a software interrupt.
+    pub fn is_irq(self) -> bool {
+        !matches!(self, InterruptIndex::Syscall)
+    }
+
     pub fn as_usize(self) -> usize {
         usize::from(self.as_u8())
     }
@@ -4,23 +4,25 @@
 //!
 //! ## Purpose
 //!
-//! By default, the PIC may mask the timer interrupt. This function
+//! By default, the PIC may mask the timer interrupt. The `pit` driver
 //! clears the mask bit to enable timer interrupts (IRQ0).
 //!
 //! ## Usage
 //!
-//! Called during interrupt initialization to ensure the timer
-//! interrupt reaches the CPU.
+//! Registered and initialized from the `Devices` boot stage (see
+//! `devices::registry`).
 
-pub fn init_pic_timer() {
-    unsafe {
-        use x86_64::instructions::port::Port;
-        
-        let mut pic_mask_port = Port::<u8>::new(0x21);
-        let mut mask = pic_mask_port.read();
-        
-        mask &= !0x01;
-        
-        pic_mask_port.write(mask);
+use crate::devices::registry::{Driver, DriverError};
+
+pub struct Pit;
+
+impl Driver for Pit {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn init(&mut self) -> Result<(), DriverError> {
+        super::pic::unmask(0);
+        Ok(())
     }
-}
\ No newline at end of file
+}
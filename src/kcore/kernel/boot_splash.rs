@@ -0,0 +1,85 @@
+//! # Boot Splash
+//!
+//! Draws the boot stages, then the drivers in `devices::registry`, with
+//! their init status to the framebuffer while the kernel boots. Does
+//! nothing until the display component has initialized the framebuffer.
+
+use crate::devices::framebuffer::framebuffer::with_fb_blocking;
+use crate::kcore::kernel::status::{get_all_statuses, ComponentStatus, InitStatus};
+use crate::ui_provider::{
+    color::Color,
+    render::{RenderList, TextStyle},
+    shape::Rect,
+    theme::Theme,
+};
+use alloc::string::String;
+
+const ROW_HEIGHT: usize = 28;
+const PANEL_WIDTH: usize = 520;
+const PADDING: usize = 20;
+const TITLE_HEIGHT: usize = 40;
+
+const OK_COLOR: Color = Color::new(0xa6, 0xe3, 0xa1);
+const FAIL_COLOR: Color = Color::new(0xf3, 0x8b, 0xa8);
+
+fn indicator(status: InitStatus, theme: &Theme) -> (&'static str, Color) {
+    match status {
+        InitStatus::NotStarted => ("[    ]", theme.muted),
+        InitStatus::InProgress => ("[ .. ]", theme.accent),
+        InitStatus::Completed => ("[ OK ]", OK_COLOR),
+        InitStatus::Failed(_) => ("[FAIL]", FAIL_COLOR),
+    }
+}
+
+pub fn collect_render(
+    components: &[ComponentStatus],
+    theme: &Theme,
+    screen_w: usize,
+    screen_h: usize,
+    out: &mut RenderList,
+) {
+    let panel_h = TITLE_HEIGHT + components.len() * ROW_HEIGHT + PADDING * 2;
+    let panel_w = PANEL_WIDTH.min(screen_w);
+    let panel = Rect::new(
+        screen_w.saturating_sub(panel_w) / 2,
+        screen_h.saturating_sub(panel_h) / 2,
+        panel_w,
+        panel_h,
+    );
+
+    out.clear_with(theme.background);
+    out.fill_rounded_rect(panel, 10, theme.surface);
+    out.styled_text(
+        "DuxOS is starting",
+        panel.x + PADDING,
+        panel.y + PADDING,
+        TextStyle::new(theme.accent),
+    );
+
+    let max_cols = panel.w.saturating_sub(PADDING * 2) / 10;
+    for (i, component) in components.iter().enumerate() {
+        let y = panel.y + PADDING + TITLE_HEIGHT + i * ROW_HEIGHT;
+        let (mark, color) = indicator(component.status, theme);
+        out.text(mark, panel.x + PADDING, y, color);
+
+        let mut label = String::from(component.name);
+        if let InitStatus::Failed(reason) = component.status {
+            label.push_str(": ");
+            label.push_str(reason);
+        }
+        let label: String = label.chars().take(max_cols.saturating_sub(7)).collect();
+        out.text(label, panel.x + PADDING + 70, y, theme.text);
+    }
+}
+
+pub fn draw() {
+    let theme = Theme::dark_modern();
+    let mut statuses = get_all_statuses();
+    statuses.extend(crate::devices::registry::components());
+    let _ = with_fb_blocking(|fb| {
+        let mut list = RenderList::new();
+        collect_render(&statuses, &theme, fb.width, fb.height, &mut list);
+        list.flush(fb);
+        fb.render_frame();
+    });
+}
@@ -2,122 +2,406 @@
 //!
 //! Orchestrates the kernel boot sequence with proper error handling
 //! and status tracking.
+//!
+//! ## Stages
+//!
+//! Boot is a fixed sequence of `Stage`s, `Stage::ALL` in order. Each stage
+//! names the stages it `requires`; `run_stage` skips a stage whose
+//! requirements did not complete (recording why in `status`) and, in debug
+//! builds, asserts that stages run in table order, so moving a call in
+//! `init_kernel` cannot silently put, say, the IO-APIC ahead of ACPI.
+//! The status table and the boot splash list the same stages. Devices
+//! are drivers in `devices::registry`: the `Devices` stage registers and
+//! starts the PIT, keyboard, mouse, RTC and serial port, the `Hpet` stage
+//! the HPET, and the splash lists them under the stages.
+//!
+//! Code that needs a stage behind it says so at its entry point with
+//! `require_stage!(Stage::Memory)`, which in debug builds panics with
+//! "<module> called before stage <name> completed".
+//!
+//! Stage times are measured with the TSC. They are printed once interrupts
+//! are on and the TSC can be calibrated against the timer: the stages
+//! before that in one block, later ones as they finish.
 
-use crate::kcore::kernel::status::{update_component_status, InitStatus};
+use crate::devices::drivers::hpet;
+use crate::devices::registry::{self, DriverStatus};
+use crate::kcore::kernel::boot_splash;
+use crate::kcore::kernel::status::{register_component, update_component_status, InitStatus};
 use crate::println;
+use crate::stats::latency;
+use alloc::boxed::Box;
+use bootloader_api::BootInfo;
+use core::fmt;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Memory,
+    Fs,
+    Framebuffer,
+    Gdt,
+    Idt,
+    Hardening,
+    Pic,
+    Devices,
+    Acpi,
+    IoApic,
+    Hpet,
+    Smp,
+    Apps,
+}
+
+impl Stage {
+    /// Boot order.
+    pub const ALL: [Stage; 13] = [
+        Stage::Memory,
+        Stage::Fs,
+        Stage::Framebuffer,
+        Stage::Gdt,
+        Stage::Idt,
+        Stage::Hardening,
+        Stage::Pic,
+        Stage::Devices,
+        Stage::Acpi,
+        Stage::IoApic,
+        Stage::Hpet,
+        Stage::Smp,
+        Stage::Apps,
+    ];
+
+    /// The name in `status` and on the boot splash.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Memory => "Memory Management",
+            Stage::Fs => "Saved State",
+            Stage::Framebuffer => "Display System",
+            Stage::Gdt => "GDT",
+            Stage::Idt => "IDT",
+            Stage::Hardening => "SMEP/SMAP",
+            Stage::Pic => "PIC",
+            Stage::Devices => "Devices",
+            Stage::Acpi => "ACPI Tables",
+            Stage::IoApic => "IO-APIC",
+            Stage::Hpet => "HPET",
+            Stage::Smp => "SMP",
+            Stage::Apps => "Apps",
+        }
+    }
+
+    /// What must have completed before this stage runs.
+    pub fn requires(self) -> &'static [Stage] {
+        match self {
+            Stage::Memory => &[],
+            // The allocator, and the GDT's stack tracking
+            Stage::Fs | Stage::Framebuffer | Stage::Gdt | Stage::Acpi => &[Stage::Memory],
+            // The double fault handler's IST slot lives in the TSS
+            Stage::Idt => &[Stage::Gdt],
+            // A violation should reach the page fault handler
+            Stage::Hardening => &[Stage::Idt],
+            // Remapped vectors must already have handlers
+            Stage::Pic => &[Stage::Idt],
+            Stage::Devices => &[Stage::Pic],
+            Stage::IoApic => &[Stage::Acpi, Stage::Pic],
+            Stage::Hpet => &[Stage::Acpi],
+            // Calibrates its delays against the running timer
+            Stage::Smp => &[Stage::Acpi, Stage::Devices],
+            Stage::Apps => &[Stage::Framebuffer, Stage::Devices],
+        }
+    }
+
+    /// Whether boot counts as failed without it.
+    fn essential(self) -> bool {
+        matches!(
+            self,
+            Stage::Memory | Stage::Gdt | Stage::Idt | Stage::Pic | Stage::Devices
+        )
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+static COMPLETED: AtomicU32 = AtomicU32::new(0);
+/// Stages that have run or been skipped, to check the order.
+static ATTEMPTED: AtomicU32 = AtomicU32::new(0);
+static CYCLES: [AtomicU64; Stage::ALL.len()] = [const { AtomicU64::new(0) }; Stage::ALL.len()];
+
+pub fn is_complete(stage: Stage) -> bool {
+    COMPLETED.load(Ordering::Acquire) & stage.bit() != 0
+}
+
+/// A call made before the stage it needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unmet {
+    pub caller: &'static str,
+    pub stage: Stage,
+}
+
+impl fmt::Display for Unmet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} called before stage {} completed",
+            self.caller,
+            self.stage.name()
+        )
+    }
+}
+
+pub fn unmet(stage: Stage, caller: &'static str) -> Option<Unmet> {
+    (!is_complete(stage)).then_some(Unmet { caller, stage })
+}
+
+/// Backs `require_stage!`. Formats nothing on the heap, which may not
+/// exist yet.
+pub fn require(stage: Stage, caller: &'static str) {
+    if let Some(unmet) = unmet(stage, caller) {
+        panic!("{}", unmet);
+    }
+}
+
+/// Panics in debug builds unless the given `Stage` has completed.
+#[macro_export]
+macro_rules! require_stage {
+    ($stage:expr) => {
+        if cfg!(debug_assertions) {
+            $crate::kcore::kernel::init::require($stage, module_path!());
+        }
+    };
+}
+
+fn micros(cycles: u64) -> u64 {
+    cycles / latency::tsc_per_us().max(1)
+}
+
+/// Needs interrupts on, to calibrate the TSC.
+fn print_times(stages: &[Stage]) {
+    for &stage in stages {
+        if ATTEMPTED.load(Ordering::Relaxed) & stage.bit() != 0 {
+            let cycles = CYCLES[stage as usize].load(Ordering::Relaxed);
+            println!("    {:<20} {:>8} us", stage.name(), micros(cycles));
+        }
+    }
+}
 
-use crate::kcore::kernel::status::register_component;
+pub fn init_kernel(boot_info: &'static mut BootInfo) -> Result<(), &'static str> {
+    // Before anything allocates, so before the status table too
+    let start = latency::rdtsc();
+    if let Err(e) = unsafe { crate::memory::init(boot_info) } {
+        println!("PANIC: Failed to init memory: {}", e);
+        crate::loop_arch_mm();
+    }
+    crate::stats::stacks::track_boot_stack(crate::KERNEL_STACK_SIZE);
+    CYCLES[Stage::Memory as usize].store(latency::rdtsc() - start, Ordering::Relaxed);
+    ATTEMPTED.fetch_or(Stage::Memory.bit(), Ordering::Relaxed);
+    COMPLETED.fetch_or(Stage::Memory.bit(), Ordering::Release);
 
-pub fn init_kernel() -> Result<(), &'static str> {
+    for stage in Stage::ALL {
+        register_component(stage.name());
+    }
+    update_component_status(Stage::Memory.name(), InitStatus::Completed);
 
-    register_component("CPU Features");
-    register_component("Memory Management");
-    register_component("Interrupt System");
-    register_component("Display System");
-    register_component("Input Devices");
     println!("╔════════════════════════════════════════╗");
     println!("║      RustOS Kernel Initialization      ║");
     println!("╚════════════════════════════════════════╝\n");
 
-    init_phase("Interrupt System", init_interrupts)?;
+    // The framebuffer keeps boot_info; take what ACPI and SMP need first.
+    let rsdp = boot_info.rsdp_addr.into_option();
+    let trampoline_page = crate::kcore::smp::trampoline_page(&boot_info.memory_regions);
+    let regions = &boot_info.memory_regions;
+    let _ = run_stage(Stage::Fs, || restore_saved_state(regions));
+
+    // Without a framebuffer the kernel falls back to the serial console.
+    let _ = run_stage(Stage::Framebuffer, || {
+        crate::devices::framebuffer::framebuffer::init_framebuffer(boot_info)
+    });
+    let _ = run_stage(Stage::Gdt, init_gdt);
+    let _ = run_stage(Stage::Idt, init_idt);
+    let _ = run_stage(Stage::Hardening, crate::kcore::hardening::init);
+    let _ = run_stage(Stage::Pic, init_pic);
+    // Boot continues without a mouse; the failure stays visible in `lsdev`.
+    let _ = run_stage(Stage::Devices, init_devices);
+
+    // Not before: the keyboard and mouse setup read their replies from
+    // port 0x60, which the IRQ handlers would drain first.
+    x86_64::instructions::interrupts::enable();
+    crate::util::rand::seed_at_boot();
+    println!("Stage times before interrupts:");
+    print_times(&Stage::ALL[..=Stage::Devices as usize]);
+    println!("");
+
+    // Missing or broken tables only cost the IO-APIC, HPET and SMP.
+    let _ = run_stage(Stage::Acpi, || crate::kcore::acpi::init(rsdp));
+    // On failure the 8259 PIC keeps delivering interrupts.
+    let _ = run_stage(Stage::IoApic, crate::kcore::interrupts::ioapic::init);
+    // Without it, delays fall back to the TSC.
+    let _ = run_stage(Stage::Hpet, || {
+        registry::register(Box::new(hpet::HpetDriver));
+        registry::init_pending();
+        driver_up("hpet")
+    });
+    // Single-CPU boot is fine if it fails. The APs copy the BSP's CR4,
+    // SMEP/SMAP included.
+    let _ = run_stage(Stage::Smp, || crate::kcore::smp::init(trampoline_page));
 
     println!("\n Kernel initialization complete!\n");
+    match Stage::ALL
+        .into_iter()
+        .find(|&stage| stage.essential() && !is_complete(stage))
+    {
+        Some(stage) => Err(stage.name()),
+        None => Ok(()),
+    }
+}
+
+/// Brings back what the last session put away with `sync`, if the RAM
+/// area survived.
+fn restore_saved_state(regions: &[bootloader_api::info::MemoryRegion]) -> Result<(), &'static str> {
+    use crate::fs::persist;
+    use crate::memory::{PHYSICAL_MEMORY_END, PHYSICAL_MEMORY_START};
+    use core::sync::atomic::Ordering;
+
+    let window = (
+        PHYSICAL_MEMORY_START.load(Ordering::SeqCst),
+        PHYSICAL_MEMORY_END.load(Ordering::SeqCst),
+    );
+    let Some(area) = persist::find_area(regions, window) else {
+        println!("persist: no area for saved state; sync is unavailable");
+        return Err("no area for saved state");
+    };
+    match persist::restore() {
+        Ok(files) => {
+            println!(
+                "persist: restored {} files from the previous session",
+                files
+            );
+            crate::ui_provider::theme::load_config();
+            crate::ui_provider::bell::load_config();
+        }
+        Err(err) => println!("persist: nothing restored from {:#x}: {}", area, err),
+    }
+    // After the restore, which may bring back an older copy of the file.
+    crate::fs::lastpanic::report();
     Ok(())
 }
 
-fn init_phase(
-    name: &'static str,
-    init_fn: fn() -> Result<(), &'static str>,
-) -> Result<(), &'static str> {
+/// Runs one stage, or records why it cannot run. `pub` for the `Apps`
+/// stage, which `kernel_main` runs once it has a framebuffer to lay out.
+pub fn run_stage<T>(
+    stage: Stage,
+    init_fn: impl FnOnce() -> Result<T, &'static str>,
+) -> Result<T, &'static str> {
+    let name = stage.name();
+    let earlier = Stage::ALL.iter().take_while(|&&s| s != stage);
+    let expected = earlier.fold(0, |bits, s| bits | s.bit());
+    debug_assert_eq!(
+        ATTEMPTED.load(Ordering::Relaxed),
+        expected,
+        "stage {} run out of order",
+        name
+    );
+    ATTEMPTED.fetch_or(stage.bit(), Ordering::Relaxed);
+
+    if let Some(&missing) = stage.requires().iter().find(|&&s| !is_complete(s)) {
+        update_component_status(name, InitStatus::Failed("skipped, a stage it needs failed"));
+        println!("    ✗ {} skipped: needs {}\n", name, missing.name());
+        return Err("skipped, a stage it needs failed");
+    }
+
+    let step = stage as usize + 1;
     update_component_status(name, InitStatus::InProgress);
-    println!("[1/5] Initializing {}...", name);
+    println!("[{}/{}] Initializing {}...", step, Stage::ALL.len(), name);
+    boot_splash::draw();
 
-    match init_fn() {
-        Ok(()) => {
+    let start = latency::rdtsc();
+    let result = init_fn();
+    let cycles = latency::rdtsc() - start;
+    CYCLES[stage as usize].store(cycles, Ordering::Relaxed);
+    match &result {
+        Ok(_) => {
+            COMPLETED.fetch_or(stage.bit(), Ordering::Release);
             update_component_status(name, InitStatus::Completed);
-            println!("    ✓ {} initialized successfully\n", name);
-            Ok(())
+            if x86_64::instructions::interrupts::are_enabled() {
+                println!("    ✓ {} initialized in {} us\n", name, micros(cycles));
+            } else {
+                println!("    ✓ {} initialized successfully\n", name);
+            }
         }
         Err(e) => {
             update_component_status(name, InitStatus::Failed(e));
             println!("    ✗ {} failed: {}\n", name, e);
-            Err(e)
         }
     }
+    boot_splash::draw();
+    result
 }
 
-fn init_interrupts() -> Result<(), &'static str> {
-    crate::kcore::interrupts::init();
-    // enable timer interrupts
-    unsafe {
-        use x86_64::instructions::port::Port;
-        let mut pic1_data = Port::<u8>::new(0x21);
-        let mask: u8 = pic1_data.read();
-        let new_mask = mask & !(1 << 0); // enable irq0 (timer)
-        pic1_data.write(new_mask);
-    }
-    println!("1");
-    // enable keyboard interrupt (irq1)
-    unsafe {
-        use x86_64::instructions::port::Port;
-        let mut pic1_data = Port::<u8>::new(0x21);
-        let mask: u8 = pic1_data.read();
-        let new_mask = mask & !(1 << 1); // enable irq1 (keyboard)
-        pic1_data.write(new_mask);
-    }
+fn init_gdt() -> Result<(), &'static str> {
+    crate::kcore::interrupts::gdt::init();
+    Ok(())
+}
 
-    println!("2");
-    // enable mouse interrupt (irq12)
-    // enable ps/2 mouse via controller
-    unsafe {
-        use x86_64::instructions::port::Port;
-
-        let mut cmd = Port::<u8>::new(0x64);
-        let mut data = Port::<u8>::new(0x60);
-
-        // helper to wait until controller is ready to accept a command
-        let wait_write = || {
-            while Port::<u8>::new(0x64).read() & 0x2 != 0 {}
-        };
-        let wait_read = || {
-            while Port::<u8>::new(0x64).read() & 0x1 == 0 {}
-        };
-        // Enable auxiliary (mouse) port
-        wait_write();
-        cmd.write(0xA8);
-
-        // Read controller config byte
-        wait_write();
-        cmd.write(0x20);
-        wait_read();
-        let mut config = data.read();
-
-        // Enable IRQ12 (mouse) and enable auxiliary device
-        config |= 0x02; // IRQ12
-        config &= !0x20; // enable aux clock
-        wait_write();
-        cmd.write(0x60);
-        wait_write();
-        data.write(config);
-
-        // Set defaults
-        wait_write();
-        cmd.write(0xD4);
-        wait_write();
-        data.write(0xF6);
-        wait_read();
-        data.read(); // consume ack
-
-        // Enable data reporting
-        wait_write();
-        cmd.write(0xD4);
-        wait_write();
-        data.write(0xF4);
-        wait_read();
-        data.read(); // consume ack
-    }
-    println!("3");
+fn init_idt() -> Result<(), &'static str> {
+    crate::kcore::interrupts::interrupts::init_idt();
+    Ok(())
+}
 
-    x86_64::instructions::interrupts::enable();
+fn init_pic() -> Result<(), &'static str> {
+    crate::kcore::interrupts::pic::remap();
     Ok(())
 }
+
+/// Registers the devices the PIC serves, and the rest that need nothing
+/// but port I/O, and starts them. Fails if the timer or keyboard is down.
+fn init_devices() -> Result<(), &'static str> {
+    use crate::devices::drivers::{ps2_keyboard, ps2_mouse, rtc, serial};
+
+    registry::register(Box::new(crate::kcore::interrupts::timer::Pit));
+    registry::register(Box::new(ps2_keyboard::Keyboard));
+    registry::register(Box::new(ps2_mouse::Mouse));
+    registry::register(Box::new(rtc::Rtc));
+    registry::register(Box::new(serial::Serial));
+    registry::init_pending();
+    driver_up("pit")?;
+    driver_up("keyboard")
+}
+
+fn driver_up(name: &'static str) -> Result<(), &'static str> {
+    match registry::status(name) {
+        Some(DriverStatus::Ok) => Ok(()),
+        Some(DriverStatus::Failed(reason)) => Err(reason),
+        _ => Err("no device"),
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn stages_follow_their_requirements() {
+        for (index, stage) in Stage::ALL.into_iter().enumerate() {
+            assert_eq!(stage as usize, index, "{}", stage.name());
+            for required in stage.requires() {
+                assert!(
+                    (*required as usize) < index,
+                    "{} needs {}",
+                    stage.name(),
+                    required.name()
+                );
+            }
+        }
+
+        // Tests run before kernel_main gets to the apps
+        assert_eq!(unmet(Stage::Memory, "test"), None);
+        let early = unmet(Stage::Apps, "kernel::tests").map(|unmet| alloc::format!("{}", unmet));
+        assert_eq!(
+            early.as_deref(),
+            Some("kernel::tests called before stage Apps completed")
+        );
+        assert!(ATTEMPTED.load(Ordering::Relaxed) & Stage::Smp.bit() != 0);
+    }
+}
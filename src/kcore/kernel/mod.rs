@@ -4,13 +4,15 @@
 //!
 //! ## Submodules
 //!
-//! - `init`: Kernel initialization sequence
+//! - `init`: Kernel initialization sequence, as ordered stages
 //! - `status`: Component status tracking for startup display
+//! - `boot_splash`: Framebuffer rendering of the component list
 //!
 //! ## Status Tracking
 //!
-//! Components register themselves and update their initialization status.
-//! This is used to display a boot splash showing initialization progress.
+//! Every boot stage is registered as a component and updates its
+//! initialization status. This is used to display a boot splash showing
+//! initialization progress.
 //!
 //! ## Example
 //!
@@ -22,6 +24,7 @@
 //! update_component_status("Memory", InitStatus::Done);
 //! ```
 
+pub mod boot_splash;
 /// Kernel initialization and bootstrap module
 pub mod init;
 pub mod status;
@@ -7,6 +7,15 @@
 //!
 //! - `kernel`: Kernel initialization, status tracking, and component registration
 //! - `interrupts`: IDT setup, exception handlers, PIC configuration, timer
+//! - `acpi`: RSDP discovery and the MADT/FADT tables
+//! - `smp`: application processor bring-up
+//! - `power`: reboot and ACPI power-off
+//! - `task`: cooperative kernel tasks and their signals
+//! - `run_queue`: the ready queue the task scheduler runs from
+//! - `timer_wheel`: bucketed timeouts for sleeping tasks
+//! - `regs`: control register and EFER/RFLAGS decoding for `regs`
+//! - `hardening`: SMEP/SMAP enablement and the `stac`/`clac` user-access window
+//! - `panic`: nested-panic guard, lock-free serial output and fault injection for the panic path
 //!
 //! ## Initialization Order
 //!
@@ -17,3 +26,12 @@
 
 pub mod kernel;
 pub mod interrupts;
+pub mod acpi;
+pub mod hardening;
+pub mod panic;
+pub mod power;
+pub mod regs;
+pub mod smp;
+pub mod run_queue;
+pub mod task;
+pub mod timer_wheel;
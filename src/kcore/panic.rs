@@ -0,0 +1,169 @@
+//! # Panic Path
+//!
+//! The panic handler must not wait on anything the panicking code may
+//! hold. Two things it prints through could be mid-use:
+//!
+//! - The framebuffer: `with_fb`/`with_fb_blocking` mark it held for as
+//!   long as their closure runs, and the mark stays set if the closure
+//!   panics (there is no unwinding). The panic screen takes it with
+//!   `steal_for_panic` rather than the lock either way; the mark only
+//!   tells the log that the screen may be torn.
+//! - COM1: `SERIAL` is a `static mut` with no lock, so a second writer
+//!   would alias the first one's `&mut`. Once a panic is in progress
+//!   `kprintln` writes through `RawSerial`, straight to the port.
+//!
+//! A panic inside the handler (while formatting, say) enters it again.
+//! `enter` counts entries; the second one writes `NESTED` with `RawSerial`,
+//! formats nothing and halts.
+//!
+//! Debug builds can arm a panic at a `Site` with `crash in <site>`, to
+//! check that the message still gets out from there.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+/// Line status register: bit 5 is set while the transmit register is empty.
+const COM1_LSR: u16 = COM1 + 5;
+const THR_EMPTY: u8 = 1 << 5;
+/// How long to wait for the UART per byte before writing anyway.
+const TX_SPINS: usize = 100_000;
+
+/// What a panic inside the panic handler prints.
+const NESTED: &str = "\r\nKERNEL PANIC while panicking; halting\r\n";
+
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+static ARMED: AtomicU8 = AtomicU8::new(0);
+
+/// Counts an entry into the panic handler. Returns true for the first;
+/// for any later one it reports the nested panic and halts.
+pub fn enter() -> bool {
+    if DEPTH.fetch_add(1, Ordering::SeqCst) == 0 {
+        return true;
+    }
+    let _ = fmt::Write::write_str(&mut RawSerial, NESTED);
+    halt()
+}
+
+pub fn in_progress() -> bool {
+    DEPTH.load(Ordering::Relaxed) != 0
+}
+
+/// Interrupts off, then `hlt` for good.
+pub fn halt() -> ! {
+    x86_64::instructions::interrupts::disable();
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// COM1 without `SERIAL`: polls the line status and writes the data port
+/// directly. Gives up waiting on a byte after `TX_SPINS`, so a missing or
+/// wedged UART cannot hang the panic path.
+pub struct RawSerial;
+
+impl fmt::Write for RawSerial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut status = Port::<u8>::new(COM1_LSR);
+        let mut data = Port::<u8>::new(COM1);
+        for byte in s.bytes() {
+            for _ in 0..TX_SPINS {
+                if unsafe { status.read() } & THR_EMPTY != 0 {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+            unsafe { data.write(byte) };
+        }
+        Ok(())
+    }
+}
+
+/// Where `crash in` can plant a panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Site {
+    /// Inside `render_frame`, with the framebuffer held.
+    RenderFrame = 1,
+    /// Inside `kprintln`, between the serial and the log write.
+    Println = 2,
+}
+
+impl Site {
+    pub const ALL: [Site; 2] = [Site::RenderFrame, Site::Println];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Site::RenderFrame => "render",
+            Site::Println => "println",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Site> {
+        Self::ALL.into_iter().find(|site| site.name() == name)
+    }
+}
+
+/// Panics the next time `site` is passed. Only debug builds check.
+pub fn arm(site: Site) {
+    ARMED.store(site as u8, Ordering::SeqCst);
+}
+
+/// Whether a panic is armed at `site`, disarming it. Checked by the sites
+/// themselves, under `cfg(debug_assertions)`.
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+pub fn hit(site: Site) -> bool {
+    ARMED
+        .compare_exchange(site as u8, 0, Ordering::SeqCst, Ordering::Relaxed)
+        .is_ok()
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn armed_sites_fire_once_and_only_there() {
+        assert!(!in_progress());
+        assert!(!hit(Site::Println));
+        arm(Site::RenderFrame);
+        assert!(!hit(Site::Println));
+        assert!(hit(Site::RenderFrame));
+        assert!(!hit(Site::RenderFrame));
+        for site in Site::ALL {
+            assert_eq!(Site::parse(site.name()), Some(site));
+        }
+    }
+
+    #[test_case]
+    fn println_during_a_panic_leaves_the_log_alone() {
+        let before = crate::klog::KLOG.total_written();
+        DEPTH.fetch_add(1, Ordering::SeqCst);
+        crate::println!("printed while panicking");
+        DEPTH.fetch_sub(1, Ordering::SeqCst);
+        assert_eq!(crate::klog::KLOG.total_written(), before);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test_case]
+    fn an_armed_println_panics() {
+        arm(Site::Println);
+        crate::tests::runner::expect_panic("injected panic in println");
+        crate::println!("this line panics");
+    }
+
+    #[cfg(debug_assertions)]
+    #[test_case]
+    fn an_armed_render_frame_panics() {
+        use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+
+        let buffer: &'static mut [u8] = alloc::vec![0u8; 64 * 32 * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, 64, 32, 64, 4);
+        arm(Site::RenderFrame);
+        crate::tests::runner::expect_panic("injected panic in render_frame");
+        fb.render_frame();
+    }
+}
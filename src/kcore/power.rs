@@ -0,0 +1,54 @@
+//! # Reboot and Power-Off
+//!
+//! Reboot pulses the CPU reset line through the 8042 keyboard controller,
+//! which every PC and QEMU machine type honours; a triple fault is the
+//! fallback. Power-off writes SLP_EN to the FADT's PM1 control ports with
+//! sleep type 0, which is what QEMU's `\_S5` uses. The real value lives in
+//! the DSDT's AML, which is not parsed, so on hardware this may do
+//! nothing and the call returns.
+
+use x86_64::instructions::port::Port;
+
+const KBC_STATUS: u16 = 0x64;
+const KBC_PULSE_RESET: u8 = 0xFE;
+const SLP_EN: u16 = 1 << 13;
+
+pub fn reboot() -> ! {
+    x86_64::instructions::interrupts::disable();
+    unsafe {
+        let mut status = Port::<u8>::new(KBC_STATUS);
+        // Wait for the input buffer to drain before sending the command.
+        for _ in 0..100_000 {
+            if status.read() & 0x02 == 0 {
+                break;
+            }
+        }
+        status.write(KBC_PULSE_RESET);
+    }
+    crate::devices::drivers::hpet::busy_wait_us(50_000);
+
+    // Still here: load an empty IDT and fault.
+    unsafe {
+        let empty = x86_64::structures::DescriptorTablePointer {
+            limit: 0,
+            base: x86_64::VirtAddr::new(0),
+        };
+        x86_64::instructions::tables::lidt(&empty);
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
+
+/// Returns only if the machine is still on.
+pub fn poweroff() {
+    let Some(fadt) = crate::kcore::acpi::fadt() else {
+        return;
+    };
+    x86_64::instructions::interrupts::disable();
+    for port in [fadt.pm1a_cnt, fadt.pm1b_cnt] {
+        if port != 0 {
+            unsafe { Port::<u16>::new(port as u16).write(SLP_EN) };
+        }
+    }
+    crate::devices::drivers::hpet::busy_wait_us(50_000);
+    x86_64::instructions::interrupts::enable();
+}
@@ -0,0 +1,98 @@
+//! # Control Registers
+//!
+//! Reads CR0, CR2, CR3, CR4, EFER and RFLAGS and names the bits that
+//! matter for paging, memory protection and interrupts, for the `regs`
+//! command. Only the listed bits are decoded; the raw value is printed
+//! alongside for the rest.
+
+use alloc::{format, string::String};
+use x86_64::registers::{
+    control::{Cr0, Cr2, Cr3, Cr4},
+    model_specific::Efer,
+    rflags,
+};
+
+/// A decoded bit: position, short name, what it turns on.
+type Bit = (u8, &'static str, &'static str);
+
+const CR0_BITS: &[Bit] = &[
+    (0, "PE", "protected mode"),
+    (16, "WP", "write protect in ring 0"),
+    (29, "NW", "not write-through"),
+    (30, "CD", "cache disable"),
+    (31, "PG", "paging"),
+];
+
+const CR4_BITS: &[Bit] = &[
+    (5, "PAE", "physical address extension"),
+    (7, "PGE", "global pages"),
+    (9, "OSFXSR", "SSE, FXSAVE/FXRSTOR"),
+    (20, "SMEP", "no ring 0 execution of user pages"),
+    (21, "SMAP", "no ring 0 access to user pages"),
+];
+
+const EFER_BITS: &[Bit] = &[
+    (0, "SCE", "syscall/sysret"),
+    (8, "LME", "long mode enable"),
+    (10, "LMA", "long mode active"),
+    (11, "NXE", "no-execute pages"),
+];
+
+const RFLAGS_BITS: &[Bit] = &[
+    (9, "IF", "interrupts enabled"),
+    (10, "DF", "string ops count down"),
+    (18, "AC", "user access allowed under SMAP"),
+];
+
+/// One register: its raw value, then a row per bit in `bits`.
+fn decode(out: &mut String, name: &str, raw: u64, bits: &[Bit]) {
+    out.push_str(&format!("{:<7} {:#018x}\n", name, raw));
+    for &(bit, short, meaning) in bits {
+        let state = if raw & (1 << bit) != 0 { "on" } else { "off" };
+        out.push_str(&format!("  {:<7} {:<4} {}\n", short, state, meaning));
+    }
+}
+
+pub fn report() -> String {
+    let mut out = String::new();
+    decode(&mut out, "CR0", Cr0::read_raw(), CR0_BITS);
+    out.push_str(&format!(
+        "{:<7} {:#018x} (last page fault address)\n",
+        "CR2",
+        Cr2::read_raw()
+    ));
+    let (p4, flags) = Cr3::read_raw();
+    out.push_str(&format!(
+        "{:<7} P4 frame {:#x}, flags {:#x}\n",
+        "CR3",
+        p4.start_address().as_u64(),
+        flags
+    ));
+    decode(&mut out, "CR4", Cr4::read_raw(), CR4_BITS);
+    decode(&mut out, "EFER", Efer::read_raw(), EFER_BITS);
+    decode(&mut out, "RFLAGS", rflags::read_raw(), RFLAGS_BITS);
+    out.push_str(&super::hardening::report());
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn long_mode_shows_as_on() {
+        let mut out = String::new();
+        decode(&mut out, "EFER", 0x0d01, EFER_BITS);
+        assert!(out.starts_with("EFER    0x0000000000000d01\n"));
+        assert!(out.contains("  SCE     on   syscall/sysret\n"));
+        assert!(out.contains("  LME     on   long mode enable\n"));
+        assert!(out.contains("  NXE     on   no-execute pages\n"));
+
+        // We are running, so paging and long mode are on
+        let live = report();
+        assert!(live.contains("  PG      on"));
+        assert!(live.contains("  LMA     on"));
+    }
+}
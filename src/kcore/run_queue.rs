@@ -0,0 +1,119 @@
+//! # Run Queue
+//!
+//! The ready tasks of one CPU, in the order they will run. The queue only
+//! does the mechanics; which end a task goes back on (and so who runs
+//! next) is the scheduler's policy.
+//!
+//! The owner works the front: `pop_next` takes the next task to run and
+//! `push` puts a task at the back after its turn. `steal` takes from the
+//! back, the end the owner will reach last, so that once there is one
+//! queue per CPU an idle CPU can take work from a busy one without
+//! fighting the owner over the same tasks.
+
+use alloc::collections::VecDeque;
+
+pub struct RunQueue<T> {
+    tasks: VecDeque<T>,
+}
+
+impl<T> RunQueue<T> {
+    pub const fn new() -> Self {
+        Self {
+            tasks: VecDeque::new(),
+        }
+    }
+
+    /// Queues `task` behind everything already waiting.
+    pub fn push(&mut self, task: T) {
+        self.tasks.push_back(task);
+    }
+
+    /// Queues `task` to run next.
+    pub fn push_front(&mut self, task: T) {
+        self.tasks.push_front(task);
+    }
+
+    /// The task to run now.
+    pub fn pop_next(&mut self) -> Option<T> {
+        self.tasks.pop_front()
+    }
+
+    /// The task the owner would run last, for another CPU to take.
+    #[allow(dead_code)] // no second queue to steal into yet
+    pub fn steal(&mut self) -> Option<T> {
+        self.tasks.pop_back()
+    }
+
+    /// Takes out the first task matching `pred`, wherever it is.
+    pub fn take(&mut self, pred: impl FnMut(&T) -> bool) -> Option<T> {
+        let idx = self.tasks.iter().position(pred)?;
+        self.tasks.remove(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// In run order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tasks.iter()
+    }
+}
+
+impl<T> Default for RunQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn queue(items: &[u32]) -> RunQueue<u32> {
+        let mut q = RunQueue::new();
+        for &item in items {
+            q.push(item);
+        }
+        q
+    }
+
+    #[test_case]
+    fn pop_next_is_fifo_and_push_front_jumps_the_line() {
+        let mut q = queue(&[1, 2, 3]);
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.pop_next(), Some(1));
+        q.push(1);
+        q.push_front(9);
+        assert_eq!(q.iter().copied().collect::<Vec<_>>(), [9, 2, 3, 1]);
+        while q.pop_next().is_some() {}
+        assert!(q.is_empty());
+        assert_eq!(q.pop_next(), None);
+    }
+
+    #[test_case]
+    fn steal_takes_from_the_far_end() {
+        let mut q = queue(&[1, 2, 3, 4]);
+        let mut thief = RunQueue::new();
+        thief.push(q.steal().unwrap());
+        assert_eq!(q.pop_next(), Some(1));
+        assert_eq!(thief.pop_next(), Some(4));
+        assert_eq!(q.len(), 2);
+    }
+
+    #[test_case]
+    fn take_removes_from_the_middle() {
+        let mut q = queue(&[1, 2, 3]);
+        assert_eq!(q.take(|&t| t == 2), Some(2));
+        assert_eq!(q.take(|&t| t == 2), None);
+        assert_eq!(q.iter().copied().collect::<Vec<_>>(), [1, 3]);
+    }
+}
@@ -0,0 +1,52 @@
+//! Per-CPU block, reached through the GS base.
+//!
+//! Each CPU installs its own `CpuLocal` once its GDT is loaded (loading a
+//! GS selector afterwards would clear the base again). The kernel has no
+//! `swapgs` path yet, so the base is never shared with user code.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{registers::model_specific::GsBase, VirtAddr};
+
+#[derive(Debug)]
+pub struct CpuLocal {
+    /// Position in `smp::cpus()`; 0 is the bootstrap CPU.
+    pub index: usize,
+    pub apic_id: u8,
+    /// Bumped by an idle AP on every poll of the work queue.
+    pub heartbeat: AtomicU64,
+    pub jobs_done: AtomicU64,
+    /// Id of the job running now, 0 when idle.
+    pub current_job: AtomicU64,
+}
+
+impl CpuLocal {
+    /// Leaked: CPUs are never taken offline.
+    pub fn new(index: usize, apic_id: u8) -> &'static Self {
+        alloc::boxed::Box::leak(alloc::boxed::Box::new(Self {
+            index,
+            apic_id,
+            heartbeat: AtomicU64::new(0),
+            jobs_done: AtomicU64::new(0),
+            current_job: AtomicU64::new(0),
+        }))
+    }
+
+    pub fn heartbeat(&self) -> u64 {
+        self.heartbeat.load(Ordering::Relaxed)
+    }
+}
+
+/// Makes `local` the block `this_cpu` returns on the calling CPU.
+pub fn install(local: &'static CpuLocal) {
+    GsBase::write(VirtAddr::from_ptr(local));
+}
+
+/// The calling CPU's block, `None` before `install`.
+pub fn this_cpu() -> Option<&'static CpuLocal> {
+    let base = GsBase::read();
+    if base.is_null() {
+        None
+    } else {
+        Some(unsafe { &*base.as_ptr::<CpuLocal>() })
+    }
+}
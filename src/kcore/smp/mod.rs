@@ -0,0 +1,334 @@
+//! # SMP Bring-up
+//!
+//! Starts the application processors (APs) listed in the ACPI MADT. An AP
+//! loads its own GDT/TSS and the shared IDT, installs its `CpuLocal` in the
+//! GS base, reports in and then runs jobs from the AP work queue (`work`)
+//! with interrupts off. There is no scheduler across CPUs beyond that.
+//!
+//! ## Sequence
+//!
+//! 1. Take the MADT from `acpi` and map the local APIC.
+//! 2. Copy the trampoline to a free page below 1 MiB and identity-map it.
+//! 3. For each AP, one at a time: allocate a stack and per-CPU tables, send
+//!    INIT, wait 10 ms, then STARTUP (repeated once if the AP is silent)
+//!    and wait for it to report in.
+//! 4. Remove the identity mapping, unless an AP never answered.
+//!
+//! ## Opt-in
+//!
+//! AP bring-up is off unless `SMP_CONFIG` says `on` (`smp on`, then `sync`
+//! and a warm reboot). Until then `init` only sets up the BSP's local APIC
+//! and `CpuLocal`, and counts the APs it left alone for `smp status`.
+//!
+//! ## Shared state
+//!
+//! The trampoline's data block and `AP_READY` are only touched by the BSP
+//! and the single AP currently starting. `CPUS` is written by the BSP alone;
+//! APs only bump the `ONLINE` counter and their own `CpuLocal` counters.
+//!
+//! Once up, APs share the heap with the BSP; its spin lock is the only
+//! global lock they take besides the work queue. They never touch the
+//! framebuffer or the serial port. State that each CPU needs its own copy
+//! of lives in a `percpu::PerCpu`.
+
+pub mod local;
+pub mod percpu;
+mod trampoline;
+pub mod work;
+
+use crate::devices::drivers::hpet;
+use crate::kcore::acpi;
+use crate::kcore::interrupts::{
+    gdt,
+    lapic::{self, LocalApic},
+};
+use crate::memory;
+use alloc::{format, string::String, vec, vec::Vec};
+use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use local::CpuLocal;
+use spin::Mutex;
+use trampoline::Trampoline;
+use x86_64::{
+    registers::control::{Cr0, Cr3, Cr4},
+    structures::paging::{PageTableFlags, PhysFrame},
+    PhysAddr, VirtAddr,
+};
+
+const AP_STACK_SIZE: usize = 16 * 1024;
+/// Upper bound on CPUs started; extra MADT entries are ignored.
+pub const MAX_CPUS: usize = 16;
+const REAL_MODE_LIMIT: u64 = 0x10_0000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CpuState {
+    Bsp,
+    /// Started and running the work queue.
+    Online,
+    /// Did not report in after the STARTUP IPIs.
+    NoResponse,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cpu {
+    pub apic_id: u8,
+    pub state: CpuState,
+    pub local: &'static CpuLocal,
+}
+
+/// `on` starts the APs at boot; anything else, or no file, leaves them off.
+pub const SMP_CONFIG: &str = "/config/smp";
+
+static CPUS: Mutex<Vec<Cpu>> = Mutex::new(Vec::new());
+/// APs in the MADT that `init` did not start, since SMP is off.
+static LEFT_OFF: AtomicUsize = AtomicUsize::new(0);
+/// CPUs running kernel code, the BSP included.
+static ONLINE: AtomicUsize = AtomicUsize::new(1);
+/// Set by the AP being started once it no longer needs the trampoline.
+static AP_READY: AtomicBool = AtomicBool::new(false);
+
+/// Handed to an AP through the trampoline.
+struct ApBoot {
+    tables: &'static gdt::CpuTables,
+    local: &'static CpuLocal,
+}
+
+pub fn online_count() -> usize {
+    ONLINE.load(Ordering::SeqCst)
+}
+
+pub fn cpus() -> Vec<Cpu> {
+    CPUS.lock().clone()
+}
+
+/// Waits up to `us` microseconds for the AP to report in.
+fn wait_ready(us: u64) -> bool {
+    let per_us = crate::stats::latency::tsc_per_us();
+    let end = crate::stats::latency::rdtsc() + us * per_us;
+    while crate::stats::latency::rdtsc() < end {
+        if AP_READY.load(Ordering::SeqCst) {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    AP_READY.load(Ordering::SeqCst)
+}
+
+/// First usable page below 1 MiB for the trampoline. Page 0 holds the
+/// real-mode IVT, and pages 0xA0-0xBF are reserved STARTUP vectors.
+pub fn trampoline_page(regions: &[MemoryRegion]) -> Option<u64> {
+    regions
+        .iter()
+        .filter(|r| r.kind == MemoryRegionKind::Usable && r.start < REAL_MODE_LIMIT)
+        .find_map(|r| {
+            let page = r.start.max(0x1000).next_multiple_of(4096);
+            let fits = page + 4096 <= r.end.min(REAL_MODE_LIMIT);
+            (fits && page < 0xA_0000).then_some(page)
+        })
+}
+
+extern "C" fn ap_main(arg: u64) -> ! {
+    let boot = unsafe { &*(arg as *const ApBoot) };
+    gdt::init_cpu(boot.tables);
+    crate::kcore::interrupts::interrupts::init_idt();
+    local::install(boot.local);
+
+    ONLINE.fetch_add(1, Ordering::SeqCst);
+    AP_READY.store(true, Ordering::SeqCst);
+
+    work::run()
+}
+
+/// Starts the AP with `apic_id` and waits for it to report in.
+fn start_ap(lapic: &LocalApic, tramp: &Trampoline, local: &'static CpuLocal) -> CpuState {
+    let apic_id = local.apic_id;
+    let stack: &'static mut [u8] = vec![0u8; AP_STACK_SIZE].leak();
+    crate::stats::stacks::track(
+        &format!("cpu{}", local.index),
+        stack.as_ptr() as usize,
+        AP_STACK_SIZE,
+    );
+    let stack_top = (stack.as_ptr() as u64 + AP_STACK_SIZE as u64) & !0xF;
+    let boot: &'static ApBoot = alloc::boxed::Box::leak(alloc::boxed::Box::new(ApBoot {
+        tables: gdt::new_cpu_tables(local.index),
+        local,
+    }));
+
+    AP_READY.store(false, Ordering::SeqCst);
+    tramp.prepare(stack_top, ap_main, boot as *const ApBoot as u64);
+
+    lapic.send_init(apic_id);
+    hpet::busy_wait_us(10_000);
+    for _ in 0..2 {
+        lapic.send_startup(apic_id, tramp.vector());
+        if wait_ready(1_000) {
+            return CpuState::Online;
+        }
+    }
+    if wait_ready(100_000) {
+        CpuState::Online
+    } else {
+        CpuState::NoResponse
+    }
+}
+
+/// Whether `SMP_CONFIG` asks for the APs to be started.
+pub fn aps_enabled() -> bool {
+    crate::fs::ramfs::read(SMP_CONFIG).is_ok_and(|value| value.trim_ascii() == b"on")
+}
+
+/// Brings up every enabled AP from the MADT, using `page` from
+/// `trampoline_page` for the trampoline. Needs the heap, the timer (for TSC
+/// calibration), `acpi::init` and interrupts enabled on the BSP.
+pub fn init(page: Option<u64>) -> Result<(), &'static str> {
+    let madt = acpi::madt().ok_or("no MADT")?;
+
+    let lapic = lapic::init(madt.lapic_phys).map_err(|_| "cannot map local APIC")?;
+    let bsp_id = lapic.id();
+    let bsp_local = CpuLocal::new(0, bsp_id);
+    local::install(bsp_local);
+
+    let mut cpus = vec![Cpu {
+        apic_id: bsp_id,
+        state: CpuState::Bsp,
+        local: bsp_local,
+    }];
+    let aps: Vec<u8> = madt
+        .enabled_apic_ids()
+        .filter(|&id| id != bsp_id)
+        .take(MAX_CPUS - 1)
+        .collect();
+    if aps.is_empty() {
+        *CPUS.lock() = cpus;
+        return Ok(());
+    }
+    if !aps_enabled() {
+        LEFT_OFF.store(aps.len(), Ordering::Relaxed);
+        *CPUS.lock() = cpus;
+        crate::println!(
+            "    SMP: {} APs left off ('smp on' to start them)",
+            aps.len()
+        );
+        return Ok(());
+    }
+
+    let page = page.ok_or("no free page below 1 MiB")?;
+    if Trampoline::len() > 4096 {
+        return Err("trampoline larger than a page");
+    }
+    let (p4, _) = Cr3::read();
+    let cr3 = p4.start_address().as_u64();
+    if cr3 > u32::MAX as u64 {
+        return Err("page tables above 4 GiB");
+    }
+    let identity = VirtAddr::new(page);
+    if memory::page_is_mapped(identity) {
+        return Err("trampoline page already mapped");
+    }
+
+    let tramp = unsafe { Trampoline::install(page, Cr0::read_raw(), cr3, Cr4::read_raw()) };
+    let frame = PhysFrame::containing_address(PhysAddr::new(page));
+    memory::map_single_page(
+        identity,
+        frame,
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+    )
+    .map_err(|_| "cannot identity-map trampoline")?;
+
+    for apic_id in aps {
+        let local = CpuLocal::new(cpus.len(), apic_id);
+        let state = start_ap(lapic, &tramp, local);
+        cpus.push(Cpu {
+            apic_id,
+            state,
+            local,
+        });
+    }
+
+    // A silent AP may still come through the trampoline later.
+    if cpus.iter().all(|c| c.state != CpuState::NoResponse) {
+        memory::unmap_single_page(identity);
+    }
+    *CPUS.lock() = cpus;
+    crate::println!(
+        "    SMP: {} of {} CPUs online",
+        online_count(),
+        CPUS.lock().len()
+    );
+    Ok(())
+}
+
+/// CPU list and AP jobs for `smp status`.
+pub fn report() -> String {
+    let cpus = cpus();
+    if cpus.is_empty() {
+        return String::from("SMP: not initialized (single CPU)");
+    }
+    let mut out = format!("CPUs online: {} of {}\n", online_count(), cpus.len());
+    match LEFT_OFF.load(Ordering::Relaxed) {
+        0 => {}
+        n => out.push_str(&format!(
+            "  {} APs left off; 'smp on' starts them from the next boot\n",
+            n
+        )),
+    }
+    for (idx, cpu) in cpus.iter().enumerate() {
+        let state = match cpu.state {
+            CpuState::Bsp => "bootstrap",
+            CpuState::Online => "online",
+            CpuState::NoResponse => "no response",
+        };
+        out.push_str(&format!(
+            "  cpu{:<3} apic {:<3} {:<11}",
+            idx, cpu.apic_id, state
+        ));
+        if cpu.state == CpuState::Online {
+            out.push_str(&format!(
+                " heartbeat {:<8} jobs {}",
+                cpu.local.heartbeat(),
+                cpu.local.jobs_done.load(Ordering::Relaxed)
+            ));
+            match cpu.local.current_job.load(Ordering::Relaxed) {
+                0 => {}
+                id => out.push_str(&format!(" (running #{})", id)),
+            }
+        }
+        out.push('\n');
+    }
+
+    let finished = work::finished();
+    if work::queued() > 0 || !finished.is_empty() {
+        out.push_str(&format!("AP jobs: {} queued\n", work::queued()));
+    }
+    for job in finished {
+        out.push_str(&format!(
+            "  #{} {} on cpu{}, {} us: {}\n",
+            job.id, job.name, job.cpu, job.micros, job.output
+        ));
+    }
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::ramfs;
+
+    #[test_case]
+    fn aps_start_only_when_the_config_says_on() {
+        let saved = ramfs::read(SMP_CONFIG).ok();
+        let was_on = aps_enabled();
+        for (value, on) in [(&b"on\n"[..], true), (b"off", false), (b"yes", false)] {
+            ramfs::write(SMP_CONFIG, value).unwrap();
+            assert_eq!(aps_enabled(), on);
+        }
+        match &saved {
+            Some(value) => ramfs::write(SMP_CONFIG, value).unwrap(),
+            None => drop(ramfs::remove(SMP_CONFIG).unwrap()),
+        }
+        assert_eq!(ramfs::read(SMP_CONFIG).ok(), saved);
+        assert_eq!(aps_enabled(), was_on);
+    }
+}
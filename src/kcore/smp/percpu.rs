@@ -0,0 +1,53 @@
+//! # Per-CPU Values
+//!
+//! `PerCpu<T>` keeps one `T` per CPU, and `this_cpu` picks the calling
+//! CPU's. The slot is the CPU's position in `smp::cpus()`, read from its
+//! `CpuLocal`, rather than its APIC ID, which can be sparse. Before
+//! `smp::init` installs the BSP's block every caller gets slot 0, which is
+//! right: only the BSP runs then.
+//!
+//! Only `&T` is handed out, since an interrupt on the same CPU can reach
+//! the same slot, so `T` is usually an atomic or a lock.
+
+use super::{local, MAX_CPUS};
+
+pub struct PerCpu<T> {
+    slots: [T; MAX_CPUS],
+}
+
+impl<T> PerCpu<T> {
+    /// One value per slot, e.g. `[const { AtomicUsize::new(0) }; MAX_CPUS]`.
+    pub const fn new(slots: [T; MAX_CPUS]) -> Self {
+        Self { slots }
+    }
+
+    pub fn this_cpu(&self) -> &T {
+        &self.slots[current_index()]
+    }
+}
+
+/// The calling CPU's position in `smp::cpus()`, 0 before `smp::init`.
+pub fn current_index() -> usize {
+    local::this_cpu().map_or(0, |local| local.index)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test_case]
+    fn tests_run_on_slot_zero_and_instances_are_separate() {
+        static A: PerCpu<AtomicUsize> = PerCpu::new([const { AtomicUsize::new(0) }; MAX_CPUS]);
+        static B: PerCpu<AtomicUsize> = PerCpu::new([const { AtomicUsize::new(0) }; MAX_CPUS]);
+
+        assert_eq!(current_index(), 0);
+        A.this_cpu().fetch_add(3, Ordering::Relaxed);
+        assert!(core::ptr::eq(A.this_cpu(), &A.slots[0]));
+        assert_eq!(A.this_cpu().load(Ordering::Relaxed), 3);
+        assert_eq!(A.slots[1].load(Ordering::Relaxed), 0);
+        assert_eq!(B.this_cpu().load(Ordering::Relaxed), 0);
+    }
+}
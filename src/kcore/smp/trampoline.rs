@@ -0,0 +1,178 @@
+//! Real-mode entry for application processors.
+//!
+//! A STARTUP IPI starts an AP in real mode at `page << 12`. The code below
+//! is copied to such a page below 1 MiB, which must also be identity-mapped
+//! in the BSP's page tables: the AP switches straight from real mode to long
+//! mode using the BSP's CR3, so it keeps executing from the same addresses.
+//!
+//! The data block at the end is filled in by `Trampoline::prepare` before
+//! each AP is started; only one AP runs through the trampoline at a time.
+
+use crate::memory::phys_to_virt;
+use core::ptr::addr_of;
+use x86_64::PhysAddr;
+
+core::arch::global_asm!(
+    r#"
+.section .text.smp_trampoline, "ax"
+.code16
+.global smp_trampoline_start
+smp_trampoline_start:
+    cli
+    cld
+    mov %cs, %ax
+    mov %ax, %ds
+
+    mov (smp_ap_cr4 - smp_trampoline_start), %eax
+    mov %eax, %cr4
+    mov (smp_ap_cr3 - smp_trampoline_start), %eax
+    mov %eax, %cr3
+
+    # EFER: long mode and no-execute, which the kernel's page tables use
+    mov $0xC0000080, %ecx
+    rdmsr
+    or $0x900, %eax
+    wrmsr
+
+    lgdtl (smp_ap_gdtr - smp_trampoline_start)
+
+    # protection and paging in one go puts the CPU in long mode
+    mov (smp_ap_cr0 - smp_trampoline_start), %eax
+    mov %eax, %cr0
+    ljmpl *(smp_ap_farptr - smp_trampoline_start)
+
+.code64
+.global smp_ap_long_mode
+smp_ap_long_mode:
+    mov $0x10, %ax
+    mov %ax, %ds
+    mov %ax, %es
+    mov %ax, %ss
+    xor %ax, %ax
+    mov %ax, %fs
+    mov %ax, %gs
+
+    mov smp_ap_stack(%rip), %rsp
+    mov smp_ap_arg(%rip), %rdi
+    mov smp_ap_entry(%rip), %rax
+    call *%rax
+1:
+    hlt
+    jmp 1b
+
+.balign 8
+.global smp_ap_gdt
+smp_ap_gdt:
+    .quad 0
+    .quad 0x00AF9A000000FFFF
+    .quad 0x00CF92000000FFFF
+.global smp_ap_gdtr
+smp_ap_gdtr:
+    .word 23
+    .long 0
+.global smp_ap_farptr
+smp_ap_farptr:
+    .long 0
+    .word 0x08
+
+.balign 8
+.global smp_ap_cr0
+smp_ap_cr0: .quad 0
+.global smp_ap_cr3
+smp_ap_cr3: .quad 0
+.global smp_ap_cr4
+smp_ap_cr4: .quad 0
+.global smp_ap_stack
+smp_ap_stack: .quad 0
+.global smp_ap_entry
+smp_ap_entry: .quad 0
+.global smp_ap_arg
+smp_ap_arg: .quad 0
+.global smp_trampoline_end
+smp_trampoline_end:
+.text
+"#,
+    options(att_syntax)
+);
+
+extern "C" {
+    static smp_trampoline_start: u8;
+    static smp_trampoline_end: u8;
+    static smp_ap_long_mode: u8;
+    static smp_ap_gdt: u8;
+    static smp_ap_gdtr: u8;
+    static smp_ap_farptr: u8;
+    static smp_ap_cr0: u8;
+    static smp_ap_cr3: u8;
+    static smp_ap_cr4: u8;
+    static smp_ap_stack: u8;
+    static smp_ap_entry: u8;
+    static smp_ap_arg: u8;
+}
+
+/// CR4.PCIDE cannot be set outside long mode.
+const CR4_PCIDE: u64 = 1 << 17;
+
+/// The trampoline copied to a low physical page.
+pub struct Trampoline {
+    page: u64,
+}
+
+fn offset_of(symbol: *const u8) -> usize {
+    symbol as usize - addr_of!(smp_trampoline_start) as usize
+}
+
+impl Trampoline {
+    pub fn len() -> usize {
+        addr_of!(smp_trampoline_end) as usize - addr_of!(smp_trampoline_start) as usize
+    }
+
+    /// Copies the code to the page at `page` (below 1 MiB) and fills in
+    /// everything that is the same for all APs.
+    ///
+    /// # Safety
+    /// The page must be unused RAM covered by the physical memory mapping.
+    pub unsafe fn install(page: u64, cr0: u64, cr3: u64, cr4: u64) -> Self {
+        let dst = phys_to_virt(PhysAddr::new(page)).as_mut_ptr::<u8>();
+        core::ptr::copy_nonoverlapping(addr_of!(smp_trampoline_start), dst, Self::len());
+
+        let t = Self { page };
+        t.write_u32(
+            offset_of(addr_of!(smp_ap_gdtr)) + 2,
+            (page as usize + offset_of(addr_of!(smp_ap_gdt))) as u32,
+        );
+        t.write_u32(
+            offset_of(addr_of!(smp_ap_farptr)),
+            (page as usize + offset_of(addr_of!(smp_ap_long_mode))) as u32,
+        );
+        t.write_u64(offset_of(addr_of!(smp_ap_cr0)), cr0);
+        t.write_u64(offset_of(addr_of!(smp_ap_cr3)), cr3);
+        t.write_u64(offset_of(addr_of!(smp_ap_cr4)), cr4 & !CR4_PCIDE);
+        t
+    }
+
+    /// Sets up the next AP: it starts on `stack_top` and calls
+    /// `entry(arg)`, which must not return.
+    pub fn prepare(&self, stack_top: u64, entry: extern "C" fn(u64) -> !, arg: u64) {
+        unsafe {
+            self.write_u64(offset_of(addr_of!(smp_ap_stack)), stack_top);
+            self.write_u64(offset_of(addr_of!(smp_ap_entry)), entry as usize as u64);
+            self.write_u64(offset_of(addr_of!(smp_ap_arg)), arg);
+        }
+    }
+
+    /// Page number for the STARTUP IPI.
+    pub fn vector(&self) -> u8 {
+        (self.page >> 12) as u8
+    }
+
+    unsafe fn write_u32(&self, offset: usize, value: u32) {
+        let p = phys_to_virt(PhysAddr::new(self.page + offset as u64)).as_mut_ptr::<u32>();
+        core::ptr::write_volatile(p, value);
+    }
+
+    unsafe fn write_u64(&self, offset: usize, value: u64) {
+        let p = phys_to_virt(PhysAddr::new(self.page + offset as u64)).as_mut_ptr::<u64>();
+        core::ptr::write_volatile(p, value);
+    }
+}
@@ -0,0 +1,97 @@
+//! # AP Work Queue
+//!
+//! Jobs for the application processors, kept apart from anything interrupt
+//! handlers queue. Any online AP takes the oldest job. An idle AP polls
+//! the queue about once a millisecond (it has no timer interrupt to wake
+//! it from `hlt` yet) and bumps its heartbeat on every poll.
+//!
+//! Jobs run with interrupts off and must stay away from the framebuffer
+//! and `println!`: both are written without a lock that covers other
+//! CPUs. A job returns its output instead, which `smp status` shows.
+
+use super::local::this_cpu;
+use crate::devices::drivers::hpet;
+use crate::stats::latency::{rdtsc, tsc_per_us};
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+pub type Job = Box<dyn FnOnce() -> String + Send>;
+
+const IDLE_POLL_US: u64 = 1_000;
+/// Finished jobs kept for `smp status`.
+const KEEP_FINISHED: usize = 8;
+
+struct Queued {
+    id: u64,
+    name: String,
+    job: Job,
+}
+
+#[derive(Clone)]
+pub struct Finished {
+    pub id: u64,
+    pub name: String,
+    pub cpu: usize,
+    pub micros: u64,
+    pub output: String,
+}
+
+static QUEUE: Mutex<VecDeque<Queued>> = Mutex::new(VecDeque::new());
+static FINISHED: Mutex<VecDeque<Finished>> = Mutex::new(VecDeque::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Queues `job` for an AP and returns its id.
+pub fn submit(name: &str, job: Job) -> Result<u64, &'static str> {
+    if super::online_count() < 2 {
+        return Err("no application processor online");
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    QUEUE.lock().push_back(Queued {
+        id,
+        name: String::from(name),
+        job,
+    });
+    Ok(id)
+}
+
+pub fn queued() -> usize {
+    QUEUE.lock().len()
+}
+
+/// Most recent last.
+pub fn finished() -> Vec<Finished> {
+    FINISHED.lock().iter().cloned().collect()
+}
+
+/// What an AP does once it is up. Needs `local::install` first.
+pub(super) fn run() -> ! {
+    let local = this_cpu().expect("AP started without a CpuLocal");
+    loop {
+        let next = QUEUE.lock().pop_front();
+        let Some(queued) = next else {
+            hpet::busy_wait_us(IDLE_POLL_US);
+            local.heartbeat.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+
+        local.current_job.store(queued.id, Ordering::Relaxed);
+        let start = rdtsc();
+        let output = (queued.job)();
+        let micros = (rdtsc() - start) / tsc_per_us().max(1);
+        local.current_job.store(0, Ordering::Relaxed);
+        local.jobs_done.fetch_add(1, Ordering::Relaxed);
+
+        let mut finished = FINISHED.lock();
+        if finished.len() == KEEP_FINISHED {
+            finished.pop_front();
+        }
+        finished.push_back(Finished {
+            id: queued.id,
+            name: queued.name,
+            cpu: local.index,
+            micros,
+            output,
+        });
+    }
+}
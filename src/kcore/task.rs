@@ -0,0 +1,595 @@
+//! # Cooperative Tasks
+//!
+//! Kernel tasks are closures that the main loop steps once per frame on
+//! the BSP. A step does a bounded amount of work and returns; there is no
+//! preemption and no separate stack.
+//!
+//! ## Signals
+//!
+//! A task can be told about an event without being stopped: `send_signal`
+//! sets a bit in its pending set, and the task checks and clears it with
+//! `TaskContext::take_signal` at the start of a step (its only yield
+//! point). Sending a signal moves the task to the front of the run queue,
+//! so a `Terminate` is seen on the next step rather than after a full
+//! round. A task that ignores a signal just leaves the bit set; it gets
+//! that one early step and then goes back to taking turns.
+//!
+//! ## Locking
+//!
+//! `run_pending` takes each task out of the scheduler for its step and
+//! drops the lock while it runs, so a step may spawn, signal or list
+//! tasks. The running task stays listed, and a signal sent to it while it
+//! runs is kept for its next step, which comes first.
+//!
+//! ## Storage
+//!
+//! Task records come from a slab cache of fixed-size blocks rather than
+//! the general heap, so spawning and finishing tasks does not fragment it.
+//!
+//! ## Sleeping
+//!
+//! A step can return `Sleep(ticks)` to be left alone for that many timer
+//! ticks. The wake-up goes into a timer wheel, which `run_pending` moves
+//! to `TIMER_TICKS` each frame, so sleepers cost nothing until they are
+//! due. A signal wakes a sleeping task early.
+//!
+//! ## Waiting for input
+//!
+//! A task that reads the keyboard registers with
+//! `ps2_keyboard::register_waiter` and returns a long `Sleep`. The IRQ
+//! only raises a flag; `wake_input_waiters`, run before every round,
+//! turns it into `Signal::Key` for each waiter, which wakes it like any
+//! other signal.
+//!
+//! ## Activity
+//!
+//! `ActivitySampler` turns the running step counts into steps per
+//! interval, for `top`: each sample reports how far every task got since
+//! the previous one.
+
+use super::{run_queue::RunQueue, timer_wheel::TimerWheel};
+use crate::kcore::interrupts::interrupts::TIMER_TICKS;
+use crate::memory::allocators::slab::{SlabBox, SlabCache};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::sync::atomic::Ordering;
+use spin::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Signal {
+    /// Finish up and return `Completed`.
+    Terminate = 0,
+    /// Stop the current piece of work (Ctrl+C style).
+    Interrupt = 1,
+    Timer = 2,
+    Key = 3,
+    User = 4,
+}
+
+impl Signal {
+    pub const ALL: [Signal; 5] = [
+        Signal::Terminate,
+        Signal::Interrupt,
+        Signal::Timer,
+        Signal::Key,
+        Signal::User,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Signal::Terminate => "term",
+            Signal::Interrupt => "int",
+            Signal::Timer => "timer",
+            Signal::Key => "key",
+            Signal::User => "user",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sig| sig.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// More work left; step again later.
+    Yield,
+    /// Nothing to do for this many timer ticks.
+    Sleep(u64),
+    /// Done; the scheduler drops the task.
+    Completed,
+}
+
+/// What a task sees of itself while it runs.
+pub struct TaskContext {
+    pub id: u64,
+    signals: u32,
+}
+
+impl TaskContext {
+    /// Clears `sig` and reports whether it was pending.
+    pub fn take_signal(&mut self, sig: Signal) -> bool {
+        let pending = self.signals & sig.bit() != 0;
+        self.signals &= !sig.bit();
+        pending
+    }
+}
+
+pub type TaskFn = Box<dyn FnMut(&mut TaskContext) -> TaskState + Send>;
+
+struct Task {
+    ctx: TaskContext,
+    name: String,
+    steps: u64,
+    /// Tick at which a sleeping task is due.
+    wake_at: Option<u64>,
+    /// Out with the caller while the task runs.
+    func: Option<TaskFn>,
+}
+
+/// Slab block for a `Task`, with room to grow.
+const TASK_BLOCK: usize = 128;
+static TASK_SLAB: SlabCache<Task, TASK_BLOCK, 8> = SlabCache::new();
+type TaskBox = SlabBox<Task, TASK_BLOCK, 8>;
+
+/// Frames in the task slab and tasks allocated from it.
+pub fn slab_stats() -> (usize, usize) {
+    TASK_SLAB.stats()
+}
+
+pub struct TaskInfo {
+    pub id: u64,
+    pub name: String,
+    pub steps: u64,
+    pub pending: Vec<Signal>,
+    pub wake_at: Option<u64>,
+}
+
+/// A task and the steps it ran since the previous sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskActivity {
+    pub id: u64,
+    pub name: String,
+    pub steps: u64,
+    pub recent: u64,
+}
+
+#[derive(Default)]
+pub struct ActivitySampler {
+    /// Step counts at the previous sample, by id.
+    last: Vec<(u64, u64)>,
+}
+
+impl ActivitySampler {
+    pub const fn new() -> Self {
+        Self { last: Vec::new() }
+    }
+
+    /// Busiest first, then by id. A task the previous sample did not see
+    /// counts all its steps as recent.
+    pub fn sample(&mut self, tasks: Vec<TaskInfo>) -> Vec<TaskActivity> {
+        let mut rows: Vec<TaskActivity> = tasks
+            .into_iter()
+            .map(|t| {
+                let before = self
+                    .last
+                    .iter()
+                    .find(|(id, _)| *id == t.id)
+                    .map_or(0, |&(_, steps)| steps);
+                TaskActivity {
+                    id: t.id,
+                    name: t.name,
+                    steps: t.steps,
+                    recent: t.steps.saturating_sub(before),
+                }
+            })
+            .collect();
+        self.last = rows.iter().map(|r| (r.id, r.steps)).collect();
+        rows.sort_by_key(|r| (core::cmp::Reverse(r.recent), r.id));
+        rows
+    }
+}
+
+pub struct TaskScheduler {
+    ready: RunQueue<TaskBox>,
+    sleeping: Vec<TaskBox>,
+    next_id: u64,
+    sleepers: TimerWheel,
+    /// Reused by `advance` for the ids the wheel hands back.
+    woken: Vec<u64>,
+    /// The task whose step is running, between `start_next` and `finish`.
+    running: Option<TaskBox>,
+}
+
+impl TaskScheduler {
+    pub const fn new() -> Self {
+        Self {
+            ready: RunQueue::new(),
+            sleeping: Vec::new(),
+            next_id: 1,
+            sleepers: TimerWheel::new(0),
+            woken: Vec::new(),
+            running: None,
+        }
+    }
+
+    pub fn spawn(&mut self, name: &str, func: TaskFn) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let task = Task {
+            ctx: TaskContext { id, signals: 0 },
+            name: String::from(name),
+            steps: 0,
+            wake_at: None,
+            func: Some(func),
+        };
+        let task = SlabBox::new_in(task, &TASK_SLAB).expect("task slab: out of frames");
+        self.ready.push(task);
+        id
+    }
+
+    /// Marks `sig` pending for the task, waking it if it sleeps, and moves
+    /// it to the front of the queue. False if there is no such task.
+    pub fn send_signal(&mut self, id: u64, sig: Signal) -> bool {
+        if let Some(task) = self.running.as_mut().filter(|t| t.ctx.id == id) {
+            task.ctx.signals |= sig.bit();
+            return true;
+        }
+        let mut task = match self.ready.take(|t| t.ctx.id == id) {
+            Some(task) => task,
+            None => {
+                let Some(idx) = self.sleeping.iter().position(|t| t.ctx.id == id) else {
+                    return false;
+                };
+                let mut task = self.sleeping.swap_remove(idx);
+                if let Some(deadline) = task.wake_at.take() {
+                    self.sleepers.cancel(deadline, id);
+                }
+                task
+            }
+        };
+        task.ctx.signals |= sig.bit();
+        self.ready.push_front(task);
+        true
+    }
+
+    /// Sends `Signal::Key` to the tasks the keyboard IRQ has woken.
+    pub fn wake_input_waiters(&mut self) {
+        crate::devices::drivers::ps2_keyboard::drain_woken(|id| {
+            self.send_signal(id, Signal::Key);
+        });
+    }
+
+    /// Wakes the tasks whose sleep ends by tick `now`.
+    pub fn advance(&mut self, now: u64) {
+        self.woken.clear();
+        self.sleepers.advance_to(now, &mut self.woken);
+        for id in &self.woken {
+            if let Some(idx) = self.sleeping.iter().position(|t| t.ctx.id == *id) {
+                let mut task = self.sleeping.swap_remove(idx);
+                task.wake_at = None;
+                self.ready.push(task);
+            }
+        }
+    }
+
+    /// Runs one step of the task at the front of the queue. Returns its
+    /// id, or `None` if no task is ready. `run_pending` does the same with
+    /// the lock dropped around the step.
+    #[cfg(test)]
+    pub fn step(&mut self) -> Option<u64> {
+        let (mut func, mut ctx) = self.start_next()?;
+        let state = func(&mut ctx);
+        Some(self.finish(func, ctx, state))
+    }
+
+    /// Takes the task at the front of the queue as the running one and
+    /// hands out its function and context for a step; `finish` takes them
+    /// back. `None` if no task is ready.
+    fn start_next(&mut self) -> Option<(TaskFn, TaskContext)> {
+        let mut task = self.ready.pop_next()?;
+        task.steps += 1;
+        let func = task.func.take().expect("task already running");
+        let ctx = TaskContext {
+            id: task.ctx.id,
+            signals: core::mem::take(&mut task.ctx.signals),
+        };
+        self.running = Some(task);
+        Some((func, ctx))
+    }
+
+    /// Puts the running task back after its step returned `state` and
+    /// returns its id. Signals sent during the step put it at the front
+    /// of the queue, even if it asked to sleep.
+    fn finish(&mut self, func: TaskFn, ctx: TaskContext, state: TaskState) -> u64 {
+        let mut task = self.running.take().expect("no task running");
+        let id = task.ctx.id;
+        let signalled = task.ctx.signals != 0;
+        task.ctx.signals |= ctx.signals;
+        task.func = Some(func);
+        match state {
+            TaskState::Completed => {}
+            _ if signalled => self.ready.push_front(task),
+            TaskState::Yield => self.ready.push(task),
+            TaskState::Sleep(ticks) => {
+                let deadline = self.sleepers.now() + ticks.max(1);
+                task.wake_at = Some(deadline);
+                self.sleepers.add_timeout(deadline, id);
+                self.sleeping.push(task);
+            }
+        }
+        id
+    }
+
+    /// Steps every task that is ready now at most once (signalled ones
+    /// first).
+    #[cfg(test)]
+    pub fn run_round(&mut self) {
+        for _ in 0..self.ready.len() {
+            if self.step().is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Ready and sleeping tasks, by id.
+    pub fn info(&self) -> Vec<TaskInfo> {
+        let mut info: Vec<TaskInfo> = self
+            .ready
+            .iter()
+            .chain(&self.sleeping)
+            .chain(&self.running)
+            .map(|t| TaskInfo {
+                id: t.ctx.id,
+                name: t.name.clone(),
+                steps: t.steps,
+                pending: Signal::ALL
+                    .into_iter()
+                    .filter(|sig| t.ctx.signals & sig.bit() != 0)
+                    .collect(),
+                wake_at: t.wake_at,
+            })
+            .collect();
+        info.sort_by_key(|t| t.id);
+        info
+    }
+}
+
+impl Default for TaskScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub static SCHEDULER: Mutex<TaskScheduler> = Mutex::new(TaskScheduler::new());
+
+/// Needs the timer: a task that sleeps waits for ticks.
+pub fn spawn(name: &str, func: TaskFn) -> u64 {
+    crate::require_stage!(crate::kcore::kernel::init::Stage::Devices);
+    SCHEDULER.lock().spawn(name, func)
+}
+
+pub fn send_signal(id: u64, sig: Signal) -> bool {
+    SCHEDULER.lock().send_signal(id, sig)
+}
+
+pub fn info() -> Vec<TaskInfo> {
+    SCHEDULER.lock().info()
+}
+
+/// Called once per frame from the main loop. Steps every ready task at
+/// most once, with the scheduler unlocked while each step runs.
+pub fn run_pending() {
+    let round = {
+        let mut sched = SCHEDULER.lock();
+        sched.wake_input_waiters();
+        sched.advance(TIMER_TICKS.load(Ordering::Relaxed));
+        sched.ready.len()
+    };
+    for _ in 0..round {
+        let next = SCHEDULER.lock().start_next();
+        let Some((mut func, mut ctx)) = next else {
+            break;
+        };
+        let state = func(&mut ctx);
+        SCHEDULER.lock().finish(func, ctx, state);
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    /// Counts steps until told to terminate.
+    fn counter(count: Arc<AtomicU64>) -> TaskFn {
+        Box::new(move |ctx| {
+            if ctx.take_signal(Signal::Terminate) {
+                return TaskState::Completed;
+            }
+            count.fetch_add(1, Ordering::Relaxed);
+            TaskState::Yield
+        })
+    }
+
+    #[test_case]
+    fn terminate_signal_completes_a_task() {
+        let mut sched = TaskScheduler::new();
+        let count = Arc::new(AtomicU64::new(0));
+        let id = sched.spawn("counter", counter(count.clone()));
+
+        sched.run_round();
+        sched.run_round();
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+
+        assert!(sched.send_signal(id, Signal::Terminate));
+        assert_eq!(sched.info()[0].pending, [Signal::Terminate]);
+        sched.run_round();
+        assert!(sched.info().is_empty());
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+        assert!(!sched.send_signal(id, Signal::Terminate));
+    }
+
+    #[test_case]
+    fn signalled_task_is_stepped_first() {
+        let mut sched = TaskScheduler::new();
+        let a = sched.spawn("a", counter(Arc::new(AtomicU64::new(0))));
+        let b = sched.spawn("b", counter(Arc::new(AtomicU64::new(0))));
+
+        assert_eq!(sched.step(), Some(a));
+        sched.send_signal(a, Signal::User);
+        assert_eq!(sched.step(), Some(a));
+        // a ignores User: the bit stays set but turns resume with b
+        assert_eq!(sched.info()[0].pending, [Signal::User]);
+        assert_eq!(sched.step(), Some(b));
+
+        sched.send_signal(b, Signal::Terminate);
+        sched.send_signal(a, Signal::Terminate);
+        assert_eq!(sched.step(), Some(a));
+        assert_eq!(sched.step(), Some(b));
+        assert!(sched.info().is_empty());
+    }
+
+    #[test_case]
+    fn a_step_runs_with_the_scheduler_unlocked() {
+        let listed = Arc::new(AtomicU64::new(0));
+        let child = Arc::new(AtomicU64::new(0));
+        let (seen, spawned) = (listed.clone(), child.clone());
+        let parent = SCHEDULER.lock().spawn(
+            "parent",
+            Box::new(move |ctx| {
+                if ctx.take_signal(Signal::Terminate) {
+                    return TaskState::Completed;
+                }
+                // Each of these takes the lock
+                seen.store(
+                    info().iter().any(|t| t.id == ctx.id) as u64,
+                    Ordering::Relaxed,
+                );
+                assert!(send_signal(ctx.id, Signal::User));
+                let id = SCHEDULER
+                    .lock()
+                    .spawn("child", counter(Arc::new(AtomicU64::new(0))));
+                spawned.store(id, Ordering::Relaxed);
+                TaskState::Sleep(1_000)
+            }),
+        );
+
+        run_pending();
+        assert_eq!(listed.load(Ordering::Relaxed), 1);
+        let child = child.load(Ordering::Relaxed);
+        let tasks = info();
+        let me = tasks.iter().find(|t| t.id == parent).unwrap();
+        // Signalled while it ran, so it is not left asleep
+        assert_eq!(me.pending, [Signal::User]);
+        assert_eq!(me.wake_at, None);
+        assert!(tasks.iter().any(|t| t.id == child));
+
+        send_signal(parent, Signal::Terminate);
+        send_signal(child, Signal::Terminate);
+        run_pending();
+        assert!(info().iter().all(|t| t.id != parent && t.id != child));
+    }
+
+    #[test_case]
+    fn sleeping_task_waits_for_its_tick() {
+        let mut sched = TaskScheduler::new();
+        let naps = Arc::new(AtomicU64::new(0));
+        let count = naps.clone();
+        let sleeper = sched.spawn(
+            "sleeper",
+            Box::new(move |ctx| {
+                if ctx.take_signal(Signal::Terminate) {
+                    return TaskState::Completed;
+                }
+                count.fetch_add(1, Ordering::Relaxed);
+                TaskState::Sleep(10)
+            }),
+        );
+        let busy = sched.spawn("busy", counter(Arc::new(AtomicU64::new(0))));
+
+        sched.run_round();
+        assert_eq!(sched.info()[0].wake_at, Some(10));
+        sched.advance(9);
+        assert_eq!(sched.step(), Some(busy));
+        assert_eq!(sched.step(), Some(busy));
+        sched.advance(10);
+        assert_eq!(sched.step(), Some(sleeper));
+        assert_eq!(naps.load(Ordering::Relaxed), 2);
+
+        // a signal cuts the sleep short
+        sched.send_signal(sleeper, Signal::Terminate);
+        assert_eq!(sched.step(), Some(sleeper));
+        assert_eq!(sched.info().len(), 1);
+    }
+
+    #[test_case]
+    fn activity_counts_steps_since_the_last_sample() {
+        let mut sched = TaskScheduler::new();
+        let a = sched.spawn("a", counter(Arc::new(AtomicU64::new(0))));
+        let b = sched.spawn("b", counter(Arc::new(AtomicU64::new(0))));
+        let mut sampler = ActivitySampler::new();
+        let recent =
+            |rows: &[TaskActivity]| rows.iter().map(|r| (r.id, r.recent)).collect::<Vec<_>>();
+
+        sched.run_round();
+        sched.run_round();
+        assert_eq!(recent(&sampler.sample(sched.info())), [(a, 2), (b, 2)]);
+
+        // only b runs this interval, so it sorts first
+        sched.send_signal(a, Signal::Terminate);
+        sched.step();
+        sched.step();
+        let c = sched.spawn("c", counter(Arc::new(AtomicU64::new(0))));
+        let rows = sampler.sample(sched.info());
+        assert_eq!(recent(&rows), [(b, 1), (c, 0)]);
+        assert_eq!(rows[0].steps, 3);
+    }
+
+    #[test_case]
+    fn key_waiter_sleeps_until_a_scancode() {
+        use crate::devices::drivers::ps2_keyboard as kbd;
+
+        let mut sched = TaskScheduler::new();
+        let keys = Arc::new(AtomicU64::new(0));
+        let seen = keys.clone();
+        let reader = sched.spawn(
+            "reader",
+            Box::new(move |ctx| {
+                if ctx.take_signal(Signal::Key) {
+                    while kbd::dequeue_scancode().is_some() {
+                        seen.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                kbd::register_waiter(ctx.id);
+                if kbd::has_pending() {
+                    return TaskState::Yield;
+                }
+                TaskState::Sleep(1_000)
+            }),
+        );
+
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            while kbd::dequeue_scancode().is_some() {}
+            kbd::drain_woken(|_| {});
+            sched.run_round();
+            sched.wake_input_waiters();
+            assert_eq!(sched.step(), None, "asleep without input");
+
+            kbd::enqueue_scancode(0x1E);
+            kbd::enqueue_scancode(0x9E);
+            sched.wake_input_waiters();
+            assert_eq!(sched.step(), Some(reader));
+            assert_eq!(keys.load(Ordering::Relaxed), 2);
+            assert_eq!(sched.step(), None);
+            kbd::unregister_waiter(reader);
+        });
+    }
+}
@@ -0,0 +1,183 @@
+//! # Timer Wheel
+//!
+//! Timeouts kept in `SLOTS` buckets indexed by deadline tick modulo the
+//! wheel size. Advancing by one tick only looks at the bucket for that
+//! tick, so the cost of a tick is the size of one bucket rather than the
+//! number of pending timeouts. A deadline more than a full turn away sits
+//! in its bucket while the wheel passes it on earlier turns.
+//!
+//! The wheel itself is a plain data structure with no lock. Whoever owns
+//! it decides the tick source; the task scheduler advances its wheel to
+//! `TIMER_TICKS` once per frame rather than from the timer interrupt, so
+//! the interrupt never has to allocate or take a lock.
+
+use alloc::vec::Vec;
+
+pub const SLOTS: usize = 256;
+
+#[derive(Clone, Copy)]
+struct Timeout {
+    deadline: u64,
+    id: u64,
+}
+
+pub struct TimerWheel {
+    buckets: Vec<Vec<Timeout>>,
+    /// Last tick processed; deadlines at or before it fire on the next
+    /// advance.
+    now: u64,
+    pending: usize,
+}
+
+impl TimerWheel {
+    /// The buckets are allocated on the first `add_timeout`, so an empty
+    /// wheel can live in a static.
+    pub const fn new(now: u64) -> Self {
+        Self {
+            buckets: Vec::new(),
+            now,
+            pending: 0,
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Fires `id` once the wheel reaches `deadline`. A deadline already
+    /// passed fires on the next advance.
+    pub fn add_timeout(&mut self, deadline: u64, id: u64) {
+        if self.buckets.is_empty() {
+            self.buckets = (0..SLOTS).map(|_| Vec::new()).collect();
+        }
+        let deadline = deadline.max(self.now + 1);
+        self.buckets[slot(deadline)].push(Timeout { deadline, id });
+        self.pending += 1;
+    }
+
+    /// Drops a timeout added with the same `deadline` and `id`. False if
+    /// it has fired already or never existed.
+    pub fn cancel(&mut self, deadline: u64, id: u64) -> bool {
+        if self.pending == 0 {
+            return false;
+        }
+        let deadline = deadline.max(self.now + 1);
+        let bucket = &mut self.buckets[slot(deadline)];
+        match bucket
+            .iter()
+            .position(|t| t.deadline == deadline && t.id == id)
+        {
+            Some(idx) => {
+                bucket.remove(idx);
+                self.pending -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the wheel to `now` and appends the ids whose deadline has
+    /// come to `expired`, earliest deadline first and in insertion order
+    /// within a tick.
+    pub fn advance_to(&mut self, now: u64, expired: &mut Vec<u64>) {
+        if now <= self.now {
+            return;
+        }
+        if self.pending == 0 {
+            self.now = now;
+            return;
+        }
+        // Past a full turn every bucket is visited anyway; stop there and
+        // let the last visit fire everything up to `now`.
+        let first = (now + 1).saturating_sub(SLOTS as u64).max(self.now + 1);
+        if first > self.now + 1 {
+            let mut late: Vec<Timeout> = Vec::new();
+            for bucket in &mut self.buckets {
+                bucket.retain(|t| {
+                    let fire = t.deadline < first;
+                    if fire {
+                        late.push(*t);
+                    }
+                    !fire
+                });
+            }
+            late.sort_by_key(|t| t.deadline);
+            self.pending -= late.len();
+            expired.extend(late.iter().map(|t| t.id));
+        }
+
+        for tick in first..=now {
+            let bucket = &mut self.buckets[slot(tick)];
+            if bucket.is_empty() {
+                continue;
+            }
+            let before = expired.len();
+            bucket.retain(|t| {
+                let fire = t.deadline == tick;
+                if fire {
+                    expired.push(t.id);
+                }
+                !fire
+            });
+            self.pending -= expired.len() - before;
+        }
+        self.now = now;
+    }
+}
+
+fn slot(tick: u64) -> usize {
+    (tick % SLOTS as u64) as usize
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn timeouts_fire_in_deadline_order() {
+        let mut wheel = TimerWheel::new(0);
+        wheel.add_timeout(5, 50);
+        wheel.add_timeout(2, 20);
+        wheel.add_timeout(5, 51);
+        wheel.add_timeout(3 + SLOTS as u64, 300);
+        wheel.add_timeout(1, 10);
+
+        let mut fired = Vec::new();
+        for tick in 1..=6 {
+            wheel.advance_to(tick, &mut fired);
+        }
+        assert_eq!(fired, [10, 20, 50, 51]);
+
+        // same bucket as tick 3, but a turn later
+        fired.clear();
+        wheel.advance_to(2 + SLOTS as u64, &mut fired);
+        assert!(fired.is_empty());
+        wheel.advance_to(3 + SLOTS as u64, &mut fired);
+        assert_eq!(fired, [300]);
+    }
+
+    #[test_case]
+    fn long_jumps_and_cancel() {
+        let mut wheel = TimerWheel::new(100);
+        wheel.add_timeout(90, 1); // already due
+        wheel.add_timeout(400, 2);
+        wheel.add_timeout(150, 3);
+        wheel.add_timeout(2_000, 4);
+        wheel.add_timeout(500, 5);
+        assert!(wheel.cancel(500, 5));
+        assert!(!wheel.cancel(500, 5));
+
+        let mut fired = Vec::new();
+        wheel.advance_to(1_000, &mut fired);
+        assert_eq!(fired, [1, 3, 2]);
+        assert_eq!(wheel.now(), 1_000);
+
+        fired.clear();
+        wheel.advance_to(1_999, &mut fired);
+        assert!(fired.is_empty());
+        wheel.advance_to(2_000, &mut fired);
+        assert_eq!(fired, [4]);
+    }
+}
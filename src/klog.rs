@@ -0,0 +1,130 @@
+//! # Kernel Log Ring
+//!
+//! Fixed-size byte ring that keeps the most recent kernel output so it can be
+//! read back with `dmesg` when no serial console is attached.
+//!
+//! ## Concurrency
+//!
+//! Writers never block: each append reserves its byte range with a single
+//! `fetch_add` on the head counter and then copies into the reserved slots.
+//! This makes the ring safe to use from interrupt handlers while the main
+//! context is mid-write. Concurrent writers may interleave at fragment
+//! granularity, and a reader racing a writer may see a partially written
+//! tail; both are acceptable for a diagnostic log and never deadlock.
+
+use alloc::{string::String, vec::Vec};
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Capacity of the global kernel log in bytes.
+pub const KLOG_CAPACITY: usize = 16 * 1024;
+
+pub static KLOG: LogRing<KLOG_CAPACITY> = LogRing::new();
+
+pub struct LogRing<const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    /// Total bytes ever reserved; slot for byte `i` is `i % N`.
+    head: AtomicUsize,
+    /// Bytes before this offset are hidden from readers (`dmesg -c`).
+    floor: AtomicUsize,
+}
+
+// SAFETY: slots are only written through ranges reserved by `fetch_add`,
+// so no two writers touch the same byte unless the ring laps itself within
+// one write; readers tolerate torn bytes.
+unsafe impl<const N: usize> Sync for LogRing<N> {}
+
+impl<const N: usize> LogRing<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            floor: AtomicUsize::new(0),
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn append(&self, bytes: &[u8]) {
+        if N == 0 || bytes.is_empty() {
+            return;
+        }
+        // A line longer than the ring only keeps its tail.
+        let bytes = &bytes[bytes.len().saturating_sub(N)..];
+        let start = self.head.fetch_add(bytes.len(), Ordering::AcqRel);
+        let base = self.buf.get() as *mut u8;
+        for (i, b) in bytes.iter().enumerate() {
+            unsafe {
+                base.add((start + i) % N).write_volatile(*b);
+            }
+        }
+    }
+
+    /// Number of readable bytes currently retained.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        head - self.readable_start(head)
+    }
+
+    /// Total bytes written since boot, including evicted ones.
+    pub fn total_written(&self) -> usize {
+        self.head.load(Ordering::Acquire)
+    }
+
+    pub fn clear(&self) {
+        self.floor
+            .store(self.head.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    pub fn writer(&self) -> RingWriter<'_, N> {
+        RingWriter { ring: self }
+    }
+
+    /// Copy out the retained text, dropping a leading partial line if the
+    /// oldest bytes were evicted.
+    pub fn snapshot(&self) -> String {
+        let head = self.head.load(Ordering::Acquire);
+        let start = self.readable_start(head);
+        let base = self.buf.get() as *const u8;
+
+        let mut bytes = Vec::with_capacity(head - start);
+        for i in start..head {
+            bytes.push(unsafe { base.add(i % N).read_volatile() });
+        }
+
+        let evicted = start > self.floor.load(Ordering::Acquire);
+        let skip = if evicted {
+            bytes
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|p| p + 1)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        String::from_utf8_lossy(&bytes[skip..]).into_owned()
+    }
+
+    fn readable_start(&self, head: usize) -> usize {
+        head.saturating_sub(N).max(self.floor.load(Ordering::Acquire))
+    }
+}
+
+pub struct RingWriter<'a, const N: usize> {
+    ring: &'a LogRing<N>,
+}
+
+impl<const N: usize> fmt::Write for RingWriter<'_, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.ring.append(s.as_bytes());
+        Ok(())
+    }
+}
+
+pub fn write_fmt(args: fmt::Arguments) {
+    use fmt::Write;
+    let _ = KLOG.writer().write_fmt(args);
+}
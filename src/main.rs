@@ -2,21 +2,35 @@
 
 #![no_std]
 #![no_main]
-#![feature(slice_pattern, abi_x86_interrupt, alloc_error_handler)]
+#![feature(slice_pattern, abi_x86_interrupt, alloc_error_handler, custom_test_frameworks)]
+#![test_runner(crate::tests::runner::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
 extern crate alloc;
 extern crate rlibc;
 
 use crate::{
-    app::{AppEvent, AppHost},
-    apps::{editor_app::EditorApp, logs_app::LogsApp, terminal_app::TerminalApp},
+    app::{macros::Intercept, AppEvent, AppHost, KeyCode, Modifiers},
+    apps::{
+        editor_app::EditorApp, form_demo::FormDemoApp, logs_app::LogsApp,
+        settings_app::SettingsApp, terminal_app::TerminalApp,
+    },
     devices::{
-        drivers::{ps2_keyboard, ps2_mouse},
-        framebuffer::framebuffer::{init_framebuffer, FRAMEBUFFER},
+        drivers::{hpet, ps2_keyboard, ps2_mouse},
+        framebuffer::framebuffer::{self, with_fb_blocking},
         mouse_cursor,
     },
-    kcore::interrupts::interrupts::TIMER_TICKS,
-    ui_provider::{shape::Rect, theme::Theme},
+    kcore::{
+        interrupts::interrupts::TIMER_TICKS,
+        kernel::init::{run_stage, Stage},
+    },
+    ui_provider::{
+        layers::{self, Layer},
+        layout::{UiLayout, TAB_COUNT, TAB_NAMES},
+        pacing::{self, FramePacer, Pace},
+        theme::Theme,
+        top_bar::{BarState, TopBar},
+    },
 };
 
 use alloc::{boxed::Box, vec::Vec};
@@ -28,18 +42,26 @@ mod apps;
 mod cmd_executor;
 mod debug_pipeline;
 mod devices;
+mod fs;
+mod gfx;
+mod headless;
 mod kcore;
+mod klog;
 mod memory;
+mod stats;
 mod syscalls;
 mod terminal_v2;
 mod tests;
 mod ui_provider;
+mod util;
 mod vm;
 
+const KERNEL_STACK_SIZE: usize = 128 * 1024;
+
 const BOOTLOADER_CONFIG: bootloader_api::BootloaderConfig = {
     let mut config = bootloader_api::BootloaderConfig::new_default();
     config.mappings.physical_memory = Some(bootloader_api::config::Mapping::Dynamic);
-    config.kernel_stack_size = 128 * 1024;
+    config.kernel_stack_size = KERNEL_STACK_SIZE as u64;
     config
 };
 
@@ -47,10 +69,35 @@ entry_point!(kernel_main, config = &BOOTLOADER_CONFIG);
 
 pub static mut SERIAL: SerialPort = unsafe { SerialPort::new(0x3F8) };
 
+/// Serial and the kernel log. During a panic, serial goes through
+/// `kcore::panic::RawSerial`, since the panic may have hit mid-write, and
+/// the log is skipped: the panic may have hit inside it too.
 pub fn kprintln(args: alloc::fmt::Arguments) {
+    use alloc::fmt::Write;
+    if kcore::panic::in_progress() {
+        let _ = kcore::panic::RawSerial.write_fmt(args);
+        let _ = kcore::panic::RawSerial.write_str("\n");
+    } else {
+        unsafe {
+            let _ = crate::SERIAL.write_fmt(args);
+        }
+    }
+    #[cfg(debug_assertions)]
+    if kcore::panic::hit(kcore::panic::Site::Println) {
+        panic!("injected panic in println");
+    }
+    if !kcore::panic::in_progress() {
+        klog::write_fmt(args);
+        klog::KLOG.append(b"\n");
+    }
+}
+
+/// Live progress on the serial console, rewriting one line in place (see
+/// `terminal_v2::progress_line`); an empty `text` clears it. Not logged.
+pub fn serial_progress(text: &str) {
     use alloc::fmt::Write;
     unsafe {
-        let _ = crate::SERIAL.write_fmt(args);
+        let _ = (*core::ptr::addr_of_mut!(SERIAL)).write_str(&terminal_v2::progress_line(text));
     }
 }
 
@@ -69,50 +116,99 @@ fn loop_arch_mm() -> ! {
     }
 }
 
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &::core::panic::PanicInfo) -> ! {
+    // A second entry halts in here
+    kcore::panic::enter();
+    // First, while nothing else has had a chance to fault again.
+    fs::lastpanic::record(info);
     println!("KERNEL PANIC: {}", info);
+    if devices::framebuffer::framebuffer::held_by_accessor() {
+        println!("(the framebuffer was in use; the screen may be torn)");
+    }
+    draw_panic_screen(info);
     loop_arch_mm()
 }
 
-#[alloc_error_handler]
-fn alloc_error(layout: ::alloc::alloc::Layout) -> ! {
-    println!("ALLOC ERROR: {:?}", layout);
-    loop_arch_mm()
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &::core::panic::PanicInfo) -> ! {
+    tests::runner::on_panic(info)
 }
 
-#[derive(Clone, Copy)]
-struct UiLayout {
-    content_width: usize,
-    content_height: usize,
-    tab_height: usize,
+/// Best effort: the framebuffer is taken without the lock, so whatever was
+/// mid-draw may leave torn pixels around the message. Formats on the
+/// stack, since the panic may have come from inside the allocator.
+fn draw_panic_screen(info: &::core::panic::PanicInfo) {
+    use core::fmt::Write;
+    let mut message = memory::oom::Report::new();
+    let _ = write!(message, "{}", info);
+    draw_failure_screen("KERNEL PANIC", message.as_str());
 }
 
-impl UiLayout {
-    fn from_framebuffer(width: usize, height: usize) -> Self {
-        let tab_height = 38;
-        Self {
-            content_width: width,
-            content_height: height.saturating_sub(tab_height),
-            tab_height,
+/// The panic screen with `title` over `message`, wrapped to the screen
+/// width. Does not allocate, so the out-of-memory handler can use it.
+fn draw_failure_screen(title: &str, message: &str) {
+    use embedded_graphics::{
+        mono_font::{ascii::FONT_10X20, MonoTextStyle},
+        pixelcolor::Rgb888,
+    };
+
+    let Some(fb) = (unsafe { devices::framebuffer::framebuffer::steal_for_panic() }) else {
+        return;
+    };
+    let style = MonoTextStyle::new(&FONT_10X20, Rgb888::new(255, 255, 255));
+    let cols = (fb.width / 10).saturating_sub(4).max(1);
+
+    fb.fill_rect(0, 0, fb.width, fb.height, ui_provider::color::Color::new(0x80, 0, 0));
+    fb.draw_text(title, 20, 40, &style);
+    let mut row = 0;
+    for line in message.lines() {
+        let mut rest = line;
+        loop {
+            let cut = rest.char_indices().nth(cols).map_or(rest.len(), |(idx, _)| idx);
+            fb.draw_text(&rest[..cut], 20, 80 + row * 20, &style);
+            row += 1;
+            rest = &rest[cut..];
+            if rest.is_empty() {
+                break;
+            }
         }
     }
+    fb.render_frame();
+}
 
-    fn app_bounds(&self) -> Rect {
-        Rect::new(0, self.tab_height, self.content_width, self.content_height)
-    }
+/// Reports the failed allocation (see `memory::oom`) and halts. Must not
+/// allocate: the heap just refused.
+#[alloc_error_handler]
+fn alloc_error(layout: ::alloc::alloc::Layout) -> ! {
+    let mut report = memory::oom::Report::new();
+    let _ = memory::oom::describe(layout, &mut report);
+    println!("ALLOC ERROR: {}", report.as_str());
+    draw_failure_screen("OUT OF MEMORY", report.as_str());
+    loop_arch_mm()
+}
 
-    fn tab_bounds(&self, index: usize) -> Rect {
-        let tab_width = self.content_width / 3;
-        let x = index * tab_width;
-        Rect::new(x, 0, tab_width, self.tab_height)
+/// What the top bar should show now. The clock is read from the RTC at
+/// most once a second.
+fn bar_state(bar: &mut TopBar, host: &AppHost, caps_lock: bool) -> BarState {
+    BarState {
+        app: TAB_NAMES
+            .get(host.focused_app_index())
+            .copied()
+            .unwrap_or(""),
+        clock: bar.clock(hpet::monotonic_ms()),
+        mouse: devices::registry::is_up("mouse"),
+        caps_lock,
+        recording: app::macros::recording(),
+        theme: ui_provider::theme::generation(),
     }
 }
 
-fn framebuffer_size() -> (usize, usize) {
-    let guard = FRAMEBUFFER.lock();
-    let fb = guard.as_ref().unwrap();
-    (fb.width, fb.height)
+/// `None` when booted without a framebuffer.
+fn framebuffer_size() -> Option<(usize, usize)> {
+    with_fb_blocking(|fb| (fb.width, fb.height)).ok()
 }
 
 fn draw_tabs(
@@ -126,14 +222,13 @@ fn draw_tabs(
         shape::Rect,
     };
 
-    let tab_names = ["Terminal", "Logs", "Editor"];
     let mut render_list = RenderList::new();
 
     let margin_x = 10usize;
     let margin_y = 6usize;
     let radius = 10usize;
 
-    for idx in 0..3 {
+    for (idx, name) in TAB_NAMES.iter().enumerate() {
         let bounds = layout.tab_bounds(idx);
         let is_focused = idx == focused;
 
@@ -168,10 +263,10 @@ fn draw_tabs(
         } else {
             theme.text
         };
-        let text_x = inner.x + (inner.w.saturating_sub(tab_names[idx].len() * 10) / 2).max(8);
+        let text_x = inner.x + (inner.w.saturating_sub(name.len() * 10) / 2).max(8);
         let text_y = inner.y + (inner.h.saturating_sub(20) / 2).max(2);
         render_list.push(RenderCommand::text(
-            tab_names[idx],
+            *name,
             text_x,
             text_y,
             text_color,
@@ -181,7 +276,7 @@ fn draw_tabs(
     render_list.push(RenderCommand::fill_rect(
         Rect::new(
             0,
-            layout.tab_height.saturating_sub(1),
+            layout.bar_height + layout.tab_height.saturating_sub(1),
             layout.content_width,
             1,
         ),
@@ -191,9 +286,12 @@ fn draw_tabs(
     crate::ui_provider::render::flush_commands(fb, render_list.as_slice());
 }
 
-fn init_ui(theme: &Theme, fb_width: usize, fb_height: usize) -> AppHost {
+fn init_ui(theme: &Theme, fb_width: usize, fb_height: usize, bar: &mut TopBar) -> AppHost {
     let layout = UiLayout::from_framebuffer(fb_width, fb_height);
     let mut host = AppHost::new();
+    memory::pressure::register_trimmer("clipboard", apps::clipboard::trim);
+    memory::pressure::register_trimmer("glyphs", ui_provider::glyphs::trim);
+    layers::register(Layer::Cursor, "mouse cursor", |fb, _| mouse_cursor::draw(fb));
 
     host.register_app(Box::new(TerminalApp::new(
         layout.content_width,
@@ -207,30 +305,38 @@ fn init_ui(theme: &Theme, fb_width: usize, fb_height: usize) -> AppHost {
         layout.content_width,
         layout.content_height,
     )));
+    host.register_app(Box::new(SettingsApp::new(
+        layout.content_width,
+        layout.content_height,
+    )));
+    host.register_app(Box::new(FormDemoApp::new(
+        layout.content_width,
+        layout.content_height,
+    )));
 
     let app_bounds = layout.app_bounds();
-    for idx in 0..3 {
+    for idx in 0..TAB_COUNT {
         host.layout_app(idx, app_bounds);
         host.app_mut(idx).init();
     }
-    {
-        let mut guard = FRAMEBUFFER.lock();
-        let fb = guard.as_mut().unwrap();
+    let state = bar_state(bar, &host, false);
+    bar.update(state, theme);
+    let _ = with_fb_blocking(|fb| {
         fb.clear(theme.background);
         host.compose(theme, theme.accent);
         host.flush(fb);
+        crate::ui_provider::render::flush_commands(fb, bar.commands());
         draw_tabs(fb, &layout, theme, host.focused_app_index());
+        layers::composite(fb, theme, &host.layer_commands());
         fb.render_frame();
-    }
+    });
 
     host
 }
 
-fn handle_global_shortcut(host: &mut AppHost, ch: char) -> bool {
-    let switched = match ch {
-        '\x11' => host.switch_to_app(0), // F1
-        '\x12' => host.switch_to_app(1), // F2
-        '\x13' => host.switch_to_app(2), // F3
+fn handle_global_shortcut(host: &mut AppHost, code: KeyCode) -> bool {
+    let switched = match code {
+        KeyCode::Function(n @ 1..=4) => host.switch_to_app(n as usize - 1),
         _ => false,
     };
 
@@ -241,17 +347,17 @@ fn handle_global_shortcut(host: &mut AppHost, ch: char) -> bool {
     switched
 }
 
-fn handle_alt_shortcut(host: &mut AppHost, ch: char, ctrl: bool, alt: bool) -> (bool, bool) {
-    if !alt || ctrl {
+fn handle_alt_shortcut(host: &mut AppHost, code: KeyCode, mods: Modifiers) -> (bool, bool) {
+    if !mods.alt() || mods.ctrl() {
         return (false, false);
     }
 
-    match ch {
-        '\t' => {
+    match code {
+        KeyCode::Tab => {
             host.cycle_focus();
             (true, true)
         }
-        '1'..='9' => {
+        KeyCode::Char(ch @ '1'..='9') => {
             let app_idx = (ch as usize) - ('1' as usize);
             let switched = host.switch_to_app(app_idx);
             if switched {
@@ -259,30 +365,11 @@ fn handle_alt_shortcut(host: &mut AppHost, ch: char, ctrl: bool, alt: bool) -> (
             }
             (true, switched)
         }
+        KeyCode::Char('s' | 'S') => (true, host.toggle_split()),
         _ => (false, false),
     }
 }
 
-fn key_event_to_app_event(key: ps2_keyboard::KeyEvent) -> AppEvent {
-    if key.is_arrow {
-        AppEvent::KeyPress {
-            ch: '\0',
-            ctrl: key.ctrl,
-            alt: key.alt,
-            shift: key.shift,
-            arrow: key.arrow_direction,
-        }
-    } else {
-        AppEvent::KeyPress {
-            ch: key.character,
-            ctrl: key.ctrl,
-            alt: key.alt,
-            shift: key.shift,
-            arrow: None,
-        }
-    }
-}
-
 fn collect_pending_events(
     host: &mut AppHost,
     decoder: &mut ps2_keyboard::ScancodeDecoder,
@@ -298,136 +385,219 @@ fn collect_pending_events(
         *last_tick += 1;
     }
 
-    while let Some(mouse_event) = ps2_mouse::poll_mouse_event() {
+    let mut mouse_events = Vec::new();
+    ps2_mouse::poll_coalesced(&mut mouse_events);
+    for mouse_event in mouse_events {
         mouse_cursor::update_position(mouse_event.dx, -mouse_event.dy);
 
+        let (mx, my) = mouse_cursor::screen_position();
+        if host.split_mouse(mx, my, mouse_event.left_button()) {
+            need_render = true;
+            continue;
+        }
+
         if mouse_event.buttons != 0 {
-            let (mx, my) = mouse_cursor::get_position();
-            if mx >= 0 && my >= 0 {
-                let mx = mx as usize;
-                let my = my as usize;
-
-                let mut clicked_tab = false;
-                for tab_idx in 0..3 {
-                    let tab_bounds = layout.tab_bounds(tab_idx);
-                    if mx >= tab_bounds.x
-                        && mx < tab_bounds.x + tab_bounds.w
-                        && my >= tab_bounds.y
-                        && my < tab_bounds.y + tab_bounds.h
-                    {
-                        if tab_idx != host.focused_app_index() {
-                            host.switch_to_app(tab_idx);
-                            host.request_redraw();
-                        }
-                        clicked_tab = true;
-                        need_render = true;
-                        break;
+            let mut clicked_tab = false;
+            for tab_idx in 0..TAB_COUNT {
+                let tab_bounds = layout.tab_bounds(tab_idx);
+                if mx >= tab_bounds.x
+                    && mx < tab_bounds.x + tab_bounds.w
+                    && my >= tab_bounds.y
+                    && my < tab_bounds.y + tab_bounds.h
+                {
+                    if tab_idx != host.focused_app_index() {
+                        host.switch_to_app(tab_idx);
+                        host.request_redraw();
                     }
+                    clicked_tab = true;
+                    need_render = true;
+                    break;
                 }
+            }
 
-                if !clicked_tab {
-                    host.handle_mouse_click(mx, my);
-                }
+            if !clicked_tab {
+                host.handle_mouse_click(mx, my);
             }
         }
 
-        pending_events.push(AppEvent::Mouse(mouse_event));
+        pending_events.push(AppEvent::Mouse {
+            event: mouse_event,
+            x: mx,
+            y: my,
+        });
         need_render = true;
     }
 
-    while let Some(scancode) = ps2_keyboard::dequeue_scancode() {
-        if let Some(key) = decoder.process_scancode(scancode) {
-            if handle_global_shortcut(host, key.character) {
-                need_render = true;
-                continue;
-            }
+    let now = hpet::monotonic_ms();
+    while let Some((scancode, tsc)) = ps2_keyboard::dequeue_scancode_timed() {
+        if let Some(mut key) = decoder.process_scancode(scancode) {
+            key.tsc = tsc;
+            stats::latency::note_input(key.tsc);
 
-            let (handled, switched) = handle_alt_shortcut(host, key.character, key.ctrl, key.alt);
-            if handled {
-                need_render |= switched || key.character == '\t';
+            if app::macros::key(key.code, key.mods, now) != Intercept::Pass {
+                need_render = true;
                 continue;
             }
-
-            pending_events.push(key_event_to_app_event(key));
-            need_render = true;
+            need_render |= route_key(host, key.code, key.mods, &mut pending_events);
         }
     }
+    // Replayed keys skip the recorder, so a macro never starts one
+    for key in app::macros::due(now) {
+        need_render |= route_key(host, key.code, key.mods, &mut pending_events);
+    }
 
     (pending_events, need_render)
 }
 
+/// Hands a key to the global shortcuts, or queues it for the focused app.
+/// Returns whether the screen needs redrawing.
+fn route_key(
+    host: &mut AppHost,
+    code: KeyCode,
+    mods: Modifiers,
+    pending_events: &mut Vec<AppEvent>,
+) -> bool {
+    if handle_global_shortcut(host, code) {
+        return true;
+    }
+
+    if mods.contains(Modifiers::CTRL | Modifiers::ALT) && matches!(code, KeyCode::Char('m' | 'M')) {
+        let on = ui_provider::magnifier::toggle();
+        let message = if on { "Magnifier on" } else { "Magnifier off" };
+        log_info!("{}", message);
+        host.notify(message, ui_provider::toast::DEFAULT_TICKS);
+        return true;
+    }
+
+    let (handled, switched) = handle_alt_shortcut(host, code, mods);
+    if handled {
+        return switched || code == KeyCode::Tab;
+    }
+
+    pending_events.push(AppEvent::KeyPress { code, mods });
+    true
+}
+
+/// Composes and presents a frame. Returns whether the flush ran out of
+/// budget and left tiles for `flush_backlog`.
 fn render_pending(
     host: &mut AppHost,
     theme: &Theme,
     layout: &UiLayout,
+    bar: &TopBar,
     pending_events: &mut Vec<AppEvent>,
-) {
+) -> bool {
     for ev in pending_events.drain(..) {
         host.dispatch_event(ev);
     }
 
-    let mut guard = FRAMEBUFFER.lock();
-    let fb = guard.as_mut().unwrap();
-
-    fb.clear(theme.background);
-
-    let focused_idx = host.focused_app_index();
-    let content_bounds = layout.app_bounds();
-    let off_screen = Rect::new(99999, 99999, 1, 1);
+    ui_provider::frame_arena::begin_frame();
+    with_fb_blocking(|fb| {
+        let focused_idx = host.focused_app_index();
+        host.layout_content(layout.app_bounds());
 
-    for idx in 0..3 {
-        if idx != focused_idx {
-            host.layout_app(idx, off_screen);
-        } else {
-            host.layout_app(idx, content_bounds);
-        }
-    }
+        let gone: Vec<_> = gfx::surface::take_exposed().into_iter().collect();
+        host.draw_frame(fb, theme, &[layout.bar_bounds()], &gone);
+        gfx::surface::draw(fb, layout.app_bounds());
 
-    host.compose(theme, theme.accent);
-    host.flush(fb);
+        ui_provider::render::flush_commands(fb, bar.commands());
+        draw_tabs(fb, layout, theme, focused_idx);
 
-    draw_tabs(fb, layout, theme, focused_idx);
+        ui_provider::magnifier::sample(fb);
+        layers::composite(fb, theme, &host.layer_commands());
+        mouse_cursor::mark_drawn();
 
-    mouse_cursor::draw(fb);
+        stats::latency::apply_render_delay();
+        let left = fb.render_frame_within(framebuffer::flush_budget_us());
+        stats::latency::frame_presented();
+        left > 0
+    })
+    .unwrap_or(false)
+}
 
-    fb.render_frame();
+/// Copies more of the tiles the last frame had no time for, without
+/// composing a new one. Returns whether any are still left.
+fn flush_backlog() -> bool {
+    with_fb_blocking(|fb| fb.render_frame_within(framebuffer::flush_budget_us()) > 0)
+        .unwrap_or(false)
 }
 
 pub fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
-    unsafe {
-        if let Err(e) = memory::init(boot_info) {
-            println!("PANIC: Failed to init memory: {}", e);
-            loop_arch_mm();
-        }
-    }
+    let _ = kcore::kernel::init_kernel(boot_info);
 
-    let _ = kcore::kernel::init_kernel();
-    init_framebuffer(boot_info);
+    #[cfg(test)]
+    test_main();
 
-    let theme = Theme::dark_modern();
-    let (fb_width, fb_height) = framebuffer_size();
-    let layout = UiLayout::from_framebuffer(fb_width, fb_height);
+    #[cfg(feature = "ci-test")]
+    tests::ci::run_and_exit();
 
-    mouse_cursor::init(fb_width, fb_height);
+    let theme = Theme::current();
+    let Some((fb_width, fb_height)) = framebuffer_size() else {
+        headless::run();
+    };
+    let layout = UiLayout::from_framebuffer(fb_width, fb_height);
+    if let Err(why) = layout.check(fb_width, fb_height) {
+        log_warn!("layout: {} ({}x{})", why, fb_width, fb_height);
+        debug_assert!(false, "layout self-check failed: {}", why);
+    }
 
-    let mut host = init_ui(&theme, fb_width, fb_height);
+    let mut bar = TopBar::new(layout.bar_bounds());
+    let Ok(mut host) = run_stage(Stage::Apps, || {
+        mouse_cursor::init(fb_width, fb_height);
+        Ok(init_ui(&theme, fb_width, fb_height, &mut bar))
+    }) else {
+        headless::run();
+    };
     let mut decoder = ps2_keyboard::ScancodeDecoder::new();
     let mut last_tick = TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed);
 
     log_info!("Kernel ready");
-    log_info!("F1=Terminal, F2=Logs, F3=Editor, Shift+Enter=Execute/Run");
+    log_info!("F1=Terminal, F2=Logs, F3=Editor, F4=Settings, Shift+Enter=Execute/Run");
+    log_info!("Alt+Tab=Next app, Alt+S=Split with the next app (drag the divider to resize)");
+    log_info!("Ctrl+Alt+M=Magnifier, Ctrl+Alt+R=Record a macro (F5-F8 replay)");
 
-    loop {
-        let (mut pending_events, input_requested_redraw) =
-            collect_pending_events(&mut host, &mut decoder, &layout, &mut last_tick);
+    let mut pacer = FramePacer::new();
+    // Ticks held back by the frame cap, dispatched with the next frame.
+    let mut held_events: Vec<AppEvent> = Vec::new();
+    // The last flush left tiles behind.
+    let mut flushing = false;
 
-        let debug_requested_redraw = debug_pipeline::is_dirty();
-        let cursor_requested_redraw = mouse_cursor::needs_redraw();
+    loop {
+        kcore::task::run_pending();
+        // Flagged by the allocator; run here, outside its lock
+        if let Some(level) = memory::pressure::take_request() {
+            let mut summary = memory::pressure::trim(level);
+            summary.add("apps", host.trim(level));
+            memory::pressure::finish(summary);
+        }
 
-        if true {
-            render_pending(&mut host, &theme, &layout, &mut pending_events);
+        let (pending_events, input_requested_redraw) =
+            collect_pending_events(&mut host, &mut decoder, &layout, &mut last_tick);
+        // A new app name, clock second or status word
+        let state = bar_state(&mut bar, &host, decoder.caps_lock());
+        let bar_changed = bar.update(state, &Theme::current());
+        let input_driven = input_requested_redraw
+            || bar_changed
+            || gfx::surface::take_damage()
+            || debug_pipeline::is_dirty()
+            || mouse_cursor::needs_redraw()
+            || pending_events.iter().any(|ev| !matches!(ev, AppEvent::Tick));
+        held_events.extend(pending_events);
+
+        let now = hpet::monotonic_ms();
+        if flushing && !input_driven {
+            // Ticks wait in `held_events` until the backlog is out
+            flushing = flush_backlog();
+        } else if pacer.decide(now, pacing::cap(), input_driven) == Pace::Present {
+            // Per frame, so that `contrast` and `color` take effect at once.
+            flushing =
+                render_pending(&mut host, &Theme::current(), &layout, &bar, &mut held_events);
+            pacer.presented(now);
         }
 
-        x86_64::instructions::hlt();
+        // With a backlog, go round again rather than wait for an interrupt
+        if !flushing {
+            stats::idle::halt();
+        }
     }
 }
@@ -0,0 +1,224 @@
+//! # Address Space Teardown
+//!
+//! Inverse of `create_process_page_table`: `destroy_address_space` walks the
+//! lower half of a P4, frees every mapped frame, then the P1/P2/P3 tables
+//! bottom-up and finally the P4 itself. Upper-half entries are shared with
+//! the kernel and never looked at.
+//!
+//! Page-table frames go through `alloc_table_frame` and are counted on both
+//! sides, so `meminfo` shows tables that were allocated but never released.
+//! Only lower-half tables of destroyed spaces are ever freed; tables behind
+//! kernel mappings stay live for good.
+//!
+//! Leaf frames are freed unconditionally. Once copy-on-write sharing exists
+//! this is where its refcounts have to be honoured.
+
+use super::{access_page_table, allocate_frame, free_frame, zero_frame, MemError};
+use alloc::{format, string::String};
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{
+    registers::control::Cr3,
+    structures::paging::{PageTable, PageTableFlags, PhysFrame, Size4KiB},
+    PhysAddr,
+};
+
+static TABLE_FRAMES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static TABLE_FRAMES_FREED: AtomicU64 = AtomicU64::new(0);
+
+/// First P4 index of the kernel half.
+const KERNEL_P4_START: usize = 256;
+
+/// What a teardown released.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Teardown {
+    /// P4/P3/P2/P1 table frames.
+    pub tables: usize,
+    /// Mapped 4 KiB frames, counting the parts of huge pages.
+    pub frames: usize,
+    /// Mapped frames the frame allocator does not own (MMIO and the like),
+    /// left alone.
+    pub foreign: usize,
+}
+
+/// Allocates and zeroes a frame for a page table.
+pub fn alloc_table_frame() -> Option<PhysFrame<Size4KiB>> {
+    let frame = allocate_frame()?;
+    zero_frame(frame);
+    TABLE_FRAMES_ALLOCATED.fetch_add(1, Ordering::Relaxed);
+    Some(frame)
+}
+
+fn free_table_frame(frame: PhysFrame<Size4KiB>, stats: &mut Teardown) {
+    if free_frame(frame) {
+        TABLE_FRAMES_FREED.fetch_add(1, Ordering::Relaxed);
+        stats.tables += 1;
+    }
+}
+
+/// Table frames allocated and freed since boot.
+pub fn table_frame_counts() -> (u64, u64) {
+    (
+        TABLE_FRAMES_ALLOCATED.load(Ordering::Relaxed),
+        TABLE_FRAMES_FREED.load(Ordering::Relaxed),
+    )
+}
+
+fn free_leaf(start: PhysAddr, size: u64, stats: &mut Teardown) {
+    for offset in (0..size).step_by(4096) {
+        let frame = PhysFrame::containing_address(start + offset);
+        if free_frame(frame) {
+            stats.frames += 1;
+        } else {
+            stats.foreign += 1;
+        }
+    }
+}
+
+/// Frees everything below the table at `table_phys`. `level` is 3 for a
+/// P3, down to 1 for a P1. The table frame itself is left to the caller.
+unsafe fn free_subtree(table_phys: PhysAddr, level: u8, stats: &mut Teardown) {
+    let table: &mut PageTable = access_page_table(table_phys);
+    for entry in table.iter_mut() {
+        let flags = entry.flags();
+        if !flags.contains(PageTableFlags::PRESENT) {
+            continue;
+        }
+        let addr = entry.addr();
+        if level == 1 {
+            free_leaf(addr, 4096, stats);
+        } else if flags.contains(PageTableFlags::HUGE_PAGE) {
+            // 1 GiB in a P3, 2 MiB in a P2
+            let size = if level == 3 { 1 << 30 } else { 2 << 20 };
+            free_leaf(addr, size, stats);
+        } else {
+            free_subtree(addr, level - 1, stats);
+            free_table_frame(PhysFrame::containing_address(addr), stats);
+        }
+        entry.set_unused();
+    }
+}
+
+/// Tears down the address space rooted at `p4` and frees its P4.
+///
+/// Works on partially built spaces: only present entries are followed, and
+/// each entry is cleared once its subtree is gone. Refuses the active
+/// address space with `PermissionDenied`.
+pub fn destroy_address_space(p4: PhysFrame<Size4KiB>) -> Result<Teardown, MemError> {
+    let (active, _) = Cr3::read();
+    if p4 == active {
+        return Err(MemError::PermissionDenied);
+    }
+
+    let mut stats = Teardown::default();
+    unsafe {
+        let table = access_page_table(p4.start_address());
+        for entry in table.iter_mut().take(KERNEL_P4_START) {
+            if !entry.flags().contains(PageTableFlags::PRESENT) {
+                continue;
+            }
+            let p3 = entry.addr();
+            free_subtree(p3, 3, &mut stats);
+            free_table_frame(PhysFrame::containing_address(p3), &mut stats);
+            entry.set_unused();
+        }
+    }
+    free_table_frame(p4, &mut stats);
+    Ok(stats)
+}
+
+/// Frame allocator and page-table accounting for `meminfo`.
+pub fn report() -> String {
+    use super::{PHYSICAL_MEMORY_END, PHYSICAL_MEMORY_START};
+
+    let start = PHYSICAL_MEMORY_START.load(Ordering::SeqCst);
+    let end = PHYSICAL_MEMORY_END.load(Ordering::SeqCst);
+    let (allocated, freed) = table_frame_counts();
+
+    let mut out = format!(
+        "Frame window  {:#x}-{:#x} ({} frames)\n",
+        start,
+        end,
+        (end - start) / 4096
+    );
+    out.push_str(&format!(
+        "Frames        {} in use, {} free, longest free run {}\n",
+        super::used_frame_count(),
+        super::free_frame_count(),
+        super::largest_free_run()
+    ));
+    out.push_str(&format!(
+        "Page tables   {} allocated, {} freed, {} live\n",
+        allocated,
+        freed,
+        allocated - freed
+    ));
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{create_process_page_table, map_page_in};
+    use x86_64::VirtAddr;
+
+    const SPACES: usize = 50;
+    const MAPPINGS: u64 = 32;
+
+    /// Spreads mappings over several P1s, P2s and two P4 slots.
+    fn mapping_addr(i: u64) -> VirtAddr {
+        let base = if i % 4 == 0 {
+            0x80_0000_0000
+        } else {
+            0x40_0000
+        };
+        VirtAddr::new(base + i * 0x20_1000)
+    }
+
+    #[test_case]
+    fn create_and_destroy_does_not_leak_tables() {
+        let (alloc_before, freed_before) = table_frame_counts();
+        let free_before = crate::memory::free_frame_count();
+
+        for _ in 0..SPACES {
+            let p4 = create_process_page_table().expect("p4");
+            let flags = PageTableFlags::PRESENT
+                | PageTableFlags::WRITABLE
+                | PageTableFlags::USER_ACCESSIBLE;
+            for i in 0..MAPPINGS {
+                let frame = allocate_frame().expect("frame");
+                map_page_in(p4, mapping_addr(i), frame, flags).expect("map");
+            }
+            let stats = destroy_address_space(p4).expect("destroy");
+            assert_eq!(stats.frames, MAPPINGS as usize);
+            assert_eq!(stats.foreign, 0);
+        }
+
+        let (alloc_after, freed_after) = table_frame_counts();
+        assert!(alloc_after - alloc_before > SPACES as u64);
+        assert_eq!(alloc_after - alloc_before, freed_after - freed_before);
+        // every frame used by the test was freed again
+        assert!(crate::memory::free_frame_count() >= free_before);
+    }
+
+    #[test_case]
+    fn destroy_handles_empty_space_and_refuses_active() {
+        let (alloc_before, freed_before) = table_frame_counts();
+        let p4 = create_process_page_table().expect("p4");
+        let stats = destroy_address_space(p4).expect("destroy");
+        assert_eq!(
+            stats,
+            Teardown {
+                tables: 1,
+                frames: 0,
+                foreign: 0
+            }
+        );
+        let (alloc_after, freed_after) = table_frame_counts();
+        assert_eq!(alloc_after - alloc_before, freed_after - freed_before);
+
+        let (active, _) = Cr3::read();
+        assert!(destroy_address_space(active).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! # Allocation Tracker
+//!
+//! Leak hunting for the kernel heap, enabled with the `alloc-track` feature.
+//! `LockedHeap` records every live allocation here with its size, a
+//! sequence number and a caller tag; `leak_report` (the `leaks` command)
+//! groups what is still outstanding by caller.
+//!
+//! ## Recursion
+//!
+//! The table is a fixed open-addressing hash map in a static array, so
+//! recording never allocates. The report copies its summary onto the stack
+//! and releases the table lock before building any `String`. An allocation
+//! that happens while the table is locked (an interrupt handler, say) is
+//! counted as missed instead of spinning on the lock.
+//!
+//! ## Caller tags
+//!
+//! The tag is a short chain of return addresses from
+//! `stats::backtrace`. Without frame pointers the walk stops early and
+//! tags are mostly zero.
+//!
+//! ## Leak workflow
+//!
+//! `leaks mark`, run the suspect commands a few times, then `leaks`: only
+//! allocations made after the mark are listed.
+
+use alloc::{format, string::String};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use spin::Mutex;
+
+const CAPACITY: usize = 8192;
+const CALLER_DEPTH: usize = 4;
+/// Frames between the walk and the real caller: `LockedHeap::alloc`'s own
+/// return into the `__rust_alloc` shim is not interesting.
+const SKIP_FRAMES: usize = 1;
+/// Distinct caller chains shown by the report.
+const REPORT_GROUPS: usize = 32;
+
+type Callers = [usize; CALLER_DEPTH];
+
+#[derive(Clone, Copy)]
+struct Entry {
+    /// 0 marks an empty slot; the heap never hands out address 0.
+    addr: usize,
+    size: usize,
+    seq: u64,
+    callers: Callers,
+}
+
+const EMPTY: Entry = Entry {
+    addr: 0,
+    size: 0,
+    seq: 0,
+    callers: [0; CALLER_DEPTH],
+};
+
+struct Table {
+    slots: [Entry; CAPACITY],
+    live: usize,
+    live_bytes: usize,
+}
+
+static TABLE: Mutex<Table> = Mutex::new(Table {
+    slots: [EMPTY; CAPACITY],
+    live: 0,
+    live_bytes: 0,
+});
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+static MARK: AtomicU64 = AtomicU64::new(0);
+/// Allocations not recorded because the table was full or busy.
+static MISSED: AtomicUsize = AtomicUsize::new(0);
+
+impl Table {
+    fn home(addr: usize) -> usize {
+        // Fibonacci hashing; heap addresses are at least 8-aligned.
+        ((addr >> 3).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 51) % CAPACITY
+    }
+
+    fn insert(&mut self, entry: Entry) -> bool {
+        if self.live == CAPACITY - 1 {
+            return false;
+        }
+        let mut idx = Self::home(entry.addr);
+        while self.slots[idx].addr != 0 && self.slots[idx].addr != entry.addr {
+            idx = (idx + 1) % CAPACITY;
+        }
+        if self.slots[idx].addr == 0 {
+            self.live += 1;
+        } else {
+            self.live_bytes -= self.slots[idx].size;
+        }
+        self.live_bytes += entry.size;
+        self.slots[idx] = entry;
+        true
+    }
+
+    fn remove(&mut self, addr: usize) {
+        let mut idx = Self::home(addr);
+        loop {
+            match self.slots[idx].addr {
+                0 => return,
+                a if a == addr => break,
+                _ => idx = (idx + 1) % CAPACITY,
+            }
+        }
+        self.live -= 1;
+        self.live_bytes -= self.slots[idx].size;
+        self.slots[idx] = EMPTY;
+
+        // Backward-shift deletion keeps probe chains intact without tombstones.
+        let mut hole = idx;
+        let mut next = (hole + 1) % CAPACITY;
+        while self.slots[next].addr != 0 {
+            let home = Self::home(self.slots[next].addr);
+            let dist_next = (next + CAPACITY - home) % CAPACITY;
+            let dist_hole = (hole + CAPACITY - home) % CAPACITY;
+            if dist_hole < dist_next {
+                self.slots[hole] = self.slots[next];
+                self.slots[next] = EMPTY;
+                hole = next;
+            }
+            next = (next + 1) % CAPACITY;
+        }
+    }
+}
+
+#[inline(always)]
+fn capture_callers() -> Callers {
+    let mut callers = [0; CALLER_DEPTH];
+    crate::stats::backtrace::callers(SKIP_FRAMES, &mut callers);
+    callers
+}
+
+pub fn record_alloc(addr: usize, size: usize) {
+    let callers = capture_callers();
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    let recorded = match TABLE.try_lock() {
+        Some(mut table) => table.insert(Entry {
+            addr,
+            size,
+            seq,
+            callers,
+        }),
+        None => false,
+    };
+    if !recorded {
+        MISSED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn record_dealloc(addr: usize) {
+    // A busy table leaves a stale entry behind; it shows up as a leak that
+    // `leaks clear` discards.
+    if let Some(mut table) = TABLE.try_lock() {
+        table.remove(addr);
+    }
+}
+
+/// Only allocations made after this call are reported.
+pub fn mark() {
+    MARK.store(NEXT_SEQ.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Forgets everything currently tracked, e.g. entries left stale by a
+/// missed dealloc.
+pub fn clear() {
+    let mut table = TABLE.lock();
+    table.slots.fill(EMPTY);
+    table.live = 0;
+    table.live_bytes = 0;
+    MISSED.store(0, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy)]
+struct Group {
+    callers: Callers,
+    count: usize,
+    bytes: usize,
+}
+
+pub fn leak_report() -> String {
+    let mark = MARK.load(Ordering::Relaxed);
+    let mut groups = [Group {
+        callers: [0; CALLER_DEPTH],
+        count: 0,
+        bytes: 0,
+    }; REPORT_GROUPS];
+    let mut used = 0;
+    let mut other = (0usize, 0usize);
+
+    // Summarize on the stack; no allocation while the table is locked.
+    let (live, live_bytes) = {
+        let table = TABLE.lock();
+        for e in table.slots.iter().filter(|e| e.addr != 0 && e.seq >= mark) {
+            match groups[..used].iter_mut().find(|g| g.callers == e.callers) {
+                Some(g) => {
+                    g.count += 1;
+                    g.bytes += e.size;
+                }
+                None if used < REPORT_GROUPS => {
+                    groups[used] = Group {
+                        callers: e.callers,
+                        count: 1,
+                        bytes: e.size,
+                    };
+                    used += 1;
+                }
+                None => {
+                    other.0 += 1;
+                    other.1 += e.size;
+                }
+            }
+        }
+        (table.live, table.live_bytes)
+    };
+
+    let groups = &mut groups[..used];
+    groups.sort_unstable_by_key(|g| core::cmp::Reverse(g.bytes));
+
+    let mut out = format!(
+        "Live allocations: {} ({} bytes), missed: {}\n",
+        live,
+        live_bytes,
+        MISSED.load(Ordering::Relaxed)
+    );
+    if mark > 0 {
+        out.push_str(&format!("Outstanding since mark (seq {}):\n", mark));
+    } else {
+        out.push_str("Outstanding since boot:\n");
+    }
+    for g in groups.iter() {
+        out.push_str(&format!("  {:>5}x {:>9} B  ", g.count, g.bytes));
+        for (i, addr) in g.callers.iter().take_while(|&&a| a != 0).enumerate() {
+            if i > 0 {
+                out.push_str(" <- ");
+            }
+            out.push_str(&format!("{:#x}", addr));
+        }
+        if g.callers[0] == 0 {
+            out.push_str("(no frame)");
+        }
+        out.push('\n');
+    }
+    if other.0 > 0 {
+        out.push_str(&format!("  {:>5}x {:>9} B  (other callers)\n", other.0, other.1));
+    }
+    out
+}
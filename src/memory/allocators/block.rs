@@ -1,4 +1,5 @@
 use super::linked_list::LinkedListAllocator;
+use super::poison::{self, PoisonError};
 #[allow(unused_imports)]
 use crate::memory::allocators::core::{
     align_down, align_up, validate_region, AllocError, SpinLock,
@@ -20,6 +21,7 @@ pub struct BlockNode {
 struct FixedSizeBlockAllocatorInner {
     list_heads: [Option<NonNull<BlockNode>>; BLOCK_SIZES.len()],
     fallback: LinkedListAllocator,
+    poison: bool,
 }
 
 /// Fixed-size block allocator with fallback
@@ -43,6 +45,19 @@ impl FixedSizeBlockAllocator {
             inner: UnsafeCell::new(FixedSizeBlockAllocatorInner {
                 list_heads: [None; BLOCK_SIZES.len()],
                 fallback: LinkedListAllocator::new(),
+                poison: false,
+            }),
+            lock: SpinLock::new(),
+        }
+    }
+
+    /// Like `new`, with use-after-free poisoning (see `poison`).
+    pub const fn poisoned() -> Self {
+        Self {
+            inner: UnsafeCell::new(FixedSizeBlockAllocatorInner {
+                list_heads: [None; BLOCK_SIZES.len()],
+                fallback: LinkedListAllocator::poisoned(),
+                poison: true,
             }),
             lock: SpinLock::new(),
         }
@@ -66,33 +81,50 @@ impl FixedSizeBlockAllocator {
     }
 }
 
-unsafe impl GlobalAlloc for FixedSizeBlockAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+impl FixedSizeBlockAllocator {
+    /// `alloc` that reports overwritten poison instead of panicking, so the
+    /// caller can drop its own locks first. Never fails without poisoning.
+    ///
+    /// # Safety
+    /// Same contract as `GlobalAlloc::alloc`.
+    pub unsafe fn try_alloc(&self, layout: Layout) -> Result<*mut u8, PoisonError> {
         if layout.size() == 0 {
-            return ptr::null_mut();
+            return Ok(ptr::null_mut());
         }
 
         match Self::list_index(&layout) {
             Some(idx) => self.lock.with_lock(|| {
                 let inner = &mut *self.inner.get();
+                let block_size = BLOCK_SIZES[idx];
 
                 if let Some(mut node_ptr) = inner.list_heads[idx] {
                     let node = node_ptr.as_mut();
                     inner.list_heads[idx] = node.next;
-                    node_ptr.as_ptr() as *mut u8
+                    let block = node_ptr.as_ptr() as usize;
+                    if inner.poison {
+                        let skip = core::mem::size_of::<BlockNode>();
+                        poison::check_freed(block, skip, block_size)?;
+                        poison::fill(block, block_size, poison::ALLOC_BYTE);
+                    }
+                    Ok(block as *mut u8)
                 } else {
-                    let block_size = BLOCK_SIZES[idx];
                     let block_layout =
                         Layout::from_size_align(block_size, block_size).unwrap_or(layout);
-                    inner.fallback.alloc(block_layout)
+                    inner.fallback.try_alloc(block_layout)
                 }
             }),
             None => self.lock.with_lock(|| {
                 let inner = &mut *self.inner.get();
-                inner.fallback.alloc(layout)
+                inner.fallback.try_alloc(layout)
             }),
         }
     }
+}
+
+unsafe impl GlobalAlloc for FixedSizeBlockAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.try_alloc(layout).unwrap_or_else(|err| poison::fail(err))
+    }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         if ptr.is_null() {
@@ -102,6 +134,9 @@ unsafe impl GlobalAlloc for FixedSizeBlockAllocator {
         match Self::list_index(&layout) {
             Some(idx) => self.lock.with_lock(|| {
                 let inner = &mut *self.inner.get();
+                if inner.poison {
+                    poison::fill(ptr as usize, BLOCK_SIZES[idx], poison::FREE_BYTE);
+                }
                 let node_ptr = ptr as *mut BlockNode;
                 (*node_ptr).next = inner.list_heads[idx];
                 inner.list_heads[idx] = NonNull::new(node_ptr);
@@ -115,3 +150,74 @@ unsafe impl GlobalAlloc for FixedSizeBlockAllocator {
         }
     }
 }
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_HEAP_SIZE: usize = 16 * 1024;
+
+    #[repr(align(4096))]
+    struct TestHeap([u8; TEST_HEAP_SIZE]);
+
+    fn poisoned_heap(buf: &'static mut TestHeap) -> FixedSizeBlockAllocator {
+        let heap = FixedSizeBlockAllocator::poisoned();
+        unsafe { heap.init(buf.0.as_mut_ptr() as usize, TEST_HEAP_SIZE) }.unwrap();
+        heap
+    }
+
+    #[test_case]
+    fn clean_reuse_passes_and_is_filled() {
+        static mut HEAP: TestHeap = TestHeap([0; TEST_HEAP_SIZE]);
+        let heap = poisoned_heap(unsafe { &mut *core::ptr::addr_of_mut!(HEAP) });
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        unsafe {
+            let p = heap.try_alloc(layout).unwrap();
+            assert_eq!(*p.add(63), poison::ALLOC_BYTE);
+            heap.dealloc(p, layout);
+            assert_eq!(*p.add(63), poison::FREE_BYTE);
+
+            let q = heap.try_alloc(layout).unwrap();
+            assert_eq!(q, p);
+            assert_eq!(*q.add(8), poison::ALLOC_BYTE);
+        }
+    }
+
+    #[test_case]
+    fn write_after_free_is_detected() {
+        static mut HEAP: TestHeap = TestHeap([0; TEST_HEAP_SIZE]);
+        let heap = poisoned_heap(unsafe { &mut *core::ptr::addr_of_mut!(HEAP) });
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        unsafe {
+            let p = heap.try_alloc(layout).unwrap();
+            heap.dealloc(p, layout);
+            *p.add(32) = 0x42;
+
+            let err = heap.try_alloc(layout).unwrap_err();
+            assert_eq!(err.block, p as usize);
+            assert_eq!(err.offset, 32);
+            assert_eq!(err.found, 0x42);
+        }
+    }
+
+    #[test_case]
+    fn write_after_free_in_fallback_is_detected() {
+        static mut HEAP: TestHeap = TestHeap([0; TEST_HEAP_SIZE]);
+        let heap = poisoned_heap(unsafe { &mut *core::ptr::addr_of_mut!(HEAP) });
+        // Larger than any block class, so it is served by the linked list.
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+
+        unsafe {
+            let p = heap.try_alloc(layout).unwrap();
+            heap.dealloc(p, layout);
+            *p.add(1000) = 0;
+
+            let err = heap.try_alloc(layout).unwrap_err();
+            assert_eq!(err.offset, 1000);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::memory::allocators::core::{
+    align_down, align_up, validate_region, AllocError, SpinLock,
+};
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::ptr::{self, NonNull};
+
+// ============================================================================
+// 7. BUDDY ALLOCATOR (power-of-two blocks, merged on free)
+// ============================================================================
+
+/// Smallest block handed out; order 0.
+pub const MIN_BLOCK: usize = 64;
+/// Orders 0..ORDERS, so the largest block is `MIN_BLOCK << (ORDERS - 1)`.
+pub const ORDERS: usize = 26;
+
+struct FreeBlock {
+    next: Option<NonNull<FreeBlock>>,
+}
+
+struct BuddyAllocatorInner {
+    base: usize,
+    len: usize,
+    free_lists: [Option<NonNull<FreeBlock>>; ORDERS],
+    free_bytes: usize,
+    initialized: bool,
+}
+
+/// A buddy allocator over one region
+/// Best for: page-sized and larger blocks that should come back together
+/// when freed
+///
+/// Every block is `MIN_BLOCK << order` bytes and sits at an offset from
+/// the region start that is a multiple of its size. A block's buddy is
+/// the other half of the block twice its size, at `offset ^ size`; when
+/// both are free they merge, and so on up the orders.
+///
+/// # Safety
+/// - Must call `init()` before use
+/// - Blocks are as aligned as the region start allows; `init` with a
+///   page-aligned start for page-aligned blocks
+/// - Thread-safe through spin lock
+pub struct BuddyAllocator {
+    inner: UnsafeCell<BuddyAllocatorInner>,
+    lock: SpinLock,
+}
+
+// Safety: The UnsafeCell is protected by SpinLock
+unsafe impl Sync for BuddyAllocator {}
+
+impl BuddyAllocator {
+    pub const fn new() -> Self {
+        Self {
+            inner: UnsafeCell::new(BuddyAllocatorInner {
+                base: 0,
+                len: 0,
+                free_lists: [None; ORDERS],
+                free_bytes: 0,
+                initialized: false,
+            }),
+            lock: SpinLock::new(),
+        }
+    }
+
+    /// Initialize the allocator with a memory region. A region that is not
+    /// a power of two is covered by the largest blocks that fit.
+    ///
+    /// # Safety
+    /// - `heap_start` must point to valid, unused memory
+    /// - Must only be called once
+    pub unsafe fn init(&self, heap_start: usize, heap_size: usize) -> Result<(), AllocError> {
+        validate_region(heap_start, heap_size)?;
+
+        let base = align_up(heap_start, MIN_BLOCK);
+        let len = align_down((heap_start + heap_size).saturating_sub(base), MIN_BLOCK);
+        if len == 0 {
+            return Err(AllocError::InvalidSize);
+        }
+
+        self.lock.with_lock(|| {
+            let inner = &mut *self.inner.get();
+
+            if inner.initialized {
+                return Err(AllocError::InvalidAddress); // Already initialized
+            }
+
+            inner.base = base;
+            inner.len = len;
+            let mut offset = 0;
+            while offset < len {
+                let order = (0..ORDERS)
+                    .rev()
+                    .find(|&order| {
+                        let size = block_size(order);
+                        offset.is_multiple_of(size) && offset + size <= len
+                    })
+                    .unwrap_or(0);
+                inner.push(order, base + offset);
+                offset += block_size(order);
+            }
+            inner.initialized = true;
+            Ok(())
+        })
+    }
+
+    /// Free bytes, and the size of the largest free block.
+    pub fn free_space(&self) -> (usize, usize) {
+        self.lock.with_lock(|| {
+            // Safety: under the lock
+            let inner = unsafe { &*self.inner.get() };
+            let largest = (0..ORDERS)
+                .rev()
+                .find(|&order| inner.free_lists[order].is_some())
+                .map_or(0, block_size);
+            (inner.free_bytes, largest)
+        })
+    }
+}
+
+impl Default for BuddyAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn block_size(order: usize) -> usize {
+    MIN_BLOCK << order
+}
+
+/// The order of the smallest block that holds `layout`, if any does.
+fn order_for(layout: &Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align()).max(MIN_BLOCK);
+    let order = (size.next_power_of_two() / MIN_BLOCK).trailing_zeros() as usize;
+    (order < ORDERS).then_some(order)
+}
+
+impl BuddyAllocatorInner {
+    unsafe fn push(&mut self, order: usize, addr: usize) {
+        let block = addr as *mut FreeBlock;
+        block.write(FreeBlock {
+            next: self.free_lists[order],
+        });
+        self.free_lists[order] = NonNull::new(block);
+        self.free_bytes += block_size(order);
+    }
+
+    unsafe fn pop(&mut self, order: usize) -> Option<usize> {
+        let block = self.free_lists[order]?;
+        self.free_lists[order] = block.as_ref().next;
+        self.free_bytes -= block_size(order);
+        Some(block.as_ptr() as usize)
+    }
+
+    /// Takes the block at `addr` off the free list of `order`, if it is on
+    /// it.
+    unsafe fn remove(&mut self, order: usize, addr: usize) -> bool {
+        let mut current = &mut self.free_lists[order];
+        while let Some(mut block) = *current {
+            if block.as_ptr() as usize == addr {
+                *current = block.as_ref().next;
+                self.free_bytes -= block_size(order);
+                return true;
+            }
+            current = &mut block.as_mut().next;
+        }
+        false
+    }
+
+    /// The buddy of the block of `order` at `addr`, if the region has one.
+    fn buddy(&self, order: usize, addr: usize) -> Option<usize> {
+        let buddy = ((addr - self.base) ^ block_size(order)) + self.base;
+        (buddy + block_size(order) <= self.base + self.len).then_some(buddy)
+    }
+
+    /// Puts the block back, merged with its buddy for as long as that is
+    /// free.
+    unsafe fn free(&mut self, mut order: usize, mut addr: usize) {
+        while order + 1 < ORDERS {
+            match self.buddy(order, addr) {
+                Some(buddy) if self.remove(order, buddy) => {
+                    addr = addr.min(buddy);
+                    order += 1;
+                }
+                _ => break,
+            }
+        }
+        self.push(order, addr);
+    }
+}
+
+unsafe impl GlobalAlloc for BuddyAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == 0 {
+            return ptr::null_mut();
+        }
+        let Some(order) = order_for(&layout) else {
+            return ptr::null_mut();
+        };
+
+        self.lock.with_lock(|| {
+            let inner = &mut *self.inner.get();
+
+            if !inner.initialized {
+                return ptr::null_mut();
+            }
+
+            // The smallest free block big enough, split down to size
+            let Some(mut have) = (order..ORDERS).find(|&o| inner.free_lists[o].is_some()) else {
+                return ptr::null_mut();
+            };
+            let addr = inner.pop(have).unwrap();
+            while have > order {
+                have -= 1;
+                inner.push(have, addr + block_size(have));
+            }
+
+            if !addr.is_multiple_of(layout.align()) {
+                // Only when the region start is less aligned than asked
+                inner.free(order, addr);
+                return ptr::null_mut();
+            }
+            addr as *mut u8
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ptr.is_null() {
+            return;
+        }
+        let Some(order) = order_for(&layout) else {
+            return;
+        };
+
+        self.lock.with_lock(|| {
+            let inner = &mut *self.inner.get();
+
+            if !inner.initialized {
+                return;
+            }
+
+            inner.free(order, ptr as usize);
+        });
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGION: usize = 4096;
+
+    #[repr(align(4096))]
+    struct Region([u8; REGION]);
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, 8).unwrap()
+    }
+
+    #[test_case]
+    fn freed_buddies_merge_into_the_larger_block() {
+        static mut BUF: Region = Region([0; REGION]);
+        let base = unsafe { core::ptr::addr_of_mut!(BUF) } as usize;
+        let buddy = BuddyAllocator::new();
+        unsafe {
+            buddy.init(base, REGION).unwrap();
+            assert_eq!(buddy.free_space(), (REGION, REGION));
+
+            let a = buddy.alloc(layout(2048));
+            let b = buddy.alloc(layout(2048));
+            assert_eq!((a as usize, b as usize), (base, base + 2048));
+            assert!(buddy.alloc(layout(REGION)).is_null());
+
+            buddy.dealloc(a, layout(2048));
+            assert!(buddy.alloc(layout(REGION)).is_null());
+            buddy.dealloc(b, layout(2048));
+            assert_eq!(buddy.free_space(), (REGION, REGION));
+            assert_eq!(buddy.alloc(layout(REGION)) as usize, base);
+        }
+    }
+
+    #[test_case]
+    fn only_buddies_merge() {
+        static mut BUF: Region = Region([0; REGION]);
+        let base = unsafe { core::ptr::addr_of_mut!(BUF) } as usize;
+        let buddy = BuddyAllocator::new();
+        unsafe {
+            buddy.init(base, REGION).unwrap();
+            let blocks: [*mut u8; 4] = core::array::from_fn(|_| buddy.alloc(layout(1024)));
+
+            // 1 and 2 are neighbours but not buddies
+            buddy.dealloc(blocks[1], layout(1024));
+            buddy.dealloc(blocks[2], layout(1024));
+            assert_eq!(buddy.free_space(), (2048, 1024));
+            assert!(buddy.alloc(layout(2048)).is_null());
+
+            buddy.dealloc(blocks[0], layout(1024));
+            assert_eq!(buddy.free_space(), (3072, 2048));
+            assert_eq!(buddy.alloc(layout(2048)) as usize, base);
+        }
+    }
+
+    #[test_case]
+    fn uneven_region_and_page_alignment() {
+        static mut BUF: Region = Region([0; REGION]);
+        let base = unsafe { core::ptr::addr_of_mut!(BUF) } as usize;
+        let buddy = BuddyAllocator::new();
+        unsafe {
+            // 3 KiB: a 2 KiB and a 1 KiB block
+            buddy.init(base, 3072).unwrap();
+            assert_eq!(buddy.free_space(), (3072, 2048));
+            assert!(buddy.alloc(layout(3072)).is_null());
+
+            let page = Layout::from_size_align(64, 2048).unwrap();
+            let ptr = buddy.alloc(page);
+            assert_eq!(ptr as usize, base);
+            buddy.dealloc(ptr, page);
+            assert_eq!(buddy.free_space(), (3072, 2048));
+        }
+    }
+}
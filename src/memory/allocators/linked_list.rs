@@ -2,6 +2,7 @@
 use crate::memory::allocators::core::{
     align_down, align_up, validate_region, AllocError, SpinLock,
 };
+use super::poison::{self, PoisonError};
 use core::alloc::{GlobalAlloc, Layout};
 use core::cell::UnsafeCell;
 use core::ptr::{self, NonNull};
@@ -28,9 +29,12 @@ impl ListNode {
     }
 }
 
+const NODE_SIZE: usize = core::mem::size_of::<ListNode>();
+
 struct LinkedListAllocatorInner {
     head: Option<NonNull<ListNode>>,
     initialized: bool,
+    poison: bool,
 }
 
 /// A linked list allocator with proper synchronization
@@ -53,6 +57,19 @@ impl LinkedListAllocator {
             inner: UnsafeCell::new(LinkedListAllocatorInner {
                 head: None,
                 initialized: false,
+                poison: false,
+            }),
+            lock: SpinLock::new(),
+        }
+    }
+
+    /// Like `new`, with use-after-free poisoning (see `poison`).
+    pub const fn poisoned() -> Self {
+        Self {
+            inner: UnsafeCell::new(LinkedListAllocatorInner {
+                head: None,
+                initialized: false,
+                poison: true,
             }),
             lock: SpinLock::new(),
         }
@@ -78,6 +95,9 @@ impl LinkedListAllocator {
                 return Err(AllocError::InvalidAddress); // Already initialized
             }
 
+            if inner.poison {
+                poison::fill(heap_start, heap_size, poison::FREE_BYTE);
+            }
             let node_ptr = heap_start as *mut ListNode;
             node_ptr.write(ListNode::new(heap_size));
             inner.head = NonNull::new(node_ptr);
@@ -114,7 +134,9 @@ impl LinkedListAllocator {
     }
 
     /// Merge two free regions that touch in physical address order. Returns true if one merge happened.
-    unsafe fn merge_adjacent_once(head: &mut Option<NonNull<ListNode>>) -> bool {
+    /// With `poisoned`, the header of the absorbed region is poisoned too, as
+    /// it becomes plain free memory inside the merged one.
+    unsafe fn merge_adjacent_once(head: &mut Option<NonNull<ListNode>>, poisoned: bool) -> bool {
         unsafe {
             let mut prev_a: *mut Option<NonNull<ListNode>> = core::ptr::from_mut(head);
 
@@ -138,6 +160,9 @@ impl LinkedListAllocator {
                         let nb_next = nb.as_mut().next;
                         na.as_mut().size += add;
                         *prev_b = nb_next;
+                        if poisoned {
+                            poison::fill(b_start, NODE_SIZE, poison::FREE_BYTE);
+                        }
                         return true;
                     }
                     if b_end == a_start {
@@ -145,6 +170,9 @@ impl LinkedListAllocator {
                         let na_next = na.as_mut().next;
                         nb.as_mut().size += add;
                         *prev_a = na_next;
+                        if poisoned {
+                            poison::fill(a_start, NODE_SIZE, poison::FREE_BYTE);
+                        }
                         return true;
                     }
 
@@ -159,13 +187,18 @@ impl LinkedListAllocator {
     }
 }
 
-unsafe impl GlobalAlloc for LinkedListAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+impl LinkedListAllocator {
+    /// `alloc` that reports overwritten poison instead of panicking, so the
+    /// caller can drop its own locks first. Never fails without poisoning.
+    ///
+    /// # Safety
+    /// Same contract as `GlobalAlloc::alloc`.
+    pub unsafe fn try_alloc(&self, layout: Layout) -> Result<*mut u8, PoisonError> {
         let size = layout.size();
         let align = layout.align();
 
         if size == 0 {
-            return ptr::null_mut();
+            return Ok(ptr::null_mut());
         }
 
         let required_size = align_up(size.max(core::mem::size_of::<ListNode>()), align);
@@ -174,8 +207,9 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
             let inner = &mut *self.inner.get();
 
             if !inner.initialized {
-                return ptr::null_mut();
+                return Ok(ptr::null_mut());
             }
+            let poisoned = inner.poison;
 
             let mut current = &mut inner.head;
 
@@ -184,6 +218,9 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
 
                 match Self::alloc_from_region(node, required_size, align) {
                     Ok(alloc_start) => {
+                        // The only non-poison bytes in a free region are its header.
+                        let skip = if alloc_start == node.start_addr() { NODE_SIZE } else { 0 };
+
                         // Remove or split the node
                         if alloc_start == node.start_addr()
                             && alloc_start + required_size == node.end_addr()
@@ -215,7 +252,11 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
                             node.size = alloc_start - node.start_addr();
                             node.next = NonNull::new(second_ptr);
                         }
-                        return alloc_start as *mut u8;
+                        if poisoned {
+                            poison::check_freed(alloc_start, skip, required_size)?;
+                            poison::fill(alloc_start, required_size, poison::ALLOC_BYTE);
+                        }
+                        return Ok(alloc_start as *mut u8);
                     }
                     Err(_) => {
                         current = &mut node.next;
@@ -223,9 +264,15 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
                 }
             }
 
-            ptr::null_mut()
+            Ok(ptr::null_mut())
         })
     }
+}
+
+unsafe impl GlobalAlloc for LinkedListAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.try_alloc(layout).unwrap_or_else(|err| poison::fail(err))
+    }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         if ptr.is_null() {
@@ -244,6 +291,9 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
                 return;
             }
 
+            if inner.poison {
+                poison::fill(ptr as usize, size, poison::FREE_BYTE);
+            }
             let node_ptr = ptr as *mut ListNode;
             node_ptr.write(ListNode {
                 size,
@@ -252,7 +302,7 @@ unsafe impl GlobalAlloc for LinkedListAllocator {
             inner.head = NonNull::new(node_ptr);
 
             unsafe {
-                while Self::merge_adjacent_once(&mut inner.head) {}
+                while Self::merge_adjacent_once(&mut inner.head, inner.poison) {}
             }
         });
     }
@@ -1,6 +1,10 @@
 pub mod block;
+pub mod buddy;
 pub mod bump;
 mod core;
 pub mod linked_list;
+pub mod poison;
 pub mod slab;
 pub mod stack;
+
+pub use self::core::AllocError;
@@ -0,0 +1,63 @@
+//! # Heap Poisoning
+//!
+//! Use-after-free detector for the heap allocators, enabled with the
+//! `heap-poison` feature:
+//! - freed memory is filled with `FREE_BYTE`, except the free-list header
+//!   the allocator keeps at the start of a free block;
+//! - on allocation the block is checked to still hold `FREE_BYTE`, so a
+//!   write through a dangling pointer panics with the block address;
+//! - handed-out memory is filled with `ALLOC_BYTE`, which makes reads of
+//!   uninitialized memory stand out.
+//!
+//! The whole heap is poisoned once at init and every alloc/free touches
+//! every byte, so this is for debugging only.
+
+pub const FREE_BYTE: u8 = 0xDE;
+pub const ALLOC_BYTE: u8 = 0xAA;
+
+/// Freed memory that no longer holds the poison pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoisonError {
+    /// Start of the block being allocated.
+    pub block: usize,
+    /// Offset of the first overwritten byte inside the block.
+    pub offset: usize,
+    pub found: u8,
+}
+
+/// # Safety
+/// `start..start + len` must be memory owned by the allocator.
+pub(crate) unsafe fn fill(start: usize, len: usize, byte: u8) {
+    core::ptr::write_bytes(start as *mut u8, byte, len);
+}
+
+/// Verifies that `start + skip..start + len` still holds `FREE_BYTE`.
+///
+/// # Safety
+/// `start..start + len` must be memory owned by the allocator.
+pub(crate) unsafe fn check_freed(start: usize, skip: usize, len: usize) -> Result<(), PoisonError> {
+    if skip >= len {
+        return Ok(());
+    }
+    let bytes = core::slice::from_raw_parts((start + skip) as *const u8, len - skip);
+    match bytes.iter().position(|&b| b != FREE_BYTE) {
+        Some(pos) => Err(PoisonError {
+            block: start,
+            offset: skip + pos,
+            found: bytes[pos],
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cold]
+pub fn fail(err: PoisonError) -> ! {
+    panic!(
+        "heap poison overwritten at {:#x} (block {:#x} + {}): found {:#04x}, expected {:#04x} - write after free?",
+        err.block + err.offset,
+        err.block,
+        err.offset,
+        err.found,
+        FREE_BYTE
+    );
+}
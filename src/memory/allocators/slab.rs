@@ -11,7 +11,8 @@
 //!
 //! - [`BumpAllocator`]: Simple, fast allocator that never frees (initialization, temporary buffers)
 //! - [`StackAllocator`]: LIFO allocation pattern (temporary allocations with predictable lifetimes)
-//! - [`SlabAllocator`]: Cache-aligned, object-specific allocation
+//! - [`SlabAllocator`]: Cache-aligned, object-specific allocation (typed
+//!   and backed by frames through [`SlabCache`])
 //! - [`StackHeapAllocator`]: Separate stack/heap regions
 //!
 //! ## Thread Safety
@@ -25,7 +26,11 @@ use crate::memory::allocators::core::{
 };
 use core::alloc::{GlobalAlloc, Layout};
 use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ops::{Deref, DerefMut};
 use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 // ============================================================================
 // 5. SLAB ALLOCATOR (Cache-aligned, object-specific)
@@ -36,7 +41,6 @@ struct SlabAllocatorInner<const SIZE: usize, const ALIGN: usize> {
     head: Option<NonNull<BlockNode>>,
 }
 
-#[allow(dead_code)]
 pub struct SlabAllocator<const SIZE: usize, const ALIGN: usize> {
     inner: UnsafeCell<SlabAllocatorInner<SIZE, ALIGN>>,
     lock: SpinLock,
@@ -45,7 +49,6 @@ pub struct SlabAllocator<const SIZE: usize, const ALIGN: usize> {
 // Safety: The UnsafeCell is protected by SpinLock
 unsafe impl<const SIZE: usize, const ALIGN: usize> Sync for SlabAllocator<SIZE, ALIGN> {}
 
-#[allow(dead_code)]
 impl<const SIZE: usize, const ALIGN: usize> SlabAllocator<SIZE, ALIGN> {
     pub const fn new() -> Self {
         Self {
@@ -103,3 +106,197 @@ unsafe impl<const SIZE: usize, const ALIGN: usize> GlobalAlloc for SlabAllocator
         });
     }
 }
+
+// ============================================================================
+// 6. SLAB CACHE (typed, grows a frame at a time)
+// ============================================================================
+
+const SLAB_BYTES: usize = 4096;
+
+/// `T`s in `SIZE`-byte blocks of a `SlabAllocator`. When the free list
+/// runs dry the cache takes a frame from the frame allocator and carves it
+/// into blocks; frames are kept for reuse and never returned.
+///
+/// `SIZE` and `ALIGN` are fixed by the caller rather than derived from
+/// `T` so the cache can sit in a static; a `T` that outgrows them fails
+/// to compile.
+pub struct SlabCache<T, const SIZE: usize, const ALIGN: usize> {
+    slab: SlabAllocator<SIZE, ALIGN>,
+    slabs: AtomicUsize,
+    live: AtomicUsize,
+    _marker: PhantomData<T>,
+}
+
+// Safety: the blocks are handed out once each, and the free list is
+// behind the allocator's lock.
+unsafe impl<T: Send, const SIZE: usize, const ALIGN: usize> Sync for SlabCache<T, SIZE, ALIGN> {}
+
+impl<T, const SIZE: usize, const ALIGN: usize> SlabCache<T, SIZE, ALIGN> {
+    const FITS: () = assert!(
+        size_of::<T>() <= SIZE
+            && align_of::<T>() <= ALIGN
+            && SIZE >= size_of::<BlockNode>()
+            && SIZE.is_multiple_of(ALIGN)
+            && SLAB_BYTES.is_multiple_of(SIZE),
+        "T does not fit the slab block"
+    );
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        Self {
+            slab: SlabAllocator::new(),
+            slabs: AtomicUsize::new(0),
+            live: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds a fresh frame's worth of blocks.
+    fn grow(&self) -> bool {
+        let Some(frame) = crate::memory::allocate_frame() else {
+            return false;
+        };
+        let virt = crate::memory::phys_to_virt(frame.start_address());
+        // Safety: the frame was just allocated and is mapped through the
+        // physical memory offset; nothing else refers to it.
+        unsafe { self.slab.add_slab(virt.as_u64() as usize, SLAB_BYTES) };
+        self.slabs.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Moves `value` into a block. `None` when the cache is empty and no
+    /// frame is left to grow it.
+    pub fn alloc(&self, value: T) -> Option<NonNull<T>> {
+        let layout = Layout::new::<T>();
+        let mut block = unsafe { self.slab.alloc(layout) };
+        if block.is_null() && self.grow() {
+            block = unsafe { self.slab.alloc(layout) };
+        }
+        let block = NonNull::new(block as *mut T)?;
+        // Safety: the block is free, and `FITS` checked its size and
+        // alignment.
+        unsafe { block.as_ptr().write(value) };
+        self.live.fetch_add(1, Ordering::Relaxed);
+        Some(block)
+    }
+
+    /// Drops the value and puts its block back on the free list.
+    ///
+    /// # Safety
+    ///
+    /// `block` must come from `alloc` on this cache and not be used again.
+    pub unsafe fn free(&self, block: NonNull<T>) {
+        ptr::drop_in_place(block.as_ptr());
+        self.slab
+            .dealloc(block.as_ptr() as *mut u8, Layout::new::<T>());
+        self.live.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Frames taken so far and objects currently allocated.
+    pub fn stats(&self) -> (usize, usize) {
+        (
+            self.slabs.load(Ordering::Relaxed),
+            self.live.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl<T, const SIZE: usize, const ALIGN: usize> Default for SlabCache<T, SIZE, ALIGN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An owned `T` in a block of a static `SlabCache`, freed on drop; the
+/// slab counterpart of `Box`.
+pub struct SlabBox<T: 'static, const SIZE: usize, const ALIGN: usize> {
+    block: NonNull<T>,
+    cache: &'static SlabCache<T, SIZE, ALIGN>,
+}
+
+// Safety: a SlabBox owns its `T` exclusively, like a Box.
+unsafe impl<T: Send, const SIZE: usize, const ALIGN: usize> Send for SlabBox<T, SIZE, ALIGN> {}
+
+impl<T: 'static, const SIZE: usize, const ALIGN: usize> SlabBox<T, SIZE, ALIGN> {
+    /// `None` when the cache cannot grow.
+    pub fn new_in(value: T, cache: &'static SlabCache<T, SIZE, ALIGN>) -> Option<Self> {
+        Some(Self {
+            block: cache.alloc(value)?,
+            cache,
+        })
+    }
+}
+
+impl<T: 'static, const SIZE: usize, const ALIGN: usize> Deref for SlabBox<T, SIZE, ALIGN> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the block holds a live `T` until drop.
+        unsafe { self.block.as_ref() }
+    }
+}
+
+impl<T: 'static, const SIZE: usize, const ALIGN: usize> DerefMut for SlabBox<T, SIZE, ALIGN> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: as for `deref`, and `&mut self` makes the access unique.
+        unsafe { self.block.as_mut() }
+    }
+}
+
+impl<T: 'static, const SIZE: usize, const ALIGN: usize> Drop for SlabBox<T, SIZE, ALIGN> {
+    fn drop(&mut self) {
+        // Safety: the block came from this cache and is not used after.
+        unsafe { self.cache.free(self.block) };
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const PER_SLAB: usize = SLAB_BYTES / 32;
+
+    #[test_case]
+    fn cache_grows_and_reuses_blocks() {
+        static CACHE: SlabCache<[u64; 4], 32, 8> = SlabCache::new();
+
+        let count = PER_SLAB + PER_SLAB / 2;
+        let blocks: Vec<NonNull<[u64; 4]>> = (0..count as u64)
+            .map(|i| CACHE.alloc([i; 4]).expect("slab block"))
+            .collect();
+        assert_eq!(CACHE.stats(), (2, count));
+        for (i, block) in blocks.iter().enumerate() {
+            assert_eq!(unsafe { block.as_ref() }[3], i as u64);
+        }
+
+        for &block in &blocks {
+            unsafe { CACHE.free(block) };
+        }
+        assert_eq!(CACHE.stats(), (2, 0));
+
+        // every block comes back before another frame is taken
+        let again: Vec<NonNull<[u64; 4]>> =
+            (0..count).map(|_| CACHE.alloc([0; 4]).unwrap()).collect();
+        assert_eq!(CACHE.stats().0, 2);
+        assert!(again.iter().all(|b| blocks.contains(b)));
+        for block in again {
+            unsafe { CACHE.free(block) };
+        }
+    }
+
+    #[test_case]
+    fn slab_box_drops_its_value() {
+        static CACHE: SlabCache<Vec<u8>, 32, 8> = SlabCache::new();
+
+        let mut boxed = SlabBox::new_in(Vec::from([1u8, 2]), &CACHE).unwrap();
+        boxed.push(3);
+        assert_eq!(&boxed[..], [1, 2, 3]);
+        assert_eq!(CACHE.stats(), (1, 1));
+        drop(boxed);
+        assert_eq!(CACHE.stats(), (1, 0));
+    }
+}
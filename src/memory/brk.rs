@@ -38,8 +38,7 @@ pub fn sys_brk(addr: u64) -> Result<usize, crate::syscalls::dispatcher::SyscallE
             if !crate::memory::page_is_mapped(page_virt) {
                 let frame = crate::memory::allocate_frame().ok_or(SyscallError::NoMemory)?;
                 crate::memory::zero_frame(frame);
-                crate::memory::map_single_page(page_virt, frame, flags)
-                    .map_err(|_| SyscallError::NoMemory)?;
+                crate::memory::map_single_page(page_virt, frame, flags)?;
             }
             virt += 4096;
         }
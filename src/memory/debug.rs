@@ -1,6 +1,22 @@
 use crate::{memory::access_page_table, println};
+use alloc::{format, string::String};
 use x86_64::{registers::control::Cr3, structures::paging::PageTableFlags, VirtAddr};
 
+/// Compact flag summary shared by `debug_page_walk` and `vmmap`,
+/// e.g. `RW- user=0 nx=1`.
+pub fn format_flags(flags: PageTableFlags) -> String {
+    let w = if flags.contains(PageTableFlags::WRITABLE) { 'W' } else { '-' };
+    let nx = flags.contains(PageTableFlags::NO_EXECUTE);
+    let x = if nx { '-' } else { 'X' };
+    format!(
+        "R{}{} user={} nx={}",
+        w,
+        x,
+        flags.contains(PageTableFlags::USER_ACCESSIBLE) as u8,
+        nx as u8
+    )
+}
+
 pub fn debug_page_walk(virt: VirtAddr) {
     let va_u64 = virt.as_u64();
     let p4_index = ((va_u64 >> 39) & 0x1FF) as usize;
@@ -106,6 +122,7 @@ pub fn debug_page_walk(virt: VirtAddr) {
     );
 
     if p1_entry.flags().contains(PageTableFlags::PRESENT) {
+        println!("  -> {}", format_flags(p1_entry.flags()));
         if p1_nx == 1 {
             println!("  -> Page is PRESENT but NX bit is SET (not executable)!");
         } else {
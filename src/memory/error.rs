@@ -0,0 +1,149 @@
+//! # Memory Errors
+//!
+//! `MemError` is what the mapping, address-space and `mmap` family return,
+//! carrying the address or table level involved so a failure says where it
+//! happened. `Debug` reads like `AlreadyMapped at 0x401000` for test
+//! output; `Display` is the sentence for logs and command output. Syscall
+//! handlers turn it into a `SyscallError` with `?`.
+
+use crate::memory::allocators::AllocError;
+use crate::syscalls::dispatcher::SyscallError;
+use core::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MemError {
+    /// No physical frame (or run of frames) was free.
+    OutOfFrames,
+    OutOfHeap,
+    /// No room left in a virtual window (process code slots, say).
+    OutOfAddressSpace,
+    AlreadyMapped {
+        virt: u64,
+    },
+    NotMapped {
+        virt: u64,
+    },
+    Misaligned {
+        addr: u64,
+    },
+    /// A table entry at `level` (4 is the P4) could not be followed,
+    /// typically because it maps a huge page.
+    WalkFailed {
+        level: u8,
+    },
+    PermissionDenied,
+    /// A zero length, null pointer or the like.
+    InvalidArgument,
+}
+
+impl fmt::Debug for MemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemError::OutOfFrames => write!(f, "OutOfFrames"),
+            MemError::OutOfHeap => write!(f, "OutOfHeap"),
+            MemError::OutOfAddressSpace => write!(f, "OutOfAddressSpace"),
+            MemError::AlreadyMapped { virt } => write!(f, "AlreadyMapped at {:#x}", virt),
+            MemError::NotMapped { virt } => write!(f, "NotMapped at {:#x}", virt),
+            MemError::Misaligned { addr } => write!(f, "Misaligned at {:#x}", addr),
+            MemError::WalkFailed { level } => write!(f, "WalkFailed at P{}", level),
+            MemError::PermissionDenied => write!(f, "PermissionDenied"),
+            MemError::InvalidArgument => write!(f, "InvalidArgument"),
+        }
+    }
+}
+
+impl fmt::Display for MemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemError::OutOfFrames => write!(f, "out of physical frames"),
+            MemError::OutOfHeap => write!(f, "out of heap"),
+            MemError::OutOfAddressSpace => write!(f, "out of virtual address space"),
+            MemError::AlreadyMapped { virt } => write!(f, "page {:#x} is already mapped", virt),
+            MemError::NotMapped { virt } => write!(f, "page {:#x} is not mapped", virt),
+            MemError::Misaligned { addr } => write!(f, "{:#x} is not page aligned", addr),
+            MemError::WalkFailed { level } => write!(f, "cannot walk past the P{} entry", level),
+            MemError::PermissionDenied => write!(f, "permission denied"),
+            MemError::InvalidArgument => write!(f, "invalid argument"),
+        }
+    }
+}
+
+impl From<MemError> for SyscallError {
+    fn from(err: MemError) -> Self {
+        match err {
+            MemError::OutOfFrames | MemError::OutOfHeap | MemError::OutOfAddressSpace => {
+                SyscallError::NoMemory
+            }
+            MemError::AlreadyMapped { .. } => SyscallError::AlreadyExists,
+            MemError::NotMapped { .. } | MemError::WalkFailed { .. } => SyscallError::BadAddress,
+            MemError::Misaligned { .. } | MemError::InvalidArgument => {
+                SyscallError::InvalidArgument
+            }
+            MemError::PermissionDenied => SyscallError::PermissionDenied,
+        }
+    }
+}
+
+impl From<AllocError> for MemError {
+    fn from(err: AllocError) -> Self {
+        match err {
+            AllocError::OutOfMemory => MemError::OutOfHeap,
+            AllocError::InvalidAddress
+            | AllocError::InvalidSize
+            | AllocError::Overflow
+            | AllocError::Uninitialized => MemError::InvalidArgument,
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test_case]
+    fn errors_map_to_errnos_and_name_their_address() {
+        let cases = [
+            (MemError::OutOfFrames, SyscallError::NoMemory),
+            (MemError::OutOfHeap, SyscallError::NoMemory),
+            (MemError::OutOfAddressSpace, SyscallError::NoMemory),
+            (
+                MemError::AlreadyMapped { virt: 0x401000 },
+                SyscallError::AlreadyExists,
+            ),
+            (
+                MemError::NotMapped { virt: 0x1000 },
+                SyscallError::BadAddress,
+            ),
+            (
+                MemError::Misaligned { addr: 0x1234 },
+                SyscallError::InvalidArgument,
+            ),
+            (MemError::WalkFailed { level: 3 }, SyscallError::BadAddress),
+            (MemError::PermissionDenied, SyscallError::PermissionDenied),
+            (MemError::InvalidArgument, SyscallError::InvalidArgument),
+        ];
+        for (mem, sys) in cases {
+            assert_eq!(SyscallError::from(mem), sys, "{:?}", mem);
+        }
+        assert_eq!(
+            SyscallError::from(MemError::AlreadyMapped { virt: 0 }).as_errno(),
+            -17
+        );
+
+        let err = MemError::AlreadyMapped { virt: 0x401000 };
+        assert_eq!(format!("{:?}", err), "AlreadyMapped at 0x401000");
+        assert_eq!(format!("{}", err), "page 0x401000 is already mapped");
+        assert_eq!(
+            format!("{:?}", MemError::NotMapped { virt: 0xdead000 }),
+            "NotMapped at 0xdead000"
+        );
+        assert_eq!(
+            format!("{:?}", MemError::WalkFailed { level: 2 }),
+            "WalkFailed at P2"
+        );
+        assert_eq!(MemError::from(AllocError::OutOfMemory), MemError::OutOfHeap);
+    }
+}
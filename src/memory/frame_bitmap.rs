@@ -0,0 +1,207 @@
+//! # Frame Bitmap
+//!
+//! One bit per 4 KiB frame of the physical window the kernel allocates
+//! from; a set bit is a frame in use. Single frames come from the first
+//! clear bit at or after a rotating hint (the word the last allocation
+//! came from), so a run of allocations does not rescan the used prefix.
+//! `allocate_contiguous` scans for a run of clear bits from the start,
+//! for DMA buffers that need physically consecutive, aligned frames.
+//!
+//! The bitmap lives on the heap, sized from the window; the padding bits
+//! past the last frame are kept set so they are never handed out.
+
+use alloc::{vec, vec::Vec};
+
+const FRAME_SIZE: u64 = 4096;
+
+pub struct FrameBitmap {
+    /// Address of frame 0.
+    base: u64,
+    frames: usize,
+    words: Vec<u64>,
+    used: usize,
+    /// Word to start the next single-frame search at.
+    hint: usize,
+}
+
+impl FrameBitmap {
+    /// Covers the whole frames in `[start, end)`; `start` is rounded up.
+    pub fn new(start: u64, end: u64) -> Self {
+        let base = start.next_multiple_of(FRAME_SIZE);
+        let frames = (end.saturating_sub(base) / FRAME_SIZE) as usize;
+        let mut words = vec![0u64; frames.div_ceil(64)];
+        if !frames.is_multiple_of(64) {
+            *words.last_mut().unwrap() = !0 << (frames % 64);
+        }
+        Self {
+            base,
+            frames,
+            words,
+            used: 0,
+            hint: 0,
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.frames - self.used
+    }
+
+    /// Whether `addr` is the start of a frame this bitmap covers.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.base && addr.is_multiple_of(FRAME_SIZE) && self.index(addr) < self.frames
+    }
+
+    fn index(&self, addr: u64) -> usize {
+        ((addr - self.base) / FRAME_SIZE) as usize
+    }
+
+    fn is_set(&self, idx: usize) -> bool {
+        self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn set(&mut self, idx: usize) {
+        self.words[idx / 64] |= 1 << (idx % 64);
+    }
+
+    /// The address of a free frame, now marked used.
+    pub fn allocate(&mut self) -> Option<u64> {
+        let count = self.words.len();
+        let word = (0..count)
+            .map(|i| (self.hint + i) % count)
+            .find(|&w| self.words[w] != !0)?;
+        let idx = word * 64 + self.words[word].trailing_ones() as usize;
+        self.set(idx);
+        self.used += 1;
+        self.hint = word;
+        Some(self.base + idx as u64 * FRAME_SIZE)
+    }
+
+    /// `n` consecutive free frames starting at a multiple of `align`
+    /// bytes (a power of two, at least a frame), now marked used; the
+    /// address of the first. The lowest such run is taken.
+    pub fn allocate_contiguous(&mut self, n: usize, align: u64) -> Option<u64> {
+        if n == 0 || !align.is_power_of_two() {
+            return None;
+        }
+        let align = align.max(FRAME_SIZE);
+        let mut first = self.index(self.base.next_multiple_of(align));
+        while first + n <= self.frames {
+            match (first..first + n).rfind(|&i| self.is_set(i)) {
+                // Restart at the next aligned frame past the used one.
+                Some(used) => {
+                    let next = self.base + (used as u64 + 1) * FRAME_SIZE;
+                    first = self.index(next.next_multiple_of(align));
+                }
+                None => {
+                    for i in first..first + n {
+                        self.set(i);
+                    }
+                    self.used += n;
+                    return Some(self.base + first as u64 * FRAME_SIZE);
+                }
+            }
+        }
+        None
+    }
+
+    /// Length of the longest run of free frames.
+    pub fn largest_free_run(&self) -> usize {
+        let mut best = 0;
+        let mut run = 0;
+        for idx in 0..self.frames {
+            run = if self.is_set(idx) { 0 } else { run + 1 };
+            best = best.max(run);
+        }
+        best
+    }
+
+    /// Marks the frame at `addr` free. False if the bitmap does not cover
+    /// it or it was not in use.
+    pub fn free(&mut self, addr: u64) -> bool {
+        if !self.contains(addr) {
+            return false;
+        }
+        let idx = self.index(addr);
+        if !self.is_set(idx) {
+            return false;
+        }
+        self.words[idx / 64] &= !(1 << (idx % 64));
+        self.used -= 1;
+        // Freed low frames are found again before the hint wraps round.
+        self.hint = self.hint.min(idx / 64);
+        true
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0x10_0000;
+
+    fn frame(n: u64) -> u64 {
+        BASE + n * FRAME_SIZE
+    }
+
+    #[test_case]
+    fn allocate_free_and_reuse() {
+        // 70 frames: one full word and a partial one
+        let mut bitmap = FrameBitmap::new(BASE, frame(70));
+        assert_eq!(bitmap.free_count(), 70);
+
+        let got: Vec<u64> = (0..70).map(|_| bitmap.allocate().unwrap()).collect();
+        assert_eq!(got[0], frame(0));
+        assert_eq!(got[69], frame(69));
+        assert_eq!(bitmap.allocate(), None);
+        assert_eq!(bitmap.free_count(), 0);
+
+        assert!(bitmap.free(frame(3)));
+        assert!(bitmap.free(frame(65)));
+        assert!(!bitmap.free(frame(3)), "double free");
+        assert!(!bitmap.free(frame(70)), "outside the window");
+        assert!(!bitmap.free(frame(2) + 8), "not frame aligned");
+        assert_eq!(bitmap.used(), 68);
+
+        assert_eq!(bitmap.allocate(), Some(frame(3)));
+        assert_eq!(bitmap.allocate(), Some(frame(65)));
+        assert_eq!(bitmap.allocate(), None);
+    }
+
+    #[test_case]
+    fn contiguous_runs_skip_used_frames() {
+        let mut bitmap = FrameBitmap::new(BASE, frame(16));
+        for _ in 0..8 {
+            bitmap.allocate();
+        }
+        bitmap.free(frame(2));
+        bitmap.free(frame(3));
+
+        // the hole at 2..4 is too small for 4 frames
+        assert_eq!(bitmap.allocate_contiguous(4, FRAME_SIZE), Some(frame(8)));
+        assert_eq!(bitmap.allocate_contiguous(2, FRAME_SIZE), Some(frame(2)));
+        assert_eq!(bitmap.largest_free_run(), 4);
+        assert_eq!(bitmap.allocate_contiguous(5, FRAME_SIZE), None);
+        assert_eq!(bitmap.used(), 12);
+        assert_eq!(bitmap.allocate_contiguous(4, FRAME_SIZE), Some(frame(12)));
+        assert_eq!(bitmap.free_count(), 0);
+    }
+
+    #[test_case]
+    fn contiguous_runs_honour_alignment() {
+        // BASE is 1 MiB, so 16 KiB alignment means frames 0, 4, 8, ...
+        let mut bitmap = FrameBitmap::new(BASE, frame(16));
+        bitmap.allocate();
+        assert_eq!(bitmap.allocate_contiguous(2, 4 * FRAME_SIZE), Some(frame(4)));
+        assert_eq!(bitmap.allocate_contiguous(1, 4 * FRAME_SIZE), Some(frame(8)));
+        // frames 1..4 are free but not aligned; 12..16 is the last slot
+        assert_eq!(bitmap.allocate_contiguous(4, 4 * FRAME_SIZE), Some(frame(12)));
+        assert_eq!(bitmap.allocate_contiguous(1, 4 * FRAME_SIZE), None);
+        assert_eq!(bitmap.allocate_contiguous(1, 3 * FRAME_SIZE), None);
+    }
+}
@@ -0,0 +1,424 @@
+//! # Switchable Heap
+//!
+//! What the global allocator runs on. The fixed-size block allocator owns
+//! the whole kernel heap and is always there; `switch` can put another
+//! strategy in front of it for comparison (`allocator` command).
+//!
+//! ## Switching
+//!
+//! Allocators keep their metadata inside the memory they manage, so a new
+//! allocator cannot simply take over a region the old one has handed out
+//! parts of. Instead the new ("guest") allocator gets a region of its own,
+//! carved out of the fixed allocator, and new allocations come from it.
+//! Every pointer goes back to whichever allocator its address belongs to,
+//! so blocks allocated before a switch stay valid and are freed correctly
+//! afterwards. When the guest region is full, allocations fall back to the
+//! fixed allocator rather than failing.
+//!
+//! Only one guest exists at a time. Switching back to `fixed` retires the
+//! guest: it keeps serving frees and its region is returned once its last
+//! block is freed. Switching to a different guest while the previous one
+//! still has live blocks is refused; long-lived objects (the terminal's
+//! buffers, say) can keep a guest alive indefinitely, which is why a
+//! switch is best done at a quiet moment.
+//!
+//! The buddy allocator hands out power-of-two blocks, which suits page
+//! sized allocations and wastes up to half of anything else.
+//!
+//! The bump allocator never reuses memory, so under it the guest region
+//! only shrinks until everything in it is freed.
+
+use super::allocators::block::FixedSizeBlockAllocator;
+use super::allocators::buddy::BuddyAllocator;
+use super::allocators::bump::BumpAllocator;
+use super::allocators::linked_list::LinkedListAllocator;
+use super::allocators::poison::PoisonError;
+use core::alloc::{GlobalAlloc, Layout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapKind {
+    Fixed,
+    FreeList,
+    Bump,
+    Buddy,
+}
+
+impl HeapKind {
+    pub const ALL: [HeapKind; 4] = [
+        HeapKind::Fixed,
+        HeapKind::FreeList,
+        HeapKind::Bump,
+        HeapKind::Buddy,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HeapKind::Fixed => "fixed",
+            HeapKind::FreeList => "freelist",
+            HeapKind::Bump => "bump",
+            HeapKind::Buddy => "buddy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchError {
+    /// The previous guest still has this many live blocks.
+    Busy(HeapKind, usize),
+    /// The fixed allocator could not spare a guest region.
+    NoRegion,
+}
+
+impl core::fmt::Display for SwitchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Busy(kind, live) => write!(
+                f,
+                "{} still holds {} live allocation(s); switch to fixed and retry once they are freed",
+                kind.name(),
+                live
+            ),
+            Self::NoRegion => write!(f, "no room in the heap for a guest region"),
+        }
+    }
+}
+
+// Not boxed: this is the heap.
+#[allow(clippy::large_enum_variant)]
+enum GuestAlloc {
+    FreeList(LinkedListAllocator),
+    Bump(BumpAllocator),
+    Buddy(BuddyAllocator),
+}
+
+struct Guest {
+    kind: HeapKind,
+    alloc: GuestAlloc,
+    start: usize,
+    size: usize,
+    /// Blocks handed out and not yet freed.
+    live: usize,
+    allocs: u64,
+    /// Allocations the guest could not serve.
+    fallbacks: u64,
+    /// Still taking new allocations (not retired).
+    active: bool,
+}
+
+impl Guest {
+    fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.start + self.size
+    }
+
+    unsafe fn try_alloc(&self, layout: Layout) -> Result<*mut u8, PoisonError> {
+        match &self.alloc {
+            GuestAlloc::FreeList(list) => list.try_alloc(layout),
+            GuestAlloc::Bump(bump) => Ok(bump.alloc(layout)),
+            GuestAlloc::Buddy(buddy) => Ok(buddy.alloc(layout)),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match &self.alloc {
+            GuestAlloc::FreeList(list) => list.dealloc(ptr, layout),
+            GuestAlloc::Bump(bump) => bump.dealloc(ptr, layout),
+            GuestAlloc::Buddy(buddy) => buddy.dealloc(ptr, layout),
+        }
+    }
+}
+
+/// What `info` reports.
+pub struct HeapInfo {
+    pub active: HeapKind,
+    /// Bytes allocated and not yet freed.
+    pub used: usize,
+    /// The current or retired guest, if one still holds its region.
+    pub guest: Option<GuestInfo>,
+}
+
+pub struct GuestInfo {
+    pub kind: HeapKind,
+    pub start: usize,
+    pub size: usize,
+    pub live: usize,
+    pub allocs: u64,
+    pub fallbacks: u64,
+    pub retired: bool,
+    /// Bytes bumped past, for a bump guest.
+    pub bump_used: Option<usize>,
+    /// Free bytes and the largest free block, for a buddy guest.
+    pub buddy_free: Option<(usize, usize)>,
+}
+
+pub struct Heap {
+    base: FixedSizeBlockAllocator,
+    guest: Option<Guest>,
+    guest_bytes: usize,
+    /// Bytes handed out and not yet freed, whichever allocator holds them.
+    used: usize,
+}
+
+// Safety: the guest's pointers are into its region, which only this heap
+// hands out, and the heap is only used under the global allocator's lock.
+unsafe impl Send for Heap {}
+
+impl Heap {
+    /// `base` must already be initialised over the heap; guests get
+    /// regions of `guest_bytes`.
+    pub fn new(base: FixedSizeBlockAllocator, guest_bytes: usize) -> Self {
+        Self {
+            base,
+            guest: None,
+            guest_bytes,
+            used: 0,
+        }
+    }
+
+    fn region_layout(&self) -> Layout {
+        Layout::from_size_align(self.guest_bytes, 4096).unwrap()
+    }
+
+    pub fn active(&self) -> HeapKind {
+        match &self.guest {
+            Some(guest) if guest.active => guest.kind,
+            _ => HeapKind::Fixed,
+        }
+    }
+
+    /// Bytes handed out and not yet freed.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// # Safety
+    /// Same contract as `GlobalAlloc::alloc`.
+    pub unsafe fn try_alloc(&mut self, layout: Layout) -> Result<*mut u8, PoisonError> {
+        let ptr = self.alloc_block(layout)?;
+        if !ptr.is_null() {
+            self.used += layout.size();
+        }
+        Ok(ptr)
+    }
+
+    unsafe fn alloc_block(&mut self, layout: Layout) -> Result<*mut u8, PoisonError> {
+        if let Some(guest) = self.guest.as_mut().filter(|g| g.active) {
+            let ptr = guest.try_alloc(layout)?;
+            if !ptr.is_null() {
+                guest.live += 1;
+                guest.allocs += 1;
+                return Ok(ptr);
+            }
+            if layout.size() != 0 {
+                guest.fallbacks += 1;
+            }
+        }
+        self.base.try_alloc(layout)
+    }
+
+    /// # Safety
+    /// Same contract as `GlobalAlloc::dealloc`.
+    pub unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        if ptr.is_null() {
+            return;
+        }
+        self.used -= layout.size();
+        let Some(guest) = self.guest.as_mut().filter(|g| g.contains(ptr as usize)) else {
+            self.base.dealloc(ptr, layout);
+            return;
+        };
+        guest.dealloc(ptr, layout);
+        guest.live -= 1;
+        if guest.live == 0 && !guest.active {
+            self.release_guest();
+        }
+    }
+
+    /// Gives the guest region back to the fixed allocator.
+    fn release_guest(&mut self) {
+        if let Some(guest) = self.guest.take() {
+            // Safety: the region came from `base` with this layout, and no
+            // block in it is live.
+            unsafe {
+                self.base
+                    .dealloc(guest.start as *mut u8, self.region_layout())
+            };
+        }
+    }
+
+    /// Sends new allocations to `kind`. See the module docs for what
+    /// happens to blocks already handed out.
+    pub fn switch(&mut self, kind: HeapKind) -> Result<(), SwitchError> {
+        if kind == self.active() {
+            return Ok(());
+        }
+        if let Some(guest) = self.guest.as_mut() {
+            if guest.live == 0 {
+                self.release_guest();
+            } else if kind == HeapKind::Fixed {
+                guest.active = false;
+                return Ok(());
+            } else {
+                return Err(SwitchError::Busy(guest.kind, guest.live));
+            }
+        }
+        if kind == HeapKind::Fixed {
+            return Ok(());
+        }
+
+        let layout = self.region_layout();
+        // Safety: a fresh block of `base`, handed over whole to the guest.
+        let start = match unsafe { self.base.try_alloc(layout) } {
+            Ok(ptr) if !ptr.is_null() => ptr as usize,
+            _ => return Err(SwitchError::NoRegion),
+        };
+        let alloc = match kind {
+            HeapKind::Bump => {
+                let bump = BumpAllocator::new();
+                unsafe { bump.init(start, self.guest_bytes) }.map(|_| GuestAlloc::Bump(bump))
+            }
+            HeapKind::Buddy => {
+                let buddy = BuddyAllocator::new();
+                unsafe { buddy.init(start, self.guest_bytes) }.map(|_| GuestAlloc::Buddy(buddy))
+            }
+            _ => {
+                let list = if cfg!(feature = "heap-poison") {
+                    LinkedListAllocator::poisoned()
+                } else {
+                    LinkedListAllocator::new()
+                };
+                unsafe { list.init(start, self.guest_bytes) }.map(|_| GuestAlloc::FreeList(list))
+            }
+        };
+        let Ok(alloc) = alloc else {
+            unsafe { self.base.dealloc(start as *mut u8, layout) };
+            return Err(SwitchError::NoRegion);
+        };
+        self.guest = Some(Guest {
+            kind,
+            alloc,
+            start,
+            size: self.guest_bytes,
+            live: 0,
+            allocs: 0,
+            fallbacks: 0,
+            active: true,
+        });
+        Ok(())
+    }
+
+    pub fn info(&self) -> HeapInfo {
+        HeapInfo {
+            active: self.active(),
+            used: self.used,
+            guest: self.guest.as_ref().map(|g| GuestInfo {
+                kind: g.kind,
+                start: g.start,
+                size: g.size,
+                live: g.live,
+                allocs: g.allocs,
+                fallbacks: g.fallbacks,
+                retired: !g.active,
+                bump_used: match &g.alloc {
+                    GuestAlloc::Bump(bump) => Some(bump.used()),
+                    _ => None,
+                },
+                buddy_free: match &g.alloc {
+                    GuestAlloc::Buddy(buddy) => Some(buddy.free_space()),
+                    _ => None,
+                },
+            }),
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_HEAP_SIZE: usize = 64 * 1024;
+    const GUEST_BYTES: usize = 16 * 1024;
+
+    #[repr(align(4096))]
+    struct TestHeap([u8; TEST_HEAP_SIZE]);
+
+    fn heap(buf: &'static mut TestHeap) -> Heap {
+        let base = FixedSizeBlockAllocator::new();
+        unsafe { base.init(buf.0.as_mut_ptr() as usize, TEST_HEAP_SIZE) }.unwrap();
+        Heap::new(base, GUEST_BYTES)
+    }
+
+    #[test_case]
+    fn blocks_go_back_to_the_allocator_that_made_them() {
+        static mut BUF: TestHeap = TestHeap([0; TEST_HEAP_SIZE]);
+        let mut heap = heap(unsafe { &mut *core::ptr::addr_of_mut!(BUF) });
+        let layout = Layout::from_size_align(100, 8).unwrap();
+
+        unsafe {
+            let fixed = heap.try_alloc(layout).unwrap();
+            assert_eq!(heap.info().used, 100);
+            assert_eq!(heap.switch(HeapKind::Bump), Ok(()));
+            let bumped = heap.try_alloc(layout).unwrap();
+            let info = heap.info().guest.unwrap();
+            assert!((info.start..info.start + info.size).contains(&(bumped as usize)));
+            assert_eq!(info.bump_used, Some(100));
+
+            // the bump guest has a live block
+            assert_eq!(
+                heap.switch(HeapKind::FreeList),
+                Err(SwitchError::Busy(HeapKind::Bump, 1))
+            );
+            assert_eq!(
+                heap.switch(HeapKind::Buddy),
+                Err(SwitchError::Busy(HeapKind::Bump, 1))
+            );
+
+            // retire it; the next allocation comes from fixed again
+            assert_eq!(heap.switch(HeapKind::Fixed), Ok(()));
+            assert_eq!(heap.active(), HeapKind::Fixed);
+            assert!(heap.info().guest.unwrap().retired);
+            heap.dealloc(fixed, layout);
+            heap.dealloc(bumped, layout);
+            assert!(heap.info().guest.is_none());
+            assert_eq!(heap.info().used, 0);
+
+            assert_eq!(heap.switch(HeapKind::FreeList), Ok(()));
+            let listed = heap.try_alloc(layout).unwrap();
+            heap.dealloc(listed, layout);
+            assert_eq!(heap.info().guest.unwrap().live, 0);
+
+            // an idle guest is simply replaced
+            assert_eq!(heap.switch(HeapKind::Buddy), Ok(()));
+            let page = Layout::from_size_align(4096, 4096).unwrap();
+            let paged = heap.try_alloc(page).unwrap();
+            assert_eq!(paged as usize % 4096, 0);
+            let free = heap.info().guest.unwrap().buddy_free;
+            assert_eq!(free, Some((GUEST_BYTES - 4096, GUEST_BYTES / 2)));
+            heap.dealloc(paged, page);
+            let free = heap.info().guest.unwrap().buddy_free;
+            assert_eq!(free, Some((GUEST_BYTES, GUEST_BYTES)));
+        }
+    }
+
+    #[test_case]
+    fn full_guest_falls_back_to_fixed() {
+        static mut BUF: TestHeap = TestHeap([0; TEST_HEAP_SIZE]);
+        let mut heap = heap(unsafe { &mut *core::ptr::addr_of_mut!(BUF) });
+        let big = Layout::from_size_align(GUEST_BYTES, 8).unwrap();
+
+        heap.switch(HeapKind::Bump).unwrap();
+        unsafe {
+            let ptr = heap.try_alloc(big).unwrap();
+            assert!(!ptr.is_null());
+            let info = heap.info().guest.unwrap();
+            assert!(!(info.start..info.start + info.size).contains(&(ptr as usize)));
+            assert_eq!(info.fallbacks, 1);
+            heap.dealloc(ptr, big);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! # Executable Mapping Tracker
+//!
+//! Bookkeeping for `PROT_EXEC` mappings handed out by `sys_mmap`:
+//! - `sys_munmap` zeroes the frames of any region that was executable, so
+//!   old code never outlives its mapping once frames are reused, and
+//!   keeps tracking what is left of a region it only partly unmapped;
+//! - `sys_mmap` refuses a new executable mapping if one of its frames is
+//!   still mapped by a live executable region;
+//! - `AsmExecutor` counts how often each region is entered, shown by the
+//!   `jitstat` command together with recently freed regions.
+//!
+//! Process code placed by `sys_pstart` is registered too, with the pid
+//! that owns it, for `ps`.
+
+use alloc::{format, string::String, vec::Vec};
+use spin::Mutex;
+use x86_64::structures::paging::{PhysFrame, Size4KiB};
+
+/// Freed regions kept for `jitstat`.
+const HISTORY_LEN: usize = 32;
+
+struct ExecMapping {
+    start: u64,
+    size: u64,
+    frames: Vec<PhysFrame<Size4KiB>>,
+    entries: u64,
+    /// Pid whose code this is; `None` for `sys_mmap` regions.
+    owner: Option<usize>,
+}
+
+/// A process code region, for `ps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessRegion {
+    pub pid: usize,
+    pub start: u64,
+    pub size: u64,
+    pub entries: u64,
+}
+
+struct FreedMapping {
+    start: u64,
+    size: u64,
+    entries: u64,
+}
+
+struct JitRegistry {
+    live: Vec<ExecMapping>,
+    history: Vec<FreedMapping>,
+    total_freed: u64,
+}
+
+static REGISTRY: Mutex<JitRegistry> = Mutex::new(JitRegistry {
+    live: Vec::new(),
+    history: Vec::new(),
+    total_freed: 0,
+});
+
+/// True if any of `frames` is still mapped by a live executable region.
+pub fn frames_in_use(frames: &[PhysFrame<Size4KiB>]) -> bool {
+    REGISTRY
+        .lock()
+        .live
+        .iter()
+        .any(|m| m.frames.iter().any(|f| frames.contains(f)))
+}
+
+pub fn register(start: u64, frames: Vec<PhysFrame<Size4KiB>>) {
+    push(start, frames, None);
+}
+
+/// `register` for the code of process `pid`.
+pub fn register_process(pid: usize, start: u64, frames: Vec<PhysFrame<Size4KiB>>) {
+    push(start, frames, Some(pid));
+}
+
+fn push(start: u64, frames: Vec<PhysFrame<Size4KiB>>, owner: Option<usize>) {
+    let size = frames.len() as u64 * 4096;
+    REGISTRY.lock().live.push(ExecMapping {
+        start,
+        size,
+        frames,
+        entries: 0,
+        owner,
+    });
+}
+
+/// Live process code regions, by pid.
+pub fn processes() -> Vec<ProcessRegion> {
+    let mut out: Vec<ProcessRegion> = REGISTRY
+        .lock()
+        .live
+        .iter()
+        .filter_map(|m| {
+            Some(ProcessRegion {
+                pid: m.owner?,
+                start: m.start,
+                size: m.size,
+                entries: m.entries,
+            })
+        })
+        .collect();
+    out.sort_by_key(|p| p.pid);
+    out
+}
+
+/// Counts one entry into the region containing `addr`.
+pub fn note_entry(addr: u64) {
+    let mut reg = REGISTRY.lock();
+    if let Some(m) = reg
+        .live
+        .iter_mut()
+        .find(|m| addr >= m.start && addr < m.start + m.size)
+    {
+        m.entries += 1;
+    }
+}
+
+/// Forgets `start..start + size` (page aligned) of every executable
+/// region it overlaps. The unmapped part goes to the history; what is
+/// left of a region on either side stays live with its own frames.
+/// Returns how many regions were cut.
+pub fn release_range(start: u64, size: u64) -> usize {
+    let end = start.saturating_add(size);
+    let mut reg = REGISTRY.lock();
+    let mut released = 0;
+    while let Some(idx) = reg
+        .live
+        .iter()
+        .position(|m| m.start < end && start < m.start + m.size)
+    {
+        let mut m = reg.live.swap_remove(idx);
+        let m_end = m.start + m.size;
+        let (cut_start, cut_end) = (start.max(m.start), end.min(m_end));
+        reg.record_freed(cut_start, cut_end - cut_start, m.entries);
+
+        let page = |addr: u64| ((addr - m.start) / 4096) as usize;
+        if cut_end < m_end {
+            let frames = m.frames[page(cut_end)..].to_vec();
+            reg.live.push(ExecMapping {
+                start: cut_end,
+                size: m_end - cut_end,
+                frames,
+                ..m
+            });
+        }
+        if m.start < cut_start {
+            m.frames.truncate(page(cut_start));
+            m.size = cut_start - m.start;
+            reg.live.push(m);
+        }
+        released += 1;
+    }
+    released
+}
+
+/// Forgets the code region of process `pid`, returning where it started
+/// and its frames for the caller to unmap and free.
+pub fn take_process(pid: usize) -> Option<(u64, Vec<PhysFrame<Size4KiB>>)> {
+    let mut reg = REGISTRY.lock();
+    let idx = reg.live.iter().position(|m| m.owner == Some(pid))?;
+    let m = reg.retire(idx);
+    Some((m.start, m.frames))
+}
+
+impl JitRegistry {
+    /// Moves live region `idx` to the history and returns it.
+    fn retire(&mut self, idx: usize) -> ExecMapping {
+        let m = self.live.swap_remove(idx);
+        self.record_freed(m.start, m.size, m.entries);
+        m
+    }
+
+    fn record_freed(&mut self, start: u64, size: u64, entries: u64) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.remove(0);
+        }
+        self.history.push(FreedMapping {
+            start,
+            size,
+            entries,
+        });
+        self.total_freed += 1;
+    }
+}
+
+pub fn report() -> String {
+    let reg = REGISTRY.lock();
+    let mut out = format!("Live executable mappings: {}\n", reg.live.len());
+    for m in &reg.live {
+        out.push_str(&format!(
+            "  {:#x}  {:>6} B  {} frames  entered {}x",
+            m.start,
+            m.size,
+            m.frames.len(),
+            m.entries
+        ));
+        if let Some(pid) = m.owner {
+            out.push_str(&format!("  pid {}", pid));
+        }
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "Freed (zeroed) mappings: {} total, last {}:\n",
+        reg.total_freed,
+        reg.history.len()
+    ));
+    for m in reg.history.iter().rev() {
+        out.push_str(&format!(
+            "  {:#x}  {:>6} B  entered {}x\n",
+            m.start, m.size, m.entries
+        ));
+    }
+    out
+}
+
+pub fn freed_count() -> u64 {
+    REGISTRY.lock().total_freed
+}
@@ -13,6 +13,21 @@ use x86_64::{
 };
 
 use crate::memory::allocators::block::FixedSizeBlockAllocator;
+use crate::memory::MemError;
+
+/// Most pages left unmapped before a mapping when `aslr on`.
+const ASLR_MAX_GAP_PAGES: u64 = 256;
+
+static ASLR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_aslr(on: bool) {
+    ASLR.store(on, Ordering::Relaxed);
+}
+
+pub fn aslr_enabled() -> bool {
+    ASLR.load(Ordering::Relaxed)
+}
+
 pub fn sys_mmap(
     addr: usize,
     length: usize,
@@ -20,24 +35,37 @@ pub fn sys_mmap(
     _flags: usize,
     _fd: i32,
     _offset: usize,
-) -> Result<usize, crate::syscalls::dispatcher::SyscallError> {
-    use crate::syscalls::dispatcher::SyscallError;
+) -> Result<usize, MemError> {
+    crate::require_stage!(crate::kcore::kernel::init::Stage::Memory);
     println!("sys_mmap: requested {} bytes, flags={}", length, prot);
     if length == 0 {
-        return Err(SyscallError::InvalidArgument);
+        return Err(MemError::InvalidArgument);
     }
 
     if prot == 0 {
-        return Err(SyscallError::InvalidArgument);
+        return Err(MemError::InvalidArgument);
     }
 
-    let page_count = (length + 4095) / 4096;
+    let page_count = length.checked_add(4095).ok_or(MemError::InvalidArgument)? / 4096;
     let actual_size = page_count * 4096;
 
     let virt_addr = if addr != 0 {
-        addr as u64 & !0xFFF
+        // A hint is only honoured inside the range munmap accepts
+        let hint = addr as u64 & !0xFFF;
+        let end = hint.checked_add(actual_size as u64);
+        if hint < crate::memory::MMAP_BASE
+            || end.is_none_or(|end| end > crate::syscalls::user::USER_END as u64)
+        {
+            return Err(MemError::InvalidArgument);
+        }
+        hint
     } else {
-        crate::memory::NEXT_MMAP_ADDR.fetch_add(actual_size as u64, Ordering::SeqCst)
+        let gap = if aslr_enabled() {
+            crate::util::rand::next_range(0, ASLR_MAX_GAP_PAGES + 1) * 4096
+        } else {
+            0
+        };
+        crate::memory::NEXT_MMAP_ADDR.fetch_add(gap + actual_size as u64, Ordering::SeqCst) + gap
     };
     println!("sys_mmap: returning virt = {:#x}", virt_addr);
     let mut flags = PageTableFlags::PRESENT;
@@ -50,18 +78,50 @@ pub fn sys_mmap(
     if prot & 0x4 == 0 {
         flags |= PageTableFlags::NO_EXECUTE;
     }
-    for i in 0..page_count {
+    let mut frames = alloc::vec::Vec::with_capacity(page_count);
+    for _ in 0..page_count {
+        let Some(frame) = crate::memory::allocate_frame() else {
+            unwind(virt_addr, 0, &frames);
+            return Err(MemError::OutOfFrames);
+        };
+        frames.push(frame);
+    }
+
+    let executable = !flags.contains(PageTableFlags::NO_EXECUTE);
+    if executable && crate::memory::jit::frames_in_use(&frames) {
+        println!("sys_mmap: frame still mapped executable elsewhere, refusing");
+        unwind(virt_addr, 0, &frames);
+        return Err(MemError::PermissionDenied);
+    }
+
+    for (i, &frame) in frames.iter().enumerate() {
         let page_virt = VirtAddr::new(virt_addr + (i * 4096) as u64);
         println!(
             "  mapped virt {:#x}   flags={:?}",
             page_virt.as_u64(),
             flags
         );
-        let frame = crate::memory::allocate_frame().ok_or(SyscallError::NoMemory)?;
         crate::memory::zero_frame(frame);
-        crate::memory::map_single_page(page_virt, frame, flags)
-            .map_err(|_| SyscallError::NoMemory)?;
+        if let Err(err) = crate::memory::map_single_page(page_virt, frame, flags) {
+            unwind(virt_addr, i, &frames);
+            return Err(err);
+        }
+    }
+
+    if executable {
+        crate::memory::jit::register(virt_addr, frames);
     }
 
     Ok(virt_addr as usize)
 }
+
+/// Undoes a `sys_mmap` that failed part way: unmaps the first `mapped`
+/// pages at `virt_addr` and returns every frame it took.
+fn unwind(virt_addr: u64, mapped: usize, frames: &[PhysFrame<Size4KiB>]) {
+    for i in 0..mapped {
+        crate::memory::unmap_single_page(VirtAddr::new(virt_addr + (i * 4096) as u64));
+    }
+    for &frame in frames {
+        crate::memory::free_frame(frame);
+    }
+}
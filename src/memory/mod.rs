@@ -5,11 +5,21 @@ use core::alloc::{GlobalAlloc, Layout};
 use core::ptr;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
+pub mod address_space;
 pub mod allocators;
+#[cfg(feature = "alloc-track")]
+pub mod alloc_track;
 pub mod brk;
 pub mod debug;
+pub mod error;
+pub mod frame_bitmap;
+pub mod heap;
+pub mod jit;
 pub mod mmap;
 pub mod munmap;
+pub mod oom;
+pub mod pressure;
+pub mod vmmap;
 
 use x86_64::registers::control::Cr3;
 use x86_64::{
@@ -20,12 +30,16 @@ use x86_64::{
 };
 
 use crate::memory::allocators::block::FixedSizeBlockAllocator;
+use crate::memory::frame_bitmap::FrameBitmap;
+use crate::memory::heap::{Heap, HeapInfo, HeapKind, SwitchError};
 
 // ============================================================================
 // CONSTANTS AND STATICS
 // ============================================================================
 
 const KERNEL_HEAP_SIZE: usize = 256 * 1024 * 1024;
+/// Region given to an allocator switched in with `switch_heap`.
+const GUEST_HEAP_SIZE: usize = 16 * 1024 * 1024;
 
 #[repr(align(4096))]
 struct HeapBuffer([u8; KERNEL_HEAP_SIZE]);
@@ -34,9 +48,21 @@ static mut KERNEL_HEAP_BUFFER: HeapBuffer = HeapBuffer([0; KERNEL_HEAP_SIZE]);
 pub static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
 pub static PHYSICAL_MEMORY_START: AtomicU64 = AtomicU64::new(0);
 pub static PHYSICAL_MEMORY_END: AtomicU64 = AtomicU64::new(0);
-pub static NEXT_PHYSICAL_FRAME: AtomicU64 = AtomicU64::new(0);
-
-static NEXT_MMAP_ADDR: AtomicU64 = AtomicU64::new(0x2000_0000);
+/// End of the bootloader's physical memory mapping: the highest address
+/// any memory region reaches. Firmware tables lie below it.
+pub static PHYSICAL_MAPPING_END: AtomicU64 = AtomicU64::new(0);
+
+/// Where `sys_mmap` starts handing out addresses, above the process code
+/// slots; `sys_munmap` refuses anything below.
+pub const MMAP_BASE: u64 = 0x2000_0000;
+static NEXT_MMAP_ADDR: AtomicU64 = AtomicU64::new(MMAP_BASE);
+/// Device registers are mapped upwards from here, in the kernel half.
+const MMIO_BASE: u64 = 0xFFFF_FF00_0000_0000;
+static NEXT_MMIO_ADDR: AtomicU64 = AtomicU64::new(MMIO_BASE);
+/// Windows given back by `unmap_mmio`, as (start, pages), sorted and
+/// merged; `map_mmio` reuses them before the window grows.
+static MMIO_FREE: spin::Mutex<alloc::vec::Vec<(u64, u64)>> =
+    spin::Mutex::new(alloc::vec::Vec::new());
 static MEMORY_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
 // ============================================================================
@@ -47,7 +73,7 @@ static MEMORY_INITIALIZED: AtomicBool = AtomicBool::new(false);
 static KERNEL_ALLOCATOR: LockedHeap = LockedHeap::new();
 
 pub struct LockedHeap {
-    inner: spin::Mutex<Option<FixedSizeBlockAllocator>>,
+    inner: spin::Mutex<Option<Heap>>,
 }
 
 impl LockedHeap {
@@ -60,53 +86,133 @@ impl LockedHeap {
 
 unsafe impl GlobalAlloc for LockedHeap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let guard = self.inner.lock();
-        if let Some(allocator) = guard.as_ref() {
-            allocator.alloc(layout)
-        } else {
-            core::ptr::null_mut()
+        let result = match self.inner.lock().as_mut() {
+            Some(heap) => {
+                let result = heap.try_alloc(layout);
+                pressure::note_heap(heap.used());
+                result
+            }
+            None => Ok(core::ptr::null_mut()),
+        };
+        // Report poison damage only once the heap lock is released, so the
+        // panic path can still allocate.
+        let ptr = result.unwrap_or_else(|err| allocators::poison::fail(err));
+        if ptr.is_null() && layout.size() != 0 {
+            pressure::note_failure();
+        }
+        #[cfg(feature = "alloc-track")]
+        if !ptr.is_null() {
+            alloc_track::record_alloc(ptr as usize, layout.size());
         }
+        ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        let guard = self.inner.lock();
-        if let Some(allocator) = guard.as_ref() {
-            allocator.dealloc(ptr, layout);
+        #[cfg(feature = "alloc-track")]
+        alloc_track::record_dealloc(ptr as usize);
+        let mut guard = self.inner.lock();
+        if let Some(heap) = guard.as_mut() {
+            heap.dealloc(ptr, layout);
         }
     }
 }
 
+/// Sends new heap allocations to `kind`; see `heap` for what this means
+/// for memory already allocated.
+pub fn switch_heap(kind: HeapKind) -> Result<(), SwitchError> {
+    match KERNEL_ALLOCATOR.inner.lock().as_mut() {
+        Some(heap) => heap.switch(kind),
+        None => Err(SwitchError::NoRegion),
+    }
+}
+
+pub fn heap_info() -> Option<HeapInfo> {
+    KERNEL_ALLOCATOR.inner.lock().as_ref().map(Heap::info)
+}
+
+/// `heap_info` without waiting: `None` while the heap lock is held.
+pub fn try_heap_info() -> Option<HeapInfo> {
+    KERNEL_ALLOCATOR.inner.try_lock()?.as_ref().map(Heap::info)
+}
+
+/// Heap bytes in use and the heap's size.
+pub fn heap_usage() -> (usize, usize) {
+    let used = heap_info().map_or(0, |info| info.used);
+    (used, KERNEL_HEAP_SIZE)
+}
+
+/// Allocates, fills, re-reads and frees a spread of block sizes `rounds`
+/// times. Safe to run on any CPU; a block that does not read back what
+/// was written means two CPUs were handed the same memory.
+pub fn bench_alloc(rounds: u32) -> Result<alloc::string::String, alloc::string::String> {
+    let mut cycles = 0;
+    for round in 0..rounds {
+        cycles += bench_alloc_round(round)?;
+    }
+    Ok(bench_alloc_report(rounds, cycles))
+}
+
+const BENCH_SIZES: [usize; 6] = [16, 64, 256, 1024, 4096, 16384];
+const BENCH_PER_SIZE: usize = 8;
+
+/// One round of `bench_alloc`, for callers that spread the rounds out.
+/// Returns the cycles it took.
+pub fn bench_alloc_round(round: u32) -> Result<u64, alloc::string::String> {
+    use crate::stats::latency::rdtsc;
+    use alloc::{format, vec, vec::Vec};
+
+    let start = rdtsc();
+    let round = round as usize;
+    let tag = |n: usize| (round * 31 + n) as u8;
+    let mut blocks = Vec::with_capacity(BENCH_SIZES.len() * BENCH_PER_SIZE);
+    for n in 0..BENCH_SIZES.len() * BENCH_PER_SIZE {
+        blocks.push(vec![tag(n); BENCH_SIZES[n % BENCH_SIZES.len()]]);
+    }
+    for (n, block) in blocks.iter().enumerate() {
+        if let Some(bad) = block.iter().position(|&b| b != tag(n)) {
+            return Err(format!(
+                "alloc: block {:p}+{} changed under us in round {}",
+                block.as_ptr(),
+                bad,
+                round
+            ));
+        }
+    }
+    drop(blocks);
+    Ok(rdtsc() - start)
+}
+
+/// The `bench_alloc` line for `rounds` rounds that took `cycles` in all.
+pub fn bench_alloc_report(rounds: u32, cycles: u64) -> alloc::string::String {
+    use crate::stats::latency::tsc_per_us;
+
+    let allocs = rounds as u64 * (BENCH_SIZES.len() * BENCH_PER_SIZE) as u64;
+    let us = cycles / tsc_per_us().max(1);
+    alloc::format!(
+        "alloc: {} allocations in {} us ({} cycles each), contents verified",
+        allocs,
+        us,
+        cycles / allocs.max(1)
+    )
+}
+
 // ============================================================================
 // PHYSICAL FRAME ALLOCATOR
 // ============================================================================
 
+/// Every frame of the validated window, built once the heap is up.
+static FRAMES: spin::Mutex<Option<FrameBitmap>> = spin::Mutex::new(None);
+
+fn with_frames<R>(f: impl FnOnce(&mut FrameBitmap) -> Option<R>) -> Option<R> {
+    x86_64::instructions::interrupts::without_interrupts(|| FRAMES.lock().as_mut().and_then(f))
+}
+
 pub struct GlobalFrameAllocator;
 
 unsafe impl FrameAllocator<Size4KiB> for GlobalFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
-        loop {
-            let current = NEXT_PHYSICAL_FRAME.load(Ordering::SeqCst);
-            let frame_addr = (current + 4095) & !4095; // Align up to 4KB
-            let next_frame = frame_addr + 4096;
-            let memory_end = PHYSICAL_MEMORY_END.load(Ordering::SeqCst);
-
-            if next_frame > memory_end {
-                return None;
-            }
-
-            match NEXT_PHYSICAL_FRAME.compare_exchange_weak(
-                current,
-                next_frame,
-                Ordering::SeqCst,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => {
-                    let phys_addr = PhysAddr::new(frame_addr);
-                    return Some(PhysFrame::containing_address(phys_addr));
-                }
-                Err(_) => continue,
-            }
-        }
+        let addr = with_frames(FrameBitmap::allocate)?;
+        Some(PhysFrame::containing_address(PhysAddr::new(addr)))
     }
 }
 
@@ -115,11 +221,187 @@ pub fn allocate_frame() -> Option<PhysFrame<Size4KiB>> {
     alloc.allocate_frame()
 }
 
+/// `count` physically consecutive frames starting on an `align`-byte
+/// boundary; the first of them is returned. Each one goes back with
+/// `free_frame`.
+pub fn allocate_contiguous(count: usize, align: u64) -> Option<PhysFrame<Size4KiB>> {
+    let addr = with_frames(|frames| frames.allocate_contiguous(count, align))?;
+    Some(PhysFrame::containing_address(PhysAddr::new(addr)))
+}
+
+/// Frames in the longest free stretch of the window.
+pub fn largest_free_run() -> usize {
+    with_frames(|frames| Some(frames.largest_free_run())).unwrap_or(0)
+}
+
+/// Returns a frame to the allocator. Frames outside the allocator's window
+/// (MMIO, bootloader-owned memory) and frames not handed out are refused
+/// and `false` is returned. That covers a double free: the bitmap bit is
+/// already clear, so the second call changes nothing and a frame is never
+/// handed out twice. The contents are not cleared; like fresh frames,
+/// reused ones must be zeroed by whoever needs that.
+pub fn free_frame(frame: PhysFrame<Size4KiB>) -> bool {
+    let addr = frame.start_address().as_u64();
+    with_frames(|frames| frames.free(addr).then_some(())).is_some()
+}
+
+/// Frames of the window not in use.
+pub fn free_frame_count() -> usize {
+    with_frames(|frames| Some(frames.free_count())).unwrap_or(0)
+}
+
+/// Frames of the window in use.
+pub fn used_frame_count() -> usize {
+    with_frames(|frames| Some(frames.used())).unwrap_or(0)
+}
+
+// ============================================================================
+// FRAME WINDOW VALIDATION
+// ============================================================================
+
+const MAX_KERNEL_SEGMENTS: usize = 16;
+const PT_LOAD: u32 = 1;
+
+/// Physical frames backing a virtual range: lowest and highest frame, and
+/// how many of them fall inside `[win_start, win_end)`.
+struct PhysExtent {
+    lo: u64,
+    hi: u64,
+    pages: u64,
+    in_window: u64,
+}
+
+impl PhysExtent {
+    const fn empty() -> Self {
+        Self {
+            lo: u64::MAX,
+            hi: 0,
+            pages: 0,
+            in_window: 0,
+        }
+    }
+
+    fn add_frame(&mut self, frame: u64, win_start: u64, win_end: u64) {
+        self.lo = self.lo.min(frame);
+        self.hi = self.hi.max(frame + 4096);
+        self.pages += 1;
+        if frame < win_end && frame + 4096 > win_start {
+            self.in_window += 1;
+        }
+    }
+
+    /// Adds every mapped page of `[start, start + len)`.
+    fn add_virt_range(
+        &mut self,
+        mapper: &OffsetPageTable,
+        start: u64,
+        len: u64,
+        win_start: u64,
+        win_end: u64,
+    ) {
+        use x86_64::structures::paging::Translate;
+
+        let mut va = start & !0xFFF;
+        while va < start + len {
+            if let Some(pa) = mapper.translate_addr(VirtAddr::new(va)) {
+                self.add_frame(pa.as_u64() & !0xFFF, win_start, win_end);
+            }
+            va += 4096;
+        }
+    }
+}
+
+/// `(virt start, size)` of the kernel's PT_LOAD segments, read from the
+/// ELF file the bootloader left at `kernel_addr`.
+unsafe fn kernel_segments(boot_info: &BootInfo) -> ([(u64, u64); MAX_KERNEL_SEGMENTS], usize) {
+    let mut segments = [(0u64, 0u64); MAX_KERNEL_SEGMENTS];
+    let mut count = 0;
+
+    let elf = phys_to_virt(PhysAddr::new(boot_info.kernel_addr)).as_ptr::<u8>();
+    let len = boot_info.kernel_len as usize;
+    if len < 0x40 || core::slice::from_raw_parts(elf, 4) != b"\x7fELF" {
+        return (segments, 0);
+    }
+    let phoff = ptr::read_unaligned(elf.add(0x20) as *const u64) as usize;
+    let phentsize = ptr::read_unaligned(elf.add(0x36) as *const u16) as usize;
+    let phnum = ptr::read_unaligned(elf.add(0x38) as *const u16) as usize;
+    if phoff.saturating_add(phentsize.saturating_mul(phnum)) > len {
+        return (segments, 0);
+    }
+
+    for i in 0..phnum {
+        let ph = elf.add(phoff + i * phentsize);
+        if ptr::read_unaligned(ph as *const u32) != PT_LOAD || count == MAX_KERNEL_SEGMENTS {
+            continue;
+        }
+        let vaddr = ptr::read_unaligned(ph.add(0x10) as *const u64);
+        let memsz = ptr::read_unaligned(ph.add(0x28) as *const u64);
+        segments[count] = (vaddr + boot_info.kernel_image_offset, memsz);
+        count += 1;
+    }
+    (segments, count)
+}
+
+/// Fails if any frame of the kernel image, the kernel ELF file or the heap
+/// buffer lies inside the frame allocator window, which would let mapped
+/// pages alias live kernel memory.
+unsafe fn validate_frame_window(
+    boot_info: &BootInfo,
+    win_start: u64,
+    win_end: u64,
+) -> Result<(), &'static str> {
+    let mapper = active_page_table();
+
+    let mut elf = PhysExtent::empty();
+    let mut frame = boot_info.kernel_addr & !0xFFF;
+    while frame < boot_info.kernel_addr + boot_info.kernel_len {
+        elf.add_frame(frame, win_start, win_end);
+        frame += 4096;
+    }
+
+    let (segments, count) = kernel_segments(boot_info);
+    if count == 0 {
+        return Err("Cannot read kernel segments to validate the frame window");
+    }
+    let mut image = PhysExtent::empty();
+    for &(start, size) in &segments[..count] {
+        image.add_virt_range(&mapper, start, size, win_start, win_end);
+    }
+
+    let mut heap = PhysExtent::empty();
+    let heap_start = core::ptr::addr_of!(KERNEL_HEAP_BUFFER) as u64;
+    heap.add_virt_range(&mapper, heap_start, KERNEL_HEAP_SIZE as u64, win_start, win_end);
+
+    println!("INIT: Kernel ELF phys {:#x}-{:#x}", elf.lo, elf.hi);
+    println!(
+        "INIT: Kernel image phys extent {:#x}-{:#x} ({} pages in {} segments)",
+        image.lo, image.hi, image.pages, count
+    );
+    println!(
+        "INIT: Heap buffer phys extent {:#x}-{:#x} ({} pages)",
+        heap.lo, heap.hi, heap.pages
+    );
+
+    if heap.pages as usize != KERNEL_HEAP_SIZE / 4096 {
+        return Err("Kernel heap buffer is not fully mapped");
+    }
+    if elf.in_window + image.in_window + heap.in_window > 0 {
+        println!(
+            "INIT: Frame window {:#x}-{:#x} overlaps kernel memory: elf={} image={} heap={} pages",
+            win_start, win_end, elf.in_window, image.in_window, heap.in_window
+        );
+        return Err("Frame allocator window overlaps kernel memory");
+    }
+    Ok(())
+}
+
 // ============================================================================
 // INITIALIZATION
 // ============================================================================
 
 pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
+    crate::stats::backtrace::init_stack_bound();
+
     let phys_offset = boot_info.physical_memory_offset.into_option().unwrap_or(0);
 
     PHYSICAL_MEMORY_OFFSET.store(phys_offset, Ordering::SeqCst);
@@ -134,6 +416,7 @@ pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
     let mut largest_region_size = 0u64;
     let mut best_region_start = 0u64;
     let mut best_region_end = 0u64;
+    let mut mapping_end = 0u64;
 
     println!("INIT: Memory regions from bootloader:");
     for region in boot_info.memory_regions.iter() {
@@ -141,6 +424,7 @@ pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
             "  Region: {:#x}-{:#x} kind={:?}",
             region.start, region.end, region.kind
         );
+        mapping_end = mapping_end.max(region.end);
         if region.kind == MemoryRegionKind::Usable {
             let size = region.end - region.start;
             if size > largest_region_size {
@@ -162,6 +446,7 @@ pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
     if largest_region_size == 0 {
         return Err("No usable memory found");
     }
+    PHYSICAL_MAPPING_END.store(mapping_end, Ordering::SeqCst);
 
     let mut frame_start = 0u64;
     let mut frame_end = 0u64;
@@ -193,14 +478,10 @@ pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
         );
     }
 
-    println!(
-        "INIT: Kernel at ~0x1000000. Frame allocator: {:#x}-{:#x}",
-        frame_start, frame_end
-    );
+    validate_frame_window(boot_info, frame_start, frame_end)?;
 
     PHYSICAL_MEMORY_START.store(frame_start, Ordering::SeqCst);
     PHYSICAL_MEMORY_END.store(frame_end, Ordering::SeqCst);
-    NEXT_PHYSICAL_FRAME.store(frame_start, Ordering::SeqCst);
 
     println!(
         "INIT: Frame allocator: start={:#x}, end={:#x}",
@@ -208,7 +489,11 @@ pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
     );
 
     // Initialize heap allocator
-    let allocator = FixedSizeBlockAllocator::new();
+    let allocator = if cfg!(feature = "heap-poison") {
+        FixedSizeBlockAllocator::poisoned()
+    } else {
+        FixedSizeBlockAllocator::new()
+    };
     let heap_ptr = KERNEL_HEAP_BUFFER.0.as_mut_ptr() as usize;
     println!(
         "INIT: Attempting heap init: ptr={:#x}, size={:#x}",
@@ -224,7 +509,8 @@ pub unsafe fn init(boot_info: &BootInfo) -> Result<(), &'static str> {
             return Err("Failed to initialize kernel heap");
         }
     }
-    *KERNEL_ALLOCATOR.inner.lock() = Some(allocator);
+    *KERNEL_ALLOCATOR.inner.lock() = Some(Heap::new(allocator, GUEST_HEAP_SIZE));
+    *FRAMES.lock() = Some(FrameBitmap::new(frame_start, frame_end));
 
     MEMORY_INITIALIZED.store(true, Ordering::SeqCst);
     println!("INIT: Memory system initialized");
@@ -256,23 +542,36 @@ unsafe fn access_page_table(phys: PhysAddr) -> &'static mut PageTable {
 // PAGE TABLE MAPPING - DIRECT APPROACH
 // ============================================================================
 
-/// Map errors
-#[derive(Debug, Clone, Copy)]
-pub enum MapError {
-    OutOfMemory,
-    AlreadyMapped,
-    InvalidAddress,
-    WalkError,
-}
+pub use error::MemError;
 
 /// 4KiB
 pub fn map_single_page(
     virt: VirtAddr,
     frame: PhysFrame<Size4KiB>,
     flags: PageTableFlags,
-) -> Result<(), MapError> {
+) -> Result<(), MemError> {
+    let (cr3_frame, _) = Cr3::read();
+    map_page_in(cr3_frame, virt, frame, flags)?;
+
+    // Flush TLB for this page
+    x86_64::instructions::tlb::flush(virt);
+
+    Ok(())
+}
+
+/// Maps a 4 KiB page in the address space rooted at `p4`, which need not
+/// be the active one. Missing tables are allocated with
+/// `address_space::alloc_table_frame`. No TLB flush is done.
+pub fn map_page_in(
+    p4: PhysFrame<Size4KiB>,
+    virt: VirtAddr,
+    frame: PhysFrame<Size4KiB>,
+    flags: PageTableFlags,
+) -> Result<(), MemError> {
     if (virt.as_u64() & 0xfff) != 0 {
-        return Err(MapError::InvalidAddress);
+        return Err(MemError::Misaligned {
+            addr: virt.as_u64(),
+        });
     }
 
     let page = Page::<Size4KiB>::containing_address(virt);
@@ -281,8 +580,7 @@ pub fn map_single_page(
     let p2_idx = page.p2_index();
     let p1_idx = page.p1_index();
 
-    let (cr3_frame, _) = Cr3::read();
-    let cr3_phys = cr3_frame.start_address();
+    let cr3_phys = p4.start_address();
 
     // Parent entry flags - MUST NOT have NO_EXECUTE to allow executable pages
     let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
@@ -293,24 +591,21 @@ pub fn map_single_page(
 
     if p4_entry.is_unused() {
         // Allocate new P3 table
-        let new_frame = allocate_frame().ok_or(MapError::OutOfMemory)?;
-        // Zero the new table
-        unsafe {
-            let new_table = access_page_table(new_frame.start_address());
-            ptr::write_bytes(new_table as *mut PageTable as *mut u8, 0, 4096);
-        }
+        let new_frame = address_space::alloc_table_frame().ok_or(MemError::OutOfFrames)?;
         p4_entry.set_frame(new_frame, parent_flags);
     } else if p4_entry.flags().contains(PageTableFlags::NO_EXECUTE)
         && !flags.contains(PageTableFlags::NO_EXECUTE)
     {
         // Clear NO_EXECUTE on parent if we need executable page
-        let current_frame = p4_entry.frame().map_err(|_| MapError::WalkError)?;
+        let current_frame = p4_entry
+            .frame()
+            .map_err(|_| MemError::WalkFailed { level: 4 })?;
         p4_entry.set_frame(current_frame, parent_flags);
     }
 
     let p3_phys = p4_entry
         .frame()
-        .map_err(|_| MapError::WalkError)?
+        .map_err(|_| MemError::WalkFailed { level: 4 })?
         .start_address();
 
     // Walk P3 -> P2
@@ -318,22 +613,20 @@ pub fn map_single_page(
     let p3_entry = &mut p3_table[p3_idx];
 
     if p3_entry.is_unused() {
-        let new_frame = allocate_frame().ok_or(MapError::OutOfMemory)?;
-        unsafe {
-            let new_table = access_page_table(new_frame.start_address());
-            ptr::write_bytes(new_table as *mut PageTable as *mut u8, 0, 4096);
-        }
+        let new_frame = address_space::alloc_table_frame().ok_or(MemError::OutOfFrames)?;
         p3_entry.set_frame(new_frame, parent_flags);
     } else if p3_entry.flags().contains(PageTableFlags::NO_EXECUTE)
         && !flags.contains(PageTableFlags::NO_EXECUTE)
     {
-        let current_frame = p3_entry.frame().map_err(|_| MapError::WalkError)?;
+        let current_frame = p3_entry
+            .frame()
+            .map_err(|_| MemError::WalkFailed { level: 3 })?;
         p3_entry.set_frame(current_frame, parent_flags);
     }
 
     let p2_phys = p3_entry
         .frame()
-        .map_err(|_| MapError::WalkError)?
+        .map_err(|_| MemError::WalkFailed { level: 3 })?
         .start_address();
 
     // Walk P2 -> P1
@@ -341,34 +634,35 @@ pub fn map_single_page(
     let p2_entry = &mut p2_table[p2_idx];
 
     if p2_entry.is_unused() {
-        let new_frame = allocate_frame().ok_or(MapError::OutOfMemory)?;
-        unsafe {
-            let new_table = access_page_table(new_frame.start_address());
-            ptr::write_bytes(new_table as *mut PageTable as *mut u8, 0, 4096);
-        }
+        let new_frame = address_space::alloc_table_frame().ok_or(MemError::OutOfFrames)?;
         p2_entry.set_frame(new_frame, parent_flags);
     } else if p2_entry.flags().contains(PageTableFlags::NO_EXECUTE)
         && !flags.contains(PageTableFlags::NO_EXECUTE)
     {
-        let current_frame = p2_entry.frame().map_err(|_| MapError::WalkError)?;
+        let current_frame = p2_entry
+            .frame()
+            .map_err(|_| MemError::WalkFailed { level: 2 })?;
         p2_entry.set_frame(current_frame, parent_flags);
     }
 
     let p1_phys = p2_entry
         .frame()
-        .map_err(|_| MapError::WalkError)?
+        .map_err(|_| MemError::WalkFailed { level: 2 })?
         .start_address();
 
     // Set the P1 entry (final mapping)
     let p1_table = unsafe { access_page_table(p1_phys) };
     let p1_entry = &mut p1_table[p1_idx];
 
+    if p1_entry.flags().contains(PageTableFlags::PRESENT) {
+        return Err(MemError::AlreadyMapped {
+            virt: virt.as_u64(),
+        });
+    }
+
     // Set the mapping with explicit flags
     p1_entry.set_frame(frame, flags | PageTableFlags::PRESENT);
 
-    // Flush TLB for this page
-    x86_64::instructions::tlb::flush(virt);
-
     Ok(())
 }
 
@@ -425,6 +719,161 @@ pub fn page_is_mapped(virt: VirtAddr) -> bool {
     !p1_entry.is_unused() && p1_entry.flags().contains(PageTableFlags::PRESENT)
 }
 
+/// Clears the 4 KiB mapping at `virt` and returns the frame and flags it
+/// had. Intermediate tables are left in place even if they become empty.
+pub fn unmap_single_page(virt: VirtAddr) -> Option<(PhysFrame<Size4KiB>, PageTableFlags)> {
+    let page = Page::<Size4KiB>::containing_address(virt);
+    let (cr3_frame, _) = Cr3::read();
+
+    let mut table = unsafe { access_page_table(cr3_frame.start_address()) };
+    for idx in [page.p4_index(), page.p3_index(), page.p2_index()] {
+        // frame() fails for unused and huge entries alike
+        let next = table[idx].frame().ok()?;
+        table = unsafe { access_page_table(next.start_address()) };
+    }
+
+    let entry = &mut table[page.p1_index()];
+    let frame = entry.frame().ok()?;
+    let flags = entry.flags();
+    entry.set_unused();
+    x86_64::instructions::tlb::flush(virt);
+    Some((frame, flags))
+}
+
+/// `pages` pages of the MMIO window: the first returned window that is
+/// big enough, or fresh space past the end.
+fn take_mmio_window(pages: u64) -> u64 {
+    let mut free = MMIO_FREE.lock();
+    if let Some(i) = free.iter().position(|&(_, n)| n >= pages) {
+        let (start, n) = free[i];
+        if n == pages {
+            free.remove(i);
+        } else {
+            free[i] = (start + pages * 4096, n - pages);
+        }
+        return start;
+    }
+    NEXT_MMIO_ADDR.fetch_add(pages * 4096, Ordering::SeqCst)
+}
+
+/// Gives `pages` pages at `start` back to the MMIO window.
+fn release_mmio_window(start: u64, pages: u64) {
+    if pages == 0 {
+        return;
+    }
+    let mut free = MMIO_FREE.lock();
+    free.push((start, pages));
+    free.sort_unstable();
+    let mut merged: alloc::vec::Vec<(u64, u64)> = alloc::vec::Vec::with_capacity(free.len());
+    for &(start, pages) in free.iter() {
+        match merged.last_mut() {
+            Some(last) if last.0 + last.1 * 4096 == start => last.1 += pages,
+            _ => merged.push((start, pages)),
+        }
+    }
+    *free = merged;
+}
+
+/// Maps `size` bytes of device registers at `phys` uncached and returns
+/// the virtual address of `phys`. On failure nothing stays mapped. Give
+/// the window back with `unmap_mmio`; registers mapped at boot keep
+/// theirs.
+pub fn map_mmio(phys: PhysAddr, size: usize) -> Result<VirtAddr, MemError> {
+    if size == 0 {
+        return Err(MemError::InvalidArgument);
+    }
+    let first = phys.align_down(4096u64);
+    let pages = (phys.as_u64() - first.as_u64() + size as u64).div_ceil(4096);
+    let virt = take_mmio_window(pages);
+    map_mmio_at(virt, first, pages)?;
+    Ok(VirtAddr::new(virt + (phys.as_u64() - first.as_u64())))
+}
+
+/// Maps `pages` pages from `first` at `virt`, which `take_mmio_window`
+/// gave out. On failure it unmaps what it mapped and gives the whole
+/// window back; a page someone else had mapped stays theirs to unmap.
+fn map_mmio_at(virt: u64, first: PhysAddr, pages: u64) -> Result<(), MemError> {
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH
+        | PageTableFlags::NO_EXECUTE;
+    for i in 0..pages {
+        let page = VirtAddr::new(virt + i * 4096);
+        // The window is ours only if nothing else got there first.
+        let mapped = if page_is_mapped(page) {
+            Err(MemError::AlreadyMapped {
+                virt: page.as_u64(),
+            })
+        } else {
+            let frame = PhysFrame::containing_address(first + i * 4096);
+            map_single_page(page, frame, flags)
+        };
+        if let Err(err) = mapped {
+            for done in 0..i {
+                unmap_single_page(VirtAddr::new(virt + done * 4096));
+            }
+            release_mmio_window(virt, pages);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Unmaps `size` bytes mapped by `map_mmio` at `virt` and gives the
+/// window back. The frames are the device's and are left alone.
+pub fn unmap_mmio(virt: VirtAddr, size: usize) {
+    let start = virt.align_down(4096u64);
+    let pages = (virt.as_u64() - start.as_u64() + size as u64).div_ceil(4096);
+    for i in 0..pages {
+        unmap_single_page(start + i * 4096);
+    }
+    release_mmio_window(start.as_u64(), pages);
+}
+
+/// A zeroed, physically contiguous buffer of at least `bytes` for a
+/// device to read or write, mapped uncached in the MMIO window. The frame
+/// window lies below 16 MiB, so the buffer suits ISA as well as PCI DMA.
+/// Returns its virtual and physical addresses; release it with `free_dma`.
+pub fn alloc_dma(bytes: usize) -> Result<(VirtAddr, PhysAddr), MemError> {
+    alloc_dma_aligned(bytes, 4096)
+}
+
+/// `alloc_dma` with the physical start on an `align`-byte boundary (a
+/// power of two), for controllers whose descriptors must not cross one.
+pub fn alloc_dma_aligned(bytes: usize, align: u64) -> Result<(VirtAddr, PhysAddr), MemError> {
+    if bytes == 0 || !align.is_power_of_two() {
+        return Err(MemError::InvalidArgument);
+    }
+    let pages = bytes.div_ceil(4096);
+    let first = allocate_contiguous(pages, align).ok_or(MemError::OutOfFrames)?;
+    let phys = first.start_address();
+    let frames = (0..pages as u64).map(|i| PhysFrame::containing_address(phys + i * 4096));
+
+    for frame in frames.clone() {
+        zero_frame(frame);
+    }
+    match map_mmio(phys, pages * 4096) {
+        Ok(virt) => Ok((virt, phys)),
+        Err(err) => {
+            for frame in frames {
+                free_frame(frame);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Unmaps and frees a buffer from `alloc_dma`, giving its window back.
+/// `bytes` is the size it was asked for.
+pub fn free_dma(virt: VirtAddr, phys: PhysAddr, bytes: usize) {
+    let pages = bytes.div_ceil(4096);
+    unmap_mmio(virt, pages * 4096);
+    for i in 0..pages as u64 {
+        free_frame(PhysFrame::containing_address(phys + i * 4096));
+    }
+}
+
 /// Zero a physical frame's contents
 fn zero_frame(frame: PhysFrame<Size4KiB>) {
     let offset = PHYSICAL_MEMORY_OFFSET.load(Ordering::SeqCst);
@@ -460,10 +909,10 @@ pub fn map_range(
     first_frame: PhysFrame<Size4KiB>,
     flags: PageTableFlags,
     allocator: &mut impl FrameAllocator<Size4KiB>,
-) -> Result<(), MapError> {
+) -> Result<(), MemError> {
     const PAGE_SIZE: usize = 4096;
     if len == 0 || (len % PAGE_SIZE) != 0 {
-        return Err(MapError::InvalidAddress);
+        return Err(MemError::InvalidArgument);
     }
 
     let n_pages = len / PAGE_SIZE;
@@ -472,7 +921,7 @@ pub fn map_range(
 
     for i in 0..n_pages {
         if i > 0 {
-            cur_frame = allocator.allocate_frame().ok_or(MapError::OutOfMemory)?;
+            cur_frame = allocator.allocate_frame().ok_or(MemError::OutOfFrames)?;
         }
         map_single_page(v, cur_frame, flags)?;
         v = VirtAddr::new(v.as_u64() + PAGE_SIZE as u64);
@@ -485,12 +934,10 @@ pub fn map_range(
 // PROCESS SUPPORT FUNCTIONS
 // ============================================================================
 
-/// Create a new page table for a process (clone of kernel mappings)
-pub fn create_process_page_table() -> Result<PhysFrame<Size4KiB>, &'static str> {
-    let new_frame = allocate_frame().ok_or("Failed to allocate frame for process page table")?;
-
-    // Zero the new P4 table
-    zero_frame(new_frame);
+/// Create a new page table for a process (clone of kernel mappings).
+/// Release it with `address_space::destroy_address_space`.
+pub fn create_process_page_table() -> Result<PhysFrame<Size4KiB>, MemError> {
+    let new_frame = address_space::alloc_table_frame().ok_or(MemError::OutOfFrames)?;
 
     // Copy kernel mappings from current P4 to new P4
     let (current_p4_frame, _) = Cr3::read();
@@ -505,32 +952,350 @@ pub fn create_process_page_table() -> Result<PhysFrame<Size4KiB>, &'static str>
     Ok(new_frame)
 }
 
-/// Start a process with the given code
-pub unsafe fn sys_pstart(code_ptr: *const u8, code_size: usize) -> Result<usize, &'static str> {
-    use core::sync::atomic::AtomicUsize;
-    static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
+/// Process code slots start here, `PROCESS_CODE_STRIDE` apart, so
+/// processes that still share the kernel address space never overlap.
+pub const PROCESS_CODE_BASE: u64 = 0x40_0000;
+pub const PROCESS_CODE_STRIDE: u64 = 0x10_0000;
+/// The slots end where `sys_mmap` starts handing out addresses.
+const PROCESS_CODE_SLOTS: u64 = (MMAP_BASE - PROCESS_CODE_BASE) / PROCESS_CODE_STRIDE;
+
+/// The pid holding each taken code slot, by slot. A slot is taken at
+/// `sys_pstart` and given back by `release_process_code`.
+static CODE_SLOTS: spin::Mutex<alloc::collections::BTreeMap<u64, usize>> =
+    spin::Mutex::new(alloc::collections::BTreeMap::new());
+
+/// Where code slot `slot` starts.
+pub fn code_slot_base(slot: u64) -> Option<u64> {
+    (slot < PROCESS_CODE_SLOTS).then(|| PROCESS_CODE_BASE + slot * PROCESS_CODE_STRIDE)
+}
+
+/// Takes the lowest free code slot for `pid` and returns where it starts.
+fn claim_code_slot(pid: usize) -> Option<u64> {
+    let mut slots = CODE_SLOTS.lock();
+    let slot = (0..PROCESS_CODE_SLOTS).find(|slot| !slots.contains_key(slot))?;
+    slots.insert(slot, pid);
+    code_slot_base(slot)
+}
 
-    if code_ptr.is_null() || code_size == 0 {
-        return Err("Invalid code pointer or size");
+/// Patches the absolute immediates of an image loaded at `base`. Each
+/// fixup is the offset of a little-endian u64 holding an offset into the
+/// image; it becomes `base` plus that offset.
+pub fn relocate(image: &mut [u8], base: u64, fixups: &[usize]) -> Result<(), MemError> {
+    for &at in fixups {
+        // A fixup past the end of the image, or a target past the end of
+        // the address space
+        let end = at.checked_add(8).ok_or(MemError::InvalidArgument)?;
+        let field = image.get_mut(at..end).ok_or(MemError::InvalidArgument)?;
+        let offset = u64::from_le_bytes(field.try_into().unwrap());
+        let target = base.checked_add(offset).ok_or(MemError::InvalidArgument)?;
+        field.copy_from_slice(&target.to_le_bytes());
     }
+    Ok(())
+}
+
+/// Where `sys_pstart` put a process's code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessCode {
+    pub pid: usize,
+    pub base: u64,
+    pub size: usize,
+}
+
+/// Start a process with the given code, copied to the lowest free code
+/// slot and relocated there with `fixups` (see `relocate`).
+/// Position-independent code passes no fixups. The process gets
+/// `Caps::PSTART`. If any step fails, nothing it took stays taken; once
+/// the process has ended, `release_process_code` gives its slot back.
+pub unsafe fn sys_pstart(
+    code_ptr: *const u8,
+    code_size: usize,
+    fixups: &[usize],
+) -> Result<ProcessCode, MemError> {
+    use crate::syscalls::{handlers::process, policy::Caps};
+
+    // Empty, or larger than its slot
+    if code_ptr.is_null() || code_size == 0 || code_size as u64 > PROCESS_CODE_STRIDE {
+        return Err(MemError::InvalidArgument);
+    }
+
+    let pid = process::get_next_pid();
+    let code_virt = claim_code_slot(pid).ok_or(MemError::OutOfAddressSpace)?;
+    let frames = match place_code(code_virt, code_ptr, code_size, fixups) {
+        Ok(frames) => frames,
+        Err(err) => {
+            CODE_SLOTS.lock().retain(|_, owner| *owner != pid);
+            return Err(err);
+        }
+    };
+    jit::register_process(pid, code_virt, frames);
+    process::set_caps(pid, Caps::PSTART);
+
+    Ok(ProcessCode {
+        pid,
+        base: code_virt,
+        size: code_size,
+    })
+}
 
-    // Allocate memory for the process code
-    let page_count = (code_size + 4095) / 4096;
-    let code_virt = 0x40_0000u64; // Process code starts at 4MB
+/// Maps fresh frames at `code_virt`, then copies the code there and
+/// relocates it. On failure whatever it mapped is unmapped and freed.
+unsafe fn place_code(
+    code_virt: u64,
+    code_ptr: *const u8,
+    code_size: usize,
+    fixups: &[usize],
+) -> Result<alloc::vec::Vec<PhysFrame<Size4KiB>>, MemError> {
+    let page_count = code_size.div_ceil(4096);
+    let mut frames = alloc::vec::Vec::with_capacity(page_count);
+    let placed = (|| {
+        for i in 0..page_count {
+            let page_virt = VirtAddr::new(code_virt + (i * 4096) as u64);
+            let frame = allocate_frame().ok_or(MemError::OutOfFrames)?;
+            zero_frame(frame);
+
+            let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+            if let Err(err) = map_single_page(page_virt, frame, flags) {
+                free_frame(frame);
+                return Err(err);
+            }
+            frames.push(frame);
+        }
 
-    for i in 0..page_count {
-        let page_virt = VirtAddr::new(code_virt + (i * 4096) as u64);
-        let frame = allocate_frame().ok_or("Failed to allocate frame for process code")?;
+        // Copy the code, then fix it up for where it landed
+        let dest = code_virt as *mut u8;
+        ptr::copy_nonoverlapping(code_ptr, dest, code_size);
+        relocate(core::slice::from_raw_parts_mut(dest, code_size), code_virt, fixups)
+    })();
+    match placed {
+        Ok(()) => Ok(frames),
+        Err(err) => {
+            unmap_code(code_virt, &frames);
+            Err(err)
+        }
+    }
+}
+
+/// Unmaps code pages from `code_virt` on and frees their frames, zeroed
+/// so no code outlives the process.
+fn unmap_code(code_virt: u64, frames: &[PhysFrame<Size4KiB>]) {
+    for (i, &frame) in frames.iter().enumerate() {
+        unmap_single_page(VirtAddr::new(code_virt + (i * 4096) as u64));
         zero_frame(frame);
+        free_frame(frame);
+    }
+}
 
-        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
-        map_single_page(page_virt, frame, flags).map_err(|_| "Failed to map process code page")?;
+/// Takes back what `sys_pstart` gave process `pid`: its code pages, their
+/// frames and its code slot. Nothing happens for a pid without code.
+pub fn release_process_code(pid: usize) {
+    if let Some((start, frames)) = jit::take_process(pid) {
+        unmap_code(start, &frames);
     }
+    CODE_SLOTS.lock().retain(|_, owner| *owner != pid);
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
 
-    // Copy the code
-    let dest = code_virt as *mut u8;
-    ptr::copy_nonoverlapping(code_ptr, dest, code_size);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let pid = NEXT_PID.fetch_add(1, Ordering::SeqCst);
-    Ok(pid)
+    #[test_case]
+    fn process_slots_and_fixups() {
+        assert_eq!(code_slot_base(0), Some(PROCESS_CODE_BASE));
+        assert_eq!(
+            code_slot_base(2),
+            Some(PROCESS_CODE_BASE + 2 * PROCESS_CODE_STRIDE)
+        );
+        let last = code_slot_base(PROCESS_CODE_SLOTS - 1).unwrap();
+        assert!(last + PROCESS_CODE_STRIDE <= MMAP_BASE);
+        assert_eq!(code_slot_base(PROCESS_CODE_SLOTS), None);
+
+        let mut image = [0u8; 12];
+        image[2..10].copy_from_slice(&16u64.to_le_bytes());
+        relocate(&mut image, 0x50_0000, &[2]).unwrap();
+        assert_eq!(image[2..10], 0x50_0010u64.to_le_bytes());
+        assert!(relocate(&mut image, 0, &[5]).is_err());
+        assert!(relocate(&mut image, 0, &[usize::MAX - 3]).is_err());
+        assert_eq!(
+            relocate(&mut image, u64::MAX, &[2]),
+            Err(MemError::InvalidArgument)
+        );
+    }
+
+    #[test_case]
+    fn pstart_gives_everything_back() {
+        use crate::syscalls::{handlers::process, policy::Caps};
+        const RET: &[u8] = &[0xc3];
+        let free_before = free_frame_count();
+        // A fixup past the image fails once the code is mapped
+        assert_eq!(
+            unsafe { sys_pstart(RET.as_ptr(), RET.len(), &[5]) },
+            Err(MemError::InvalidArgument)
+        );
+        assert_eq!(free_frame_count(), free_before);
+
+        // More launches than there are slots, each ended before the next
+        let first = unsafe { sys_pstart(RET.as_ptr(), RET.len(), &[]) }.unwrap();
+        process::release(first.pid);
+        for _ in 0..PROCESS_CODE_SLOTS + 8 {
+            let code = unsafe { sys_pstart(RET.as_ptr(), RET.len(), &[]) }.unwrap();
+            assert_eq!(code.base, first.base);
+            assert_eq!(process::caps(code.pid), Caps::PSTART);
+            process::release(code.pid);
+            assert_eq!(process::caps(code.pid), Caps::NONE);
+        }
+        assert_eq!(free_frame_count(), free_before);
+    }
+
+    #[test_case]
+    fn mapping_errors_name_the_page() {
+        let virt = VirtAddr::new(0x5000_0000_0000);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+        let frame = allocate_frame().expect("frame");
+        map_single_page(virt, frame, flags).unwrap();
+        assert_eq!(
+            map_single_page(virt, frame, flags),
+            Err(MemError::AlreadyMapped {
+                virt: virt.as_u64()
+            })
+        );
+        assert_eq!(
+            map_single_page(virt + 8u64, frame, flags),
+            Err(MemError::Misaligned {
+                addr: virt.as_u64() + 8
+            })
+        );
+
+        unmap_single_page(virt);
+        free_frame(frame);
+        assert_eq!(
+            munmap::sys_munmap(virt.as_u64() as usize, 4096),
+            Err(MemError::NotMapped {
+                virt: virt.as_u64()
+            })
+        );
+    }
+
+    #[test_case]
+    fn munmap_returns_frames_and_keeps_to_the_mmap_range() {
+        const RW: usize = 0x3;
+        const RWX: usize = 0x7;
+        let free_before = free_frame_count();
+        let addr = mmap::sys_mmap(0, 2 * 4096, RW, 0, 0, 0).unwrap();
+        assert_eq!(free_frame_count(), free_before - 2);
+        assert_eq!(munmap::sys_munmap(addr, 2 * 4096), Ok(0));
+        assert_eq!(free_frame_count(), free_before);
+
+        // Unmapping part of an executable region keeps the rest tracked
+        let freed = jit::freed_count();
+        let code = mmap::sys_mmap(0, 2 * 4096, RWX, 0, 0, 0).unwrap();
+        let first_page = alloc::format!("{:#x}    4096 B  1 frames", code);
+        assert_eq!(munmap::sys_munmap(code + 4096, 4096), Ok(0));
+        assert_eq!(jit::freed_count(), freed + 1);
+        assert!(jit::report().contains(&first_page));
+        assert_eq!(munmap::sys_munmap(code, 4096), Ok(0));
+        assert_eq!(jit::freed_count(), freed + 2);
+        assert!(!jit::report().contains(&first_page));
+        assert_eq!(free_frame_count(), free_before);
+
+        // A placement hint outside the mmap range is refused
+        for hint in [
+            PROCESS_CODE_BASE as usize,
+            0xFFFF_8000_0000_0000,
+            usize::MAX,
+        ] {
+            assert_eq!(
+                mmap::sys_mmap(hint, 4096, RW, 0, 0, 0),
+                Err(MemError::InvalidArgument)
+            );
+        }
+
+        // Nothing below the mmap range or past the lower half, no wrapping
+        assert_eq!(
+            munmap::sys_munmap(PROCESS_CODE_BASE as usize, 4096),
+            Err(MemError::PermissionDenied)
+        );
+        assert_eq!(
+            munmap::sys_munmap(0xFFFF_8000_0000_0000, 4096),
+            Err(MemError::PermissionDenied)
+        );
+        assert_eq!(
+            munmap::sys_munmap(addr, usize::MAX),
+            Err(MemError::InvalidArgument)
+        );
+    }
+
+    #[test_case]
+    fn a_second_free_of_a_frame_is_refused() {
+        let frame = allocate_frame().expect("frame");
+        let free_before = free_frame_count();
+        assert!(free_frame(frame));
+        assert!(!free_frame(frame));
+        assert_eq!(free_frame_count(), free_before + 1);
+    }
+
+    #[test_case]
+    fn dma_buffer_is_physically_contiguous() {
+        let free_before = free_frame_count();
+        let bytes = 3 * 4096;
+        let (virt, phys) = alloc_dma(bytes).expect("dma");
+        assert_eq!(free_frame_count(), free_before - 3);
+
+        // each page of the mapping lands on the next physical frame
+        for i in 0..3u64 {
+            let page = (virt + i * 4096).as_mut_ptr::<u8>();
+            let direct = phys_to_virt(phys + i * 4096).as_ptr::<u8>();
+            unsafe {
+                assert_eq!(*page, 0);
+                *page = 0xA0 + i as u8;
+                assert_eq!(ptr::read_volatile(direct), 0xA0 + i as u8);
+            }
+        }
+
+        free_dma(virt, phys, bytes);
+        assert_eq!(free_frame_count(), free_before);
+        assert!(!page_is_mapped(virt));
+
+        let (virt, phys) = alloc_dma_aligned(4096, 64 * 1024).expect("aligned");
+        assert!(phys.is_aligned(64 * 1024u64));
+        free_dma(virt, phys, 4096);
+        assert!(alloc_dma(usize::MAX / 2).is_err());
+    }
+
+    #[test_case]
+    fn mmio_windows_come_back_whole_or_failed() {
+        // A freed buffer's window is the next one handed out
+        let (virt, phys) = alloc_dma(2 * 4096).expect("dma");
+        free_dma(virt, phys, 2 * 4096);
+        let (again, phys) = alloc_dma(2 * 4096).expect("dma again");
+        assert_eq!(again, virt);
+        free_dma(again, phys, 2 * 4096);
+
+        // Someone else's page in the middle: the pages before it are
+        // unmapped, their mapping is left alone, the window goes back whole
+        let window = take_mmio_window(3);
+        let theirs = allocate_frame().expect("frame");
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        map_single_page(VirtAddr::new(window + 4096), theirs, flags).expect("map");
+        assert_eq!(
+            map_mmio_at(window, phys, 3),
+            Err(MemError::AlreadyMapped {
+                virt: window + 4096
+            })
+        );
+        assert!(!page_is_mapped(VirtAddr::new(window)));
+        assert!(!page_is_mapped(VirtAddr::new(window + 2 * 4096)));
+        let is_free = |addr: u64| {
+            MMIO_FREE
+                .lock()
+                .iter()
+                .any(|&(start, pages)| start <= addr && addr < start + pages * 4096)
+        };
+        assert!(page_is_mapped(VirtAddr::new(window + 4096)));
+        assert!((0..3).all(|i| is_free(window + i * 4096)));
+
+        unmap_single_page(VirtAddr::new(window + 4096));
+        free_frame(theirs);
+    }
 }
@@ -4,6 +4,7 @@ use bootloader_api::BootInfo;
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::memory::MemError;
 use x86_64::registers::control::Cr3;
 use x86_64::{
     structures::paging::{
@@ -12,15 +13,48 @@ use x86_64::{
     PhysAddr, VirtAddr,
 };
 
-pub fn sys_munmap(
-    addr: usize,
-    length: usize,
-) -> Result<usize, crate::syscalls::dispatcher::SyscallError> {
-    use crate::syscalls::dispatcher::SyscallError;
+/// Unmaps the pages of `addr..addr + length` that are mapped and returns
+/// their frames. Only the range `sys_mmap` hands out may be unmapped, so a
+/// caller cannot pull kernel, device or process code pages from under
+/// their owners.
+pub fn sys_munmap(addr: usize, length: usize) -> Result<usize, MemError> {
+    if length == 0 {
+        return Err(MemError::InvalidArgument);
+    }
+    if addr & 0xFFF != 0 {
+        return Err(MemError::Misaligned { addr: addr as u64 });
+    }
+    let size = length
+        .checked_add(4095)
+        .ok_or(MemError::InvalidArgument)?
+        & !0xFFF;
+    let end = addr.checked_add(size).ok_or(MemError::InvalidArgument)?;
+    if (addr as u64) < crate::memory::MMAP_BASE || end > crate::syscalls::user::USER_END {
+        return Err(MemError::PermissionDenied);
+    }
+    // Presents read a surface's pixels; only destroying it unmaps them
+    if crate::gfx::surface::overlaps_mapping(addr, size) {
+        return Err(MemError::PermissionDenied);
+    }
 
-    if length == 0 || addr & 0xFFF != 0 {
-        return Err(SyscallError::InvalidArgument);
+    let mut unmapped = 0;
+    for page in (addr..end).step_by(4096) {
+        let page_virt = VirtAddr::try_new(page as u64).map_err(|_| MemError::InvalidArgument)?;
+        let Some((frame, flags)) = crate::memory::unmap_single_page(page_virt) else {
+            continue;
+        };
+        unmapped += 1;
+        // Never let stale code bytes outlive an executable mapping.
+        if !flags.contains(PageTableFlags::NO_EXECUTE) {
+            crate::memory::zero_frame(frame);
+        }
+        crate::memory::free_frame(frame);
     }
+    crate::memory::jit::release_range(addr as u64, size as u64);
 
+    // Holes inside the range are fine; a range with nothing mapped is not.
+    if unmapped == 0 {
+        return Err(MemError::NotMapped { virt: addr as u64 });
+    }
     Ok(0)
 }
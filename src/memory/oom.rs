@@ -0,0 +1,132 @@
+//! # Out of Memory
+//!
+//! When the heap cannot satisfy an allocation that has no fallback, `alloc`
+//! calls the kernel's `alloc_error_handler`, which halts. Before it does,
+//! `describe` writes what failed: the layout, the heap's state and the
+//! chain of callers (see `stats::backtrace`). The handler prints it over
+//! serial and on the panic screen.
+//!
+//! Nothing here allocates, since the heap just refused: the report is
+//! formatted into a `Report` on the stack, and the heap is read with
+//! `try_lock` so a handler that fires while the heap lock is held still
+//! reports.
+
+use core::alloc::Layout;
+use core::fmt::{self, Write};
+
+/// Bytes of report kept; the rest is cut.
+const REPORT_LEN: usize = 512;
+/// Callers listed.
+const CALLER_DEPTH: usize = 6;
+
+/// Text formatted on the stack, cut at `REPORT_LEN` bytes.
+pub struct Report {
+    bytes: [u8; REPORT_LEN],
+    len: usize,
+}
+
+impl Report {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; REPORT_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole `str`s (or the valid prefix of one) are copied in.
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Report {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut take = s.len().min(REPORT_LEN - self.len);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        self.bytes[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        Ok(())
+    }
+}
+
+/// Writes the report for a failed allocation of `layout`.
+#[inline(always)]
+pub fn describe(layout: Layout, out: &mut impl Write) -> fmt::Result {
+    let mut callers = [0usize; CALLER_DEPTH];
+    let found = crate::stats::backtrace::callers(0, &mut callers);
+
+    writeln!(
+        out,
+        "out of memory: {} bytes, align {}",
+        layout.size(),
+        layout.align()
+    )?;
+    match super::try_heap_info() {
+        Some(info) => {
+            writeln!(
+                out,
+                "heap: {} of {} KiB in use, {} allocator",
+                info.used / 1024,
+                super::KERNEL_HEAP_SIZE / 1024,
+                info.active.name()
+            )?;
+            if let Some(guest) = info.guest {
+                writeln!(
+                    out,
+                    "guest heap: {} live allocation(s) in {} KiB",
+                    guest.live,
+                    guest.size / 1024
+                )?;
+            }
+        }
+        None => writeln!(out, "heap: busy, not read")?,
+    }
+    write!(out, "callers:")?;
+    if found == 0 {
+        write!(out, " unknown (no frame pointers)")?;
+    }
+    for addr in &callers[..found] {
+        write!(out, " {:#x}", addr)?;
+    }
+    writeln!(out)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn refused_allocation_is_described() {
+        // Far past the heap: refused without calling the handler
+        let layout = Layout::from_size_align(2 * super::super::KERNEL_HEAP_SIZE, 4096).unwrap();
+        let ptr = unsafe { alloc::alloc::alloc(layout) };
+        assert!(ptr.is_null());
+        // The failure asked for a trim; take it so later tests start clean
+        super::super::pressure::take_request();
+
+        let mut report = Report::new();
+        describe(layout, &mut report).unwrap();
+        let text = report.as_str();
+        assert!(text.starts_with("out of memory: 536870912 bytes, align 4096\n"));
+        assert!(text.contains("KiB in use"));
+        assert!(text.contains("callers:"));
+
+        // Cut on a char boundary once full
+        let mut full = Report::new();
+        full.write_str("a").unwrap();
+        for _ in 0..REPORT_LEN {
+            full.write_str("é").unwrap();
+        }
+        assert_eq!(full.as_str().len(), REPORT_LEN - 1);
+    }
+}
@@ -0,0 +1,280 @@
+//! # Memory Pressure
+//!
+//! Caches that grow with use (the debug log, the terminal's history) give
+//! memory back when the heap or the frame pool runs low, instead of some
+//! unrelated allocation failing later. The global allocator only records
+//! that the heap looks full (`note_heap`, under its lock, so it just sets a
+//! flag); the main loop calls `take_request`, which adds the frame
+//! headroom, and runs `trim` outside every allocator lock.
+//!
+//! Trimmers are plain functions registered with `register_trimmer`; each
+//! gets the level and returns roughly how many bytes it freed. The apps
+//! belong to the main loop, so it trims them through `AppHost::trim` and
+//! adds them to the summary itself before `finish` logs it and keeps it
+//! for `meminfo`. A flagged trim runs at most once per
+//! `TRIM_INTERVAL_TICKS`, so a heap that stays full is not trimmed every
+//! frame; `memtrim` and failed allocations skip that wait.
+//!
+//! `High` is below 25% free heap or 15% free frames, `Critical` below 10%
+//! or 5%. The heap share is taken against `heap_limit`, the heap size
+//! unless lowered with `set_heap_limit` (by a test, or to watch the
+//! trimmers work).
+
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// About two seconds of timer ticks.
+const TRIM_INTERVAL_TICKS: u64 = 36;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pressure {
+    Normal = 0,
+    High = 1,
+    Critical = 2,
+}
+
+impl Pressure {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pressure::Normal => "normal",
+            Pressure::High => "high",
+            Pressure::Critical => "critical",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Pressure::Normal, Pressure::High, Pressure::Critical]
+            .into_iter()
+            .find(|level| level.name() == name)
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Pressure::Normal,
+            1 => Pressure::High,
+            _ => Pressure::Critical,
+        }
+    }
+
+    /// The level for the given free shares, in percent.
+    pub fn from_free(heap_percent: usize, frames_percent: usize) -> Self {
+        if heap_percent < 10 || frames_percent < 5 {
+            Pressure::Critical
+        } else if heap_percent < 25 || frames_percent < 15 {
+            Pressure::High
+        } else {
+            Pressure::Normal
+        }
+    }
+}
+
+/// A registered trimmer: frees what it can at a level, returns the bytes.
+pub type TrimFn = fn(Pressure) -> usize;
+
+/// What one `trim` freed, per trimmer.
+#[derive(Debug, Clone)]
+pub struct TrimSummary {
+    pub level: Pressure,
+    pub freed: Vec<(&'static str, usize)>,
+}
+
+impl TrimSummary {
+    pub fn add(&mut self, name: &'static str, bytes: usize) {
+        self.freed.push((name, bytes));
+    }
+
+    pub fn total(&self) -> usize {
+        self.freed.iter().map(|&(_, bytes)| bytes).sum()
+    }
+
+    fn describe(&self) -> String {
+        let mut out = format!("{}, {} bytes", self.level.name(), self.total());
+        for (name, bytes) in &self.freed {
+            out.push_str(&format!("; {} {}", name, bytes));
+        }
+        out
+    }
+}
+
+static TRIMMERS: Mutex<Vec<(&'static str, TrimFn)>> = Mutex::new(Vec::new());
+/// 0 for the whole heap.
+static HEAP_LIMIT: AtomicUsize = AtomicUsize::new(0);
+/// Set by the allocator when the heap crossed into `High`.
+static FLAGGED: AtomicBool = AtomicBool::new(false);
+/// A level asked for regardless of the interval: `memtrim`, or Critical
+/// after a failed allocation. 0 for none.
+static FORCED: AtomicU8 = AtomicU8::new(0);
+/// Tick of the last trim, plus one; 0 before the first.
+static LAST_TICK: AtomicU64 = AtomicU64::new(0);
+static LAST: Mutex<Option<TrimSummary>> = Mutex::new(None);
+
+fn ticks() -> u64 {
+    crate::kcore::interrupts::interrupts::TIMER_TICKS.load(Ordering::Relaxed)
+}
+
+fn free_percent(used: usize, total: usize) -> usize {
+    if total == 0 {
+        return 100;
+    }
+    total.saturating_sub(used) * 100 / total
+}
+
+pub fn register_trimmer(name: &'static str, trim: TrimFn) {
+    TRIMMERS.lock().push((name, trim));
+}
+
+pub fn heap_limit() -> usize {
+    match HEAP_LIMIT.load(Ordering::Relaxed) {
+        0 => super::heap_usage().1,
+        limit => limit,
+    }
+}
+
+/// Measures the heap against `bytes` instead of its real size; 0 undoes
+/// it. Allocation itself is not limited.
+pub fn set_heap_limit(bytes: usize) {
+    HEAP_LIMIT.store(bytes, Ordering::Relaxed);
+}
+
+/// Called by the global allocator with the heap bytes in use. Must not
+/// allocate or take a lock.
+pub fn note_heap(used: usize) {
+    let limit = match HEAP_LIMIT.load(Ordering::Relaxed) {
+        0 => super::KERNEL_HEAP_SIZE,
+        limit => limit,
+    };
+    if free_percent(used, limit) < 25 {
+        FLAGGED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Called by the global allocator when an allocation failed.
+pub fn note_failure() {
+    FORCED.fetch_max(Pressure::Critical as u8, Ordering::Relaxed);
+}
+
+/// Asks for a trim at `level` or worse on the next main-loop pass.
+pub fn request(level: Pressure) {
+    FORCED.fetch_max((level as u8).max(1), Ordering::Relaxed);
+}
+
+/// The level now, from the heap and the frame pool.
+pub fn current() -> Pressure {
+    let (used, _) = super::heap_usage();
+    let frames_used = super::used_frame_count();
+    let frames_free = super::free_frame_count();
+    Pressure::from_free(
+        free_percent(used, heap_limit()),
+        free_percent(frames_used, frames_used + frames_free),
+    )
+}
+
+/// The level to trim at, if a trim is due.
+pub fn take_request() -> Option<Pressure> {
+    let forced = FORCED.swap(0, Ordering::Relaxed);
+    let flagged = FLAGGED.swap(false, Ordering::Relaxed);
+    if forced == 0 {
+        let last = LAST_TICK.load(Ordering::Relaxed);
+        if !flagged || (last != 0 && ticks() < last - 1 + TRIM_INTERVAL_TICKS) {
+            return None;
+        }
+    }
+    let level = current().max(Pressure::from_u8(forced));
+    (level > Pressure::Normal).then_some(level)
+}
+
+/// Runs every registered trimmer at `level`. Call with no allocator or
+/// cache lock held; pass the result to `finish` once the apps are added.
+pub fn trim(level: Pressure) -> TrimSummary {
+    let trimmers = TRIMMERS.lock().clone();
+    let mut summary = TrimSummary {
+        level,
+        freed: Vec::with_capacity(trimmers.len() + 1),
+    };
+    for (name, trim) in trimmers {
+        summary.add(name, trim(level));
+    }
+    summary
+}
+
+/// Logs what each trimmer freed and keeps the summary for `meminfo`.
+pub fn finish(summary: TrimSummary) {
+    crate::log_info!("memtrim: {}", summary.describe());
+    LAST_TICK.store(ticks() + 1, Ordering::Relaxed);
+    *LAST.lock() = Some(summary);
+}
+
+/// Lines for `meminfo`.
+pub fn report() -> String {
+    let (used, _) = super::heap_usage();
+    let limit = heap_limit();
+    let mut out = format!(
+        "Pressure      {} ({}% of a {} KiB heap free)\n",
+        current().name(),
+        free_percent(used, limit),
+        limit / 1024
+    );
+    match LAST.lock().as_ref() {
+        Some(summary) => out.push_str(&format!("Last trim     {}\n", summary.describe())),
+        None => out.push_str("Last trim     none\n"),
+    }
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    static HOARD: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+    fn trim_hoard(_level: Pressure) -> usize {
+        let hoard = core::mem::take(&mut *HOARD.lock());
+        hoard.iter().map(Vec::len).sum()
+    }
+
+    #[test_case]
+    fn high_pressure_runs_the_trimmers() {
+        assert_eq!(Pressure::from_free(50, 50), Pressure::Normal);
+        assert_eq!(Pressure::from_free(20, 50), Pressure::High);
+        assert_eq!(Pressure::from_free(50, 4), Pressure::Critical);
+
+        register_trimmer("test hoard", trim_hoard);
+        take_request();
+        LAST_TICK.store(0, Ordering::Relaxed);
+
+        // Half the limit in use, then fill it in sixteenths until the
+        // allocator flags it
+        let heap_free = || free_percent(crate::memory::heap_usage().0, heap_limit());
+        let (used, _) = crate::memory::heap_usage();
+        let limit = used * 2 + 64 * 1024;
+        set_heap_limit(limit);
+        for _ in 0..16 {
+            if heap_free() < 25 {
+                break;
+            }
+            HOARD.lock().push(vec![1u8; limit / 16]);
+        }
+        let level = take_request();
+        assert!(level >= Some(Pressure::High));
+
+        let summary = trim(level.unwrap());
+        let hoarded = summary
+            .freed
+            .iter()
+            .find(|&&(name, _)| name == "test hoard")
+            .map(|&(_, bytes)| bytes);
+        assert!(hoarded >= Some(limit / 4));
+        finish(summary);
+        assert!(HOARD.lock().is_empty());
+        assert!(heap_free() >= 25);
+
+        let after = vec![2u8; limit / 8];
+        assert_eq!(after[limit / 8 - 1], 2);
+        set_heap_limit(0);
+        assert!(report().contains("test hoard"));
+    }
+}
@@ -0,0 +1,201 @@
+//! # Address Space Map
+//!
+//! Walks the active page tables and merges contiguous pages with identical
+//! effective flags into regions, for the `vmmap` command.
+//!
+//! The low (user) half is walked down to 4 KiB granularity. The kernel half
+//! holds the physical-memory window and the heap, so it is only walked to
+//! P2 granularity: every present P2 entry counts as a 2 MiB unit and P1
+//! tables are never touched, which keeps the summary fast.
+
+use crate::memory::{access_page_table, debug::format_flags};
+use alloc::{format, string::String, vec::Vec};
+use x86_64::{registers::control::Cr3, structures::paging::PageTableFlags};
+
+const SIZE_4K: u64 = 1 << 12;
+const SIZE_2M: u64 = 1 << 21;
+const SIZE_1G: u64 = 1 << 30;
+const SIZE_512G: u64 = 1 << 39;
+
+/// Flags that decide whether two adjacent pages belong to the same region.
+const SIGNIFICANT: PageTableFlags = PageTableFlags::from_bits_truncate(
+    PageTableFlags::WRITABLE.bits()
+        | PageTableFlags::USER_ACCESSIBLE.bits()
+        | PageTableFlags::NO_EXECUTE.bits(),
+);
+
+#[derive(Clone, Copy, Debug)]
+pub struct MappedRegion {
+    pub start: u64,
+    /// Exclusive.
+    pub end: u64,
+    pub flags: PageTableFlags,
+    /// True if any part of the region came from a 2 MiB/1 GiB leaf.
+    pub huge: bool,
+}
+
+impl MappedRegion {
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn format_line(&self) -> String {
+        format!(
+            "{:#x}-{:#x}  {:>9}  {}{}",
+            self.start,
+            self.end - 1,
+            format_size(self.size()),
+            format_flags(self.flags),
+            if self.huge { " huge" } else { "" }
+        )
+    }
+}
+
+pub struct RegionCollector {
+    regions: Vec<MappedRegion>,
+    max_regions: usize,
+    truncated: bool,
+}
+
+impl RegionCollector {
+    pub fn new(max_regions: usize) -> Self {
+        Self {
+            regions: Vec::new(),
+            max_regions,
+            truncated: false,
+        }
+    }
+
+    pub fn regions(&self) -> &[MappedRegion] {
+        &self.regions
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn add(&mut self, start: u64, size: u64, flags: PageTableFlags, huge: bool) {
+        let flags = flags & SIGNIFICANT;
+        if let Some(last) = self.regions.last_mut() {
+            if last.end == start && last.flags == flags {
+                last.end = start + size;
+                last.huge |= huge;
+                return;
+            }
+        }
+        if self.regions.len() >= self.max_regions {
+            self.truncated = true;
+            return;
+        }
+        self.regions.push(MappedRegion {
+            start,
+            end: start + size,
+            flags,
+            huge,
+        });
+    }
+}
+
+/// Parent entries restrict their children: W and U must be set at every
+/// level, NX at any level wins.
+fn effective(parent: PageTableFlags, entry: PageTableFlags) -> PageTableFlags {
+    let restrict = PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+    let mut flags = entry & (parent | !restrict);
+    flags |= parent & PageTableFlags::NO_EXECUTE;
+    flags
+}
+
+fn canonical(addr: u64) -> u64 {
+    if addr & (1 << 47) != 0 {
+        addr | 0xFFFF_0000_0000_0000
+    } else {
+        addr
+    }
+}
+
+fn overlaps(start: u64, size: u64, lo: u64, hi: u64) -> bool {
+    start < hi && start.saturating_add(size) > lo
+}
+
+/// Walk P4 entries `p4_range` of the active tables, visiting only parts that
+/// intersect `[lo, hi)`. With `coarse`, P2 entries are reported as whole
+/// 2 MiB units without descending into P1.
+pub fn walk(
+    p4_range: core::ops::Range<usize>,
+    lo: u64,
+    hi: u64,
+    coarse: bool,
+    out: &mut RegionCollector,
+) {
+    let (cr3_frame, _) = Cr3::read();
+    let p4 = unsafe { access_page_table(cr3_frame.start_address()) };
+    let root = PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+
+    for i4 in p4_range {
+        let base4 = canonical(i4 as u64 * SIZE_512G);
+        let e4 = &p4[i4];
+        if !e4.flags().contains(PageTableFlags::PRESENT) || !overlaps(base4, SIZE_512G, lo, hi) {
+            continue;
+        }
+        let f4 = effective(root, e4.flags());
+        let Ok(p3_frame) = e4.frame() else { continue };
+        let p3 = unsafe { access_page_table(p3_frame.start_address()) };
+
+        for i3 in 0..512 {
+            let base3 = base4 + i3 as u64 * SIZE_1G;
+            let e3 = &p3[i3];
+            if !e3.flags().contains(PageTableFlags::PRESENT) || !overlaps(base3, SIZE_1G, lo, hi) {
+                continue;
+            }
+            let f3 = effective(f4, e3.flags());
+            if e3.flags().contains(PageTableFlags::HUGE_PAGE) {
+                out.add(base3, SIZE_1G, f3, true);
+                continue;
+            }
+            let Ok(p2_frame) = e3.frame() else { continue };
+            let p2 = unsafe { access_page_table(p2_frame.start_address()) };
+
+            for i2 in 0..512 {
+                let base2 = base3 + i2 as u64 * SIZE_2M;
+                let e2 = &p2[i2];
+                if !e2.flags().contains(PageTableFlags::PRESENT)
+                    || !overlaps(base2, SIZE_2M, lo, hi)
+                {
+                    continue;
+                }
+                let f2 = effective(f3, e2.flags());
+                let huge = e2.flags().contains(PageTableFlags::HUGE_PAGE);
+                if huge || coarse {
+                    out.add(base2, SIZE_2M, f2, huge);
+                    continue;
+                }
+                let Ok(p1_frame) = e2.frame() else { continue };
+                let p1 = unsafe { access_page_table(p1_frame.start_address()) };
+
+                for i1 in 0..512 {
+                    let base1 = base2 + i1 as u64 * SIZE_4K;
+                    let e1 = &p1[i1];
+                    if !e1.flags().contains(PageTableFlags::PRESENT)
+                        || !overlaps(base1, SIZE_4K, lo, hi)
+                    {
+                        continue;
+                    }
+                    out.add(base1, SIZE_4K, effective(f2, e1.flags()), false);
+                }
+                if out.truncated() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= SIZE_1G && bytes.is_multiple_of(SIZE_1G) {
+        format!("{} GiB", bytes / SIZE_1G)
+    } else if bytes >= SIZE_2M / 2 && bytes.is_multiple_of(1 << 20) {
+        format!("{} MiB", bytes >> 20)
+    } else {
+        format!("{} KiB", bytes >> 10)
+    }
+}
@@ -0,0 +1,60 @@
+//! # Backtraces
+//!
+//! Return addresses found by walking saved `rbp`s, bounded to the boot
+//! stack. Nothing here allocates or takes a lock, so the allocation
+//! tracker and the out-of-memory report can both use it. Build with
+//! `RUSTFLAGS="-C force-frame-pointers=yes"` for meaningful chains; without
+//! frame pointers the walk stops early. Resolve addresses with
+//! `addr2line -e <kernel elf>`.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static STACK_TOP: AtomicUsize = AtomicUsize::new(0);
+
+/// Records the current stack page as the upper bound for walks. Call
+/// early on the boot stack.
+pub fn init_stack_bound() {
+    let rsp: usize;
+    unsafe {
+        core::arch::asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags))
+    };
+    STACK_TOP.store((rsp | 0xFFF) + 1, Ordering::Relaxed);
+}
+
+/// Fills `out` with the return addresses of the frames above the caller,
+/// after skipping `skip` of them. Returns how many were found; the rest of
+/// `out` is zeroed.
+#[inline(always)]
+pub fn callers(skip: usize, out: &mut [usize]) -> usize {
+    out.fill(0);
+    let top = STACK_TOP.load(Ordering::Relaxed);
+    let (mut rbp, rsp): (usize, usize);
+    unsafe {
+        core::arch::asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags));
+        core::arch::asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags));
+    }
+    // Only walk the boot stack, and only upwards.
+    if top == 0 || rsp >= top {
+        return 0;
+    }
+
+    let mut skip = skip;
+    let mut filled = 0;
+    while filled < out.len() {
+        if rbp < rsp || rbp + 16 > top || rbp % 8 != 0 {
+            break;
+        }
+        let (next, ret) = unsafe { (*(rbp as *const usize), *((rbp + 8) as *const usize)) };
+        if skip > 0 {
+            skip -= 1;
+        } else {
+            out[filled] = ret;
+            filled += 1;
+        }
+        if next <= rbp {
+            break;
+        }
+        rbp = next;
+    }
+    filled
+}
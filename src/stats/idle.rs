@@ -0,0 +1,61 @@
+//! # Idle Time
+//!
+//! TSC cycles the BSP spends halted in the main loop. Two readings of
+//! `Snapshot` give the idle share of the time between them, which `top`
+//! shows per refresh. Time spent halted elsewhere (the headless console,
+//! the APs) is not counted.
+
+use super::latency::rdtsc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+static IDLE_CYCLES: AtomicU64 = AtomicU64::new(0);
+
+/// Halts until the next interrupt and counts the wait as idle.
+pub fn halt() {
+    let start = rdtsc();
+    x86_64::instructions::hlt();
+    IDLE_CYCLES.fetch_add(rdtsc().wrapping_sub(start), Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    tsc: u64,
+    idle: u64,
+}
+
+impl Snapshot {
+    pub fn now() -> Self {
+        Self {
+            tsc: rdtsc(),
+            idle: IDLE_CYCLES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Percent of the time since `earlier` spent idle.
+    pub fn idle_percent_since(&self, earlier: &Snapshot) -> u64 {
+        let total = self.tsc.wrapping_sub(earlier.tsc);
+        let idle = self.idle.wrapping_sub(earlier.idle);
+        (idle.min(total) * 100).checked_div(total).unwrap_or(0)
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn idle_share_of_an_interval() {
+        let a = Snapshot {
+            tsc: 1_000,
+            idle: 50,
+        };
+        let b = Snapshot {
+            tsc: 3_000,
+            idle: 550,
+        };
+        assert_eq!(b.idle_percent_since(&a), 25);
+        assert_eq!(a.idle_percent_since(&a), 0);
+    }
+}
@@ -0,0 +1,219 @@
+//! # Input Latency
+//!
+//! Measures the time from the keyboard IRQ to the end of the `render_frame`
+//! that first showed the resulting key event.
+//!
+//! The IRQ stamps each scancode with the TSC (only while sampling is on),
+//! the stamp rides along in `KeyEvent::tsc`, the main loop hands it to
+//! `note_input` when the event is dispatched, and `frame_presented` closes
+//! every pending sample once the frame is on screen.
+
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+
+use crate::kcore::interrupts::interrupts::TIMER_TICKS;
+
+/// Bucket upper bounds in microseconds; the last bucket is open-ended.
+const BUCKET_BOUNDS_US: [u64; 5] = [1_000, 2_000, 4_000, 8_000, 16_000];
+const BUCKET_LABELS: [&str; 6] = ["<1ms", "1-2ms", "2-4ms", "4-8ms", "8-16ms", ">16ms"];
+const RECENT_SAMPLES: usize = 512;
+const MAX_PENDING: usize = 64;
+/// PIT ticks used to calibrate the TSC (~220 ms at 18.2 Hz).
+const CALIBRATION_TICKS: u64 = 4;
+const PIT_HZ_X1000: u64 = 18_206;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TSC_PER_US: AtomicU64 = AtomicU64::new(0);
+static RENDER_DELAY_MS: AtomicU64 = AtomicU64::new(0);
+static STATS: Mutex<LatencyStats> = Mutex::new(LatencyStats::new());
+
+struct LatencyStats {
+    buckets: [u64; 6],
+    recent: [u32; RECENT_SAMPLES],
+    recent_len: usize,
+    recent_next: usize,
+    total: u64,
+    max_us: u64,
+    pending: Vec<u64>,
+}
+
+impl LatencyStats {
+    const fn new() -> Self {
+        Self {
+            buckets: [0; 6],
+            recent: [0; RECENT_SAMPLES],
+            recent_len: 0,
+            recent_next: 0,
+            total: 0,
+            max_us: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, us: u64) {
+        let bucket = BUCKET_BOUNDS_US
+            .iter()
+            .position(|&bound| us < bound)
+            .unwrap_or(BUCKET_BOUNDS_US.len());
+        self.buckets[bucket] += 1;
+        self.recent[self.recent_next] = us.min(u32::MAX as u64) as u32;
+        self.recent_next = (self.recent_next + 1) % RECENT_SAMPLES;
+        self.recent_len = (self.recent_len + 1).min(RECENT_SAMPLES);
+        self.total += 1;
+        self.max_us = self.max_us.max(us);
+    }
+}
+
+#[inline]
+pub fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Timestamp for the IRQ path; 0 when sampling is off.
+#[inline]
+pub fn irq_timestamp() -> u64 {
+    if is_enabled() {
+        rdtsc()
+    } else {
+        0
+    }
+}
+
+/// TSC ticks per microsecond, calibrated against the PIT on first use
+/// (needs the timer interrupt running).
+pub fn tsc_per_us() -> u64 {
+    match TSC_PER_US.load(Ordering::Relaxed) {
+        0 => {
+            let per_us = calibrate_tsc_per_us();
+            TSC_PER_US.store(per_us, Ordering::Relaxed);
+            per_us
+        }
+        per_us => per_us,
+    }
+}
+
+/// Calibrates the TSC on first use, then starts sampling.
+pub fn enable() {
+    tsc_per_us();
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+    STATS.lock().pending.clear();
+}
+
+pub fn reset() {
+    let mut stats = STATS.lock();
+    let pending = core::mem::take(&mut stats.pending);
+    *stats = LatencyStats::new();
+    stats.pending = pending;
+}
+
+pub fn set_render_delay_ms(ms: u64) {
+    RENDER_DELAY_MS.store(ms, Ordering::Relaxed);
+}
+
+/// Debug knob: busy-waits inside the render path so the histogram shift
+/// can be checked end to end.
+pub fn apply_render_delay() {
+    let ms = RENDER_DELAY_MS.load(Ordering::Relaxed);
+    let per_us = TSC_PER_US.load(Ordering::Relaxed);
+    if ms == 0 || per_us == 0 {
+        return;
+    }
+    let end = rdtsc() + ms * 1000 * per_us;
+    while rdtsc() < end {
+        core::hint::spin_loop();
+    }
+}
+
+pub fn note_input(tsc: u64) {
+    if tsc == 0 || !is_enabled() {
+        return;
+    }
+    let mut stats = STATS.lock();
+    if stats.pending.len() < MAX_PENDING {
+        stats.pending.push(tsc);
+    }
+}
+
+/// Call right after `render_frame` returns.
+pub fn frame_presented() {
+    if !is_enabled() {
+        return;
+    }
+    let per_us = TSC_PER_US.load(Ordering::Relaxed).max(1);
+    let mut stats = STATS.lock();
+    if stats.pending.is_empty() {
+        return;
+    }
+    let now = rdtsc();
+    for i in 0..stats.pending.len() {
+        let start = stats.pending[i];
+        stats.record(now.saturating_sub(start) / per_us);
+    }
+    stats.pending.clear();
+}
+
+fn calibrate_tsc_per_us() -> u64 {
+    let wait_edge = || {
+        let start = TIMER_TICKS.load(Ordering::Relaxed);
+        while TIMER_TICKS.load(Ordering::Relaxed) == start {
+            core::hint::spin_loop();
+        }
+        TIMER_TICKS.load(Ordering::Relaxed)
+    };
+
+    let t0 = wait_edge();
+    let c0 = rdtsc();
+    while TIMER_TICKS.load(Ordering::Relaxed) < t0 + CALIBRATION_TICKS {
+        core::hint::spin_loop();
+    }
+    let cycles = rdtsc() - c0;
+    // cycles / (ticks / PIT_HZ) seconds / 1e6
+    (cycles * PIT_HZ_X1000 / (CALIBRATION_TICKS * 1_000_000_000)).max(1)
+}
+
+fn percentile(sorted: &[u32], pct: usize) -> u32 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let idx = (sorted.len() * pct / 100).min(sorted.len() - 1);
+    sorted[idx]
+}
+
+pub fn report() -> String {
+    let stats = STATS.lock();
+    let mut out = format!(
+        "Input latency (IRQ -> frame): sampling {}, {} samples, TSC {} MHz\n",
+        if is_enabled() { "on" } else { "off" },
+        stats.total,
+        TSC_PER_US.load(Ordering::Relaxed)
+    );
+
+    let peak = stats.buckets.iter().copied().max().unwrap_or(0).max(1);
+    for (label, &count) in BUCKET_LABELS.iter().zip(stats.buckets.iter()) {
+        let bar_len = (count * 30 / peak) as usize;
+        let bar = "#".repeat(bar_len);
+        out.push_str(&format!("  {:>7} {:>6} {}\n", label, count, bar));
+    }
+
+    let mut recent: Vec<u32> = stats.recent[..stats.recent_len].to_vec();
+    recent.sort_unstable();
+    out.push_str(&format!(
+        "  p50 {}us  p90 {}us  p99 {}us  max {}us (last {} samples)",
+        percentile(&recent, 50),
+        percentile(&recent, 90),
+        percentile(&recent, 99),
+        stats.max_us,
+        recent.len()
+    ));
+    out
+}
@@ -0,0 +1,13 @@
+//! # Runtime Statistics
+//!
+//! Counters and histograms collected by the kernel for diagnostics.
+//!
+//! - `backtrace`: return-address chains from the saved frame pointers
+//! - `latency`: keyboard IRQ to presented frame latency histogram
+//! - `stacks`: stack high-water marks from painted stacks
+//! - `idle`: time the main loop spends halted
+
+pub mod backtrace;
+pub mod idle;
+pub mod latency;
+pub mod stacks;
@@ -0,0 +1,163 @@
+//! # Stack Usage
+//!
+//! Stacks are filled with `PAINT` when they are set up, and a scan from
+//! the base upwards finds the first word that was overwritten since: the
+//! deepest point the stack ever reached. Overflows still go unnoticed
+//! until guard pages exist, but the high-water mark shows how close each
+//! stack got.
+//!
+//! A stack that is in use is only painted and scanned up to `RSP_MARGIN`
+//! below the current stack pointer, which leaves room for the caller's
+//! own frames and for interrupt frames pushed meanwhile.
+
+use crate::memory::page_is_mapped;
+use alloc::{format, string::String, vec::Vec};
+use spin::Mutex;
+use x86_64::VirtAddr;
+
+pub const PAINT: u64 = 0xCDCD_CDCD_CDCD_CDCD;
+const RSP_MARGIN: usize = 1024;
+
+struct Stack {
+    name: String,
+    base: usize,
+    size: usize,
+}
+
+static STACKS: Mutex<Vec<Stack>> = Mutex::new(Vec::new());
+
+#[inline(always)]
+fn current_rsp() -> usize {
+    let rsp: usize;
+    unsafe {
+        core::arch::asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags))
+    };
+    rsp
+}
+
+/// End of the part of `[base, base + size)` that may be painted or scanned.
+fn safe_end(base: usize, size: usize) -> usize {
+    let rsp = current_rsp();
+    if (base..base + size).contains(&rsp) {
+        rsp.saturating_sub(RSP_MARGIN).max(base) & !7
+    } else {
+        base + size
+    }
+}
+
+/// Paints the free part of the stack at `base` and adds it to `report`.
+pub fn track(name: &str, base: usize, size: usize) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let end = safe_end(base, size);
+        let mut word = base as *mut u64;
+        while (word as usize) < end {
+            unsafe {
+                word.write_volatile(PAINT);
+                word = word.add(1);
+            }
+        }
+    });
+    STACKS.lock().push(Stack {
+        name: String::from(name),
+        base,
+        size,
+    });
+}
+
+/// Tracks the stack the bootloader started the kernel on, `size` bytes
+/// ending at the page above the current stack pointer. Pages at the bottom
+/// that turn out unmapped are left out.
+pub fn track_boot_stack(size: usize) {
+    let rsp = current_rsp();
+    let top = (rsp | 0xFFF) + 1;
+    let mut base = top.saturating_sub(size);
+    while base < rsp && !page_is_mapped(VirtAddr::new(base as u64)) {
+        base += 4096;
+    }
+    track("boot", base, top - base);
+}
+
+/// Deepest use of the stack so far, in bytes.
+pub fn high_water(base: usize, size: usize) -> usize {
+    let end = safe_end(base, size);
+    let mut word = base as *const u64;
+    while (word as usize) < end && unsafe { word.read_volatile() } == PAINT {
+        word = unsafe { word.add(1) };
+    }
+    base + size - word as usize
+}
+
+/// `(name, used, size)` for every tracked stack.
+pub fn usage() -> Vec<(String, usize, usize)> {
+    let stacks = STACKS.lock();
+    stacks
+        .iter()
+        .map(|s| (s.name.clone(), high_water(s.base, s.size), s.size))
+        .collect()
+}
+
+pub fn report() -> String {
+    let usage = usage();
+    if usage.is_empty() {
+        return String::from("No stacks tracked");
+    }
+    let mut out = String::from("Stack          Used   Total   Peak\n");
+    for (name, used, size) in usage {
+        out.push_str(&format!(
+            "  {:<10} {:>5}K {:>6}K {:>5}%\n",
+            name,
+            used.div_ceil(1024),
+            size / 1024,
+            used * 100 / size.max(1)
+        ));
+    }
+    out.pop();
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn recurse(depth: usize) -> u8 {
+        let frame = core::hint::black_box([depth as u8; 512]);
+        if depth == 0 {
+            frame[0]
+        } else {
+            recurse(depth - 1).wrapping_add(frame[511])
+        }
+    }
+
+    fn boot_stack() -> (usize, usize) {
+        let stacks = STACKS.lock();
+        let boot = stacks.iter().find(|s| s.name == "boot");
+        let boot = boot.expect("boot stack tracked");
+        (boot.base, boot.size)
+    }
+
+    #[test_case]
+    fn scan_finds_the_first_clobbered_word() {
+        let mut stack = alloc::vec![0u64; 64];
+        let base = stack.as_mut_ptr() as usize;
+        track("test", base, 64 * 8);
+        assert_eq!(high_water(base, 64 * 8), 0);
+        stack[40] = 0;
+        assert_eq!(high_water(base, 64 * 8), 24 * 8);
+        STACKS.lock().retain(|s| s.name != "test");
+    }
+
+    #[test_case]
+    fn deep_recursion_raises_the_boot_stack_mark() {
+        let (base, size) = boot_stack();
+        let before = high_water(base, size);
+        let depth = base + size - current_rsp();
+        core::hint::black_box(recurse(32));
+        let after = high_water(base, size);
+        assert!(after >= before);
+        assert!(after >= depth + 32 * 512);
+        assert!(after < size);
+    }
+}
@@ -1,10 +1,14 @@
 //! # System Call Dispatcher
 //!
 //! Routes system calls to appropriate handlers based on syscall number.
+//! Calls from user code are checked against `policy` first.
 
+use crate::gfx::surface;
 use crate::memory::{brk::sys_brk, mmap::sys_mmap, munmap::sys_munmap};
+use crate::syscalls::fd::PollFd;
 use crate::syscalls::handlers;
 use crate::syscalls::numbers::SyscallNumber;
+use crate::syscalls::policy::{self, Origin};
 
 pub type SyscallResult = Result<usize, SyscallError>;
 
@@ -17,6 +21,14 @@ pub enum SyscallError {
     BadFileDescriptor,
     NoMemory,
     IoError,
+    NotFound,
+    TooManyFiles,
+    WouldBlock,
+    BrokenPipe,
+    IllegalSeek,
+    AlreadyExists,
+    BadAddress,
+    FileTooBig,
 }
 
 impl SyscallError {
@@ -29,6 +41,14 @@ impl SyscallError {
             Self::BadFileDescriptor => -9, // EBADF
             Self::NoMemory => -12,         // ENOMEM
             Self::IoError => -5,           // EIO
+            Self::NotFound => -2,          // ENOENT
+            Self::TooManyFiles => -24,     // EMFILE
+            Self::WouldBlock => -11,       // EAGAIN
+            Self::BrokenPipe => -32,       // EPIPE
+            Self::IllegalSeek => -29,      // ESPIPE
+            Self::AlreadyExists => -17,    // EEXIST
+            Self::BadAddress => -14,       // EFAULT
+            Self::FileTooBig => -27,       // EFBIG
         }
     }
 }
@@ -43,6 +63,8 @@ pub struct SyscallContext {
     pub arg3: usize,
     pub arg4: usize,
     pub arg5: usize,
+    /// Where the call came from; `from_registers` assumes the kernel.
+    pub origin: Origin,
 }
 impl SyscallContext {
     pub fn from_registers(
@@ -62,8 +84,13 @@ impl SyscallContext {
             arg3: r10,
             arg4: r8,
             arg5: r9,
+            origin: Origin::Kernel,
         }
     }
+
+    pub fn with_origin(self, origin: Origin) -> Self {
+        Self { origin, ..self }
+    }
 }
 
 pub fn dispatch_syscall(ctx: SyscallContext) -> SyscallResult {
@@ -81,6 +108,12 @@ pub fn dispatch_syscall(ctx: SyscallContext) -> SyscallResult {
         ctx.arg5
     );
 
+    let caps = match ctx.origin {
+        Origin::Kernel => policy::Caps::NONE,
+        Origin::User => handlers::process::caps(handlers::process::current_pid()),
+    };
+    policy::admit(syscall, ctx.origin, caps)?;
+
     match syscall {
         // I/O Operations
         SyscallNumber::Read => {
@@ -91,6 +124,11 @@ pub fn dispatch_syscall(ctx: SyscallContext) -> SyscallResult {
         }
         SyscallNumber::Open => handlers::io::sys_open(ctx.arg0 as *const u8, ctx.arg1, ctx.arg2),
         SyscallNumber::Close => handlers::io::sys_close(ctx.arg0 as i32),
+        SyscallNumber::Lseek => handlers::io::sys_lseek(ctx.arg0 as i32, ctx.arg1 as i64, ctx.arg2),
+        SyscallNumber::Pipe => handlers::io::sys_pipe(ctx.arg0 as *mut [i32; 2]),
+        SyscallNumber::Poll => {
+            handlers::io::sys_poll(ctx.arg0 as *mut PollFd, ctx.arg1, ctx.arg2 as isize)
+        }
 
         // Process Management
         SyscallNumber::Exit => handlers::process::sys_exit(ctx.arg0 as i32),
@@ -109,13 +147,27 @@ pub fn dispatch_syscall(ctx: SyscallContext) -> SyscallResult {
             ctx.arg3,
             ctx.arg4 as i32,
             ctx.arg5,
-        ),
-        SyscallNumber::Munmap => sys_munmap(ctx.arg0, ctx.arg1),
+        )
+        .map_err(SyscallError::from),
+        SyscallNumber::Munmap => sys_munmap(ctx.arg0, ctx.arg1).map_err(SyscallError::from),
         SyscallNumber::Brk => sys_brk(ctx.arg0 as u64),
 
         // Time
         SyscallNumber::Sleep => handlers::time::sys_sleep(ctx.arg0 as u64),
         SyscallNumber::GetTime => handlers::time::sys_gettime(),
+        SyscallNumber::Nanosleep => handlers::time::sys_nanosleep(ctx.arg0 as u64),
+
+        // Misc
+        SyscallNumber::GetRandom => {
+            handlers::random::sys_getrandom(ctx.arg0 as *mut u8, ctx.arg1, ctx.arg2)
+        }
+
+        // Graphics
+        SyscallNumber::SurfaceCreate => {
+            surface::sys_surface_create(ctx.arg0, ctx.arg1, ctx.arg2 as *mut usize)
+        }
+        SyscallNumber::SurfacePresent => surface::sys_surface_present(ctx.arg0),
+        SyscallNumber::SurfaceDestroy => surface::sys_surface_destroy(ctx.arg0),
 
         // Not yet implemented
         _ => Err(SyscallError::NotImplemented),
@@ -0,0 +1,235 @@
+//! # File Descriptor Table
+//!
+//! Open files and their offsets. There is one table for the whole kernel
+//! until processes get their own; fds 0-2 are the console and never
+//! appear here, so the table hands out numbers from 3.
+//!
+//! Flag values follow Linux so that existing user code can pass them as is.
+
+use crate::syscalls::dispatcher::SyscallError;
+use crate::syscalls::pipe::{PipeReader, PipeWriter};
+use alloc::{string::String, vec::Vec};
+use spin::Mutex;
+
+pub const O_ACCMODE: usize = 0o3;
+pub const O_RDONLY: usize = 0o0;
+pub const O_WRONLY: usize = 0o1;
+pub const O_RDWR: usize = 0o2;
+pub const O_CREAT: usize = 0o100;
+pub const O_TRUNC: usize = 0o1000;
+pub const O_APPEND: usize = 0o2000;
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+pub const POLLIN: i16 = 0x001;
+pub const POLLOUT: i16 = 0x004;
+pub const POLLERR: i16 = 0x008;
+pub const POLLHUP: i16 = 0x010;
+pub const POLLNVAL: i16 = 0x020;
+
+const FIRST_FD: i32 = 3;
+pub const MAX_OPEN: usize = 64;
+
+static FD_TABLE: Mutex<FdTable> = Mutex::new(FdTable { slots: Vec::new() });
+
+pub enum FdKind {
+    /// A ramfs file, by path.
+    File(String),
+    PipeRead(PipeReader),
+    PipeWrite(PipeWriter),
+}
+
+/// One entry of a `poll` request, laid out as in C.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PollFd {
+    pub fd: i32,
+    /// `POLL*` bits asked for.
+    pub events: i16,
+    /// `POLL*` bits that hold; `POLLHUP`, `POLLERR` and `POLLNVAL` are
+    /// reported even if not asked for.
+    pub revents: i16,
+}
+
+pub struct FileDescriptor {
+    pub kind: FdKind,
+    /// `O_*` flags given to `open`.
+    pub flags: usize,
+    /// Where the next read or write starts. May lie past the end of the
+    /// file; a write there fills the gap with zeros. Unused for pipes.
+    pub offset: usize,
+}
+
+impl FileDescriptor {
+    pub fn new(kind: FdKind, flags: usize) -> Self {
+        Self {
+            kind,
+            flags,
+            offset: 0,
+        }
+    }
+
+    pub fn readable(&self) -> bool {
+        matches!(self.flags & O_ACCMODE, O_RDONLY | O_RDWR)
+    }
+
+    pub fn writable(&self) -> bool {
+        matches!(self.flags & O_ACCMODE, O_WRONLY | O_RDWR)
+    }
+
+    /// `POLL*` bits that hold right now. Files are always ready.
+    pub fn poll_events(&self) -> i16 {
+        match &self.kind {
+            FdKind::File(_) => {
+                let mut ready = 0;
+                if self.readable() {
+                    ready |= POLLIN;
+                }
+                if self.writable() {
+                    ready |= POLLOUT;
+                }
+                ready
+            }
+            FdKind::PipeRead(reader) => {
+                let mut ready = if reader.readable() { POLLIN } else { 0 };
+                if reader.writer_closed() {
+                    ready |= POLLHUP;
+                }
+                ready
+            }
+            FdKind::PipeWrite(writer) if writer.reader_closed() => POLLERR,
+            FdKind::PipeWrite(writer) if writer.writable() => POLLOUT,
+            FdKind::PipeWrite(_) => 0,
+        }
+    }
+
+    /// Moves the offset as `lseek` does and returns the new one. `size` is
+    /// the current file size, for `SEEK_END`.
+    pub fn seek(&mut self, offset: i64, whence: usize, size: usize) -> Result<usize, SyscallError> {
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => self.offset,
+            SEEK_END => size,
+            _ => return Err(SyscallError::InvalidArgument),
+        };
+        let target = i64::try_from(base)
+            .ok()
+            .and_then(|base| base.checked_add(offset))
+            .ok_or(SyscallError::InvalidArgument)?;
+        let target = usize::try_from(target).map_err(|_| SyscallError::InvalidArgument)?;
+        self.offset = target;
+        Ok(target)
+    }
+}
+
+struct FdTable {
+    slots: Vec<Option<FileDescriptor>>,
+}
+
+impl FdTable {
+    fn free_slot(&mut self, skip: Option<usize>) -> Result<usize, SyscallError> {
+        let free = (0..self.slots.len()).find(|&i| self.slots[i].is_none() && Some(i) != skip);
+        match free {
+            Some(idx) => Ok(idx),
+            None if self.slots.len() < MAX_OPEN => {
+                self.slots.push(None);
+                Ok(self.slots.len() - 1)
+            }
+            None => Err(SyscallError::TooManyFiles),
+        }
+    }
+}
+
+/// Stores `desc` in the lowest free slot and returns its fd.
+pub fn insert(desc: FileDescriptor) -> Result<i32, SyscallError> {
+    let mut table = FD_TABLE.lock();
+    let idx = table.free_slot(None)?;
+    table.slots[idx] = Some(desc);
+    Ok(FIRST_FD + idx as i32)
+}
+
+/// Stores both descriptors or neither, for the two ends of a pipe.
+pub fn insert_pair(a: FileDescriptor, b: FileDescriptor) -> Result<[i32; 2], SyscallError> {
+    let mut table = FD_TABLE.lock();
+    let first = table.free_slot(None)?;
+    let second = table.free_slot(Some(first))?;
+    table.slots[first] = Some(a);
+    table.slots[second] = Some(b);
+    Ok([FIRST_FD + first as i32, FIRST_FD + second as i32])
+}
+
+fn slot(fd: i32) -> Result<usize, SyscallError> {
+    fd.checked_sub(FIRST_FD)
+        .and_then(|idx| usize::try_from(idx).ok())
+        .ok_or(SyscallError::BadFileDescriptor)
+}
+
+/// Runs `f` on the open descriptor `fd`.
+pub fn with_fd<R>(
+    fd: i32,
+    f: impl FnOnce(&mut FileDescriptor) -> Result<R, SyscallError>,
+) -> Result<R, SyscallError> {
+    let idx = slot(fd)?;
+    let mut table = FD_TABLE.lock();
+    match table.slots.get_mut(idx) {
+        Some(Some(desc)) => f(desc),
+        _ => Err(SyscallError::BadFileDescriptor),
+    }
+}
+
+pub fn remove(fd: i32) -> Result<FileDescriptor, SyscallError> {
+    let idx = slot(fd)?;
+    FD_TABLE
+        .lock()
+        .slots
+        .get_mut(idx)
+        .and_then(Option::take)
+        .ok_or(SyscallError::BadFileDescriptor)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc_at(offset: usize) -> FileDescriptor {
+        let mut desc = FileDescriptor::new(FdKind::File(String::from("/t")), O_RDWR);
+        desc.offset = offset;
+        desc
+    }
+
+    #[test_case]
+    fn seek_set_cur_and_end() {
+        let mut desc = desc_at(10);
+        assert_eq!(desc.seek(4, SEEK_SET, 100), Ok(4));
+        assert_eq!(desc.seek(6, SEEK_CUR, 100), Ok(10));
+        assert_eq!(desc.seek(-3, SEEK_CUR, 100), Ok(7));
+        assert_eq!(desc.seek(0, SEEK_END, 100), Ok(100));
+        assert_eq!(desc.seek(-100, SEEK_END, 100), Ok(0));
+        // past the end is allowed; reads there return 0
+        assert_eq!(desc.seek(5, SEEK_END, 100), Ok(105));
+        assert_eq!(desc.offset, 105);
+    }
+
+    #[test_case]
+    fn seek_rejects_negative_results_and_bad_whence() {
+        let mut desc = desc_at(10);
+        assert_eq!(
+            desc.seek(-1, SEEK_SET, 100),
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(
+            desc.seek(-11, SEEK_CUR, 100),
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(
+            desc.seek(-101, SEEK_END, 100),
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(desc.seek(0, 3, 100), Err(SyscallError::InvalidArgument));
+        assert_eq!(desc.offset, 10);
+    }
+}
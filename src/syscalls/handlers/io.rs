@@ -6,8 +6,11 @@
 //!
 //! - `sys_read`: Read from file descriptor
 //! - `sys_write`: Write to file descriptor
-//! - `sys_open`: Open file (not implemented)
-//! - `sys_close`: Close file descriptor (not implemented)
+//! - `sys_open`: Open a ramfs file
+//! - `sys_close`: Close file descriptor
+//! - `sys_lseek`: Move a file descriptor's offset
+//! - `sys_pipe`: Create a pipe (see `syscalls::pipe`)
+//! - `sys_poll`: Check which fds are ready, without waiting
 //!
 //! ## File Descriptors
 //!
@@ -16,15 +19,50 @@
 //! | 0  | stdin  | Keyboard buffer |
 //! | 1  | stdout | Serial/terminal |
 //! | 2  | stderr | Serial/terminal |
+//! | 3+ | files, pipes | `syscalls::fd` |
 //!
 //! ## Note
 //!
-//! Currently only stdout/stderr write is fully implemented.
+//! stdin is not implemented yet. Files opened with `O_APPEND` send
+//! every write to the current end of the file, whatever the offset. A
+//! write that would take a file past `FILE_SIZE_MAX` fails with `EFBIG`,
+//! and one the heap cannot grow the file for with `ENOMEM`, leaving the
+//! file as it was.
 
+use crate::fs::ramfs;
 use crate::syscalls::dispatcher::{SyscallError, SyscallResult};
+use crate::syscalls::fd::{
+    self, FdKind, FileDescriptor, PollFd, O_APPEND, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY, POLLERR,
+    POLLHUP, POLLIN, POLLNVAL, POLLOUT,
+};
+use crate::syscalls::pipe;
+use crate::syscalls::user::{self, copy_from_user, copy_to_user, read_user, write_user};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Longest path `sys_open` accepts, terminator included.
+const PATH_MAX: usize = 256;
+/// Largest a file may grow through `sys_write`.
+pub const FILE_SIZE_MAX: usize = 16 * 1024 * 1024;
+
+fn fs_error(_: ramfs::FsError) -> SyscallError {
+    SyscallError::NotFound
+}
+
+/// The first `len` bytes of the caller's buffer, copied in.
+fn user_bytes(buf: *const u8, len: usize) -> Result<Vec<u8>, SyscallError> {
+    let mut bytes = Vec::new();
+    bytes
+        .try_reserve_exact(len)
+        .map_err(|_| SyscallError::NoMemory)?;
+    bytes.resize(len, 0);
+    copy_from_user(&mut bytes, buf)?;
+    Ok(bytes)
+}
 
 /// Read from file descriptor
-pub fn sys_read(fd: i32, buf: *mut u8, _count: usize) -> SyscallResult {
+pub fn sys_read(fd: i32, buf: *mut u8, count: usize) -> SyscallResult {
     // Validate arguments
     if buf.is_null() {
         return Err(SyscallError::InvalidArgument);
@@ -36,7 +74,37 @@ pub fn sys_read(fd: i32, buf: *mut u8, _count: usize) -> SyscallResult {
             // TODO: Implement keyboard buffer reading
             Err(SyscallError::NotImplemented)
         }
-        _ => Err(SyscallError::BadFileDescriptor),
+        1 | 2 => Err(SyscallError::BadFileDescriptor),
+        _ => fd::with_fd(fd, |desc| {
+            if !desc.readable() {
+                return Err(SyscallError::BadFileDescriptor);
+            }
+            // Checked first so a bad buffer fails before a pipe is drained.
+            user::check_range(buf as usize, count)?;
+            match &desc.kind {
+                FdKind::File(path) => {
+                    let offset = desc.offset;
+                    // Reads stop at the end of the file, even if the offset
+                    // was seeked past it.
+                    let n = ramfs::with_file(path, |data| -> SyscallResult {
+                        let start = offset.min(data.len());
+                        let n = count.min(data.len() - start);
+                        copy_to_user(buf, &data[start..start + n])?;
+                        Ok(n)
+                    })
+                    .map_err(fs_error)??;
+                    desc.offset += n;
+                    Ok(n)
+                }
+                FdKind::PipeRead(reader) => {
+                    let mut chunk = vec![0u8; count.min(pipe::PIPE_CAPACITY)];
+                    let n = reader.read(&mut chunk)?;
+                    copy_to_user(buf, &chunk[..n])?;
+                    Ok(n)
+                }
+                FdKind::PipeWrite(_) => Err(SyscallError::BadFileDescriptor),
+            }
+        }),
     }
 }
 
@@ -50,30 +118,89 @@ pub fn sys_write(fd: i32, buf: *const u8, count: usize) -> SyscallResult {
     match fd {
         1 | 2 => {
             // stdout/stderr - write to serial/terminal
-            unsafe {
-                let slice = core::slice::from_raw_parts(buf, count);
-                if let Ok(s) = core::str::from_utf8(slice) {
-                    // Write to terminal
-                    use core::fmt::Write;
-                    let _ = write!(crate::SERIAL, "{}", s);
+            let bytes = user_bytes(buf, count)?;
+            if let Ok(s) = core::str::from_utf8(&bytes) {
+                // Write to terminal
+                use core::fmt::Write;
+                let _ = unsafe { write!(crate::SERIAL, "{}", s) };
+                Ok(count)
+            } else {
+                Err(SyscallError::InvalidArgument)
+            }
+        }
+        0 => Err(SyscallError::BadFileDescriptor),
+        _ => fd::with_fd(fd, |desc| {
+            if !desc.writable() {
+                return Err(SyscallError::BadFileDescriptor);
+            }
+            match &desc.kind {
+                FdKind::File(path) => {
+                    // Nothing this long fits under the cap from any offset.
+                    if count > FILE_SIZE_MAX {
+                        return Err(SyscallError::FileTooBig);
+                    }
+                    let src = user_bytes(buf, count)?;
+                    let append = desc.flags & O_APPEND != 0;
+                    let offset = desc.offset;
+                    let end = ramfs::with_file(path, |data| -> SyscallResult {
+                        let start = if append { data.len() } else { offset };
+                        let end = start
+                            .checked_add(src.len())
+                            .filter(|&end| end <= FILE_SIZE_MAX)
+                            .ok_or(SyscallError::FileTooBig)?;
+                        if data.len() < end {
+                            data.try_reserve(end - data.len())
+                                .map_err(|_| SyscallError::NoMemory)?;
+                            data.resize(end, 0);
+                        }
+                        data[start..end].copy_from_slice(&src);
+                        Ok(end)
+                    })
+                    .map_err(fs_error)??;
+                    desc.offset = end;
                     Ok(count)
-                } else {
-                    Err(SyscallError::InvalidArgument)
                 }
+                // May be short when the pipe is nearly full.
+                FdKind::PipeWrite(writer) => {
+                    writer.write(&user_bytes(buf, count.min(pipe::PIPE_CAPACITY))?)
+                }
+                FdKind::PipeRead(_) => Err(SyscallError::BadFileDescriptor),
             }
+        }),
+    }
+}
+
+/// NUL-terminated path from the caller, at most `PATH_MAX` bytes.
+fn user_path(path: *const u8) -> Result<String, SyscallError> {
+    let mut bytes = Vec::new();
+    loop {
+        match read_user(path.wrapping_add(bytes.len()))? {
+            0 => break,
+            _ if bytes.len() + 1 >= PATH_MAX => return Err(SyscallError::InvalidArgument),
+            byte => bytes.push(byte),
         }
-        _ => Err(SyscallError::BadFileDescriptor),
     }
+    String::from_utf8(bytes).map_err(|_| SyscallError::InvalidArgument)
 }
 
 /// Open a file
-pub fn sys_open(path: *const u8, _flags: usize, _mode: usize) -> SyscallResult {
+pub fn sys_open(path: *const u8, flags: usize, _mode: usize) -> SyscallResult {
     if path.is_null() {
         return Err(SyscallError::InvalidArgument);
     }
+    let path = &user_path(path)?;
+
+    if flags & O_CREAT != 0 {
+        ramfs::create(path).map_err(|_| SyscallError::InvalidArgument)?;
+    } else if !ramfs::exists(path) {
+        return Err(SyscallError::NotFound);
+    }
 
-    // TODO: Implement file system
-    Err(SyscallError::NotImplemented)
+    let desc = FileDescriptor::new(FdKind::File(path.into()), flags);
+    if flags & O_TRUNC != 0 && desc.writable() {
+        ramfs::with_file(path, |data| data.clear()).map_err(fs_error)?;
+    }
+    fd::insert(desc).map(|fd| fd as usize)
 }
 
 /// Close a file descriptor
@@ -82,6 +209,230 @@ pub fn sys_close(fd: i32) -> SyscallResult {
         return Err(SyscallError::BadFileDescriptor);
     }
 
-    // TODO: Implement file descriptor table
-    Err(SyscallError::NotImplemented)
+    fd::remove(fd).map(|_| 0)
+}
+
+/// Move a file descriptor's offset (`SEEK_SET`, `SEEK_CUR`, `SEEK_END`)
+pub fn sys_lseek(fd: i32, offset: i64, whence: usize) -> SyscallResult {
+    fd::with_fd(fd, |desc| {
+        let FdKind::File(path) = &desc.kind else {
+            return Err(SyscallError::IllegalSeek);
+        };
+        let size = ramfs::size(path).map_err(fs_error)?;
+        desc.seek(offset, whence, size)
+    })
+}
+
+/// Create a pipe; `fds[0]` is the read end, `fds[1]` the write end
+pub fn sys_pipe(fds: *mut [i32; 2]) -> SyscallResult {
+    if fds.is_null() {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let (reader, writer) = pipe::pipe();
+    let pair = fd::insert_pair(
+        FileDescriptor::new(FdKind::PipeRead(reader), O_RDONLY),
+        FileDescriptor::new(FdKind::PipeWrite(writer), O_WRONLY),
+    )?;
+    if let Err(err) = write_user(fds, pair) {
+        let _ = fd::remove(pair[0]);
+        let _ = fd::remove(pair[1]);
+        return Err(err);
+    }
+    Ok(0)
+}
+
+/// Sets `revents` on each of the `nfds` entries and returns how many have
+/// any. Only `timeout_ms == 0` is supported: nothing blocks yet.
+pub fn sys_poll(fds: *mut PollFd, nfds: usize, timeout_ms: isize) -> SyscallResult {
+    if timeout_ms != 0 || (fds.is_null() && nfds > 0) || nfds > fd::MAX_OPEN + 3 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if nfds == 0 {
+        return Ok(0);
+    }
+
+    user::check_range(fds as usize, nfds * core::mem::size_of::<PollFd>())?;
+    let mut ready = 0;
+    for i in 0..nfds {
+        let slot = fds.wrapping_add(i);
+        let mut entry = read_user(slot)?;
+        let events = match entry.fd {
+            // Negative fds are skipped, so callers can blank out entries.
+            fd if fd < 0 => 0,
+            0 if crate::devices::drivers::ps2_keyboard::has_pending() => POLLIN,
+            0 => 0,
+            1 | 2 => POLLOUT,
+            fd => fd::with_fd(fd, |desc| Ok(desc.poll_events())).unwrap_or(POLLNVAL),
+        };
+        entry.revents = events & (entry.events | POLLHUP | POLLERR | POLLNVAL);
+        write_user(slot, entry)?;
+        if entry.revents != 0 {
+            ready += 1;
+        }
+    }
+    Ok(ready)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscalls::fd::{O_RDWR, O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET};
+
+    #[test_case]
+    fn append_writes_go_to_the_end_and_reads_stop_there() {
+        let path = b"/tmp/io_append\0";
+        let fd = sys_open(path.as_ptr(), O_RDWR | O_CREAT | O_TRUNC, 0).unwrap() as i32;
+        sys_write(fd, b"hello".as_ptr(), 5).unwrap();
+        sys_close(fd).unwrap();
+
+        let fd = sys_open(path.as_ptr(), O_WRONLY | O_APPEND, 0).unwrap() as i32;
+        assert_eq!(sys_lseek(fd, 0, SEEK_SET), Ok(0));
+        sys_write(fd, b" world".as_ptr(), 6).unwrap();
+        assert_eq!(sys_lseek(fd, 0, SEEK_CUR), Ok(11));
+        sys_close(fd).unwrap();
+        let contents = ramfs::with_file("/tmp/io_append", |data| data.clone()).unwrap();
+        assert_eq!(contents, b"hello world");
+
+        let fd = sys_open(path.as_ptr(), O_RDWR, 0).unwrap() as i32;
+        let mut buf = [0u8; 16];
+        assert_eq!(sys_lseek(fd, -5, SEEK_END), Ok(6));
+        assert_eq!(sys_read(fd, buf.as_mut_ptr(), buf.len()), Ok(5));
+        assert_eq!(&buf[..5], b"world");
+        assert_eq!(sys_lseek(fd, 100, SEEK_SET), Ok(100));
+        assert_eq!(sys_read(fd, buf.as_mut_ptr(), buf.len()), Ok(0));
+        assert_eq!(
+            sys_lseek(fd, -1, SEEK_SET),
+            Err(SyscallError::InvalidArgument)
+        );
+        sys_close(fd).unwrap();
+        assert_eq!(sys_close(fd), Err(SyscallError::BadFileDescriptor));
+    }
+
+    #[test_case]
+    fn writes_past_the_size_cap_fail_and_change_nothing() {
+        let path = b"/tmp/io_cap\0";
+        let fd = sys_open(path.as_ptr(), O_RDWR | O_CREAT | O_TRUNC, 0).unwrap() as i32;
+        sys_write(fd, b"abc".as_ptr(), 3).unwrap();
+        assert_eq!(
+            sys_lseek(fd, FILE_SIZE_MAX as i64 - 1, SEEK_SET),
+            Ok(FILE_SIZE_MAX - 1)
+        );
+        assert_eq!(
+            sys_write(fd, b"xy".as_ptr(), 2),
+            Err(SyscallError::FileTooBig)
+        );
+        assert!(sys_lseek(fd, i64::MAX, SEEK_SET).is_ok());
+        assert_eq!(
+            sys_write(fd, b"x".as_ptr(), 1),
+            Err(SyscallError::FileTooBig)
+        );
+        sys_close(fd).unwrap();
+        assert_eq!(
+            ramfs::with_file("/tmp/io_cap", |data| data.len()),
+            Ok(3)
+        );
+    }
+
+    #[test_case]
+    fn pipe_fills_drains_and_reports_closed_ends() {
+        let mut fds = [0i32; 2];
+        sys_pipe(&mut fds).unwrap();
+        let [rd, wr] = fds;
+        let mut buf = [0u8; 64];
+        assert_eq!(
+            sys_read(rd, buf.as_mut_ptr(), buf.len()),
+            Err(SyscallError::WouldBlock)
+        );
+        assert_eq!(
+            sys_read(wr, buf.as_mut_ptr(), 1),
+            Err(SyscallError::BadFileDescriptor)
+        );
+        assert_eq!(sys_lseek(rd, 0, SEEK_SET), Err(SyscallError::IllegalSeek));
+
+        let big = [7u8; pipe::PIPE_CAPACITY + 10];
+        assert_eq!(
+            sys_write(wr, big.as_ptr(), big.len()),
+            Ok(pipe::PIPE_CAPACITY)
+        );
+        assert_eq!(
+            sys_write(wr, big.as_ptr(), 1),
+            Err(SyscallError::WouldBlock)
+        );
+        assert_eq!(sys_read(rd, buf.as_mut_ptr(), buf.len()), Ok(64));
+        assert_eq!(sys_write(wr, b"end".as_ptr(), 3), Ok(3));
+        // A buffer outside the user half is refused without draining.
+        assert_eq!(
+            sys_read(rd, user::USER_END as *mut u8, 4),
+            Err(SyscallError::InvalidArgument)
+        );
+
+        // Closing the write end leaves the data readable, then EOF.
+        sys_close(wr).unwrap();
+        let mut rest = 0;
+        loop {
+            match sys_read(rd, buf.as_mut_ptr(), buf.len()) {
+                Ok(0) => break,
+                Ok(n) => rest += n,
+                Err(e) => panic!("read failed: {:?}", e),
+            }
+        }
+        assert_eq!(rest, pipe::PIPE_CAPACITY - 64 + 3);
+        sys_close(rd).unwrap();
+
+        let mut fds = [0i32; 2];
+        sys_pipe(&mut fds).unwrap();
+        sys_close(fds[0]).unwrap();
+        assert_eq!(
+            sys_write(fds[1], b"x".as_ptr(), 1),
+            Err(SyscallError::BrokenPipe)
+        );
+        sys_close(fds[1]).unwrap();
+    }
+
+    #[test_case]
+    fn poll_reports_pipe_readiness() {
+        let mut fds = [0i32; 2];
+        sys_pipe(&mut fds).unwrap();
+        let [rd, wr] = fds;
+        let mut polls = [
+            PollFd {
+                fd: rd,
+                events: POLLIN,
+                revents: 0,
+            },
+            PollFd {
+                fd: wr,
+                events: POLLOUT,
+                revents: 0,
+            },
+            PollFd {
+                fd: 999,
+                events: POLLIN,
+                revents: 0,
+            },
+        ];
+
+        assert_eq!(sys_poll(polls.as_mut_ptr(), 3, 0), Ok(2));
+        assert_eq!(polls[0].revents, 0);
+        assert_eq!(polls[1].revents, POLLOUT);
+        assert_eq!(polls[2].revents, POLLNVAL);
+
+        sys_write(wr, b"x".as_ptr(), 1).unwrap();
+        assert_eq!(sys_poll(polls.as_mut_ptr(), 2, 0), Ok(2));
+        assert_eq!(polls[0].revents, POLLIN);
+
+        sys_close(wr).unwrap();
+        let mut buf = [0u8; 4];
+        sys_read(rd, buf.as_mut_ptr(), buf.len()).unwrap();
+        assert_eq!(sys_poll(polls.as_mut_ptr(), 1, 0), Ok(1));
+        assert_eq!(polls[0].revents, POLLIN | POLLHUP);
+        assert_eq!(
+            sys_poll(polls.as_mut_ptr(), 1, 10),
+            Err(SyscallError::InvalidArgument)
+        );
+        sys_close(rd).unwrap();
+    }
 }
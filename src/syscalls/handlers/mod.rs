@@ -6,8 +6,9 @@
 //!
 //! - `io`: File I/O operations (read, write, open, close)
 //! - `process`: Process management (exit, fork, exec, getpid)
-//! - `time`: Time operations (sleep, gettime)
+//! - `time`: Time operations (nanosleep, sleep, gettime)
 //! - `memory`: Memory management (mmap, munmap, brk)
+//! - `random`: Random bytes (getrandom)
 //!
 //! ## Handler Signature
 //!
@@ -19,4 +20,5 @@
 pub mod io;
 pub mod process;
 pub mod time;
-pub mod memory;
\ No newline at end of file
+pub mod memory;
+pub mod random;
\ No newline at end of file
@@ -16,6 +16,15 @@
 //! - Maximum 256 processes
 //! - Protected by spinlock
 //! - Each entry stores PID, parent PID, exit status
+//! - `sys_exit` removes the entry and destroys its address space
+//!
+//! Beside it, a map from pid to capability bitmap (see
+//! `syscalls::policy`), granted at `sys_pstart`, inherited at fork and
+//! cleared at exit. It is not bounded by the table, since `sys_pstart`
+//! processes have no table entry.
+//!
+//! `release` frees what an ended process held; `sys_exit` calls it, and
+//! so does whatever runs a `sys_pstart` process once it returns.
 //!
 //! ## PID Allocation
 //!
@@ -23,7 +32,10 @@
 //! PID 0 indicates no process (kernel context).
 
 use crate::syscalls::dispatcher::{SyscallError, SyscallResult};
+use crate::syscalls::policy::Caps;
+use alloc::collections::BTreeMap;
 use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::{structures::paging::PhysFrame, PhysAddr};
 
 static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
 static CURRENT_PID: AtomicUsize = AtomicUsize::new(0);
@@ -32,6 +44,20 @@ pub fn get_next_pid() -> usize {
     NEXT_PID.fetch_add(1, Ordering::SeqCst)
 }
 
+/// The process syscalls are being made for; 0 in kernel context.
+pub fn current_pid() -> usize {
+    CURRENT_PID.load(Ordering::Relaxed)
+}
+
+/// Runs `f` as process `pid`, for a kernel task that drives a process's
+/// code and makes its syscalls for it.
+pub fn with_current_pid<R>(pid: usize, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT_PID.swap(pid, Ordering::Relaxed);
+    let result = f();
+    CURRENT_PID.store(previous, Ordering::Relaxed);
+    result
+}
+
 #[derive(Debug, Clone, Copy)]
 struct ProcessContext {
     pid: usize,
@@ -42,11 +68,53 @@ struct ProcessContext {
 
 static mut PROCESS_TABLE: [Option<ProcessContext>; 256] = [None; 256];
 static PROCESS_TABLE_LOCK: spin::Mutex<()> = spin::Mutex::new(());
+/// Pids holding any capability.
+static PROCESS_CAPS: spin::Mutex<BTreeMap<usize, Caps>> = spin::Mutex::new(BTreeMap::new());
+
+/// The capabilities of process `pid`.
+pub fn caps(pid: usize) -> Caps {
+    PROCESS_CAPS.lock().get(&pid).copied().unwrap_or(Caps::NONE)
+}
+
+/// Replaces the capabilities of process `pid`.
+pub fn set_caps(pid: usize, caps: Caps) {
+    let mut table = PROCESS_CAPS.lock();
+    if caps == Caps::NONE {
+        table.remove(&pid);
+    } else {
+        table.insert(pid, caps);
+    }
+}
+
+/// Frees what process `pid` held once it has ended: its surfaces, its
+/// `sys_pstart` code and code slot, and its capabilities.
+pub fn release(pid: usize) {
+    crate::gfx::surface::release_process(pid);
+    crate::memory::release_process_code(pid);
+    set_caps(pid, Caps::NONE);
+}
 
 pub fn sys_exit(status: i32) -> SyscallResult {
     let pid = CURRENT_PID.load(Ordering::Relaxed);
     crate::println!("Process {} exiting with status: {}", pid, status);
 
+    // Release the address space recorded at fork; the active one is refused.
+    let exiting = {
+        let _guard = PROCESS_TABLE_LOCK.lock();
+        if pid < 256 {
+            unsafe { PROCESS_TABLE[pid].take() }
+        } else {
+            None
+        }
+    };
+    release(pid);
+    if let Some(ctx) = exiting {
+        let p4 = PhysFrame::containing_address(PhysAddr::new(ctx.page_table));
+        if let Err(e) = crate::memory::address_space::destroy_address_space(p4) {
+            crate::println!("Process {}: address space not freed: {}", pid, e);
+        }
+    }
+
     loop {
         ::core::hint::spin_loop();
     }
@@ -83,6 +151,7 @@ pub fn sys_fork() -> SyscallResult {
             page_table: child_page_table,
         });
     }
+    set_caps(child_pid, caps(parent_pid));
 
     Ok(child_pid)
 }
@@ -105,10 +174,10 @@ pub fn sys_exec(path: *const u8, _argv: *const *const u8) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
 
-    match unsafe { crate::memory::sys_pstart(code_ptr, code_size) } {
-        Ok(pid) => {
-            CURRENT_PID.store(pid, Ordering::Relaxed);
-            Ok(pid)
+    match unsafe { crate::memory::sys_pstart(code_ptr, code_size, &[]) } {
+        Ok(code) => {
+            CURRENT_PID.store(code.pid, Ordering::Relaxed);
+            Ok(code.pid)
         }
         Err(_) => Err(SyscallError::NoMemory),
     }
@@ -0,0 +1,69 @@
+//! # Random System Call Handler
+//!
+//! `sys_getrandom` fills a user buffer from the kernel RNG
+//! (`util::rand`). The flag values follow Linux. The RNG never runs out
+//! and nothing blocks yet, so `GRND_NONBLOCK` and `GRND_RANDOM` are
+//! accepted and change nothing.
+
+use crate::syscalls::dispatcher::{SyscallError, SyscallResult};
+use crate::syscalls::user::copy_to_user;
+use crate::util::rand;
+
+pub const GRND_NONBLOCK: usize = 0x1;
+pub const GRND_RANDOM: usize = 0x2;
+
+/// Bytes generated per copy to the caller.
+const CHUNK: usize = 256;
+
+/// Fill `buf` with `len` random bytes and return `len`.
+pub fn sys_getrandom(buf: *mut u8, len: usize, flags: usize) -> SyscallResult {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let mut chunk = [0u8; CHUNK];
+    let mut done = 0;
+    while done < len {
+        let n = CHUNK.min(len - done);
+        rand::fill_bytes(&mut chunk[..n]);
+        copy_to_user(buf.wrapping_add(done), &chunk[..n])?;
+        done += n;
+    }
+    Ok(len)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn consecutive_calls_differ() {
+        let (mut a, mut b) = ([0u8; 300], [0u8; 300]);
+        assert_eq!(sys_getrandom(a.as_mut_ptr(), a.len(), 0), Ok(300));
+        assert_eq!(
+            sys_getrandom(b.as_mut_ptr(), b.len(), GRND_NONBLOCK),
+            Ok(300)
+        );
+        assert_ne!(a, b);
+        assert!(a[256..].iter().any(|&byte| byte != 0));
+    }
+
+    #[test_case]
+    fn zero_length_and_bad_arguments() {
+        let mut buf = [0u8; 4];
+        assert_eq!(sys_getrandom(buf.as_mut_ptr(), 0, 0), Ok(0));
+        assert_eq!(
+            sys_getrandom(buf.as_mut_ptr(), buf.len(), 0x4),
+            Err(SyscallError::InvalidArgument)
+        );
+        assert_eq!(
+            sys_getrandom(core::ptr::null_mut(), 4, 0),
+            Err(SyscallError::InvalidArgument)
+        );
+    }
+}
@@ -4,37 +4,64 @@
 //!
 //! ## Supported Operations
 //!
+//! - `sys_nanosleep`: Sleep for specified nanoseconds
 //! - `sys_sleep`: Sleep for specified milliseconds
 //! - `sys_gettime`: Get time since boot in milliseconds
 //!
 //! ## Timer Resolution
 //!
 //! The system uses the PIT (Programmable Interval Timer) at ~18.2 Hz,
-//! giving approximately 55ms resolution per tick.
+//! giving approximately 55ms resolution per tick. Sleeps do not use it:
+//! they wait on the HPET counter, or on the TSC without an HPET, so even
+//! sub-tick sleeps are accurate.
 //!
 //! ## Implementation Notes
 //!
-//! `sys_sleep` currently uses busy-waiting, which is not ideal for
-//! power efficiency but simple to implement. A proper implementation
-//! would yield the CPU and use timer interrupts.
+//! Sleeping busy-waits, which is not ideal for power efficiency but
+//! simple to implement. A proper implementation would yield the CPU and
+//! use timer interrupts.
 
+use crate::devices::drivers::hpet;
 use crate::kcore::interrupts::interrupts::TIMER_TICKS;
 use crate::syscalls::dispatcher::SyscallResult;
-/// Sleep for specified milliseconds
-pub fn sys_sleep(milliseconds: u64) -> SyscallResult {
-    // busy wait (not ideal!)
-    let target_ticks =
-        TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed) + (milliseconds * 18 / 1000); // 18.2 Hz timer
 
-    while TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed) < target_ticks {
-        core::hint::spin_loop();
+/// Sleep for `nanos` nanoseconds. Returns the time left unslept, which is
+/// always 0 until something can interrupt a sleep.
+pub fn sys_nanosleep(nanos: u64) -> SyscallResult {
+    if nanos > 0 {
+        hpet::busy_wait_ns(nanos);
     }
-
     Ok(0)
 }
 
+/// Sleep for specified milliseconds
+pub fn sys_sleep(milliseconds: u64) -> SyscallResult {
+    sys_nanosleep(milliseconds.saturating_mul(1_000_000))
+}
+
 /// Get current time in milliseconds since boot
 pub fn sys_gettime() -> SyscallResult {
     let ticks = TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed);
     Ok((ticks * 55) as usize)
 }
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::latency::{rdtsc, tsc_per_us};
+
+    /// The clock sleeps wait on.
+    fn now_ns() -> u64 {
+        hpet::hpet_nanos().unwrap_or_else(|| rdtsc() * 1_000 / tsc_per_us())
+    }
+
+    #[test_case]
+    fn one_millisecond_sleep_takes_at_least_that() {
+        let start = now_ns();
+        assert_eq!(sys_nanosleep(1_000_000), Ok(0));
+        assert!(now_ns() - start >= 1_000_000);
+        assert_eq!(sys_nanosleep(0), Ok(0));
+    }
+}
@@ -2,6 +2,10 @@
 
 pub mod numbers;
 pub mod dispatcher;
+pub mod policy;
+pub mod fd;
+pub mod pipe;
+pub mod user;
 pub mod handlers;
 
 pub use dispatcher::SyscallError;
@@ -7,6 +7,9 @@ pub enum SyscallNumber {
     Write = 1,
     Open = 2,
     Close = 3,
+    Lseek = 4,
+    Pipe = 5,
+    Poll = 6,
 
     // Process Management (20-39)
     Exit = 20,
@@ -23,6 +26,7 @@ pub enum SyscallNumber {
     // Time (60-79)
     Sleep = 60,
     GetTime = 61,
+    Nanosleep = 62,
 
     // Signals (80-99)
     Kill = 80,
@@ -32,6 +36,14 @@ pub enum SyscallNumber {
     Chdir = 100,
     Mkdir = 101,
 
+    // Misc (120-139)
+    GetRandom = 120,
+
+    // Graphics (140-159)
+    SurfaceCreate = 140,
+    SurfacePresent = 141,
+    SurfaceDestroy = 142,
+
     // Unknown
     Unknown = usize::MAX,
 }
@@ -42,6 +54,9 @@ impl From<usize> for SyscallNumber {
             1 => Self::Write,
             2 => Self::Open,
             3 => Self::Close,
+            4 => Self::Lseek,
+            5 => Self::Pipe,
+            6 => Self::Poll,
             20 => Self::Exit,
             21 => Self::Fork,
             22 => Self::Exec,
@@ -52,10 +67,15 @@ impl From<usize> for SyscallNumber {
             42 => Self::Brk,
             60 => Self::Sleep,
             61 => Self::GetTime,
+            62 => Self::Nanosleep,
             80 => Self::Kill,
             81 => Self::Signal,
             100 => Self::Chdir,
             101 => Self::Mkdir,
+            120 => Self::GetRandom,
+            140 => Self::SurfaceCreate,
+            141 => Self::SurfacePresent,
+            142 => Self::SurfaceDestroy,
             _ => Self::Unknown,
         }
     }
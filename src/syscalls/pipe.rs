@@ -0,0 +1,101 @@
+//! # Pipes
+//!
+//! A pipe is a bounded byte queue shared by a read end and a write end,
+//! each held by one fd. Nothing blocks yet: reading an empty pipe or
+//! writing a full one fails with `WouldBlock` (EAGAIN) and the caller
+//! retries. Once the write end is closed, an empty pipe reads as EOF;
+//! once the read end is closed, writes fail with `BrokenPipe`.
+
+use crate::syscalls::dispatcher::SyscallError;
+use alloc::{collections::VecDeque, sync::Arc};
+use spin::Mutex;
+
+pub const PIPE_CAPACITY: usize = 4096;
+
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    reader_open: bool,
+    writer_open: bool,
+}
+
+type Shared = Arc<Mutex<PipeBuffer>>;
+
+pub struct PipeReader(Shared);
+pub struct PipeWriter(Shared);
+
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let shared = Arc::new(Mutex::new(PipeBuffer {
+        data: VecDeque::with_capacity(PIPE_CAPACITY),
+        reader_open: true,
+        writer_open: true,
+    }));
+    (PipeReader(shared.clone()), PipeWriter(shared))
+}
+
+impl PipeReader {
+    /// Moves up to `buf.len()` bytes out of the pipe. `Ok(0)` is EOF.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, SyscallError> {
+        let mut pipe = self.0.lock();
+        if pipe.data.is_empty() {
+            return if pipe.writer_open && !buf.is_empty() {
+                Err(SyscallError::WouldBlock)
+            } else {
+                Ok(0)
+            };
+        }
+        let n = buf.len().min(pipe.data.len());
+        for (dst, src) in buf.iter_mut().zip(pipe.data.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+
+    /// A read would not fail with `WouldBlock`: data or EOF is waiting.
+    pub fn readable(&self) -> bool {
+        let pipe = self.0.lock();
+        !pipe.data.is_empty() || !pipe.writer_open
+    }
+
+    pub fn writer_closed(&self) -> bool {
+        !self.0.lock().writer_open
+    }
+}
+
+impl PipeWriter {
+    /// Queues as much of `buf` as fits and returns how much that was.
+    pub fn write(&self, buf: &[u8]) -> Result<usize, SyscallError> {
+        let mut pipe = self.0.lock();
+        if !pipe.reader_open {
+            return Err(SyscallError::BrokenPipe);
+        }
+        let n = buf.len().min(PIPE_CAPACITY - pipe.data.len());
+        if n == 0 && !buf.is_empty() {
+            return Err(SyscallError::WouldBlock);
+        }
+        pipe.data.extend(&buf[..n]);
+        Ok(n)
+    }
+
+    /// Room for at least one byte.
+    pub fn writable(&self) -> bool {
+        self.0.lock().data.len() < PIPE_CAPACITY
+    }
+
+    pub fn reader_closed(&self) -> bool {
+        !self.0.lock().reader_open
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        let mut pipe = self.0.lock();
+        pipe.reader_open = false;
+        pipe.data.clear();
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.0.lock().writer_open = false;
+    }
+}
@@ -0,0 +1,234 @@
+//! # Syscall Policy
+//!
+//! Which syscalls a caller may make, by where the call came from. The
+//! trampoline records the origin: a call from CPL 3 is `Origin::User`,
+//! anything else (the shell, kernel tasks driving a process) is
+//! `Origin::Kernel`. Kernel calls are never checked.
+//!
+//! User calls go through `policy`, one entry per `SyscallNumber`:
+//!
+//! - `Allowed`: any process may make it
+//! - `KernelOnly`: refused with `PermissionDenied`, the handler never runs
+//! - `Gated(cap)`: allowed if the calling process holds `cap`
+//!
+//! Capabilities are a bitmap per process, kept by pid beside the process
+//! table (`handlers::process::caps`). `sys_pstart` grants `Caps::PSTART`; a
+//! forked child inherits its parent's.
+
+use crate::syscalls::dispatcher::SyscallError;
+use crate::syscalls::numbers::SyscallNumber;
+use core::fmt;
+use x86_64::PrivilegeLevel;
+
+/// Where a syscall came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    Kernel,
+    User,
+}
+
+impl Origin {
+    /// The origin of a call made at privilege level `cpl`.
+    pub fn from_cpl(cpl: PrivilegeLevel) -> Self {
+        match cpl {
+            PrivilegeLevel::Ring3 => Origin::User,
+            _ => Origin::Kernel,
+        }
+    }
+}
+
+/// Something a process may be allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cap {
+    /// Create, present and destroy client surfaces.
+    Surface,
+    /// Create processes and wait for them.
+    Spawn,
+}
+
+impl Cap {
+    pub const ALL: [Cap; 2] = [Cap::Surface, Cap::Spawn];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cap::Surface => "surface",
+            Cap::Spawn => "spawn",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Cap> {
+        Self::ALL.into_iter().find(|cap| cap.name() == name)
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// A set of capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Caps(u32);
+
+impl Caps {
+    pub const NONE: Caps = Caps(0);
+    /// What `sys_pstart` grants a new process.
+    pub const PSTART: Caps = Caps(1 << Cap::Surface as u32);
+
+    pub fn has(self, cap: Cap) -> bool {
+        self.0 & cap.bit() != 0
+    }
+
+    pub fn with(self, cap: Cap) -> Self {
+        Caps(self.0 | cap.bit())
+    }
+
+    pub fn without(self, cap: Cap) -> Self {
+        Caps(self.0 & !cap.bit())
+    }
+}
+
+impl fmt::Display for Caps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut held = Cap::ALL.into_iter().filter(|&cap| self.has(cap));
+        match held.next() {
+            None => f.write_str("none"),
+            Some(first) => {
+                f.write_str(first.name())?;
+                held.try_for_each(|cap| write!(f, ",{}", cap.name()))
+            }
+        }
+    }
+}
+
+/// What a user-origin call needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    Allowed,
+    KernelOnly,
+    Gated(Cap),
+}
+
+/// The policy table. Every number is listed, so a new syscall does not
+/// build until it has a policy.
+pub fn policy(num: SyscallNumber) -> Policy {
+    use SyscallNumber::*;
+    match num {
+        // Every caller buffer goes through the checked copies in
+        // `syscalls::user`
+        Read | Write | Open | Close | Lseek | Pipe | Poll => Policy::Allowed,
+
+        Exit | GetPid => Policy::Allowed,
+        Fork | Wait => Policy::Gated(Cap::Spawn),
+        // Places code read from a raw pointer, like `pstart`
+        Exec => Policy::KernelOnly,
+
+        // mmap maps at whatever address it is asked for, kernel pages
+        // included, so neither is for processes until it checks that
+        Mmap | Munmap => Policy::KernelOnly,
+        // brk takes any address, kernel half included, and grows without a
+        // cap, so it waits for the same checks
+        Brk => Policy::KernelOnly,
+
+        Sleep | GetTime | Nanosleep => Policy::Allowed,
+
+        // No notion of whose process is whose yet
+        Kill => Policy::KernelOnly,
+        Signal => Policy::Allowed,
+
+        Chdir | Mkdir => Policy::Allowed,
+
+        GetRandom => Policy::Allowed,
+
+        SurfaceCreate | SurfacePresent | SurfaceDestroy => Policy::Gated(Cap::Surface),
+
+        // Not implemented for anyone
+        Unknown => Policy::Allowed,
+    }
+}
+
+/// Whether a call to `num` from `origin`, by a process holding `caps`,
+/// may reach its handler.
+pub fn admit(num: SyscallNumber, origin: Origin, caps: Caps) -> Result<(), SyscallError> {
+    if origin == Origin::Kernel {
+        return Ok(());
+    }
+    match policy(num) {
+        Policy::Allowed => Ok(()),
+        Policy::Gated(cap) if caps.has(cap) => Ok(()),
+        Policy::KernelOnly | Policy::Gated(_) => Err(SyscallError::PermissionDenied),
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscalls::dispatcher::{dispatch_syscall, SyscallContext};
+    use crate::syscalls::handlers::process::{get_next_pid, set_caps, with_current_pid};
+
+    fn call(origin: Origin, num: SyscallNumber, arg0: usize) -> Result<usize, SyscallError> {
+        dispatch_syscall(
+            SyscallContext::from_registers(num as usize, arg0, 0, 0, 0, 0, 0).with_origin(origin),
+        )
+    }
+
+    #[test_case]
+    fn user_calls_follow_the_table_and_kernel_calls_pass() {
+        let pid = get_next_pid();
+        let numbers = (0..=255).chain([usize::MAX]).map(SyscallNumber::from);
+        with_current_pid(pid, || {
+            for num in numbers {
+                assert_eq!(admit(num, Origin::Kernel, Caps::NONE), Ok(()));
+                let expected = match policy(num) {
+                    Policy::Allowed => Ok(()),
+                    _ => Err(SyscallError::PermissionDenied),
+                };
+                assert_eq!(admit(num, Origin::User, Caps::NONE), expected, "{:?}", num);
+                // Refused before the handler, so even Exec is safe to make
+                if expected.is_err() {
+                    assert_eq!(
+                        call(Origin::User, num, 0),
+                        Err(SyscallError::PermissionDenied)
+                    );
+                }
+            }
+        });
+    }
+
+    #[test_case]
+    fn gated_calls_need_the_capability() {
+        let pid = get_next_pid();
+        let bad_handle = u32::MAX as usize;
+        with_current_pid(pid, || {
+            let destroy = |origin| call(origin, SyscallNumber::SurfaceDestroy, bad_handle);
+            assert_eq!(destroy(Origin::User), Err(SyscallError::PermissionDenied));
+            assert_eq!(
+                destroy(Origin::Kernel),
+                Err(SyscallError::BadFileDescriptor)
+            );
+
+            set_caps(pid, Caps::NONE.with(Cap::Surface));
+            assert_eq!(destroy(Origin::User), Err(SyscallError::BadFileDescriptor));
+            assert_eq!(
+                call(Origin::User, SyscallNumber::Fork, 0),
+                Err(SyscallError::PermissionDenied)
+            );
+            assert_eq!(
+                call(Origin::User, SyscallNumber::GetPid, 0),
+                call(Origin::Kernel, SyscallNumber::GetPid, 0)
+            );
+            assert_eq!(
+                call(Origin::User, SyscallNumber::Munmap, 0x2000_0000),
+                Err(SyscallError::PermissionDenied)
+            );
+            assert_eq!(
+                call(Origin::User, SyscallNumber::Brk, 0xffff_8000_0000_0000),
+                Err(SyscallError::PermissionDenied)
+            );
+            set_caps(pid, Caps::NONE);
+        });
+        assert_eq!(alloc::format!("{}", Caps::PSTART), "surface");
+        assert_eq!(Cap::parse("spawn"), Some(Cap::Spawn));
+    }
+}
@@ -0,0 +1,54 @@
+//! # User Memory Access
+//!
+//! Handlers copy through these instead of building slices from user
+//! pointers themselves. There is no separate user address space yet, so
+//! a pointer is refused only if it is null or the range wraps around or
+//! leaves the lower canonical half.
+//!
+//! The copy itself runs inside `hardening::user_access`, so it still works
+//! once SMAP is on and the pointer really is a user page.
+
+use crate::kcore::hardening::user_access;
+use crate::syscalls::dispatcher::SyscallError;
+
+/// End of the lower canonical half, where user mappings live.
+pub const USER_END: usize = 0x0000_8000_0000_0000;
+
+/// Refuses a user range that is null, wraps, or leaves the lower half.
+/// Handlers that would consume data before copying check up front.
+pub fn check_range(addr: usize, len: usize) -> Result<(), SyscallError> {
+    if addr == 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    match addr.checked_add(len) {
+        Some(end) if end <= USER_END => Ok(()),
+        _ => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// Copies `src` to the caller's buffer at `dst`.
+pub fn copy_to_user(dst: *mut u8, src: &[u8]) -> Result<(), SyscallError> {
+    check_range(dst as usize, src.len())?;
+    user_access(|| unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), dst, src.len()) });
+    Ok(())
+}
+
+/// Fills `dst` from the caller's buffer at `src`.
+pub fn copy_from_user(dst: &mut [u8], src: *const u8) -> Result<(), SyscallError> {
+    check_range(src as usize, dst.len())?;
+    user_access(|| unsafe { core::ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), dst.len()) });
+    Ok(())
+}
+
+/// Loads a `T` from the caller's `src`, which need not be aligned.
+pub fn read_user<T: Copy>(src: *const T) -> Result<T, SyscallError> {
+    check_range(src as usize, core::mem::size_of::<T>())?;
+    Ok(user_access(|| unsafe { src.read_unaligned() }))
+}
+
+/// Stores `value` at the caller's `dst`, which need not be aligned.
+pub fn write_user<T: Copy>(dst: *mut T, value: T) -> Result<(), SyscallError> {
+    check_range(dst as usize, core::mem::size_of::<T>())?;
+    user_access(|| unsafe { dst.write_unaligned(value) });
+    Ok(())
+}
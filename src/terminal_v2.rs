@@ -2,16 +2,150 @@
  //!
  //! A high-performance terminal buffer with ANSI escape support that can emit
  //! render commands for the unified graphics pipeline.
+ //!
+ //! ## Links
+ //!
+ //! Text written with `write_link` (or wrapped in an OSC 8 sequence, see
+ //! `format_link`) is drawn underlined in the theme accent and remembered as
+ //! a span on its line, so a click can be resolved back to the payload with
+ //! `link_at`. A link never wraps: the span is cut at the end of the line.
+ //! Spans are dropped with their line and whenever one of their cells is
+ //! written again.
+ //!
+ //! ## Line drawing
+ //!
+ //! `ESC ( 0` selects the DEC Special Graphics set, under which `q`, `x`,
+ //! `l`, `k`, `m`, `j` and friends are stored as box-drawing characters;
+ //! `ESC ( B` goes back to ASCII. Only G0 is supported, so there is no
+ //! shifting between sets.
+ //!
+ //! ## Scrollback
+ //!
+ //! Lines scrolled off the top are kept, up to `SCROLLBACK_LINES`, and
+ //! `scroll_view` shows them in place of the live screen until the next
+ //! write brings the view back. `clear_viewport` (`ESC [ 2 J`, Ctrl+L)
+ //! scrolls the rows in use away rather than erasing them; only `reset`
+ //! (`ESC [ 3 J`) wipes the scrollback too.
+ //!
+ //! ## Reflow
+ //!
+ //! A row that filled up and carried on to the next one is marked
+ //! `wrapped`; a newline leaves it unmarked. `resize` joins wrapped rows
+ //! back into the lines that were written, drops their trailing default
+ //! blanks and wraps them again at the new width, colours and links
+ //! included, so shrinking and growing back gives the same rows. The
+ //! cursor and the prompt start keep their offset in their line, and the
+ //! cursor keeps its distance from the bottom of the screen where it can;
+ //! rows above the new screen go to the scrollback.
+ //!
+ //! ## Escape sequences
+ //!
+ //! The parser knows three sequences: CSI (`ESC [` up to a final byte in
+ //! `@`..`~`), charset designation (`ESC (` and one character) and OSC
+ //! (`ESC ]` up to BEL or ST, `ESC \`). An ESC followed by anything else
+ //! is dropped and the character after it is written as text. Output that
+ //! does not play by these rules cannot wedge the parser:
+ //!
+ //! - a CSI longer than `MAX_ESCAPE_LEN` bytes is abandoned and what it
+ //!   held is written as text;
+ //! - an OSC longer than `MAX_OSC_LEN` stops being kept and is skipped up
+ //!   to its terminator;
+ //! - a character that cannot appear in the sequence ends it unparsed;
+ //! - a newline ends any sequence before moving the cursor.
+ //!
+ //! Each case is counted, across all terminals, in `escape_errors`.
+ //!
+ //! ## Attributes
+ //!
+ //! SGR sets bold (1), underline (4) and inverse (7) alongside the colours,
+ //! and 22, 24 and 27 clear them; 0 clears everything. Each cell keeps its
+ //! `Attrs`, and a run of text is cut wherever they change. Inverse swaps
+ //! the colours at draw time, so the cell still holds the ones written.
+ //! Bold comes from `glyphs`, underline is a line where links draw theirs.
 
  use crate::ui_provider::{
      color::Color,
+     frame_arena::{arena_chars, arena_str},
      render::{RenderCommand, RenderList, TextStyle},
      theme::Theme,
  };
- use alloc::{string::String, vec::Vec};
+ use alloc::{collections::VecDeque, string::String, vec::Vec};
  use core::fmt::{self, Write};
+ use core::sync::atomic::{AtomicU32, Ordering};
 
  const FONT_BASELINE_OFFSET: usize = 16;
+ /// Longest OSC sequence kept; a longer one is skipped to its terminator.
+ const MAX_OSC_LEN: usize = 256;
+ /// Longest CSI or charset sequence, in bytes after the ESC.
+ const MAX_ESCAPE_LEN: usize = 64;
+ /// Lines kept once they scroll off the top.
+ pub const SCROLLBACK_LINES: usize = 1000;
+
+ static ESCAPE_OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+ static ESCAPE_MALFORMED: AtomicU32 = AtomicU32::new(0);
+ static ESCAPE_NEWLINE_RESETS: AtomicU32 = AtomicU32::new(0);
+ static ESCAPE_BARE: AtomicU32 = AtomicU32::new(0);
+
+ /// Sequences the parser gave up on since boot, over all terminals.
+ #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+ pub struct EscapeErrors {
+     /// Too long: a CSI written out as text, or an OSC skipped.
+     pub overflowed: u32,
+     /// Ended by a character that does not belong in them.
+     pub malformed: u32,
+     /// Cut short by a newline.
+     pub newline_resets: u32,
+     /// An ESC not starting a known sequence.
+     pub bare_escapes: u32,
+ }
+
+ pub fn escape_errors() -> EscapeErrors {
+     EscapeErrors {
+         overflowed: ESCAPE_OVERFLOWS.load(Ordering::Relaxed),
+         malformed: ESCAPE_MALFORMED.load(Ordering::Relaxed),
+         newline_resets: ESCAPE_NEWLINE_RESETS.load(Ordering::Relaxed),
+         bare_escapes: ESCAPE_BARE.load(Ordering::Relaxed),
+     }
+ }
+
+ /// A line for `renderstat`.
+ pub fn escape_report() -> String {
+     let e = escape_errors();
+     alloc::format!(
+         "Escapes: {} overflowed, {} malformed, {} cut by newline, {} bare ESC",
+         e.overflowed,
+         e.malformed,
+         e.newline_resets,
+         e.bare_escapes
+     )
+ }
+
+ fn count(counter: &AtomicU32) {
+     counter.fetch_add(1, Ordering::Relaxed);
+ }
+
+ /// SGR rendition flags of a cell.
+ #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+ pub struct Attrs(u8);
+
+ impl Attrs {
+     pub const NONE: Self = Self(0);
+     pub const BOLD: Self = Self(1);
+     pub const UNDERLINE: Self = Self(2);
+     pub const INVERSE: Self = Self(4);
+
+     pub const fn has(self, other: Self) -> bool {
+         self.0 & other.0 == other.0
+     }
+
+     pub const fn with(self, other: Self) -> Self {
+         Self(self.0 | other.0)
+     }
+
+     pub const fn without(self, other: Self) -> Self {
+         Self(self.0 & !other.0)
+     }
+ }
 
  /// A single character cell with foreground and background colors.
  #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -19,17 +153,100 @@
      pub ch: char,
      pub fg: Color,
      pub bg: Color,
+     pub attrs: Attrs,
  }
 
  impl Cell {
      #[inline]
      pub const fn new(ch: char, fg: Color, bg: Color) -> Self {
-         Self { ch, fg, bg }
+         Self {
+             ch,
+             fg,
+             bg,
+             attrs: Attrs::NONE,
+         }
      }
 
      #[inline]
      pub const fn blank(fg: Color, bg: Color) -> Self {
-         Self { ch: ' ', fg, bg }
+         Self::new(' ', fg, bg)
+     }
+
+     #[inline]
+     pub const fn with_attrs(mut self, attrs: Attrs) -> Self {
+         self.attrs = attrs;
+         self
+     }
+
+     /// Foreground and background as drawn, swapped for inverse.
+     #[inline]
+     pub const fn drawn(&self) -> (Color, Color) {
+         if self.attrs.has(Attrs::INVERSE) {
+             (self.bg, self.fg)
+         } else {
+             (self.fg, self.bg)
+         }
+     }
+ }
+
+ /// The set printable ASCII is drawn from.
+ #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+ pub enum Charset {
+     Ascii,
+     DecSpecialGraphics,
+ }
+
+ /// Where the parser is within an escape sequence.
+ #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+ enum Escape {
+     Ground,
+     /// Just after ESC.
+     Start,
+     /// `ESC [`, up to the final byte.
+     Csi,
+     /// `ESC (`, one character to come.
+     Charset,
+     /// `ESC ]`, up to BEL or ST.
+     Osc,
+     /// An OSC past `MAX_OSC_LEN`, skipped to its terminator.
+     OscSkip,
+ }
+
+ impl Charset {
+     /// `ch` as this set draws it.
+     pub fn translate(self, ch: char) -> char {
+         if self == Charset::Ascii {
+             return ch;
+         }
+         match ch {
+             '`' => '◆',
+             'a' => '▒',
+             'f' => '°',
+             'g' => '±',
+             'j' => '┘',
+             'k' => '┐',
+             'l' => '┌',
+             'm' => '└',
+             'n' => '┼',
+             'o' => '⎺',
+             'p' => '⎻',
+             'q' => '─',
+             'r' => '⎼',
+             's' => '⎽',
+             't' => '├',
+             'u' => '┤',
+             'v' => '┴',
+             'w' => '┬',
+             'x' => '│',
+             'y' => '≤',
+             'z' => '≥',
+             '{' => 'π',
+             '|' => '≠',
+             '}' => '£',
+             '~' => '·',
+             '_' => ' ',
+             _ => ch,
+         }
      }
  }
 
@@ -39,10 +256,30 @@
      }
  }
 
+ /// Cells `start..end` of a line that resolve to `payload` when clicked.
+ #[derive(Clone, Debug, PartialEq, Eq)]
+ struct LinkSpan {
+     start: usize,
+     end: usize,
+     payload: String,
+ }
+
+ /// Link being written: where it started and what it resolves to.
+ #[derive(Clone)]
+ struct OpenLink {
+     line_idx: usize,
+     start: usize,
+     payload: String,
+ }
+
  #[derive(Clone)]
  struct Line {
      cells: Vec<Cell>,
+     links: Vec<LinkSpan>,
      dirty: bool,
+     /// Filled up and carried on to the next row, rather than ended by a
+     /// newline.
+     wrapped: bool,
  }
 
  impl Line {
@@ -52,7 +289,12 @@
          for _ in 0..width {
              cells.push(blank);
          }
-         Self { cells, dirty: true }
+         Self {
+             cells,
+             links: Vec::new(),
+             dirty: true,
+             wrapped: false,
+         }
      }
 
      fn clear(&mut self, fg: Color, bg: Color) {
@@ -60,41 +302,163 @@
          for cell in &mut self.cells {
              *cell = blank;
          }
+         self.links.clear();
          self.dirty = true;
+         self.wrapped = false;
+     }
+
+     /// Forgets links touching cells `from..to`.
+     fn drop_links(&mut self, from: usize, to: usize) {
+         self.links.retain(|l| l.end <= from || l.start >= to);
      }
  }
 
+ /// A line as written, its wrapped rows joined: cells up to the last one
+ /// that is not a default blank, and links by cell offset.
+ struct Logical {
+     cells: Vec<Cell>,
+     links: Vec<LinkSpan>,
+ }
+
+ impl Logical {
+     /// Appends `row`, joining a link that carries on from the last one.
+     fn push_row(&mut self, row: Line) {
+         let start = self.cells.len();
+         for link in row.links {
+             match self.links.last_mut() {
+                 Some(last) if last.end == start + link.start && last.payload == link.payload => {
+                     last.end = start + link.end;
+                 }
+                 _ => self.links.push(LinkSpan {
+                     start: start + link.start,
+                     end: start + link.end,
+                     payload: link.payload,
+                 }),
+             }
+         }
+         self.cells.extend(row.cells);
+     }
+
+     /// Cells `from..to` as one row of `width`, blank-padded.
+     fn row(&self, from: usize, to: usize, width: usize, blank: Cell) -> Line {
+         let mut cells = Vec::with_capacity(width);
+         cells.extend_from_slice(&self.cells[from..to]);
+         cells.resize(width, blank);
+         let links = self
+             .links
+             .iter()
+             .filter(|l| l.start < to && l.end > from)
+             .map(|l| LinkSpan {
+                 start: l.start.max(from) - from,
+                 end: l.end.min(to) - from,
+                 payload: l.payload.clone(),
+             })
+             .collect();
+         Line {
+             cells,
+             links,
+             dirty: true,
+             wrapped: false,
+         }
+     }
+ }
+
+ /// Row and column of `offset` into a line of `len` cells wrapped at
+ /// `width`. An offset just past a full row stays at the end of it, as a
+ /// cursor that filled the row does, unless more of the line follows.
+ fn wrap_position(offset: usize, len: usize, width: usize) -> (usize, usize) {
+     if offset > 0 && offset.is_multiple_of(width) && offset >= len {
+         (offset / width - 1, width)
+     } else {
+         (offset / width, offset % width)
+     }
+ }
+
+ /// Wraps `text` in an OSC 8 sequence so that writing the result to a
+ /// `Terminal` records it as a link to `payload`.
+ pub fn format_link(text: &str, payload: &str) -> String {
+     let mut out = String::with_capacity(text.len() + payload.len() + 12);
+     out.push_str("\x1b]8;;");
+     out.push_str(payload);
+     out.push('\x07');
+     out.push_str(text);
+     out.push_str("\x1b]8;;\x07");
+     out
+ }
+
+ /// `text` as a progress line: back to column 0, the text, then the rest
+ /// of the row erased, so a shorter update leaves nothing of the last one.
+ pub fn progress_line(text: &str) -> String {
+     let mut out = String::with_capacity(text.len() + 4);
+     out.push('\r');
+     out.push_str(text);
+     out.push_str("\x1b[K");
+     out
+ }
+
  /// High-performance terminal with ring buffer for efficient scrolling.
  pub struct Terminal {
      lines: Vec<Line>,
      top_line: usize,
 
+     /// Lines that scrolled off the top, oldest first.
+     scrollback: VecDeque<Line>,
+     /// Lines the view is scrolled back by; 0 shows the live screen.
+     view_offset: usize,
+     /// The scrolled-back view needs drawing again.
+     view_dirty: bool,
+
      width: usize,
      height: usize,
 
      cursor_x: usize,
      cursor_y: usize,
 
-     prompt_start_x: usize,
-     prompt_start_y: usize,
+     /// Lines scrolled off the top since the last reset, so `scrolled + y`
+     /// names the line on row `y` however often the screen scrolls later.
+     scrolled: usize,
+     /// Where input starts: a column and a line named as above.
+     prompt_x: usize,
+     prompt_line: usize,
 
      last_cursor_x: usize,
      last_cursor_y: usize,
 
      fg: Color,
      bg: Color,
+     /// Given to cells as they are written, like `fg` and `bg`.
+     attrs: Attrs,
      default_fg: Color,
      default_bg: Color,
+     /// Cells in `default_bg` are left undrawn, showing what is behind.
+     transparent: bool,
 
      char_width: usize,
      char_height: usize,
 
+     /// The sequence so far, from the character after the ESC. Bounded by
+     /// `MAX_ESCAPE_LEN`, or `MAX_OSC_LEN` for an OSC.
      escape_buffer: String,
-     in_escape: bool,
+     escape: Escape,
+
+     block_cursor: bool,
+
+     link_fg: Color,
+     open_link: Option<OpenLink>,
+
+     caret: Color,
+     caret_height: usize,
+
+     /// A BEL arrived since the last `take_bell`.
+     bell_pending: bool,
+
+     /// G0, as designated by `ESC (`.
+     charset: Charset,
  }
 
  impl Terminal {
      pub fn new(width: usize, height: usize, theme: &Theme) -> Self {
+         crate::require_stage!(crate::kcore::kernel::init::Stage::Memory);
          let mut lines = Vec::with_capacity(height);
          for _ in 0..height {
              lines.push(Line::new(width, theme.text, theme.surface));
@@ -103,23 +467,83 @@
          Self {
              lines,
              top_line: 0,
+             scrollback: VecDeque::new(),
+             view_offset: 0,
+             view_dirty: false,
              width,
              height,
              cursor_x: 0,
              cursor_y: 0,
-             prompt_start_x: 0,
-             prompt_start_y: 0,
+             scrolled: 0,
+             prompt_x: 0,
+             prompt_line: 0,
              last_cursor_x: 0,
              last_cursor_y: 0,
             fg: theme.text,
             bg: theme.surface,
+             attrs: Attrs::NONE,
             default_fg: theme.text,
             default_bg: theme.surface,
+             transparent: false,
              char_width: 10,
              char_height: 20,
              escape_buffer: String::new(),
-             in_escape: false,
+             escape: Escape::Ground,
+             block_cursor: false,
+             link_fg: theme.accent,
+             open_link: None,
+             caret: theme.caret,
+             caret_height: theme.caret_height,
+             bell_pending: false,
+             charset: Charset::Ascii,
+         }
+     }
+
+     /// Returns whether it changed, in which case every row is dirty.
+     pub fn set_transparent(&mut self, on: bool) -> bool {
+         if self.transparent == on {
+             return false;
          }
+         self.transparent = on;
+         self.invalidate_all();
+         true
+     }
+
+     /// Switches to the colours of `theme`. Cells in the old default or link
+     /// colours take the new ones; ANSI colours stay. Returns whether
+     /// anything changed, in which case every row is dirty.
+     pub fn set_theme(&mut self, theme: &Theme) -> bool {
+         if self.default_fg == theme.text
+             && self.default_bg == theme.surface
+             && self.link_fg == theme.accent
+             && self.caret == theme.caret
+             && self.caret_height == theme.caret_height
+         {
+             return false;
+         }
+
+         let (old_fg, old_bg, old_link) = (self.default_fg, self.default_bg, self.link_fg);
+         let fg = |c: Color| match c {
+             c if c == old_fg => theme.text,
+             c if c == old_link => theme.accent,
+             c => c,
+         };
+         let bg = |c: Color| if c == old_bg { theme.surface } else { c };
+         for line in &mut self.lines {
+             for cell in &mut line.cells {
+                 cell.fg = fg(cell.fg);
+                 cell.bg = bg(cell.bg);
+             }
+             line.dirty = true;
+         }
+         self.fg = fg(self.fg);
+         self.bg = bg(self.bg);
+         self.default_fg = theme.text;
+         self.default_bg = theme.surface;
+         self.link_fg = theme.accent;
+         self.caret = theme.caret;
+         self.caret_height = theme.caret_height;
+         true
      }
 
      pub fn size(&self) -> (usize, usize) {
@@ -130,9 +554,209 @@
          (self.width * self.char_width, self.height * self.char_height)
      }
 
+     /// Re-wraps the screen and the scrollback to `width` x `height`; see
+     /// the module docs. An escape sequence in progress carries on, and an
+     /// open link is cut at the old cursor and goes on from the new one.
+     pub fn resize(&mut self, width: usize, height: usize) {
+         let (width, height) = (width.max(1), height.max(1));
+         if (width, height) == (self.width, self.height) || self.width == 0 || self.height == 0 {
+             return;
+         }
+         let payload = self.open_link.as_ref().map(|link| link.payload.clone());
+         self.end_link();
+         let blank = Cell::blank(self.default_fg, self.default_bg);
+
+         // Every row, oldest first; line `first + n` in `scrolled` terms
+         // is row `n`
+         let first = self.scrolled - self.scrollback.len();
+         let cursor_row = self.scrollback.len() + self.cursor_y;
+         let below = self.height - 1 - self.cursor_y;
+         let prompt_row = self.prompt_line.checked_sub(first);
+         let mut rows: Vec<Line> = self.scrollback.drain(..).collect();
+         let mut screen = core::mem::take(&mut self.lines);
+         screen.rotate_left(self.top_line);
+         rows.extend(screen);
+         while rows.len() > cursor_row + 1
+             && rows
+                 .last()
+                 .is_some_and(|row| !row.wrapped && row.cells.iter().all(|&c| c == blank))
+         {
+             rows.pop();
+         }
+
+         // Join wrapped rows, noting where the cursor and the prompt start
+         // fall as (line, offset)
+         let mut marks = [
+             (Some(cursor_row), self.cursor_x),
+             (prompt_row, self.prompt_x),
+         ];
+         let mut lines: Vec<Logical> = Vec::new();
+         let mut carried = false;
+         for (idx, row) in rows.into_iter().enumerate() {
+             if !carried {
+                 lines.push(Logical {
+                     cells: Vec::new(),
+                     links: Vec::new(),
+                 });
+             }
+             carried = row.wrapped;
+             let line = lines.len() - 1;
+             let start = lines[line].cells.len();
+             for (row_of, x) in &mut marks {
+                 if *row_of == Some(idx) {
+                     *row_of = Some(line);
+                     *x += start;
+                 }
+             }
+             lines[line].push_row(row);
+         }
+         for line in &mut lines {
+             let len = line.cells.iter().rposition(|&c| c != blank).map_or(0, |i| i + 1);
+             line.cells.truncate(len);
+         }
+
+         // Wrap them again; a line gets a row for each mark in it too
+         let mut out: Vec<Line> = Vec::new();
+         let mut placed = [None; 2];
+         for (idx, line) in lines.iter().enumerate() {
+             let len = line.cells.len();
+             let mut needed = len.div_ceil(width).max(1);
+             for (slot, &(line_of, offset)) in placed.iter_mut().zip(&marks) {
+                 if line_of == Some(idx) {
+                     let (row, x) = wrap_position(offset, len, width);
+                     needed = needed.max(row + 1);
+                     *slot = Some((out.len() + row, x));
+                 }
+             }
+             for k in 0..needed {
+                 let (from, to) = ((k * width).min(len), ((k + 1) * width).min(len));
+                 let mut row = line.row(from, to, width, blank);
+                 row.wrapped = k + 1 < needed;
+                 out.push(row);
+             }
+         }
+
+         // The cursor keeps its distance from the bottom where it can
+         let (cursor_row, cursor_x) = placed[0].unwrap_or((out.len().saturating_sub(1), 0));
+         let top = (cursor_row + 1 + below.min(height - 1))
+             .saturating_sub(height)
+             .min(cursor_row);
+         let evicted = top.saturating_sub(SCROLLBACK_LINES);
+         let mut rest = out.split_off(top);
+         rest.truncate(height);
+         rest.resize_with(height, || Line::new(width, self.default_fg, self.default_bg));
+         self.scrollback = out.into_iter().skip(evicted).collect();
+         self.lines = rest;
+         self.top_line = 0;
+         self.width = width;
+         self.height = height;
+         self.scrolled = self.scrollback.len();
+         self.cursor_x = cursor_x;
+         self.cursor_y = cursor_row - top;
+         (self.prompt_line, self.prompt_x) = match placed[1] {
+             Some((row, x)) if row >= evicted => (row - evicted, x),
+             _ => (0, 0),
+         };
+         self.last_cursor_x = self.cursor_x;
+         self.last_cursor_y = self.cursor_y;
+         self.view_offset = 0;
+         self.invalidate_all();
+         if let Some(payload) = payload {
+             if self.cursor_x < self.width {
+                 self.begin_link(&payload);
+             }
+         }
+     }
+
      pub fn set_prompt_start(&mut self) {
-         self.prompt_start_x = self.cursor_x;
-         self.prompt_start_y = self.cursor_y;
+         self.prompt_x = self.cursor_x;
+         self.prompt_line = self.scrolled + self.cursor_y;
+     }
+
+     /// The prompt start on screen; the top-left corner once it has
+     /// scrolled off.
+     fn prompt_start(&self) -> (usize, usize) {
+         match self.prompt_line.checked_sub(self.scrolled) {
+             Some(y) => (self.prompt_x, y),
+             None => (0, 0),
+         }
+     }
+
+     /// Whether a BEL was written since the last call. Any number of them
+     /// between two frames counts once.
+     pub fn take_bell(&mut self) -> bool {
+         core::mem::take(&mut self.bell_pending)
+     }
+
+     /// Block caret for overwrite mode, underline bar otherwise.
+     pub fn set_block_cursor(&mut self, block: bool) {
+         if self.block_cursor != block {
+             self.block_cursor = block;
+             self.mark_line_dirty(self.cursor_y);
+         }
+     }
+
+     /// Replaces everything after the prompt with `text` and puts the cursor
+     /// on char `cursor` of it, so mid-line edits stay in sync with the
+     /// logical input. Cells that already match are left clean.
+     pub fn redraw_input(&mut self, text: &str, cursor: usize) {
+         if self.width == 0 || self.height == 0 {
+             return;
+         }
+
+         let blank = Cell::blank(self.fg, self.bg);
+         let (prompt_x, prompt_y) = self.prompt_start();
+         for y in prompt_y..self.height {
+             let from = if y == prompt_y {
+                 prompt_x.min(self.width)
+             } else {
+                 0
+             };
+             let idx = self.line_index(y);
+             let line = &mut self.lines[idx];
+             line.drop_links(from, self.width);
+             for cell in &mut line.cells[from..] {
+                 if *cell != blank {
+                     *cell = blank;
+                     line.dirty = true;
+                 }
+             }
+         }
+
+         self.cursor_x = prompt_x;
+         self.cursor_y = prompt_y;
+         self.write(text);
+
+         // Walk the text again from the (possibly scrolled) prompt start.
+         let (mut x, mut y) = self.prompt_start();
+         for ch in text.chars().take(cursor) {
+             if ch == '\n' {
+                 x = 0;
+                 y += 1;
+             } else {
+                 if x >= self.width {
+                     x = 0;
+                     y += 1;
+                 }
+                 x += 1;
+             }
+         }
+         if x >= self.width && cursor < text.chars().count() {
+             x = 0;
+             y += 1;
+         }
+         self.cursor_x = x;
+         self.cursor_y = y.min(self.height - 1);
+     }
+
+     /// Replaces the whole prompt row, prompt included, with `text`, as the
+     /// history search does. Write the prompt again to go back to editing.
+     pub fn redraw_prompt_row(&mut self, text: &str) {
+         let x = self.prompt_x;
+         self.prompt_x = 0;
+         self.redraw_input("", 0);
+         self.prompt_x = x;
+         self.write(text);
      }
 
      #[inline]
@@ -149,25 +773,79 @@
      }
 
      pub fn write(&mut self, text: &str) {
+         // New output is shown live.
+         if self.view_offset != 0 {
+             self.view_offset = 0;
+             self.invalidate_all();
+         }
          for ch in text.chars() {
              self.process_char(ch);
          }
      }
 
+     /// Writes `text` as a link that `link_at` resolves to `payload`.
+     pub fn write_link(&mut self, text: &str, payload: &str) {
+         self.begin_link(payload);
+         self.write(text);
+         self.end_link();
+     }
+
+     fn begin_link(&mut self, payload: &str) {
+         self.end_link();
+         if self.width == 0 || self.height == 0 {
+             return;
+         }
+         // A link starting past the last column begins on the next line.
+         if self.cursor_x >= self.width {
+             self.newline();
+         }
+         self.open_link = Some(OpenLink {
+             line_idx: self.line_index(self.cursor_y),
+             start: self.cursor_x,
+             payload: String::from(payload),
+         });
+     }
+
+     /// Closes the open link at the cursor. Also called when the link would
+     /// wrap, so the rest of its text is written as plain text.
+     fn end_link(&mut self) {
+         let Some(link) = self.open_link.take() else {
+             return;
+         };
+         let end = self.cursor_x.min(self.width);
+         if self.line_index(self.cursor_y) != link.line_idx || end <= link.start {
+             return;
+         }
+         self.lines[link.line_idx].links.push(LinkSpan {
+             start: link.start,
+             end,
+             payload: link.payload,
+         });
+     }
+
+     /// Payload of the link under the pixel `(px, py)`, relative to the
+     /// terminal's top-left corner.
+     pub fn link_at(&self, px: usize, py: usize) -> Option<&str> {
+         let (x, y) = (px / self.char_width, py / self.char_height);
+         if x >= self.width || y >= self.height {
+             return None;
+         }
+         self.lines[self.line_index(y)]
+             .links
+             .iter()
+             .find(|l| x >= l.start && x < l.end)
+             .map(|l| l.payload.as_str())
+     }
+
      fn process_char(&mut self, ch: char) {
-         if self.in_escape {
-             self.escape_buffer.push(ch);
-             if self.is_escape_complete() {
-                 self.process_escape();
-                 self.escape_buffer.clear();
-                 self.in_escape = false;
-             }
+         if self.escape != Escape::Ground {
+             self.escape_char(ch);
              return;
          }
 
          match ch {
              '\x1b' => {
-                 self.in_escape = true;
+                 self.escape = Escape::Start;
                  self.escape_buffer.clear();
              }
              '\n' => self.newline(),
@@ -175,27 +853,59 @@
                  self.cursor_x = 0;
              }
              '\x08' => self.backspace(),
+             '\x07' => self.bell_pending = true,
              '\t' => {
                  let next_tab = ((self.cursor_x / 8) + 1) * 8;
-                 self.cursor_x = next_tab.min(self.width.saturating_sub(1));
+                 let next_tab = next_tab.min(self.width.saturating_sub(1));
+                 // The cells skipped over are blanked, not left as they were.
+                 self.blank_cells(self.cursor_x, next_tab);
+                 self.cursor_x = next_tab;
              }
              _ if !ch.is_control() => self.put_char(ch),
              _ => {}
          }
      }
 
+     /// Blanks cells `from..to` of the cursor row in the current colours.
+     fn blank_cells(&mut self, from: usize, to: usize) {
+         if from >= to || self.height == 0 {
+             return;
+         }
+         let blank = Cell::blank(self.fg, self.bg);
+         let idx = self.line_index(self.cursor_y);
+         let line = &mut self.lines[idx];
+         line.drop_links(from, to);
+         for cell in &mut line.cells[from..to] {
+             if *cell != blank {
+                 *cell = blank;
+                 line.dirty = true;
+             }
+         }
+     }
+
      fn put_char(&mut self, ch: char) {
          if self.width == 0 || self.height == 0 {
              return;
          }
 
          if self.cursor_x >= self.width {
+             let idx = self.line_index(self.cursor_y);
+             self.lines[idx].wrapped = true;
              self.newline();
          }
 
-         let new_cell = Cell::new(ch, self.fg, self.bg);
+         let fg = if self.open_link.is_some() {
+             self.link_fg
+         } else {
+             self.fg
+         };
+         let new_cell = Cell::new(self.charset.translate(ch), fg, self.bg).with_attrs(self.attrs);
          let idx = self.line_index(self.cursor_y);
 
+         if !self.lines[idx].links.is_empty() {
+             self.lines[idx].drop_links(self.cursor_x, self.cursor_x + 1);
+             self.lines[idx].dirty = true;
+         }
          if self.lines[idx].cells[self.cursor_x] != new_cell {
              self.lines[idx].cells[self.cursor_x] = new_cell;
              self.lines[idx].dirty = true;
@@ -209,6 +919,7 @@
              return;
          }
 
+         self.end_link();
          self.cursor_x = 0;
          self.cursor_y += 1;
 
@@ -222,66 +933,230 @@
          let old_top = self.top_line;
          self.top_line = (self.top_line + 1) % self.height;
 
-         self.lines[old_top].clear(self.fg, self.bg);
+         // The oldest scrollback line, once there are enough, is reused for
+         // the new bottom row.
+         let mut fresh = if self.scrollback.len() >= SCROLLBACK_LINES {
+             self.scrollback.pop_front()
+         } else {
+             None
+         }
+         .unwrap_or_else(|| Line::new(self.width, self.fg, self.bg));
+         fresh.clear(self.fg, self.bg);
+         core::mem::swap(&mut self.lines[old_top], &mut fresh);
+         self.scrollback.push_back(fresh);
+         self.view_offset = self.view_offset.min(self.scrollback.len());
 
          for line in &mut self.lines {
              line.dirty = true;
          }
-
-         if self.prompt_start_y > 0 {
-             self.prompt_start_y -= 1;
-         } else {
-             self.prompt_start_x = 0;
-         }
+         self.scrolled += 1;
      }
 
+     /// Steps back one cell and blanks it. Positions count cells from the
+     /// first line since the reset, so wraps and scrolls during input do
+     /// not matter; the cursor never goes before the prompt start, nor
+     /// above the top row once the prompt has scrolled off.
      fn backspace(&mut self) {
          if self.width == 0 || self.height == 0 {
              return;
          }
 
-         let can_backspace = if self.cursor_y == self.prompt_start_y {
-             self.cursor_x > self.prompt_start_x
-         } else {
-             self.cursor_x > 0 || self.cursor_y > self.prompt_start_y
-         };
+         let cell = |line: usize, x: usize| line * self.width + x;
+         // A cursor parked past the last column is the next line's first cell
+         let cursor = cell(self.scrolled + self.cursor_y, self.cursor_x);
+         let floor = cell(self.prompt_line, self.prompt_x).max(cell(self.scrolled, 0));
+         if cursor <= floor {
+             return;
+         }
+         let pos = cursor - 1;
+         self.cursor_y = pos / self.width - self.scrolled;
+         self.cursor_x = pos % self.width;
 
-         if can_backspace {
-             if self.cursor_x > 0 {
-                 self.cursor_x -= 1;
-             } else if self.cursor_y > 0 {
-                 self.cursor_y -= 1;
-                 self.cursor_x = self.width - 1;
-             }
+         let idx = self.line_index(self.cursor_y);
+         self.lines[idx].cells[self.cursor_x] = Cell::blank(self.fg, self.bg);
+         self.lines[idx].drop_links(self.cursor_x, self.cursor_x + 1);
+         self.lines[idx].dirty = true;
+     }
 
-             let idx = self.line_index(self.cursor_y);
-             self.lines[idx].cells[self.cursor_x] = Cell::blank(self.fg, self.bg);
-             self.lines[idx].dirty = true;
+     /// Scrolls the rows in use into the scrollback and homes the cursor,
+     /// as Ctrl+L does. The prompt start moves to the top-left with it.
+     pub fn clear_viewport(&mut self) {
+         if self.height == 0 {
+             return;
+         }
+         self.open_link = None;
+         let used = (0..self.height)
+             .rev()
+             .find(|&y| {
+                 self.lines[self.line_index(y)]
+                     .cells
+                     .iter()
+                     .any(|c| c.ch != ' ')
+             })
+             .map_or(0, |y| y + 1);
+         for _ in 0..used {
+             self.scroll_up();
          }
+         for line in &mut self.lines {
+             line.clear(self.default_fg, self.default_bg);
+         }
+         self.view_offset = 0;
+         self.cursor_x = 0;
+         self.cursor_y = 0;
+         self.prompt_x = 0;
+         self.prompt_line = self.scrolled;
+         self.last_cursor_x = 0;
+         self.last_cursor_y = 0;
      }
 
-     pub fn clear(&mut self) {
+     /// Wipes the screen and the scrollback.
+     pub fn reset(&mut self) {
+         self.scrollback.clear();
+         self.view_offset = 0;
+         self.open_link = None;
          for line in &mut self.lines {
              line.clear(self.default_fg, self.default_bg);
          }
          self.cursor_x = 0;
          self.cursor_y = 0;
          self.top_line = 0;
-         self.prompt_start_x = 0;
-         self.prompt_start_y = 0;
+         self.scrolled = 0;
+         self.prompt_x = 0;
+         self.prompt_line = 0;
          self.last_cursor_x = 0;
          self.last_cursor_y = 0;
      }
 
-     fn is_escape_complete(&self) -> bool {
-         if self.escape_buffer.is_empty() {
-             return false;
+     /// Feeds `ch` to the escape sequence being parsed.
+     fn escape_char(&mut self, ch: char) {
+         // Whatever state a sequence was left in, a newline ends it.
+         if ch == '\n' {
+             count(&ESCAPE_NEWLINE_RESETS);
+             self.end_escape();
+             self.newline();
+             return;
+         }
+         match self.escape {
+             Escape::Ground => {}
+             Escape::Start => {
+                 self.escape = match ch {
+                     '[' => Escape::Csi,
+                     '(' => Escape::Charset,
+                     ']' => Escape::Osc,
+                     _ => {
+                         // Not a sequence we know: drop the ESC, keep the text.
+                         count(&ESCAPE_BARE);
+                         self.end_escape();
+                         self.process_char(ch);
+                         return;
+                     }
+                 };
+                 self.escape_buffer.push(ch);
+             }
+             Escape::Csi => match ch {
+                 '\x40'..='\x7e' => {
+                     self.escape_buffer.push(ch);
+                     self.finish_escape();
+                 }
+                 '\x20'..='\x3f' if self.escape_buffer.len() < MAX_ESCAPE_LEN => {
+                     self.escape_buffer.push(ch);
+                 }
+                 '\x20'..='\x3f' => {
+                     count(&ESCAPE_OVERFLOWS);
+                     self.flush_escape();
+                     self.process_char(ch);
+                 }
+                 _ => {
+                     count(&ESCAPE_MALFORMED);
+                     self.end_escape();
+                     self.process_char(ch);
+                 }
+             },
+             Escape::Charset => {
+                 self.escape_buffer.push(ch);
+                 self.finish_escape();
+             }
+             Escape::Osc | Escape::OscSkip => self.osc_char(ch),
          }
-         let last = self.escape_buffer.chars().last().unwrap();
-         last.is_alphabetic() || last == 'm'
+     }
+
+     /// OSC body: kept up to `MAX_OSC_LEN`, ended by BEL or `ESC \`. Only the
+     /// trailing ESC is kept while skipping.
+     fn osc_char(&mut self, ch: char) {
+         let after_esc = self.escape_buffer.ends_with('\x1b');
+         if after_esc {
+             self.escape_buffer.pop();
+         }
+         match ch {
+             '\x07' => self.finish_escape(),
+             '\\' if after_esc => self.finish_escape(),
+             _ if after_esc => {
+                 // The ESC starts another sequence; this one is dropped.
+                 count(&ESCAPE_MALFORMED);
+                 self.end_escape();
+                 self.escape = Escape::Start;
+                 self.process_char(ch);
+             }
+             '\x1b' if self.escape == Escape::OscSkip => self.escape_buffer.push(ch),
+             _ if self.escape == Escape::OscSkip => {}
+             _ if self.escape_buffer.len() < MAX_OSC_LEN => self.escape_buffer.push(ch),
+             _ => {
+                 count(&ESCAPE_OVERFLOWS);
+                 self.escape = Escape::OscSkip;
+                 self.escape_buffer.clear();
+                 if ch == '\x1b' {
+                     self.escape_buffer.push(ch);
+                 }
+             }
+         }
+     }
+
+     /// Acts on a complete sequence, unless it was being skipped.
+     fn finish_escape(&mut self) {
+         if self.escape != Escape::OscSkip {
+             self.process_escape();
+         }
+         self.end_escape();
+     }
+
+     fn end_escape(&mut self) {
+         self.escape = Escape::Ground;
+         self.escape_buffer.clear();
+     }
+
+     /// Gives up on an overlong sequence, writing what it held as text.
+     fn flush_escape(&mut self) {
+         let held = core::mem::take(&mut self.escape_buffer);
+         self.escape = Escape::Ground;
+         for ch in held.chars() {
+             self.put_char(ch);
+         }
+         // Keep the allocation for the next sequence.
+         self.escape_buffer = held;
+         self.escape_buffer.clear();
      }
 
      fn process_escape(&mut self) {
+         if let Some(body) = self.escape_buffer.strip_prefix("]8;") {
+             // OSC 8 ; params ; uri - an empty uri closes the link.
+             let uri = body.split_once(';').map_or("", |(_, uri)| uri);
+             if uri.is_empty() {
+                 self.end_link();
+             } else {
+                 let uri = String::from(uri);
+                 self.begin_link(&uri);
+             }
+             return;
+         }
+         if let Some(set) = self.escape_buffer.strip_prefix('(') {
+             // Sets other than these two are ignored.
+             match set {
+                 "0" => self.charset = Charset::DecSpecialGraphics,
+                 "B" => self.charset = Charset::Ascii,
+                 _ => {}
+             }
+             return;
+         }
          if !self.escape_buffer.starts_with('[') {
              return;
          }
@@ -309,9 +1184,10 @@
                  }
              }
              'J' => {
-                 let mode = params.first().copied().unwrap_or(0);
-                 if mode == 2 {
-                     self.clear();
+                 match params.first().copied().unwrap_or(0) {
+                     2 => self.clear_viewport(),
+                     3 => self.reset(),
+                     _ => {}
                  }
              }
              'K' => {
@@ -321,6 +1197,12 @@
                  let mode = params.first().copied().unwrap_or(0);
                  let line_idx = self.line_index(self.cursor_y);
                  let blank = Cell::blank(self.fg, self.bg);
+                 let (from, to) = match mode {
+                     0 => (self.cursor_x, self.width),
+                     1 => (0, self.cursor_x + 1),
+                     _ => (0, self.width),
+                 };
+                 self.lines[line_idx].drop_links(from, to);
                  match mode {
                      0 => {
                          for x in self.cursor_x..self.width {
@@ -345,13 +1227,21 @@
                  if params.is_empty() {
                      self.fg = self.default_fg;
                      self.bg = self.default_bg;
+                     self.attrs = Attrs::NONE;
                  } else {
                      for &p in &params {
                          match p {
                              0 => {
                                  self.fg = self.default_fg;
                                  self.bg = self.default_bg;
+                                 self.attrs = Attrs::NONE;
                              }
+                             1 => self.attrs = self.attrs.with(Attrs::BOLD),
+                             4 => self.attrs = self.attrs.with(Attrs::UNDERLINE),
+                             7 => self.attrs = self.attrs.with(Attrs::INVERSE),
+                             22 => self.attrs = self.attrs.without(Attrs::BOLD),
+                             24 => self.attrs = self.attrs.without(Attrs::UNDERLINE),
+                             27 => self.attrs = self.attrs.without(Attrs::INVERSE),
                              30..=37 => self.fg = ansi_color(p - 30, false),
                              40..=47 => self.bg = ansi_color(p - 40, false),
                              90..=97 => self.fg = ansi_color(p - 90, true),
@@ -365,10 +1255,73 @@
          }
      }
 
+     /// Marks the rows under pixel rows `top..bottom` of the terminal for
+     /// drawing again, for when something drawn over them has gone.
+     pub fn invalidate_rows(&mut self, top: usize, bottom: usize) {
+         if self.view_offset > 0 {
+             self.view_dirty = true;
+             return;
+         }
+         let end = bottom.div_ceil(self.char_height).min(self.height);
+         for y in top / self.char_height..end {
+             self.mark_line_dirty(y);
+         }
+     }
+
      pub fn invalidate_all(&mut self) {
          for line in &mut self.lines {
              line.dirty = true;
          }
+         self.view_dirty = true;
+     }
+
+     /// Moves the view `lines` further back into the scrollback, or towards
+     /// the live screen when negative. Returns whether it moved.
+     pub fn scroll_view(&mut self, lines: isize) -> bool {
+         let offset = self
+             .view_offset
+             .saturating_add_signed(lines)
+             .min(self.scrollback.len());
+         if offset == self.view_offset {
+             return false;
+         }
+         self.view_offset = offset;
+         self.invalidate_all();
+         true
+     }
+
+     pub fn scrollback_len(&self) -> usize {
+         self.scrollback.len()
+     }
+
+     /// How many lines the view is scrolled back; 0 at the live screen.
+     pub fn view_offset(&self) -> usize {
+         self.view_offset
+     }
+
+     /// Drops the oldest scrollback lines down to `keep`. Returns about the
+     /// bytes freed.
+     pub fn trim_scrollback(&mut self, keep: usize) -> usize {
+         let excess = self.scrollback.len().saturating_sub(keep);
+         let freed = self
+             .scrollback
+             .drain(..excess)
+             .map(|line| line.cells.capacity() * core::mem::size_of::<Cell>())
+             .sum();
+         if self.view_offset > self.scrollback.len() {
+             self.view_offset = self.scrollback.len();
+             self.invalidate_all();
+         }
+         freed
+     }
+
+     /// The line shown on row `screen_y`, scrollback included.
+     fn view_line(&self, screen_y: usize) -> &Line {
+         if screen_y < self.view_offset {
+             &self.scrollback[self.scrollback.len() - self.view_offset + screen_y]
+         } else {
+             &self.lines[self.line_index(screen_y - self.view_offset)]
+         }
      }
 
      pub fn collect_render(
@@ -390,6 +1343,18 @@
              return;
          }
 
+         if self.view_offset > 0 {
+             // Scrolled back: every row moves at once, and there is no cursor.
+             if self.view_dirty {
+                 for screen_y in 0..max_rows {
+                     let line = self.view_line(screen_y);
+                     self.collect_line(out, screen_y, line, off_x, off_y, max_cols);
+                 }
+                 self.view_dirty = false;
+             }
+             return;
+         }
+
          if self.last_cursor_x < self.width && self.last_cursor_y < self.height {
              self.mark_line_dirty(self.last_cursor_y);
          }
@@ -402,7 +1367,7 @@
                  continue;
              }
 
-             self.collect_line(out, screen_y, line_idx, off_x, off_y, max_cols);
+             self.collect_line(out, screen_y, &self.lines[line_idx], off_x, off_y, max_cols);
              self.lines[line_idx].dirty = false;
          }
 
@@ -422,19 +1387,18 @@
          &self,
          out: &mut RenderList,
          screen_y: usize,
-         line_idx: usize,
+         line: &Line,
          off_x: usize,
          off_y: usize,
          max_cols: usize,
      ) {
-         let line = &self.lines[line_idx];
          let py = off_y + screen_y * self.char_height;
 
          let mut x = 0usize;
          while x < max_cols {
              let cell = line.cells[x];
-             let run_fg = cell.fg;
-             let run_bg = cell.bg;
+             let (run_fg, run_bg) = cell.drawn();
+             let run_attrs = cell.attrs;
 
              let start_x = x;
              let mut run_len = 1usize;
@@ -443,7 +1407,7 @@
 
              while x < max_cols {
                  let c = line.cells[x];
-                 if c.fg == run_fg && c.bg == run_bg {
+                 if c.drawn() == (run_fg, run_bg) && c.attrs == run_attrs {
                      has_text |= c.ch != ' ';
                      run_len += 1;
                      x += 1;
@@ -454,29 +1418,62 @@
 
              let px = off_x + start_x * self.char_width;
 
-             out.push(RenderCommand::fill_rect(
-                 crate::ui_provider::shape::Rect::new(
-                     px,
-                     py,
-                     run_len * self.char_width,
-                     self.char_height,
-                 ),
-                 run_bg,
-             ));
+             if !(self.transparent && run_bg == self.default_bg) {
+                 out.push(RenderCommand::fill_rect(
+                     crate::ui_provider::shape::Rect::new(
+                         px,
+                         py,
+                         run_len * self.char_width,
+                         self.char_height,
+                     ),
+                     run_bg,
+                 ));
+             }
 
              if has_text {
-                 let mut s = String::with_capacity(run_len);
-                 for xi in start_x..start_x + run_len {
-                     s.push(line.cells[xi].ch);
-                 }
+                 let run = &line.cells[start_x..start_x + run_len];
                  out.push(RenderCommand::styled_text(
-                     s,
+                     arena_chars(run.iter().map(|c| c.ch)),
                      px,
                      py,
-                     TextStyle::new(run_fg).with_baseline_offset(FONT_BASELINE_OFFSET),
+                     TextStyle::new(run_fg)
+                         .with_baseline_offset(FONT_BASELINE_OFFSET)
+                         .bold(run_attrs.has(Attrs::BOLD)),
                  ));
              }
+             if run_attrs.has(Attrs::UNDERLINE) {
+                 self.collect_underline(out, off_x, py, start_x, start_x + run_len, run_fg);
+             }
          }
+
+         for link in &line.links {
+             let end = link.end.min(max_cols);
+             if link.start >= end {
+                 continue;
+             }
+             self.collect_underline(out, off_x, py, link.start, end, self.link_fg);
+         }
+     }
+
+     /// A line under columns `from..to` of the row at `py`.
+     fn collect_underline(
+         &self,
+         out: &mut RenderList,
+         off_x: usize,
+         py: usize,
+         from: usize,
+         to: usize,
+         color: Color,
+     ) {
+         out.push(RenderCommand::fill_rect(
+             crate::ui_provider::shape::Rect::new(
+                 off_x + from * self.char_width,
+                 py + self.char_height - 3,
+                 (to - from) * self.char_width,
+                 1,
+             ),
+             color,
+         ));
      }
 
      fn collect_cursor(
@@ -493,9 +1490,31 @@
 
          let px = off_x + self.cursor_x * self.char_width;
          let py = off_y + self.cursor_y * self.char_height;
+         let caret = self.caret;
+
+         if self.block_cursor {
+             let cell = self.lines[self.line_index(self.cursor_y)].cells[self.cursor_x];
+             out.push(RenderCommand::fill_rect(
+                 crate::ui_provider::shape::Rect::new(px, py, self.char_width, self.char_height),
+                 caret,
+             ));
+             if cell.ch != ' ' {
+                 let mut buf = [0u8; 4];
+                 out.push(RenderCommand::styled_text(
+                     arena_str(cell.ch.encode_utf8(&mut buf)),
+                     px,
+                     py,
+                     TextStyle::new(cell.drawn().1)
+                         .with_baseline_offset(FONT_BASELINE_OFFSET)
+                         .bold(cell.attrs.has(Attrs::BOLD)),
+                 ));
+             }
+             return;
+         }
+
          let inset = 2usize;
          let w = (self.char_width.saturating_sub(inset * 2)).max(1);
-         let h = 2usize;
+         let h = self.caret_height;
 
          out.push(RenderCommand::fill_rect(
              crate::ui_provider::shape::Rect::new(
@@ -504,9 +1523,21 @@
                  w,
                  h,
              ),
-             Color::from_hex(0xCCCCCC),
+             caret,
          ));
      }
+
+     #[cfg(test)]
+     pub(crate) fn row_text(&self, y: usize) -> String {
+         let line = self.view_line(y);
+         let s: String = line.cells.iter().map(|c| c.ch).collect();
+         String::from(s.trim_end())
+     }
+
+     #[cfg(test)]
+     pub(crate) fn cursor_pos(&self) -> (usize, usize) {
+         (self.cursor_x, self.cursor_y)
+     }
  }
 
  impl Write for Terminal {
@@ -521,22 +1552,35 @@
          Self {
              lines: self.lines.clone(),
              top_line: self.top_line,
+             scrollback: self.scrollback.clone(),
+             view_offset: self.view_offset,
+             view_dirty: self.view_dirty,
              width: self.width,
              height: self.height,
              cursor_x: self.cursor_x,
              cursor_y: self.cursor_y,
-             prompt_start_x: self.prompt_start_x,
-             prompt_start_y: self.prompt_start_y,
+             scrolled: self.scrolled,
+             prompt_x: self.prompt_x,
+             prompt_line: self.prompt_line,
              last_cursor_x: self.last_cursor_x,
              last_cursor_y: self.last_cursor_y,
              fg: self.fg,
              bg: self.bg,
+             attrs: self.attrs,
              default_fg: self.default_fg,
              default_bg: self.default_bg,
+             transparent: self.transparent,
              char_width: self.char_width,
              char_height: self.char_height,
              escape_buffer: self.escape_buffer.clone(),
-             in_escape: self.in_escape,
+             escape: self.escape,
+             block_cursor: self.block_cursor,
+             link_fg: self.link_fg,
+             open_link: self.open_link.clone(),
+             caret: self.caret,
+             caret_height: self.caret_height,
+             bell_pending: self.bell_pending,
+             charset: self.charset,
          }
      }
  }
@@ -562,3 +1606,370 @@
          _ => Color::WHITE,
      }
  }
+
+ // ── tests ─────────────────────────────────────────────────────────────────────
+
+ #[cfg(test)]
+ mod tests {
+     use super::*;
+
+     fn term(cols: usize, rows: usize) -> Terminal {
+         Terminal::new(cols, rows, &Theme::dark_modern())
+     }
+
+     /// Pixel in the middle of cell `(x, y)`.
+     fn at(x: usize, y: usize) -> (usize, usize) {
+         (x * 10 + 5, y * 20 + 10)
+     }
+
+     #[test_case]
+     fn links_resolve_by_cell_and_use_the_accent() {
+         let mut t = term(20, 4);
+         t.write("see ");
+         t.write_link("meminfo", "meminfo");
+         t.write(&format_link("ls", "ls -l"));
+         t.write(" x");
+         let (px, py) = at(4, 0);
+         assert_eq!(t.link_at(px, py), Some("meminfo"));
+         let (px, py) = at(12, 0);
+         assert_eq!(t.link_at(px, py), Some("ls -l"));
+         let (px, py) = at(3, 0);
+         assert_eq!(t.link_at(px, py), None);
+         assert_eq!(t.row_text(0), "see meminfols x");
+         assert_eq!(t.lines[0].cells[4].fg, Theme::dark_modern().accent);
+         assert_eq!(t.lines[0].cells[15].fg, Theme::dark_modern().text);
+     }
+
+     #[test_case]
+     fn link_is_cut_at_the_wrap() {
+         let mut t = term(8, 4);
+         t.write("abcde");
+         t.write_link("meminfo", "meminfo");
+         let (px, py) = at(7, 0);
+         assert_eq!(t.link_at(px, py), Some("meminfo"));
+         let (px, py) = at(0, 1);
+         assert_eq!(t.link_at(px, py), None);
+         assert_eq!(t.row_text(1), "info");
+     }
+
+     #[test_case]
+     fn tab_blanks_the_cells_it_skips() {
+         let mut t = term(20, 2);
+         t.write(&"X".repeat(20));
+         t.write("\rabc\tdef");
+         assert_eq!(t.row_text(0), "abc     defXXXXXXXXX");
+         let blank = Cell::blank(t.fg, t.bg);
+         assert!(t.lines[0].cells[3..8].iter().all(|&c| c == blank));
+         assert_eq!(t.cursor_pos(), (11, 0));
+     }
+
+     #[test_case]
+     fn progress_line_leaves_no_residue() {
+         let mut t = term(20, 2);
+         t.write("> ");
+         t.write(&progress_line("copying 10/100"));
+         assert_eq!(t.row_text(0), "copying 10/100");
+         t.write(&progress_line("done"));
+         assert_eq!(t.row_text(0), "done");
+         assert_eq!(t.cursor_pos(), (4, 0));
+         assert_eq!(t.row_text(1), "");
+     }
+
+     #[test_case]
+     fn overwriting_or_scrolling_drops_links() {
+         let mut t = term(10, 2);
+         t.write_link("help", "help");
+         t.write("\r");
+         t.write("X");
+         assert_eq!(t.link_at(at(2, 0).0, at(2, 0).1), None);
+
+         t.write("\n");
+         t.write_link("info", "info");
+         assert!(t.link_at(at(0, 1).0, at(0, 1).1).is_some());
+         t.write("\n");
+         // the link moved up one row with its line
+         assert_eq!(t.link_at(at(0, 0).0, at(0, 0).1), Some("info"));
+         t.write("\n");
+         assert_eq!(t.link_at(at(0, 0).0, at(0, 0).1), None);
+     }
+
+     #[test_case]
+     fn bel_rings_once_and_not_inside_a_link() {
+         let mut t = term(20, 2);
+         t.write("\x1b]8;;info\x07x\x1b]8;;\x07");
+         assert!(!t.take_bell());
+         t.write("a\x07\x07b");
+         assert!(t.take_bell());
+         assert!(!t.take_bell());
+         assert_eq!(t.lines[0].cells[1].ch, 'a');
+         assert_eq!(t.lines[0].cells[2].ch, 'b');
+     }
+
+     #[test_case]
+     fn special_graphics_draw_a_box() {
+         let mut t = term(8, 4);
+         t.write("\x1b(0lqqk\r\nx  x\r\nmqqj\x1b(B q");
+         let row = |y: usize| -> String { t.lines[y].cells[..5].iter().map(|c| c.ch).collect() };
+         assert_eq!(row(0), "┌──┐ ");
+         assert_eq!(row(1), "│  │ ");
+         assert_eq!(row(2), "└──┘ ");
+         // back in ASCII after `ESC ( B`
+         assert_eq!(t.lines[2].cells[5].ch, 'q');
+         assert_eq!(Charset::Ascii.translate('q'), 'q');
+     }
+
+     #[test_case]
+     fn backspace_across_wraps_and_scrolls_stops_at_the_prompt() {
+         // Every line from the oldest in the scrollback down, as cells
+         let all_cells = |t: &Terminal| -> Vec<Vec<Cell>> {
+             let screen = (0..t.height).map(|y| t.lines[t.line_index(y)].cells.clone());
+             t.scrollback.iter().map(|l| l.cells.clone()).chain(screen).collect()
+         };
+         let mut t = term(10, 4);
+         for n in 0..6 {
+             t.write(&alloc::format!("out {}\n", n));
+         }
+         t.write("> ");
+         t.set_prompt_start();
+         let before = all_cells(&t);
+         // out 0..5 and the prompt, three of them in the scrollback
+         assert_eq!(before.len(), 7);
+
+         // Echoed one key at a time: wraps twice and scrolls twice
+         for _ in 0..25 {
+             t.write("x");
+         }
+         for _ in 0..40 {
+             t.write("\x08");
+         }
+         let after = all_cells(&t);
+         assert_eq!(after[..7], before[..7]);
+         assert!(after[7..].iter().flatten().all(|c| c.ch == ' '));
+         assert_eq!(t.cursor_pos(), (2, 1));
+
+         // Long enough to push the prompt off the screen: backspace stops
+         // at the top row, and the output and prompt above stay as they were
+         for _ in 0..50 {
+             t.write("y");
+         }
+         for _ in 0..60 {
+             t.write("\x08");
+         }
+         assert_eq!(t.cursor_pos(), (0, 0));
+         let after = all_cells(&t);
+         assert_eq!(after[..6], before[..6]);
+         assert_eq!(after[6][..2], before[6][..2]);
+     }
+
+     #[test_case]
+     fn malformed_csi_and_bare_esc_do_not_wedge_the_parser() {
+         let mut t = term(20, 4);
+         let before = escape_errors();
+
+         // Thousands of parameters: cut at the cap and written as text
+         t.write("\x1b[");
+         t.write(&"1;".repeat(5000));
+         assert!(t.escape_buffer.capacity() <= 2 * MAX_ESCAPE_LEN);
+         t.write("m\r\n\x1b[31mred\x1b[0m");
+         assert_eq!(t.row_text(3), "red");
+         assert_eq!(t.lines[t.line_index(3)].cells[0].fg, ansi_color(1, false));
+         assert_eq!(t.cursor_pos(), (3, 3));
+
+         // Truncated by a newline, then a bare ESC
+         t.reset();
+         t.write("\x1b[12\nhello\x1bx!\x1b");
+         t.write("\r\n\x1b[2;3Hz");
+         assert_eq!(t.row_text(1), "hezlox!");
+         assert_eq!(t.cursor_pos(), (3, 1));
+         assert_eq!(t.escape, Escape::Ground);
+
+         let after = escape_errors();
+         assert!(after.overflowed > before.overflowed);
+         assert!(after.newline_resets > before.newline_resets);
+         assert!(after.bare_escapes > before.bare_escapes);
+         assert!(escape_report().starts_with("Escapes: "));
+     }
+
+     #[test_case]
+     fn osc_sequences_end_on_bel_or_st_and_are_bounded() {
+         let mut t = term(20, 2);
+         t.write("\x1b]0;window title\x07a\x1b]2;other\x1b\\b");
+         assert_eq!(t.row_text(0), "ab");
+
+         // A link closed with ST instead of BEL
+         t.write("\x1b]8;;ls -l\x1b\\ls\x1b]8;;\x1b\\.");
+         let (px, py) = at(3, 0);
+         assert_eq!(t.link_at(px, py), Some("ls -l"));
+         assert_eq!(t.row_text(0), "abls.");
+
+         // Far past the cap: skipped, and nothing after it is lost
+         t.write("\x1b]0;");
+         t.write(&"t".repeat(10_000));
+         assert!(t.escape_buffer.capacity() <= 2 * MAX_OSC_LEN);
+         t.write("\x07done");
+         assert_eq!(t.row_text(0), "abls.done");
+         assert_eq!(t.cursor_pos(), (9, 0));
+     }
+
+     #[test_case]
+     fn clear_keeps_the_scrollback_and_reset_wipes_it() {
+         let mut t = term(10, 5);
+         for n in 0..30 {
+             t.write(&alloc::format!("line {}\n", n));
+         }
+         t.clear_viewport();
+         assert_eq!(t.cursor_pos(), (0, 0));
+         assert!((0..5).all(|y| t.row_text(y).is_empty()));
+
+         // Every line is still there, oldest first
+         assert_eq!(t.scrollback_len(), 30);
+         assert!(t.scroll_view(100));
+         assert_eq!(t.view_offset(), 30);
+         for page in 0..6 {
+             for y in 0..5 {
+                 assert_eq!(t.row_text(y), alloc::format!("line {}", page * 5 + y));
+             }
+             t.scroll_view(-5);
+         }
+         assert_eq!(t.view_offset(), 0);
+
+         // Output brings the view back to the live screen
+         t.write("> ");
+         assert_eq!(t.view_offset(), 0);
+         assert_eq!(t.row_text(0), ">");
+
+         t.write("\x1b[2J");
+         assert_eq!(t.scrollback_len(), 31);
+         t.write("\x1b[3J");
+         assert_eq!(t.scrollback_len(), 0);
+         assert!(!t.scroll_view(1));
+         assert!((0..5).all(|y| t.row_text(y).is_empty()));
+     }
+
+     /// Every row, scrollback first, as what `resize` must keep.
+     fn rows(t: &Terminal) -> Vec<(Vec<Cell>, Vec<LinkSpan>, bool)> {
+         let screen = (0..t.height).map(|y| &t.lines[t.line_index(y)]);
+         t.scrollback
+             .iter()
+             .chain(screen)
+             .map(|line| (line.cells.clone(), line.links.clone(), line.wrapped))
+             .collect()
+     }
+
+     #[test_case]
+     fn reflow_round_trip_restores_every_cell() {
+         let mut t = term(100, 10);
+         let red = ansi_color(1, false);
+         let text: String = (0..150).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+         // Red across both the 60- and the 100-column cut
+         t.write(&text[..40]);
+         t.write("\x1b[31m");
+         t.write(&text[40..130]);
+         t.write("\x1b[0m");
+         t.write(&text[130..]);
+         t.write("\n");
+         t.write(&"x".repeat(100));
+         t.write("\nshort\n");
+         t.write_link(&"L".repeat(70), "link");
+         t.write(&"y".repeat(180));
+         t.write("\n$ ");
+         t.set_prompt_start();
+         t.write("typed");
+         let before = rows(&t);
+         assert_eq!(t.cursor_pos(), (7, 7));
+
+         t.resize(60, 10);
+         // 3 + 2 + 1 + 5 + 1 rows; the cursor stays two rows off the bottom
+         assert_eq!(t.scrollback_len(), 4);
+         assert_eq!(t.cursor_pos(), (7, 7));
+         assert_eq!(t.prompt_start(), (2, 7));
+         assert_eq!(t.row_text(0), "x".repeat(40));
+         assert_eq!(t.row_text(1), "short");
+         assert_eq!(t.row_text(7), "$ typed");
+         assert_eq!(t.scrollback[0].cells[59], Cell::new('h', red, t.default_bg));
+         assert_eq!(t.scrollback[1].cells[10], Cell::new('s', red, t.default_bg));
+         assert_eq!(t.scrollback[2].cells[10].fg, t.default_fg);
+         assert!(t.scrollback[0].wrapped && !t.scrollback[2].wrapped);
+         // The link is cut at the new wrap, like one written there
+         assert_eq!(t.link_at(at(5, 3).0, at(5, 3).1), Some("link"));
+         assert_eq!(t.link_at(at(15, 3).0, at(15, 3).1), None);
+
+         t.resize(100, 10);
+         assert!(rows(&t) == before);
+         assert_eq!(t.cursor_pos(), (7, 7));
+         assert_eq!(t.prompt_start(), (2, 7));
+         // Backspace still stops at the prompt
+         for _ in 0..10 {
+             t.write("\x08");
+         }
+         assert_eq!(t.row_text(7), "$");
+     }
+
+     #[test_case]
+     fn reflow_keeps_the_cursor_on_screen_and_the_escape_going() {
+         let mut t = term(60, 3);
+         t.write(&"z".repeat(500));
+         t.write("\x1b[3");
+         // 13 rows at 40 columns, all but the last three in the scrollback
+         t.resize(40, 3);
+         assert_eq!(t.scrollback_len(), 10);
+         assert_eq!(t.cursor_pos(), (20, 2));
+         t.write("1mR");
+         let cell = t.lines[t.line_index(2)].cells[20];
+         assert_eq!(cell, Cell::new('R', ansi_color(1, false), t.default_bg));
+
+         t.resize(60, 3);
+         t.resize(40, 3);
+         t.resize(60, 3);
+         assert_eq!(t.row_text(2), alloc::format!("{}R", "z".repeat(20)));
+         assert_eq!(t.cursor_pos(), (21, 2));
+         assert_eq!(t.scrollback_len(), 6);
+     }
+
+     #[test_case]
+     fn attributes_mix_with_colours_and_cut_the_runs() {
+         let mut t = term(10, 2);
+         let red = ansi_color(1, false);
+         let bg = t.default_bg;
+         t.write("\x1b[1;31mA\x1b[4mB\x1b[22;7mC\x1b[0mD");
+         let cells = &t.lines[t.line_index(0)].cells;
+         let bold_underline = Attrs::BOLD.with(Attrs::UNDERLINE);
+         assert_eq!(cells[0], Cell::new('A', red, bg).with_attrs(Attrs::BOLD));
+         assert_eq!(cells[1], Cell::new('B', red, bg).with_attrs(bold_underline));
+         let inverse = Attrs::UNDERLINE.with(Attrs::INVERSE);
+         assert_eq!(cells[2], Cell::new('C', red, bg).with_attrs(inverse));
+         assert_eq!(cells[2].drawn(), (bg, red));
+         assert_eq!(cells[3], Cell::new('D', t.default_fg, bg));
+
+         let mut out = RenderList::new();
+         t.collect_render_full(&mut out, 0, 0);
+         let texts: Vec<(String, Color, bool)> = out
+             .iter()
+             .filter_map(|cmd| match cmd {
+                 RenderCommand::Text { text, y: 0, style, .. } => {
+                     Some((String::from(text.trim_end()), style.fg, style.bold))
+                 }
+                 _ => None,
+             })
+             .collect();
+         let expected = [
+             ("A", red, true),
+             ("B", red, true),
+             ("C", bg, false),
+             ("D", t.default_fg, false),
+         ];
+         assert_eq!(texts.len(), expected.len());
+         for ((text, fg, bold), (want, want_fg, want_bold)) in texts.iter().zip(expected) {
+             assert_eq!((text.as_str(), *fg, *bold), (want, want_fg, want_bold));
+         }
+
+         let underlines: Vec<(usize, Color)> = out
+             .iter()
+             .filter_map(|cmd| match cmd {
+                 RenderCommand::FillRect { rect, color } if rect.h == 1 => Some((rect.x, *color)),
+                 _ => None,
+             })
+             .collect();
+         assert_eq!(underlines, [(10, red), (20, bg)]);
+     }
+ }
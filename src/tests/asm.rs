@@ -1,7 +1,8 @@
+use crate::kcore::interrupts::guard;
 use crate::memory::{mmap::sys_mmap, munmap::sys_munmap};
 use crate::{log_error, log_info, println};
 use alloc::alloc::{alloc, dealloc};
-use alloc::{string::String, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
 use core::alloc::Layout;
 
 const MAX_CODE_SIZE: usize = 4096;
@@ -38,32 +39,31 @@ impl AsmExecutor {
                 let result = unsafe {
                     let dst = virt_addr as *mut u8;
                     core::ptr::copy_nonoverlapping(code.as_ptr(), dst, code.len());
-                    execute_code(dst as *const ())
+                    crate::memory::jit::note_entry(virt_addr as u64);
+                    let base = virt_addr as u64;
+                    guard::call(base, base..base + code.len() as u64, [0; 4])
                 };
                 let _ = sys_munmap(virt_addr, map_size);
-                log_info!("ASM: Result = {}", result);
-                Ok(result)
+                match result {
+                    Ok(result) => {
+                        log_info!("ASM: Result = {}", result);
+                        Ok(result)
+                    }
+                    Err(fault) => {
+                        log_error!("ASM: vector {} at {:#x}", fault.vector, fault.rip);
+                        Err(format!("faulted: vector {} at {:#x}", fault.vector, fault.rip))
+                    }
+                }
             }
             Err(e) => {
-                log_error!(
-                    "ASM: mmap failed: {:?} — cannot execute on heap (NX bit)",
-                    e
-                );
-                Err(String::from(
-                    "mmap failed; heap execution blocked by NX bit",
-                ))
+                // The heap is NX, so there is nowhere else to run it.
+                log_error!("ASM: mmap failed: {:?}", e);
+                Err(format!("mmap failed: {}", e))
             }
         }
     }
 }
 
-extern "C" fn execute_code(code_ptr: *const ()) -> u64 {
-    unsafe {
-        let code_fn: extern "C" fn() -> u64 = core::mem::transmute(code_ptr);
-        code_fn()
-    }
-}
-
 pub struct AsmProgram;
 
 impl AsmProgram {
@@ -90,4 +90,135 @@ impl AsmProgram {
         code.push(0xc3);
         code
     }
+
+    /// `return_argument` with the value kept as data after the code and
+    /// loaded rip-relative, so it runs wherever it is copied:
+    /// `mov rax, [rip + 1]; ret; dq value`
+    pub fn return_argument_pic(value: u64) -> Vec<u8> {
+        let mut code = alloc::vec![0x48, 0x8b, 0x05, 0x01, 0x00, 0x00, 0x00, 0xc3];
+        code.extend_from_slice(&value.to_le_bytes());
+        code
+    }
+
+    /// The same with the load base passed in rdi, as the first argument of
+    /// an `extern "C" fn(base: u64) -> u64`:
+    /// `mov rax, [rdi + 8]; ret; int3 x3; dq value`
+    pub fn return_argument_based(value: u64) -> Vec<u8> {
+        let mut code = alloc::vec![0x48, 0x8b, 0x47, 0x08, 0xc3, 0xcc, 0xcc, 0xcc];
+        code.extend_from_slice(&value.to_le_bytes());
+        code
+    }
+
+    /// The same through an absolute address, which only works once the
+    /// image is relocated for its base with `memory::relocate`:
+    /// `mov rax, imm64; mov rax, [rax]; ret; int3 x2; dq value`. The
+    /// immediate starts as the offset of the value.
+    pub fn return_argument_absolute(value: u64) -> Relocatable {
+        let mut code = alloc::vec![0x48, 0xb8];
+        code.extend_from_slice(&16u64.to_le_bytes());
+        code.extend_from_slice(&[0x48, 0x8b, 0x00, 0xc3, 0xcc, 0xcc]);
+        code.extend_from_slice(&value.to_le_bytes());
+        Relocatable {
+            code,
+            fixups: alloc::vec![2],
+        }
+    }
+
+    /// Fills RGBA8 pixels with a gradient, red across (shifted by
+    /// `frame`) and green down, both wrapping at 256:
+    /// `extern "C" fn(pixels: *mut u32, w: usize, h: usize, frame: u32)`.
+    pub fn gradient() -> &'static [u8] {
+        &[
+            0x45, 0x31, 0xc0, // xor r8d, r8d        ; y = 0
+            0x49, 0x39, 0xd0, // row: cmp r8, rdx
+            0x73, 0x33, // jae done
+            0x45, 0x31, 0xc9, // xor r9d, r9d        ; x = 0
+            0x49, 0x39, 0xf1, // col: cmp r9, rsi
+            0x73, 0x26, // jae next_row
+            0x44, 0x89, 0xc8, // mov eax, r9d
+            0x01, 0xc8, // add eax, ecx
+            0x0f, 0xb6, 0xc0, // movzx eax, al        ; red
+            0x45, 0x89, 0xc2, // mov r10d, r8d
+            0x45, 0x0f, 0xb6, 0xd2, // movzx r10d, r10b
+            0x41, 0xc1, 0xe2, 0x08, // shl r10d, 8       ; green
+            0x44, 0x09, 0xd0, // or eax, r10d
+            0x0d, 0x00, 0x00, 0x80, 0xff, // or eax, 0xff800000 ; blue, alpha
+            0x89, 0x07, // mov [rdi], eax
+            0x48, 0x83, 0xc7, 0x04, // add rdi, 4
+            0x49, 0xff, 0xc1, // inc r9
+            0xeb, 0xd5, // jmp col
+            0x49, 0xff, 0xc0, // next_row: inc r8
+            0xeb, 0xc8, // jmp row
+            0xc3, // done: ret
+        ]
+    }
+}
+
+/// Code with absolute immediates still to be patched for its base.
+pub struct Relocatable {
+    pub code: Vec<u8>,
+    /// Offsets of the immediates, as `memory::relocate` takes them.
+    pub fixups: Vec<usize>,
+}
+
+impl From<Vec<u8>> for Relocatable {
+    /// Position-independent code: nothing to patch.
+    fn from(code: Vec<u8>) -> Self {
+        Self {
+            code,
+            fixups: Vec::new(),
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn position_independent_programs_keep_their_bytes() {
+        let value = 0x1122_3344_5566_7788u64;
+        let pic = AsmProgram::return_argument_pic(value);
+        assert_eq!(pic[..8], [0x48, 0x8b, 0x05, 0x01, 0x00, 0x00, 0x00, 0xc3]);
+        assert_eq!(pic[8..], value.to_le_bytes());
+
+        // the displacement lands on the value from the end of the load
+        let based = AsmProgram::return_argument_based(value);
+        assert_eq!(based[3] as usize, 8);
+        assert_eq!(based[8..], value.to_le_bytes());
+    }
+
+    #[test_case]
+    fn absolute_program_is_patched_for_its_base() {
+        for base in [0x40_0000u64, 0x50_0000, 0x1ff0_0000] {
+            let mut image = AsmProgram::return_argument_absolute(7);
+            crate::memory::relocate(&mut image.code, base, &image.fixups).unwrap();
+            let mut want = alloc::vec![0x48, 0xb8];
+            want.extend_from_slice(&(base + 16).to_le_bytes());
+            want.extend_from_slice(&[0x48, 0x8b, 0x00, 0xc3, 0xcc, 0xcc]);
+            want.extend_from_slice(&7u64.to_le_bytes());
+            assert_eq!(image.code, want);
+        }
+    }
+
+    #[test_case]
+    fn gradient_program_fills_rows() {
+        let code = AsmProgram::gradient();
+        let addr = sys_mmap(0, PAGE_SIZE, PROT_READ | PROT_WRITE | PROT_EXEC, 0, 0, 0).unwrap();
+        let mut pixels = [0u32; 6];
+        unsafe {
+            core::ptr::copy_nonoverlapping(code.as_ptr(), addr as *mut u8, code.len());
+            crate::memory::jit::note_entry(addr as u64);
+            let fill: extern "C" fn(*mut u32, usize, usize, u32) = core::mem::transmute(addr);
+            fill(pixels.as_mut_ptr(), 3, 2, 254);
+        }
+        let _ = sys_munmap(addr, PAGE_SIZE);
+        // red wraps past 255, green is the row
+        let want = [[254, 0], [255, 0], [0, 0], [254, 1], [255, 1], [0, 1]];
+        for (px, [r, g]) in pixels.iter().zip(want) {
+            assert_eq!(px.to_le_bytes(), [r, g, 0x80, 0xff]);
+        }
+    }
 }
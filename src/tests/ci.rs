@@ -0,0 +1,70 @@
+//! # CI Test Harness
+//!
+//! Built with `--features ci-test`, the kernel runs the `test_env` tests
+//! right after init, prints the results over serial and exits QEMU through
+//! the `isa-debug-exit` device:
+//!
+//! ```text
+//! qemu-system-x86_64 ... -serial stdio -display none \
+//!     -device isa-debug-exit,iobase=0xf4,iosize=0x04
+//! echo $?   # 33 = pass, 35 = fail
+//! ```
+//!
+//! QEMU exits with `(code << 1) | 1`, so the codes below never collide
+//! with QEMU's own exit statuses 0 and 1.
+//!
+//! The bootloader passes no command line, so the test selection is fixed
+//! at build time: `KERNEL_SELFTEST=memory,asm cargo build --features
+//! ci-test` runs only tests matching those tags or names (as `test run`).
+
+#[cfg(feature = "ci-test")]
+use crate::{println, tests::test_env};
+use x86_64::instructions::port::Port;
+
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+/// Halts forever if the VM was started without `isa-debug-exit`.
+pub fn exit_qemu(code: QemuExitCode) -> ! {
+    unsafe {
+        Port::<u32>::new(ISA_DEBUG_EXIT_PORT).write(code as u32);
+    }
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+#[cfg(feature = "ci-test")]
+pub fn run_and_exit() -> ! {
+    let patterns: alloc::vec::Vec<&str> = option_env!("KERNEL_SELFTEST")
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    if patterns.is_empty() {
+        println!("CI: running all tests");
+    } else {
+        println!("CI: running tests matching {}", patterns.join(","));
+    }
+    test_env::reset_failures();
+    let output = test_env::run_tests(&patterns, false);
+    for line in output.lines() {
+        println!("{}", line);
+    }
+
+    let failures = test_env::failure_count();
+    if failures == 0 {
+        println!("CI: PASS");
+        exit_qemu(QemuExitCode::Success)
+    } else {
+        println!("CI: FAIL ({} failures)", failures);
+        exit_qemu(QemuExitCode::Failed)
+    }
+}
@@ -0,0 +1,157 @@
+//! # Kernel Assertions
+//!
+//! `kassert!`, `kassert_eq!` and `kassert_ne!` print the failed expression,
+//! the values involved and the location over serial, then fail:
+//! - with the `ci-test` feature, QEMU exits through `isa-debug-exit` with
+//!   the failure code straight away;
+//! - otherwise they panic, which the `cargo test` runner reports as a
+//!   failed test and a normal kernel treats as any other panic.
+//!
+//! Unlike `debug_assert!` they stay enabled in release builds, so they can
+//! double as runtime sanity checks.
+//!
+//! The tests the shell runs (`test_env`) must not take the kernel down, so
+//! they use `kassert_eq!(out; left, right)`: on failure it prints the same
+//! report, records the failure in the `out` string and the failure count,
+//! and returns `out` from the enclosing function.
+
+use crate::println;
+use core::fmt;
+
+#[cold]
+#[allow(dead_code)] // the shell-run tests record instead; kept for sanity checks
+pub fn fail(msg: fmt::Arguments, file: &str, line: u32) -> ! {
+    println!("KASSERT FAILED at {}:{}", file, line);
+    println!("  {}", msg);
+
+    #[cfg(feature = "ci-test")]
+    crate::tests::ci::exit_qemu(crate::tests::ci::QemuExitCode::Failed);
+
+    #[cfg(not(feature = "ci-test"))]
+    panic!("kassert failed at {}:{}", file, line);
+}
+
+/// `fail` for a shell-run test: reports the failure over serial and
+/// records it in `out` without stopping anything.
+#[cold]
+pub fn record(out: &mut alloc::string::String, msg: fmt::Arguments, file: &str, line: u32) {
+    println!("KASSERT FAILED at {}:{}", file, line);
+    println!("  {}", msg);
+    let msg = alloc::format!("{} at {}:{}", msg, file, line);
+    crate::tests::test_env::record_failure(out, &msg);
+}
+
+#[macro_export]
+macro_rules! kassert {
+    ($cond:expr $(,)?) => {
+        if !$cond {
+            $crate::tests::kassert::fail(
+                format_args!("kassert!({})", stringify!($cond)),
+                file!(),
+                line!(),
+            );
+        }
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::tests::kassert::fail(
+                format_args!("kassert!({}): {}", stringify!($cond), format_args!($($arg)+)),
+                file!(),
+                line!(),
+            );
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! kassert_eq {
+    ($out:ident; $left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::tests::kassert::record(
+                        &mut $out,
+                        format_args!(
+                            "kassert_eq!({}, {}): left {:?}, right {:?}",
+                            stringify!($left),
+                            stringify!($right),
+                            left,
+                            right
+                        ),
+                        file!(),
+                        line!(),
+                    );
+                    return $out;
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::tests::kassert::fail(
+                        format_args!(
+                            "kassert_eq!({}, {})\n    left: {:?}\n   right: {:?}",
+                            stringify!($left),
+                            stringify!($right),
+                            left,
+                            right
+                        ),
+                        file!(),
+                        line!(),
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! kassert_ne {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left == *right {
+                    $crate::tests::kassert::fail(
+                        format_args!(
+                            "kassert_ne!({}, {})\n    both: {:?}",
+                            stringify!($left),
+                            stringify!($right),
+                            left
+                        ),
+                        file!(),
+                        line!(),
+                    );
+                }
+            }
+        }
+    };
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_env::failure_count;
+    use alloc::string::String;
+
+    fn compare(a: u32, b: u32) -> String {
+        let mut out = String::from("ran\n");
+        kassert_eq!(out; a, b);
+        out.push_str("matched\n");
+        out
+    }
+
+    #[test_case]
+    fn a_recorded_failure_returns_the_output_so_far() {
+        let before = failure_count();
+        assert_eq!(compare(1, 1), "ran\nmatched\n");
+        assert_eq!(failure_count(), before);
+
+        let failed = compare(1, 2);
+        assert!(failed.starts_with("ran\nFAIL: kassert_eq!(a, b): left 1, right 2 at "));
+        assert!(!failed.contains("matched"));
+        assert_eq!(failure_count(), before + 1);
+    }
+}
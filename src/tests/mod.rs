@@ -1,2 +1,7 @@
 pub mod asm;
+#[cfg(any(test, feature = "ci-test"))]
+pub mod ci;
+pub mod kassert;
+#[cfg(test)]
+pub mod runner;
 pub mod test_env;
@@ -0,0 +1,117 @@
+//! # Test Runner
+//!
+//! `cargo test` harness built on `custom_test_frameworks`. Every
+//! `#[test_case]` item is handed to `test_runner`, which prints
+//! `name ... ok`/`FAIL` over serial and exits QEMU through `isa-debug-exit`
+//! (see `tests::ci`).
+//!
+//! There is no unwinding, so a panicking test can't return to the runner.
+//! The test panic handler reports FAIL and calls back into `run_from` with
+//! the next index; the panicked frames below it are simply abandoned, which
+//! is fine for a run that always ends in an exit.
+//!
+//! A test that means to panic calls `expect_panic` first; the handler then
+//! counts the panic as a pass if its message says what was expected, and
+//! a test that returns without panicking fails.
+//!
+//! Test targets ignore `panic = "abort"` from the profile, so build them
+//! with `cargo test -Zpanic-abort-tests`.
+
+use crate::println;
+use crate::tests::ci::{exit_qemu, QemuExitCode};
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Once;
+
+pub trait Testable: Sync {
+    fn name(&self) -> &'static str;
+    fn run(&self);
+}
+
+impl<T: Fn() + Sync> Testable for T {
+    fn name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+
+    fn run(&self) {
+        self()
+    }
+}
+
+static TESTS: Once<&'static [&'static dyn Testable]> = Once::new();
+/// Index of the running test plus one; 0 while no test is running.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static FAILED: AtomicUsize = AtomicUsize::new(0);
+/// What the running test's panic message should contain, if it should panic.
+static EXPECTED: spin::Mutex<Option<&'static str>> = spin::Mutex::new(None);
+
+/// Makes the running test pass only by panicking with a message that
+/// contains `message`.
+pub fn expect_panic(message: &'static str) {
+    *EXPECTED.lock() = Some(message);
+}
+
+pub fn test_runner(tests: &[&dyn Testable]) {
+    // The harness passes a promoted slice of statics, so it lives forever.
+    let tests: &'static [&'static dyn Testable] = unsafe { core::mem::transmute(tests) };
+    TESTS.call_once(|| tests);
+    println!("running {} tests", tests.len());
+    run_from(0)
+}
+
+fn run_from(start: usize) -> ! {
+    let tests = TESTS.get().copied().unwrap_or(&[]);
+    for (idx, test) in tests.iter().enumerate().skip(start) {
+        CURRENT.store(idx + 1, Ordering::SeqCst);
+        test.run();
+        if let Some(message) = EXPECTED.lock().take() {
+            FAILED.fetch_add(1, Ordering::SeqCst);
+            println!("{} ... FAIL", test.name());
+            println!("    did not panic with \"{}\"", message);
+            continue;
+        }
+        println!("{} ... ok", test.name());
+    }
+    CURRENT.store(0, Ordering::SeqCst);
+
+    let failed = FAILED.load(Ordering::SeqCst);
+    println!(
+        "test result: {} passed; {} failed",
+        tests.len() - failed,
+        failed
+    );
+    exit_qemu(if failed == 0 {
+        QemuExitCode::Success
+    } else {
+        QemuExitCode::Failed
+    })
+}
+
+/// Called by the `cfg(test)` panic handler.
+pub fn on_panic(info: &PanicInfo) -> ! {
+    let current = CURRENT.load(Ordering::SeqCst);
+    let Some(test) = current
+        .checked_sub(1)
+        .and_then(|idx| TESTS.get().and_then(|tests| tests.get(idx)))
+    else {
+        println!("panic outside of a test: {}", info);
+        exit_qemu(QemuExitCode::Failed)
+    };
+
+    // The panicking test may hold it, but only a panic in here would.
+    let expected = EXPECTED.try_lock().and_then(|mut expected| expected.take());
+    if let Some(expected) = expected {
+        let mut message = crate::memory::oom::Report::new();
+        let _ = write!(message, "{}", info.message());
+        if message.as_str().contains(expected) {
+            println!("{} ... ok", test.name());
+            run_from(current)
+        }
+    }
+
+    FAILED.fetch_add(1, Ordering::SeqCst);
+    println!("{} ... FAIL", test.name());
+    println!("    {}", info);
+    run_from(current)
+}
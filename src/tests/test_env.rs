@@ -1,13 +1,32 @@
-use crate::println;
-use alloc::string::String;
+use crate::{kassert_eq, println};
+use alloc::{format, string::String, vec::Vec};
 use core::sync::atomic::{AtomicUsize, Ordering};
 use x86_64::structures::paging::{FrameAllocator, Mapper, Page, PageTableFlags, Translate};
 use x86_64::VirtAddr;
 
 static TEST_EXECUTION_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Failures recorded since the last `reset_failures`, for the CI harness.
+static TEST_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn reset_failures() {
+    TEST_FAILURES.store(0, Ordering::Relaxed);
+}
+
+pub fn failure_count() -> usize {
+    TEST_FAILURES.load(Ordering::Relaxed)
+}
+
+pub(crate) fn record_failure(result: &mut String, msg: &str) {
+    TEST_FAILURES.fetch_add(1, Ordering::Relaxed);
+    result.push_str("FAIL: ");
+    result.push_str(msg);
+    result.push('\n');
+}
 
 pub fn test_basic_paging() -> String {
-    let _count = TEST_EXECUTION_COUNT.fetch_add(1, Ordering::Relaxed);
+    // A fresh page per run, so repeated or reordered runs never find the
+    // previous run's mapping.
+    let run = TEST_EXECUTION_COUNT.fetch_add(1, Ordering::Relaxed) as u64;
     let mut result = String::new();
 
     println!("TEST_PAGING: Starting basic paging test\n");
@@ -31,9 +50,9 @@ pub fn test_basic_paging() -> String {
                 println!(
                     "TEST_PAGING: physical_memory_offset == 0, using low virt (0x400000) for test"
                 );
-                VirtAddr::new(0x400000)
+                VirtAddr::new(0x400000 + run * 4096)
             } else {
-                VirtAddr::new(0xffff_8800_0000_0000) // high kernel space
+                VirtAddr::new(0xffff_8800_0000_0000 + run * 4096) // high kernel space
             };
             let page = Page::containing_address(test_vaddr);
 
@@ -78,14 +97,11 @@ pub fn test_basic_paging() -> String {
 
                     // Read back
                     let read_val = core::ptr::read(test_ptr);
-                    if read_val == 0xdeadbeef {
-                        println!("TEST_PAGING: Successfully wrote and read from mapped page (val={:#x})\n", read_val);
-                    } else {
-                        println!(
-                            "TEST_PAGING: Value mismatch: expected 0xdeadbeef, got {:#x}\n",
-                            read_val
-                        );
+                    if let Some((frame, _)) = crate::memory::unmap_single_page(test_vaddr) {
+                        crate::memory::free_frame(frame);
                     }
+                    kassert_eq!(result; read_val, 0xdeadbeef);
+                    println!("TEST_PAGING: Successfully wrote and read from mapped page (val={:#x})\n", read_val);
                 }
                 Err(e) => {
                     let msg = match e {
@@ -100,10 +116,12 @@ pub fn test_basic_paging() -> String {
                         }
                     };
                     println!("TEST_PAGING: Page mapping failed: {}", msg);
+                    crate::memory::free_frame(frame);
+                    record_failure(&mut result, msg);
                 }
             }
         } else {
-            result.push_str("Frame allocation failed\n");
+            record_failure(&mut result, "Frame allocation failed");
         }
     }
 
@@ -138,10 +156,29 @@ pub fn test_memory_allocation() -> String {
             dealloc(ptr, layout);
             result.push_str("Memory deallocated successfully\n");
         } else {
-            result.push_str("Memory allocation failed\n");
+            record_failure(&mut result, "Memory allocation failed");
         }
     }
 
+    match crate::memory::alloc_dma(3 * 4096) {
+        Ok((virt, phys)) => {
+            // a byte written through each page shows up at the next frame
+            let contiguous = (0..3u64).all(|i| unsafe {
+                let page = (virt + i * 4096).as_mut_ptr::<u8>();
+                page.write_volatile(0x5A + i as u8);
+                let direct = crate::memory::phys_to_virt(phys + i * 4096).as_ptr::<u8>();
+                direct.read_volatile() == 0x5A + i as u8
+            });
+            crate::memory::free_dma(virt, phys, 3 * 4096);
+            if contiguous {
+                result.push_str(&format!("DMA buffer at {:#x} is contiguous\n", phys.as_u64()));
+            } else {
+                record_failure(&mut result, "DMA buffer is not physically contiguous");
+            }
+        }
+        Err(e) => record_failure(&mut result, &format!("DMA allocation failed: {:?}", e)),
+    }
+
     result
 }
 
@@ -168,8 +205,8 @@ pub fn test_mmap_mapping() -> String {
             let _ = sys_munmap(virt_addr, 4096);
             result.push_str("sys_mmap & write test succeeded\n");
         }
-        Err(_) => {
-            result.push_str("sys_mmap failed (no memory or invalid alloc)\n");
+        Err(e) => {
+            record_failure(&mut result, &format!("sys_mmap failed: {:?}", e));
         }
     }
 
@@ -185,17 +222,13 @@ pub fn test_asm_simple_return() -> String {
     println!("TEST_ENV: calling AsmExecutor::execute for simple_return_42");
     match AsmExecutor::execute(AsmProgram::simple_return_42()) {
         Ok(ret_val) => {
-            if ret_val == 42 {
-                result.push_str("Assembly executed successfully, returned 42\n");
-            } else {
-                result.push_str("Got unexpected return value\n");
-            }
+            kassert_eq!(result; ret_val, 42);
+            result.push_str("Assembly executed successfully, returned 42\n");
         }
         Err(e) => {
             let mut msg = String::from("Assembly execution failed: ");
             msg.push_str(&e);
-            result.push_str(&msg);
-            result.push('\n');
+            record_failure(&mut result, &msg);
         }
     }
 
@@ -211,37 +244,309 @@ pub fn test_asm_add() -> String {
     println!("TEST_ENV: calling AsmExecutor::execute for simple_add_1_2");
     match AsmExecutor::execute(AsmProgram::simple_add_1_2()) {
         Ok(ret_val) => {
-            if ret_val == 3 {
-                result.push_str("Assembly executed successfully, returned 3\n");
-            } else {
-                result.push_str("Got unexpected return value\n");
-            }
+            kassert_eq!(result; ret_val, 3);
+            result.push_str("Assembly executed successfully, returned 3\n");
         }
         Err(e) => {
             let mut msg = String::from("Assembly execution failed: ");
             msg.push_str(&e);
-            result.push_str(&msg);
-            result.push('\n');
+            record_failure(&mut result, &msg);
         }
     }
 
     result
 }
 
-pub fn test_all() -> String {
+pub fn test_jit_zeroize() -> String {
     let mut result = String::new();
-    result.push_str("=== RUNNING ALL TESTS ===\n");
-    result.push_str(&test_memory_allocation());
-    result.push_str("\n");
-    result.push_str(&test_basic_paging());
-    result.push_str("\n");
-    result.push_str(&test_mmap_mapping());
-    result.push_str("\n");
-    result.push_str(&test_process_creation());
-    result.push_str("\n");
-    result.push_str(&test_asm_simple_return());
-    result.push_str("\n");
-    result.push_str(&test_asm_add());
-    result.push_str("=== TESTS COMPLETE ===\n");
+    result.push_str("Testing executable page zeroize on munmap...\n");
+
+    use crate::memory::{jit, mmap::sys_mmap, munmap::sys_munmap};
+    use crate::tests::asm::AsmProgram;
+
+    const PROT_RWX: usize = 0x7;
+    const PROT_RW: usize = 0x3;
+
+    let code = AsmProgram::simple_return_42();
+    let freed_before = jit::freed_count();
+    let virt_addr = match sys_mmap(0, 4096, PROT_RWX, 0, 0, 0) {
+        Ok(addr) => addr,
+        Err(e) => {
+            record_failure(&mut result, &format!("sys_mmap PROT_EXEC failed: {:?}", e));
+            return result;
+        }
+    };
+
+    let mapper = unsafe { crate::syscalls::handlers::memory::get_active_mapper() };
+    let Some(phys) = mapper.translate_addr(VirtAddr::new(virt_addr as u64)) else {
+        record_failure(&mut result, "executable page not mapped");
+        return result;
+    };
+
+    let ret = unsafe {
+        core::ptr::copy_nonoverlapping(code.as_ptr(), virt_addr as *mut u8, code.len());
+        jit::note_entry(virt_addr as u64);
+        let entry: extern "C" fn() -> u64 = core::mem::transmute(virt_addr);
+        entry()
+    };
+    let _ = sys_munmap(virt_addr, 4096);
+    kassert_eq!(result; ret, 42);
+
+    // the old frame, read through the physical memory window
+    let old = crate::memory::phys_to_virt(phys).as_ptr::<u8>();
+    let stale = unsafe { core::slice::from_raw_parts(old, code.len()) };
+    if stale.iter().any(|&b| b != 0) {
+        record_failure(&mut result, "freed executable frame still holds code");
+    }
+
+    match sys_mmap(virt_addr, 4096, PROT_RW, 0, 0, 0) {
+        Ok(addr) => {
+            let first = unsafe { core::ptr::read(addr as *const u64) };
+            if first != 0 {
+                record_failure(&mut result, "remapped page is not zeroed");
+            }
+            let _ = sys_munmap(addr, 4096);
+        }
+        Err(e) => record_failure(&mut result, &format!("re-mapping the same address failed: {:?}", e)),
+    }
+
+    if jit::freed_count() != freed_before + 1 {
+        record_failure(&mut result, "jitstat history did not record the mapping");
+    }
+    result.push_str("Executable frame zeroed after munmap\n");
     result
 }
+
+// ── registry ──────────────────────────────────────────────────────────────────
+
+pub struct TestEntry {
+    pub name: &'static str,
+    pub func: fn() -> String,
+    pub tags: &'static [&'static str],
+}
+
+/// Every kernel self-test, for `test list`, `test run` and the CI harness.
+/// Tests must not depend on each other; `test run --reverse` checks that.
+pub static TESTS: &[TestEntry] = &[
+    TestEntry { name: "memory_allocation", func: test_memory_allocation, tags: &["memory", "heap"] },
+    TestEntry { name: "basic_paging", func: test_basic_paging, tags: &["memory", "paging"] },
+    TestEntry { name: "mmap_mapping", func: test_mmap_mapping, tags: &["memory", "mmap"] },
+    TestEntry { name: "process_creation", func: test_process_creation, tags: &["process"] },
+    TestEntry { name: "asm_simple_return", func: test_asm_simple_return, tags: &["asm"] },
+    TestEntry { name: "asm_add", func: test_asm_add, tags: &["asm"] },
+    TestEntry { name: "jit_zeroize", func: test_jit_zeroize, tags: &["memory", "mmap", "asm"] },
+];
+
+pub struct TestResult {
+    pub name: &'static str,
+    pub output: String,
+    pub failures: usize,
+    pub cycles: u64,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures == 0
+    }
+}
+
+impl TestEntry {
+    /// A pattern selects a test by exact tag or by substring of its name.
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.tags.contains(&pattern) || self.name.contains(pattern)
+    }
+
+    pub fn run(&self) -> TestResult {
+        let before = failure_count();
+        let start = crate::stats::latency::rdtsc();
+        let output = (self.func)();
+        let cycles = crate::stats::latency::rdtsc() - start;
+        TestResult {
+            name: self.name,
+            output,
+            failures: failure_count() - before,
+            cycles,
+        }
+    }
+}
+
+/// Tests matching any of `patterns`, in registry order; all if empty.
+pub fn select(patterns: &[&str]) -> Vec<&'static TestEntry> {
+    TESTS
+        .iter()
+        .filter(|t| patterns.is_empty() || patterns.iter().any(|p| t.matches(p)))
+        .collect()
+}
+
+pub fn list() -> String {
+    let mut out = format!("{} tests:\n", TESTS.len());
+    for t in TESTS {
+        out.push_str(&format!("  {:<20} [{}]\n", t.name, t.tags.join(", ")));
+    }
+    out
+}
+
+fn format_duration(cycles: u64, tsc_per_us: u64) -> String {
+    let us = cycles / tsc_per_us.max(1);
+    if us >= 1000 {
+        format!("{}.{:03} ms", us / 1000, us % 1000)
+    } else {
+        format!("{} us", us)
+    }
+}
+
+/// Runs the selected tests and returns their output plus a summary,
+/// slowest first. Failures are added to `failure_count`.
+///
+/// With `reverse`, the selection runs a second time in reverse order and
+/// any test whose pass/fail result changed is reported as a failure.
+pub fn run_tests(patterns: &[&str], reverse: bool) -> String {
+    let mut run = TestRun::new(patterns, reverse);
+    let mut out = String::new();
+    while run.step(&mut out) {}
+    run.finish(&mut out);
+    out
+}
+
+/// A `run_tests` taken one test per `step`, so a background job can run
+/// the suite without holding up a frame for all of it.
+pub struct TestRun {
+    tests: Vec<&'static TestEntry>,
+    reverse: bool,
+    next: usize,
+    results: Vec<TestResult>,
+    failures: usize,
+}
+
+impl TestRun {
+    pub fn new(patterns: &[&str], reverse: bool) -> Self {
+        let tests = select(patterns);
+        TestRun {
+            results: Vec::with_capacity(tests.len()),
+            tests,
+            reverse,
+            next: 0,
+            failures: 0,
+        }
+    }
+
+    /// Runs the next test, appending its output. False once every test
+    /// (and, with `reverse`, every rerun) has run.
+    pub fn step(&mut self, out: &mut String) -> bool {
+        let n = self.tests.len();
+        let i = self.next;
+        if i == 0 {
+            out.push_str(&format!("=== RUNNING {} TESTS ===\n", n));
+        }
+        if i < n {
+            let t = self.tests[i];
+            crate::serial_progress(&format!("[{}/{}] {}", i + 1, n, t.name));
+            let result = t.run();
+            out.push_str(&format!("--- {} ---\n", t.name));
+            out.push_str(&result.output);
+            self.failures += result.failures;
+            self.results.push(result);
+            if i + 1 == n {
+                crate::serial_progress("");
+            }
+        } else if self.reverse && i < 2 * n {
+            if i == n {
+                out.push_str("=== REVERSE ORDER ===\n");
+            }
+            self.rerun(self.tests[2 * n - 1 - i], out);
+        } else {
+            return false;
+        }
+        self.next += 1;
+        true
+    }
+
+    fn rerun(&mut self, t: &TestEntry, out: &mut String) {
+        // Only a changed outcome counts here, not the same failure twice.
+        let before = failure_count();
+        let again = t.run();
+        TEST_FAILURES.store(before, Ordering::Relaxed);
+        let first = self
+            .results
+            .iter()
+            .find(|r| r.name == t.name)
+            .map(|r| r.passed());
+        if first != Some(again.passed()) {
+            let msg = format!(
+                "{} is order-dependent (passed={} then {})",
+                t.name,
+                first.unwrap_or(false),
+                again.passed()
+            );
+            record_failure(out, &msg);
+            self.failures += 1;
+        }
+    }
+
+    /// Failures so far, in this run only.
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    /// Appends the summary, slowest first.
+    pub fn finish(&mut self, out: &mut String) {
+        let tsc_per_us = crate::stats::latency::tsc_per_us();
+        self.results.sort_unstable_by_key(|r| core::cmp::Reverse(r.cycles));
+        out.push_str("Summary (slowest first):\n");
+        for r in &self.results {
+            out.push_str(&format!(
+                "  {:<20} {:<4} {}\n",
+                r.name,
+                if r.passed() { "ok" } else { "FAIL" },
+                format_duration(r.cycles, tsc_per_us)
+            ));
+        }
+        out.push_str("=== TESTS COMPLETE ===\n");
+    }
+}
+
+// ── test cases ────────────────────────────────────────────────────────────────
+
+/// Runs one of the `test_*` functions above as a `#[test_case]`, failing if
+/// it recorded any failure.
+#[cfg(test)]
+fn expect_pass(test: fn() -> String) {
+    reset_failures();
+    let output = test();
+    assert_eq!(failure_count(), 0, "{}", output);
+}
+
+#[test_case]
+fn memory_allocation() {
+    expect_pass(test_memory_allocation);
+}
+
+#[test_case]
+fn process_creation() {
+    expect_pass(test_process_creation);
+}
+
+#[test_case]
+fn basic_paging() {
+    expect_pass(test_basic_paging);
+}
+
+#[test_case]
+fn mmap_mapping() {
+    expect_pass(test_mmap_mapping);
+}
+
+#[test_case]
+fn asm_simple_return() {
+    expect_pass(test_asm_simple_return);
+}
+
+#[test_case]
+fn asm_add() {
+    expect_pass(test_asm_add);
+}
+
+#[test_case]
+fn jit_zeroize() {
+    expect_pass(test_jit_zeroize);
+}
@@ -0,0 +1,270 @@
+//! # Background
+//!
+//! What shows wherever no opaque app, nor the top bar, covers the screen:
+//! the theme's background colour, a solid colour, a vertical gradient or
+//! an image. `draw` paints only the regions left exposed by the rects it
+//! is given, so a full-screen terminal costs nothing here however large the
+//! wallpaper is.
+//!
+//! A gradient or image is drawn once, into a cache at the screen's size
+//! (one packed colour per row for a gradient, every pixel for an image),
+//! and later frames copy from the cache. The cache is rebuilt when the
+//! wallpaper or the screen size changes. An image is cropped to the
+//! screen's aspect ratio around its centre, then scaled with
+//! `scale_bilinear`, so it fills the screen without stretching.
+//!
+//! The terminal is opaque by default. `set_terminal_transparent` has it
+//! leave cells in its default background undrawn, so the wallpaper shows
+//! behind the text. It is then not opaque, so the wallpaper is painted
+//! over all of it every frame, and it draws all of its text again each
+//! frame rather than only the rows that changed.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::ui_provider::{
+    color::Color, image::Image, render::gradient_color, shape::Rect, theme::Theme,
+};
+use alloc::{format, string::String, vec, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+pub enum Wallpaper {
+    /// `theme.background`, following theme switches.
+    Theme,
+    Solid(Color),
+    /// Top row to bottom row.
+    Gradient(Color, Color),
+    Image(Image),
+}
+
+struct Background {
+    wallpaper: Wallpaper,
+    /// Screen size `cache` was built for.
+    size: (usize, usize),
+    /// Packed RGB888: a colour per row for a gradient, row-major pixels
+    /// for an image, empty otherwise.
+    cache: Vec<u32>,
+}
+
+static BACKGROUND: Mutex<Background> = Mutex::new(Background {
+    wallpaper: Wallpaper::Theme,
+    size: (0, 0),
+    cache: Vec::new(),
+});
+static TERMINAL_TRANSPARENT: AtomicBool = AtomicBool::new(false);
+
+/// Replaces the wallpaper; the next frame shows it.
+pub fn set(wallpaper: Wallpaper) {
+    let mut background = BACKGROUND.lock();
+    background.wallpaper = wallpaper;
+    background.size = (0, 0);
+    background.cache = Vec::new();
+}
+
+/// The wallpaper in words, for the `background` command.
+pub fn describe() -> String {
+    let hex = |c: Color| format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b);
+    let wallpaper = match &BACKGROUND.lock().wallpaper {
+        Wallpaper::Theme => String::from("theme background"),
+        Wallpaper::Solid(color) => hex(*color),
+        Wallpaper::Gradient(top, bottom) => format!("gradient {} to {}", hex(*top), hex(*bottom)),
+        Wallpaper::Image(image) => format!("image, {}x{}", image.w, image.h),
+    };
+    let terminal = if terminal_transparent() {
+        "transparent"
+    } else {
+        "opaque"
+    };
+    format!("{}; terminal {}", wallpaper, terminal)
+}
+
+pub fn set_terminal_transparent(on: bool) {
+    TERMINAL_TRANSPARENT.store(on, Ordering::Relaxed);
+}
+
+pub fn terminal_transparent() -> bool {
+    TERMINAL_TRANSPARENT.load(Ordering::Relaxed)
+}
+
+/// `#rrggbb`, `0xrrggbb` or plain `rrggbb`.
+pub fn parse_color(text: &str) -> Option<Color> {
+    let digits = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    if digits.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok().map(Color::from_hex)
+}
+
+/// `screen` minus every rect in `covered`, as disjoint rects.
+pub fn exposed(screen: Rect, covered: &[Rect]) -> Vec<Rect> {
+    let mut out = vec![screen];
+    for cover in covered {
+        out = out
+            .into_iter()
+            .flat_map(|rect| subtract(rect, *cover))
+            .collect();
+    }
+    out
+}
+
+/// The parts of `rect` outside `cover`: full-width strips above and below
+/// it, then what is left and right of it in between.
+fn subtract(rect: Rect, cover: Rect) -> Vec<Rect> {
+    let top = cover.y.max(rect.y);
+    let bottom = cover.bottom().min(rect.bottom());
+    let left = cover.x.max(rect.x);
+    let right = cover.right().min(rect.right());
+    if top >= bottom || left >= right {
+        return vec![rect];
+    }
+    [
+        Rect::new(rect.x, rect.y, rect.w, top - rect.y),
+        Rect::new(rect.x, bottom, rect.w, rect.bottom() - bottom),
+        Rect::new(rect.x, top, left - rect.x, bottom - top),
+        Rect::new(right, top, rect.right() - right, bottom - top),
+    ]
+    .into_iter()
+    .filter(|piece| piece.w > 0 && piece.h > 0)
+    .collect()
+}
+
+/// Row `y` of `rows` going from `top` to `bottom`, packed.
+fn gradient_row(top: Color, bottom: Color, y: usize, rows: usize) -> u32 {
+    let c = gradient_color(top, bottom, y, rows);
+    (u32::from(c.r) << 16) | (u32::from(c.g) << 8) | u32::from(c.b)
+}
+
+/// `image` cropped to the aspect ratio of `w` x `h` around its centre,
+/// then scaled to exactly that size.
+fn cover(image: &Image, w: usize, h: usize) -> Vec<u32> {
+    let (crop_w, crop_h) = if image.w * h > image.h * w {
+        ((image.h * w / h).max(1), image.h)
+    } else {
+        (image.w, (image.w * h / w).max(1))
+    };
+    let (x0, y0) = ((image.w - crop_w) / 2, (image.h - crop_h) / 2);
+    let mut crop = Vec::with_capacity(crop_w * crop_h);
+    for y in y0..y0 + crop_h {
+        let row = y * image.w + x0;
+        crop.extend_from_slice(&image.pixels[row..row + crop_w]);
+    }
+    crate::ui_provider::image::scale_bilinear(&crop, crop_w, crop_h, w, h)
+}
+
+impl Background {
+    /// Builds `cache` for a `w` x `h` screen unless it already is.
+    fn prepare(&mut self, w: usize, h: usize) {
+        if self.size == (w, h) {
+            return;
+        }
+        self.size = (w, h);
+        self.cache = match &self.wallpaper {
+            Wallpaper::Theme | Wallpaper::Solid(_) => Vec::new(),
+            Wallpaper::Gradient(top, bottom) => {
+                (0..h).map(|y| gradient_row(*top, *bottom, y, h)).collect()
+            }
+            Wallpaper::Image(image) => cover(image, w, h),
+        };
+    }
+
+    fn draw_rect(&self, fb: &mut FramebufferWriter, theme: &Theme, rect: Rect) {
+        match &self.wallpaper {
+            Wallpaper::Theme => fb.fill_rect(rect.x, rect.y, rect.w, rect.h, theme.background),
+            Wallpaper::Solid(color) => fb.fill_rect(rect.x, rect.y, rect.w, rect.h, *color),
+            Wallpaper::Gradient(..) => {
+                for y in rect.y..rect.bottom() {
+                    fb.fill_rect(rect.x, y, rect.w, 1, Color::from_hex(self.cache[y]));
+                }
+            }
+            Wallpaper::Image(_) => {
+                let stride = self.size.0;
+                for y in rect.y..rect.bottom() {
+                    let row = y * stride + rect.x;
+                    fb.blit(rect.x, y, rect.w, 1, &self.cache[row..row + rect.w]);
+                }
+            }
+        }
+    }
+}
+
+/// Paints the wallpaper wherever `covered` leaves the screen exposed, and
+/// over `stale` (what the layers drew last frame) whatever covers it; the
+/// apps there are told to draw it again. Returns the area painted, in
+/// pixels.
+pub fn draw(fb: &mut FramebufferWriter, theme: &Theme, covered: &[Rect], stale: &[Rect]) -> usize {
+    let screen = Rect::new(0, 0, fb.width, fb.height);
+    let mut background = BACKGROUND.lock();
+    background.prepare(fb.width, fb.height);
+    let stale = stale.iter().filter_map(|rect| rect.intersect(screen));
+    exposed(screen, covered)
+        .into_iter()
+        .chain(stale)
+        .map(|rect| {
+            background.draw_rect(fb, theme, rect);
+            rect.w * rect.h
+        })
+        .sum()
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn wallpaper_fills_only_what_apps_expose() {
+        let (w, h) = (64, 48);
+        let buffer: &'static mut [u8] = vec![0u8; w * h * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, w, h, w, 4);
+        let theme = Theme::dark_modern();
+        let app = Rect::new(8, 10, 40, 20);
+        let sentinel = Color::from_hex(0x123456);
+
+        // Pieces are disjoint and add up to the screen minus the app
+        let pieces = exposed(Rect::new(0, 0, w, h), &[app, Rect::new(0, 0, w, 4)]);
+        let area: usize = pieces.iter().map(|r| r.w * r.h).sum();
+        assert_eq!(area, w * h - app.w * app.h - w * 4);
+
+        set(Wallpaper::Gradient(Color::BLACK, Color::from_hex(0x0000ff)));
+        fb.fill_rect(app.x, app.y, app.w, app.h, sentinel);
+        assert_eq!(draw(&mut fb, &theme, &[app], &[]), w * h - app.w * app.h);
+        assert_eq!(fb.get_pixel(app.x + 1, app.y + 1), sentinel);
+        // Where a layer was, it goes under the app as well
+        let stale = Rect::new(app.x, app.y, 2, 2);
+        assert_eq!(draw(&mut fb, &theme, &[app], &[stale]), w * h - app.w * app.h + 4);
+        assert_ne!(fb.get_pixel(app.x + 1, app.y + 1), sentinel);
+        assert_eq!(fb.get_pixel(app.x + 2, app.y + 2), sentinel);
+        assert_eq!(fb.get_pixel(0, 0), Color::BLACK);
+        assert_eq!(fb.get_pixel(w - 1, h - 1), Color::from_hex(0x0000ff));
+        // Beside the app, the same row as at the left edge
+        assert_eq!(
+            fb.get_pixel(app.right(), app.y + 5),
+            fb.get_pixel(0, app.y + 5)
+        );
+
+        // An image is cropped, not stretched: a 4:1 image on a 4:3
+        // screen keeps its middle, a uniform colour here
+        let mut pixels = vec![0xff0000u32; 16 * 4];
+        for y in 0..4 {
+            pixels[y * 16 + 6..y * 16 + 10].fill(0x00ff00);
+        }
+        let image = Image {
+            w: 16,
+            h: 4,
+            pixels,
+        };
+        set(Wallpaper::Image(image));
+        draw(&mut fb, &theme, &[], &[]);
+        assert_eq!(fb.get_pixel(w / 2, 0), Color::from_hex(0x00ff00));
+        assert_eq!(fb.get_pixel(w / 2, h - 1), Color::from_hex(0x00ff00));
+
+        set(Wallpaper::Theme);
+        draw(&mut fb, &theme, &[], &[]);
+        assert_eq!(fb.get_pixel(w / 2, h / 2), theme.background);
+        assert_eq!(parse_color("#FF8000"), Some(Color::from_hex(0xff8000)));
+        assert_eq!(parse_color("fff"), None);
+    }
+}
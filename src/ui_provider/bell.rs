@@ -0,0 +1,216 @@
+//! # Bell
+//!
+//! What a BEL (`\x07`) written to a terminal does: flash the terminal's
+//! border for `FLASH_TICKS` timer ticks, beep the PC speaker for
+//! `BEEP_TICKS`, both, or nothing, as set by the `bell` command and kept
+//! in `/config/bell`.
+//!
+//! A bell that arrives while the last one is still flashing or beeping is
+//! dropped rather than extending it, so a flood of BELs blinks at most
+//! once per ring instead of holding the flash on.
+
+use crate::devices::drivers::pc_speaker;
+use spin::Mutex;
+
+pub const FLASH_TICKS: u64 = 3;
+pub const BEEP_TICKS: u64 = 2;
+const BEEP_HZ: u32 = 880;
+
+/// Where `bell <style>` is remembered, so that `sync` carries it over a
+/// warm reboot.
+pub const BELL_CONFIG: &str = "/config/bell";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BellStyle {
+    Visual,
+    Audible,
+    Both,
+    Off,
+}
+
+impl BellStyle {
+    pub const ALL: [BellStyle; 4] = [
+        BellStyle::Visual,
+        BellStyle::Audible,
+        BellStyle::Both,
+        BellStyle::Off,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BellStyle::Visual => "visual",
+            BellStyle::Audible => "audible",
+            BellStyle::Both => "both",
+            BellStyle::Off => "off",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.name() == name)
+    }
+
+    fn visual(self) -> bool {
+        matches!(self, BellStyle::Visual | BellStyle::Both)
+    }
+
+    fn audible(self) -> bool {
+        matches!(self, BellStyle::Audible | BellStyle::Both)
+    }
+}
+
+/// Flash and beep deadlines, in timer ticks; 0 when not running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bell {
+    style: BellStyle,
+    flash_until: u64,
+    beep_until: u64,
+}
+
+/// What changed on a `ring` or `tick`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BellChange {
+    pub flash: bool,
+    pub beep: bool,
+}
+
+impl Bell {
+    pub const fn new(style: BellStyle) -> Self {
+        Self {
+            style,
+            flash_until: 0,
+            beep_until: 0,
+        }
+    }
+
+    /// Starts a flash and/or a beep at tick `now`, unless one is running.
+    pub fn ring(&mut self, now: u64) -> BellChange {
+        if self.flashing(now) || now < self.beep_until {
+            return BellChange::default();
+        }
+        let change = BellChange {
+            flash: self.style.visual(),
+            beep: self.style.audible(),
+        };
+        if change.flash {
+            self.flash_until = now + FLASH_TICKS;
+        }
+        if change.beep {
+            self.beep_until = now + BEEP_TICKS;
+        }
+        change
+    }
+
+    /// Ends whatever has run its course by tick `now`.
+    pub fn tick(&mut self, now: u64) -> BellChange {
+        let change = BellChange {
+            flash: self.flash_until != 0 && now >= self.flash_until,
+            beep: self.beep_until != 0 && now >= self.beep_until,
+        };
+        if change.flash {
+            self.flash_until = 0;
+        }
+        if change.beep {
+            self.beep_until = 0;
+        }
+        change
+    }
+
+    pub fn flashing(&self, now: u64) -> bool {
+        now < self.flash_until
+    }
+}
+
+static BELL: Mutex<Bell> = Mutex::new(Bell::new(BellStyle::Visual));
+
+fn ticks() -> u64 {
+    crate::kcore::interrupts::interrupts::TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+pub fn style() -> BellStyle {
+    BELL.lock().style
+}
+
+pub fn set_style(style: BellStyle) {
+    let mut bell = BELL.lock();
+    bell.style = style;
+    if bell.beep_until != 0 && !style.audible() {
+        bell.beep_until = 0;
+        pc_speaker::stop();
+    }
+}
+
+/// Rings the bell in the current style. Returns whether a flash started.
+pub fn ring() -> bool {
+    let change = BELL.lock().ring(ticks());
+    if change.beep {
+        pc_speaker::start(BEEP_HZ);
+    }
+    change.flash
+}
+
+/// Called on every timer tick event. Returns whether a flash just ended,
+/// so its border has to be redrawn away.
+pub fn tick() -> bool {
+    let change = BELL.lock().tick(ticks());
+    if change.beep {
+        pc_speaker::stop();
+    }
+    change.flash
+}
+
+pub fn flashing() -> bool {
+    BELL.lock().flashing(ticks())
+}
+
+/// Applies the style saved in ramfs, after `persist::restore`.
+pub fn load_config() {
+    if let Ok(value) = crate::fs::ramfs::read(BELL_CONFIG) {
+        if let Some(style) = core::str::from_utf8(&value)
+            .ok()
+            .and_then(BellStyle::from_name)
+        {
+            set_style(style);
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn repeated_bells_do_not_stack() {
+        let mut bell = Bell::new(BellStyle::Both);
+        let both = BellChange {
+            flash: true,
+            beep: true,
+        };
+        assert_eq!(bell.ring(10), both);
+        // a flood while it runs changes nothing
+        for now in 10..10 + FLASH_TICKS {
+            assert_eq!(bell.ring(now), BellChange::default());
+            assert!(bell.flashing(now));
+        }
+        assert!(!bell.flashing(10 + FLASH_TICKS));
+
+        assert!(bell.tick(10 + BEEP_TICKS).beep);
+        assert!(bell.tick(10 + FLASH_TICKS).flash);
+        assert_eq!(bell.tick(20), BellChange::default());
+        assert_eq!(bell.ring(20), both);
+    }
+
+    #[test_case]
+    fn styles_pick_flash_and_beep() {
+        for style in BellStyle::ALL {
+            assert_eq!(BellStyle::from_name(style.name()), Some(style));
+            let change = Bell::new(style).ring(1);
+            assert_eq!(change.flash, style.visual(), "{}", style.name());
+            assert_eq!(change.beep, style.audible(), "{}", style.name());
+        }
+        let mut off = Bell::new(BellStyle::Off);
+        off.ring(1);
+        assert!(!off.flashing(1));
+    }
+}
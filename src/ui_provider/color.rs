@@ -142,7 +142,15 @@ impl Color {
     pub fn to_rgb888(self) -> Rgb888 {
         Rgb888::new(self.r, self.g, self.b)
     }
-
-
 }
 
+/// Packed RGB888 `over` drawn at `alpha`/255 on `under`, rounded, in
+/// integers.
+pub fn blend_packed(under: u32, over: u32, alpha: u8) -> u32 {
+    let a = u32::from(alpha);
+    let mix = |shift: u32| {
+        let (u, o) = ((under >> shift) & 0xFF, (over >> shift) & 0xFF);
+        ((o * a + u * (255 - a) + 127) / 255) << shift
+    };
+    mix(16) | mix(8) | mix(0)
+}
@@ -0,0 +1,258 @@
+//! # Frame Arena
+//!
+//! Per-frame scratch space for render text. A `BumpAllocator` over a fixed
+//! 64 KiB buffer hands out string storage for `RenderCommand::Text`, and
+//! `begin_frame` resets it before the next frame is composed, so the
+//! transient strings of a frame never touch the global heap.
+//!
+//! Strings that do not fit fall back to a heap `String` and are counted.
+//! A `FrameStr` remembers the generation it was allocated in and reads as
+//! empty once the arena has been reset, so a command kept across frames
+//! can go blank but never show another frame's bytes.
+
+use crate::memory::allocators::bump::BumpAllocator;
+use alloc::{format, string::String};
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+pub const FRAME_ARENA_SIZE: usize = 64 * 1024;
+
+#[repr(align(16))]
+struct ArenaBuffer([u8; FRAME_ARENA_SIZE]);
+
+static mut ARENA_BUFFER: ArenaBuffer = ArenaBuffer([0; FRAME_ARENA_SIZE]);
+static ARENA: BumpAllocator = BumpAllocator::new();
+static ARENA_READY: AtomicBool = AtomicBool::new(false);
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+static FRAME_STRINGS: AtomicUsize = AtomicUsize::new(0);
+static FRAME_FALLBACKS: AtomicUsize = AtomicUsize::new(0);
+static LAST_USED: AtomicUsize = AtomicUsize::new(0);
+static LAST_STRINGS: AtomicUsize = AtomicUsize::new(0);
+static LAST_FALLBACKS: AtomicUsize = AtomicUsize::new(0);
+static PEAK_USED: AtomicUsize = AtomicUsize::new(0);
+static TOTAL_FALLBACKS: AtomicUsize = AtomicUsize::new(0);
+
+fn ensure_ready() -> bool {
+    if ARENA_READY.load(Ordering::Acquire) {
+        return true;
+    }
+    let start = unsafe { core::ptr::addr_of_mut!(ARENA_BUFFER.0) } as usize;
+    let ok = unsafe { ARENA.init(start, FRAME_ARENA_SIZE) }.is_ok();
+    ARENA_READY.store(ok, Ordering::Release);
+    ok
+}
+
+/// Call once per frame before any app collects its render commands.
+pub fn begin_frame() {
+    if !ensure_ready() {
+        return;
+    }
+    let used = ARENA.used();
+    LAST_USED.store(used, Ordering::Relaxed);
+    PEAK_USED.fetch_max(used, Ordering::Relaxed);
+    LAST_STRINGS.store(FRAME_STRINGS.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+    LAST_FALLBACKS.store(FRAME_FALLBACKS.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    // Commands from the previous frame read as empty from here on.
+    unsafe { ARENA.reset() };
+}
+
+/// Borrowed view of a string living in the frame arena.
+#[derive(Clone, Copy)]
+pub struct FrameStr {
+    ptr: *const u8,
+    len: usize,
+    generation: u32,
+}
+
+impl FrameStr {
+    pub fn as_str(&self) -> &str {
+        if self.generation != GENERATION.load(Ordering::Acquire) {
+            return "";
+        }
+        unsafe {
+            let bytes = core::slice::from_raw_parts(self.ptr, self.len);
+            core::str::from_utf8_unchecked(bytes)
+        }
+    }
+}
+
+/// Text owned by a render command: arena-backed when it fits, heap otherwise.
+#[derive(Clone)]
+pub enum TextBuf {
+    Frame(FrameStr),
+    Owned(String),
+}
+
+impl TextBuf {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TextBuf::Frame(s) => s.as_str(),
+            TextBuf::Owned(s) => s,
+        }
+    }
+}
+
+impl core::ops::Deref for TextBuf {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for TextBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for TextBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for TextBuf {}
+
+impl From<String> for TextBuf {
+    fn from(s: String) -> Self {
+        TextBuf::Owned(s)
+    }
+}
+
+impl From<&str> for TextBuf {
+    fn from(s: &str) -> Self {
+        arena_str(s)
+    }
+}
+
+impl From<&String> for TextBuf {
+    fn from(s: &String) -> Self {
+        arena_str(s)
+    }
+}
+
+fn alloc_bytes(len: usize) -> Option<&'static mut [u8]> {
+    if len == 0 || !ensure_ready() {
+        return None;
+    }
+    let layout = Layout::from_size_align(len, 1).ok()?;
+    let ptr = unsafe { ARENA.alloc(layout) };
+    if ptr.is_null() {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts_mut(ptr, len) })
+}
+
+fn frame_str(bytes: &[u8]) -> TextBuf {
+    FRAME_STRINGS.fetch_add(1, Ordering::Relaxed);
+    TextBuf::Frame(FrameStr {
+        ptr: bytes.as_ptr(),
+        len: bytes.len(),
+        generation: GENERATION.load(Ordering::Acquire),
+    })
+}
+
+fn note_fallback() {
+    FRAME_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+    TOTAL_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Copies `s` into the arena, or onto the heap if the frame budget is spent.
+pub fn arena_str(s: &str) -> TextBuf {
+    if s.is_empty() {
+        return TextBuf::Owned(String::new());
+    }
+    match alloc_bytes(s.len()) {
+        Some(buf) => {
+            buf.copy_from_slice(s.as_bytes());
+            frame_str(buf)
+        }
+        None => {
+            note_fallback();
+            TextBuf::Owned(String::from(s))
+        }
+    }
+}
+
+/// Collects chars into the arena without an intermediate `String`.
+pub fn arena_chars<I>(chars: I) -> TextBuf
+where
+    I: Iterator<Item = char> + Clone,
+{
+    let len: usize = chars.clone().map(char::len_utf8).sum();
+    match alloc_bytes(len) {
+        Some(buf) => {
+            let mut at = 0;
+            for ch in chars {
+                at += ch.encode_utf8(&mut buf[at..]).len();
+            }
+            frame_str(buf)
+        }
+        None => {
+            if len != 0 {
+                note_fallback();
+            }
+            TextBuf::Owned(chars.collect())
+        }
+    }
+}
+
+/// `format!` into the arena: measures first, then writes in place.
+pub fn arena_format(args: fmt::Arguments) -> TextBuf {
+    struct Counter(usize);
+    impl Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    struct Filler<'a> {
+        buf: &'a mut [u8],
+        at: usize,
+    }
+    impl Write for Filler<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.at + s.len();
+            if end > self.buf.len() {
+                return Err(fmt::Error);
+            }
+            self.buf[self.at..end].copy_from_slice(s.as_bytes());
+            self.at = end;
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    let _ = counter.write_fmt(args);
+    if let Some(buf) = alloc_bytes(counter.0) {
+        let mut filler = Filler { buf, at: 0 };
+        // Formatting is deterministic, so the second pass fits exactly.
+        if filler.write_fmt(args).is_ok() && filler.at == filler.buf.len() {
+            return frame_str(filler.buf);
+        }
+    }
+    if counter.0 != 0 {
+        note_fallback();
+    }
+    TextBuf::Owned(format!("{}", args))
+}
+
+pub fn report() -> String {
+    format!(
+        "Frame arena: {} KiB budget\n  \
+         last frame: {} B in {} strings, {} heap fallbacks\n  \
+         peak: {} B, total fallbacks: {}",
+        FRAME_ARENA_SIZE / 1024,
+        LAST_USED.load(Ordering::Relaxed),
+        LAST_STRINGS.load(Ordering::Relaxed),
+        LAST_FALLBACKS.load(Ordering::Relaxed),
+        PEAK_USED.load(Ordering::Relaxed),
+        TOTAL_FALLBACKS.load(Ordering::Relaxed)
+    )
+}
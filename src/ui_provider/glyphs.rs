@@ -0,0 +1,204 @@
+//! # Glyph Cache
+//!
+//! `FONT_10X20` has no bold face, so bold text is synthesized: each glyph
+//! is drawn twice, the second time a pixel to the right, and clipped to
+//! its cell. Doing that per character per frame would rasterize twice
+//! over, so the result is kept as a coverage mask keyed on the character
+//! and its `Variant`. Regular text still goes straight through the font.
+//!
+//! The cache holds at most `CACHE_MAX` masks and empties itself when it
+//! fills (a terminal uses a few dozen glyphs; refilling is cheap). It is
+//! also emptied by `invalidate`, which anything that changes the font or
+//! its size must call, and by the memory pressure trimmer.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::memory::pressure::Pressure;
+use crate::ui_provider::color::Color;
+use alloc::collections::BTreeMap;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Baseline, Text},
+};
+use spin::Mutex;
+
+pub const GLYPH_WIDTH: usize = 10;
+pub const GLYPH_HEIGHT: usize = 20;
+/// Masks kept before the cache starts over.
+pub const CACHE_MAX: usize = 512;
+
+/// One bit per pixel, bit `x` of row `y`.
+pub type Mask = [u16; GLYPH_HEIGHT];
+
+/// A way of drawing a character that gets its own mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+    Regular,
+    Bold,
+}
+
+struct Cache {
+    masks: BTreeMap<(char, Variant), Mask>,
+    hits: u64,
+    misses: u64,
+}
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    masks: BTreeMap::new(),
+    hits: 0,
+    misses: 0,
+});
+
+/// Collects the pixels a glyph sets, in place of a framebuffer.
+struct MaskTarget(Mask);
+
+impl DrawTarget for MaskTarget {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(Point { x, y }, color) in pixels {
+            if color.is_on()
+                && (0..GLYPH_WIDTH as i32).contains(&x)
+                && (0..GLYPH_HEIGHT as i32).contains(&y)
+            {
+                self.0[y as usize] |= 1 << x;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OriginDimensions for MaskTarget {
+    fn size(&self) -> Size {
+        Size::new(GLYPH_WIDTH as u32, GLYPH_HEIGHT as u32)
+    }
+}
+
+fn rasterize(ch: char, variant: Variant) -> Mask {
+    if variant == Variant::Bold {
+        let cell = (1u16 << GLYPH_WIDTH) - 1;
+        return rasterize(ch, Variant::Regular).map(|row| (row | row << 1) & cell);
+    }
+    let mut buf = [0u8; 4];
+    let mut target = MaskTarget([0; GLYPH_HEIGHT]);
+    let style = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    Text::with_baseline(
+        ch.encode_utf8(&mut buf),
+        Point::zero(),
+        style,
+        Baseline::Top,
+    )
+    .draw(&mut target)
+    .ok();
+    target.0
+}
+
+/// The mask for `ch` drawn as `variant`, from the cache if it is there.
+pub fn mask(ch: char, variant: Variant) -> Mask {
+    let mut cache = CACHE.lock();
+    if let Some(&mask) = cache.masks.get(&(ch, variant)) {
+        cache.hits += 1;
+        return mask;
+    }
+    cache.misses += 1;
+    if cache.masks.len() >= CACHE_MAX {
+        cache.clear();
+    }
+    let mask = rasterize(ch, variant);
+    cache.masks.insert((ch, variant), mask);
+    mask
+}
+
+/// Draws `text` in bold with its baseline at `y`, as `draw_text` would
+/// place it.
+pub fn draw_bold(fb: &mut FramebufferWriter, text: &str, x: usize, y: usize, fg: Color) {
+    let top = y.saturating_sub(FONT_10X20.baseline as usize);
+    for (i, ch) in text.chars().enumerate() {
+        let left = x + i * GLYPH_WIDTH;
+        for (dy, row) in mask(ch, Variant::Bold).iter().enumerate() {
+            let mut bits = *row;
+            while bits != 0 {
+                let dx = bits.trailing_zeros() as usize;
+                fb.put_pixel(left + dx, top + dy, fg);
+                bits &= bits - 1;
+            }
+        }
+    }
+}
+
+/// Drops every mask, for a font change. Returns the bytes freed.
+#[allow(dead_code)] // there is one font and one size so far
+pub fn invalidate() -> usize {
+    CACHE.lock().clear()
+}
+
+impl Cache {
+    fn clear(&mut self) -> usize {
+        let freed = bytes(self.masks.len());
+        self.masks.clear();
+        freed
+    }
+}
+
+fn bytes(masks: usize) -> usize {
+    masks * core::mem::size_of::<((char, Variant), Mask)>()
+}
+
+/// Memory-pressure trimmer: the masks are rebuilt on use, so all go.
+pub fn trim(_level: Pressure) -> usize {
+    // Held by a frame being drawn: next time
+    let Some(mut cache) = CACHE.try_lock() else {
+        return 0;
+    };
+    cache.clear()
+}
+
+/// A line for `renderstat`.
+pub fn report() -> alloc::string::String {
+    let cache = CACHE.lock();
+    alloc::format!(
+        "Glyphs: {} of {} cached ({} bytes), {} hits, {} misses",
+        cache.masks.len(),
+        CACHE_MAX,
+        bytes(cache.masks.len()),
+        cache.hits,
+        cache.misses
+    )
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn bold_is_the_glyph_smeared_right_within_its_cell() {
+        for ch in ['H', 'm', '#', '|'] {
+            let regular = rasterize(ch, Variant::Regular);
+            let bold = mask(ch, Variant::Bold);
+            assert!(regular.iter().any(|&row| row != 0));
+            for (r, b) in regular.iter().zip(bold) {
+                assert_eq!(b, (r | r << 1) & 0x3ff);
+            }
+        }
+        assert_eq!(mask(' ', Variant::Bold), [0; GLYPH_HEIGHT]);
+    }
+
+    #[test_case]
+    fn the_cache_stays_bounded_and_trims_to_nothing() {
+        let chars = (0x20u32..0x20 + CACHE_MAX as u32 + 40).filter_map(char::from_u32);
+        for ch in chars {
+            mask(ch, Variant::Bold);
+            assert!(CACHE.lock().masks.len() <= CACHE_MAX);
+        }
+        assert!(trim(Pressure::High) > 0);
+        assert!(CACHE.lock().masks.is_empty());
+        assert_eq!(trim(Pressure::High), 0);
+    }
+}
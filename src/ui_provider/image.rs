@@ -0,0 +1,423 @@
+//! # Images
+//!
+//! Decodes the two formats simple enough to write by hand or with any
+//! tool: binary PPM (`P6`, 8-bit samples) and uncompressed 24-bit BMP.
+//! Either comes out as an `Image` of packed RGB888 rows, top row first,
+//! ready for `FramebufferWriter::blit`.
+//!
+//! PPM headers are whitespace-separated fields with `#` comments; BMP rows
+//! are stored bottom-up (unless the height is negative) in BGR order and
+//! padded to four bytes. Anything else, or a header that does not add up,
+//! is an `ImageError`, never a panic.
+//!
+//! `scale_nearest` and `scale_bilinear` resize packed pixels to any size.
+//! Bilinear works in 8-bit fixed point, so neither touches the FPU.
+//!
+//! `show` puts one image centred on screen in the `Dialog` layer until
+//! `hide`, scaled down to fit first if it is larger than the screen.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::ui_provider::{
+    layers::{self, Layer},
+    shape::Rect,
+    theme::Theme,
+};
+use alloc::vec::Vec;
+use core::fmt;
+use spin::Mutex;
+
+/// Largest side accepted, so a bad header cannot ask for a huge buffer.
+pub const MAX_DIM: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// Neither `P6` nor `BM`.
+    UnknownFormat,
+    BadHeader,
+    /// A BMP that is compressed or not 24 bits per pixel, or a PPM with
+    /// 16-bit samples.
+    Unsupported,
+    TooLarge,
+    /// Fewer pixel bytes than the header promises.
+    Truncated,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::UnknownFormat => write!(f, "not a P6 PPM or a BMP"),
+            ImageError::BadHeader => write!(f, "malformed header"),
+            ImageError::Unsupported => {
+                write!(
+                    f,
+                    "only 8-bit PPM and uncompressed 24-bit BMP are supported"
+                )
+            }
+            ImageError::TooLarge => write!(f, "sides must be 1..={}", MAX_DIM),
+            ImageError::Truncated => write!(f, "pixel data cut short"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub w: usize,
+    pub h: usize,
+    /// Packed RGB888, row-major, top row first.
+    pub pixels: Vec<u32>,
+}
+
+fn pack(r: u8, g: u8, b: u8) -> u32 {
+    u32::from_be_bytes([0, r, g, b])
+}
+
+fn check_size(w: usize, h: usize) -> Result<(), ImageError> {
+    if (1..=MAX_DIM).contains(&w) && (1..=MAX_DIM).contains(&h) {
+        Ok(())
+    } else {
+        Err(ImageError::TooLarge)
+    }
+}
+
+/// Either format, by its magic bytes.
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    match bytes.get(..2) {
+        Some(b"P6") => decode_ppm(bytes),
+        Some(b"BM") => decode_bmp(bytes),
+        _ => Err(ImageError::UnknownFormat),
+    }
+}
+
+/// The next decimal header field of a PPM at `*pos`, skipping whitespace
+/// and comments before it.
+fn ppm_field(bytes: &[u8], pos: &mut usize) -> Result<usize, ImageError> {
+    loop {
+        match bytes.get(*pos) {
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(b'#') => {
+                while bytes.get(*pos).is_some_and(|&b| b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+        *pos += 1;
+    }
+    core::str::from_utf8(&bytes[start..*pos])
+        .ok()
+        .and_then(|digits| digits.parse().ok())
+        .ok_or(ImageError::BadHeader)
+}
+
+pub fn decode_ppm(bytes: &[u8]) -> Result<Image, ImageError> {
+    if !bytes.starts_with(b"P6") {
+        return Err(ImageError::UnknownFormat);
+    }
+    let mut pos = 2;
+    let w = ppm_field(bytes, &mut pos)?;
+    let h = ppm_field(bytes, &mut pos)?;
+    let max = ppm_field(bytes, &mut pos)?;
+    // Exactly one whitespace byte separates the header from the samples
+    if !bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        return Err(ImageError::BadHeader);
+    }
+    pos += 1;
+    match max {
+        0 => return Err(ImageError::BadHeader),
+        1..=255 => {}
+        _ => return Err(ImageError::Unsupported),
+    }
+    check_size(w, h)?;
+
+    let data = bytes
+        .get(pos..pos + w * h * 3)
+        .ok_or(ImageError::Truncated)?;
+    let scale = |v: u8| (usize::from(v).min(max) * 255 / max) as u8;
+    let pixels = data
+        .chunks_exact(3)
+        .map(|rgb| pack(scale(rgb[0]), scale(rgb[1]), scale(rgb[2])))
+        .collect();
+    Ok(Image { w, h, pixels })
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Result<u16, ImageError> {
+    let field = bytes.get(at..at + 2).ok_or(ImageError::BadHeader)?;
+    Ok(u16::from_le_bytes([field[0], field[1]]))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Result<u32, ImageError> {
+    let field = bytes.get(at..at + 4).ok_or(ImageError::BadHeader)?;
+    Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+pub fn decode_bmp(bytes: &[u8]) -> Result<Image, ImageError> {
+    if !bytes.starts_with(b"BM") {
+        return Err(ImageError::UnknownFormat);
+    }
+    let offset = le_u32(bytes, 10)? as usize;
+    if le_u32(bytes, 14)? < 40 || le_u16(bytes, 26)? != 1 {
+        return Err(ImageError::BadHeader);
+    }
+    if le_u16(bytes, 28)? != 24 || le_u32(bytes, 30)? != 0 {
+        return Err(ImageError::Unsupported);
+    }
+    let width = le_u32(bytes, 18)? as i32;
+    let height = le_u32(bytes, 22)? as i32;
+    if width < 0 {
+        return Err(ImageError::BadHeader);
+    }
+    // A negative height means the rows are stored top-down
+    let top_down = height < 0;
+    let (w, h) = (width as usize, height.unsigned_abs() as usize);
+    check_size(w, h)?;
+
+    let stride = (w * 3).div_ceil(4) * 4;
+    let data = bytes
+        .get(offset..)
+        .and_then(|data| data.get(..stride * (h - 1) + w * 3))
+        .ok_or(ImageError::Truncated)?;
+    let mut pixels = Vec::with_capacity(w * h);
+    for row in 0..h {
+        let stored = if top_down { row } else { h - 1 - row };
+        let line = &data[stored * stride..stored * stride + w * 3];
+        pixels.extend(line.chunks_exact(3).map(|bgr| pack(bgr[2], bgr[1], bgr[0])));
+    }
+    Ok(Image { w, h, pixels })
+}
+
+impl Image {
+    /// A `w` x `h` copy, smoothed with `scale_bilinear`.
+    pub fn scaled(&self, w: usize, h: usize) -> Image {
+        Image {
+            w,
+            h,
+            pixels: scale_bilinear(&self.pixels, self.w, self.h, w, h),
+        }
+    }
+
+    /// A copy no larger than `max_w` x `max_h`, keeping the aspect ratio,
+    /// by nearest neighbour. An image that already fits is copied as is.
+    pub fn fit(&self, max_w: usize, max_h: usize) -> Image {
+        if self.w <= max_w && self.h <= max_h {
+            return self.clone();
+        }
+        // Scale by the tighter of the two ratios, compared without floats
+        let (w, h) = if max_w * self.h <= max_h * self.w {
+            (max_w, (self.h * max_w / self.w).max(1))
+        } else {
+            ((self.w * max_h / self.h).max(1), max_h)
+        };
+        Image {
+            w,
+            h,
+            pixels: scale_nearest(&self.pixels, self.w, self.h, w, h),
+        }
+    }
+}
+
+/// Whether `src` holds a `w` x `h` image and `dst_w` x `dst_h` is not
+/// empty.
+fn scalable(src: &[u32], w: usize, h: usize, dst_w: usize, dst_h: usize) -> bool {
+    w > 0 && h > 0 && dst_w > 0 && dst_h > 0 && w.checked_mul(h).is_some_and(|n| src.len() >= n)
+}
+
+/// `src` (`src_w` x `src_h`, packed RGB888) resized to `dst_w` x `dst_h`
+/// by repeating or dropping pixels. Empty if either size is empty or `src`
+/// is short.
+pub fn scale_nearest(
+    src: &[u32],
+    src_w: usize,
+    src_h: usize,
+    dst_w: usize,
+    dst_h: usize,
+) -> Vec<u32> {
+    if !scalable(src, src_w, src_h, dst_w, dst_h) {
+        return Vec::new();
+    }
+    let mut out = Vec::with_capacity(dst_w * dst_h);
+    for y in 0..dst_h {
+        let row = &src[y * src_h / dst_h * src_w..];
+        out.extend((0..dst_w).map(|x| row[x * src_w / dst_w]));
+    }
+    out
+}
+
+/// Where destination pixel `i` of `dst` samples a source of `src` pixels,
+/// in 1/256ths, centre to centre: the left source pixel and the weight of
+/// the one after it.
+fn bilinear_tap(i: usize, src: usize, dst: usize) -> (usize, usize) {
+    let pos = ((2 * i + 1) * src * 256 / (2 * dst)).saturating_sub(128);
+    let pos = pos.min((src - 1) * 256);
+    (pos / 256, pos % 256)
+}
+
+/// `a` and `b` mixed per channel, `b` weighing `t`/256. Kept in 1/256ths
+/// of a channel so the second pass does not lose the first's fraction.
+fn lerp_channels(a: u32, b: u32, t: usize) -> [usize; 3] {
+    let [_, ar, ag, ab] = a.to_be_bytes();
+    let [_, br, bg, bb] = b.to_be_bytes();
+    let mix = |a: u8, b: u8| usize::from(a) * (256 - t) + usize::from(b) * t;
+    [mix(ar, br), mix(ag, bg), mix(ab, bb)]
+}
+
+/// `scale_nearest`, but each destination pixel mixes the four source
+/// pixels around where it falls. Integer arithmetic throughout.
+pub fn scale_bilinear(
+    src: &[u32],
+    src_w: usize,
+    src_h: usize,
+    dst_w: usize,
+    dst_h: usize,
+) -> Vec<u32> {
+    if !scalable(src, src_w, src_h, dst_w, dst_h) {
+        return Vec::new();
+    }
+    let taps: Vec<(usize, usize)> = (0..dst_w).map(|x| bilinear_tap(x, src_w, dst_w)).collect();
+    let mut out = Vec::with_capacity(dst_w * dst_h);
+    for y in 0..dst_h {
+        let (sy, ty) = bilinear_tap(y, src_h, dst_h);
+        let top = &src[sy * src_w..];
+        let bottom = &src[(sy + 1).min(src_h - 1) * src_w..];
+        for &(sx, tx) in &taps {
+            let next = (sx + 1).min(src_w - 1);
+            let upper = lerp_channels(top[sx], top[next], tx);
+            let lower = lerp_channels(bottom[sx], bottom[next], tx);
+            let channel = |i: usize| ((upper[i] * (256 - ty) + lower[i] * ty + 32_768) >> 16) as u8;
+            out.push(pack(channel(0), channel(1), channel(2)));
+        }
+    }
+    out
+}
+
+static SHOWN: Mutex<Option<Image>> = Mutex::new(None);
+
+/// Shows `image` centred on a `screen_w` x `screen_h` screen until `hide`,
+/// replacing any image already shown.
+pub fn show(image: &Image, screen_w: usize, screen_h: usize) {
+    *SHOWN.lock() = Some(image.fit(screen_w, screen_h));
+    layers::register(Layer::Dialog, "image", draw);
+}
+
+/// Returns whether an image was shown.
+pub fn hide() -> bool {
+    layers::unregister("image");
+    SHOWN.lock().take().is_some()
+}
+
+fn draw(fb: &mut FramebufferWriter, _theme: &Theme) -> Option<Rect> {
+    let shown = SHOWN.lock();
+    let image = shown.as_ref()?;
+    let x = fb.width.saturating_sub(image.w) / 2;
+    let y = fb.height.saturating_sub(image.h) / 2;
+    fb.blit(x, y, image.w, image.h, &image.pixels);
+    Some(Rect::new(x, y, image.w, image.h))
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `w` x `h` 24-bit BMP with the given file rows (bottom row first),
+    /// each padded to four bytes.
+    fn bmp(w: u32, h: i32, rows: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(b"BM");
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&54u32.to_le_bytes());
+        out.extend_from_slice(&40u32.to_le_bytes());
+        out.extend_from_slice(&w.to_le_bytes());
+        out.extend_from_slice(&h.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&24u16.to_le_bytes());
+        out.extend_from_slice(&[0; 24]);
+        for row in rows {
+            out.extend_from_slice(row);
+            out.resize(out.len() + (4 - row.len() % 4) % 4, 0);
+        }
+        out
+    }
+
+    #[test_case]
+    fn both_formats_decode_top_row_first() {
+        let ppm = b"P6\n# made by hand\n2 1\n255\n\xff\x00\x00\x00\x00\xff";
+        let image = decode(ppm).unwrap();
+        assert_eq!((image.w, image.h), (2, 1));
+        assert_eq!(image.pixels, [0xff0000, 0x0000ff]);
+        // Samples scale up from a smaller maximum
+        assert_eq!(
+            decode(b"P6 1 1 15 \x0f\x00\x05").unwrap().pixels,
+            [0xff0055]
+        );
+
+        // Red over blue, stored blue row first; 2 * 3 bytes pad to 8
+        let red = [0, 0, 0xff, 0, 0, 0xff];
+        let blue = [0xff, 0, 0, 0xff, 0, 0];
+        let image = decode(&bmp(2, 2, &[&blue, &red])).unwrap();
+        assert_eq!(image.pixels, [0xff0000, 0xff0000, 0x0000ff, 0x0000ff]);
+        let top_down = decode(&bmp(2, -2, &[&red, &blue])).unwrap();
+        assert_eq!(top_down, image);
+    }
+
+    #[test_case]
+    fn malformed_images_are_errors() {
+        assert_eq!(decode(b"GIF89a"), Err(ImageError::UnknownFormat));
+        assert_eq!(decode(b"P6 2"), Err(ImageError::BadHeader));
+        assert_eq!(decode(b"P6 2 x 255 "), Err(ImageError::BadHeader));
+        assert_eq!(decode(b"P6 1 1 65535 \0\0"), Err(ImageError::Unsupported));
+        assert_eq!(decode(b"P6 0 1 255 "), Err(ImageError::TooLarge));
+        assert_eq!(decode(b"P6 2 2 255 \0\0\0"), Err(ImageError::Truncated));
+        assert_eq!(decode(b"BM\0\0"), Err(ImageError::BadHeader));
+
+        let mut eight_bit = bmp(1, 1, &[&[0, 0, 0]]);
+        eight_bit[28] = 8;
+        assert_eq!(decode(&eight_bit), Err(ImageError::Unsupported));
+        let mut short = bmp(2, 2, &[&[0; 6], &[0; 6]]);
+        short.truncate(60);
+        assert_eq!(decode(&short), Err(ImageError::Truncated));
+    }
+
+    #[test_case]
+    fn large_images_shrink_to_fit() {
+        let image = Image {
+            w: 4,
+            h: 2,
+            pixels: (0..8).collect(),
+        };
+        let small = image.fit(2, 2);
+        assert_eq!((small.w, small.h), (2, 1));
+        assert_eq!(small.pixels, [0, 2]);
+        assert_eq!(image.fit(10, 10), image);
+        assert_eq!(image.fit(4, 1).pixels, [0, 2]);
+    }
+
+    #[test_case]
+    fn gradients_scale_up_exactly_and_smoothly() {
+        let gray = |v: u32| v * 0x010101;
+        // Dark to light left to right, and in blue top to bottom
+        let across = [gray(0), gray(255), gray(0), gray(255)];
+        let down = [0, 0, 0xff, 0xff];
+
+        let nearest = scale_nearest(&across, 2, 2, 4, 4);
+        for row in nearest.chunks(4) {
+            assert_eq!(row, [gray(0), gray(0), gray(255), gray(255)]);
+        }
+
+        let smooth = scale_bilinear(&across, 2, 2, 4, 4);
+        for row in smooth.chunks(4) {
+            assert_eq!(row, [gray(0), gray(64), gray(191), gray(255)]);
+        }
+        let smooth = scale_bilinear(&down, 2, 2, 4, 4);
+        let column: Vec<u32> = smooth.chunks(4).map(|row| row[3]).collect();
+        assert_eq!(column, [0, 64, 191, 255]);
+
+        // Same size is a copy; nothing to scale is nothing out
+        assert_eq!(scale_bilinear(&across, 2, 2, 2, 2), across);
+        assert!(scale_nearest(&across, 2, 3, 4, 4).is_empty());
+        assert!(scale_bilinear(&across, 2, 2, 0, 4).is_empty());
+    }
+}
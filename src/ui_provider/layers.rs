@@ -0,0 +1,352 @@
+//! # Overlay Layers
+//!
+//! Everything drawn over the composed apps, bar and tabs goes through
+//! `composite`, bottom to top in `Layer` order: the focused app's own
+//! overlay, selections, the focus ring, dialogs and pickers, toasts, the
+//! magnifier, and the mouse cursor last so nothing ever covers it.
+//!
+//! A layer never saves or restores the pixels under it. Apps may draw only
+//! what changed since their last frame, so what a layer drew would stay
+//! in the back buffer; instead `composite` keeps the rect each layer drew
+//! over, and the next frame starts by handing those back (`stale`): the
+//! background paints them again and so does every app under them
+//! (`App::invalidate`). A layer that stops drawing or moves is therefore
+//! gone from its old place in the next frame, whatever order layers come
+//! and go in. A feature that keeps its own copy of what it covered will
+//! put stale pixels back; draw into a layer instead.
+//!
+//! A layer's content comes from two places: the commands the focused app
+//! and `AppHost` collect for it, handed to `composite`, and draw functions
+//! registered with `register`. Each reports the rect it touched, and the
+//! last frame's damage per layer is kept for the `layers` command.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::ui_provider::{
+    render::{flush_commands, shadow_bounds, RenderCommand},
+    shape::Rect,
+    theme::Theme,
+};
+use alloc::{format, string::String, vec::Vec};
+use spin::Mutex;
+
+const CHAR_W: usize = 10;
+const CHAR_H: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// What the focused app draws over itself (the bell border).
+    AppOverlay,
+    Selection,
+    FocusRing,
+    /// Dialogs and pickers.
+    Dialog,
+    Toast,
+    Magnifier,
+    Cursor,
+}
+
+impl Layer {
+    /// Bottom to top.
+    pub const ALL: [Layer; 7] = [
+        Layer::AppOverlay,
+        Layer::Selection,
+        Layer::FocusRing,
+        Layer::Dialog,
+        Layer::Toast,
+        Layer::Magnifier,
+        Layer::Cursor,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::AppOverlay => "app overlay",
+            Layer::Selection => "selection",
+            Layer::FocusRing => "focus ring",
+            Layer::Dialog => "dialog",
+            Layer::Toast => "toast",
+            Layer::Magnifier => "magnifier",
+            Layer::Cursor => "cursor",
+        }
+    }
+}
+
+/// A registered layer source: draws, returns the rect it touched, or
+/// `None` if it drew nothing.
+pub type DrawFn = fn(&mut FramebufferWriter, &Theme) -> Option<Rect>;
+
+struct Source {
+    layer: Layer,
+    name: &'static str,
+    draw: DrawFn,
+}
+
+static SOURCES: Mutex<Vec<Source>> = Mutex::new(Vec::new());
+static DAMAGE: Mutex<[Option<Rect>; Layer::ALL.len()]> = Mutex::new([None; Layer::ALL.len()]);
+
+/// Adds `draw` to `layer`. A source already registered under `name` is
+/// replaced. Sources in one layer draw in registration order.
+pub fn register(layer: Layer, name: &'static str, draw: DrawFn) {
+    let mut sources = SOURCES.lock();
+    sources.retain(|source| source.name != name);
+    sources.push(Source { layer, name, draw });
+}
+
+/// Removes the source registered as `name`; its pixels are gone from the
+/// next frame.
+pub fn unregister(name: &'static str) {
+    SOURCES.lock().retain(|source| source.name != name);
+}
+
+/// The smallest rect covering both.
+pub fn union(a: Option<Rect>, b: Option<Rect>) -> Option<Rect> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+            Some(Rect::new(
+                x,
+                y,
+                a.right().max(b.right()) - x,
+                a.bottom().max(b.bottom()) - y,
+            ))
+        }
+        (rect, None) | (None, rect) => rect,
+    }
+}
+
+/// About the area `command` draws, before clipping to the screen.
+fn command_bounds(command: &RenderCommand, screen: Rect) -> Rect {
+    match command {
+        RenderCommand::Clear { .. } => screen,
+        RenderCommand::FillRect { rect, .. }
+        | RenderCommand::FillRoundedRect { rect, .. }
+        | RenderCommand::FillStyled { rect, .. }
+        | RenderCommand::StrokeRect { rect, .. } => *rect,
+        RenderCommand::Shadow { rect, blur, .. } => shadow_bounds(*rect, *blur),
+        RenderCommand::Text { text, x, y, .. } => {
+            Rect::new(*x, *y, text.chars().count() * CHAR_W, CHAR_H)
+        }
+    }
+}
+
+/// Draws every layer over what is in `fb`, bottom to top. `commands`
+/// gives the collected commands per layer (from `AppHost::layer_commands`);
+/// they draw before the layer's registered sources.
+pub fn composite(
+    fb: &mut FramebufferWriter,
+    theme: &Theme,
+    commands: &[(Layer, &[RenderCommand])],
+) {
+    let screen = Rect::new(0, 0, fb.width, fb.height);
+    // Copied out so a source may register or unregister while it draws
+    let sources: Vec<(Layer, DrawFn)> = SOURCES
+        .lock()
+        .iter()
+        .map(|source| (source.layer, source.draw))
+        .collect();
+    let mut damage = [None; Layer::ALL.len()];
+
+    for (slot, &layer) in damage.iter_mut().zip(Layer::ALL.iter()) {
+        for &(_, list) in commands.iter().filter(|&&(l, _)| l == layer) {
+            flush_commands(fb, list);
+            for command in list {
+                *slot = union(*slot, Some(command_bounds(command, screen)));
+            }
+        }
+        for &(_, draw) in sources.iter().filter(|&&(l, _)| l == layer) {
+            *slot = union(*slot, draw(fb, theme));
+        }
+    }
+    *DAMAGE.lock() = damage;
+}
+
+/// Everything the last `composite` drew over, to be painted again from
+/// below before the next one.
+pub fn stale() -> Vec<Rect> {
+    DAMAGE.lock().iter().flatten().copied().collect()
+}
+
+/// Per layer, bottom to top, what the last `composite` drew over.
+pub fn last_damage() -> [(Layer, Option<Rect>); Layer::ALL.len()] {
+    let damage = *DAMAGE.lock();
+    Layer::ALL.map(|layer| (layer, damage[layer as usize]))
+}
+
+/// Lines for the `layers` command.
+pub fn report() -> String {
+    let sources = SOURCES.lock();
+    let mut out = format!(
+        "{:<3}{:<13}{:<24}{}\n",
+        "", "Layer", "Sources", "Last damage"
+    );
+    for (layer, damage) in last_damage() {
+        let mut names: Vec<&str> = match layer {
+            Layer::AppOverlay | Layer::FocusRing | Layer::Dialog => alloc::vec!["app host"],
+            _ => Vec::new(),
+        };
+        names.extend(
+            sources
+                .iter()
+                .filter(|source| source.layer == layer)
+                .map(|source| source.name),
+        );
+        let names = if names.is_empty() {
+            String::from("-")
+        } else {
+            names.join(", ")
+        };
+        let damage = match damage {
+            Some(r) => format!("{}x{} at {},{}", r.w, r.h, r.x, r.y),
+            None => String::from("-"),
+        };
+        out.push_str(&format!(
+            "{:<3}{:<13}{:<24}{}\n",
+            layer as usize,
+            layer.name(),
+            names,
+            damage
+        ));
+    }
+    out
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::mouse_cursor;
+    use crate::ui_provider::{
+        color::Color,
+        render::RenderList,
+        widgets::{Panel, Widget},
+    };
+
+    const TOAST_RGB: u32 = 0x00c800;
+    const PICKER_RGB: u32 = 0x0000c8;
+    const TOAST: Rect = Rect {
+        x: 50,
+        y: 40,
+        w: 100,
+        h: 30,
+    };
+
+    const APP: Rect = Rect {
+        x: 20,
+        y: 20,
+        w: 160,
+        h: 80,
+    };
+
+    fn screen(w: usize, h: usize) -> FramebufferWriter {
+        let buffer: &'static mut [u8] = alloc::vec![0u8; w * h * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, w, h, w, 4);
+        paint(&mut fb, Rect::new(0, 0, w, h));
+        fb
+    }
+
+    /// Background, and an opaque app over part of it, within `rect`.
+    fn paint(fb: &mut FramebufferWriter, rect: Rect) {
+        fb.fill_rect(rect.x, rect.y, rect.w, rect.h, Color::new(10, 10, 10));
+        if let Some(app) = rect.intersect(APP) {
+            fb.fill_rect(app.x, app.y, app.w, app.h, Color::new(40, 40, 40));
+        }
+    }
+
+    fn toast(fb: &mut FramebufferWriter, _theme: &Theme) -> Option<Rect> {
+        let color = Color::from_hex(TOAST_RGB);
+        fb.fill_rect(TOAST.x, TOAST.y, TOAST.w, TOAST.h, color);
+        Some(TOAST)
+    }
+
+    /// A frame over an app that draws only what it is told to: nothing
+    /// is cleared, last frame's layer damage is painted again from below
+    /// (as `AppHost::draw_frame` has it done), then the layers go on.
+    fn frame(fb: &mut FramebufferWriter, theme: &Theme, picker: &RenderList) -> Vec<u32> {
+        for rect in stale() {
+            paint(fb, rect);
+        }
+        composite(fb, theme, &[(Layer::Dialog, picker.as_slice())]);
+        fb.snapshot()
+    }
+
+    #[test_case]
+    fn layers_stack_in_order_and_leave_nothing_behind() {
+        let theme = Theme::dark_modern();
+        let (w, h) = (200, 120);
+        let mut fb = screen(w, h);
+        mouse_cursor::init(w, h);
+        mouse_cursor::set_visible(true);
+        let (cx, cy) = mouse_cursor::screen_position();
+        register(Layer::Cursor, "test cursor", |fb, _| mouse_cursor::draw(fb));
+        register(Layer::Toast, "test toast", toast);
+
+        let mut picker = RenderList::new();
+        picker.fill_rect(Rect::new(40, 30, 120, 60), Color::from_hex(PICKER_RGB));
+        frame(&mut fb, &theme, &picker);
+
+        // Cursor over toast over picker, all at the centre
+        assert!(TOAST.x < cx && cx < TOAST.right() && TOAST.y < cy && cy < TOAST.bottom());
+        assert_eq!(fb.get_pixel(cx, cy), Color::BLACK);
+        assert_eq!(fb.get_pixel(cx + 1, cy + 2), Color::WHITE);
+        assert_eq!(
+            fb.get_pixel(TOAST.x + 2, TOAST.y + 2),
+            Color::from_hex(TOAST_RGB)
+        );
+        assert_eq!(fb.get_pixel(42, 32), Color::from_hex(PICKER_RGB));
+        let damage = last_damage();
+        assert_eq!(damage[Layer::Toast as usize].1, Some(TOAST));
+        assert_eq!(
+            damage[Layer::Dialog as usize].1,
+            Some(Rect::new(40, 30, 120, 60))
+        );
+        assert_eq!(damage[Layer::Selection as usize].1, None);
+
+        // Dismissed toast first, then the picker, with the cursor moving
+        // in between: each frame matches one drawn without them
+        mouse_cursor::update_position(-30, 10);
+        unregister("test toast");
+        let without_toast = frame(&mut fb, &theme, &picker);
+        let mut clean = screen(w, h);
+        unregister("test cursor");
+        frame(&mut clean, &theme, &picker);
+        register(Layer::Cursor, "test cursor", |fb, _| mouse_cursor::draw(fb));
+        mouse_cursor::draw(&mut clean);
+        assert!(without_toast == clean.snapshot());
+
+        let without_either = frame(&mut fb, &theme, &RenderList::new());
+        assert!(without_either
+            .iter()
+            .all(|&px| px != TOAST_RGB && px != PICKER_RGB));
+
+        // And the other order: picker back, toast back, picker gone first
+        register(Layer::Toast, "test toast", toast);
+        frame(&mut fb, &theme, &picker);
+        let toast_only = frame(&mut fb, &theme, &RenderList::new());
+        unregister("test toast");
+        let nothing = frame(&mut fb, &theme, &RenderList::new());
+        assert!(toast_only != nothing);
+        assert!(nothing == without_either);
+        assert_eq!(last_damage()[Layer::Toast as usize].1, None);
+
+        unregister("test cursor");
+        assert!(report().contains("focus ring"));
+    }
+
+    #[test_case]
+    fn a_shadow_goes_over_fresh_pixels_each_frame() {
+        let theme = Theme::dark_modern();
+        let (w, h) = (200, 120);
+        let mut fb = screen(w, h);
+        let mut dialog = RenderList::new();
+        Panel::new(Rect::new(10, 10, 100, 50))
+            .with_shadow(true)
+            .collect_render(&theme, false, &mut dialog);
+
+        let first = frame(&mut fb, &theme, &dialog);
+        assert!(frame(&mut fb, &theme, &dialog) == first);
+        assert!(frame(&mut fb, &theme, &dialog) == first);
+        // Closed, it takes its shadow with it
+        let closed = frame(&mut fb, &theme, &RenderList::new());
+        assert!(closed == screen(w, h).snapshot());
+    }
+}
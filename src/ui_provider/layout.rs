@@ -0,0 +1,109 @@
+//! # Screen Layout
+//!
+//! The top bar, then the tab row, then the apps, top to bottom. Every band
+//! is clamped to the framebuffer: a screen shorter than the bar and tabs
+//! gets thinner bands and an empty app area rather than rectangles that
+//! hang off the bottom. `check` confirms that at boot against the real
+//! dimensions, before anything is drawn with them.
+
+use crate::ui_provider::{shape::Rect, top_bar::TopBar};
+
+pub const TAB_COUNT: usize = 5;
+pub const TAB_NAMES: [&str; TAB_COUNT] = ["Terminal", "Logs", "Editor", "Settings", "Form"];
+const TAB_HEIGHT: usize = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UiLayout {
+    pub content_width: usize,
+    pub content_height: usize,
+    pub bar_height: usize,
+    pub tab_height: usize,
+}
+
+impl UiLayout {
+    pub fn from_framebuffer(width: usize, height: usize) -> Self {
+        let bar_height = TopBar::height_for(height).min(height);
+        let tab_height = TAB_HEIGHT.min(height - bar_height);
+        Self {
+            content_width: width,
+            content_height: height - bar_height - tab_height,
+            bar_height,
+            tab_height,
+        }
+    }
+
+    pub fn bar_bounds(&self) -> Rect {
+        Rect::new(0, 0, self.content_width, self.bar_height)
+    }
+
+    pub fn app_bounds(&self) -> Rect {
+        Rect::new(
+            0,
+            self.bar_height + self.tab_height,
+            self.content_width,
+            self.content_height,
+        )
+    }
+
+    pub fn tab_bounds(&self, index: usize) -> Rect {
+        let tab_width = self.content_width / TAB_COUNT;
+        let x = index * tab_width;
+        Rect::new(x, self.bar_height, tab_width, self.tab_height)
+    }
+
+    /// Whether every band fits a `width` x `height` framebuffer, stacks
+    /// without gaps, and has coordinates embedded-graphics can take.
+    pub fn check(&self, width: usize, height: usize) -> Result<(), &'static str> {
+        if i32::try_from(width).is_err() || i32::try_from(height).is_err() {
+            return Err("framebuffer larger than i32 coordinates");
+        }
+        let bar = self.bar_bounds();
+        let app = self.app_bounds();
+        if bar.y != 0 || bar.bottom() != self.tab_bounds(0).y {
+            return Err("tab row does not start under the top bar");
+        }
+        if self.tab_bounds(0).bottom() != app.y || app.bottom() != height {
+            return Err("apps do not fill the rest of the screen");
+        }
+        let last_tab = self.tab_bounds(TAB_COUNT - 1);
+        if bar.right() > width || app.right() > width || last_tab.right() > width {
+            return Err("a band is wider than the framebuffer");
+        }
+        Ok(())
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui_provider::shape::{to_i32_clamped, to_u32_clamped};
+
+    #[test_case]
+    fn extreme_framebuffers_lay_out_without_panicking() {
+        for (w, h) in [(100_000, 100_000), (1, 1), (0, 0), (1280, 720), (1, 30)] {
+            let layout = UiLayout::from_framebuffer(w, h);
+            assert_eq!(layout.check(w, h), Ok(()), "{}x{}", w, h);
+            for idx in 0..TAB_COUNT {
+                let tab = layout.tab_bounds(idx);
+                assert!(tab.right() <= w && tab.bottom() <= h, "{}x{}", w, h);
+            }
+        }
+
+        let tiny = UiLayout::from_framebuffer(1, 1);
+        assert_eq!((tiny.bar_height, tiny.tab_height), (1, 0));
+        assert_eq!(tiny.app_bounds(), Rect::new(0, 1, 1, 0));
+
+        let huge = UiLayout::from_framebuffer(100_000, 100_000);
+        assert_eq!(huge.bar_height, TopBar::height_for(100_000));
+        assert_eq!(huge.app_bounds().bottom(), 100_000);
+        assert!(UiLayout::from_framebuffer(usize::MAX, 1)
+            .check(usize::MAX, 1)
+            .is_err());
+
+        assert_eq!(to_i32_clamped(100_000), 100_000);
+        assert_eq!(to_i32_clamped(usize::MAX), i32::MAX);
+        assert_eq!(to_u32_clamped(usize::MAX), u32::MAX);
+    }
+}
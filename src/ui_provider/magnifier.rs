@@ -0,0 +1,193 @@
+//! # Magnifier
+//!
+//! Ctrl+Alt+M toggles a box in a screen corner showing the `SOURCE_W` x
+//! `SOURCE_H` pixels around the mouse cursor at `SCALE` times their size.
+//! What it shows is copied by `sample` once the apps, bar and tabs are in
+//! the back buffer and before any layer goes on, so it is fresh content:
+//! never the cursor, nor its own box (which the source region may overlap)
+//! or anything else a layer drew last frame. It is then drawn as the
+//! `Magnifier` layer (`ui_provider::layers`), under the cursor. The box
+//! moves to the opposite corner when the cursor comes near it.
+//!
+//! The enlarged copy goes in through `blit`, which only marks the tiles
+//! whose pixels change, so a still cursor over still content costs no
+//! screen writes.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::ui_provider::{
+    layers::{self, Layer},
+    shape::Rect,
+    theme::Theme,
+};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+pub const SOURCE_W: usize = 200;
+pub const SOURCE_H: usize = 150;
+pub const SCALE: usize = 2;
+const FRAME: usize = 2;
+/// Gap to the screen edge, and how close the cursor may come to the box.
+const MARGIN: usize = 16;
+pub const BOX_W: usize = SOURCE_W * SCALE + 2 * FRAME;
+pub const BOX_H: usize = SOURCE_H * SCALE + 2 * FRAME;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// This frame's source region from `sample`, already enlarged.
+static SAMPLE: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Returns whether the magnifier is now on. The layer is only registered
+/// while it is, so `layers` lists it then.
+pub fn toggle() -> bool {
+    let on = !ENABLED.fetch_xor(true, Ordering::Relaxed);
+    if on {
+        layers::register(Layer::Magnifier, "magnifier", draw);
+    } else {
+        layers::unregister("magnifier");
+    }
+    on
+}
+
+/// The box, frame included: in the bottom-right corner, or the top-left
+/// one while the cursor is near the bottom-right spot. `None` if the
+/// screen is too small to hold it.
+pub fn placement(screen_w: usize, screen_h: usize, cx: usize, cy: usize) -> Option<Rect> {
+    if screen_w < BOX_W + 2 * MARGIN || screen_h < BOX_H + 2 * MARGIN {
+        return None;
+    }
+    let (x, y) = (screen_w - MARGIN - BOX_W, screen_h - MARGIN - BOX_H);
+    let near = cx + MARGIN >= x && cy + MARGIN >= y;
+    Some(if near {
+        Rect::new(MARGIN, MARGIN, BOX_W, BOX_H)
+    } else {
+        Rect::new(x, y, BOX_W, BOX_H)
+    })
+}
+
+/// Top-left of the source region: centred on the cursor, kept on screen.
+pub fn source_origin(screen_w: usize, screen_h: usize, cx: usize, cy: usize) -> (usize, usize) {
+    let x = cx
+        .saturating_sub(SOURCE_W / 2)
+        .min(screen_w.saturating_sub(SOURCE_W));
+    let y = cy
+        .saturating_sub(SOURCE_H / 2)
+        .min(screen_h.saturating_sub(SOURCE_H));
+    (x, y)
+}
+
+/// Nearest-neighbour enlargement of a `w * h` row-major image.
+pub fn scale_nearest(src: &[u32], w: usize, h: usize, factor: usize) -> Vec<u32> {
+    let out_w = w * factor;
+    let mut out = Vec::with_capacity(out_w * h * factor);
+    for row in src.chunks_exact(w).take(h) {
+        let start = out.len();
+        for &px in row {
+            out.extend(core::iter::repeat_n(px, factor));
+        }
+        for _ in 1..factor {
+            out.extend_from_within(start..start + out_w);
+        }
+    }
+    out
+}
+
+fn cursor() -> (usize, usize) {
+    let (mx, my) = crate::devices::mouse_cursor::get_position();
+    (mx.max(0) as usize, my.max(0) as usize)
+}
+
+/// Copies the region around the cursor for this frame's box, if the
+/// magnifier is on. Called before `layers::composite`.
+pub fn sample(fb: &FramebufferWriter) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let (cx, cy) = cursor();
+    let (sx, sy) = source_origin(fb.width, fb.height, cx, cy);
+    let source = fb.snapshot_rect(sx, sy, SOURCE_W, SOURCE_H);
+    *SAMPLE.lock() = scale_nearest(&source, SOURCE_W, SOURCE_H, SCALE);
+}
+
+/// Draws the box if the magnifier is on and `sample` ran this frame,
+/// framed in the theme accent, and returns where.
+pub fn draw(fb: &mut FramebufferWriter, theme: &Theme) -> Option<Rect> {
+    let scaled = core::mem::take(&mut *SAMPLE.lock());
+    if !ENABLED.load(Ordering::Relaxed) || scaled.is_empty() {
+        return None;
+    }
+    let (cx, cy) = cursor();
+    let dst = placement(fb.width, fb.height, cx, cy)?;
+    let frame_color = theme.accent;
+
+    let (x1, y1) = (dst.x + dst.w, dst.y + dst.h);
+    fb.draw_rect(dst.x, dst.y, x1, dst.y + FRAME, frame_color);
+    fb.draw_rect(dst.x, y1 - FRAME, x1, y1, frame_color);
+    fb.draw_rect(dst.x, dst.y, dst.x + FRAME, y1, frame_color);
+    fb.draw_rect(x1 - FRAME, dst.y, x1, y1, frame_color);
+    fb.blit(
+        dst.x + FRAME,
+        dst.y + FRAME,
+        SOURCE_W * SCALE,
+        SOURCE_H * SCALE,
+        &scaled,
+    );
+    Some(dst)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn scale_repeats_each_pixel_in_both_directions() {
+        let scaled = scale_nearest(&[1, 2, 3, 4], 2, 2, 2);
+        assert_eq!(scaled, [1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]);
+        assert_eq!(scale_nearest(&[7, 8], 2, 1, 1), [7, 8]);
+    }
+
+    #[test_case]
+    fn box_avoids_the_cursor() {
+        let (w, h) = (1280, 800);
+        let corner = placement(w, h, 100, 100).unwrap();
+        assert_eq!(
+            (corner.x, corner.y),
+            (w - MARGIN - BOX_W, h - MARGIN - BOX_H)
+        );
+        let moved = placement(w, h, w - 50, h - 50).unwrap();
+        assert_eq!((moved.x, moved.y), (MARGIN, MARGIN));
+        assert!(placement(400, 300, 0, 0).is_none());
+    }
+
+    #[test_case]
+    fn source_stays_on_screen() {
+        assert_eq!(source_origin(1280, 800, 640, 400), (540, 325));
+        assert_eq!(source_origin(1280, 800, 10, 10), (0, 0));
+        assert_eq!(source_origin(1280, 800, 1279, 799), (1080, 650));
+    }
+
+    #[test_case]
+    fn shows_what_was_there_before_the_layers() {
+        use crate::ui_provider::color::Color;
+
+        let (w, h) = (640, 480);
+        let buffer: &'static mut [u8] = alloc::vec![0u8; w * h * 4].leak();
+        let mut fb = FramebufferWriter::from_raw(buffer, w, h, w, 4);
+        crate::devices::mouse_cursor::init(w, h);
+        assert!(toggle());
+
+        fb.clear(Color::from_hex(0x204060));
+        sample(&fb);
+        // Layers, this box last frame among them, go on after the sample
+        fb.clear(Color::from_hex(0xff00ff));
+        let dst = draw(&mut fb, &Theme::dark_modern()).unwrap();
+        assert_eq!(
+            fb.get_pixel(dst.x + FRAME + 1, dst.y + FRAME + 1),
+            Color::from_hex(0x204060)
+        );
+        // Nothing sampled, nothing drawn
+        assert!(draw(&mut fb, &Theme::dark_modern()).is_none());
+        assert!(!toggle());
+    }
+}
@@ -1,4 +1,17 @@
+pub mod background;
+pub mod bell;
 pub mod color;
+pub mod frame_arena;
+pub mod glyphs;
+pub mod image;
+pub mod layers;
+pub mod layout;
+pub mod magnifier;
+pub mod pacing;
+pub mod progress;
 pub mod render;
 pub mod shape;
 pub mod theme;
+pub mod toast;
+pub mod top_bar;
+pub mod widgets;
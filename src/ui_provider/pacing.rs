@@ -0,0 +1,186 @@
+//! # Frame Pacing
+//!
+//! Caps how often the compositor presents frames that only animation asked
+//! for. A frame is input-driven when a key, mouse event or explicit redraw
+//! request is pending; those present at once, whatever the cap, so input
+//! latency does not change. A frame that only timer ticks asked for waits
+//! until a full frame interval has passed since the last present, and the
+//! ticks that arrive meanwhile are dispatched together in that one frame.
+//!
+//! Nothing spins while a frame waits. On `Pace::Defer` the main loop
+//! keeps the ticks, halts as usual and decides again when the next
+//! interrupt wakes it. There is no one-shot timer to program for the
+//! deadline, so with the PIT as the only periodic interrupt a held frame
+//! goes out at the first tick after it.
+
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpsCap {
+    Off,
+    Hz30,
+    Hz60,
+}
+
+impl FpsCap {
+    /// Shortest gap between animation-only presents.
+    pub fn interval_ms(self) -> Option<u64> {
+        match self {
+            FpsCap::Off => None,
+            FpsCap::Hz30 => Some(1_000 / 30),
+            FpsCap::Hz60 => Some(1_000 / 60),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FpsCap::Off => "off",
+            FpsCap::Hz30 => "30",
+            FpsCap::Hz60 => "60",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(FpsCap::Off),
+            "30" => Some(FpsCap::Hz30),
+            "60" => Some(FpsCap::Hz60),
+            _ => None,
+        }
+    }
+}
+
+static CAP: AtomicU8 = AtomicU8::new(FpsCap::Off as u8);
+static PRESENTS_PER_SEC: AtomicU64 = AtomicU64::new(0);
+static COALESCED: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_cap(cap: FpsCap) {
+    CAP.store(cap as u8, Ordering::Relaxed);
+}
+
+pub fn cap() -> FpsCap {
+    match CAP.load(Ordering::Relaxed) {
+        1 => FpsCap::Hz30,
+        2 => FpsCap::Hz60,
+        _ => FpsCap::Off,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    Present,
+    /// Hold the frame until `until_ms`.
+    Defer {
+        until_ms: u64,
+    },
+}
+
+#[derive(Default)]
+pub struct FramePacer {
+    last_present_ms: Option<u64>,
+    window_start_ms: u64,
+    window_presents: u64,
+    /// A frame is being held back.
+    holding: bool,
+}
+
+impl FramePacer {
+    pub const fn new() -> Self {
+        Self {
+            last_present_ms: None,
+            window_start_ms: 0,
+            window_presents: 0,
+            holding: false,
+        }
+    }
+
+    /// Whether to present now. Every `Defer` of a frame that was already
+    /// held counts as one coalesced update.
+    pub fn decide(&mut self, now_ms: u64, cap: FpsCap, input_driven: bool) -> Pace {
+        let due = match (cap.interval_ms(), self.last_present_ms) {
+            (Some(interval), Some(last)) if !input_driven => last + interval,
+            _ => return Pace::Present,
+        };
+        if now_ms >= due {
+            return Pace::Present;
+        }
+        if self.holding {
+            COALESCED.fetch_add(1, Ordering::Relaxed);
+        }
+        self.holding = true;
+        Pace::Defer { until_ms: due }
+    }
+
+    /// Records a present and, once a second, the rate over that second.
+    pub fn presented(&mut self, now_ms: u64) {
+        self.last_present_ms = Some(now_ms);
+        self.holding = false;
+        self.window_presents += 1;
+        let elapsed = now_ms.saturating_sub(self.window_start_ms);
+        if elapsed >= 1_000 {
+            PRESENTS_PER_SEC.store(self.window_presents * 1_000 / elapsed, Ordering::Relaxed);
+            self.window_start_ms = now_ms;
+            self.window_presents = 0;
+        }
+    }
+}
+
+pub fn report() -> alloc::string::String {
+    alloc::format!(
+        "Presents: {}/s, {} animation updates coalesced, cap {}",
+        PRESENTS_PER_SEC.load(Ordering::Relaxed),
+        COALESCED.load(Ordering::Relaxed),
+        match cap() {
+            FpsCap::Off => alloc::string::String::from("off"),
+            other => alloc::format!("{} Hz", other.name()),
+        }
+    )
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn animation_frames_wait_for_the_interval() {
+        let mut pacer = FramePacer::new();
+        assert_eq!(pacer.decide(0, FpsCap::Hz30, false), Pace::Present);
+        pacer.presented(0);
+
+        assert_eq!(
+            pacer.decide(10, FpsCap::Hz30, false),
+            Pace::Defer { until_ms: 33 }
+        );
+        assert_eq!(
+            pacer.decide(20, FpsCap::Hz30, false),
+            Pace::Defer { until_ms: 33 }
+        );
+        assert_eq!(pacer.decide(33, FpsCap::Hz30, false), Pace::Present);
+        pacer.presented(33);
+
+        // 60 Hz halves the wait, off never waits
+        assert_eq!(
+            pacer.decide(40, FpsCap::Hz60, false),
+            Pace::Defer { until_ms: 49 }
+        );
+        assert_eq!(pacer.decide(40, FpsCap::Off, false), Pace::Present);
+    }
+
+    #[test_case]
+    fn input_always_presents() {
+        let mut pacer = FramePacer::new();
+        pacer.presented(100);
+        assert_eq!(pacer.decide(101, FpsCap::Hz30, true), Pace::Present);
+    }
+
+    #[test_case]
+    fn rate_is_measured_per_second() {
+        let mut pacer = FramePacer::new();
+        for frame in 1..=30 {
+            pacer.presented(frame * 1_000 / 30);
+        }
+        assert_eq!(PRESENTS_PER_SEC.load(Ordering::Relaxed), 30);
+    }
+}
@@ -0,0 +1,82 @@
+//! # Progress
+//!
+//! Where a long operation reports how far it has got. There is one slot:
+//! `begin` claims it (replacing whatever had it), `set` moves it on and
+//! `finish` clears it. The terminal polls `generation` on every tick and
+//! shows the slot as a `ProgressBar` along its bottom edge while it is
+//! taken.
+//!
+//! The UI only draws between main loop rounds, so a command that runs to
+//! the end in one go is never seen part way. Reporters are tasks (or
+//! jobs) that do a bounded amount per step, like `demo_progress`.
+
+use alloc::string::String;
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub label: String,
+    pub done: u32,
+    pub total: u32,
+}
+
+static CURRENT: Mutex<Option<Report>> = Mutex::new(None);
+/// Bumped on every change, so pollers can tell without comparing.
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+pub fn begin(label: &str, total: u32) {
+    *CURRENT.lock() = Some(Report {
+        label: String::from(label),
+        done: 0,
+        total,
+    });
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Clamped to the total. Does nothing when no operation has begun.
+pub fn set(done: u32) {
+    if let Some(report) = CURRENT.lock().as_mut() {
+        report.done = done.min(report.total);
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn finish() {
+    if CURRENT.lock().take().is_some() {
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn current() -> Option<Report> {
+    CURRENT.lock().clone()
+}
+
+pub fn generation() -> u32 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn changes_bump_the_generation() {
+        finish();
+        let start = generation();
+        set(3);
+        assert_eq!(generation(), start);
+
+        begin("copy", 10);
+        set(4);
+        set(40);
+        assert_eq!(current().map(|r| r.done), Some(10));
+        finish();
+        assert_eq!(current(), None);
+        assert_eq!(generation(), start + 4);
+        finish();
+        assert_eq!(generation(), start + 4);
+    }
+}
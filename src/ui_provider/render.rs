@@ -1,6 +1,6 @@
 use crate::devices::framebuffer::framebuffer::FramebufferWriter;
-use crate::ui_provider::{color::Color, shape::Rect};
-use alloc::{string::String, vec::Vec};
+use crate::ui_provider::{color::Color, frame_arena::TextBuf, glyphs, shape::Rect};
+use alloc::vec::Vec;
 use embedded_graphics::{
     mono_font::{ascii::FONT_10X20, MonoTextStyle, MonoTextStyleBuilder},
     pixelcolor::Rgb888,
@@ -12,6 +12,8 @@ const DEFAULT_BASELINE_OFFSET: usize = 16;
 pub struct TextStyle {
     pub fg: Color,
     pub baseline_offset: usize,
+    /// Drawn from the synthesized bold masks in `glyphs`.
+    pub bold: bool,
 }
 
 impl TextStyle {
@@ -19,9 +21,15 @@ impl TextStyle {
         Self {
             fg,
             baseline_offset: DEFAULT_BASELINE_OFFSET,
+            bold: false,
         }
     }
 
+    pub const fn bold(mut self, bold: bool) -> Self {
+        self.bold = bold;
+        self
+    }
+
     pub const fn with_baseline_offset(mut self, baseline_offset: usize) -> Self {
         self.baseline_offset = baseline_offset;
         self
@@ -35,6 +43,47 @@ impl TextStyle {
     }
 }
 
+/// How a shape is filled. Offsets are from the shape's top left, so a
+/// gradient runs across the whole shape however much of it is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fill {
+    Solid(Color),
+    /// Top to bottom.
+    VerticalGradient(Color, Color),
+    /// Left to right.
+    HorizontalGradient(Color, Color),
+    /// Squares `size` pixels on a side, the first colour at the top left.
+    #[allow(dead_code)] // nothing draws one yet
+    Checkerboard(Color, Color, usize),
+}
+
+impl Fill {
+    /// The colour at `x`, `y` in a `w` x `h` shape.
+    pub fn color_at(&self, x: usize, y: usize, w: usize, h: usize) -> Color {
+        match *self {
+            Fill::Solid(color) => color,
+            Fill::VerticalGradient(top, bottom) => gradient_color(top, bottom, y, h),
+            Fill::HorizontalGradient(left, right) => gradient_color(left, right, x, w),
+            Fill::Checkerboard(a, b, size) => {
+                let size = size.max(1);
+                if (x / size + y / size).is_multiple_of(2) {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+}
+
+/// Step `i` of `steps` going from `from` to `to`, both ends included.
+pub fn gradient_color(from: Color, to: Color, i: usize, steps: usize) -> Color {
+    let span = steps.saturating_sub(1).max(1) as i32;
+    let i = i.min(span as usize) as i32;
+    let mix = |a: u8, b: u8| (i32::from(a) + (i32::from(b) - i32::from(a)) * i / span) as u8;
+    Color::new(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RenderCommand {
     Clear {
@@ -49,13 +98,25 @@ pub enum RenderCommand {
         radius: usize,
         color: Color,
     },
+    /// `FillRoundedRect` with a `Fill` that is not `Solid`.
+    FillStyled {
+        rect: Rect,
+        radius: usize,
+        fill: Fill,
+    },
     StrokeRect {
         rect: Rect,
         color: Color,
         thickness: usize,
     },
+    /// A drop shadow for `rect`; see `draw_shadow`.
+    Shadow {
+        rect: Rect,
+        blur: usize,
+        color: Color,
+    },
     Text {
-        text: String,
+        text: TextBuf,
         x: usize,
         y: usize,
         style: TextStyle,
@@ -75,7 +136,7 @@ impl RenderCommand {
         }
     }
 
-    pub fn text(text: impl Into<String>, x: usize, y: usize, color: Color) -> Self {
+    pub fn text(text: impl Into<TextBuf>, x: usize, y: usize, color: Color) -> Self {
         Self::Text {
             text: text.into(),
             x,
@@ -84,7 +145,7 @@ impl RenderCommand {
         }
     }
 
-    pub fn styled_text(text: impl Into<String>, x: usize, y: usize, style: TextStyle) -> Self {
+    pub fn styled_text(text: impl Into<TextBuf>, x: usize, y: usize, style: TextStyle) -> Self {
         Self::Text {
             text: text.into(),
             x,
@@ -153,6 +214,19 @@ impl RenderList {
         self.push(RenderCommand::FillRoundedRect { rect, radius, color });
     }
 
+    /// A rounded rect filled with `fill`. Solid fills go as a plain
+    /// `FillRoundedRect`, which is cheaper to draw.
+    pub fn fill_styled(&mut self, rect: Rect, radius: usize, fill: Fill) {
+        match fill {
+            Fill::Solid(color) => self.fill_rounded_rect(rect, radius, color),
+            fill => self.push(RenderCommand::FillStyled { rect, radius, fill }),
+        }
+    }
+
+    pub fn shadow(&mut self, rect: Rect, blur: usize, color: Color) {
+        self.push(RenderCommand::Shadow { rect, blur, color });
+    }
+
     pub fn stroke_rect(&mut self, rect: Rect, color: Color, thickness: usize) {
         self.push(RenderCommand::StrokeRect {
             rect,
@@ -161,13 +235,13 @@ impl RenderList {
         });
     }
 
-    pub fn text(&mut self, text: impl Into<String>, x: usize, y: usize, color: Color) {
+    pub fn text(&mut self, text: impl Into<TextBuf>, x: usize, y: usize, color: Color) {
         self.push(RenderCommand::text(text, x, y, color));
     }
 
     pub fn styled_text(
         &mut self,
-        text: impl Into<String>,
+        text: impl Into<TextBuf>,
         x: usize,
         y: usize,
         style: TextStyle,
@@ -202,6 +276,9 @@ pub fn execute_command(fb: &mut FramebufferWriter, command: &RenderCommand) {
         RenderCommand::FillRoundedRect { rect, radius, color } => {
             fill_rounded_rect(fb, *rect, *radius, *color);
         }
+        RenderCommand::FillStyled { rect, radius, fill } => {
+            fill_rounded(fb, *rect, *radius, fill);
+        }
         RenderCommand::StrokeRect {
             rect,
             color,
@@ -209,23 +286,37 @@ pub fn execute_command(fb: &mut FramebufferWriter, command: &RenderCommand) {
         } => {
             draw_stroke_rect(fb, *rect, *color, *thickness);
         }
+        RenderCommand::Shadow { rect, blur, color } => {
+            draw_shadow(fb, *rect, *blur, *color);
+        }
         RenderCommand::Text { text, x, y, style } => {
             if text.is_empty() {
                 return;
             }
             let draw_y = y.saturating_add(style.baseline_offset);
-            fb.draw_text(text, *x, draw_y, &style.mono_style());
+            if style.bold {
+                glyphs::draw_bold(fb, text, *x, draw_y, style.fg);
+            } else {
+                fb.draw_text(text, *x, draw_y, &style.mono_style());
+            }
         }
     }
 }
 
-/// Filled rounded rectangle (quarter-circle corners, axis-aligned).
+/// Filled rounded rectangle (quarter-circle corners, axis-aligned), with
+/// anti-aliased corners. Radius 0 is a plain fill.
 pub fn fill_rounded_rect(
     fb: &mut FramebufferWriter,
     rect: Rect,
     radius: usize,
     color: Color,
 ) {
+    fill_rounded(fb, rect, radius, &Fill::Solid(color));
+}
+
+/// `rect` with corners of `radius` filled with `fill`; the corners clip
+/// gradients and patterns the same as they do a solid colour.
+pub fn fill_rounded(fb: &mut FramebufferWriter, rect: Rect, radius: usize, fill: &Fill) {
     let w = rect.w;
     let h = rect.h;
     if w == 0 || h == 0 {
@@ -236,66 +327,175 @@ pub fn fill_rounded_rect(
     let y = rect.y;
 
     if r == 0 {
-        fb.fill_rect(x, y, w, h, color);
+        fill_part(fb, rect, rect, fill);
         return;
     }
 
     let mid_w = w.saturating_sub(2 * r);
     let mid_h = h.saturating_sub(2 * r);
 
+    // Edges past `usize::MAX` saturate; that far out nothing is on screen.
     if mid_w > 0 {
-        fb.fill_rect(x + r, y, mid_w, h, color);
+        fill_part(fb, rect, Rect::new(x.saturating_add(r), y, mid_w, h), fill);
     }
     if mid_h > 0 {
-        fb.fill_rect(x, y + r, w, mid_h, color);
+        fill_part(fb, rect, Rect::new(x, y.saturating_add(r), w, mid_h), fill);
     }
 
-    let r2 = (r * r) as i32;
+    let (left, top) = (x.saturating_add(r), y.saturating_add(r));
+    let (right, bottom) = (x.saturating_add(w - r), y.saturating_add(h - r));
+    fill_corner(fb, rect, (x, y), (left, top), r, fill);
+    fill_corner(fb, rect, (right, y), (right, top), r, fill);
+    fill_corner(fb, rect, (x, bottom), (left, bottom), r, fill);
+    fill_corner(fb, rect, (right, bottom), (right, bottom), r, fill);
+}
 
-    // Top-left
-    let cx = (x + r) as i32;
-    let cy = (y + r) as i32;
-    for py in y..y + r {
-        for px in x..x + r {
-            let dx = px as i32 - cx;
-            let dy = py as i32 - cy;
-            if dx * dx + dy * dy <= r2 {
-                fb.put_pixel(px, py, color);
+/// The `part` of `shape` filled as `fill` fills `shape`: one `fill_rect`
+/// per gradient row or column, or per checkerboard square.
+fn fill_part(fb: &mut FramebufferWriter, shape: Rect, part: Rect, fill: &Fill) {
+    let part = Rect::new(
+        part.x,
+        part.y,
+        part.w.min(fb.width.saturating_sub(part.x)),
+        part.h.min(fb.height.saturating_sub(part.y)),
+    );
+    if part.w == 0 || part.h == 0 {
+        return;
+    }
+    match *fill {
+        Fill::Solid(color) => fb.fill_rect(part.x, part.y, part.w, part.h, color),
+        Fill::VerticalGradient(top, bottom) => {
+            for y in part.y..part.bottom() {
+                let color = gradient_color(top, bottom, y - shape.y, shape.h);
+                fb.fill_rect(part.x, y, part.w, 1, color);
             }
         }
-    }
-    // Top-right
-    let cx = (x + w - r) as i32;
-    for py in y..y + r {
-        for px in (x + w - r)..(x + w) {
-            let dx = px as i32 - cx;
-            let dy = py as i32 - cy;
-            if dx * dx + dy * dy <= r2 {
-                fb.put_pixel(px, py, color);
+        Fill::HorizontalGradient(left, right) => {
+            for x in part.x..part.right() {
+                let color = gradient_color(left, right, x - shape.x, shape.w);
+                fb.fill_rect(x, part.y, 1, part.h, color);
+            }
+        }
+        Fill::Checkerboard(_, _, size) => {
+            let size = size.max(1);
+            let mut y = part.y;
+            while y < part.bottom() {
+                let y_end = (shape.y + ((y - shape.y) / size + 1) * size).min(part.bottom());
+                let mut x = part.x;
+                while x < part.right() {
+                    let x_end = (shape.x + ((x - shape.x) / size + 1) * size).min(part.right());
+                    let color = fill.color_at(x - shape.x, y - shape.y, shape.w, shape.h);
+                    fb.fill_rect(x, y, x_end - x, y_end - y, color);
+                    x = x_end;
+                }
+                y = y_end;
             }
         }
     }
-    // Bottom-left
-    let cx = (x + r) as i32;
-    let cy = (y + h - r) as i32;
-    for py in (y + h - r)..(y + h) {
-        for px in x..x + r {
-            let dx = px as i32 - cx;
-            let dy = py as i32 - cy;
-            if dx * dx + dy * dy <= r2 {
+}
+
+/// Sub-pixel steps per pixel for corner coverage.
+const AA_SCALE: i128 = 256;
+
+/// The part of the disc of radius `r` around `centre` inside the `r * r`
+/// square at `(x0, y0)`, cut to the screen first so a corner far off it
+/// costs nothing. `centre` is a pixel corner; each pixel is covered by how
+/// far its centre lies inside the arc, so the edge pixels are blended in
+/// rather than set or left, which smooths the curve.
+fn fill_corner(
+    fb: &mut FramebufferWriter,
+    shape: Rect,
+    (x0, y0): (usize, usize),
+    centre: (usize, usize),
+    r: usize,
+    fill: &Fill,
+) {
+    let x1 = x0.saturating_add(r).min(fb.width);
+    let y1 = y0.saturating_add(r).min(fb.height);
+    let radius = r as i128 * AA_SCALE;
+    let offset = |p: usize, c: usize| (p as i128 - c as i128) * AA_SCALE + AA_SCALE / 2;
+    for py in y0..y1 {
+        let dy = offset(py, centre.1);
+        for px in x0..x1 {
+            let dx = offset(px, centre.0);
+            let distance = ((dx * dx + dy * dy) as u128).isqrt() as i128;
+            // A pixel is a unit wide: half of it either side of the arc
+            let coverage = (radius - distance + AA_SCALE / 2).clamp(0, AA_SCALE);
+            let color = fill.color_at(px - shape.x, py - shape.y, shape.w, shape.h);
+            if coverage == AA_SCALE {
                 fb.put_pixel(px, py, color);
+            } else if coverage > 0 {
+                fb.blend_pixel(px, py, color, (coverage * 255 / AA_SCALE) as u8);
             }
         }
     }
-    // Bottom-right
-    let cx = (x + w - r) as i32;
-    let cy = (y + h - r) as i32;
-    for py in (y + h - r)..(y + h) {
-        for px in (x + w - r)..(x + w) {
-            let dx = px as i32 - cx;
-            let dy = py as i32 - cy;
-            if dx * dx + dy * dy <= r2 {
-                fb.put_pixel(px, py, color);
+}
+
+/// What each shadow layer adds, in 255ths of the shadow colour's alpha,
+/// outermost and widest first. Stacked, they ramp up towards the middle
+/// about the way a blurred edge does, with no per-pixel filter.
+const SHADOW_FALLOFF: [u8; 4] = [48, 72, 104, 152];
+
+/// Everything `draw_shadow` may touch for `rect`.
+pub fn shadow_bounds(rect: Rect, blur: usize) -> Rect {
+    let offset = blur / 2;
+    let x = rect.x.saturating_add(offset).saturating_sub(blur);
+    let y = rect.y.saturating_add(offset).saturating_sub(blur);
+    let right = rect.right().saturating_add(offset + blur);
+    let bottom = rect.bottom().saturating_add(offset + blur);
+    Rect::new(x, y, right - x, bottom - y)
+}
+
+/// A soft shadow under `rect`, offset down and right by half of
+/// `blur_radius`: `SHADOW_FALLOFF.len()` rounded rects, each narrower and
+/// more opaque than the last, blended in `color` at its alpha. The caster
+/// is drawn over it, so `rect` itself is left alone.
+///
+/// It darkens whatever is there, so drawn twice over the same pixels it
+/// comes out darker. As a layer command that does not happen: its
+/// `shadow_bounds` are part of the layer's damage, which is painted again
+/// from below before the next frame (see `layers`).
+pub fn draw_shadow(fb: &mut FramebufferWriter, rect: Rect, blur_radius: usize, color: Color) {
+    if rect.w == 0 || rect.h == 0 || color.a == 0 {
+        return;
+    }
+    let offset = blur_radius / 2;
+    let layers = SHADOW_FALLOFF.len();
+    for (k, &share) in SHADOW_FALLOFF.iter().enumerate() {
+        let spread = blur_radius * (layers - k) / layers;
+        let x0 = rect.x.saturating_add(offset).saturating_sub(spread);
+        let y0 = rect.y.saturating_add(offset).saturating_sub(spread);
+        let x1 = rect.right().saturating_add(offset + spread);
+        let y1 = rect.bottom().saturating_add(offset + spread);
+        let r = spread.min((x1 - x0) / 2).min((y1 - y0) / 2);
+        let alpha = (u32::from(color.a) * u32::from(share) / 255) as u8;
+
+        for y in y0..y1.min(fb.height) {
+            // Rows into a corner, counted from its flat end
+            let dy = if y < y0 + r {
+                y0 + r - y
+            } else if y >= y1 - r {
+                y + r + 1 - y1
+            } else {
+                0
+            };
+            let inset = if dy == 0 {
+                0
+            } else {
+                r - (r * r - (dy - 1) * (dy - 1)).isqrt()
+            };
+            let (sx0, sx1) = (x0 + inset, x1 - inset);
+            if (rect.y..rect.bottom()).contains(&y) {
+                let left_end = sx1.min(rect.x);
+                let right_start = sx0.max(rect.right());
+                if left_end > sx0 {
+                    fb.blend_span(sx0, y, left_end - sx0, color, alpha);
+                }
+                if sx1 > right_start {
+                    fb.blend_span(right_start, y, sx1 - right_start, color, alpha);
+                }
+            } else {
+                fb.blend_span(sx0, y, sx1 - sx0, color, alpha);
             }
         }
     }
@@ -313,22 +513,161 @@ fn draw_stroke_rect(
 
     let t = thickness.max(1).min(rect.w).min(rect.h);
 
+    let right = rect.x.saturating_add(rect.w - t);
     fb.fill_rect(rect.x, rect.y, rect.w, t, color);
 
     if rect.h > t {
-        fb.fill_rect(rect.x, rect.y + rect.h - t, rect.w, t, color);
+        fb.fill_rect(rect.x, rect.y.saturating_add(rect.h - t), rect.w, t, color);
     }
 
-    let side_height = rect.h.saturating_sub(t * 2);
+    let side_height = rect.h.saturating_sub(t.saturating_mul(2));
     if side_height > 0 {
-        fb.fill_rect(rect.x, rect.y + t, t, side_height, color);
+        fb.fill_rect(rect.x, rect.y.saturating_add(t), t, side_height, color);
         if rect.w > t {
-            fb.fill_rect(rect.x + rect.w - t, rect.y + t, t, side_height, color);
+            fb.fill_rect(right, rect.y.saturating_add(t), t, side_height, color);
         }
     } else {
         fb.fill_rect(rect.x, rect.y, t, rect.h, color);
         if rect.w > t {
-            fb.fill_rect(rect.x + rect.w - t, rect.y, t, rect.h, color);
+            fb.fill_rect(right, rect.y, t, rect.h, color);
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rand::Xorshift64Star;
+
+    fn screen(w: usize, h: usize) -> FramebufferWriter {
+        let buffer: &'static mut [u8] = alloc::vec![0u8; w * h * 4].leak();
+        FramebufferWriter::from_raw(buffer, w, h, w, 4)
+    }
+
+    #[test_case]
+    fn rounded_rects_stay_inside_their_rect() {
+        let (sw, sh) = (64, 48);
+        let mut rng = Xorshift64Star::new(1169);
+        let white = Color::new(255, 255, 255);
+        let mut pick = |rng: &mut Xorshift64Star| match rng.next_range(0, 6) {
+            0 => usize::MAX - rng.next_range(0, 40) as usize,
+            _ => rng.next_range(0, 90) as usize,
+        };
+        for _ in 0..500 {
+            let mut fb = screen(sw, sh);
+            let rect = Rect::new(
+                pick(&mut rng),
+                pick(&mut rng),
+                pick(&mut rng),
+                pick(&mut rng),
+            );
+            let radius = pick(&mut rng);
+            fill_rounded_rect(&mut fb, rect, radius, white);
+            draw_stroke_rect(&mut fb, rect, white, radius);
+            for (i, &px) in fb.snapshot().iter().enumerate() {
+                let (x, y) = (i % sw, i / sw);
+                let inside =
+                    x >= rect.x && y >= rect.y && x - rect.x < rect.w && y - rect.y < rect.h;
+                assert!(px == 0 || inside, "{:?} r {} at {},{}", rect, radius, x, y);
+            }
+        }
+    }
+
+    #[test_case]
+    fn rounded_corners_blend_their_edge_pixels() {
+        let mut fb = screen(48, 32);
+        let white = Color::new(255, 255, 255);
+        let rect = Rect::new(4, 4, 30, 20);
+        fill_rounded_rect(&mut fb, rect, 8, white);
+
+        let level = |x, y| fb.get_pixel(x, y).r;
+        assert_eq!(level(4, 4), 0);
+        assert_eq!(level(12, 12), 255);
+        assert_eq!(level(19, 4), 255);
+        // Along the arc: some pixels part covered, none missing from the
+        // inside of it
+        let corner: Vec<u8> = (4..12)
+            .flat_map(|y| (4..12).map(move |x| (x, y)))
+            .map(|(x, y)| level(x, y))
+            .collect();
+        assert!(corner.iter().filter(|&&v| v > 0 && v < 255).count() >= 8);
+        assert!(level(7, 7) > 0 && level(5, 5) < level(7, 7));
+        // The four corners match
+        for (dx, dy) in [(0, 0), (1, 2), (3, 0), (2, 5)] {
+            let v = level(4 + dx, 4 + dy);
+            assert_eq!(level(rect.right() - 1 - dx, 4 + dy), v);
+            assert_eq!(level(4 + dx, rect.bottom() - 1 - dy), v);
+            assert_eq!(level(rect.right() - 1 - dx, rect.bottom() - 1 - dy), v);
+        }
+
+        let mut fb = screen(8, 8);
+        fill_rounded_rect(&mut fb, Rect::new(1, 1, 4, 4), 0, white);
+        assert_eq!(fb.get_pixel(1, 1), white);
+    }
+
+    #[test_case]
+    fn shadows_darken_around_the_caster_only() {
+        let (sw, sh) = (64, 48);
+        let mut fb = screen(sw, sh);
+        let white = Color::new(255, 255, 255);
+        fb.fill_rect(0, 0, sw, sh, white);
+        let rect = Rect::new(16, 12, 20, 14);
+        let bounds = shadow_bounds(rect, 8);
+        draw_shadow(&mut fb, rect, 8, Color::with_alpha(0, 0, 0, 200));
+
+        let inside = |r: Rect, x: usize, y: usize| x >= r.x && y >= r.y && x < r.right() && y < r.bottom();
+        for (i, &px) in fb.snapshot().iter().enumerate() {
+            let (x, y) = (i % sw, i / sw);
+            if inside(rect, x, y) || !inside(bounds, x, y) {
+                assert_eq!(px & 0xffffff, 0xffffff, "{},{}", x, y);
+            }
         }
+        // Darkest against the caster's bottom-right corner, fading out
+        let level = |x, y| fb.get_pixel(x, y).r;
+        let (x, y) = (rect.right(), rect.bottom());
+        assert!(level(x, y) < level(x + 6, y + 6));
+        assert!(level(x + 6, y + 6) < level(x + 9, y + 9));
+        // The offset leaves less of it above and left than below and right
+        assert!(level(rect.x - 1, rect.y + 6) > level(rect.right(), rect.y + 6));
+    }
+
+    #[test_case]
+    fn styled_fills_keep_to_the_rounded_shape() {
+        let (black, white) = (Color::new(0, 0, 0), Color::new(255, 255, 255));
+        let (red, blue) = (Color::new(200, 0, 0), Color::new(0, 0, 200));
+        let rect = Rect::new(4, 4, 30, 21);
+
+        let mut fb = screen(48, 32);
+        fill_rounded(&mut fb, rect, 8, &Fill::VerticalGradient(black, white));
+        // Clipped by the corner, even at the gradient's darkest row
+        assert_eq!(fb.get_pixel(4, 4), black);
+        assert_eq!(fb.get_pixel(19, 4), black);
+        assert_eq!(fb.get_pixel(19, 14), Color::new(127, 127, 127));
+        assert_eq!(fb.get_pixel(19, 24), white);
+        assert_eq!(fb.get_pixel(4, 24).r, 0);
+        assert_eq!(fb.get_pixel(34, 14), black);
+
+        let mut fb = screen(48, 32);
+        fill_rounded(&mut fb, rect, 0, &Fill::HorizontalGradient(red, blue));
+        assert_eq!(fb.get_pixel(4, 4), red);
+        assert_eq!(fb.get_pixel(33, 24), blue);
+
+        // Squares of 4 from the shape's corner, not the screen's
+        let mut fb = screen(48, 32);
+        fill_rounded(&mut fb, rect, 0, &Fill::Checkerboard(red, blue, 4));
+        assert_eq!(fb.get_pixel(4, 4), red);
+        assert_eq!(fb.get_pixel(8, 4), blue);
+        assert_eq!(fb.get_pixel(11, 11), red);
+        assert_eq!(fb.get_pixel(33, 23), blue);
+        // A square bigger than the shape is just the first colour
+        fill_rounded(&mut fb, rect, 0, &Fill::Checkerboard(red, blue, 100));
+        assert_eq!(fb.get_pixel(8, 4), red);
+        assert_eq!(fb.get_pixel(33, 23), red);
+
+        let mut list = RenderList::new();
+        list.fill_styled(rect, 8, Fill::Solid(red));
+        assert!(matches!(list.as_slice(), [RenderCommand::FillRoundedRect { .. }]));
     }
 }
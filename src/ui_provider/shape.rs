@@ -1,3 +1,12 @@
+//! Screen geometry. Layout works in `usize` pixels; embedded-graphics and
+//! the cursor want `i32`/`u32`, and the conversion goes through
+//! `to_i32_clamped`/`to_u32_clamped` instead of `as` or `try_into().unwrap()`.
+//! A value that does not fit saturates and is logged once, since it means
+//! either an impossible framebuffer or a layout bug, not something to panic
+//! the boot over.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Rect {
     pub x: usize,
@@ -9,4 +18,49 @@ impl Rect {
     pub fn new(x: usize, y: usize, w: usize, h: usize) -> Self {
         Self { x, y, w, h }
     }
+
+    /// One past the last column, saturating.
+    pub fn right(&self) -> usize {
+        self.x.saturating_add(self.w)
+    }
+
+    /// One past the last row, saturating.
+    pub fn bottom(&self) -> usize {
+        self.y.saturating_add(self.h)
+    }
+
+    /// Whether the pixel at `x`, `y` is inside.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The part of `self` inside `other`, if they overlap.
+    pub fn intersect(&self, other: Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        (x < right && y < bottom).then(|| Rect::new(x, y, right - x, bottom - y))
+    }
+}
+
+static CLAMP_LOGGED: AtomicBool = AtomicBool::new(false);
+
+fn clamped(value: usize, to: &str) {
+    if !CLAMP_LOGGED.swap(true, Ordering::Relaxed) {
+        crate::log_warn!("geometry: {} does not fit in {}, clamped", value, to);
+    }
+}
+
+pub fn to_i32_clamped(value: usize) -> i32 {
+    i32::try_from(value).unwrap_or_else(|_| {
+        clamped(value, "i32");
+        i32::MAX
+    })
+}
+
+pub fn to_u32_clamped(value: usize) -> u32 {
+    u32::try_from(value).unwrap_or_else(|_| {
+        clamped(value, "u32");
+        u32::MAX
+    })
 }
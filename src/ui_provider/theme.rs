@@ -1,4 +1,63 @@
+use crate::ui_provider::background::parse_color;
 use crate::ui_provider::color::Color;
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use spin::Mutex;
+
+static HIGH_CONTRAST: AtomicBool = AtomicBool::new(false);
+/// Colours set with `color`, laid over whichever theme is current.
+static CUSTOM: Mutex<[Option<Color>; ColorField::ALL.len()]> =
+    Mutex::new([None; ColorField::ALL.len()]);
+/// Bumped by every change to the current theme.
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// A colour of the theme, by the name `color` knows it by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorField {
+    Text,
+    Background,
+    Accent,
+    Surface,
+    Border,
+    Muted,
+    OnAccent,
+    Caret,
+}
+
+impl ColorField {
+    pub const ALL: [ColorField; 8] = [
+        ColorField::Text,
+        ColorField::Background,
+        ColorField::Accent,
+        ColorField::Surface,
+        ColorField::Border,
+        ColorField::Muted,
+        ColorField::OnAccent,
+        ColorField::Caret,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorField::Text => "text",
+            ColorField::Background => "background",
+            ColorField::Accent => "accent",
+            ColorField::Surface => "surface",
+            ColorField::Border => "border",
+            ColorField::Muted => "muted",
+            ColorField::OnAccent => "on_accent",
+            ColorField::Caret => "caret",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.name() == name)
+    }
+
+    /// Every name, for error messages.
+    pub fn names() -> String {
+        Self::ALL.map(Self::name).join(", ")
+    }
+}
 
 pub struct Theme {
     pub text: Color,
@@ -8,6 +67,11 @@ pub struct Theme {
     pub border: Color,
     pub muted: Color,
     pub on_accent: Color,
+    /// Width of the focus ring, in pixels.
+    pub focus_ring: usize,
+    /// Terminal text cursor colour and underline height.
+    pub caret: Color,
+    pub caret_height: usize,
 }
 
 impl Theme {
@@ -20,6 +84,9 @@ impl Theme {
             border: Color::from_hex(0x45475a),
             muted: Color::from_hex(0x6c7086),
             on_accent: Color::from_hex(0x1e1e2e),
+            focus_ring: 2,
+            caret: Color::from_hex(0xCCCCCC),
+            caret_height: 2,
         }
     }
 
@@ -32,6 +99,167 @@ impl Theme {
             border: Color::from_hex(0x45475a),
             muted: Color::from_hex(0x6c7086),
             on_accent: Color::from_hex(0x1e1e2e),
+            focus_ring: 2,
+            caret: Color::from_hex(0xCCCCCC),
+            caret_height: 2,
+        }
+    }
+
+    /// White on black with a yellow accent and a thick focus ring.
+    pub fn high_contrast() -> Self {
+        Self {
+            text: Color::WHITE,
+            background: Color::BLACK,
+            accent: Color::from_hex(0xFFD700),
+            surface: Color::BLACK,
+            border: Color::WHITE,
+            muted: Color::from_hex(0xC0C0C0),
+            on_accent: Color::BLACK,
+            focus_ring: 4,
+            caret: Color::WHITE,
+            caret_height: 4,
+        }
+    }
+
+    /// The theme the UI is drawn with: `high_contrast` while `contrast on`
+    /// is in effect, `dark_modern` otherwise, with any colours set by
+    /// `color` on top.
+    pub fn current() -> Self {
+        let mut theme = if high_contrast_enabled() {
+            Self::high_contrast()
+        } else {
+            Self::dark_modern()
+        };
+        for (field, color) in custom_colors() {
+            theme.set_color(field, color);
+        }
+        theme
+    }
+
+    pub fn color(&self, field: ColorField) -> Color {
+        match field {
+            ColorField::Text => self.text,
+            ColorField::Background => self.background,
+            ColorField::Accent => self.accent,
+            ColorField::Surface => self.surface,
+            ColorField::Border => self.border,
+            ColorField::Muted => self.muted,
+            ColorField::OnAccent => self.on_accent,
+            ColorField::Caret => self.caret,
+        }
+    }
+
+    pub fn set_color(&mut self, field: ColorField, color: Color) {
+        let slot = match field {
+            ColorField::Text => &mut self.text,
+            ColorField::Background => &mut self.background,
+            ColorField::Accent => &mut self.accent,
+            ColorField::Surface => &mut self.surface,
+            ColorField::Border => &mut self.border,
+            ColorField::Muted => &mut self.muted,
+            ColorField::OnAccent => &mut self.on_accent,
+            ColorField::Caret => &mut self.caret,
+        };
+        *slot = color;
+    }
+}
+
+pub fn set_high_contrast(on: bool) {
+    if HIGH_CONTRAST.swap(on, Ordering::Relaxed) != on {
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Lays `color` over `field` of the current theme, or with `None` goes
+/// back to the theme's own colour.
+pub fn set_custom_color(field: ColorField, color: Option<Color>) {
+    CUSTOM.lock()[field as usize] = color;
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The colours set with `color`, in field order.
+pub fn custom_colors() -> Vec<(ColorField, Color)> {
+    let custom = CUSTOM.lock();
+    ColorField::ALL
+        .into_iter()
+        .filter_map(|field| Some((field, custom[field as usize]?)))
+        .collect()
+}
+
+/// Changes whenever the current theme does, so that callers holding on
+/// to what they drew with can tell it is stale.
+pub fn generation() -> u32 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+pub fn high_contrast_enabled() -> bool {
+    HIGH_CONTRAST.load(Ordering::Relaxed)
+}
+
+/// Where `contrast on|off` is remembered, so that `sync` carries it over a
+/// warm reboot.
+pub const CONTRAST_CONFIG: &str = "/config/contrast";
+
+/// Where the colours set with `color` are remembered, one
+/// `<field> #rrggbb` per line.
+pub const COLORS_CONFIG: &str = "/config/colors";
+
+/// Writes the custom colours to `COLORS_CONFIG`.
+pub fn save_colors() {
+    let text: String = custom_colors()
+        .into_iter()
+        .map(|(field, c)| format!("{} #{:02x}{:02x}{:02x}\n", field.name(), c.r, c.g, c.b))
+        .collect();
+    let _ = crate::fs::ramfs::write(COLORS_CONFIG, text.as_bytes());
+}
+
+/// The lines of a `COLORS_CONFIG` file that name a field and a colour.
+fn parse_colors(text: &str) -> impl Iterator<Item = (ColorField, Color)> + '_ {
+    text.lines().filter_map(|line| {
+        let mut words = line.split_whitespace();
+        let field = ColorField::parse(words.next()?)?;
+        Some((field, parse_color(words.next()?)?))
+    })
+}
+
+/// Applies settings saved in ramfs, after `persist::restore`.
+pub fn load_config() {
+    if let Ok(value) = crate::fs::ramfs::read(CONTRAST_CONFIG) {
+        set_high_contrast(value == b"on");
+    }
+    if let Ok(value) = crate::fs::ramfs::read(COLORS_CONFIG) {
+        for (field, color) in parse_colors(core::str::from_utf8(&value).unwrap_or("")) {
+            set_custom_color(field, Some(color));
         }
     }
 }
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn colors_set_by_field_and_read_back_from_config() {
+        let mut theme = Theme::dark_modern();
+        for (i, field) in ColorField::ALL.into_iter().enumerate() {
+            assert_eq!(ColorField::parse(field.name()), Some(field));
+            theme.set_color(field, Color::from_hex(i as u32));
+        }
+        for (i, field) in ColorField::ALL.into_iter().enumerate() {
+            assert_eq!(theme.color(field), Color::from_hex(i as u32));
+        }
+        assert_eq!(ColorField::parse("primary"), None);
+
+        let saved = "accent #ff8800\nprimary #ffffff\ntext 12\n\ncaret 0x00ff00\n";
+        let parsed: Vec<_> = parse_colors(saved).collect();
+        assert_eq!(
+            parsed,
+            [
+                (ColorField::Accent, Color::from_hex(0xff8800)),
+                (ColorField::Caret, Color::from_hex(0x00ff00)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! # Toasts
+//!
+//! Short notices in the bottom-right corner. `notify` (or
+//! `AppHost::notify`) queues one to show for a number of timer ticks,
+//! after which it fades out over `FADE_TICKS` and is dropped. Toasts stack
+//! upwards, newest at the bottom. Past `MAX_TOASTS` the oldest goes at
+//! once, so a burst of notices cannot fill the screen; neither can the
+//! stack, which stops at the top of the screen.
+//!
+//! They are the `toast` layer (`ui_provider::layers`), registered while
+//! any is queued. What a layer covered is painted again from below before
+//! the next frame's layers go on, so a toast that ends is gone as soon as
+//! it is not drawn again, and a fading toast, drawn over a copy of what is
+//! under it and blended with it as translucent apps are, blends with the
+//! fresh content there rather than its own last frame. Since the
+//! framebuffer only copies tiles whose pixels changed, a fade costs the
+//! tiles under the toasts rather than a full repaint.
+//!
+//! Background jobs toast their end notice (`apps::jobs`), so it shows
+//! even while the terminal is not in front.
+//!
+//! A message is word-wrapped to the toast's width, `TOAST_W` or the
+//! screen less its margins, and cut with `...` after `MAX_LINES` lines.
+
+use crate::devices::framebuffer::framebuffer::FramebufferWriter;
+use crate::ui_provider::{
+    layers::{self, Layer},
+    render::{flush_commands, RenderList},
+    shape::Rect,
+    theme::Theme,
+    widgets::{Panel, Widget},
+};
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use spin::Mutex;
+
+/// Toasts queued at once; a new one past this pushes out the oldest.
+pub const MAX_TOASTS: usize = 4;
+/// Lines of a message shown; the rest is cut.
+pub const MAX_LINES: usize = 3;
+/// Ticks a toast takes to fade out once its time is up.
+pub const FADE_TICKS: u64 = 9;
+/// How long `notify` from the shell shows for, about three seconds.
+pub const DEFAULT_TICKS: u64 = 54;
+
+const TOAST_W: usize = 360;
+/// Gap to the screen edges.
+const MARGIN: usize = 16;
+const PAD: usize = 10;
+const GAP: usize = 8;
+const CHAR_W: usize = 10;
+const CHAR_H: usize = 20;
+const LAYER_NAME: &str = "toasts";
+
+struct Toast {
+    message: String,
+    /// Fully shown until this tick, gone `FADE_TICKS` after it.
+    until: u64,
+}
+
+impl Toast {
+    /// Opacity at tick `now`: opaque, then falling linearly to 0.
+    fn alpha(&self, now: u64) -> u8 {
+        let end = self.until + FADE_TICKS;
+        if now < self.until {
+            u8::MAX
+        } else {
+            (end.saturating_sub(now) * u64::from(u8::MAX) / FADE_TICKS) as u8
+        }
+    }
+}
+
+/// Oldest first.
+static QUEUE: Mutex<VecDeque<Toast>> = Mutex::new(VecDeque::new());
+
+fn ticks() -> u64 {
+    crate::kcore::interrupts::interrupts::TIMER_TICKS.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Shows `message` for `duration_ticks` timer ticks, then fades it out.
+pub fn notify(message: &str, duration_ticks: u64) {
+    let mut queue = QUEUE.lock();
+    push(&mut queue, message, ticks() + duration_ticks);
+    layers::register(Layer::Toast, LAYER_NAME, draw);
+}
+
+fn push(queue: &mut VecDeque<Toast>, message: &str, until: u64) {
+    if queue.len() >= MAX_TOASTS {
+        queue.pop_front();
+    }
+    queue.push_back(Toast {
+        message: String::from(message),
+        until,
+    });
+}
+
+/// Drops every toast, returning their messages, oldest first.
+#[cfg(test)]
+pub fn take_all() -> Vec<String> {
+    layers::unregister(LAYER_NAME);
+    QUEUE.lock().drain(..).map(|toast| toast.message).collect()
+}
+
+/// Drops the toasts that have faded out by tick `now`.
+fn expire(queue: &mut VecDeque<Toast>, now: u64) {
+    queue.retain(|toast| now < toast.until + FADE_TICKS);
+}
+
+/// Width of a toast on a screen `screen_w` wide.
+fn toast_width(screen_w: usize) -> usize {
+    TOAST_W.min(screen_w.saturating_sub(2 * MARGIN))
+}
+
+/// `message` word-wrapped at `cols` columns, words longer than that
+/// broken, and cut to `max_lines` with `...` on the last one.
+pub fn wrap(message: &str, cols: usize, max_lines: usize) -> Vec<String> {
+    let cols = cols.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for mut word in message.split_whitespace() {
+        loop {
+            let used = line.chars().count();
+            let room = if used == 0 {
+                cols
+            } else {
+                cols.saturating_sub(used + 1)
+            };
+            if word.chars().count() <= room {
+                if used > 0 {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                break;
+            }
+            if used > 0 {
+                lines.push(core::mem::take(&mut line));
+                continue;
+            }
+            let cut = word.char_indices().nth(cols).map_or(word.len(), |(i, _)| i);
+            lines.push(String::from(&word[..cut]));
+            word = &word[cut..];
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines.max(1));
+        if let Some(last) = lines.last_mut() {
+            let keep = cols.saturating_sub(3);
+            let cut = last.char_indices().nth(keep).map_or(last.len(), |(i, _)| i);
+            last.truncate(cut);
+            last.push_str("...");
+        }
+    }
+    lines
+}
+
+/// Panels for toasts of `line_counts` lines, newest first, stacked up
+/// from the bottom-right corner. Toasts that no longer fit get none.
+fn placements(screen_w: usize, screen_h: usize, line_counts: &[usize]) -> Vec<Rect> {
+    let w = toast_width(screen_w);
+    if w < 2 * PAD + CHAR_W {
+        return Vec::new();
+    }
+    let x = screen_w - MARGIN - w;
+    let mut bottom = screen_h.saturating_sub(MARGIN);
+    let mut rects = Vec::new();
+    for &lines in line_counts {
+        let h = lines * CHAR_H + 2 * PAD;
+        if bottom < MARGIN + h {
+            break;
+        }
+        rects.push(Rect::new(x, bottom - h, w, h));
+        bottom -= h + GAP;
+    }
+    rects
+}
+
+/// The `toast` layer. Unregisters itself once the last toast is gone.
+fn draw(fb: &mut FramebufferWriter, theme: &Theme) -> Option<Rect> {
+    let now = ticks();
+    let cols = toast_width(fb.width).saturating_sub(2 * PAD) / CHAR_W;
+    let toasts: Vec<(Vec<String>, u8)> = {
+        let mut queue = QUEUE.lock();
+        expire(&mut queue, now);
+        if queue.is_empty() {
+            layers::unregister(LAYER_NAME);
+            return None;
+        }
+        queue
+            .iter()
+            .rev()
+            .map(|toast| (wrap(&toast.message, cols, MAX_LINES), toast.alpha(now)))
+            .collect()
+    };
+    let line_counts: Vec<usize> = toasts.iter().map(|(lines, _)| lines.len()).collect();
+
+    let mut damage = None;
+    for (rect, (lines, alpha)) in placements(fb.width, fb.height, &line_counts)
+        .into_iter()
+        .zip(toasts)
+    {
+        let panel = Panel::new(rect).with_shadow(true);
+        let b = panel.bounds();
+        let under = (alpha < u8::MAX).then(|| fb.snapshot_rect(b.x, b.y, b.w, b.h));
+
+        let mut out = RenderList::new();
+        panel.collect_render(theme, false, &mut out);
+        for (i, line) in lines.iter().enumerate() {
+            out.text(
+                line.as_str(),
+                rect.x + PAD,
+                rect.y + PAD + i * CHAR_H,
+                theme.text,
+            );
+        }
+        flush_commands(fb, out.as_slice());
+        if let Some(under) = under {
+            fb.blend_over(b.x, b.y, b.w, b.h, &under, alpha);
+        }
+        damage = layers::union(damage, Some(b));
+    }
+    damage
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn a_burst_keeps_the_newest_and_each_fades_after_its_time() {
+        let mut queue = VecDeque::new();
+        for i in 0..MAX_TOASTS + 3 {
+            push(&mut queue, &alloc::format!("toast {}", i), 100 + i as u64);
+        }
+        assert_eq!(queue.len(), MAX_TOASTS);
+        assert_eq!(queue[0].message, "toast 3");
+
+        let first = &queue[0];
+        assert_eq!(first.alpha(0), u8::MAX);
+        assert_eq!(first.alpha(102), u8::MAX);
+        let half = first.alpha(103 + FADE_TICKS / 2);
+        assert!(0 < half && half < u8::MAX);
+        assert_eq!(first.alpha(103 + FADE_TICKS), 0);
+
+        expire(&mut queue, 103 + FADE_TICKS);
+        assert_eq!(queue.len(), MAX_TOASTS - 1);
+        expire(&mut queue, 200);
+        assert!(queue.is_empty());
+    }
+
+    #[test_case]
+    fn long_messages_wrap_then_get_cut() {
+        assert_eq!(wrap("build finished", 20, MAX_LINES), ["build finished"]);
+        assert_eq!(wrap("", 20, MAX_LINES), [""]);
+        assert_eq!(wrap("one two three", 8, MAX_LINES), ["one two", "three"]);
+        // A word wider than the toast is broken
+        assert_eq!(
+            wrap("abcdefghij xy", 5, MAX_LINES),
+            ["abcde", "fghij", "xy"]
+        );
+
+        let long = wrap(&"word ".repeat(50), 12, MAX_LINES);
+        assert_eq!(long.len(), MAX_LINES);
+        assert_eq!(long[2], "word word...");
+        assert!(long.iter().all(|line| line.chars().count() <= 12));
+    }
+
+    #[test_case]
+    fn toasts_stack_up_and_stay_on_screen() {
+        let rects = placements(1024, 768, &[1, 3, 2]);
+        assert_eq!(rects.len(), 3);
+        assert_eq!(rects[0].right(), 1024 - MARGIN);
+        assert_eq!(rects[0].bottom(), 768 - MARGIN);
+        for pair in rects.windows(2) {
+            assert_eq!(pair[1].bottom() + GAP, pair[0].y);
+        }
+
+        // A narrow screen narrows them; a short one drops what does not fit
+        let narrow = placements(200, 100, &[1, 1, 1]);
+        assert_eq!(narrow.len(), 1);
+        assert_eq!(narrow[0].w, 200 - 2 * MARGIN);
+        assert!(placements(40, 768, &[1]).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! # Top Bar
+//!
+//! The strip above the app tabs: the focused app's name on the left, the
+//! system title in the middle, and status words and the RTC clock on the
+//! right. Its render list is rebuilt only when one of those values (or the
+//! theme) changes; every other frame replays the cached commands.
+//!
+//! On a narrow screen the title goes first, then the status words, so the
+//! app name and the clock are the last to be squeezed out.
+
+use crate::devices::drivers::rtc::{self, RtcTime};
+use crate::ui_provider::{
+    render::{Fill, RenderCommand, RenderList},
+    shape::Rect,
+    theme::Theme,
+};
+use alloc::{format, string::String};
+
+const TITLE: &str = "DuxOS";
+const CHAR_W: usize = 10;
+const CHAR_H: usize = 20;
+/// Left and right padding, and the least space between two parts.
+const PAD: usize = 10;
+const GAP: usize = 20;
+
+/// Everything the bar shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarState {
+    pub app: &'static str,
+    pub clock: RtcTime,
+    pub mouse: bool,
+    pub caps_lock: bool,
+    /// A macro is being recorded.
+    pub recording: bool,
+    /// `theme::generation`, standing in for the theme, which has no
+    /// equality of its own.
+    pub theme: u32,
+}
+
+/// Where the three parts start, for a bar `width` pixels wide. A part
+/// that does not fit is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub left: usize,
+    pub center: Option<usize>,
+    pub right: Option<usize>,
+}
+
+/// Places parts `left`, `center` and `right`, given in characters.
+pub fn place(width: usize, left: usize, center: usize, right: usize) -> Placement {
+    let (left, center, right) = (left * CHAR_W, center * CHAR_W, right * CHAR_W);
+    let left_end = PAD + left;
+    let right_x = width
+        .checked_sub(PAD + right)
+        .filter(|&x| x >= left_end + GAP);
+    let right_start = right_x.unwrap_or(width.saturating_sub(PAD));
+    let center_x = width.saturating_sub(center) / 2;
+    let center =
+        (center_x >= left_end + GAP && center_x + center + GAP <= right_start).then_some(center_x);
+    Placement {
+        left: PAD,
+        center,
+        right: right_x,
+    }
+}
+
+pub struct TopBar {
+    rect: Rect,
+    state: Option<BarState>,
+    commands: RenderList,
+    /// The uptime second the clock was last read in.
+    clock_second: Option<u64>,
+    clock: RtcTime,
+}
+
+impl TopBar {
+    /// Bar height for a framebuffer `fb_height` pixels tall: one text row
+    /// with a little room, growing slowly with the screen.
+    pub fn height_for(fb_height: usize) -> usize {
+        (fb_height / 30).clamp(CHAR_H + 4, CHAR_H * 2)
+    }
+
+    pub fn new(rect: Rect) -> Self {
+        Self {
+            rect,
+            state: None,
+            commands: RenderList::new(),
+            clock_second: None,
+            clock: RtcTime {
+                hour: 0,
+                minute: 0,
+                second: 0,
+            },
+        }
+    }
+
+    /// The RTC time, read at most once per uptime second.
+    pub fn clock(&mut self, now_ms: u64) -> RtcTime {
+        let second = now_ms / 1_000;
+        if self.clock_second != Some(second) {
+            self.clock_second = Some(second);
+            self.clock = rtc::read_time();
+        }
+        self.clock
+    }
+
+    /// Rebuilds the bar if `state` differs from what it shows. Returns
+    /// whether it did.
+    pub fn update(&mut self, state: BarState, theme: &Theme) -> bool {
+        if self.state == Some(state) {
+            return false;
+        }
+        self.state = Some(state);
+        self.commands.clear();
+        self.collect(&state, theme);
+        true
+    }
+
+    pub fn commands(&self) -> &[RenderCommand] {
+        self.commands.as_slice()
+    }
+
+    fn collect(&mut self, state: &BarState, theme: &Theme) {
+        let rect = self.rect;
+        let out = &mut self.commands;
+        out.fill_styled(rect, 0, Fill::HorizontalGradient(theme.background, theme.surface));
+        out.fill_rect(
+            Rect::new(rect.x, rect.y + rect.h.saturating_sub(1), rect.w, 1),
+            theme.border,
+        );
+
+        let mut status = String::new();
+        if state.recording {
+            status.push_str("REC ");
+        }
+        if state.caps_lock {
+            status.push_str("CAPS ");
+        }
+        if state.mouse {
+            status.push_str("mouse ");
+        }
+        let clock = format!(
+            "{:02}:{:02}:{:02}",
+            state.clock.hour, state.clock.minute, state.clock.second
+        );
+        let mut right = format!("{}{}", status, clock);
+        let mut at = place(rect.w, state.app.len(), TITLE.len(), right.len());
+        if at.right.is_none() {
+            right = clock;
+            at = place(rect.w, state.app.len(), TITLE.len(), right.len());
+        }
+
+        let y = rect.y + rect.h.saturating_sub(CHAR_H) / 2;
+        out.text(state.app, rect.x + at.left, y, theme.accent);
+        if let Some(x) = at.center {
+            out.text(TITLE, rect.x + x, y, theme.text);
+        }
+        if let Some(x) = at.right {
+            out.text(right, rect.x + x, y, theme.muted);
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn parts_drop_out_as_the_bar_narrows() {
+        // 1280 wide: everything fits, the title centred
+        let wide = place(1280, 8, 5, 14);
+        assert_eq!(wide.left, PAD);
+        assert_eq!(wide.center, Some((1280 - 50) / 2));
+        assert_eq!(wide.right, Some(1280 - PAD - 140));
+
+        // the title needs a gap on both sides of it
+        let narrow = place(300, 8, 5, 14);
+        assert_eq!(narrow.center, None);
+        assert_eq!(narrow.right, Some(300 - PAD - 140));
+
+        let tiny = place(120, 8, 5, 14);
+        assert_eq!((tiny.center, tiny.right), (None, None));
+        assert_eq!(place(0, 8, 5, 14).right, None);
+    }
+
+    #[test_case]
+    fn rebuilt_only_on_change() {
+        let theme = Theme::dark_modern();
+        let mut bar = TopBar::new(Rect::new(0, 0, 640, TopBar::height_for(480)));
+        let mut state = BarState {
+            app: "Terminal",
+            clock: RtcTime {
+                hour: 9,
+                minute: 5,
+                second: 0,
+            },
+            mouse: true,
+            caps_lock: false,
+            recording: false,
+            theme: 0,
+        };
+        assert!(bar.update(state, &theme));
+        let drawn = bar.commands().len();
+        assert!(!bar.update(state, &theme));
+        assert_eq!(bar.commands().len(), drawn);
+
+        state.app = "Editor";
+        assert!(bar.update(state, &theme));
+        state.clock.second = 1;
+        assert!(bar.update(state, &theme));
+        assert_eq!(bar.commands().len(), drawn);
+        assert_eq!(TopBar::height_for(480), 24);
+        assert_eq!(TopBar::height_for(2160), 40);
+    }
+}
@@ -0,0 +1,767 @@
+//! # Widgets
+//!
+//! Reusable building blocks for app UIs. Widgets do not own focus: the
+//! owning app tracks which `FocusBlock` is focused and routes key events
+//! to the matching widget, then reacts to the returned `WidgetEvent`.
+
+use crate::app::{AppEvent, Arrow, KeyCode, Modifiers};
+use crate::ui_provider::{
+    color::Color,
+    render::{shadow_bounds, Fill, RenderList, TextStyle},
+    shape::Rect,
+    theme::Theme,
+};
+use alloc::string::String;
+
+const CHAR_WIDTH: usize = 10;
+const CHAR_HEIGHT: usize = 20;
+const PADDING_X: usize = 6;
+const CARET_WIDTH: usize = 2;
+/// PIT runs at ~18.2 Hz, so this toggles the caret roughly twice a second.
+const CARET_BLINK_TICKS: u32 = 9;
+const PANEL_RADIUS: usize = 8;
+const SHADOW_BLUR: usize = 12;
+const SHADOW_COLOR: Color = Color::with_alpha(0, 0, 0, 140);
+/// Width of a `Scrollbar` track, and the shortest its thumb gets.
+pub const SCROLLBAR_WIDTH: usize = 4;
+const MIN_THUMB: usize = 12;
+
+/// What the owning app should do after routing an event to a widget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WidgetEvent {
+    /// The widget did not consume the event.
+    Ignored,
+    /// Only the visual state changed (caret moved, blink).
+    Redraw,
+    /// The widget's value changed.
+    Changed,
+    /// Enter was pressed in a text input.
+    Submit,
+    /// A button was pressed.
+    Activate,
+}
+
+impl WidgetEvent {
+    pub fn needs_redraw(self) -> bool {
+        self != WidgetEvent::Ignored
+    }
+}
+
+pub trait Widget {
+    fn rect(&self) -> Rect;
+    fn set_rect(&mut self, rect: Rect);
+    fn collect_render(&self, theme: &Theme, focused: bool, out: &mut RenderList);
+
+    /// Enter or Space while focused. Most widgets have nothing to do.
+    fn activate(&mut self) -> WidgetEvent {
+        WidgetEvent::Ignored
+    }
+}
+
+// ── TextInput ─────────────────────────────────────────────────────────────────
+
+/// Single-line text field.
+///
+/// `cursor` and `anchor` are char indices into `value`. When `anchor` is
+/// set and differs from `cursor`, the range between them is selected.
+pub struct TextInput {
+    value: String,
+    cursor: usize,
+    anchor: Option<usize>,
+    scroll: usize,
+    placeholder: String,
+    max_len: usize,
+    rect: Rect,
+    caret_visible: bool,
+    blink_ticks: u32,
+}
+
+impl TextInput {
+    pub fn new(placeholder: &str) -> Self {
+        Self {
+            value: String::new(),
+            cursor: 0,
+            anchor: None,
+            scroll: 0,
+            placeholder: String::from(placeholder),
+            max_len: usize::MAX,
+            rect: Rect::new(0, 0, 0, 0),
+            caret_visible: true,
+            blink_ticks: 0,
+        }
+    }
+
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.chars().take(self.max_len).collect();
+        self.cursor = self.len();
+        self.anchor = None;
+        self.ensure_cursor_visible();
+    }
+
+    /// Drops the selection, leaving the cursor where it is. False if
+    /// nothing was selected.
+    pub fn clear_selection(&mut self) -> bool {
+        let had = self.selection().is_some();
+        self.anchor = None;
+        had
+    }
+
+    /// Selected char range as `(start, end)`, if any.
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(a) if a != self.cursor => Some((a.min(self.cursor), a.max(self.cursor))),
+            _ => None,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &AppEvent) -> WidgetEvent {
+        match *event {
+            AppEvent::KeyPress { code, mods } => {
+                if mods.alt() {
+                    return WidgetEvent::Ignored;
+                }
+                self.handle_key(code, mods)
+            }
+            AppEvent::Tick => self.tick(),
+            AppEvent::Mouse { .. } => WidgetEvent::Ignored,
+        }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode, mods: Modifiers) -> WidgetEvent {
+        let (ctrl, shift) = (mods.ctrl(), mods.shift());
+        let result = match code {
+            KeyCode::Arrow(Arrow::Left) => self.move_to(self.cursor.saturating_sub(1), shift),
+            KeyCode::Arrow(Arrow::Right) => self.move_to((self.cursor + 1).min(self.len()), shift),
+            KeyCode::Enter => WidgetEvent::Submit,
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Home => self.move_to(0, shift),
+            KeyCode::End => self.move_to(self.len(), shift),
+            KeyCode::Char('u') if ctrl => self.delete_to_start(),
+            KeyCode::Char('a') if ctrl => self.move_to(0, shift),
+            KeyCode::Char('e') if ctrl => self.move_to(self.len(), shift),
+            KeyCode::Char(ch) if !ctrl && !ch.is_control() => self.insert(ch),
+            _ => WidgetEvent::Ignored,
+        };
+
+        if result.needs_redraw() {
+            self.reset_blink();
+        }
+        result
+    }
+
+    /// Advances the caret blink; returns `Redraw` when visibility flips.
+    pub fn tick(&mut self) -> WidgetEvent {
+        self.blink_ticks += 1;
+        if self.blink_ticks >= CARET_BLINK_TICKS {
+            self.blink_ticks = 0;
+            self.caret_visible = !self.caret_visible;
+            return WidgetEvent::Redraw;
+        }
+        WidgetEvent::Ignored
+    }
+
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    fn byte_index(&self, char_idx: usize) -> usize {
+        match self.value.char_indices().nth(char_idx) {
+            Some((idx, _)) => idx,
+            None => self.value.len(),
+        }
+    }
+
+    fn reset_blink(&mut self) {
+        self.caret_visible = true;
+        self.blink_ticks = 0;
+    }
+
+    fn visible_cols(&self) -> usize {
+        (self.rect.w.saturating_sub(PADDING_X * 2) / CHAR_WIDTH).max(1)
+    }
+
+    fn ensure_cursor_visible(&mut self) {
+        let cols = self.visible_cols();
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + cols {
+            self.scroll = self.cursor + 1 - cols;
+        }
+    }
+
+    fn move_to(&mut self, pos: usize, extend: bool) -> WidgetEvent {
+        if extend {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.cursor);
+            }
+        } else if let Some((start, end)) = self.selection() {
+            // Collapsing a selection with a bare arrow lands on its edge.
+            self.anchor = None;
+            self.cursor = if pos < self.cursor { start } else { end };
+            self.ensure_cursor_visible();
+            return WidgetEvent::Redraw;
+        } else {
+            self.anchor = None;
+        }
+
+        if pos == self.cursor {
+            return WidgetEvent::Ignored;
+        }
+        self.cursor = pos;
+        self.ensure_cursor_visible();
+        WidgetEvent::Redraw
+    }
+
+    fn delete_range(&mut self, start: usize, end: usize) {
+        let s = self.byte_index(start);
+        let e = self.byte_index(end);
+        self.value.drain(s..e);
+        self.cursor = start;
+        self.anchor = None;
+        self.ensure_cursor_visible();
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.delete_range(start, end);
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    fn insert(&mut self, ch: char) -> WidgetEvent {
+        let had_selection = self.delete_selection();
+        if self.len() >= self.max_len {
+            return if had_selection {
+                WidgetEvent::Changed
+            } else {
+                WidgetEvent::Ignored
+            };
+        }
+        let idx = self.byte_index(self.cursor);
+        self.value.insert(idx, ch);
+        self.cursor += 1;
+        self.ensure_cursor_visible();
+        WidgetEvent::Changed
+    }
+
+    fn backspace(&mut self) -> WidgetEvent {
+        if self.delete_selection() {
+            return WidgetEvent::Changed;
+        }
+        if self.cursor == 0 {
+            return WidgetEvent::Ignored;
+        }
+        self.delete_range(self.cursor - 1, self.cursor);
+        WidgetEvent::Changed
+    }
+
+    fn delete(&mut self) -> WidgetEvent {
+        if self.delete_selection() {
+            return WidgetEvent::Changed;
+        }
+        if self.cursor >= self.len() {
+            return WidgetEvent::Ignored;
+        }
+        self.delete_range(self.cursor, self.cursor + 1);
+        WidgetEvent::Changed
+    }
+
+    fn delete_to_start(&mut self) -> WidgetEvent {
+        if self.cursor == 0 {
+            return WidgetEvent::Ignored;
+        }
+        self.delete_range(0, self.cursor);
+        WidgetEvent::Changed
+    }
+}
+
+impl Widget for TextInput {
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.ensure_cursor_visible();
+    }
+
+    fn collect_render(&self, theme: &Theme, focused: bool, out: &mut RenderList) {
+        let r = self.rect;
+        if r.w == 0 || r.h == 0 {
+            return;
+        }
+
+        out.fill_rect(r, theme.background);
+        let border = if focused { theme.accent } else { theme.border };
+        out.stroke_rect(r, border, 1);
+
+        let cols = self.visible_cols();
+        let text_x = r.x + PADDING_X;
+        let text_y = r.y + r.h.saturating_sub(CHAR_HEIGHT) / 2;
+
+        if let Some((start, end)) = self.selection() {
+            let vis_start = start.max(self.scroll);
+            let vis_end = end.min(self.scroll + cols);
+            if vis_end > vis_start {
+                out.fill_rect(
+                    Rect::new(
+                        text_x + (vis_start - self.scroll) * CHAR_WIDTH,
+                        text_y,
+                        (vis_end - vis_start) * CHAR_WIDTH,
+                        CHAR_HEIGHT,
+                    ),
+                    theme.surface,
+                );
+            }
+        }
+
+        if self.value.is_empty() {
+            let hint: String = self.placeholder.chars().take(cols).collect();
+            out.styled_text(hint, text_x, text_y, TextStyle::new(theme.muted));
+        } else {
+            let visible: String = self.value.chars().skip(self.scroll).take(cols).collect();
+            out.styled_text(visible, text_x, text_y, TextStyle::new(theme.text));
+        }
+
+        if focused && self.caret_visible {
+            let caret_x = text_x + (self.cursor - self.scroll) * CHAR_WIDTH;
+            out.fill_rect(
+                Rect::new(caret_x, text_y, CARET_WIDTH, CHAR_HEIGHT),
+                theme.accent,
+            );
+        }
+    }
+}
+
+// ── Button ────────────────────────────────────────────────────────────────────
+
+pub struct Button {
+    label: String,
+    rect: Rect,
+}
+
+impl Button {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: String::from(label),
+            rect: Rect::new(0, 0, 0, 0),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &AppEvent) -> WidgetEvent {
+        match *event {
+            AppEvent::KeyPress {
+                code: KeyCode::Enter | KeyCode::Char(' '),
+                mods,
+            } if !mods.ctrl() && !mods.alt() => self.activate(),
+            _ => WidgetEvent::Ignored,
+        }
+    }
+}
+
+impl Widget for Button {
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+    }
+
+    fn collect_render(&self, theme: &Theme, focused: bool, out: &mut RenderList) {
+        let r = self.rect;
+        if r.w == 0 || r.h == 0 {
+            return;
+        }
+
+        let (fill, fg) = if focused {
+            (theme.accent, theme.on_accent)
+        } else {
+            (theme.surface, theme.text)
+        };
+        out.fill_rounded_rect(r, 6, fill);
+
+        let text_w = self.label.chars().count() * CHAR_WIDTH;
+        let text_x = r.x + r.w.saturating_sub(text_w) / 2;
+        let text_y = r.y + r.h.saturating_sub(CHAR_HEIGHT) / 2;
+        out.styled_text(self.label.as_str(), text_x, text_y, TextStyle::new(fg));
+    }
+
+    fn activate(&mut self) -> WidgetEvent {
+        WidgetEvent::Activate
+    }
+}
+
+// ── Panel ─────────────────────────────────────────────────────────────────────
+
+/// A raised surface for dialogs and pickers: a rounded rect with a
+/// border, over a drop shadow when `shadow` is set. Filled with `fill`,
+/// or with no fill set, solid `theme.surface`.
+pub struct Panel {
+    rect: Rect,
+    pub shadow: bool,
+    pub fill: Option<Fill>,
+}
+
+impl Panel {
+    pub fn new(rect: Rect) -> Self {
+        Self {
+            rect,
+            shadow: false,
+            fill: None,
+        }
+    }
+
+    pub fn with_shadow(mut self, shadow: bool) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    pub fn with_fill(mut self, fill: Fill) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    /// Everything drawing it touches, the shadow included: what has to be
+    /// painted again from below before it is drawn over once more, and
+    /// when it moves or goes away.
+    pub fn bounds(&self) -> Rect {
+        if self.shadow {
+            shadow_bounds(self.rect, SHADOW_BLUR)
+        } else {
+            self.rect
+        }
+    }
+}
+
+impl Widget for Panel {
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+    }
+
+    fn collect_render(&self, theme: &Theme, _focused: bool, out: &mut RenderList) {
+        if self.shadow {
+            out.shadow(self.rect, SHADOW_BLUR, SHADOW_COLOR);
+        }
+        let fill = self.fill.unwrap_or(Fill::Solid(theme.surface));
+        out.fill_styled(self.rect, PANEL_RADIUS, fill);
+        out.stroke_rect(self.rect, theme.border, 1);
+    }
+}
+
+// ── Scrollbar ─────────────────────────────────────────────────────────────────
+
+/// Where a list of `total` lines is scrolled to: `visible` of them are
+/// shown, from line `first`. The thumb's length is the visible share of
+/// the track, but at least `MIN_THUMB`; its travel maps linearly onto the
+/// first lines there can be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scrollbar {
+    pub track: Rect,
+    pub visible: usize,
+    pub total: usize,
+    pub first: usize,
+}
+
+impl Scrollbar {
+    /// The thumb, inside `track`.
+    pub fn thumb(&self) -> Rect {
+        let (top, len) = thumb_span(self.track.h, self.visible, self.total, self.first);
+        Rect::new(self.track.x, self.track.y + top, self.track.w, len)
+    }
+
+    /// The first line shown with the thumb's top `y` pixels down the
+    /// track, clamped to the ends.
+    pub fn first_at(&self, y: usize) -> usize {
+        first_at(self.track.h, self.visible, self.total, y)
+    }
+}
+
+impl Widget for Scrollbar {
+    fn rect(&self) -> Rect {
+        self.track
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.track = rect;
+    }
+
+    fn collect_render(&self, theme: &Theme, _focused: bool, out: &mut RenderList) {
+        out.fill_rect(self.track, theme.border);
+        out.fill_rect(self.thumb(), theme.muted);
+    }
+}
+
+/// The thumb's top and length on a track `track` pixels long.
+pub fn thumb_span(track: usize, visible: usize, total: usize, first: usize) -> (usize, usize) {
+    if total <= visible {
+        return (0, track);
+    }
+    let len = (track * visible / total).max(MIN_THUMB).min(track);
+    let last = total - visible;
+    let top = (track - len) * first.min(last);
+    (div_round(top, last), len)
+}
+
+/// The inverse of `thumb_span`: the first line for a thumb top of `y`.
+pub fn first_at(track: usize, visible: usize, total: usize, y: usize) -> usize {
+    if total <= visible {
+        return 0;
+    }
+    let (_, len) = thumb_span(track, visible, total, 0);
+    let travel = track - len;
+    if travel == 0 {
+        return 0;
+    }
+    div_round(y.min(travel) * (total - visible), travel)
+}
+
+fn div_round(n: usize, d: usize) -> usize {
+    (n + d / 2) / d
+}
+
+// ── ProgressBar ───────────────────────────────────────────────────────────────
+
+/// How far through `max` steps something is: an `accent` fill over a
+/// `surface` track, with the percentage on top if `show_percent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgressBar {
+    pub value: u32,
+    pub max: u32,
+    pub show_percent: bool,
+    rect: Rect,
+}
+
+impl ProgressBar {
+    pub fn new(max: u32) -> Self {
+        Self {
+            value: 0,
+            max,
+            show_percent: false,
+            rect: Rect::new(0, 0, 0, 0),
+        }
+    }
+
+    pub fn with_percent(mut self) -> Self {
+        self.show_percent = true;
+        self
+    }
+
+    /// Clamped to `max`.
+    pub fn set_value(&mut self, value: u32) {
+        self.value = value.min(self.max);
+    }
+
+    /// Whole percent, rounded down so that 100 means done.
+    pub fn percent(&self) -> u32 {
+        progress_fill(self.value, self.max, 100) as u32
+    }
+}
+
+impl Widget for ProgressBar {
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+    }
+
+    fn collect_render(&self, theme: &Theme, _focused: bool, out: &mut RenderList) {
+        let r = self.rect;
+        if r.w == 0 || r.h == 0 {
+            return;
+        }
+        let radius = r.h / 2;
+        out.fill_rounded_rect(r, radius, theme.surface);
+        let filled = progress_fill(self.value, self.max, r.w);
+        if filled > 0 {
+            let fill = Rect::new(r.x, r.y, filled, r.h);
+            out.fill_rounded_rect(fill, radius.min(filled / 2), theme.accent);
+        }
+        out.stroke_rect(r, theme.border, 1);
+
+        if self.show_percent && r.h >= CHAR_HEIGHT {
+            let label = alloc::format!("{}%", self.percent());
+            let text_w = label.len() * CHAR_WIDTH;
+            let text_x = r.x + r.w.saturating_sub(text_w) / 2;
+            let text_y = r.y + (r.h - CHAR_HEIGHT) / 2;
+            out.styled_text(&label, text_x, text_y, TextStyle::new(theme.text));
+        }
+    }
+}
+
+/// Pixels of a `width`-pixel track filled at `value` of `max`, rounded
+/// down. A `max` of 0 counts as done.
+pub fn progress_fill(value: u32, max: u32, width: usize) -> usize {
+    if max == 0 {
+        return width;
+    }
+    let value = value.min(max) as u64;
+    (value * width as u64 / max as u64) as usize
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(input: &mut TextInput, ch: char) -> WidgetEvent {
+        press(input, KeyCode::Char(ch))
+    }
+
+    fn press(input: &mut TextInput, code: KeyCode) -> WidgetEvent {
+        input.handle_key(code, Modifiers::NONE)
+    }
+
+    fn ctrl(input: &mut TextInput, ch: char) -> WidgetEvent {
+        input.handle_key(KeyCode::Char(ch), Modifiers::CTRL)
+    }
+
+    fn arrow(input: &mut TextInput, dir: Arrow, shift: bool) -> WidgetEvent {
+        let mods = if shift {
+            Modifiers::SHIFT
+        } else {
+            Modifiers::NONE
+        };
+        input.handle_key(KeyCode::Arrow(dir), mods)
+    }
+
+    fn typed(s: &str) -> TextInput {
+        let mut input = TextInput::new("");
+        for ch in s.chars() {
+            key(&mut input, ch);
+        }
+        input
+    }
+
+    #[test_case]
+    fn test_insert_and_backspace() {
+        let mut input = typed("hello");
+        assert_eq!(input.value(), "hello");
+        assert_eq!(input.cursor, 5);
+
+        assert_eq!(press(&mut input, KeyCode::Backspace), WidgetEvent::Changed);
+        assert_eq!(input.value(), "hell");
+        assert_eq!(input.cursor, 4);
+    }
+
+    #[test_case]
+    fn test_mid_line_editing() {
+        let mut input = typed("helo");
+        arrow(&mut input, Arrow::Left, false);
+        key(&mut input, 'l');
+        assert_eq!(input.value(), "hello");
+        assert_eq!(input.cursor, 4);
+
+        press(&mut input, KeyCode::Home);
+        assert_eq!(input.cursor, 0);
+        assert_eq!(press(&mut input, KeyCode::Delete), WidgetEvent::Changed);
+        assert_eq!(input.value(), "ello");
+
+        press(&mut input, KeyCode::End);
+        assert_eq!(input.cursor, 4);
+        assert_eq!(press(&mut input, KeyCode::Delete), WidgetEvent::Ignored);
+    }
+
+    #[test_case]
+    fn test_ctrl_u_and_submit() {
+        let mut input = typed("abc def");
+        arrow(&mut input, Arrow::Left, false);
+        arrow(&mut input, Arrow::Left, false);
+        arrow(&mut input, Arrow::Left, false);
+        ctrl(&mut input, 'u');
+        assert_eq!(input.value(), "def");
+        assert_eq!(input.cursor, 0);
+        assert_eq!(press(&mut input, KeyCode::Enter), WidgetEvent::Submit);
+        assert_eq!(input.value(), "def");
+    }
+
+    #[test_case]
+    fn test_bounds_and_max_len() {
+        let mut input = TextInput::new("").with_max_len(3);
+        assert_eq!(press(&mut input, KeyCode::Backspace), WidgetEvent::Ignored);
+        assert_eq!(arrow(&mut input, Arrow::Left, false), WidgetEvent::Ignored);
+        for ch in "abcd".chars() {
+            key(&mut input, ch);
+        }
+        assert_eq!(input.value(), "abc");
+        assert_eq!(input.cursor, 3);
+    }
+
+    #[test_case]
+    fn test_shift_selection_replace() {
+        let mut input = typed("hello");
+        arrow(&mut input, Arrow::Left, true);
+        arrow(&mut input, Arrow::Left, true);
+        assert_eq!(input.selection(), Some((3, 5)));
+        key(&mut input, 'p');
+        assert_eq!(input.value(), "help");
+        assert_eq!(input.selection(), None);
+        assert_eq!(input.cursor, 4);
+    }
+
+    #[test_case]
+    fn scrollbar_thumb_tracks_the_view() {
+        // Everything fits: the thumb is the whole track
+        assert_eq!(thumb_span(200, 10, 4, 0), (0, 200));
+        assert_eq!(first_at(200, 10, 4, 50), 0);
+
+        // A tiny history: a long thumb with little travel
+        assert_eq!(thumb_span(200, 10, 12, 0), (0, 166));
+        assert_eq!(thumb_span(200, 10, 12, 2), (34, 166));
+        assert_eq!(first_at(200, 10, 12, 17), 1);
+
+        // A huge history: the thumb stops shrinking at MIN_THUMB
+        let (total, last) = (100_000, 100_000 - 10);
+        assert_eq!(thumb_span(200, 10, total, 0), (0, MIN_THUMB));
+        assert_eq!(thumb_span(200, 10, total, last), (200 - MIN_THUMB, MIN_THUMB));
+        assert_eq!(thumb_span(200, 10, total, usize::MAX), (200 - MIN_THUMB, MIN_THUMB));
+
+        // The ends map exactly, and past them clamps
+        assert_eq!(first_at(200, 10, total, 0), 0);
+        assert_eq!(first_at(200, 10, total, 200 - MIN_THUMB), last);
+        assert_eq!(first_at(200, 10, total, 10_000), last);
+        for first in [0, 1, 500, 49_999, last] {
+            let (top, _) = thumb_span(200, 10, total, first);
+            let back = first_at(200, 10, total, top);
+            assert!(back.abs_diff(first) <= total / (200 - MIN_THUMB), "{}", first);
+        }
+    }
+
+    #[test_case]
+    fn progress_fills_in_proportion() {
+        assert_eq!(progress_fill(0, 10, 200), 0);
+        assert_eq!(progress_fill(5, 10, 200), 100);
+        assert_eq!(progress_fill(99, 100, 50), 49);
+        assert_eq!(progress_fill(20, 10, 200), 200);
+        assert_eq!(progress_fill(0, 0, 200), 200);
+
+        let mut bar = ProgressBar::new(3).with_percent();
+        bar.set_value(2);
+        assert_eq!(bar.percent(), 66);
+        bar.set_value(7);
+        assert_eq!((bar.value, bar.percent()), (3, 100));
+    }
+}
@@ -0,0 +1,262 @@
+//! # Line Diff
+//!
+//! Myers' O((n+m)·d) algorithm over lines, plus a unified-style formatter.
+//! Lines keep their `\n`, so a missing newline at the end of a file shows
+//! up as a changed last line, as it does in `diff -u`.
+//!
+//! The edit trace grows with d², so past `MAX_EDITS` the diff gives up on
+//! being minimal and reports every line of `a` removed and every line of
+//! `b` added. That is still a correct diff, just a large one.
+
+use alloc::{format, string::String, vec::Vec};
+
+/// Inputs longer than this (in lines) are refused by `diff` callers.
+pub const MAX_LINES: usize = 4000;
+const MAX_EDITS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub removed: usize,
+    pub added: usize,
+}
+
+impl Summary {
+    pub fn of(diff: &[DiffLine]) -> Self {
+        let mut summary = Self::default();
+        for line in diff {
+            match line {
+                DiffLine::Removed(_) => summary.removed += 1,
+                DiffLine::Added(_) => summary.added += 1,
+                DiffLine::Same(_) => {}
+            }
+        }
+        summary
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed == 0 && self.added == 0
+    }
+}
+
+pub fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Edit script turning `a` into `b`, in order.
+pub fn diff_lines<'a>(a: &'a str, b: &'a str) -> Vec<DiffLine<'a>> {
+    let (a, b) = (lines(a), lines(b));
+    myers(&a, &b).unwrap_or_else(|| {
+        let removed = a.iter().map(|l| DiffLine::Removed(l));
+        removed
+            .chain(b.iter().map(|l| DiffLine::Added(l)))
+            .collect()
+    })
+}
+
+/// `None` if more than `MAX_EDITS` edits are needed.
+fn myers<'a>(a: &[&'a str], b: &[&'a str]) -> Option<Vec<DiffLine<'a>>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let limit = (a.len() + b.len()).min(MAX_EDITS) as isize;
+    let off = limit + 1;
+    // v[k + off] is the furthest x reached on diagonal k.
+    let mut v = alloc::vec![0isize; 2 * limit as usize + 3];
+    // trace[d] holds v for diagonals -d-1..=d+1 as it was before step d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=limit {
+        trace.push(v[(off - d - 1) as usize..=(off + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + off) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(a, b, &trace));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack<'a>(a: &[&'a str], b: &[&'a str], trace: &[Vec<isize>]) -> Vec<DiffLine<'a>> {
+    let mut out = Vec::new();
+    let (mut x, mut y) = (a.len() as isize, b.len() as isize);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let (prev_x, prev_y) = if d == 0 {
+            (0, 0)
+        } else {
+            let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+                k + 1
+            } else {
+                k - 1
+            };
+            (at(prev_k), at(prev_k) - prev_k)
+        };
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            out.push(DiffLine::Same(a[x as usize]));
+        }
+        if d > 0 {
+            if x == prev_x {
+                out.push(DiffLine::Added(b[prev_y as usize]));
+            } else {
+                out.push(DiffLine::Removed(a[prev_x as usize]));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    out.reverse();
+    out
+}
+
+/// Unified-style hunks with `context` unchanged lines around each change.
+/// With `color`, removed lines are red and added ones green (ANSI).
+pub fn unified(diff: &[DiffLine], context: usize, color: bool) -> String {
+    let changes: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, DiffLine::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut out = String::new();
+    let mut idx = 0;
+    while idx < changes.len() {
+        // Extend the hunk while the next change is within reach of its context.
+        let mut last = idx;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * context + 1 {
+            last += 1;
+        }
+        let start = changes[idx].saturating_sub(context);
+        let end = (changes[last] + context + 1).min(diff.len());
+        write_hunk(&mut out, diff, start, end, color);
+        idx = last + 1;
+    }
+    out
+}
+
+fn write_hunk(out: &mut String, diff: &[DiffLine], start: usize, end: usize, color: bool) {
+    let in_a = |l: &DiffLine| !matches!(l, DiffLine::Added(_));
+    let in_b = |l: &DiffLine| !matches!(l, DiffLine::Removed(_));
+    let a_start = diff[..start].iter().filter(|l| in_a(l)).count();
+    let b_start = diff[..start].iter().filter(|l| in_b(l)).count();
+    let a_len = diff[start..end].iter().filter(|l| in_a(l)).count();
+    let b_len = diff[start..end].iter().filter(|l| in_b(l)).count();
+    // An empty side is numbered from the line before it, as diff does.
+    let first = |from: usize, len: usize| if len == 0 { from } else { from + 1 };
+    out.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        first(a_start, a_len),
+        a_len,
+        first(b_start, b_len),
+        b_len
+    ));
+
+    for line in &diff[start..end] {
+        let (mark, text, sgr) = match line {
+            DiffLine::Same(t) => (' ', *t, ""),
+            DiffLine::Removed(t) => ('-', *t, "\x1b[31m"),
+            DiffLine::Added(t) => ('+', *t, "\x1b[32m"),
+        };
+        if color && !sgr.is_empty() {
+            out.push_str(sgr);
+        }
+        out.push(mark);
+        out.push_str(text.strip_suffix('\n').unwrap_or(text));
+        if color && !sgr.is_empty() {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+        if !text.ends_with('\n') {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    }
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffLine::*;
+
+    #[test_case]
+    fn identical_files_have_no_hunks() {
+        let text = "a\nb\nc\n";
+        let diff = diff_lines(text, text);
+        assert_eq!(diff, [Same("a\n"), Same("b\n"), Same("c\n")]);
+        assert!(Summary::of(&diff).is_empty());
+        assert_eq!(unified(&diff, 3, false), "");
+        assert!(diff_lines("", "").is_empty());
+    }
+
+    #[test_case]
+    fn completely_different_files() {
+        let diff = diff_lines("a\nb\n", "x\ny\nz\n");
+        assert_eq!(
+            Summary::of(&diff),
+            Summary {
+                removed: 2,
+                added: 3
+            }
+        );
+        assert_eq!(
+            unified(&diff, 3, false),
+            "@@ -1,2 +1,3 @@\n-a\n-b\n+x\n+y\n+z\n"
+        );
+        let diff = diff_lines("", "new\n");
+        assert_eq!(unified(&diff, 3, false), "@@ -0,0 +1,1 @@\n+new\n");
+    }
+
+    #[test_case]
+    fn one_line_change_keeps_context() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let b = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let diff = diff_lines(a, b);
+        assert_eq!(
+            Summary::of(&diff),
+            Summary {
+                removed: 1,
+                added: 1
+            }
+        );
+        assert_eq!(
+            unified(&diff, 1, false),
+            "@@ -4,3 +4,3 @@\n 4\n-5\n+five\n 6\n"
+        );
+        assert_eq!(
+            unified(&diff, 1, true),
+            "@@ -4,3 +4,3 @@\n 4\n\x1b[31m-5\x1b[0m\n\x1b[32m+five\x1b[0m\n 6\n"
+        );
+    }
+
+    #[test_case]
+    fn trailing_newline_differences() {
+        let diff = diff_lines("a\nb\n", "a\nb");
+        assert_eq!(diff, [Same("a\n"), Removed("b\n"), Added("b")]);
+        assert_eq!(
+            unified(&diff, 3, false),
+            "@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file\n"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! # Utilities
+//!
+//! Helpers shared by commands and tests. Apart from `rand`, which keeps
+//! the kernel RNG, they are pure.
+
+pub mod diff;
+pub mod rand;
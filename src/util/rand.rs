@@ -0,0 +1,191 @@
+//! # Kernel RNG
+//!
+//! One xorshift64* generator for the whole kernel. It is seeded at boot
+//! from the TSC, the RTC and, when the CPU has it, RDRAND, and the timer
+//! interrupt feeds it the low TSC bits of every tick, whose jitter is
+//! folded into the state on the next draw. Good enough to keep programs
+//! from seeing the same bytes twice; not a cryptographic generator.
+//!
+//! `seed` switches the jitter off, so that after an explicit seed the
+//! sequence is fixed: fuzz tests and benchmarks that draw from here can
+//! be rerun exactly with `seed <value>`. The next boot seed turns it back
+//! on.
+
+use crate::devices::drivers::rtc;
+use crate::stats::latency::rdtsc;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::random::RdRand;
+
+/// Any nonzero value; xorshift never leaves zero once there.
+const FALLBACK_STATE: u64 = 0x9E37_79B9_7F4A_7C15;
+/// Intel's guidance for RDRAND underflow: retry ten times, then give up.
+const RDRAND_RETRIES: usize = 10;
+
+static RNG: Mutex<Xorshift64Star> = Mutex::new(Xorshift64Star {
+    state: FALLBACK_STATE,
+});
+/// Timer jitter gathered since the last draw.
+static JITTER: AtomicU64 = AtomicU64::new(0);
+/// Set by `seed`: draws ignore the jitter.
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// splitmix64's finalizer, to spread a few changing bits over the word.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The bare generator, also usable on its own for a private sequence.
+pub struct Xorshift64Star {
+    state: u64,
+}
+
+impl Xorshift64Star {
+    pub fn new(seed: u64) -> Self {
+        let state = mix(seed);
+        Self {
+            state: if state == 0 { FALLBACK_STATE } else { state },
+        }
+    }
+
+    /// Folds gathered jitter into the state.
+    fn absorb(&mut self, jitter: u64) {
+        self.state ^= mix(jitter);
+        if self.state == 0 {
+            self.state = FALLBACK_STATE;
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `lo..hi`; `lo` if the range is empty. Multiply-shift
+    /// with rejection, so there is no modulo bias.
+    pub fn next_range(&mut self, lo: u64, hi: u64) -> u64 {
+        if hi <= lo {
+            return lo;
+        }
+        let span = hi - lo;
+        let threshold = span.wrapping_neg() % span;
+        loop {
+            let wide = self.next_u64() as u128 * span as u128;
+            if wide as u64 >= threshold {
+                return lo + (wide >> 64) as u64;
+            }
+        }
+    }
+
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// Reseeds with a fixed value and stops mixing in timer jitter.
+pub fn seed(value: u64) {
+    *RNG.lock() = Xorshift64Star::new(value);
+    DETERMINISTIC.store(true, Ordering::Relaxed);
+}
+
+/// One RDRAND value, or `None` if the CPU lacks it or keeps underflowing.
+pub fn rdrand() -> Option<u64> {
+    let rdrand = RdRand::new()?;
+    (0..RDRAND_RETRIES).find_map(|_| rdrand.get_u64())
+}
+
+/// Seeds from the TSC, the time of day and RDRAND if present. Needs
+/// interrupts up, since the RTC read may wait out an update cycle.
+pub fn seed_at_boot() {
+    let time = rtc::read_time();
+    let clock = (time.hour as u64) << 16 | (time.minute as u64) << 8 | time.second as u64;
+    let hw = rdrand().unwrap_or(0);
+    *RNG.lock() = Xorshift64Star::new(rdtsc() ^ clock.rotate_left(40) ^ hw);
+    DETERMINISTIC.store(false, Ordering::Relaxed);
+}
+
+/// Called from the timer interrupt; takes no lock.
+pub fn add_jitter(sample: u64) {
+    JITTER.fetch_xor(sample.rotate_left((sample & 63) as u32), Ordering::Relaxed);
+}
+
+/// Runs `f` on the kernel generator with the pending jitter folded in.
+fn with_rng<R>(f: impl FnOnce(&mut Xorshift64Star) -> R) -> R {
+    let mut rng = RNG.lock();
+    let jitter = JITTER.swap(0, Ordering::Relaxed);
+    if !DETERMINISTIC.load(Ordering::Relaxed) {
+        rng.absorb(jitter);
+    }
+    f(&mut rng)
+}
+
+pub fn next_u64() -> u64 {
+    with_rng(|rng| rng.next_u64())
+}
+
+/// Uniform in `lo..hi`; `lo` if the range is empty.
+pub fn next_range(lo: u64, hi: u64) -> u64 {
+    with_rng(|rng| rng.next_range(lo, hi))
+}
+
+pub fn fill_bytes(buf: &mut [u8]) {
+    with_rng(|rng| rng.fill_bytes(buf))
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn fixed_seed_gives_fixed_sequence() {
+        let mut rng = Xorshift64Star::new(42);
+        assert_eq!(rng.next_u64(), 0xd75f_b1e8_6299_b002);
+        assert_eq!(rng.next_u64(), 0x8213_f6ca_8439_876e);
+        assert_eq!(rng.next_u64(), 0x15bc_4499_6652_71a8);
+    }
+
+    #[test_case]
+    fn next_range_is_roughly_uniform() {
+        const BUCKETS: usize = 10;
+        const SAMPLES: u64 = 10_000;
+        let mut rng = Xorshift64Star::new(7);
+        let mut counts = [0u64; BUCKETS];
+        for _ in 0..SAMPLES {
+            let v = rng.next_range(100, 100 + BUCKETS as u64);
+            assert!((100..110).contains(&v));
+            counts[(v - 100) as usize] += 1;
+        }
+        // chi-square with 9 degrees of freedom; 27.9 is the 0.1% tail
+        let expected = SAMPLES / BUCKETS as u64;
+        let chi2: u64 = counts
+            .iter()
+            .map(|&c| c.abs_diff(expected).pow(2) * 10 / expected)
+            .sum();
+        assert!(chi2 < 279, "chi2 x10 = {}", chi2);
+        assert_eq!(rng.next_range(5, 5), 5);
+    }
+
+    #[test_case]
+    fn fill_bytes_fills_exactly() {
+        let mut rng = Xorshift64Star::new(1);
+        let mut buf = [0u8; 21];
+        rng.fill_bytes(&mut buf[..13]);
+        assert!(buf[..13].iter().any(|&b| b != 0));
+        assert!(buf[13..].iter().all(|&b| b == 0));
+
+        // the same seed gives the same bytes, 8 at a time
+        let mut again = Xorshift64Star::new(1);
+        assert_eq!(buf[..8], again.next_u64().to_le_bytes());
+    }
+}
@@ -3,6 +3,7 @@
 //! Execution engine for the kernel bytecode VM.
 
 use super::bytecode::{Instruction, Program};
+use crate::devices::framebuffer::framebuffer::with_fb;
 
 macro_rules! vm_trace {
     ($($t:tt)*) => {{
@@ -317,11 +318,7 @@ impl Vm {
                     let x = self.pop()?;
                     if x >= 0 && y >= 0 {
                         let c = color_from_packed(color);
-                        let mut guard =
-                            crate::devices::framebuffer::framebuffer::FRAMEBUFFER.lock();
-                        if let Some(fb) = guard.as_mut() {
-                            fb.put_pixel(x as usize, y as usize, c);
-                        }
+                        let _ = with_fb(|fb| fb.put_pixel(x as usize, y as usize, c));
                     }
                 }
                 Instruction::FillRect => {
@@ -330,28 +327,20 @@ impl Vm {
                     let w = self.pop()?;
                     let y = self.pop()?;
                     let x = self.pop()?;
-                    if x >= 0 && y >= 0 && w > 0 && h > 0 {
+                    if w > 0 && h > 0 {
                         let c = color_from_packed(color);
-                        let mut guard =
-                            crate::devices::framebuffer::framebuffer::FRAMEBUFFER.lock();
-                        if let Some(fb) = guard.as_mut() {
-                            fb.fill_rect(x as usize, y as usize, w as usize, h as usize, c);
-                        }
+                        let _ = with_fb(|fb| {
+                            fb.fill_rect_signed(x, y, w as u64, h as u64, c)
+                        });
                     }
                 }
                 Instruction::ClearScr => {
                     let color = self.pop()?;
                     let c = color_from_packed(color);
-                    let mut guard = crate::devices::framebuffer::framebuffer::FRAMEBUFFER.lock();
-                    if let Some(fb) = guard.as_mut() {
-                        fb.clear(c);
-                    }
+                    let _ = with_fb(|fb| fb.clear(c));
                 }
                 Instruction::Present => {
-                    let mut guard = crate::devices::framebuffer::framebuffer::FRAMEBUFFER.lock();
-                    if let Some(fb) = guard.as_mut() {
-                        fb.render_frame();
-                    }
+                    let _ = with_fb(|fb| fb.render_frame());
                 }
             }
 
@@ -182,7 +182,7 @@ pub fn free_vm_page(addr: usize) -> Result<(), VmError> {
 mod tests {
     use super::*;
 
-    #[test]
+    #[test_case]
     fn test_vm_process_creation() {
         let proc = VmProcess::create();
         assert!(proc.is_ok());
@@ -193,7 +193,7 @@ mod tests {
         // Drop here calls sys_munmap automatically
     }
 
-    #[test]
+    #[test_case]
     fn test_vm_arena_size_fits_vm() {
         assert!(
             VM_ARENA_SIZE >= core::mem::size_of::<Vm>(),